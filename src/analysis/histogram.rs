@@ -0,0 +1,101 @@
+//! 固定桶直方图
+
+use super::rank_index;
+
+/// 按上界分桶的直方图：第 `i` 个桶统计 `(bounds[i-1], bounds[i]]` 内的样本，
+/// 大于最后一个上界的样本落入溢出桶。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: Vec<u64>,
+    /// 长度为 `bounds.len() + 1`，最后一个是溢出桶
+    counts: Vec<u64>,
+    total: u64,
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+impl Histogram {
+    /// 用严格递增的桶上界创建直方图。
+    pub fn new(bounds: Vec<u64>) -> Self {
+        assert!(
+            bounds.windows(2).all(|w| w[0] < w[1]),
+            "histogram bounds must be strictly increasing: {bounds:?}"
+        );
+        let counts = vec![0; bounds.len() + 1];
+        Self {
+            bounds,
+            counts,
+            total: 0,
+            min: None,
+            max: None,
+        }
+    }
+
+    /// `count` 个等宽桶：上界为 `width, 2*width, ...`。
+    pub fn linear(width: u64, count: usize) -> Self {
+        assert!(width > 0, "histogram bucket width must be > 0");
+        Self::new((1..=count as u64).map(|i| i * width).collect())
+    }
+
+    /// `count` 个指数增长的桶：上界为 `start, start*factor, ...`。
+    pub fn exponential(start: u64, factor: u64, count: usize) -> Self {
+        assert!(
+            start > 0 && factor > 1,
+            "exponential buckets need start > 0 and factor > 1"
+        );
+        let mut bounds = Vec::with_capacity(count);
+        let mut b = start;
+        for _ in 0..count {
+            bounds.push(b);
+            b = b.saturating_mul(factor);
+            if bounds.last() == Some(&b) {
+                break;
+            }
+        }
+        Self::new(bounds)
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let idx = self.bounds.partition_point(|&b| b < value);
+        self.counts[idx] += 1;
+        self.total += 1;
+        self.min = Some(self.min.map_or(value, |m| m.min(value)));
+        self.max = Some(self.max.map_or(value, |m| m.max(value)));
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    pub fn min(&self) -> Option<u64> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<u64> {
+        self.max
+    }
+
+    /// 每个桶的 `(上界, 样本数)`；溢出桶的上界为 `None`。
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .map(|&b| Some(b))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+
+    /// 分位数估计：返回包含该秩样本的桶上界（不超过观测到的最大值）；
+    /// 落在溢出桶时返回最大值。空直方图返回 `None`。
+    pub fn percentile(&self, p: f64) -> Option<u64> {
+        let max = self.max?;
+        let rank = rank_index(self.total as usize, p) as u64;
+        let mut seen = 0u64;
+        for (upper, count) in self.buckets() {
+            seen += count;
+            if seen > rank {
+                return Some(upper.map_or(max, |u| u.min(max)));
+            }
+        }
+        Some(max)
+    }
+}
@@ -0,0 +1,27 @@
+//! 结果分析工具
+//!
+//! 各个二进制共用的统计工具：分位数与直方图。
+
+mod histogram;
+
+pub use histogram::Histogram;
+
+/// 最近秩（nearest-rank）分位数：`p` 取值 [0, 1]，超出范围时截断；空输入返回 `None`。
+///
+/// 与各个二进制原先的 `percentile_ns` 行为一致：取排序后第 `ceil(p * n)` 个值
+/// （`p <= 0` 时为最小值）。
+pub fn percentile(values: &[u64], p: f64) -> Option<u64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted.get(rank_index(sorted.len(), p)).copied()
+}
+
+/// `n` 个有序样本中分位数 `p` 对应的下标（`n > 0`）。
+fn rank_index(n: usize, p: f64) -> usize {
+    let p = p.clamp(0.0, 1.0);
+    let idx = (p * n as f64).ceil() as usize;
+    idx.saturating_sub(1).min(n.saturating_sub(1))
+}
@@ -0,0 +1,163 @@
+//! 背景流量仿真
+//!
+//! 在 fat-tree 上用 `experiments::traffic_matrix` 按均匀需求矩阵生成 Poisson 到达的
+//! 背景流，流大小取自 web search / data mining 经验分布，输出提供负载、FCT 与丢包数。
+
+use clap::Parser;
+use htsim_rs::cc::ring::RoutingMode;
+use htsim_rs::experiments::{FlowSizeDist, TrafficMatrix, TrafficMatrixOpts, traffic_matrix};
+use htsim_rs::net::NetWorld;
+use htsim_rs::proto::dctcp::DctcpConfig;
+use htsim_rs::proto::tcp::TcpConfig;
+use htsim_rs::sim::{SimTime, Simulator, TransportProtocol};
+use htsim_rs::topo::fat_tree::{FatTreeOpts, build_fat_tree};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "background-traffic-sim",
+    about = "背景流量：fat-tree 上按目标负载生成 Poisson 到达的随机流，统计 FCT 与丢包"
+)]
+struct Args {
+    /// 目标负载（每个 host 平均发送速率占 host 链路带宽的比例）
+    #[arg(long, default_value_t = 0.6)]
+    load: f64,
+
+    /// 流大小分布：websearch 或 datamining
+    #[arg(long, default_value = "websearch")]
+    cdf: String,
+
+    /// 流到达窗口（毫秒）
+    #[arg(long, default_value_t = 10)]
+    duration_ms: u64,
+
+    /// 仿真运行到多少毫秒；默认运行到所有流结束（data mining 的大流可能很慢）
+    #[arg(long)]
+    until_ms: Option<u64>,
+
+    /// 到达过程的随机种子
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// fat-tree 参数 k
+    #[arg(long, default_value_t = 4)]
+    k: usize,
+
+    /// 链路带宽（Gbps）
+    #[arg(long, default_value_t = 10)]
+    link_gbps: u64,
+
+    /// 单向链路传播时延（微秒）
+    #[arg(long, default_value_t = 2)]
+    link_latency_us: u64,
+
+    /// 传输协议
+    #[arg(long, value_enum, default_value_t = TransportProtocol::Tcp)]
+    protocol: TransportProtocol,
+
+    /// 路由模式
+    #[arg(long, value_enum, default_value_t = RoutingMode::PerFlow)]
+    routing: RoutingMode,
+
+    /// 交换机出方向队列大小（单位：MSS 个数）
+    #[arg(long, default_value_t = 100)]
+    queue_pkts: u64,
+
+    /// DCTCP 的 ECN 标记阈值 K（单位：MSS 个数，仅 dctcp 生效）
+    #[arg(long, default_value_t = 20)]
+    ecn_k_pkts: u64,
+
+    /// 初始与最小 RTO（毫秒）
+    #[arg(long, default_value_t = 1)]
+    min_rto_ms: u64,
+}
+
+const MSS: u32 = 1460;
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .with_file(true)
+        .with_line_number(true)
+        .with_target(true)
+        .init();
+
+    let args = Args::parse();
+    let sizes = FlowSizeDist::parse(&args.cdf).unwrap_or_else(|err| panic!("--cdf: {err}"));
+
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let topo = build_fat_tree(
+        &mut world,
+        &FatTreeOpts {
+            k: args.k,
+            link_gbps: args.link_gbps,
+            link_latency: SimTime::from_micros(args.link_latency_us),
+            ..FatTreeOpts::default()
+        },
+    );
+    let protocol = args.protocol;
+    world.net.set_max_packet_bytes(MSS as u64);
+    world
+        .net
+        .set_switch_egress_queue_capacity_bytes(args.queue_pkts.saturating_mul(MSS as u64));
+    if protocol == TransportProtocol::Dctcp {
+        world
+            .net
+            .set_all_link_ecn_threshold_bytes(args.ecn_k_pkts.saturating_mul(MSS as u64));
+    }
+    world
+        .net
+        .validate_queue_capacities()
+        .unwrap_or_else(|err| panic!("{err}"));
+
+    let min_rto = SimTime::from_millis(args.min_rto_ms);
+    let matrix = TrafficMatrix::uniform(topo.hosts.clone());
+    let opts = TrafficMatrixOpts {
+        load: args.load,
+        host_link_bps: args.link_gbps.saturating_mul(1_000_000_000),
+        sizes,
+        duration: SimTime::from_millis(args.duration_ms),
+        seed: args.seed,
+        protocol,
+        routing: args.routing,
+        tcp_cfg: TcpConfig {
+            mss: MSS,
+            init_rto: min_rto,
+            min_rto,
+            handshake: false,
+            ..TcpConfig::default()
+        },
+        dctcp_cfg: DctcpConfig {
+            mss: MSS,
+            init_rto: min_rto,
+            min_rto,
+            ..DctcpConfig::default()
+        },
+        ..TrafficMatrixOpts::default()
+    };
+    let run = traffic_matrix(&mut sim, &mut world, &matrix, &opts);
+    match args.until_ms {
+        Some(until_ms) => sim.run_until(SimTime::from_millis(until_ms), &mut world),
+        None => sim.run(&mut world),
+    }
+
+    let r = run.report(&world);
+    let ms = |t: Option<SimTime>| {
+        t.map_or_else(|| "-".to_string(), |t| format!("{:.3}", t.0 as f64 / 1e6))
+    };
+    println!(
+        "load={} cdf={} hosts={} flows={} flows_done={} offered_load={:.3} p50_fct_ms={} p99_fct_ms={} dropped_pkts={}",
+        args.load,
+        args.cdf,
+        matrix.hosts().len(),
+        r.flows,
+        r.flows_done,
+        r.offered_load,
+        ms(r.p50_fct),
+        ms(r.p99_fct),
+        r.dropped_pkts
+    );
+}
@@ -66,6 +66,9 @@ struct Args {
     /// 仿真运行到多少毫秒
     #[arg(long, default_value_t = 50)]
     until_ms: u64,
+    /// 结束时打印仿真统计（已执行事件数、墙钟耗时、事件吞吐）
+    #[arg(long)]
+    sim_stats: bool,
 }
 
 fn main() {
@@ -93,6 +96,7 @@ fn main() {
         bottleneck_gbps: args.bottleneck_gbps,
         link_latency: SimTime::from_micros(args.link_latency_us),
         until: SimTime::from_millis(args.until_ms),
+        ..DumbbellOpts::default()
     };
 
     let (src, _dst, route) = build_dumbbell(&mut world, &opts);
@@ -118,4 +122,8 @@ fn main() {
         world.net.stats.delivered_pkts,
         world.net.stats.delivered_bytes
     );
+
+    if args.sim_stats {
+        println!("sim_stats {}", sim.metrics());
+    }
 }
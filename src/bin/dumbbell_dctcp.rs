@@ -4,7 +4,7 @@
 
 use clap::Parser;
 use htsim_rs::net::NetWorld;
-use htsim_rs::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use htsim_rs::proto::dctcp::{DctcpConfig, DctcpConn, DctcpEcnEchoMode, DctcpStart};
 use htsim_rs::sim::{SimTime, Simulator};
 use htsim_rs::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use std::fs;
@@ -32,7 +32,7 @@ struct Args {
     #[arg(long, default_value_t = 1_000)]
     init_ssthresh_pkts: u64,
 
-    /// 初始 RTO（微秒）
+    /// 初始与最小 RTO（微秒）
     #[arg(long, default_value_t = 200)]
     rto_us: u64,
 
@@ -44,6 +44,14 @@ struct Args {
     #[arg(long, default_value_t = 0.0625)]
     dctcp_g: f64,
 
+    /// 接收端 CE 回显方式：per_ack（每段一个 ACK）或 classic（RFC 8257 状态机 + 延迟 ACK）
+    #[arg(long, default_value = "per_ack")]
+    ecn_echo: String,
+
+    /// 启用 pacing（按 cwnd/srtt 逐段发送）
+    #[arg(long, default_value_t = false)]
+    pacing: bool,
+
     #[arg(long, default_value_t = 100)]
     host_link_gbps: u64,
 
@@ -77,6 +85,10 @@ struct Args {
     /// 不打印日志或统计信息（仅输出到文件）
     #[arg(long)]
     quiet: bool,
+
+    /// 结束时打印仿真统计（已执行事件数、墙钟耗时、事件吞吐）
+    #[arg(long)]
+    sim_stats: bool,
 }
 
 fn main() {
@@ -105,10 +117,12 @@ fn main() {
         bottleneck_gbps: args.bottleneck_gbps,
         link_latency: SimTime::from_micros(args.link_latency_us),
         until: SimTime::from_millis(args.until_ms),
+        ..DumbbellOpts::default()
     };
 
     let (src, dst, route) = build_dumbbell(&mut world, &opts);
 
+    world.net.set_max_packet_bytes(args.mss as u64);
     if args.queue_pkts > 0 {
         let cap_bytes = args.queue_pkts.saturating_mul(args.mss as u64);
         if route.len() >= 3 {
@@ -118,6 +132,10 @@ fn main() {
             world.net.set_link_queue_capacity_bytes(s1, s0, cap_bytes);
         }
     }
+    if let Err(err) = world.net.validate_queue_capacities() {
+        eprintln!("{err}");
+        return;
+    }
 
     if args.ecn_k_pkts > 0 {
         let k_bytes = args.ecn_k_pkts.saturating_mul(args.mss as u64);
@@ -140,8 +158,16 @@ fn main() {
         init_cwnd_bytes: args.init_cwnd_pkts.saturating_mul(args.mss as u64),
         init_ssthresh_bytes: args.init_ssthresh_pkts.saturating_mul(args.mss as u64),
         init_rto: SimTime::from_micros(args.rto_us),
+        min_rto: SimTime::from_micros(args.rto_us),
         max_rto: SimTime::from_millis(args.max_rto_ms),
         g: args.dctcp_g,
+        done_notify_delay: SimTime::ZERO,
+        data_priority: 0,
+        ack_priority: 0,
+        ecn_echo_mode: DctcpEcnEchoMode::parse(&args.ecn_echo)
+            .unwrap_or_else(|err| panic!("{err}")),
+        delayed_ack_timeout: SimTime::from_micros(40),
+        pacing: args.pacing,
     };
 
     let conn_id = 1;
@@ -214,4 +240,8 @@ fn main() {
             world.net.stats.dropped_bytes
         );
     }
+
+    if args.sim_stats {
+        println!("sim_stats {}", sim.metrics());
+    }
 }
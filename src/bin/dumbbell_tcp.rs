@@ -4,7 +4,7 @@
 
 use clap::Parser;
 use htsim_rs::net::NetWorld;
-use htsim_rs::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use htsim_rs::proto::tcp::{CcAlgo, TcpConfig, TcpConn, TcpStart};
 use htsim_rs::sim::{SimTime, Simulator};
 use htsim_rs::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use std::fs;
@@ -48,10 +48,18 @@ struct Args {
     #[arg(long, default_value_t = false)]
     handshake: bool,
 
+    /// 启用 pacing（按 cwnd/srtt 逐段发送）
+    #[arg(long, default_value_t = false)]
+    pacing: bool,
+
     /// 应用层限速（包/秒）
     #[arg(long)]
     app_limited_pps: Option<u64>,
 
+    /// 拥塞控制算法：reno / vegas / bbr
+    #[arg(long, default_value = "reno")]
+    cc: String,
+
     #[arg(long, default_value_t = 100)]
     host_link_gbps: u64,
 
@@ -62,6 +70,18 @@ struct Args {
     #[arg(long, default_value_t = 2)]
     link_latency_us: u64,
 
+    /// 瓶颈反向（ACK 方向）带宽（Gbps）；不填则与正向相同
+    #[arg(long)]
+    reverse_bottleneck_gbps: Option<u64>,
+
+    /// 瓶颈正向传播时延（微秒）；不填则为 `--link-latency-us`
+    #[arg(long)]
+    bottleneck_latency_us: Option<u64>,
+
+    /// 瓶颈反向（ACK 方向）传播时延（微秒）；不填则与正向相同
+    #[arg(long)]
+    reverse_bottleneck_latency_us: Option<u64>,
+
     /// 仿真运行到多少毫秒
     #[arg(long, default_value_t = 200)]
     until_ms: u64,
@@ -73,6 +93,10 @@ struct Args {
     /// 输出可视化 JSON 事件文件（供 `viz/index.html` 加载）；不填则不生成
     #[arg(long)]
     viz_json: Option<PathBuf>,
+
+    /// 结束时打印仿真统计（已执行事件数、墙钟耗时、事件吞吐）
+    #[arg(long)]
+    sim_stats: bool,
 }
 
 fn main() {
@@ -99,11 +123,16 @@ fn main() {
         bottleneck_gbps: args.bottleneck_gbps,
         link_latency: SimTime::from_micros(args.link_latency_us),
         until: SimTime::from_millis(args.until_ms),
+        bottleneck_gbps_reverse: args.reverse_bottleneck_gbps,
+        bottleneck_latency: args.bottleneck_latency_us.map(SimTime::from_micros),
+        bottleneck_latency_reverse: args.reverse_bottleneck_latency_us.map(SimTime::from_micros),
+        ..DumbbellOpts::default()
     };
     let (src, dst, route) = build_dumbbell(&mut world, &opts);
 
     // 按 C++ 的 -qs 逻辑：把瓶颈链路（s0->s1 及 s1->s0）队列设为有限缓冲
     // dumbbell 路径固定为 [h0, s0, s1, h1]
+    world.net.set_max_packet_bytes(args.mss as u64);
     if args.queue_pkts > 0 {
         let cap_bytes = args.queue_pkts.saturating_mul(args.mss as u64);
         if route.len() >= 3 {
@@ -113,6 +142,10 @@ fn main() {
             world.net.set_link_queue_capacity_bytes(s1, s0, cap_bytes);
         }
     }
+    if let Err(err) = world.net.validate_queue_capacities() {
+        eprintln!("{err}");
+        return;
+    }
 
     // 启用可视化：在拓扑与队列容量设置完成后，发出 meta（含带宽/时延/队列容量）
     if args.viz_json.is_some() {
@@ -130,6 +163,14 @@ fn main() {
         max_rto: SimTime::from_millis(args.max_rto_ms),
         handshake: args.handshake,
         app_limited_pps: args.app_limited_pps,
+        done_notify_delay: SimTime::ZERO,
+        cc: CcAlgo::parse(&args.cc).unwrap_or_else(|err| panic!("{err}")),
+        enable_tlp: false,
+        delayed_ack: None,
+        data_priority: 0,
+        ack_priority: 0,
+        pacing: args.pacing,
+        subflows: 1,
     };
 
     let conn_id = 1;
@@ -177,4 +218,8 @@ fn main() {
         world.net.stats.dropped_pkts,
         world.net.stats.dropped_bytes
     );
+
+    if args.sim_stats {
+        println!("sim_stats {}", sim.metrics());
+    }
 }
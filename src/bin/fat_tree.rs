@@ -55,6 +55,7 @@ fn main() {
         k: 4,
         link_gbps: 100,
         link_latency: SimTime::from_micros(2),
+        oversubscription: 1,
     };
     let topo = build_fat_tree(&mut world, &topo_opts);
 
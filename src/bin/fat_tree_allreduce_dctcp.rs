@@ -2,8 +2,8 @@
 
 use clap::{Parser, ValueEnum};
 use htsim_rs::cc::ring::{self, RingAllreduceConfig, RingTransport, RoutingMode as CcRoutingMode};
-use htsim_rs::net::{EcmpHashMode, NetWorld, NodeId};
-use htsim_rs::proto::dctcp::DctcpConfig;
+use htsim_rs::net::{EcmpHashMode, FlowTags, NetWorld, NodeId};
+use htsim_rs::proto::dctcp::{DctcpConfig, DctcpEcnEchoMode};
 use htsim_rs::sim::{SimTime, Simulator};
 use htsim_rs::topo::fat_tree::{FatTreeOpts, build_fat_tree};
 use std::fs;
@@ -33,6 +33,10 @@ struct Args {
     #[arg(long, default_value_t = 1460)]
     mss: u32,
 
+    /// Link MTU in bytes (e.g. 9000 for jumbo frames); mss + 40 must fit
+    #[arg(long, default_value_t = 1500)]
+    mtu: u64,
+
     /// Initial cwnd in MSS packets
     #[arg(long, default_value_t = 10)]
     init_cwnd_pkts: u64,
@@ -41,7 +45,7 @@ struct Args {
     #[arg(long, default_value_t = 1_000)]
     init_ssthresh_pkts: u64,
 
-    /// Initial RTO (microseconds)
+    /// Initial and minimum RTO (microseconds)
     #[arg(long, default_value_t = 200)]
     rto_us: u64,
 
@@ -60,14 +64,28 @@ struct Args {
     #[arg(long, default_value_t = 2)]
     link_latency_us: u64,
 
-    /// Queue capacity per link in MSS packets; 0 keeps default
+    /// Queue capacity per link in MTU-sized packets; 0 keeps default
     #[arg(long, default_value_t = 0)]
     queue_pkts: u64,
 
-    /// ECN marking threshold per link in MSS packets; 0 disables ECN
+    /// Queue capacity per link as a multiple of its bandwidth-delay product,
+    /// taking the cross-pod base RTT (12 link latencies) as the delay
+    #[arg(long, conflicts_with = "queue_pkts")]
+    queue_bdp: Option<f64>,
+
+    /// ECN marking threshold per link in MTU-sized packets; 0 disables ECN
     #[arg(long, default_value_t = 0)]
     ecn_k_pkts: u64,
 
+    /// Mark ECN with RED, ramping from --ecn-k-pkts up to this many MTU-sized
+    /// packets, instead of a step at --ecn-k-pkts
+    #[arg(long)]
+    ecn_red_max_pkts: Option<u64>,
+
+    /// RED marking probability just below --ecn-red-max-pkts
+    #[arg(long, default_value_t = 1.0)]
+    ecn_red_max_p: f64,
+
     /// Output visualization JSON (for viz/index.html)
     #[arg(long)]
     viz_json: Option<PathBuf>,
@@ -91,6 +109,14 @@ struct Args {
     /// ECMP routing mode
     #[arg(long, value_enum, default_value_t = RoutingMode::PerPacket)]
     routing: RoutingMode,
+
+    /// ECMP hash seed (defaults to the built-in fixed seed)
+    #[arg(long)]
+    ecmp_seed: Option<u64>,
+
+    /// Print simulator stats (events executed, wall-clock time, events/sec) at the end
+    #[arg(long)]
+    sim_stats: bool,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -171,6 +197,7 @@ fn main() {
         k: args.k,
         link_gbps: args.link_gbps,
         link_latency: SimTime::from_micros(args.link_latency_us),
+        oversubscription: 1,
     };
     let topo = build_fat_tree(&mut world, &topo_opts);
 
@@ -204,19 +231,47 @@ fn main() {
         }
     }
 
+    world.net.set_mtu(args.mtu);
+    if let Err(err) = world.net.validate_mss(args.mss) {
+        eprintln!("{err}");
+        return;
+    }
+    world.net.set_max_packet_bytes(args.mss as u64);
     if args.queue_pkts > 0 {
-        let cap_bytes = args.queue_pkts.saturating_mul(args.mss as u64);
+        let cap_bytes = world.net.mem_from_pkt(args.queue_pkts);
         world.net.set_all_link_queue_capacity_bytes(cap_bytes);
     }
+    if let Some(multiple) = args.queue_bdp {
+        // A cross-pod path crosses 6 links each way.
+        let base_rtt = SimTime::from_micros(args.link_latency_us.saturating_mul(12));
+        world
+            .net
+            .set_all_link_queue_capacity_bdp(multiple, base_rtt);
+    }
+    if let Err(err) = world.net.validate_queue_capacities() {
+        eprintln!("{err}");
+        return;
+    }
     if args.ecn_k_pkts > 0 {
-        let th_bytes = args.ecn_k_pkts.saturating_mul(args.mss as u64);
-        world.net.set_all_link_ecn_threshold_bytes(th_bytes);
+        let th_bytes = world.net.mem_from_pkt(args.ecn_k_pkts);
+        match args.ecn_red_max_pkts {
+            Some(max_pkts) => {
+                let max_bytes = world.net.mem_from_pkt(max_pkts);
+                world
+                    .net
+                    .set_all_link_red_ecn(th_bytes, max_bytes, args.ecn_red_max_p);
+            }
+            None => world.net.set_all_link_ecn_threshold_bytes(th_bytes),
+        }
     }
 
     world.net.set_ecmp_hash_mode(match args.routing {
         RoutingMode::PerFlow => EcmpHashMode::Flow,
         RoutingMode::PerPacket => EcmpHashMode::Packet,
     });
+    if let Some(seed) = args.ecmp_seed {
+        world.net.set_ecmp_seed(seed);
+    }
 
     if args.viz_json.is_some() {
         world.net.viz = Some(htsim_rs::viz::VizLogger::default());
@@ -229,8 +284,15 @@ fn main() {
         init_cwnd_bytes: args.init_cwnd_pkts.saturating_mul(args.mss as u64),
         init_ssthresh_bytes: args.init_ssthresh_pkts.saturating_mul(args.mss as u64),
         init_rto: SimTime::from_micros(args.rto_us),
+        min_rto: SimTime::from_micros(args.rto_us),
         max_rto: SimTime::from_millis(args.max_rto_ms),
         g: args.dctcp_g,
+        done_notify_delay: SimTime::ZERO,
+        data_priority: 0,
+        ack_priority: 0,
+        ecn_echo_mode: DctcpEcnEchoMode::PerAck,
+        delayed_ack_timeout: SimTime::from_micros(40),
+        pacing: false,
     };
 
     let probe_flow_id = args.cwnd_csv.as_ref().map(|_| {
@@ -257,6 +319,10 @@ fn main() {
             start_flow_id: 1,
             transport: Box::new(transport),
             done_cb: None,
+            tags: FlowTags::new(),
+            direction: ring::RingDirection::Clockwise,
+            rotation: 0,
+            reduce_ns_per_byte: 0.0,
         },
     );
     sim.run(&mut world);
@@ -313,4 +379,8 @@ fn main() {
             }
         }
     }
+
+    if args.sim_stats {
+        println!("sim_stats {}", sim.metrics());
+    }
 }
@@ -1,9 +1,10 @@
 //! Fat-tree ring allreduce with TCP flows.
 
 use clap::{Parser, ValueEnum};
+use htsim_rs::analysis::percentile;
 use htsim_rs::cc::ring::{self, RingAllreduceConfig, RingTransport, RoutingMode as CcRoutingMode};
-use htsim_rs::net::{EcmpHashMode, NetWorld, NodeId};
-use htsim_rs::proto::tcp::TcpConfig;
+use htsim_rs::net::{EcmpHashMode, FlowTags, NetWorld, NodeId};
+use htsim_rs::proto::tcp::{CcAlgo, TcpConfig};
 use htsim_rs::sim::{SimTime, Simulator};
 use htsim_rs::topo::fat_tree::{FatTreeOpts, build_fat_tree};
 use std::fs;
@@ -33,6 +34,10 @@ struct Args {
     #[arg(long, default_value_t = 1460)]
     mss: u32,
 
+    /// Link MTU in bytes (e.g. 9000 for jumbo frames); mss + 40 must fit
+    #[arg(long, default_value_t = 1500)]
+    mtu: u64,
+
     /// Initial cwnd in MSS packets
     #[arg(long, default_value_t = 10)]
     init_cwnd_pkts: u64,
@@ -60,14 +65,31 @@ struct Args {
     #[arg(long, default_value_t = 2)]
     link_latency_us: u64,
 
-    /// Queue capacity per link in MSS packets; 0 keeps default
+    /// Queue capacity per link in MTU-sized packets; 0 keeps default
     #[arg(long, default_value_t = 0)]
     queue_pkts: u64,
 
+    /// Queue capacity per link as a multiple of its bandwidth-delay product,
+    /// taking the cross-pod base RTT (12 link latencies) as the delay
+    #[arg(long, conflicts_with = "queue_pkts")]
+    queue_bdp: Option<f64>,
+
     /// Output visualization JSON (for viz/index.html)
     #[arg(long)]
     viz_json: Option<PathBuf>,
 
+    /// Output cwnd CSV for a probe flow
+    #[arg(long)]
+    cwnd_csv: Option<PathBuf>,
+
+    /// Probe rank for cwnd logging (requires --cwnd-csv)
+    #[arg(long, default_value_t = 0)]
+    probe_rank: usize,
+
+    /// Probe step index for cwnd logging (requires --cwnd-csv)
+    #[arg(long, default_value_t = 0)]
+    probe_step: usize,
+
     /// Disable tracing and summary output
     #[arg(long)]
     quiet: bool,
@@ -80,6 +102,14 @@ struct Args {
     #[arg(long, default_value_t = false)]
     handshake: bool,
 
+    /// Pace segments at cwnd/srtt instead of sending the window in one burst
+    #[arg(long, default_value_t = false)]
+    pacing: bool,
+
+    /// Split each flow into this many MPTCP subflows over distinct ECMP paths
+    #[arg(long, default_value_t = 1)]
+    subflows: usize,
+
     /// Application limit (packets per second)
     #[arg(long)]
     app_limited_pps: Option<u64>,
@@ -87,6 +117,14 @@ struct Args {
     /// ECMP routing mode
     #[arg(long, value_enum, default_value_t = RoutingMode::PerFlow)]
     routing: RoutingMode,
+
+    /// ECMP hash seed (defaults to the built-in fixed seed)
+    #[arg(long)]
+    ecmp_seed: Option<u64>,
+
+    /// Print simulator stats (events executed, wall-clock time, events/sec) at the end
+    #[arg(long)]
+    sim_stats: bool,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
@@ -97,6 +135,7 @@ enum RoutingMode {
 
 struct TcpRingTransport {
     cfg: TcpConfig,
+    probe_flow_id: Option<u64>,
 }
 
 impl RingTransport for TcpRingTransport {
@@ -112,7 +151,7 @@ impl RingTransport for TcpRingTransport {
         done: ring::RingDoneCallback,
     ) {
         let mut tcp = std::mem::take(&mut world.net.tcp);
-        let conn = match routing {
+        let mut conn = match routing {
             CcRoutingMode::PerFlow => {
                 let route = world.net.route_ecmp_path(src, dst, flow_id);
                 htsim_rs::proto::tcp::TcpConn::new(
@@ -132,6 +171,9 @@ impl RingTransport for TcpRingTransport {
                 self.cfg.clone(),
             ),
         };
+        if Some(flow_id) == self.probe_flow_id {
+            conn.enable_cwnd_log();
+        }
         let done_cb: htsim_rs::proto::tcp::TcpDoneCallback = Box::new(move |_, now, sim| {
             done(now, sim);
         });
@@ -141,24 +183,6 @@ impl RingTransport for TcpRingTransport {
     }
 }
 
-fn percentile_ns(values: &[u64], p: f64) -> Option<u64> {
-    if values.is_empty() {
-        return None;
-    }
-    let p = if p <= 0.0 {
-        0.0
-    } else if p >= 1.0 {
-        1.0
-    } else {
-        p
-    };
-    let mut sorted = values.to_vec();
-    sorted.sort_unstable();
-    let idx = (p * sorted.len() as f64).ceil() as usize;
-    let idx = idx.saturating_sub(1).min(sorted.len().saturating_sub(1));
-    sorted.get(idx).copied()
-}
-
 fn main() {
     let args = Args::parse();
 
@@ -181,6 +205,7 @@ fn main() {
         k: args.k,
         link_gbps: args.link_gbps,
         link_latency: SimTime::from_micros(args.link_latency_us),
+        oversubscription: 1,
     };
     let topo = build_fat_tree(&mut world, &topo_opts);
 
@@ -203,15 +228,44 @@ fn main() {
         return;
     }
 
+    let total_steps = ranks.saturating_sub(1) * 2;
+    if args.cwnd_csv.is_some() && (args.probe_rank >= ranks || args.probe_step >= total_steps) {
+        eprintln!(
+            "probe out of range: rank={} step={} (ranks={}, steps={})",
+            args.probe_rank, args.probe_step, ranks, total_steps
+        );
+        return;
+    }
+
+    world.net.set_mtu(args.mtu);
+    if let Err(err) = world.net.validate_mss(args.mss) {
+        eprintln!("{err}");
+        return;
+    }
+    world.net.set_max_packet_bytes(args.mss as u64);
     if args.queue_pkts > 0 {
-        let cap_bytes = args.queue_pkts.saturating_mul(args.mss as u64);
+        let cap_bytes = world.net.mem_from_pkt(args.queue_pkts);
         world.net.set_all_link_queue_capacity_bytes(cap_bytes);
     }
+    if let Some(multiple) = args.queue_bdp {
+        // A cross-pod path crosses 6 links each way.
+        let base_rtt = SimTime::from_micros(args.link_latency_us.saturating_mul(12));
+        world
+            .net
+            .set_all_link_queue_capacity_bdp(multiple, base_rtt);
+    }
+    if let Err(err) = world.net.validate_queue_capacities() {
+        eprintln!("{err}");
+        return;
+    }
 
     world.net.set_ecmp_hash_mode(match args.routing {
         RoutingMode::PerFlow => EcmpHashMode::Flow,
         RoutingMode::PerPacket => EcmpHashMode::Packet,
     });
+    if let Some(seed) = args.ecmp_seed {
+        world.net.set_ecmp_seed(seed);
+    }
 
     if args.viz_json.is_some() {
         world.net.viz = Some(htsim_rs::viz::VizLogger::default());
@@ -228,9 +282,27 @@ fn main() {
         max_rto: SimTime::from_millis(args.max_rto_ms),
         handshake: args.handshake,
         app_limited_pps: args.app_limited_pps,
+        done_notify_delay: SimTime::ZERO,
+        cc: CcAlgo::Reno,
+        enable_tlp: false,
+        delayed_ack: None,
+        data_priority: 0,
+        ack_priority: 0,
+        pacing: args.pacing,
+        subflows: args.subflows,
     };
 
-    let transport = TcpRingTransport { cfg: cfg.clone() };
+    let probe_flow_id = args.cwnd_csv.as_ref().map(|_| {
+        let step_offset = (args.probe_step as u64).saturating_mul(ranks as u64);
+        1_u64
+            .saturating_add(step_offset)
+            .saturating_add(args.probe_rank as u64)
+    });
+
+    let transport = TcpRingTransport {
+        cfg: cfg.clone(),
+        probe_flow_id,
+    };
     let handle = ring::start_ring_allreduce(
         &mut sim,
         RingAllreduceConfig {
@@ -244,6 +316,10 @@ fn main() {
             start_flow_id: 1,
             transport: Box::new(transport),
             done_cb: None,
+            tags: FlowTags::new(),
+            direction: ring::RingDirection::Clockwise,
+            rotation: 0,
+            reduce_ns_per_byte: 0.0,
         },
     );
     sim.run(&mut world);
@@ -252,7 +328,7 @@ fn main() {
     let start = stats.start_at.unwrap_or(sim.now());
     let fct_ns = stats.done_at.map(|d| d.0.saturating_sub(start.0));
     let reduce_ns = stats.reduce_done_at.map(|d| d.0.saturating_sub(start.0));
-    let p99_ns = percentile_ns(&stats.flow_fct_ns, 0.99);
+    let p99_ns = percentile(&stats.flow_fct_ns, 0.99);
     let max_flow_ns = stats.flow_fct_ns.iter().copied().max();
     let slow_threshold_ns = SimTime::from_secs(1).0;
     let slow_count = stats
@@ -313,4 +389,27 @@ fn main() {
             }
         }
     }
+
+    let probe_samples = probe_flow_id
+        .and_then(|id| world.net.tcp.get(id))
+        .and_then(|c| c.cwnd_samples());
+    if let (Some(path), Some(samples)) = (args.cwnd_csv, probe_samples) {
+        let mut out =
+            String::from("t_ns,cwnd_bytes,ssthresh_bytes,srtt_ns,inflight_bytes,acked_bytes\n");
+        for s in samples {
+            let srtt = s.srtt_ns.map_or_else(String::new, |ns| ns.to_string());
+            out.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                s.t_ns, s.cwnd_bytes, s.ssthresh_bytes, srtt, s.inflight_bytes, s.acked_bytes
+            ));
+        }
+        fs::write(&path, out).expect("write cwnd csv");
+        if !args.quiet {
+            eprintln!("wrote cwnd samples to {}", path.display());
+        }
+    }
+
+    if args.sim_stats {
+        println!("sim_stats {}", sim.metrics());
+    }
 }
@@ -0,0 +1,273 @@
+//! Incast 流量生成器
+//!
+//! 在多发送端 dumbbell 上用 `experiments::incast` 发起 N -> 1 的同步流，
+//! 可选 TCP / DCTCP 与路由模式，扫描发送端数量，输出 goodput、RTO 次数与丢包数。
+//! N 增大、缓冲变浅时同步丢包触发整窗 RTO，goodput 崩溃（经典场景下 min RTO 为 200ms）。
+
+use clap::Parser;
+use htsim_rs::cc::ring::RoutingMode;
+use htsim_rs::experiments::{IncastOpts, IncastReport, incast};
+use htsim_rs::net::NetWorld;
+use htsim_rs::proto::dctcp::DctcpConfig;
+use htsim_rs::proto::tcp::TcpConfig;
+use htsim_rs::sim::{SimTime, Simulator, TransportProtocol};
+use htsim_rs::topo::dumbbell::{DumbbellOpts, build_fanin_dumbbell};
+use htsim_rs::viz::{VizEventKind, VizLogger};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "incast-sim",
+    about = "Incast 流量生成：N 个发送端同时向 1 个接收端发送，扫描 N 统计 goodput、RTO 与丢包"
+)]
+struct Args {
+    /// 要扫描的发送端数量（逗号分隔）
+    #[arg(long, value_delimiter = ',', default_value = "8")]
+    fanin: Vec<usize>,
+
+    /// 每条流的字节数
+    #[arg(long, default_value_t = 256 * 1024)]
+    bytes: u64,
+
+    /// 传输协议
+    #[arg(long, value_enum, default_value_t = TransportProtocol::Tcp)]
+    protocol: TransportProtocol,
+
+    /// 路由模式
+    #[arg(long, value_enum, default_value_t = RoutingMode::PerFlow)]
+    routing: RoutingMode,
+
+    /// 交换机出方向队列大小（单位：MSS 个数）
+    #[arg(long, default_value_t = 32)]
+    queue_pkts: u64,
+
+    /// DCTCP 的 ECN 标记阈值 K（单位：MSS 个数，仅 dctcp 生效）
+    #[arg(long, default_value_t = 20)]
+    ecn_k_pkts: u64,
+
+    /// Host 链路带宽（Gbps）
+    #[arg(long, default_value_t = 100)]
+    host_gbps: u64,
+
+    /// 瓶颈链路带宽（Gbps）
+    #[arg(long, default_value_t = 10)]
+    bottleneck_gbps: u64,
+
+    /// 单向链路传播时延（微秒）
+    #[arg(long, default_value_t = 2)]
+    link_latency_us: u64,
+
+    /// 最小 RTO（毫秒）
+    #[arg(long, default_value_t = 1)]
+    min_rto_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+struct IncastSimOpts {
+    fanin: usize,
+    bytes: u64,
+    protocol: TransportProtocol,
+    routing: RoutingMode,
+    queue_pkts: u64,
+    ecn_k_pkts: u64,
+    topo: DumbbellOpts,
+    min_rto: SimTime,
+}
+
+#[derive(Debug, Clone)]
+struct IncastSimResult {
+    report: IncastReport,
+    /// 所有流的 RTO 超时次数之和
+    rtos: usize,
+}
+
+const MSS: u32 = 1460;
+
+/// 搭建多发送端 dumbbell 并运行一次 incast，直到所有流结束。
+fn run_incast(opts: &IncastSimOpts) -> IncastSimResult {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (senders, rx) = build_fanin_dumbbell(&mut world, &opts.topo, opts.fanin);
+
+    world.net.set_max_packet_bytes(MSS as u64);
+    world
+        .net
+        .set_switch_egress_queue_capacity_bytes(opts.queue_pkts.saturating_mul(MSS as u64));
+    if opts.protocol == TransportProtocol::Dctcp {
+        world
+            .net
+            .set_all_link_ecn_threshold_bytes(opts.ecn_k_pkts.saturating_mul(MSS as u64));
+    }
+    world
+        .net
+        .validate_queue_capacities()
+        .unwrap_or_else(|err| panic!("{err}"));
+    world.net.viz = Some(VizLogger::default());
+
+    let incast_opts = IncastOpts {
+        bytes: opts.bytes,
+        protocol: opts.protocol,
+        routing: opts.routing,
+        tcp_cfg: TcpConfig {
+            mss: MSS,
+            init_rto: opts.min_rto,
+            min_rto: opts.min_rto,
+            handshake: false,
+            ..TcpConfig::default()
+        },
+        dctcp_cfg: DctcpConfig {
+            mss: MSS,
+            init_rto: opts.min_rto,
+            min_rto: opts.min_rto,
+            ..DctcpConfig::default()
+        },
+        first_flow_id: 1,
+    };
+    let run = incast(&mut sim, &mut world, &senders, rx, &incast_opts);
+    sim.run(&mut world);
+    let rtos = world.net.viz.as_ref().map_or(0, |v| {
+        v.events
+            .iter()
+            .filter(|ev| matches!(ev.kind, VizEventKind::TcpRto(_)))
+            .count()
+    });
+    IncastSimResult {
+        report: run.report(&world),
+        rtos,
+    }
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .with_file(true)
+        .with_line_number(true)
+        .with_target(true)
+        .init();
+
+    let args = Args::parse();
+    for &fanin in &args.fanin {
+        let IncastSimResult { report: r, rtos } = run_incast(&IncastSimOpts {
+            fanin,
+            bytes: args.bytes,
+            protocol: args.protocol,
+            routing: args.routing,
+            queue_pkts: args.queue_pkts,
+            ecn_k_pkts: args.ecn_k_pkts,
+            topo: DumbbellOpts {
+                host_link_gbps: args.host_gbps,
+                bottleneck_gbps: args.bottleneck_gbps,
+                link_latency: SimTime::from_micros(args.link_latency_us),
+                ..DumbbellOpts::default()
+            },
+            min_rto: SimTime::from_millis(args.min_rto_ms),
+        });
+        println!(
+            "fanin={} bytes={} flows_done={} completion_ms={} goodput_gbps={:.3} rtos={} dropped_pkts={}",
+            r.fanin,
+            r.bytes_per_flow,
+            r.flows_done,
+            r.completion
+                .map_or_else(|| "-".to_string(), |t| format!("{:.3}", t.0 as f64 / 1e6)),
+            r.goodput_gbps,
+            rtos,
+            r.dropped_pkts
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_opts(protocol: TransportProtocol) -> IncastSimOpts {
+        IncastSimOpts {
+            fanin: 8,
+            bytes: 64 * 1024,
+            protocol,
+            routing: RoutingMode::PerFlow,
+            queue_pkts: 32,
+            ecn_k_pkts: 20,
+            topo: DumbbellOpts::default(),
+            min_rto: SimTime::from_millis(1),
+        }
+    }
+
+    /// 经典 incast：256KB 同步读、10Gbps 链路、200ms min RTO
+    fn classic_opts(fanin: usize) -> IncastSimOpts {
+        IncastSimOpts {
+            fanin,
+            bytes: 256 * 1024,
+            topo: DumbbellOpts {
+                host_link_gbps: 10,
+                ..DumbbellOpts::default()
+            },
+            min_rto: SimTime::from_millis(200),
+            ..default_opts(TransportProtocol::Tcp)
+        }
+    }
+
+    #[test]
+    fn tcp_incast_recovers_from_drops_in_shallow_queue() {
+        let shallow = run_incast(&IncastSimOpts {
+            queue_pkts: 8,
+            ..default_opts(TransportProtocol::Tcp)
+        })
+        .report;
+        let deep = run_incast(&IncastSimOpts {
+            queue_pkts: 1000,
+            ..default_opts(TransportProtocol::Tcp)
+        })
+        .report;
+        assert_eq!(shallow.flows_done, 8, "{shallow:?}");
+        assert_eq!(deep.flows_done, 8, "{deep:?}");
+        assert_eq!(deep.dropped_pkts, 0, "{deep:?}");
+        assert!(shallow.dropped_pkts > 0, "{shallow:?}");
+        assert!(
+            shallow.goodput_gbps < deep.goodput_gbps,
+            "{shallow:?} {deep:?}"
+        );
+    }
+
+    #[test]
+    fn dctcp_incast_completes_without_drops_in_deep_queue() {
+        let r = run_incast(&IncastSimOpts {
+            queue_pkts: 1000,
+            ..default_opts(TransportProtocol::Dctcp)
+        })
+        .report;
+        assert_eq!(r.flows_done, 8, "{r:?}");
+        assert_eq!(r.dropped_pkts, 0, "{r:?}");
+        assert!(r.goodput_gbps > 0.0, "{r:?}");
+    }
+
+    #[test]
+    fn goodput_collapses_once_synchronized_losses_force_rtos() {
+        // A lone sender fills the bottleneck without timing out.
+        let solo = run_incast(&classic_opts(1));
+        assert_eq!(solo.rtos, 0, "{solo:?}");
+        assert!(solo.report.goodput_gbps > 4.0, "{solo:?}");
+        // Past the threshold whole windows are lost and a 200ms RTO dominates.
+        for n in [8, 16] {
+            let r = run_incast(&classic_opts(n));
+            assert!(r.rtos > 0, "n={n}: {r:?}");
+            assert!(
+                r.report.completion >= Some(SimTime::from_millis(200)),
+                "n={n}: {r:?}"
+            );
+            assert!(r.report.goodput_gbps < 0.1, "n={n}: {r:?}");
+        }
+    }
+
+    #[test]
+    fn small_min_rto_mitigates_incast_collapse() {
+        let r = run_incast(&IncastSimOpts {
+            min_rto: SimTime::from_millis(1),
+            ..classic_opts(16)
+        });
+        assert!(r.rtos > 0, "{r:?}");
+        assert!(r.report.goodput_gbps > 1.0, "{r:?}");
+    }
+}
@@ -96,6 +96,7 @@ fn main() {
         bottleneck_gbps: args.bottleneck_gbps,
         link_latency: SimTime::from_micros(args.link_latency_us),
         until: SimTime::from_millis(100),
+        ..DumbbellOpts::default()
     };
 
     info!("╔════════════════════════════════════════════════════════════════════════════════╗");
@@ -1,22 +1,33 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use htsim_rs::analysis::percentile;
 use htsim_rs::cc::collective::CollectiveOp;
 use htsim_rs::cc::ring::{self, RingAllreduceConfig, RingTransport, RoutingMode as CcRoutingMode};
-use htsim_rs::net::{EcmpHashMode, NetWorld, NodeId};
+use htsim_rs::experiments::start_p2p_flow;
+use htsim_rs::net::{EcmpHashMode, FlowTags, NetWorld, NodeId};
 use htsim_rs::proto::dctcp::{DctcpConfig, DctcpConn, DctcpDoneCallback};
 use htsim_rs::proto::tcp::{TcpConfig, TcpConn, TcpDoneCallback};
-use htsim_rs::queue::DEFAULT_PKT_BYTES;
+use htsim_rs::queue::{DEFAULT_PKT_BYTES, EgressScheduler};
 use htsim_rs::sim::{
-    HostSpec, RankStepKind, RankStepSpec, RoutingMode, SendRecvDirection, SimTime, Simulator,
-    StepSpec, TopologySpec, TransportProtocol, WorkloadDefaults, WorkloadSpec,
+    GpuSpec, HostSpec, RankStepKind, RankStepSpec, RoutingMode, SendRecvDirection, SimTime,
+    Simulator, StepSpec, TopologySpec, TransportProtocol, WorkloadDefaults, WorkloadSpec,
+    check_determinism,
 };
 use htsim_rs::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use htsim_rs::topo::fat_tree::{FatTreeOpts, build_fat_tree};
 use htsim_rs::viz::{VizEvent, VizEventKind, VizLogger};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum VizFormat {
+    Json,
+    Jsonl,
+    Csv,
+}
+
 const DEFAULT_HOST_EGRESS_QUEUE_BYTES: u64 = 16_u64 * 1024 * 1024 * 1024;
 
 #[derive(Debug, Parser)]
@@ -29,10 +40,34 @@ struct Args {
     #[arg(long)]
     workload: PathBuf,
 
-    /// Output viz JSON file (for viz/index.html)
+    /// Output viz events file (JSON is for viz/index.html; see --viz-format)
     #[arg(long)]
     viz_json: Option<PathBuf>,
 
+    /// Format of --viz-json: json (pretty array), jsonl (streamed during the run) or csv
+    #[arg(long, value_enum, default_value_t = VizFormat::Json)]
+    viz_format: VizFormat,
+
+    /// Stop recording viz events after this many (the output is marked truncated on stderr)
+    #[arg(long)]
+    viz_max_events: Option<u64>,
+
+    /// Only record viz events of these flow ids (comma-separated); meta events are always kept
+    #[arg(long, value_delimiter = ',')]
+    viz_flows: Vec<u64>,
+
+    /// Only record viz events touching these node ids (comma-separated, as in the meta event)
+    #[arg(long, value_delimiter = ',')]
+    viz_nodes: Vec<usize>,
+
+    /// Output per-link/per-flow throughput series (JSON) pre-aggregated for charts
+    #[arg(long)]
+    viz_throughput_json: Option<PathBuf>,
+
+    /// Bin width (us) for --viz-throughput-json
+    #[arg(long, default_value_t = 10)]
+    viz_throughput_bin_us: u64,
+
     /// Run until this time (ms); defaults to running until completion
     #[arg(long)]
     until_ms: Option<u64>,
@@ -45,10 +80,19 @@ struct Args {
     #[arg(long)]
     routing: Option<String>,
 
-    /// Print per-collective flow completion time (FCT) stats
+    /// ECMP hash seed (defaults to the built-in fixed seed)
+    #[arg(long)]
+    ecmp_seed: Option<u64>,
+
+    /// Print per-collective flow completion time (FCT) stats, with the analytical ring
+    /// cost model alongside, and per-RPC latencies
     #[arg(long)]
     fct_stats: bool,
 
+    /// Output one row of flow completion time (FCT) stats per collective as CSV
+    #[arg(long)]
+    fct_csv: Option<PathBuf>,
+
     /// Override switch egress queue capacity in bytes
     #[arg(long)]
     queue_bytes: Option<u64>,
@@ -61,9 +105,47 @@ struct Args {
     #[arg(long)]
     host_queue_bytes: Option<u64>,
 
+    /// Serve host egress data packets round-robin per flow instead of FIFO
+    #[arg(long)]
+    host_egress_flow_wrr: bool,
+
     /// Override host egress queue capacity in packets (1500B each)
     #[arg(long)]
     host_queue_pkts: Option<u64>,
+
+    /// Halt each rank when it reaches a step with this label
+    #[arg(long)]
+    stop_at_label: Option<String>,
+
+    /// Only run steps with these labels (comma-separated); other steps are no-ops
+    #[arg(long, value_delimiter = ',')]
+    only_labels: Vec<String>,
+
+    /// Output per-rank step timeline as JSON (rank-based workloads only)
+    #[arg(long)]
+    timeline_json: Option<PathBuf>,
+
+    /// Output per-rank step timeline as CSV (rank-based workloads only)
+    #[arg(long)]
+    timeline_csv: Option<PathBuf>,
+
+    /// Launch a collective once this fraction of its ranks has arrived, in (0, 1];
+    /// ranks arriving after launch are dropped from that collective
+    #[arg(long, default_value_t = 1.0)]
+    collective_quorum: f64,
+
+    /// Max comm streams a host's NIC serves at once (e.g. DMA engines); collectives
+    /// on other streams wait for a free slot on every member host. Unlimited by default
+    #[arg(long)]
+    nic_streams: Option<usize>,
+
+    /// Run the simulation twice and fail if the event sequences or stats differ
+    #[arg(long)]
+    check_determinism: bool,
+
+    /// Print simulator stats (events executed, wall-clock time, events/sec) at the end
+    #[arg(long)]
+    sim_stats: bool,
 }
 
 struct CollectiveRecord {
@@ -80,13 +162,14 @@ struct WorkloadState {
     steps: Vec<StepSpec>,
     hosts_all: Vec<usize>,
     host_map: HashMap<usize, NodeId>,
-    gpu_map: HashMap<usize, Option<String>>,
+    gpu_map: HashMap<usize, Option<GpuSpec>>,
     protocol: TransportProtocol,
     routing: CcRoutingMode,
     next_flow_id: u64,
     tcp_cfg: TcpConfig,
     dctcp_cfg: DctcpConfig,
     collective_handles: Arc<Mutex<Vec<CollectiveRecord>>>,
+    step_filter: StepFilter,
 }
 
 struct StartWorkloadStep {
@@ -99,6 +182,8 @@ enum AsyncWaitKind {
     None,
     All,
     Stream(u64),
+    /// Waiting for the async collectives named in the current step's `depends_on`.
+    Deps,
 }
 
 struct RankState {
@@ -106,7 +191,142 @@ struct RankState {
     idx: usize,
     pending_async_total: usize,
     pending_async_by_stream: HashMap<u64, usize>,
+    pending_async_by_comm_id: HashMap<String, usize>,
     waiting_for_async: AsyncWaitKind,
+    timeline: Vec<TimelineRow>,
+}
+
+/// One executed step in a rank's timeline.
+#[derive(Debug, Clone, Serialize)]
+struct TimelineRow {
+    rank: usize,
+    step_idx: usize,
+    kind: RankStepKind,
+    label: Option<String>,
+    start_ns: u64,
+    /// `None` if the step was still running when the simulation stopped.
+    end_ns: Option<u64>,
+    /// Gap between the previous step's end (or t=0) and this step's start.
+    idle_before_ns: u64,
+}
+
+impl RankState {
+    fn start_timeline_row(&mut self, rank: usize, kind: &RankStepKind, now: SimTime) {
+        let prev_end = self.timeline.last().and_then(|r| r.end_ns).unwrap_or(0);
+        let step = &self.steps[self.idx];
+        self.timeline.push(TimelineRow {
+            rank,
+            step_idx: self.idx,
+            kind: kind.clone(),
+            label: step.label.clone(),
+            start_ns: now.0,
+            end_ns: None,
+            idle_before_ns: now.0.saturating_sub(prev_end),
+        });
+    }
+
+    /// The executor re-enters `StartRankStep` exactly when the running step
+    /// releases the rank, so that is where the open row gets closed.
+    fn finish_timeline_row(&mut self, now: SimTime) {
+        if let Some(row) = self.timeline.last_mut().filter(|r| r.end_ns.is_none()) {
+            row.end_ns = Some(now.0);
+        }
+    }
+}
+
+fn rank_step_kind_name(kind: &RankStepKind) -> &'static str {
+    match kind {
+        RankStepKind::Compute => "compute",
+        RankStepKind::Collective => "collective",
+        RankStepKind::CollectiveWait => "collective_wait",
+        RankStepKind::Sendrecv => "sendrecv",
+        RankStepKind::Barrier => "barrier",
+        RankStepKind::Rpc => "rpc",
+    }
+}
+
+fn timeline_rows(state: &RankWorkloadState) -> Vec<TimelineRow> {
+    let mut rank_ids = state.ranks.keys().copied().collect::<Vec<_>>();
+    rank_ids.sort_unstable();
+    rank_ids
+        .iter()
+        .flat_map(|rid| state.ranks[rid].timeline.iter().cloned())
+        .collect()
+}
+
+fn timeline_csv(rows: &[TimelineRow]) -> String {
+    let mut out = String::from("rank,step_idx,kind,label,start_ns,end_ns,idle_before_ns\n");
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            r.rank,
+            r.step_idx,
+            rank_step_kind_name(&r.kind),
+            r.label.as_deref().unwrap_or(""),
+            r.start_ns,
+            r.end_ns.map(|ns| ns.to_string()).unwrap_or_default(),
+            r.idle_before_ns
+        ));
+    }
+    out
+}
+
+/// Flow completion time summary of one collective, for `--fct-csv`.
+#[derive(Debug, Clone)]
+struct FctRow {
+    step_id: Option<u64>,
+    label: Option<String>,
+    comm_id: Option<String>,
+    op: Option<String>,
+    hosts: usize,
+    comm_bytes: u64,
+    /// `None` if the collective had not finished when the simulation stopped.
+    makespan_ns: Option<u64>,
+    flow_fct_ns: Vec<u64>,
+}
+
+fn fct_rows(records: &[CollectiveRecord]) -> Vec<FctRow> {
+    records
+        .iter()
+        .map(|record| {
+            let stats = record.handle.stats();
+            FctRow {
+                step_id: record.step_id,
+                label: record.label.clone(),
+                comm_id: record.comm_id.clone(),
+                op: record.op.clone(),
+                hosts: record.hosts,
+                comm_bytes: record.comm_bytes,
+                makespan_ns: stats.makespan_ns(),
+                flow_fct_ns: stats.flow_fct_ns,
+            }
+        })
+        .collect()
+}
+
+fn fct_csv(rows: &[FctRow]) -> String {
+    let mut out = String::from(
+        "step_id,label,comm_id,op,hosts,comm_bytes,makespan_ns,p50_flow_fct_ns,p90_flow_fct_ns,p99_flow_fct_ns,max_flow_fct_ns,flows\n",
+    );
+    let cell = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_default();
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            cell(r.step_id),
+            r.label.as_deref().unwrap_or(""),
+            r.comm_id.as_deref().unwrap_or(""),
+            r.op.as_deref().unwrap_or(""),
+            r.hosts,
+            r.comm_bytes,
+            cell(r.makespan_ns),
+            cell(percentile(&r.flow_fct_ns, 0.50)),
+            cell(percentile(&r.flow_fct_ns, 0.90)),
+            cell(percentile(&r.flow_fct_ns, 0.99)),
+            cell(r.flow_fct_ns.iter().copied().max()),
+            r.flow_fct_ns.len()
+        ));
+    }
+    out
 }
 
 struct CollectiveWait {
@@ -115,29 +335,227 @@ struct CollectiveWait {
     op: String,
     is_async: bool,
     comm_stream: u64,
+    /// Per-rank decompression cost charged after the collective completes.
+    decompress_ns: u64,
+    /// Per-pair bytes for `alltoall`, indexed by position in `hosts`.
+    alltoall_matrix: Option<Vec<Vec<u64>>>,
     arrived: Vec<usize>,
 }
 
+/// Number of arrived ranks needed to launch a collective over `hosts` ranks.
+fn collective_quorum_count(hosts: usize, quorum: f64) -> usize {
+    // Small epsilon so that e.g. 10 * 0.9 does not round up to 10.
+    let needed = (hosts as f64 * quorum - 1e-9).ceil() as usize;
+    needed.clamp(1, hosts.max(1))
+}
+
 struct SendRecvWait {
     comm_bytes: u64,
+    /// Transport requested by either side's step (both must agree if both set).
+    protocol: Option<TransportProtocol>,
     sender: Option<usize>,
     receiver: Option<usize>,
     arrived: Vec<usize>,
 }
 
+struct BarrierWait {
+    hosts: Vec<usize>,
+    arrived: Vec<usize>,
+}
+
+/// One `rpc` step: the request leg to `peer` and the response leg back.
+struct RpcRecord {
+    rank: usize,
+    peer: usize,
+    label: Option<String>,
+    req_bytes: u64,
+    resp_bytes: u64,
+    /// Transport used for both legs.
+    protocol: TransportProtocol,
+    start_ns: u64,
+    /// Set once the response has fully arrived back at `rank`.
+    end_ns: Option<u64>,
+}
+
+/// Debug filters consulted by the executor before running each step.
+#[derive(Debug, Clone, Default)]
+struct StepFilter {
+    /// Halt a rank when it reaches a step with this label.
+    stop_at_label: Option<String>,
+    /// If non-empty, steps whose label is not listed are skipped.
+    only_labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepFilterAction {
+    Run,
+    Skip,
+    Stop,
+}
+
+impl StepFilter {
+    fn action(&self, label: Option<&str>) -> StepFilterAction {
+        if label.is_some() && label == self.stop_at_label.as_deref() {
+            return StepFilterAction::Stop;
+        }
+        if !self.only_labels.is_empty()
+            && !label.is_some_and(|l| self.only_labels.iter().any(|x| x == l))
+        {
+            return StepFilterAction::Skip;
+        }
+        StepFilterAction::Run
+    }
+}
+
 struct RankWorkloadState {
     ranks: HashMap<usize, RankState>,
     hosts_all: Vec<usize>,
     host_map: HashMap<usize, NodeId>,
-    gpu_map: HashMap<usize, Option<String>>,
+    gpu_map: HashMap<usize, Option<GpuSpec>>,
     protocol: TransportProtocol,
     routing: CcRoutingMode,
     next_flow_id: u64,
     tcp_cfg: TcpConfig,
     dctcp_cfg: DctcpConfig,
     pending_collectives: HashMap<String, CollectiveWait>,
-    pending_sendrecv: HashMap<String, SendRecvWait>,
+    /// Fraction of ranks that must arrive before a collective launches.
+    collective_quorum: f64,
+    /// comm_id -> rank -> quorum launches that went ahead without that rank and that
+    /// it has not yet skipped (a straggler can fall several iterations behind)
+    late_collectives: HashMap<String, HashMap<usize, u32>>,
+    /// Per-host comm stream slots; collectives beyond the limit are held paused.
+    nic: NicStreams,
+    /// Keyed by `(comm_id, tag)`.
+    pending_sendrecv: HashMap<(String, Option<u64>), SendRecvWait>,
+    pending_barriers: HashMap<String, BarrierWait>,
+    /// RPCs issued by rank steps, in issue order.
+    rpcs: Vec<RpcRecord>,
     collective_handles: Arc<Mutex<Vec<CollectiveRecord>>>,
+    step_filter: StepFilter,
+}
+
+impl RankWorkloadState {
+    /// Consume one quorum launch of `comm_id` that went ahead without `rank`;
+    /// returns false if the rank is not behind on that collective.
+    fn take_late_collective(&mut self, comm_id: &str, rank: usize) -> bool {
+        let Some(late) = self.late_collectives.get_mut(comm_id) else {
+            return false;
+        };
+        let Some(missed) = late.get_mut(&rank) else {
+            return false;
+        };
+        *missed -= 1;
+        if *missed == 0 {
+            late.remove(&rank);
+            if late.is_empty() {
+                self.late_collectives.remove(comm_id);
+            }
+        }
+        true
+    }
+}
+
+/// Limits how many comm streams each host drives through the network at once.
+///
+/// Streams only order steps within a rank; without a limit every stream gets the
+/// NIC to itself. With one, a collective whose stream is not already active on a
+/// member host that is out of slots is launched paused and resumed, in launch
+/// order, once slots free up.
+#[derive(Default)]
+struct NicStreams {
+    limit: Option<usize>,
+    /// host id -> stream -> collectives in flight on it
+    active: HashMap<usize, HashMap<u64, usize>>,
+    waiting: VecDeque<(Vec<usize>, u64, ring::RingAllreduceHandle)>,
+}
+
+impl NicStreams {
+    fn new(limit: Option<usize>) -> Self {
+        if limit == Some(0) {
+            panic!("--nic-streams must be at least 1");
+        }
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    fn has_slot(&self, hosts: &[usize], stream: u64) -> bool {
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        hosts.iter().all(|h| {
+            self.active
+                .get(h)
+                .is_none_or(|streams| streams.contains_key(&stream) || streams.len() < limit)
+        })
+    }
+
+    fn acquire(&mut self, hosts: &[usize], stream: u64) {
+        if self.limit.is_none() {
+            return;
+        }
+        for h in hosts {
+            let streams = self.active.entry(*h).or_default();
+            *streams.entry(stream).or_insert(0) += 1;
+        }
+    }
+
+    /// Start `handle` now if every host has a slot for `stream`, otherwise pause it in line.
+    fn admit(&mut self, hosts: Vec<usize>, stream: u64, handle: &ring::RingAllreduceHandle) {
+        if self.has_slot(&hosts, stream) {
+            self.acquire(&hosts, stream);
+        } else {
+            handle.pause();
+            self.waiting.push_back((hosts, stream, handle.clone()));
+        }
+    }
+
+    /// Free `stream` on `hosts` and return the waiting collectives that now fit.
+    fn release(&mut self, hosts: &[usize], stream: u64) -> Vec<ring::RingAllreduceHandle> {
+        if self.limit.is_none() {
+            return Vec::new();
+        }
+        for h in hosts {
+            let Some(streams) = self.active.get_mut(h) else {
+                continue;
+            };
+            if let Some(count) = streams.get_mut(&stream) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    streams.remove(&stream);
+                }
+            }
+        }
+        let mut ready = Vec::new();
+        let mut still_waiting = VecDeque::new();
+        while let Some((hosts, stream, handle)) = self.waiting.pop_front() {
+            if self.has_slot(&hosts, stream) {
+                self.acquire(&hosts, stream);
+                ready.push(handle);
+            } else {
+                still_waiting.push_back((hosts, stream, handle));
+            }
+        }
+        self.waiting = still_waiting;
+        ready
+    }
+}
+
+/// Release a finished collective's NIC stream slots and resume whatever they unblock.
+fn release_nic_streams(
+    state: &Arc<Mutex<RankWorkloadState>>,
+    hosts: &[usize],
+    stream: u64,
+    sim: &mut Simulator,
+) {
+    let ready = {
+        let mut st = state.lock().expect("rank workload state lock");
+        st.nic.release(hosts, stream)
+    };
+    for handle in ready {
+        handle.resume(sim);
+    }
 }
 
 struct StartRankStep {
@@ -145,6 +563,91 @@ struct StartRankStep {
     state: Arc<Mutex<RankWorkloadState>>,
 }
 
+/// Sends the response leg of `state.rpcs[rpc_idx]` once its request has arrived.
+struct StartRpcResponse {
+    rpc_idx: usize,
+    state: Arc<Mutex<RankWorkloadState>>,
+}
+
+impl htsim_rs::sim::Event for StartRpcResponse {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn htsim_rs::sim::World) {
+        let StartRpcResponse { rpc_idx, state } = *self;
+        let w = world
+            .as_any_mut()
+            .downcast_mut::<NetWorld>()
+            .expect("world must be NetWorld");
+        start_rpc_leg(sim, w, &state, rpc_idx, true);
+    }
+}
+
+/// Start the request (`response == false`) or response leg of an RPC.
+///
+/// Finishing the request schedules the response; finishing the response records
+/// the RPC latency and releases the calling rank.
+fn start_rpc_leg(
+    sim: &mut Simulator,
+    w: &mut NetWorld,
+    state: &Arc<Mutex<RankWorkloadState>>,
+    rpc_idx: usize,
+    response: bool,
+) {
+    let (rank, peer, bytes, flow_id, src, dst, protocol, routing, tcp_cfg, dctcp_cfg) = {
+        let mut st = state.lock().expect("rank workload state lock");
+        let rpc = &st.rpcs[rpc_idx];
+        let (rank, peer, protocol) = (rpc.rank, rpc.peer, rpc.protocol);
+        let (from, to, bytes) = if response {
+            (peer, rank, rpc.resp_bytes)
+        } else {
+            (rank, peer, rpc.req_bytes)
+        };
+        let src = *st.host_map.get(&from).expect("unknown host id");
+        let dst = *st.host_map.get(&to).expect("unknown host id");
+        let flow_id = st.next_flow_id;
+        st.next_flow_id = st.next_flow_id.saturating_add(1);
+        (
+            rank,
+            peer,
+            bytes,
+            flow_id,
+            src,
+            dst,
+            protocol,
+            st.routing,
+            st.tcp_cfg.clone(),
+            st.dctcp_cfg.clone(),
+        )
+    };
+
+    let done_state = Arc::clone(state);
+    let done_cb: ring::RingDoneCallback = Box::new(move |now, sim| {
+        if !response {
+            sim.schedule(
+                now,
+                StartRpcResponse {
+                    rpc_idx,
+                    state: Arc::clone(&done_state),
+                },
+            );
+            return;
+        }
+        done_state.lock().expect("rank workload state lock").rpcs[rpc_idx].end_ns = Some(now.0);
+        sim.schedule(
+            now,
+            StartRankStep {
+                rank_id: rank,
+                state: Arc::clone(&done_state),
+            },
+        );
+    });
+    if bytes == 0 || rank == peer {
+        done_cb(sim.now(), sim);
+        return;
+    }
+    start_p2p_flow(
+        sim, w, protocol, routing, &tcp_cfg, &dctcp_cfg, flow_id, src, dst, bytes, done_cb,
+    );
+}
+
 struct TcpRingTransport {
     cfg: TcpConfig,
 }
@@ -215,6 +718,23 @@ impl RingTransport for DctcpRingTransport {
     }
 }
 
+/// Transport configs for a collective step, with its initial window overrides applied.
+fn step_transport_cfgs(
+    step: &RankStepSpec,
+    mut tcp_cfg: TcpConfig,
+    mut dctcp_cfg: DctcpConfig,
+) -> (TcpConfig, DctcpConfig) {
+    if let Some(bytes) = step.init_cwnd_bytes {
+        tcp_cfg.init_cwnd_bytes = bytes;
+        dctcp_cfg.init_cwnd_bytes = bytes;
+    }
+    if let Some(bytes) = step.init_ssthresh_bytes {
+        tcp_cfg.init_ssthresh_bytes = bytes;
+        dctcp_cfg.init_ssthresh_bytes = bytes;
+    }
+    (tcp_cfg, dctcp_cfg)
+}
+
 fn compute_duration_ns_from_ms(ms: f64) -> u64 {
     if !ms.is_finite() || ms <= 0.0 {
         return 0;
@@ -222,6 +742,79 @@ fn compute_duration_ns_from_ms(ms: f64) -> u64 {
     (ms * 1_000_000.0).round() as u64
 }
 
+/// Duration of a rank compute step: `flops / peak_flops` when the step gives
+/// `flops` and the rank's GPU has a peak rate, otherwise `compute_ms`. A derived
+/// duration that is not finite or not positive (zero, negative or NaN inputs)
+/// also falls back to `compute_ms`.
+fn rank_compute_duration_ns(step: &RankStepSpec, gpu: Option<&GpuSpec>) -> u64 {
+    let peak_flops = gpu.and_then(|g| g.peak_flops);
+    if let (Some(flops), Some(peak_flops)) = (step.flops, peak_flops) {
+        let ns = flops / peak_flops * 1e9;
+        if ns.is_finite() && ns > 0.0 {
+            return ns.round() as u64;
+        }
+    }
+    compute_duration_ns_from_ms(step.compute_ms.unwrap_or(0.0))
+}
+
+/// Apply a gradient compression ratio in (0, 1] to a collective's payload.
+fn compressed_comm_bytes(comm_bytes: u64, compression: Option<f64>) -> u64 {
+    let Some(ratio) = compression else {
+        return comm_bytes;
+    };
+    if !(ratio > 0.0 && ratio <= 1.0) {
+        panic!("compression ratio must be in (0, 1], got {ratio}");
+    }
+    (comm_bytes as f64 * ratio).ceil() as u64
+}
+
+/// Validated, compressed per-pair matrix of an `alltoall` step over `ranks` hosts.
+fn alltoall_matrix(step: &RankStepSpec, op: &str, ranks: usize) -> Option<Vec<Vec<u64>>> {
+    let matrix = step.alltoall_matrix.as_ref()?;
+    if op != "alltoall" {
+        panic!("alltoall_matrix is only supported for op \"alltoall\", got {op:?}");
+    }
+    if matrix.len() != ranks || matrix.iter().any(|row| row.len() != ranks) {
+        panic!("alltoall_matrix must be {ranks}x{ranks} (one row and column per collective host)");
+    }
+    Some(
+        matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|b| compressed_comm_bytes(*b, step.compression))
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Keep the rows and columns of `matrix` whose position is marked in `keep`.
+fn retain_matrix(matrix: &[Vec<u64>], keep: &[bool]) -> Vec<Vec<u64>> {
+    matrix
+        .iter()
+        .zip(keep)
+        .filter(|(_, k)| **k)
+        .map(|(row, _)| {
+            row.iter()
+                .zip(keep)
+                .filter(|(_, k)| **k)
+                .map(|(b, _)| *b)
+                .collect()
+        })
+        .collect()
+}
+
+/// Total bytes moved by an all-to-all matrix (the diagonal never leaves the host).
+fn alltoall_matrix_bytes(matrix: &[Vec<u64>]) -> u64 {
+    matrix
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| row.iter().enumerate().filter(move |(j, _)| *j != i))
+        .map(|(_, b)| *b)
+        .sum()
+}
+
 fn default_tcp_cfg() -> TcpConfig {
     // Keep RTOs reasonably small to avoid huge FCT inflation after drops, but
     // avoid sub-ms floors that can trigger spurious timeouts due to ACK/data
@@ -239,75 +832,15 @@ fn default_tcp_cfg() -> TcpConfig {
     cfg
 }
 
-fn percentile_ns(values: &[u64], p: f64) -> Option<u64> {
-    if values.is_empty() {
-        return None;
-    }
-    let p = if p <= 0.0 {
-        0.0
-    } else if p >= 1.0 {
-        1.0
-    } else {
-        p
-    };
-    let mut sorted = values.to_vec();
-    sorted.sort_unstable();
-    let idx = (p * sorted.len() as f64).ceil() as usize;
-    let idx = idx.saturating_sub(1).min(sorted.len().saturating_sub(1));
-    sorted.get(idx).copied()
-}
-
-fn start_p2p_flow(
-    sim: &mut Simulator,
-    world: &mut NetWorld,
-    protocol: TransportProtocol,
-    routing: CcRoutingMode,
-    tcp_cfg: &TcpConfig,
-    dctcp_cfg: &DctcpConfig,
-    flow_id: u64,
-    src: NodeId,
-    dst: NodeId,
-    bytes: u64,
-    done: ring::RingDoneCallback,
-) {
-    match protocol {
-        TransportProtocol::Tcp => {
-            let mut tcp = std::mem::take(&mut world.net.tcp);
-            let conn = match routing {
-                CcRoutingMode::PerFlow => {
-                    let route = world.net.route_ecmp_path(src, dst, flow_id);
-                    TcpConn::new(flow_id, src, dst, route, bytes, tcp_cfg.clone())
-                }
-                CcRoutingMode::PerPacket => {
-                    TcpConn::new_dynamic(flow_id, src, dst, bytes, tcp_cfg.clone())
-                }
-            };
-            let done_cb: TcpDoneCallback = Box::new(move |_, now, sim| {
-                done(now, sim);
-            });
-            tcp.set_done_callback(flow_id, done_cb);
-            tcp.start_conn(conn, sim, &mut world.net);
-            world.net.tcp = tcp;
-        }
-        TransportProtocol::Dctcp => {
-            let mut dctcp = std::mem::take(&mut world.net.dctcp);
-            let conn = match routing {
-                CcRoutingMode::PerFlow => {
-                    let route = world.net.route_ecmp_path(src, dst, flow_id);
-                    DctcpConn::new(flow_id, src, dst, route, bytes, dctcp_cfg.clone())
-                }
-                CcRoutingMode::PerPacket => {
-                    DctcpConn::new_dynamic(flow_id, src, dst, bytes, dctcp_cfg.clone())
-                }
-            };
-            let done_cb: DctcpDoneCallback = Box::new(move |_, now, sim| {
-                done(now, sim);
-            });
-            dctcp.set_done_callback(flow_id, done_cb);
-            dctcp.start_conn(conn, sim, &mut world.net);
-            world.net.dctcp = dctcp;
+/// Tags attached to every flow of a collective so per-flow stats can be grouped.
+fn collective_flow_tags(label: Option<&str>, comm_id: Option<&str>, op: Option<&str>) -> FlowTags {
+    let mut tags = FlowTags::new();
+    for (key, value) in [("label", label), ("comm_id", comm_id), ("op", op)] {
+        if let Some(value) = value {
+            tags.insert(key.to_string(), value.to_string());
         }
     }
+    tags
 }
 
 impl StartWorkloadStep {
@@ -321,6 +854,9 @@ fn rank_step_kind(step: &RankStepSpec) -> RankStepKind {
     if let Some(kind) = &step.kind {
         return kind.clone();
     }
+    if step.req_bytes.is_some() || step.resp_bytes.is_some() {
+        return RankStepKind::Rpc;
+    }
     if step.peer.is_some() {
         return RankStepKind::Sendrecv;
     }
@@ -349,6 +885,13 @@ fn comm_stream_id(comm_id: &str) -> u64 {
     hash
 }
 
+/// Whether any async collective named in `step.depends_on` is still in flight on this rank.
+fn has_pending_deps(step: &RankStepSpec, rank_state: &RankState) -> bool {
+    step.depends_on
+        .iter()
+        .any(|comm_id| rank_state.pending_async_by_comm_id.contains_key(comm_id))
+}
+
 fn pending_async_on_stream(rank_state: &RankState, stream: u64) -> usize {
     rank_state
         .pending_async_by_stream
@@ -362,8 +905,11 @@ fn async_wait_kind_for_step(
     kind: &RankStepKind,
     rank_state: &RankState,
 ) -> AsyncWaitKind {
+    if has_pending_deps(step, rank_state) {
+        return AsyncWaitKind::Deps;
+    }
     match kind {
-        RankStepKind::Compute => AsyncWaitKind::None,
+        RankStepKind::Compute | RankStepKind::Barrier | RankStepKind::Rpc => AsyncWaitKind::None,
         RankStepKind::CollectiveWait => {
             if let Some(stream) = step.comm_stream {
                 let stream = u64::from(stream);
@@ -409,6 +955,21 @@ impl htsim_rs::sim::Event for StartWorkloadStep {
                 return;
             }
             let step = st.steps[idx].clone();
+            match st.step_filter.action(step.label.as_deref()) {
+                StepFilterAction::Run => {}
+                StepFilterAction::Skip => {
+                    drop(st);
+                    sim.schedule(
+                        sim.now(),
+                        StartWorkloadStep {
+                            idx: idx.saturating_add(1),
+                            state,
+                        },
+                    );
+                    return;
+                }
+                StepFilterAction::Stop => return,
+            }
             let hosts = step.hosts.clone().unwrap_or_else(|| st.hosts_all.clone());
             let protocol = step.protocol.unwrap_or(st.protocol);
             let routing = st.routing;
@@ -444,7 +1005,9 @@ impl htsim_rs::sim::Event for StartWorkloadStep {
             if let Some(v) = &mut w.net.viz {
                 for (idx, hid) in hosts.iter().enumerate() {
                     let node = host_nodes[idx];
-                    let gpu = gpu_map.get(hid).and_then(|g| g.clone());
+                    let gpu = gpu_map
+                        .get(hid)
+                        .and_then(|g| g.as_ref().map(|g| g.model.clone()));
                     v.push(VizEvent {
                         t_ns: sim.now().0,
                         pkt_id: None,
@@ -520,6 +1083,10 @@ impl htsim_rs::sim::Event for StartWorkloadStep {
                 start_flow_id: next_flow_id,
                 transport,
                 done_cb: Some(done_cb),
+                tags: collective_flow_tags(step.label.as_deref(), None, Some("allreduce")),
+                direction: ring::RingDirection::Clockwise,
+                rotation: 0,
+                reduce_ns_per_byte: 0.0,
             },
             next_at,
         );
@@ -546,6 +1113,41 @@ impl htsim_rs::sim::Event for StartRankStep {
             .downcast_mut::<NetWorld>()
             .expect("world must be NetWorld");
 
+        // Apply label filters before any async bookkeeping so that a skipped
+        // step never blocks the rank.
+        let filter_action = {
+            let mut guard = state.lock().expect("rank workload state lock");
+            let st = &mut *guard;
+            let Some(rank_state) = st.ranks.get_mut(&rank_id) else {
+                return;
+            };
+            rank_state.finish_timeline_row(sim.now());
+            let action = match rank_state.steps.get(rank_state.idx) {
+                Some(step) => st.step_filter.action(step.label.as_deref()),
+                None => StepFilterAction::Run,
+            };
+            match action {
+                StepFilterAction::Run => {}
+                StepFilterAction::Skip => rank_state.idx = rank_state.idx.saturating_add(1),
+                StepFilterAction::Stop => rank_state.idx = rank_state.steps.len(),
+            }
+            action
+        };
+        match filter_action {
+            StepFilterAction::Run => {}
+            StepFilterAction::Skip => {
+                sim.schedule(
+                    sim.now(),
+                    StartRankStep {
+                        rank_id,
+                        state: Arc::clone(&state),
+                    },
+                );
+                return;
+            }
+            StepFilterAction::Stop => return,
+        }
+
         let (step, kind, wait_kind, host_node, gpu, protocol, routing, tcp_cfg, dctcp_cfg, hosts_all) = {
             let mut st = state.lock().expect("rank workload state lock");
             let rank_state = match st.ranks.get_mut(&rank_id) {
@@ -563,13 +1165,14 @@ impl htsim_rs::sim::Event for StartRankStep {
             let wait_kind = async_wait_kind_for_step(&step, &kind, rank_state);
             let host_node = *st.host_map.get(&rank_id).expect("unknown host id");
             let gpu = st.gpu_map.get(&rank_id).and_then(|g| g.clone());
+            let protocol = step.protocol.unwrap_or(st.protocol);
             (
                 step,
                 kind,
                 wait_kind,
                 host_node,
                 gpu,
-                st.protocol,
+                protocol,
                 st.routing,
                 st.tcp_cfg.clone(),
                 st.dctcp_cfg.clone(),
@@ -593,12 +1196,13 @@ impl htsim_rs::sim::Event for StartRankStep {
             if rank_state.idx >= rank_state.steps.len() {
                 return;
             }
+            rank_state.start_timeline_row(rank_id, &kind, sim.now());
             rank_state.idx = rank_state.idx.saturating_add(1);
         }
 
         match kind {
             RankStepKind::Compute => {
-                let duration_ns = compute_duration_ns_from_ms(step.compute_ms.unwrap_or(0.0));
+                let duration_ns = rank_compute_duration_ns(&step, gpu.as_ref());
                 if duration_ns > 0 {
                     if let Some(v) = &mut w.net.viz {
                         v.push(VizEvent {
@@ -610,7 +1214,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                             kind: VizEventKind::GpuBusy {
                                 node: host_node.0,
                                 duration_ns,
-                                gpu,
+                                gpu: gpu.map(|g| g.model),
                                 step_id: step.id,
                                 label: step.label.clone(),
                             },
@@ -651,7 +1255,9 @@ impl htsim_rs::sim::Event for StartRankStep {
                         return;
                     }
                 };
-                let comm_bytes = step.comm_bytes.unwrap_or(0);
+                let comm_bytes =
+                    compressed_comm_bytes(step.comm_bytes.unwrap_or(0), step.compression);
+                let decompress_ns = compute_duration_ns_from_ms(step.compression_ms.unwrap_or(0.0));
                 let op = step
                     .op
                     .clone()
@@ -664,6 +1270,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                     .map(u64::from)
                     .unwrap_or_else(|| comm_stream_id(&comm_id));
                 let is_async = collective_is_async(&op);
+                let alltoall_matrix = alltoall_matrix(&step, &op, hosts.len());
+                let comm_bytes = alltoall_matrix
+                    .as_deref()
+                    .map_or(comm_bytes, alltoall_matrix_bytes);
 
                 if !hosts.contains(&rank_id) {
                     panic!(
@@ -672,6 +1282,23 @@ impl htsim_rs::sim::Event for StartRankStep {
                     );
                 }
 
+                // The collective already launched without this rank (quorum reached):
+                // drop it from this iteration and move on.
+                {
+                    let mut st = state.lock().expect("rank workload state lock");
+                    if st.take_late_collective(&comm_id, rank_id) {
+                        drop(st);
+                        sim.schedule(
+                            sim.now(),
+                            StartRankStep {
+                                rank_id,
+                                state: Arc::clone(&state),
+                            },
+                        );
+                        return;
+                    }
+                }
+
                 // Non-blocking collective launch: allow this rank to continue immediately.
                 if is_async {
                     if comm_bytes > 0 && hosts.len() > 1 {
@@ -684,6 +1311,11 @@ impl htsim_rs::sim::Event for StartRankStep {
                             .entry(comm_stream)
                             .or_insert(0);
                         *counter = counter.saturating_add(1);
+                        let counter = rank_state
+                            .pending_async_by_comm_id
+                            .entry(comm_id.clone())
+                            .or_insert(0);
+                        *counter = counter.saturating_add(1);
                     }
                     sim.schedule(
                         sim.now(),
@@ -697,6 +1329,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                 let mut start_cfg = None;
                 {
                     let mut st = state.lock().expect("rank workload state lock");
+                    let quorum = st.collective_quorum;
                     let entry = st
                         .pending_collectives
                         .entry(comm_id.clone())
@@ -706,6 +1339,8 @@ impl htsim_rs::sim::Event for StartRankStep {
                             op: op.clone(),
                             is_async,
                             comm_stream,
+                            decompress_ns,
+                            alltoall_matrix: alltoall_matrix.clone(),
                             arrived: Vec::new(),
                         });
                     if entry.op != op || entry.is_async != is_async {
@@ -732,16 +1367,43 @@ impl htsim_rs::sim::Event for StartRankStep {
                             comm_id, entry.comm_stream, comm_stream
                         );
                     }
+                    if entry.decompress_ns != decompress_ns {
+                        panic!(
+                            "comm_id {:?} collective compression_ms mismatch: existing ns={} vs new ns={}",
+                            comm_id, entry.decompress_ns, decompress_ns
+                        );
+                    }
+                    if entry.alltoall_matrix != alltoall_matrix {
+                        panic!(
+                            "comm_id {:?} collective alltoall_matrix mismatch across ranks",
+                            comm_id
+                        );
+                    }
                     if !entry.arrived.contains(&rank_id) {
                         entry.arrived.push(rank_id);
                     }
-                    if entry.arrived.len() == entry.hosts.len() {
-                        let entry = st
+                    if entry.arrived.len() >= collective_quorum_count(entry.hosts.len(), quorum) {
+                        let mut entry = st
                             .pending_collectives
                             .remove(&comm_id)
                             .expect("pending collective missing");
-                        if entry.comm_bytes == 0 || entry.hosts.len() <= 1 {
-                            start_cfg = Some((
+                        if entry.arrived.len() < entry.hosts.len() {
+                            let late = st.late_collectives.entry(comm_id.clone()).or_default();
+                            for h in entry.hosts.iter().filter(|h| !entry.arrived.contains(h)) {
+                                *late.entry(*h).or_insert(0) += 1;
+                            }
+                            let keep = entry
+                                .hosts
+                                .iter()
+                                .map(|h| entry.arrived.contains(h))
+                                .collect::<Vec<_>>();
+                            if let Some(matrix) = &mut entry.alltoall_matrix {
+                                *matrix = retain_matrix(matrix, &keep);
+                            }
+                            entry.hosts.retain(|h| entry.arrived.contains(h));
+                        }
+                        if entry.comm_bytes == 0 || entry.hosts.len() <= 1 {
+                            start_cfg = Some((
                                 None,
                                 entry.hosts,
                                 entry.comm_bytes,
@@ -749,6 +1411,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                                 Some(entry.op),
                                 entry.is_async,
                                 entry.comm_stream,
+                                entry.decompress_ns,
                             ));
                         } else {
                             let host_nodes = entry
@@ -769,20 +1432,29 @@ impl htsim_rs::sim::Event for StartRankStep {
                             let start_flow_id = st.next_flow_id;
                             st.next_flow_id = st.next_flow_id.saturating_add(flow_span);
                             start_cfg = Some((
-                                Some((host_nodes, start_flow_id, algo)),
+                                Some((host_nodes, start_flow_id, algo, entry.alltoall_matrix)),
                                 entry.hosts,
                                 entry.comm_bytes,
                                 Some(comm_id.clone()),
                                 Some(entry.op),
                                 entry.is_async,
                                 entry.comm_stream,
+                                entry.decompress_ns,
                             ));
                         }
                     }
                 }
 
-                if let Some((maybe_hosts, hosts, bytes, comm_id, op, is_async, comm_stream)) =
-                    start_cfg
+                if let Some((
+                    maybe_hosts,
+                    hosts,
+                    bytes,
+                    comm_id,
+                    op,
+                    is_async,
+                    comm_stream,
+                    decompress_ns,
+                )) = start_cfg
                 {
                     if bytes == 0 || hosts.len() <= 1 {
                         if !is_async {
@@ -799,9 +1471,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                         }
                         return;
                     }
-                    let (host_nodes, start_flow_id, algo) =
+                    let (host_nodes, start_flow_id, algo, alltoall_matrix) =
                         maybe_hosts.expect("collective config missing");
                     let chunk_bytes = algo.chunk_bytes(bytes, host_nodes.len());
+                    let (tcp_cfg, dctcp_cfg) = step_transport_cfgs(&step, tcp_cfg, dctcp_cfg);
                     let transport: Box<dyn RingTransport> = match protocol {
                         TransportProtocol::Tcp => Box::new(TcpRingTransport { cfg: tcp_cfg }),
                         TransportProtocol::Dctcp => Box::new(DctcpRingTransport { cfg: dctcp_cfg }),
@@ -810,7 +1483,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                         let done_state = Arc::clone(&state);
                         let done_hosts = hosts.clone();
                         let done_comm_stream = comm_stream;
+                        let done_comm_id = comm_id.clone().unwrap_or_default();
                         Some(Box::new(move |now, sim| {
+                            release_nic_streams(&done_state, &done_hosts, done_comm_stream, sim);
+                            let wake_at = SimTime(now.0.saturating_add(decompress_ns));
                             let mut wake = Vec::new();
                             {
                                 let mut st = done_state.lock().expect("rank workload state lock");
@@ -830,6 +1506,16 @@ impl htsim_rs::sim::Event for StartRankStep {
                                     if remove_stream {
                                         rank_state.pending_async_by_stream.remove(&done_comm_stream);
                                     }
+                                    let mut remove_comm_id = false;
+                                    if let Some(counter) =
+                                        rank_state.pending_async_by_comm_id.get_mut(&done_comm_id)
+                                    {
+                                        *counter = counter.saturating_sub(1);
+                                        remove_comm_id = *counter == 0;
+                                    }
+                                    if remove_comm_id {
+                                        rank_state.pending_async_by_comm_id.remove(&done_comm_id);
+                                    }
                                     let should_wake = match rank_state.waiting_for_async {
                                         AsyncWaitKind::None => false,
                                         AsyncWaitKind::All => rank_state.pending_async_total == 0,
@@ -839,6 +1525,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                                             .copied()
                                             .unwrap_or(0)
                                             == 0,
+                                        AsyncWaitKind::Deps => rank_state
+                                            .steps
+                                            .get(rank_state.idx)
+                                            .is_none_or(|step| !has_pending_deps(step, rank_state)),
                                     };
                                     if should_wake {
                                         rank_state.waiting_for_async = AsyncWaitKind::None;
@@ -848,7 +1538,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                             }
                             for hid in wake {
                                 sim.schedule(
-                                    now,
+                                    wake_at,
                                     StartRankStep {
                                         rank_id: hid,
                                         state: Arc::clone(&done_state),
@@ -860,9 +1550,11 @@ impl htsim_rs::sim::Event for StartRankStep {
                         let done_state = Arc::clone(&state);
                         let done_hosts = hosts.clone();
                         Some(Box::new(move |now, sim| {
+                            release_nic_streams(&done_state, &done_hosts, comm_stream, sim);
+                            let wake_at = SimTime(now.0.saturating_add(decompress_ns));
                             for hid in &done_hosts {
                                 sim.schedule(
-                                    now,
+                                    wake_at,
                                     StartRankStep {
                                         rank_id: *hid,
                                         state: Arc::clone(&done_state),
@@ -883,6 +1575,14 @@ impl htsim_rs::sim::Event for StartRankStep {
                         start_flow_id,
                         transport,
                         done_cb,
+                        tags: collective_flow_tags(
+                            step.label.as_deref(),
+                            comm_id.as_deref(),
+                            op.as_deref(),
+                        ),
+                        direction: ring::RingDirection::Clockwise,
+                        rotation: 0,
+                        reduce_ns_per_byte: 0.0,
                     };
                     let handle = match algo {
                         CollectiveOp::Allreduce => {
@@ -894,8 +1594,15 @@ impl htsim_rs::sim::Event for StartRankStep {
                         CollectiveOp::Reducescatter => {
                             ring::start_ring_reducescatter_at(sim, cfg, sim.now())
                         }
-                        CollectiveOp::Alltoall => ring::start_ring_alltoall_at(sim, cfg, sim.now()),
+                        CollectiveOp::Alltoall => match alltoall_matrix {
+                            Some(m) => ring::start_ring_alltoallv_at(sim, cfg, m, sim.now()),
+                            None => ring::start_ring_alltoall_at(sim, cfg, sim.now()),
+                        },
                     };
+                    {
+                        let mut st = state.lock().expect("rank workload state lock");
+                        st.nic.admit(hosts.clone(), comm_stream, &handle);
+                    }
                     let record = CollectiveRecord {
                         step_id: step.id,
                         label: step.label.clone(),
@@ -910,6 +1617,64 @@ impl htsim_rs::sim::Event for StartRankStep {
                     }
                 }
             }
+            RankStepKind::Barrier => {
+                let comm_id = match step.comm_id.clone() {
+                    Some(id) => id,
+                    None => {
+                        sim.schedule(
+                            sim.now(),
+                            StartRankStep {
+                                rank_id,
+                                state: Arc::clone(&state),
+                            },
+                        );
+                        return;
+                    }
+                };
+                let hosts = step.hosts.clone().unwrap_or_else(|| hosts_all.clone());
+                if !hosts.contains(&rank_id) {
+                    panic!(
+                        "rank {} not included in barrier hosts for comm_id {:?}: hosts={:?}",
+                        rank_id, comm_id, hosts
+                    );
+                }
+
+                let release = {
+                    let mut st = state.lock().expect("rank workload state lock");
+                    let entry = st
+                        .pending_barriers
+                        .entry(comm_id.clone())
+                        .or_insert_with(|| BarrierWait {
+                            hosts: hosts.clone(),
+                            arrived: Vec::new(),
+                        });
+                    if entry.hosts != hosts {
+                        panic!(
+                            "comm_id {:?} barrier hosts mismatch: existing hosts={:?} vs new hosts={:?}",
+                            comm_id, entry.hosts, hosts
+                        );
+                    }
+                    if !entry.arrived.contains(&rank_id) {
+                        entry.arrived.push(rank_id);
+                    }
+                    if entry.arrived.len() == entry.hosts.len() {
+                        st.pending_barriers.remove(&comm_id).map(|b| b.hosts)
+                    } else {
+                        None
+                    }
+                };
+
+                // The last arrival releases every member at once; no flows are started.
+                for hid in release.into_iter().flatten() {
+                    sim.schedule(
+                        sim.now(),
+                        StartRankStep {
+                            rank_id: hid,
+                            state: Arc::clone(&state),
+                        },
+                    );
+                }
+            }
             RankStepKind::Sendrecv => {
                 let comm_id = match step.comm_id.clone() {
                     Some(id) => id,
@@ -933,9 +1698,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                     let mut st = state.lock().expect("rank workload state lock");
                     let entry = st
                         .pending_sendrecv
-                        .entry(comm_id.clone())
+                        .entry((comm_id.clone(), step.tag))
                         .or_insert_with(|| SendRecvWait {
                             comm_bytes,
+                            protocol: None,
                             sender: None,
                             receiver: None,
                             arrived: Vec::new(),
@@ -946,6 +1712,15 @@ impl htsim_rs::sim::Event for StartRankStep {
                             comm_id, entry.comm_bytes, comm_bytes
                         );
                     }
+                    if let Some(p) = step.protocol {
+                        match entry.protocol {
+                            Some(existing) if existing != p => panic!(
+                                "comm_id {:?} sendrecv protocol mismatch: existing {:?} vs new {:?}",
+                                comm_id, existing, p
+                            ),
+                            _ => entry.protocol = Some(p),
+                        }
+                    }
                     if !entry.arrived.contains(&rank_id) {
                         entry.arrived.push(rank_id);
                     }
@@ -1012,17 +1787,26 @@ impl htsim_rs::sim::Event for StartRankStep {
                         };
                         let entry = st
                             .pending_sendrecv
-                            .remove(&comm_id)
+                            .remove(&(comm_id.clone(), step.tag))
                             .expect("pending sendrecv missing");
                         let src = *st.host_map.get(&sender).expect("unknown host id");
                         let dst = *st.host_map.get(&receiver).expect("unknown host id");
                         let flow_id = st.next_flow_id;
                         st.next_flow_id = st.next_flow_id.saturating_add(1);
-                        start_cfg = Some((sender, receiver, entry.comm_bytes, flow_id, src, dst));
+                        let protocol = entry.protocol.unwrap_or(st.protocol);
+                        start_cfg = Some((
+                            sender,
+                            receiver,
+                            entry.comm_bytes,
+                            flow_id,
+                            src,
+                            dst,
+                            protocol,
+                        ));
                     }
                 }
 
-                if let Some((sender, receiver, bytes, flow_id, src, dst)) = start_cfg {
+                if let Some((sender, receiver, bytes, flow_id, src, dst, protocol)) = start_cfg {
                     let done_state = Arc::clone(&state);
                     let done_cb: ring::RingDoneCallback = Box::new(move |now, sim| {
                         for hid in [sender, receiver] {
@@ -1045,6 +1829,29 @@ impl htsim_rs::sim::Event for StartRankStep {
                     );
                 }
             }
+            RankStepKind::Rpc => {
+                let peer = step
+                    .peer
+                    .unwrap_or_else(|| panic!("rank {rank_id} rpc step needs a peer"));
+                let rpc_idx = {
+                    let mut st = state.lock().expect("rank workload state lock");
+                    if !st.host_map.contains_key(&peer) {
+                        panic!("rank {rank_id} rpc step has unknown peer {peer}");
+                    }
+                    st.rpcs.push(RpcRecord {
+                        rank: rank_id,
+                        peer,
+                        label: step.label.clone(),
+                        req_bytes: step.req_bytes.unwrap_or(0),
+                        resp_bytes: step.resp_bytes.unwrap_or(0),
+                        protocol,
+                        start_ns: sim.now().0,
+                        end_ns: None,
+                    });
+                    st.rpcs.len() - 1
+                };
+                start_rpc_leg(sim, w, &state, rpc_idx, false);
+            }
         }
     }
 }
@@ -1093,6 +1900,7 @@ fn build_topology(world: &mut NetWorld, topo: &TopologySpec) -> Vec<NodeId> {
                 k: *k as usize,
                 link_gbps: link_gbps.unwrap_or(100),
                 link_latency: SimTime::from_micros(link_latency_us.unwrap_or(2)),
+                oversubscription: 1,
             };
             let topo = build_fat_tree(world, &opts);
             topo.hosts
@@ -1100,14 +1908,42 @@ fn build_topology(world: &mut NetWorld, topo: &TopologySpec) -> Vec<NodeId> {
     }
 }
 
-fn resolve_hosts(
-    hosts: &[HostSpec],
-    topo_hosts: &[NodeId],
-) -> (
+/// Bottleneck link rate (Gbps) and one-way propagation of a ring step on `topo`,
+/// fed to `CollectiveOp::cost_model` for the analytical column of `--fct-stats`.
+///
+/// Fat-tree steps are charged the cross-pod path (six links), the worst case.
+fn topology_cost_params(topo: &TopologySpec) -> (f64, SimTime) {
+    match topo {
+        TopologySpec::Dumbbell {
+            host_link_gbps,
+            bottleneck_gbps,
+            link_latency_us,
+        } => {
+            let gbps = host_link_gbps
+                .unwrap_or(100)
+                .min(bottleneck_gbps.unwrap_or(10));
+            let latency = SimTime::from_micros(3 * link_latency_us.unwrap_or(2));
+            (gbps as f64, latency)
+        }
+        TopologySpec::FatTree {
+            link_gbps,
+            link_latency_us,
+            ..
+        } => {
+            let latency = SimTime::from_micros(6 * link_latency_us.unwrap_or(2));
+            (link_gbps.unwrap_or(100) as f64, latency)
+        }
+    }
+}
+
+/// Workload host ids, plus each host's topology node and GPU.
+type ResolvedHosts = (
     Vec<usize>,
     HashMap<usize, NodeId>,
-    HashMap<usize, Option<String>>,
-) {
+    HashMap<usize, Option<GpuSpec>>,
+);
+
+fn resolve_hosts(hosts: &[HostSpec], topo_hosts: &[NodeId]) -> ResolvedHosts {
     let mut host_ids = Vec::new();
     let mut host_map = HashMap::new();
     let mut gpu_map = HashMap::new();
@@ -1133,29 +1969,21 @@ fn resolve_hosts(
         }
         host_ids.push(h.id);
         host_map.insert(h.id, topo_hosts[topo_index]);
-        gpu_map.insert(h.id, h.gpu.as_ref().map(|g| g.model.clone()));
+        gpu_map.insert(h.id, h.gpu.clone());
     }
 
     host_ids.sort_unstable();
     (host_ids, host_map, gpu_map)
 }
 
-fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .with_file(true)
-        .with_line_number(true)
-        .with_target(true)
-        .init();
-
-    let args = Args::parse();
-    let raw = fs::read_to_string(&args.workload).expect("read workload.json");
-    let workload: WorkloadSpec = serde_json::from_str(&raw).expect("parse workload.json");
+struct WorkloadRun {
+    world: NetWorld,
+    collective_handles: Arc<Mutex<Vec<CollectiveRecord>>>,
+    rank_state_check: Option<Arc<Mutex<RankWorkloadState>>>,
+}
 
-    let mut sim = Simulator::default();
+/// Build the network and workload state described by `args`/`workload` and run `sim`.
+fn run_workload(args: &Args, workload: &WorkloadSpec, sim: &mut Simulator) -> WorkloadRun {
     let mut world = NetWorld::default();
 
     let topo_hosts = build_topology(&mut world, &workload.topology);
@@ -1182,6 +2010,17 @@ fn main() {
     world
         .net
         .set_host_egress_queue_capacity_bytes(host_queue_bytes);
+    if args.host_egress_flow_wrr {
+        for node in &topo_hosts {
+            world
+                .net
+                .set_egress_scheduler(*node, EgressScheduler::FlowWrr);
+        }
+    }
+    world
+        .net
+        .validate_queue_capacities()
+        .unwrap_or_else(|err| panic!("invalid queue configuration: {err}"));
 
     let defaults = workload.defaults.clone().unwrap_or(WorkloadDefaults {
         protocol: Some(TransportProtocol::Tcp),
@@ -1189,19 +2028,56 @@ fn main() {
         bytes_per_element: None,
     });
 
-    let protocol = parse_protocol(args.protocol, defaults.protocol);
-    let routing = parse_routing(args.routing, defaults.routing);
+    let protocol = parse_protocol(args.protocol.clone(), defaults.protocol);
+    let routing = parse_routing(args.routing.clone(), defaults.routing);
 
     world.net.set_ecmp_hash_mode(match routing {
         CcRoutingMode::PerFlow => EcmpHashMode::Flow,
         CcRoutingMode::PerPacket => EcmpHashMode::Packet,
     });
+    if let Some(seed) = args.ecmp_seed {
+        world.net.set_ecmp_seed(seed);
+    }
 
-    if args.viz_json.is_some() {
-        world.net.viz = Some(VizLogger::default());
+    let stream_viz = args.viz_format == VizFormat::Jsonl && args.viz_json.is_some();
+    let viz = if let (true, Some(path)) = (stream_viz, &args.viz_json) {
+        Some(VizLogger::jsonl(path).expect("create viz jsonl"))
+    } else if args.viz_json.is_some() {
+        Some(VizLogger::default())
+    } else if args.viz_throughput_json.is_some() {
+        // Only the throughput aggregate is wanted; keep no raw events.
+        Some(VizLogger::default().with_max_events(0))
+    } else {
+        None
+    };
+    if let Some(mut v) = viz {
+        if args.viz_throughput_json.is_some() {
+            v = v.with_throughput_bin(SimTime::from_micros(args.viz_throughput_bin_us));
+        }
+        if let Some(max) = args.viz_max_events {
+            v = v.with_max_events(max);
+        }
+        if !args.viz_flows.is_empty() {
+            v = v.with_flow_filter(args.viz_flows.iter().copied().collect());
+        }
+        if !args.viz_nodes.is_empty() {
+            v = v.with_node_filter(args.viz_nodes.iter().copied().collect());
+        }
+        world.net.viz = Some(v);
         world.net.emit_viz_meta();
     }
 
+    assert!(
+        args.collective_quorum > 0.0 && args.collective_quorum <= 1.0,
+        "--collective-quorum must be in (0, 1], got {}",
+        args.collective_quorum
+    );
+
+    let step_filter = StepFilter {
+        stop_at_label: args.stop_at_label.clone(),
+        only_labels: args.only_labels.clone(),
+    };
+
     let collective_handles = Arc::new(Mutex::new(Vec::new()));
     let mut rank_state_check: Option<Arc<Mutex<RankWorkloadState>>> = None;
 
@@ -1215,7 +2091,9 @@ fn main() {
                     idx: 0,
                     pending_async_total: 0,
                     pending_async_by_stream: HashMap::new(),
+                    pending_async_by_comm_id: HashMap::new(),
                     waiting_for_async: AsyncWaitKind::None,
+                    timeline: Vec::new(),
                 },
             );
         }
@@ -1230,8 +2108,14 @@ fn main() {
             tcp_cfg: default_tcp_cfg(),
             dctcp_cfg: DctcpConfig::default(),
             pending_collectives: HashMap::new(),
+            collective_quorum: args.collective_quorum,
+            late_collectives: HashMap::new(),
+            nic: NicStreams::new(args.nic_streams),
             pending_sendrecv: HashMap::new(),
+            pending_barriers: HashMap::new(),
+            rpcs: Vec::new(),
             collective_handles: Arc::clone(&collective_handles),
+            step_filter,
         }));
         rank_state_check = Some(Arc::clone(&state));
 
@@ -1256,6 +2140,7 @@ fn main() {
             tcp_cfg: default_tcp_cfg(),
             dctcp_cfg: DctcpConfig::default(),
             collective_handles: Arc::clone(&collective_handles),
+            step_filter,
         }));
 
         sim.schedule(
@@ -1273,7 +2158,109 @@ fn main() {
         sim.run(&mut world);
     }
 
-    if args.until_ms.is_none() {
+    WorkloadRun {
+        world,
+        collective_handles,
+        rank_state_check,
+    }
+}
+
+/// Collectives that launched but never finished, e.g. because a member flow was failed.
+fn stuck_collectives(list: &[CollectiveRecord]) -> Vec<String> {
+    list.iter()
+        .filter_map(|record| {
+            let stats = record.handle.stats();
+            if stats.done_at.is_some() {
+                return None;
+            }
+            Some(format!(
+                "step_id={:?} label={:?} comm_id={:?} op={:?} hosts={} pending_flows={:?}",
+                record.step_id,
+                record.label,
+                record.comm_id,
+                record.op,
+                record.hosts,
+                stats.pending_flows
+            ))
+        })
+        .collect()
+}
+
+/// Stats compared between the two runs of `--check-determinism`.
+fn run_digest(run: &WorkloadRun) -> String {
+    let mut out = format!("{:?}\n", run.world.net.stats);
+    if let Ok(list) = run.collective_handles.lock() {
+        for record in list.iter() {
+            let stats = record.handle.stats();
+            out.push_str(&format!(
+                "{:?} {:?} {:?} {:?} {:?}\n",
+                record.comm_id,
+                stats.start_at,
+                stats.done_at,
+                stats.reduce_done_at,
+                stats.flow_fct_ns
+            ));
+        }
+    }
+    if let Some(state) = &run.rank_state_check {
+        let st = state.lock().expect("rank workload state lock");
+        out.push_str(&timeline_csv(&timeline_rows(&st)));
+        for rpc in &st.rpcs {
+            out.push_str(&format!(
+                "rpc {} {} {} {:?}\n",
+                rpc.rank, rpc.peer, rpc.start_ns, rpc.end_ns
+            ));
+        }
+    }
+    out
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .with_file(true)
+        .with_line_number(true)
+        .with_target(true)
+        .init();
+
+    let args = Args::parse();
+    let raw = fs::read_to_string(&args.workload).expect("read workload.json");
+    let workload: WorkloadSpec = serde_json::from_str(&raw).expect("parse workload.json");
+
+    let (run, metrics) = if args.check_determinism {
+        let (sim, run) = check_determinism(|sim| {
+            let run = run_workload(&args, &workload, sim);
+            let digest = run_digest(&run);
+            (run, digest)
+        })
+        .unwrap_or_else(|err| panic!("determinism check failed: {err}"));
+        eprintln!(
+            "determinism check passed ({} events)",
+            sim.event_trace().len()
+        );
+        (run, sim.metrics())
+    } else {
+        let mut sim = Simulator::default();
+        let run = run_workload(&args, &workload, &mut sim);
+        (run, sim.metrics())
+    };
+    let WorkloadRun {
+        mut world,
+        collective_handles,
+        rank_state_check,
+    } = run;
+
+    if let Ok(list) = collective_handles.lock() {
+        for stuck in stuck_collectives(&list) {
+            eprintln!("stuck collective: {stuck}");
+        }
+    }
+
+    // Halting at a label legitimately leaves collectives unresolved.
+    if args.until_ms.is_none() && args.stop_at_label.is_none() {
         if let Some(state) = &rank_state_check {
             let st = state.lock().expect("rank workload state lock");
             if !st.pending_collectives.is_empty() {
@@ -1284,6 +2271,19 @@ fn main() {
                 let keys = st.pending_sendrecv.keys().cloned().collect::<Vec<_>>();
                 panic!("unresolved sendrecv at end of sim: {keys:?}");
             }
+            if !st.pending_barriers.is_empty() {
+                let keys = st.pending_barriers.keys().cloned().collect::<Vec<_>>();
+                panic!("unresolved barriers at end of sim: {keys:?}");
+            }
+            let pending_rpcs = st
+                .rpcs
+                .iter()
+                .filter(|rpc| rpc.end_ns.is_none())
+                .map(|rpc| (rpc.rank, rpc.peer))
+                .collect::<Vec<_>>();
+            if !pending_rpcs.is_empty() {
+                panic!("unresolved rpcs at end of sim: {pending_rpcs:?}");
+            }
             let pending_async = st
                 .ranks
                 .iter()
@@ -1302,6 +2302,7 @@ fn main() {
     }
 
     if args.fct_stats {
+        let (model_gbps, model_latency) = topology_cost_params(&workload.topology);
         if let Ok(list) = collective_handles.lock() {
             for record in list.iter() {
                 let stats = record.handle.stats();
@@ -1310,13 +2311,27 @@ fn main() {
                     .done_at
                     .map(|d| d.0.saturating_sub(start.0))
                     .unwrap_or(0);
-                let p99_ns = percentile_ns(&stats.flow_fct_ns, 0.99).unwrap_or(0);
+                let p99_ns = percentile(&stats.flow_fct_ns, 0.99).unwrap_or(0);
                 let max_flow_ns = stats.flow_fct_ns.iter().copied().max().unwrap_or(0);
                 let makespan_ms = fct_ns as f64 / 1_000_000.0;
                 let p99_ms = p99_ns as f64 / 1_000_000.0;
                 let max_flow_ms = max_flow_ns as f64 / 1_000_000.0;
+                let model_ms = record
+                    .op
+                    .as_deref()
+                    .and_then(|op| CollectiveOp::parse(op).ok())
+                    .map(|op| {
+                        let est = op.cost_model(
+                            record.hosts,
+                            record.comm_bytes,
+                            model_gbps,
+                            model_latency,
+                        );
+                        format!("{:.6}", est.time_ns as f64 / 1_000_000.0)
+                    })
+                    .unwrap_or_else(|| "-".to_string());
                 println!(
-                    "collective_fct step_id={:?} label={:?} comm_id={:?} op={:?} hosts={} comm_bytes={} makespan_ms={:.6} p99_flow_fct_ms={:.6} max_flow_fct_ms={:.6} flows={}",
+                    "collective_fct step_id={:?} label={:?} comm_id={:?} op={:?} hosts={} comm_bytes={} makespan_ms={:.6} model_ms={} p99_flow_fct_ms={:.6} max_flow_fct_ms={:.6} flows={}",
                     record.step_id,
                     record.label,
                     record.comm_id,
@@ -1324,21 +2339,84 @@ fn main() {
                     record.hosts,
                     record.comm_bytes,
                     makespan_ms,
+                    model_ms,
                     p99_ms,
                     max_flow_ms,
                     stats.flow_fct_ns.len()
                 );
             }
         }
+        if let Some(state) = &rank_state_check {
+            let st = state.lock().expect("rank workload state lock");
+            for rpc in &st.rpcs {
+                let Some(end_ns) = rpc.end_ns else {
+                    continue;
+                };
+                let latency_ms = end_ns.saturating_sub(rpc.start_ns) as f64 / 1_000_000.0;
+                println!(
+                    "rpc_latency rank={} peer={} label={:?} req_bytes={} resp_bytes={} latency_ms={:.6}",
+                    rpc.rank, rpc.peer, rpc.label, rpc.req_bytes, rpc.resp_bytes, latency_ms
+                );
+            }
+        }
+    }
+
+    if let Some(path) = &args.fct_csv {
+        let rows = collective_handles
+            .lock()
+            .map(|list| fct_rows(&list))
+            .unwrap_or_default();
+        fs::write(path, fct_csv(&rows)).expect("write fct csv");
+        eprintln!("wrote collective fct stats to {}", path.display());
+    }
+
+    if args.timeline_json.is_some() || args.timeline_csv.is_some() {
+        let rows = match &rank_state_check {
+            Some(state) => timeline_rows(&state.lock().expect("rank workload state lock")),
+            None => Vec::new(),
+        };
+        if let Some(path) = &args.timeline_json {
+            let json = serde_json::to_string_pretty(&rows).expect("serialize timeline");
+            fs::write(path, json).expect("write timeline json");
+            eprintln!("wrote rank timeline to {}", path.display());
+        }
+        if let Some(path) = &args.timeline_csv {
+            fs::write(path, timeline_csv(&rows)).expect("write timeline csv");
+            eprintln!("wrote rank timeline to {}", path.display());
+        }
+    }
+
+    if let (Some(path), Some(v)) = (&args.viz_throughput_json, &world.net.viz) {
+        let series = v.throughput_series(SimTime::from_micros(args.viz_throughput_bin_us));
+        let json = serde_json::to_string_pretty(&series).expect("serialize throughput series");
+        fs::write(path, json).expect("write throughput json");
+        eprintln!("wrote throughput series to {}", path.display());
     }
 
     if let Some(path) = args.viz_json {
-        if let Some(v) = world.net.viz.take() {
-            let json = serde_json::to_string_pretty(&v.events).expect("serialize viz events");
-            fs::write(&path, json).expect("write viz json");
+        if let Some(mut v) = world.net.viz.take() {
+            match args.viz_format {
+                VizFormat::Json => {
+                    let json =
+                        serde_json::to_string_pretty(&v.events).expect("serialize viz events");
+                    fs::write(&path, json).expect("write viz json");
+                }
+                VizFormat::Jsonl => v.flush().expect("write viz jsonl"),
+                VizFormat::Csv => v.write_csv(&path).expect("write viz csv"),
+            }
             eprintln!("wrote viz events to {}", path.display());
+            if v.is_truncated() {
+                eprintln!(
+                    "viz events truncated after {} (--viz-max-events)",
+                    v.recorded()
+                );
+            }
         }
     }
+
+    if args.sim_stats {
+        println!("sim_stats {metrics}");
+    }
 }
 
 #[cfg(test)]
@@ -1346,9 +2424,14 @@ mod tests {
     use super::*;
 
     fn build_two_rank_dumbbell_world() -> (NetWorld, Vec<usize>, HashMap<usize, NodeId>) {
+        build_two_rank_dumbbell_world_with(&DumbbellOpts::default())
+    }
+
+    fn build_two_rank_dumbbell_world_with(
+        opts: &DumbbellOpts,
+    ) -> (NetWorld, Vec<usize>, HashMap<usize, NodeId>) {
         let mut world = NetWorld::default();
-        let opts = DumbbellOpts::default();
-        let (h0, h1, _route) = build_dumbbell(&mut world, &opts);
+        let (h0, h1, _route) = build_dumbbell(&mut world, opts);
 
         // Keep queues large to avoid drops that would add variability to timings.
         world
@@ -1367,45 +2450,94 @@ mod tests {
         (world, host_ids, host_map)
     }
 
-    fn run_two_rank_workload(
-        steps0: Vec<RankStepSpec>,
-        steps1: Vec<RankStepSpec>,
-    ) -> (
+    type RankRun = (
         Simulator,
         NetWorld,
         Arc<Mutex<RankWorkloadState>>,
         Arc<Mutex<Vec<CollectiveRecord>>>,
-    ) {
-        let mut sim = Simulator::default();
-        let (mut world, host_ids, host_map) = build_two_rank_dumbbell_world();
+    );
+
+    fn run_two_rank_workload(steps0: Vec<RankStepSpec>, steps1: Vec<RankStepSpec>) -> RankRun {
+        run_two_rank_workload_with_filter(steps0, steps1, StepFilter::default())
+    }
+
+    fn run_two_rank_workload_with_filter(
+        steps0: Vec<RankStepSpec>,
+        steps1: Vec<RankStepSpec>,
+        step_filter: StepFilter,
+    ) -> RankRun {
+        let (world, host_ids, host_map) = build_two_rank_dumbbell_world();
+        run_rank_workload(
+            world,
+            host_ids,
+            host_map,
+            vec![steps0, steps1],
+            step_filter,
+            1.0,
+            None,
+            None,
+        )
+    }
 
-        let mut gpu_map = HashMap::new();
-        gpu_map.insert(0, None);
-        gpu_map.insert(1, None);
+    /// Run one step list per rank; rank `i` gets `steps[i]` and host `host_ids[i]`.
+    #[allow(clippy::too_many_arguments)]
+    fn run_rank_workload(
+        world: NetWorld,
+        host_ids: Vec<usize>,
+        host_map: HashMap<usize, NodeId>,
+        steps: Vec<Vec<RankStepSpec>>,
+        step_filter: StepFilter,
+        collective_quorum: f64,
+        nic_streams: Option<usize>,
+        until: Option<SimTime>,
+    ) -> RankRun {
+        let gpu_map = host_ids.iter().map(|hid| (*hid, None)).collect();
+        run_rank_workload_on_gpus(
+            world,
+            host_ids,
+            host_map,
+            gpu_map,
+            steps,
+            step_filter,
+            collective_quorum,
+            nic_streams,
+            until,
+        )
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    fn run_rank_workload_on_gpus(
+        mut world: NetWorld,
+        host_ids: Vec<usize>,
+        host_map: HashMap<usize, NodeId>,
+        gpu_map: HashMap<usize, Option<GpuSpec>>,
+        steps: Vec<Vec<RankStepSpec>>,
+        step_filter: StepFilter,
+        collective_quorum: f64,
+        nic_streams: Option<usize>,
+        until: Option<SimTime>,
+    ) -> RankRun {
+        let mut sim = Simulator::default();
         let collective_handles = Arc::new(Mutex::new(Vec::new()));
 
-        let mut ranks = HashMap::new();
-        ranks.insert(
-            0,
-            RankState {
-                steps: steps0,
-                idx: 0,
-                pending_async_total: 0,
-                pending_async_by_stream: HashMap::new(),
-                waiting_for_async: AsyncWaitKind::None,
-            },
-        );
-        ranks.insert(
-            1,
-            RankState {
-                steps: steps1,
-                idx: 0,
-                pending_async_total: 0,
-                pending_async_by_stream: HashMap::new(),
-                waiting_for_async: AsyncWaitKind::None,
-            },
-        );
+        let ranks = host_ids
+            .iter()
+            .zip(steps)
+            .map(|(hid, steps)| {
+                (
+                    *hid,
+                    RankState {
+                        steps,
+                        idx: 0,
+                        pending_async_total: 0,
+                        pending_async_by_stream: HashMap::new(),
+                        pending_async_by_comm_id: HashMap::new(),
+                        waiting_for_async: AsyncWaitKind::None,
+                        timeline: Vec::new(),
+                    },
+                )
+            })
+            .collect();
 
         let state = Arc::new(Mutex::new(RankWorkloadState {
             ranks,
@@ -1418,8 +2550,14 @@ mod tests {
             tcp_cfg: default_tcp_cfg(),
             dctcp_cfg: DctcpConfig::default(),
             pending_collectives: HashMap::new(),
+            collective_quorum,
+            late_collectives: HashMap::new(),
+            nic: NicStreams::new(nic_streams),
             pending_sendrecv: HashMap::new(),
+            pending_barriers: HashMap::new(),
+            rpcs: Vec::new(),
             collective_handles: Arc::clone(&collective_handles),
+            step_filter,
         }));
 
         for rank_id in host_ids {
@@ -1432,7 +2570,10 @@ mod tests {
             );
         }
 
-        sim.run(&mut world);
+        match until {
+            Some(t) => sim.run_until(t, &mut world),
+            None => sim.run(&mut world),
+        }
 
         (sim, world, state, collective_handles)
     }
@@ -1462,12 +2603,23 @@ mod tests {
             kind: Some(RankStepKind::Collective),
             op: Some(op.to_string()),
             compute_ms: None,
+            flops: None,
             comm_bytes: Some(comm_bytes),
             comm_id: Some(comm_id.to_string()),
             comm_stream: None,
+            depends_on: Vec::new(),
             hosts: Some(vec![0, 1]),
             peer: None,
             direction: None,
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }
     }
 
@@ -1478,12 +2630,23 @@ mod tests {
             kind: Some(RankStepKind::Compute),
             op: None,
             compute_ms: Some(compute_ms),
+            flops: None,
             comm_bytes: None,
             comm_id: None,
             comm_stream: None,
+            depends_on: Vec::new(),
             hosts: None,
             peer: None,
             direction: None,
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }
     }
 
@@ -1494,12 +2657,50 @@ mod tests {
             kind: Some(RankStepKind::CollectiveWait),
             op: None,
             compute_ms: None,
+            flops: None,
             comm_bytes: None,
             comm_id: None,
             comm_stream: None,
+            depends_on: Vec::new(),
+            hosts: None,
+            peer: None,
+            direction: None,
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
+        }
+    }
+
+    fn step_barrier(comm_id: &str) -> RankStepSpec {
+        RankStepSpec {
+            id: None,
+            label: Some(format!("{comm_id}:barrier")),
+            kind: Some(RankStepKind::Barrier),
+            op: None,
+            compute_ms: None,
+            flops: None,
+            comm_bytes: None,
+            comm_id: Some(comm_id.to_string()),
+            comm_stream: None,
+            depends_on: Vec::new(),
             hosts: None,
             peer: None,
             direction: None,
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }
     }
 
@@ -1515,12 +2716,50 @@ mod tests {
             kind: Some(RankStepKind::Sendrecv),
             op: None,
             compute_ms: None,
+            flops: None,
             comm_bytes: Some(comm_bytes),
             comm_id: Some(comm_id.to_string()),
             comm_stream: None,
+            depends_on: Vec::new(),
             hosts: None,
             peer,
             direction: Some(direction),
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
+        }
+    }
+
+    fn step_rpc(label: &str, peer: usize, req_bytes: u64, resp_bytes: u64) -> RankStepSpec {
+        RankStepSpec {
+            id: None,
+            label: Some(label.to_string()),
+            kind: Some(RankStepKind::Rpc),
+            op: None,
+            compute_ms: None,
+            flops: None,
+            comm_bytes: None,
+            comm_id: None,
+            comm_stream: None,
+            depends_on: Vec::new(),
+            hosts: None,
+            peer: Some(peer),
+            direction: None,
+            tag: None,
+            req_bytes: Some(req_bytes),
+            resp_bytes: Some(resp_bytes),
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }
     }
 
@@ -1582,17 +2821,99 @@ mod tests {
     }
 
     #[test]
-    fn async_collective_allows_following_collective_to_overlap() {
-        let steps = vec![
-            // Large async collective, so the follow-up collective has a chance to overlap.
-            step_collective("allreduce_async", 1_000_000, "c0"),
-            step_collective("allgather", 1, "c1"),
-        ];
-        let (_sim, _world, _state, handles) = run_two_rank_workload(steps.clone(), steps.clone());
-
-        let list = handles.lock().expect("handles lock");
-        assert_eq!(list.len(), 2);
-
+    fn flops_compute_step_scales_with_gpu_peak_flops() {
+        let (world, host_ids, host_map) = build_two_rank_dumbbell_world();
+        let (slow_node, fast_node) = (host_map[&0].0, host_map[&1].0);
+        let gpu = |model: &str, peak_flops: f64| {
+            Some(GpuSpec {
+                model: model.to_string(),
+                peak_flops: Some(peak_flops),
+            })
+        };
+        let gpu_map = HashMap::from([(0, gpu("slow", 100e12)), (1, gpu("fast", 400e12))]);
+        // Same work on both ranks; compute_ms is ignored once flops can be timed.
+        let step = RankStepSpec {
+            flops: Some(2e12),
+            ..step_compute("fwd", 50.0)
+        };
+        let (_sim, world, _state, _handles) = run_rank_workload_on_gpus(
+            world,
+            host_ids,
+            host_map,
+            gpu_map,
+            vec![vec![step.clone()], vec![step]],
+            StepFilter::default(),
+            1.0,
+            None,
+            None,
+        );
+
+        let busy = gpu_busy_events(&world);
+        let duration = |node: usize| {
+            busy.iter()
+                .find(|(_, n, _, _)| *n == node)
+                .map(|(_, _, dur_ns, _)| *dur_ns)
+                .expect("missing GpuBusy event")
+        };
+        // 2 TFLOP at 100 and 400 TFLOP/s.
+        assert_eq!(duration(slow_node), 20_000_000);
+        assert_eq!(duration(fast_node), 5_000_000);
+    }
+
+    #[test]
+    fn flops_compute_step_falls_back_to_compute_ms_without_peak_flops() {
+        let step = RankStepSpec {
+            flops: Some(2e12),
+            ..step_compute("fwd", 1.5)
+        };
+        let (_sim, world, _state, _handles) = run_two_rank_workload(vec![step.clone()], vec![step]);
+
+        let busy = gpu_busy_events(&world);
+        assert_eq!(busy.len(), 2);
+        assert!(busy.iter().all(|(_, _, dur_ns, _)| *dur_ns == 1_500_000));
+    }
+
+    #[test]
+    fn flops_compute_step_falls_back_to_compute_ms_when_flops_cannot_be_timed() {
+        let gpu = |peak_flops: f64| GpuSpec {
+            model: "gpu".to_string(),
+            peak_flops: Some(peak_flops),
+        };
+        let step = |flops: f64| RankStepSpec {
+            flops: Some(flops),
+            ..step_compute("fwd", 1.5)
+        };
+        for (flops, peak_flops) in [
+            (0.0, 100e12),
+            (-2e12, 100e12),
+            (f64::NAN, 100e12),
+            (2e12, 0.0),
+            (f64::MAX, 1e-300),
+        ] {
+            assert_eq!(
+                rank_compute_duration_ns(&step(flops), Some(&gpu(peak_flops))),
+                1_500_000,
+                "flops={flops} peak_flops={peak_flops}"
+            );
+        }
+        assert_eq!(
+            rank_compute_duration_ns(&step(2e12), Some(&gpu(100e12))),
+            20_000_000
+        );
+    }
+
+    #[test]
+    fn async_collective_allows_following_collective_to_overlap() {
+        let steps = vec![
+            // Large async collective, so the follow-up collective has a chance to overlap.
+            step_collective("allreduce_async", 1_000_000, "c0"),
+            step_collective("allgather", 1, "c1"),
+        ];
+        let (_sim, _world, _state, handles) = run_two_rank_workload(steps.clone(), steps.clone());
+
+        let list = handles.lock().expect("handles lock");
+        assert_eq!(list.len(), 2);
+
         let mut by_id = HashMap::new();
         for record in list.iter() {
             let id = record.comm_id.clone().expect("comm_id missing");
@@ -1610,6 +2931,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compute_depends_on_starts_when_named_async_collective_completes() {
+        let steps = vec![
+            step_collective("allreduce_async", 1_000_000, "c0"),
+            step_collective("allreduce_async", 10_000, "c1"),
+            RankStepSpec {
+                depends_on: vec!["c1".to_string()],
+                ..step_compute("after_c1", 0.001)
+            },
+            step_wait("wait_all"),
+        ];
+        let (_sim, world, _state, handles) = run_two_rank_workload(steps.clone(), steps);
+
+        let list = handles.lock().expect("handles lock");
+        let done_at = |comm_id: &str| {
+            list.iter()
+                .find(|record| record.comm_id.as_deref() == Some(comm_id))
+                .and_then(|record| record.handle.stats().done_at)
+                .expect("collective done_at missing")
+        };
+        let (c0_done, c1_done) = (done_at("c0"), done_at("c1"));
+        assert!(
+            c1_done < c0_done,
+            "expected the small collective to finish first"
+        );
+
+        let busy = gpu_busy_events(&world)
+            .into_iter()
+            .filter(|(_, _, _, label)| label.as_deref() == Some("after_c1"))
+            .collect::<Vec<_>>();
+        assert_eq!(busy.len(), 2);
+        for (t_ns, _, _, _) in busy {
+            assert_eq!(t_ns, c1_done.0);
+        }
+    }
+
     #[test]
     fn async_collective_blocks_following_collective_on_same_comm_stream() {
         let mut c0 = step_collective("allreduce_async", 1_000_000, "c0");
@@ -1639,6 +2996,69 @@ mod tests {
         );
     }
 
+    /// Two async allreduces on streams 0 and 1, then a wait. Returns (c0, c1) stats.
+    fn run_two_stream_async_allreduces(
+        nic_streams: Option<usize>,
+    ) -> (ring::RingAllreduceStats, ring::RingAllreduceStats) {
+        let mut c0 = step_collective("allreduce_async", 1_000_000, "c0");
+        c0.comm_stream = Some(0);
+        let mut c1 = step_collective("allreduce_async", 1_000_000, "c1");
+        c1.comm_stream = Some(1);
+        let steps = vec![c0, c1, step_wait("wait")];
+        let (world, host_ids, host_map) = build_two_rank_dumbbell_world();
+        let (_sim, _world, state, handles) = run_rank_workload(
+            world,
+            host_ids,
+            host_map,
+            vec![steps.clone(), steps],
+            StepFilter::default(),
+            1.0,
+            nic_streams,
+            None,
+        );
+
+        // The executor lets both collectives launch at t=0 either way.
+        let st = state.lock().expect("state lock");
+        for rank_state in st.ranks.values() {
+            assert_eq!(rank_state.idx, 3);
+            assert_eq!(rank_state.timeline[1].start_ns, 0);
+        }
+        assert!(st.nic.waiting.is_empty());
+        drop(st);
+
+        let list = handles.lock().expect("handles lock");
+        let stats = |id: &str| {
+            list.iter()
+                .find(|r| r.comm_id.as_deref() == Some(id))
+                .expect("missing collective record")
+                .handle
+                .stats()
+        };
+        (stats("c0"), stats("c1"))
+    }
+
+    #[test]
+    fn nic_stream_limit_serializes_collectives_on_different_streams() {
+        let (c0, c1) = run_two_stream_async_allreduces(None);
+        let c0_done = c0.done_at.expect("c0 done_at missing");
+        assert_eq!(c1.start_at, Some(SimTime::ZERO));
+        assert!(c1.start_at.unwrap() < c0_done, "expected overlap");
+
+        // One stream per NIC: c1 only reaches the network once c0 is done.
+        let (c0, c1) = run_two_stream_async_allreduces(Some(1));
+        let c0_done = c0.done_at.expect("c0 done_at missing");
+        assert_eq!(c0.start_at, Some(SimTime::ZERO));
+        assert_eq!(c1.start_at, Some(c0_done));
+        assert_eq!(c1.paused_ns, 0);
+        let c1_done = c1.done_at.expect("c1 done_at missing");
+        assert!(c1_done > c0_done);
+        assert_eq!(c1.makespan_ns(), Some(c1_done.0 - c0_done.0));
+
+        // Two slots are enough for both streams.
+        let (_c0, c1) = run_two_stream_async_allreduces(Some(2));
+        assert_eq!(c1.start_at, Some(SimTime::ZERO));
+    }
+
     #[test]
     fn collective_wait_is_noop_without_pending_async() {
         let steps = vec![
@@ -1764,6 +3184,46 @@ mod tests {
         let _ = run_two_rank_workload(rank0, rank1);
     }
 
+    #[test]
+    fn barrier_releases_both_ranks_at_the_later_arrival() {
+        let rank0 = vec![
+            step_compute("before", 0.001),
+            step_barrier("b0"),
+            step_compute("after", 0.001),
+        ];
+        let rank1 = vec![
+            step_compute("before", 0.005),
+            step_barrier("b0"),
+            step_compute("after", 0.001),
+        ];
+        let (_sim, world, state, handles) = run_two_rank_workload(rank0, rank1);
+
+        let later_arrival = compute_duration_ns_from_ms(0.005);
+        let after = gpu_busy_events(&world)
+            .into_iter()
+            .filter(|(_, _, _, label)| label.as_deref() == Some("after"))
+            .collect::<Vec<_>>();
+        assert_eq!(after.len(), 2, "expected one after-compute per rank");
+        for (t_ns, node, _dur_ns, _) in &after {
+            assert!(matches!(*node, 0 | 1));
+            assert_eq!(*t_ns, later_arrival);
+        }
+
+        // A barrier moves no data: no collective handle, no flows.
+        assert!(handles.lock().expect("handles lock").is_empty());
+        let st = state.lock().expect("state lock");
+        assert!(st.pending_barriers.is_empty());
+        assert_eq!(st.next_flow_id, 1);
+        let barrier_ends = st
+            .ranks
+            .values()
+            .flat_map(|rs| rs.timeline.iter())
+            .filter(|row| matches!(row.kind, RankStepKind::Barrier))
+            .map(|row| row.end_ns)
+            .collect::<Vec<_>>();
+        assert_eq!(barrier_ends, vec![Some(later_arrival); 2]);
+    }
+
     #[test]
     fn sendrecv_completes_and_unblocks_both_ranks_when_both_arrive() {
         let rank0 = vec![
@@ -1793,6 +3253,167 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rpc_waits_for_request_and_response_and_records_latency() {
+        const BYTES: u64 = 10_000;
+        // One-way reference: the same payload as a plain sendrecv, which releases
+        // the sender once the data has fully arrived.
+        let (_sim, _world, state, _handles) = run_two_rank_workload(
+            vec![
+                step_sendrecv("p0", SendRecvDirection::Send, Some(1), BYTES),
+                step_compute("after", 0.001),
+            ],
+            vec![step_sendrecv("p0", SendRecvDirection::Recv, Some(0), BYTES)],
+        );
+        let one_way_ns = {
+            let st = state.lock().expect("state lock");
+            st.ranks[&0].timeline[0].end_ns.expect("sendrecv end")
+        };
+
+        // Rank 1 serves the RPC without any steps of its own.
+        let (_sim, _world, state, _handles) = run_two_rank_workload(
+            vec![
+                step_rpc("get", 1, BYTES, BYTES),
+                step_compute("after", 0.001),
+            ],
+            Vec::new(),
+        );
+        let st = state.lock().expect("state lock");
+        assert_eq!(st.rpcs.len(), 1);
+        let rpc = &st.rpcs[0];
+        assert_eq!(
+            (rpc.rank, rpc.peer, rpc.label.as_deref()),
+            (0, 1, Some("get"))
+        );
+        let latency_ns = rpc.end_ns.expect("rpc never completed") - rpc.start_ns;
+        assert_eq!(
+            st.ranks[&0].timeline[0].end_ns, rpc.end_ns,
+            "the caller should only continue once the response arrived"
+        );
+        assert_eq!(
+            st.next_flow_id, 3,
+            "expected one request and one response flow"
+        );
+
+        // Two legs of 10KB over the 10Gbps bottleneck and three 2us hops each.
+        let propagation_ns = 3 * DumbbellOpts::default().link_latency.0;
+        let transfer_ns = BYTES * 8 / 10;
+        assert!(
+            latency_ns >= 2 * (transfer_ns + propagation_ns),
+            "rpc latency {latency_ns}ns"
+        );
+        let ratio = latency_ns as f64 / (2 * one_way_ns) as f64;
+        assert!(
+            (0.9..=1.1).contains(&ratio),
+            "rpc latency {latency_ns}ns vs one-way sendrecv {one_way_ns}ns"
+        );
+    }
+
+    #[test]
+    fn collective_init_cwnd_override_skips_slow_start() {
+        const BYTES: u64 = 4_000_000;
+        let allreduce_end = |init_cwnd_bytes| {
+            // 100us links make the RTT ~0.6ms, so slow start from the default
+            // 10-MSS window takes several round trips to fill the 10Gbps pipe.
+            let (world, host_ids, host_map) = build_two_rank_dumbbell_world_with(&DumbbellOpts {
+                link_latency: SimTime::from_micros(100),
+                ..DumbbellOpts::default()
+            });
+            let step = RankStepSpec {
+                init_cwnd_bytes,
+                ..step_collective("allreduce", BYTES, "c0")
+            };
+            let (_sim, _world, state, _handles) = run_rank_workload(
+                world,
+                host_ids,
+                host_map,
+                vec![vec![step.clone()], vec![step]],
+                StepFilter::default(),
+                1.0,
+                None,
+                None,
+            );
+            let st = state.lock().expect("state lock");
+            st.ranks[&0].timeline[0].end_ns.expect("allreduce end")
+        };
+
+        let default_ns = allreduce_end(None);
+        let iw_ns = allreduce_end(Some(BYTES));
+        // Two ring steps of a 2MB chunk take 3.2ms at line rate; with the
+        // whole chunk in the first window only the round trips remain on top.
+        assert!(
+            iw_ns < default_ns / 2,
+            "init cwnd {iw_ns}ns vs default {default_ns}ns"
+        );
+        assert!(iw_ns < 3_200_000 + 2_000_000, "init cwnd {iw_ns}ns");
+    }
+
+    fn tagged(step: RankStepSpec, tag: u64) -> RankStepSpec {
+        RankStepSpec {
+            tag: Some(tag),
+            ..step
+        }
+    }
+
+    #[test]
+    fn tagged_sendrecv_pairs_on_one_comm_id_match_by_tag() {
+        // Two transfers of different sizes share comm_id "p0" between the same
+        // two ranks, one in each direction; the tag decides which send a recv pairs with.
+        let p0 = |direction, peer, bytes, tag| {
+            tagged(step_sendrecv("p0", direction, Some(peer), bytes), tag)
+        };
+        let rank0 = vec![
+            p0(SendRecvDirection::Send, 1, 10_000, 1),
+            p0(SendRecvDirection::Recv, 1, 30_000, 2),
+        ];
+        let rank1 = vec![
+            p0(SendRecvDirection::Recv, 0, 10_000, 1),
+            p0(SendRecvDirection::Send, 0, 30_000, 2),
+        ];
+
+        let (_sim, world, state, _handles) = run_two_rank_workload(rank0, rank1);
+
+        let st = state.lock().expect("state lock");
+        assert!(st.pending_sendrecv.is_empty());
+        assert_eq!(st.next_flow_id, 3, "expected one flow per tag");
+        let delivered = |flow_id| {
+            world
+                .net
+                .flow_stats(flow_id)
+                .expect("sendrecv flow stats")
+                .bytes_delivered
+        };
+        assert_eq!(delivered(1), 10_000);
+        assert_eq!(delivered(2), 30_000);
+        let ends = |rank: usize| {
+            st.ranks[&rank]
+                .timeline
+                .iter()
+                .map(|row| row.end_ns.expect("sendrecv end"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ends(0), ends(1));
+    }
+
+    #[test]
+    fn tagged_send_does_not_match_untagged_recv() {
+        let rank0 = vec![tagged(
+            step_sendrecv("p0", SendRecvDirection::Send, Some(1), 1),
+            1,
+        )];
+        let rank1 = vec![step_sendrecv("p0", SendRecvDirection::Recv, Some(0), 1)];
+        let (_sim, _world, state, _handles) = run_two_rank_workload(rank0, rank1);
+
+        let st = state.lock().expect("state lock");
+        let mut keys = st.pending_sendrecv.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![("p0".to_string(), None), ("p0".to_string(), Some(1))]
+        );
+        assert_eq!(st.next_flow_id, 1);
+    }
+
     #[test]
     #[should_panic]
     fn sendrecv_comm_bytes_mismatch_panics() {
@@ -1809,4 +3430,416 @@ mod tests {
         let rank1 = vec![step_sendrecv("p0", SendRecvDirection::Send, Some(0), 1)];
         let _ = run_two_rank_workload(rank0, rank1);
     }
+
+    #[test]
+    fn sendrecv_protocol_override_mixes_tcp_and_dctcp_flows() {
+        const BYTES: u64 = 200_000;
+        let with_protocol = |step: RankStepSpec, protocol| RankStepSpec {
+            protocol: Some(protocol),
+            ..step
+        };
+        // Rank 0 -> 1 over TCP and rank 2 -> 3 over DCTCP, concurrently; the
+        // recv sides leave the protocol to their sender.
+        let steps = vec![
+            vec![with_protocol(
+                step_sendrecv("p0", SendRecvDirection::Send, Some(1), BYTES),
+                TransportProtocol::Tcp,
+            )],
+            vec![step_sendrecv("p0", SendRecvDirection::Recv, Some(0), BYTES)],
+            vec![with_protocol(
+                step_sendrecv("p1", SendRecvDirection::Send, Some(3), BYTES),
+                TransportProtocol::Dctcp,
+            )],
+            vec![step_sendrecv("p1", SendRecvDirection::Recv, Some(2), BYTES)],
+        ];
+        let (_sim, world, state, _handles) = run_fat_tree_rank_workload(steps, 1.0);
+
+        let st = state.lock().expect("state lock");
+        assert!(st.pending_sendrecv.is_empty());
+        assert_eq!(st.next_flow_id, 3);
+        let tcp = world.net.tcp.get(1).expect("p0 should run over tcp");
+        let dctcp = world.net.dctcp.get(2).expect("p1 should run over dctcp");
+        assert!(world.net.dctcp.get(1).is_none() && world.net.tcp.get(2).is_none());
+        assert!(tcp.is_done() && dctcp.is_done());
+        assert_eq!(tcp.start_time(), Some(SimTime::ZERO));
+        assert_eq!(dctcp.start_time(), Some(SimTime::ZERO));
+        for (flow_id, ranks) in [(1, [0, 1]), (2, [2, 3])] {
+            let stats = world.net.flow_stats(flow_id).expect("flow stats");
+            assert_eq!(stats.bytes_delivered, BYTES);
+            for rank in ranks {
+                assert_eq!(
+                    st.ranks[&rank].timeline[0].end_ns,
+                    stats.completion.map(|t| t.0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "sendrecv protocol mismatch")]
+    fn sendrecv_protocol_mismatch_panics() {
+        let rank0 = vec![RankStepSpec {
+            protocol: Some(TransportProtocol::Tcp),
+            ..step_sendrecv("p0", SendRecvDirection::Send, Some(1), 1)
+        }];
+        let rank1 = vec![RankStepSpec {
+            protocol: Some(TransportProtocol::Dctcp),
+            ..step_sendrecv("p0", SendRecvDirection::Recv, Some(0), 1)
+        }];
+        let _ = run_two_rank_workload(rank0, rank1);
+    }
+
+    #[test]
+    fn stop_at_label_prevents_later_steps_from_generating_flows() {
+        let steps = vec![
+            step_compute("warmup", 0.001),
+            step_collective("allreduce", 1000, "c0"),
+            step_compute("halt_here", 0.001),
+            step_collective("allgather", 1000, "c1"),
+        ];
+        let filter = StepFilter {
+            stop_at_label: Some("halt_here".to_string()),
+            only_labels: Vec::new(),
+        };
+        let (_sim, world, state, handles) =
+            run_two_rank_workload_with_filter(steps.clone(), steps, filter);
+
+        let list = handles.lock().expect("handles lock");
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].comm_id.as_deref(), Some("c0"));
+        let delivered_after_c0 = world.net.stats.delivered_pkts;
+        assert!(delivered_after_c0 > 0);
+        assert!(world.net.tcp.get(1).is_some());
+
+        let busy = gpu_busy_events(&world);
+        assert!(
+            busy.iter()
+                .all(|(_, _, _, label)| label.as_deref() == Some("warmup")),
+            "no step at or after the stop label should run: {busy:?}"
+        );
+
+        let st = state.lock().expect("state lock");
+        assert!(st.pending_collectives.is_empty());
+        for rs in st.ranks.values() {
+            assert_eq!(rs.idx, rs.steps.len());
+        }
+    }
+
+    #[test]
+    fn only_labels_turns_other_steps_into_noops() {
+        let steps = vec![
+            step_compute("skip_me", 5.0),
+            step_collective("allreduce", 1000, "c0"),
+            step_compute("keep_me", 0.001),
+        ];
+        let filter = StepFilter {
+            stop_at_label: None,
+            only_labels: vec!["keep_me".to_string()],
+        };
+        let (sim, world, _state, handles) =
+            run_two_rank_workload_with_filter(steps.clone(), steps, filter);
+
+        assert!(handles.lock().expect("handles lock").is_empty());
+        assert_eq!(world.net.stats.delivered_pkts, 0);
+
+        let busy = gpu_busy_events(&world);
+        assert_eq!(busy.len(), 2);
+        for (t_ns, _, _, label) in &busy {
+            assert_eq!(label.as_deref(), Some("keep_me"));
+            // The skipped 5ms compute step must not delay the kept one.
+            assert_eq!(*t_ns, 0);
+        }
+        assert_eq!(sim.now(), SimTime::from_micros(1));
+    }
+
+    #[test]
+    fn rank_timeline_rows_are_monotonic_and_non_overlapping() {
+        let rank0 = vec![
+            step_compute("fwd", 0.001),
+            step_collective("allreduce", 100_000, "c0"),
+            step_compute("bwd", 0.002),
+        ];
+        // Rank 1 computes longer, so rank 0 waits inside the collective.
+        let rank1 = vec![
+            step_compute("fwd", 0.005),
+            step_collective("allreduce", 100_000, "c0"),
+            step_compute("bwd", 0.002),
+        ];
+        let (_sim, _world, state, _handles) = run_two_rank_workload(rank0, rank1);
+
+        let st = state.lock().expect("state lock");
+        let rows = timeline_rows(&st);
+        assert_eq!(rows.len(), 6);
+        for rank in [0_usize, 1] {
+            let mine = rows.iter().filter(|r| r.rank == rank).collect::<Vec<_>>();
+            assert_eq!(mine.len(), 3);
+            let mut prev_end = 0;
+            for (i, row) in mine.iter().enumerate() {
+                assert_eq!(row.step_idx, i);
+                let end = row.end_ns.expect("every step finishes");
+                assert!(row.start_ns >= prev_end, "rank {rank} overlaps: {row:?}");
+                assert!(end >= row.start_ns, "rank {rank} runs backwards: {row:?}");
+                assert_eq!(row.idle_before_ns, row.start_ns - prev_end);
+                prev_end = end;
+            }
+        }
+
+        // Both ranks leave the collective at the same time.
+        let coll = rows
+            .iter()
+            .filter(|r| matches!(r.kind, RankStepKind::Collective))
+            .collect::<Vec<_>>();
+        assert_eq!(coll.len(), 2);
+        assert_eq!(coll[0].end_ns, coll[1].end_ns);
+        assert_eq!(coll[0].start_ns, 1_000);
+        assert_eq!(coll[1].start_ns, 5_000);
+        let coll_end = coll[0].end_ns.unwrap();
+        for row in rows.iter().filter(|r| r.label.as_deref() == Some("bwd")) {
+            assert_eq!(row.start_ns, coll_end);
+            assert_eq!(row.end_ns, Some(coll_end + 2_000));
+        }
+
+        let csv = timeline_csv(&rows);
+        assert_eq!(csv.lines().count(), rows.len() + 1);
+        assert!(
+            csv.lines()
+                .nth(2)
+                .unwrap()
+                .starts_with("0,1,collective,c0:allreduce,1000,")
+        );
+    }
+
+    #[test]
+    fn fct_csv_reports_flow_fct_percentiles_per_collective() {
+        let row = |step_id, flow_fct_ns: Vec<u64>, makespan_ns| FctRow {
+            step_id,
+            label: Some("grad".to_string()),
+            comm_id: Some("dp".to_string()),
+            op: Some("allreduce".to_string()),
+            hosts: 4,
+            comm_bytes: 1_000_000,
+            makespan_ns,
+            flow_fct_ns,
+        };
+        // 1..=100us in shuffled order: nearest-rank p50/p90/p99 are 50/90/99us.
+        let fcts = (1..=100u64).map(|k| (k * 37 % 101) * 1_000).collect();
+        let rows = vec![row(Some(3), fcts, Some(120_000)), row(None, vec![], None)];
+
+        let csv = fct_csv(&rows);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "step_id,label,comm_id,op,hosts,comm_bytes,makespan_ns,p50_flow_fct_ns,p90_flow_fct_ns,p99_flow_fct_ns,max_flow_fct_ns,flows"
+        );
+        assert_eq!(
+            lines[1],
+            "3,grad,dp,allreduce,4,1000000,120000,50000,90000,99000,100000,100"
+        );
+        // An unfinished collective with no flows leaves the numeric cells empty.
+        assert_eq!(lines[2], ",grad,dp,allreduce,4,1000000,,,,,,0");
+    }
+
+    #[test]
+    fn collective_compression_shrinks_chunks_and_adds_decompression_delay() {
+        let mut coll = step_collective("allreduce", 4_000_000, "c0");
+        coll.compression = Some(0.25);
+        coll.compression_ms = Some(1.0);
+        let steps = vec![coll, step_compute("after", 0.001)];
+        let (_sim, world, state, handles) = run_two_rank_workload(steps.clone(), steps);
+
+        let list = handles.lock().expect("handles lock");
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].comm_bytes, 1_000_000);
+        let done = list[0].handle.stats().done_at.expect("collective done");
+        // Two ranks: each ring flow carries half of the compressed payload.
+        let conn = world.net.tcp.get(1).expect("first ring flow");
+        assert_eq!(conn.total_bytes, 500_000);
+
+        let st = state.lock().expect("state lock");
+        let rows = timeline_rows(&st);
+        let after = rows
+            .iter()
+            .filter(|r| r.label.as_deref() == Some("after"))
+            .collect::<Vec<_>>();
+        assert_eq!(after.len(), 2);
+        for row in after {
+            assert_eq!(row.start_ns, done.0 + 1_000_000);
+        }
+    }
+
+    fn run_fat_tree_rank_workload(steps: Vec<Vec<RankStepSpec>>, quorum: f64) -> RankRun {
+        let mut world = NetWorld::default();
+        let topo = build_fat_tree(
+            &mut world,
+            &FatTreeOpts {
+                k: 4,
+                link_gbps: 100,
+                link_latency: SimTime::from_micros(2),
+                oversubscription: 1,
+            },
+        );
+        world
+            .net
+            .set_host_egress_queue_capacity_bytes(1024 * 1024 * 1024);
+        world
+            .net
+            .set_switch_egress_queue_capacity_bytes(1024 * 1024 * 1024);
+        let host_ids = (0..steps.len()).collect::<Vec<_>>();
+        let host_map = host_ids
+            .iter()
+            .map(|hid| (*hid, topo.hosts[*hid]))
+            .collect();
+        run_rank_workload(
+            world,
+            host_ids,
+            host_map,
+            steps,
+            StepFilter::default(),
+            quorum,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn collective_quorum_launches_before_straggler_arrives() {
+        // Rank 2 computes for 5ms before joining; ranks 0/1 arrive at t=0.
+        let mut coll = step_collective("allreduce", 1_000_000, "c0");
+        coll.hosts = Some(vec![0, 1, 2]);
+        let straggler_ns = 5_000_000;
+        let steps = vec![
+            vec![coll.clone()],
+            vec![coll.clone()],
+            vec![step_compute("straggle", 5.0), coll],
+        ];
+
+        let (_sim, _world, state, handles) = run_fat_tree_rank_workload(steps.clone(), 0.6);
+        {
+            let list = handles.lock().expect("handles lock");
+            assert_eq!(list.len(), 1);
+            assert_eq!(list[0].hosts, 2);
+            let done = list[0].handle.stats().done_at.expect("collective done");
+            assert!(done.0 < straggler_ns, "done at {done:?}");
+        }
+        let st = state.lock().expect("rank workload state lock");
+        assert!(st.pending_collectives.is_empty());
+        assert!(st.late_collectives.is_empty());
+        assert!(st.ranks.values().all(|rs| rs.idx == rs.steps.len()));
+        drop(st);
+
+        // Without slack the collective waits for the straggler.
+        let (_sim, _world, _state, handles) = run_fat_tree_rank_workload(steps, 1.0);
+        let list = handles.lock().expect("handles lock");
+        assert_eq!(list[0].hosts, 3);
+        assert!(list[0].handle.stats().done_at.expect("collective done").0 > straggler_ns);
+    }
+
+    #[test]
+    fn collective_quorum_skips_only_the_excluded_rank_across_iterations() {
+        // Iteration 1 launches without straggler rank 2. Rank 0 reaches iteration 2 of the
+        // same comm_id before rank 2 shows up and must still take part in it.
+        let mut iter1 = step_collective("allreduce", 1_000_000, "c0");
+        iter1.hosts = Some(vec![0, 1, 2]);
+        iter1.label = Some("iter1".to_string());
+        let mut iter2 = iter1.clone();
+        iter2.label = Some("iter2".to_string());
+        let straggler_ns = 5_000_000;
+        let steps = vec![
+            vec![iter1.clone(), iter2.clone()],
+            vec![iter1.clone(), step_compute("slow", 10.0), iter2.clone()],
+            vec![step_compute("straggle", 5.0), iter1, iter2],
+        ];
+
+        let (_sim, _world, state, handles) = run_fat_tree_rank_workload(steps, 0.6);
+        {
+            let list = handles.lock().expect("handles lock");
+            assert_eq!(list.len(), 2);
+            assert!(list.iter().all(|rec| rec.hosts == 2));
+            for rec in list.iter() {
+                assert!(rec.handle.stats().done_at.is_some());
+            }
+        }
+        let st = state.lock().expect("rank workload state lock");
+        assert!(st.pending_collectives.is_empty());
+        assert!(st.late_collectives.is_empty());
+        assert!(st.ranks.values().all(|rs| rs.idx == rs.steps.len()));
+        // Rank 0 waited for rank 2 to form the iteration-2 quorum instead of skipping it.
+        let rank0_iter2 = st.ranks[&0]
+            .timeline
+            .iter()
+            .find(|r| r.label.as_deref() == Some("iter2"))
+            .expect("rank 0 iter2 row");
+        assert!(rank0_iter2.end_ns.expect("iter2 finished") > straggler_ns);
+    }
+
+    #[test]
+    fn collective_quorum_straggler_two_iterations_behind_skips_both() {
+        // Ranks 0/1 finish both iterations of c0 before straggler rank 2 arrives, so
+        // both launch without it and rank 2 must skip each of them once.
+        let mut iter1 = step_collective("allreduce", 1_000_000, "c0");
+        iter1.hosts = Some(vec![0, 1, 2]);
+        iter1.label = Some("iter1".to_string());
+        let mut iter2 = iter1.clone();
+        iter2.label = Some("iter2".to_string());
+        let straggler_ns = 5_000_000;
+        let steps = vec![
+            vec![iter1.clone(), iter2.clone()],
+            vec![iter1.clone(), iter2.clone()],
+            vec![step_compute("straggle", 5.0), iter1, iter2],
+        ];
+
+        let (_sim, _world, state, handles) = run_fat_tree_rank_workload(steps, 0.6);
+        {
+            let list = handles.lock().expect("handles lock");
+            assert_eq!(list.len(), 2);
+            for rec in list.iter() {
+                assert_eq!(rec.hosts, 2);
+                let done = rec.handle.stats().done_at.expect("collective done");
+                assert!(done.0 < straggler_ns, "done at {done:?}");
+            }
+        }
+        let st = state.lock().expect("rank workload state lock");
+        assert!(st.pending_collectives.is_empty());
+        assert!(st.late_collectives.is_empty());
+        assert!(st.ranks.values().all(|rs| rs.idx == rs.steps.len()));
+    }
+
+    #[test]
+    fn collective_quorum_count_rounds_up() {
+        assert_eq!(collective_quorum_count(10, 0.9), 9);
+        assert_eq!(collective_quorum_count(3, 0.6), 2);
+        assert_eq!(collective_quorum_count(4, 1.0), 4);
+        assert_eq!(collective_quorum_count(4, 0.01), 1);
+    }
+
+    #[test]
+    fn failed_flow_surfaces_as_stuck_collective() {
+        let (mut world, host_ids, host_map) = build_two_rank_dumbbell_world();
+        // Flow ids are handed out from 1; kill the first ring flow outright.
+        world.net.fail_flow_at(1, SimTime::ZERO);
+        let steps = vec![
+            vec![step_collective("allreduce", 1_000_000, "c0")],
+            vec![step_collective("allreduce", 1_000_000, "c0")],
+        ];
+        // No --until-ms: the failed flow gives up at its first RTO, so the run ends.
+        let (sim, world, _state, handles) = run_rank_workload(
+            world,
+            host_ids,
+            host_map,
+            steps,
+            StepFilter::default(),
+            1.0,
+            None,
+            None,
+        );
+        assert!(sim.now() < SimTime::from_millis(50), "{:?}", sim.now());
+        assert!(world.net.stats.dropped_pkts > 0);
+
+        let list = handles.lock().expect("handles lock");
+        let stuck = stuck_collectives(&list);
+        assert_eq!(stuck.len(), 1, "{stuck:?}");
+        assert!(stuck[0].contains("comm_id=Some(\"c0\")"), "{}", stuck[0]);
+        assert!(stuck[0].contains("pending_flows=[1]"), "{}", stuck[0]);
+    }
 }
@@ -1,18 +1,21 @@
 use clap::Parser;
+use htsim_rs::analysis::percentile;
 use htsim_rs::cc::collective::CollectiveOp;
 use htsim_rs::cc::ring::{self, RingAllreduceConfig, RingTransport, RoutingMode as CcRoutingMode};
-use htsim_rs::net::{EcmpHashMode, NetWorld, NodeId};
+use htsim_rs::experiments::start_p2p_flow;
+use htsim_rs::net::{EcmpHashMode, FlowTags, NetWorld, Network, NodeId};
 use htsim_rs::proto::dctcp::{DctcpConfig, DctcpConn, DctcpDoneCallback};
 use htsim_rs::proto::tcp::{TcpConfig, TcpConn, TcpDoneCallback};
-use htsim_rs::queue::DEFAULT_PKT_BYTES;
+use htsim_rs::queue::{DEFAULT_PKT_BYTES, EgressScheduler};
 use htsim_rs::sim::{
-    RankStepKind, RankStepSpec, RoutingMode, SendRecvDirection, SimTime, Simulator, TopologySpec,
-    TransportProtocol, WorkloadDefaults, WorkloadSpec,
+    GpuSpec, RankStepKind, RankStepSpec, RoutingMode, SendRecvDirection, SimTime, Simulator,
+    TopologySpec, TransportProtocol, WorkloadDefaults, WorkloadSpec,
 };
 use htsim_rs::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use htsim_rs::topo::fat_tree::{FatTreeOpts, build_fat_tree};
 use htsim_rs::viz::{VizEvent, VizEventKind, VizLogger};
-use std::collections::HashMap;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -33,6 +36,14 @@ struct Args {
     #[arg(long)]
     viz_json: Option<PathBuf>,
 
+    /// Output per-link/per-flow throughput series (JSON) pre-aggregated for charts
+    #[arg(long)]
+    viz_throughput_json: Option<PathBuf>,
+
+    /// Bin width (us) for --viz-throughput-json
+    #[arg(long, default_value_t = 10)]
+    viz_throughput_bin_us: u64,
+
     /// Run until this time (ms); defaults to running until completion
     #[arg(long)]
     until_ms: Option<u64>,
@@ -45,10 +56,18 @@ struct Args {
     #[arg(long)]
     routing: Option<String>,
 
-    /// Print per-collective flow completion time (FCT) stats
+    /// ECMP hash seed (defaults to the built-in fixed seed)
+    #[arg(long)]
+    ecmp_seed: Option<u64>,
+
+    /// Print per-collective flow completion time (FCT) stats and per-RPC latencies
     #[arg(long)]
     fct_stats: bool,
 
+    /// Output one row of flow completion time (FCT) stats per collective as CSV
+    #[arg(long)]
+    fct_csv: Option<PathBuf>,
+
     /// Override switch egress queue capacity in bytes
     #[arg(long)]
     queue_bytes: Option<u64>,
@@ -61,9 +80,48 @@ struct Args {
     #[arg(long)]
     host_queue_bytes: Option<u64>,
 
+    /// Serve host egress data packets round-robin per flow instead of FIFO
+    #[arg(long)]
+    host_egress_flow_wrr: bool,
+
     /// Override host egress queue capacity in packets (1500B each)
     #[arg(long)]
     host_queue_pkts: Option<u64>,
+
+    /// Halt each rank when it reaches a step with this label
+    #[arg(long)]
+    stop_at_label: Option<String>,
+
+    /// Only run steps with these labels (comma-separated); other steps are no-ops
+    #[arg(long, value_delimiter = ',')]
+    only_labels: Vec<String>,
+
+    /// Output per-rank step timeline as JSON (rank-based workloads only)
+    #[arg(long)]
+    timeline_json: Option<PathBuf>,
+
+    /// Output per-rank step timeline as CSV (rank-based workloads only)
+    #[arg(long)]
+    timeline_csv: Option<PathBuf>,
+
+    /// Launch a collective once this fraction of its ranks has arrived, in (0, 1];
+    /// ranks arriving after launch are dropped from that collective
+    #[arg(long, default_value_t = 1.0)]
+    collective_quorum: f64,
+
+    /// Max comm streams a host's NIC serves at once (e.g. DMA engines); collectives
+    /// on other streams wait for a free slot on every member host. Unlimited by default
+    #[arg(long)]
+    nic_streams: Option<usize>,
+
+    /// Print simulator stats (events executed, wall-clock time, events/sec) at the end
+    #[arg(long)]
+    sim_stats: bool,
+
+    /// Share switch egress bandwidth between tenants by weight (comma-separated, the i-th
+    /// weight for the i-th --workload) using per-tenant DRR; tenants share FIFO queues otherwise
+    #[arg(long, value_delimiter = ',')]
+    tenant_weights: Vec<u32>,
 }
 
 struct CollectiveRecord {
@@ -81,14 +139,153 @@ enum AsyncWaitKind {
     None,
     All,
     Stream(u64),
+    /// Waiting for the async collectives named in the current step's `depends_on`.
+    Deps,
 }
 
 struct RankState {
+    /// Index of the --workload this rank belongs to; its flows are tagged with it.
+    tenant: u32,
     steps: Vec<RankStepSpec>,
     idx: usize,
     pending_async_total: usize,
     pending_async_by_stream: HashMap<u64, usize>,
+    pending_async_by_comm_id: HashMap<String, usize>,
     waiting_for_async: AsyncWaitKind,
+    timeline: Vec<TimelineRow>,
+}
+
+/// One executed step in a rank's timeline.
+#[derive(Debug, Clone, Serialize)]
+struct TimelineRow {
+    rank: usize,
+    step_idx: usize,
+    kind: RankStepKind,
+    label: Option<String>,
+    start_ns: u64,
+    /// `None` if the step was still running when the simulation stopped.
+    end_ns: Option<u64>,
+    /// Gap between the previous step's end (or t=0) and this step's start.
+    idle_before_ns: u64,
+}
+
+impl RankState {
+    fn start_timeline_row(&mut self, rank: usize, kind: &RankStepKind, now: SimTime) {
+        let prev_end = self.timeline.last().and_then(|r| r.end_ns).unwrap_or(0);
+        let step = &self.steps[self.idx];
+        self.timeline.push(TimelineRow {
+            rank,
+            step_idx: self.idx,
+            kind: kind.clone(),
+            label: step.label.clone(),
+            start_ns: now.0,
+            end_ns: None,
+            idle_before_ns: now.0.saturating_sub(prev_end),
+        });
+    }
+
+    /// The executor re-enters `StartRankStep` exactly when the running step
+    /// releases the rank, so that is where the open row gets closed.
+    fn finish_timeline_row(&mut self, now: SimTime) {
+        if let Some(row) = self.timeline.last_mut().filter(|r| r.end_ns.is_none()) {
+            row.end_ns = Some(now.0);
+        }
+    }
+}
+
+fn rank_step_kind_name(kind: &RankStepKind) -> &'static str {
+    match kind {
+        RankStepKind::Compute => "compute",
+        RankStepKind::Collective => "collective",
+        RankStepKind::CollectiveWait => "collective_wait",
+        RankStepKind::Sendrecv => "sendrecv",
+        RankStepKind::Barrier => "barrier",
+        RankStepKind::Rpc => "rpc",
+    }
+}
+
+fn timeline_rows(state: &RankWorkloadState) -> Vec<TimelineRow> {
+    let mut rank_ids = state.ranks.keys().copied().collect::<Vec<_>>();
+    rank_ids.sort_unstable();
+    rank_ids
+        .iter()
+        .flat_map(|rid| state.ranks[rid].timeline.iter().cloned())
+        .collect()
+}
+
+fn timeline_csv(rows: &[TimelineRow]) -> String {
+    let mut out = String::from("rank,step_idx,kind,label,start_ns,end_ns,idle_before_ns\n");
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            r.rank,
+            r.step_idx,
+            rank_step_kind_name(&r.kind),
+            r.label.as_deref().unwrap_or(""),
+            r.start_ns,
+            r.end_ns.map(|ns| ns.to_string()).unwrap_or_default(),
+            r.idle_before_ns
+        ));
+    }
+    out
+}
+
+/// Flow completion time summary of one collective, for `--fct-csv`.
+#[derive(Debug, Clone)]
+struct FctRow {
+    step_id: Option<u64>,
+    label: Option<String>,
+    comm_id: Option<String>,
+    op: Option<String>,
+    hosts: usize,
+    comm_bytes: u64,
+    /// `None` if the collective had not finished when the simulation stopped.
+    makespan_ns: Option<u64>,
+    flow_fct_ns: Vec<u64>,
+}
+
+fn fct_rows(records: &[CollectiveRecord]) -> Vec<FctRow> {
+    records
+        .iter()
+        .map(|record| {
+            let stats = record.handle.stats();
+            FctRow {
+                step_id: record.step_id,
+                label: record.label.clone(),
+                comm_id: record.comm_id.clone(),
+                op: record.op.clone(),
+                hosts: record.hosts,
+                comm_bytes: record.comm_bytes,
+                makespan_ns: stats.makespan_ns(),
+                flow_fct_ns: stats.flow_fct_ns,
+            }
+        })
+        .collect()
+}
+
+fn fct_csv(rows: &[FctRow]) -> String {
+    let mut out = String::from(
+        "step_id,label,comm_id,op,hosts,comm_bytes,makespan_ns,p50_flow_fct_ns,p90_flow_fct_ns,p99_flow_fct_ns,max_flow_fct_ns,flows\n",
+    );
+    let cell = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or_default();
+    for r in rows {
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            cell(r.step_id),
+            r.label.as_deref().unwrap_or(""),
+            r.comm_id.as_deref().unwrap_or(""),
+            r.op.as_deref().unwrap_or(""),
+            r.hosts,
+            r.comm_bytes,
+            cell(r.makespan_ns),
+            cell(percentile(&r.flow_fct_ns, 0.50)),
+            cell(percentile(&r.flow_fct_ns, 0.90)),
+            cell(percentile(&r.flow_fct_ns, 0.99)),
+            cell(r.flow_fct_ns.iter().copied().max()),
+            r.flow_fct_ns.len()
+        ));
+    }
+    out
 }
 
 struct CollectiveWait {
@@ -97,29 +294,232 @@ struct CollectiveWait {
     op: String,
     is_async: bool,
     comm_stream: u64,
+    /// Per-rank decompression cost charged after the collective completes.
+    decompress_ns: u64,
+    /// Per-pair bytes for `alltoall`, indexed by position in `hosts`.
+    alltoall_matrix: Option<Vec<Vec<u64>>>,
     arrived: Vec<usize>,
 }
 
+/// Number of arrived ranks needed to launch a collective over `hosts` ranks.
+fn collective_quorum_count(hosts: usize, quorum: f64) -> usize {
+    // Small epsilon so that e.g. 10 * 0.9 does not round up to 10.
+    let needed = (hosts as f64 * quorum - 1e-9).ceil() as usize;
+    needed.clamp(1, hosts.max(1))
+}
+
 struct SendRecvWait {
     comm_bytes: u64,
+    /// Transport requested by either side's step (both must agree if both set).
+    protocol: Option<TransportProtocol>,
     sender: Option<usize>,
     receiver: Option<usize>,
     arrived: Vec<usize>,
 }
 
+struct BarrierWait {
+    hosts: Vec<usize>,
+    arrived: Vec<usize>,
+}
+
+/// One `rpc` step: the request leg to `peer` and the response leg back.
+struct RpcRecord {
+    rank: usize,
+    peer: usize,
+    label: Option<String>,
+    req_bytes: u64,
+    resp_bytes: u64,
+    /// Transport used for both legs.
+    protocol: TransportProtocol,
+    start_ns: u64,
+    /// Set once the response has fully arrived back at `rank`.
+    end_ns: Option<u64>,
+}
+
+/// Debug filters consulted by the executor before running each step.
+#[derive(Debug, Clone, Default)]
+struct StepFilter {
+    /// Halt a rank when it reaches a step with this label.
+    stop_at_label: Option<String>,
+    /// If non-empty, steps whose label is not listed are skipped.
+    only_labels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StepFilterAction {
+    Run,
+    Skip,
+    Stop,
+}
+
+impl StepFilter {
+    fn action(&self, label: Option<&str>) -> StepFilterAction {
+        if label.is_some() && label == self.stop_at_label.as_deref() {
+            return StepFilterAction::Stop;
+        }
+        if !self.only_labels.is_empty()
+            && !label.is_some_and(|l| self.only_labels.iter().any(|x| x == l))
+        {
+            return StepFilterAction::Skip;
+        }
+        StepFilterAction::Run
+    }
+}
+
 struct RankWorkloadState {
     ranks: HashMap<usize, RankState>,
     hosts_all: Vec<usize>,
     host_map: HashMap<usize, NodeId>,
-    gpu_map: HashMap<usize, Option<String>>,
+    gpu_map: HashMap<usize, Option<GpuSpec>>,
     protocol: TransportProtocol,
     routing: CcRoutingMode,
     next_flow_id: u64,
     tcp_cfg: TcpConfig,
     dctcp_cfg: DctcpConfig,
     pending_collectives: HashMap<String, CollectiveWait>,
-    pending_sendrecv: HashMap<String, SendRecvWait>,
+    /// Fraction of ranks that must arrive before a collective launches.
+    collective_quorum: f64,
+    /// comm_id -> rank -> quorum launches that went ahead without that rank and that
+    /// it has not yet skipped (a straggler can fall several iterations behind)
+    late_collectives: HashMap<String, HashMap<usize, u32>>,
+    /// Per-host comm stream slots; collectives beyond the limit are held paused.
+    nic: NicStreams,
+    /// Keyed by `(comm_id, tag)`.
+    pending_sendrecv: HashMap<(String, Option<u64>), SendRecvWait>,
+    pending_barriers: HashMap<String, BarrierWait>,
+    /// RPCs issued by rank steps, in issue order.
+    rpcs: Vec<RpcRecord>,
     collective_handles: Arc<Mutex<Vec<CollectiveRecord>>>,
+    step_filter: StepFilter,
+}
+
+impl RankWorkloadState {
+    /// Consume one quorum launch of `comm_id` that went ahead without `rank`;
+    /// returns false if the rank is not behind on that collective.
+    fn take_late_collective(&mut self, comm_id: &str, rank: usize) -> bool {
+        let Some(late) = self.late_collectives.get_mut(comm_id) else {
+            return false;
+        };
+        let Some(missed) = late.get_mut(&rank) else {
+            return false;
+        };
+        *missed -= 1;
+        if *missed == 0 {
+            late.remove(&rank);
+            if late.is_empty() {
+                self.late_collectives.remove(comm_id);
+            }
+        }
+        true
+    }
+
+    /// Tenant (index of the --workload) that `rank` belongs to.
+    fn tenant_of(&self, rank: usize) -> u32 {
+        self.ranks.get(&rank).map_or(0, |r| r.tenant)
+    }
+}
+
+/// Limits how many comm streams each host drives through the network at once.
+///
+/// Streams only order steps within a rank; without a limit every stream gets the
+/// NIC to itself. With one, a collective whose stream is not already active on a
+/// member host that is out of slots is launched paused and resumed, in launch
+/// order, once slots free up.
+#[derive(Default)]
+struct NicStreams {
+    limit: Option<usize>,
+    /// host id -> stream -> collectives in flight on it
+    active: HashMap<usize, HashMap<u64, usize>>,
+    waiting: VecDeque<(Vec<usize>, u64, ring::RingAllreduceHandle)>,
+}
+
+impl NicStreams {
+    fn new(limit: Option<usize>) -> Self {
+        if limit == Some(0) {
+            panic!("--nic-streams must be at least 1");
+        }
+        Self {
+            limit,
+            ..Self::default()
+        }
+    }
+
+    fn has_slot(&self, hosts: &[usize], stream: u64) -> bool {
+        let Some(limit) = self.limit else {
+            return true;
+        };
+        hosts.iter().all(|h| {
+            self.active
+                .get(h)
+                .is_none_or(|streams| streams.contains_key(&stream) || streams.len() < limit)
+        })
+    }
+
+    fn acquire(&mut self, hosts: &[usize], stream: u64) {
+        if self.limit.is_none() {
+            return;
+        }
+        for h in hosts {
+            let streams = self.active.entry(*h).or_default();
+            *streams.entry(stream).or_insert(0) += 1;
+        }
+    }
+
+    /// Start `handle` now if every host has a slot for `stream`, otherwise pause it in line.
+    fn admit(&mut self, hosts: Vec<usize>, stream: u64, handle: &ring::RingAllreduceHandle) {
+        if self.has_slot(&hosts, stream) {
+            self.acquire(&hosts, stream);
+        } else {
+            handle.pause();
+            self.waiting.push_back((hosts, stream, handle.clone()));
+        }
+    }
+
+    /// Free `stream` on `hosts` and return the waiting collectives that now fit.
+    fn release(&mut self, hosts: &[usize], stream: u64) -> Vec<ring::RingAllreduceHandle> {
+        if self.limit.is_none() {
+            return Vec::new();
+        }
+        for h in hosts {
+            let Some(streams) = self.active.get_mut(h) else {
+                continue;
+            };
+            if let Some(count) = streams.get_mut(&stream) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    streams.remove(&stream);
+                }
+            }
+        }
+        let mut ready = Vec::new();
+        let mut still_waiting = VecDeque::new();
+        while let Some((hosts, stream, handle)) = self.waiting.pop_front() {
+            if self.has_slot(&hosts, stream) {
+                self.acquire(&hosts, stream);
+                ready.push(handle);
+            } else {
+                still_waiting.push_back((hosts, stream, handle));
+            }
+        }
+        self.waiting = still_waiting;
+        ready
+    }
+}
+
+/// Release a finished collective's NIC stream slots and resume whatever they unblock.
+fn release_nic_streams(
+    state: &Arc<Mutex<RankWorkloadState>>,
+    hosts: &[usize],
+    stream: u64,
+    sim: &mut Simulator,
+) {
+    let ready = {
+        let mut st = state.lock().expect("rank workload state lock");
+        st.nic.release(hosts, stream)
+    };
+    for handle in ready {
+        handle.resume(sim);
+    }
 }
 
 struct StartRankStep {
@@ -127,8 +527,96 @@ struct StartRankStep {
     state: Arc<Mutex<RankWorkloadState>>,
 }
 
+/// Sends the response leg of `state.rpcs[rpc_idx]` once its request has arrived.
+struct StartRpcResponse {
+    rpc_idx: usize,
+    state: Arc<Mutex<RankWorkloadState>>,
+}
+
+impl htsim_rs::sim::Event for StartRpcResponse {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn htsim_rs::sim::World) {
+        let StartRpcResponse { rpc_idx, state } = *self;
+        let w = world
+            .as_any_mut()
+            .downcast_mut::<NetWorld>()
+            .expect("world must be NetWorld");
+        start_rpc_leg(sim, w, &state, rpc_idx, true);
+    }
+}
+
+/// Start the request (`response == false`) or response leg of an RPC.
+///
+/// Finishing the request schedules the response; finishing the response records
+/// the RPC latency and releases the calling rank.
+fn start_rpc_leg(
+    sim: &mut Simulator,
+    w: &mut NetWorld,
+    state: &Arc<Mutex<RankWorkloadState>>,
+    rpc_idx: usize,
+    response: bool,
+) {
+    let (rank, peer, bytes, flow_id, tenant, src, dst, protocol, routing, tcp_cfg, dctcp_cfg) = {
+        let mut st = state.lock().expect("rank workload state lock");
+        let rpc = &st.rpcs[rpc_idx];
+        let (rank, peer, protocol) = (rpc.rank, rpc.peer, rpc.protocol);
+        let (from, to, bytes) = if response {
+            (peer, rank, rpc.resp_bytes)
+        } else {
+            (rank, peer, rpc.req_bytes)
+        };
+        let src = *st.host_map.get(&from).expect("unknown host id");
+        let dst = *st.host_map.get(&to).expect("unknown host id");
+        let flow_id = st.next_flow_id;
+        st.next_flow_id = st.next_flow_id.saturating_add(1);
+        (
+            rank,
+            peer,
+            bytes,
+            flow_id,
+            st.tenant_of(rank),
+            src,
+            dst,
+            protocol,
+            st.routing,
+            st.tcp_cfg.clone(),
+            st.dctcp_cfg.clone(),
+        )
+    };
+
+    let done_state = Arc::clone(state);
+    let done_cb: ring::RingDoneCallback = Box::new(move |now, sim| {
+        if !response {
+            sim.schedule(
+                now,
+                StartRpcResponse {
+                    rpc_idx,
+                    state: Arc::clone(&done_state),
+                },
+            );
+            return;
+        }
+        done_state.lock().expect("rank workload state lock").rpcs[rpc_idx].end_ns = Some(now.0);
+        sim.schedule(
+            now,
+            StartRankStep {
+                rank_id: rank,
+                state: Arc::clone(&done_state),
+            },
+        );
+    });
+    if bytes == 0 || rank == peer {
+        done_cb(sim.now(), sim);
+        return;
+    }
+    w.net.set_flow_tenant(flow_id, tenant);
+    start_p2p_flow(
+        sim, w, protocol, routing, &tcp_cfg, &dctcp_cfg, flow_id, src, dst, bytes, done_cb,
+    );
+}
+
 struct TcpRingTransport {
     cfg: TcpConfig,
+    tenant: u32,
 }
 
 impl RingTransport for TcpRingTransport {
@@ -143,6 +631,7 @@ impl RingTransport for TcpRingTransport {
         world: &mut NetWorld,
         done: ring::RingDoneCallback,
     ) {
+        world.net.set_flow_tenant(flow_id, self.tenant);
         let mut tcp = std::mem::take(&mut world.net.tcp);
         let conn = match routing {
             CcRoutingMode::PerFlow => {
@@ -164,6 +653,7 @@ impl RingTransport for TcpRingTransport {
 
 struct DctcpRingTransport {
     cfg: DctcpConfig,
+    tenant: u32,
 }
 
 impl RingTransport for DctcpRingTransport {
@@ -178,6 +668,7 @@ impl RingTransport for DctcpRingTransport {
         world: &mut NetWorld,
         done: ring::RingDoneCallback,
     ) {
+        world.net.set_flow_tenant(flow_id, self.tenant);
         let mut dctcp = std::mem::take(&mut world.net.dctcp);
         let conn = match routing {
             CcRoutingMode::PerFlow => {
@@ -197,6 +688,23 @@ impl RingTransport for DctcpRingTransport {
     }
 }
 
+/// Transport configs for a collective step, with its initial window overrides applied.
+fn step_transport_cfgs(
+    step: &RankStepSpec,
+    mut tcp_cfg: TcpConfig,
+    mut dctcp_cfg: DctcpConfig,
+) -> (TcpConfig, DctcpConfig) {
+    if let Some(bytes) = step.init_cwnd_bytes {
+        tcp_cfg.init_cwnd_bytes = bytes;
+        dctcp_cfg.init_cwnd_bytes = bytes;
+    }
+    if let Some(bytes) = step.init_ssthresh_bytes {
+        tcp_cfg.init_ssthresh_bytes = bytes;
+        dctcp_cfg.init_ssthresh_bytes = bytes;
+    }
+    (tcp_cfg, dctcp_cfg)
+}
+
 fn compute_duration_ns_from_ms(ms: f64) -> u64 {
     if !ms.is_finite() || ms <= 0.0 {
         return 0;
@@ -204,6 +712,77 @@ fn compute_duration_ns_from_ms(ms: f64) -> u64 {
     (ms * 1_000_000.0).round() as u64
 }
 
+/// Duration of a rank compute step: `flops / peak_flops` when the step gives
+/// `flops` and the rank's GPU has a peak rate, otherwise `compute_ms`.
+fn rank_compute_duration_ns(step: &RankStepSpec, gpu: Option<&GpuSpec>) -> u64 {
+    let peak_flops = gpu.and_then(|g| g.peak_flops);
+    if let (Some(flops), Some(peak_flops)) = (step.flops, peak_flops) {
+        if flops.is_finite() && flops > 0.0 && peak_flops.is_finite() && peak_flops > 0.0 {
+            return (flops / peak_flops * 1e9).round() as u64;
+        }
+        return 0;
+    }
+    compute_duration_ns_from_ms(step.compute_ms.unwrap_or(0.0))
+}
+
+/// Apply a gradient compression ratio in (0, 1] to a collective's payload.
+fn compressed_comm_bytes(comm_bytes: u64, compression: Option<f64>) -> u64 {
+    let Some(ratio) = compression else {
+        return comm_bytes;
+    };
+    if !(ratio > 0.0 && ratio <= 1.0) {
+        panic!("compression ratio must be in (0, 1], got {ratio}");
+    }
+    (comm_bytes as f64 * ratio).ceil() as u64
+}
+
+/// Validated, compressed per-pair matrix of an `alltoall` step over `ranks` hosts.
+fn alltoall_matrix(step: &RankStepSpec, op: &str, ranks: usize) -> Option<Vec<Vec<u64>>> {
+    let matrix = step.alltoall_matrix.as_ref()?;
+    if op != "alltoall" {
+        panic!("alltoall_matrix is only supported for op \"alltoall\", got {op:?}");
+    }
+    if matrix.len() != ranks || matrix.iter().any(|row| row.len() != ranks) {
+        panic!("alltoall_matrix must be {ranks}x{ranks} (one row and column per collective host)");
+    }
+    Some(
+        matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|b| compressed_comm_bytes(*b, step.compression))
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+/// Keep the rows and columns of `matrix` whose position is marked in `keep`.
+fn retain_matrix(matrix: &[Vec<u64>], keep: &[bool]) -> Vec<Vec<u64>> {
+    matrix
+        .iter()
+        .zip(keep)
+        .filter(|(_, k)| **k)
+        .map(|(row, _)| {
+            row.iter()
+                .zip(keep)
+                .filter(|(_, k)| **k)
+                .map(|(b, _)| *b)
+                .collect()
+        })
+        .collect()
+}
+
+/// Total bytes moved by an all-to-all matrix (the diagonal never leaves the host).
+fn alltoall_matrix_bytes(matrix: &[Vec<u64>]) -> u64 {
+    matrix
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| row.iter().enumerate().filter(move |(j, _)| *j != i))
+        .map(|(_, b)| *b)
+        .sum()
+}
+
 fn default_tcp_cfg() -> TcpConfig {
     let mut cfg = TcpConfig::default();
     cfg.init_rto = SimTime::from_millis(1);
@@ -213,81 +792,24 @@ fn default_tcp_cfg() -> TcpConfig {
     cfg
 }
 
-fn percentile_ns(values: &[u64], p: f64) -> Option<u64> {
-    if values.is_empty() {
-        return None;
-    }
-    let p = if p <= 0.0 {
-        0.0
-    } else if p >= 1.0 {
-        1.0
-    } else {
-        p
-    };
-    let mut sorted = values.to_vec();
-    sorted.sort_unstable();
-    let idx = (p * sorted.len() as f64).ceil() as usize;
-    let idx = idx.saturating_sub(1).min(sorted.len().saturating_sub(1));
-    sorted.get(idx).copied()
-}
-
-fn start_p2p_flow(
-    sim: &mut Simulator,
-    world: &mut NetWorld,
-    protocol: TransportProtocol,
-    routing: CcRoutingMode,
-    tcp_cfg: &TcpConfig,
-    dctcp_cfg: &DctcpConfig,
-    flow_id: u64,
-    src: NodeId,
-    dst: NodeId,
-    bytes: u64,
-    done: ring::RingDoneCallback,
-) {
-    match protocol {
-        TransportProtocol::Tcp => {
-            let mut tcp = std::mem::take(&mut world.net.tcp);
-            let conn = match routing {
-                CcRoutingMode::PerFlow => {
-                    let route = world.net.route_ecmp_path(src, dst, flow_id);
-                    TcpConn::new(flow_id, src, dst, route, bytes, tcp_cfg.clone())
-                }
-                CcRoutingMode::PerPacket => {
-                    TcpConn::new_dynamic(flow_id, src, dst, bytes, tcp_cfg.clone())
-                }
-            };
-            let done_cb: TcpDoneCallback = Box::new(move |_, now, sim| {
-                done(now, sim);
-            });
-            tcp.set_done_callback(flow_id, done_cb);
-            tcp.start_conn(conn, sim, &mut world.net);
-            world.net.tcp = tcp;
-        }
-        TransportProtocol::Dctcp => {
-            let mut dctcp = std::mem::take(&mut world.net.dctcp);
-            let conn = match routing {
-                CcRoutingMode::PerFlow => {
-                    let route = world.net.route_ecmp_path(src, dst, flow_id);
-                    DctcpConn::new(flow_id, src, dst, route, bytes, dctcp_cfg.clone())
-                }
-                CcRoutingMode::PerPacket => {
-                    DctcpConn::new_dynamic(flow_id, src, dst, bytes, dctcp_cfg.clone())
-                }
-            };
-            let done_cb: DctcpDoneCallback = Box::new(move |_, now, sim| {
-                done(now, sim);
-            });
-            dctcp.set_done_callback(flow_id, done_cb);
-            dctcp.start_conn(conn, sim, &mut world.net);
-            world.net.dctcp = dctcp;
+/// Tags attached to every flow of a collective so per-flow stats can be grouped.
+fn collective_flow_tags(label: Option<&str>, comm_id: Option<&str>, op: Option<&str>) -> FlowTags {
+    let mut tags = FlowTags::new();
+    for (key, value) in [("label", label), ("comm_id", comm_id), ("op", op)] {
+        if let Some(value) = value {
+            tags.insert(key.to_string(), value.to_string());
         }
     }
+    tags
 }
 
 fn rank_step_kind(step: &RankStepSpec) -> RankStepKind {
     if let Some(kind) = &step.kind {
         return kind.clone();
     }
+    if step.req_bytes.is_some() || step.resp_bytes.is_some() {
+        return RankStepKind::Rpc;
+    }
     if step.peer.is_some() {
         return RankStepKind::Sendrecv;
     }
@@ -316,6 +838,13 @@ fn comm_stream_id(comm_id: &str) -> u64 {
     hash
 }
 
+/// Whether any async collective named in `step.depends_on` is still in flight on this rank.
+fn has_pending_deps(step: &RankStepSpec, rank_state: &RankState) -> bool {
+    step.depends_on
+        .iter()
+        .any(|comm_id| rank_state.pending_async_by_comm_id.contains_key(comm_id))
+}
+
 fn pending_async_on_stream(rank_state: &RankState, stream: u64) -> usize {
     rank_state
         .pending_async_by_stream
@@ -329,8 +858,11 @@ fn async_wait_kind_for_step(
     kind: &RankStepKind,
     rank_state: &RankState,
 ) -> AsyncWaitKind {
+    if has_pending_deps(step, rank_state) {
+        return AsyncWaitKind::Deps;
+    }
     match kind {
-        RankStepKind::Compute => AsyncWaitKind::None,
+        RankStepKind::Compute | RankStepKind::Barrier | RankStepKind::Rpc => AsyncWaitKind::None,
         RankStepKind::CollectiveWait => {
             if let Some(stream) = step.comm_stream {
                 let stream = u64::from(stream);
@@ -370,6 +902,41 @@ impl htsim_rs::sim::Event for StartRankStep {
             .downcast_mut::<NetWorld>()
             .expect("world must be NetWorld");
 
+        // Apply label filters before any async bookkeeping so that a skipped
+        // step never blocks the rank.
+        let filter_action = {
+            let mut guard = state.lock().expect("rank workload state lock");
+            let st = &mut *guard;
+            let Some(rank_state) = st.ranks.get_mut(&rank_id) else {
+                return;
+            };
+            rank_state.finish_timeline_row(sim.now());
+            let action = match rank_state.steps.get(rank_state.idx) {
+                Some(step) => st.step_filter.action(step.label.as_deref()),
+                None => StepFilterAction::Run,
+            };
+            match action {
+                StepFilterAction::Run => {}
+                StepFilterAction::Skip => rank_state.idx = rank_state.idx.saturating_add(1),
+                StepFilterAction::Stop => rank_state.idx = rank_state.steps.len(),
+            }
+            action
+        };
+        match filter_action {
+            StepFilterAction::Run => {}
+            StepFilterAction::Skip => {
+                sim.schedule(
+                    sim.now(),
+                    StartRankStep {
+                        rank_id,
+                        state: Arc::clone(&state),
+                    },
+                );
+                return;
+            }
+            StepFilterAction::Stop => return,
+        }
+
         let (step, kind, wait_kind, host_node, gpu, protocol, routing, tcp_cfg, dctcp_cfg, hosts_all) = {
             let mut st = state.lock().expect("rank workload state lock");
             let rank_state = match st.ranks.get_mut(&rank_id) {
@@ -387,13 +954,14 @@ impl htsim_rs::sim::Event for StartRankStep {
             let wait_kind = async_wait_kind_for_step(&step, &kind, rank_state);
             let host_node = *st.host_map.get(&rank_id).expect("unknown host id");
             let gpu = st.gpu_map.get(&rank_id).and_then(|g| g.clone());
+            let protocol = step.protocol.unwrap_or(st.protocol);
             (
                 step,
                 kind,
                 wait_kind,
                 host_node,
                 gpu,
-                st.protocol,
+                protocol,
                 st.routing,
                 st.tcp_cfg.clone(),
                 st.dctcp_cfg.clone(),
@@ -417,12 +985,13 @@ impl htsim_rs::sim::Event for StartRankStep {
             if rank_state.idx >= rank_state.steps.len() {
                 return;
             }
+            rank_state.start_timeline_row(rank_id, &kind, sim.now());
             rank_state.idx = rank_state.idx.saturating_add(1);
         }
 
         match kind {
             RankStepKind::Compute => {
-                let duration_ns = compute_duration_ns_from_ms(step.compute_ms.unwrap_or(0.0));
+                let duration_ns = rank_compute_duration_ns(&step, gpu.as_ref());
                 if duration_ns > 0 {
                     if let Some(v) = &mut w.net.viz {
                         v.push(VizEvent {
@@ -434,7 +1003,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                             kind: VizEventKind::GpuBusy {
                                 node: host_node.0,
                                 duration_ns,
-                                gpu,
+                                gpu: gpu.map(|g| g.model),
                                 step_id: step.id,
                                 label: step.label.clone(),
                             },
@@ -475,7 +1044,9 @@ impl htsim_rs::sim::Event for StartRankStep {
                         return;
                     }
                 };
-                let comm_bytes = step.comm_bytes.unwrap_or(0);
+                let comm_bytes =
+                    compressed_comm_bytes(step.comm_bytes.unwrap_or(0), step.compression);
+                let decompress_ns = compute_duration_ns_from_ms(step.compression_ms.unwrap_or(0.0));
                 let op = step
                     .op
                     .clone()
@@ -488,6 +1059,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                     .map(u64::from)
                     .unwrap_or_else(|| comm_stream_id(&comm_id));
                 let is_async = collective_is_async(&op);
+                let alltoall_matrix = alltoall_matrix(&step, &op, hosts.len());
+                let comm_bytes = alltoall_matrix
+                    .as_deref()
+                    .map_or(comm_bytes, alltoall_matrix_bytes);
 
                 if !hosts.contains(&rank_id) {
                     panic!(
@@ -496,6 +1071,23 @@ impl htsim_rs::sim::Event for StartRankStep {
                     );
                 }
 
+                // The collective already launched without this rank (quorum reached):
+                // drop it from this iteration and move on.
+                {
+                    let mut st = state.lock().expect("rank workload state lock");
+                    if st.take_late_collective(&comm_id, rank_id) {
+                        drop(st);
+                        sim.schedule(
+                            sim.now(),
+                            StartRankStep {
+                                rank_id,
+                                state: Arc::clone(&state),
+                            },
+                        );
+                        return;
+                    }
+                }
+
                 // Non-blocking collective launch: allow this rank to continue immediately.
                 if is_async {
                     if comm_bytes > 0 && hosts.len() > 1 {
@@ -508,6 +1100,11 @@ impl htsim_rs::sim::Event for StartRankStep {
                             .entry(comm_stream)
                             .or_insert(0);
                         *counter = counter.saturating_add(1);
+                        let counter = rank_state
+                            .pending_async_by_comm_id
+                            .entry(comm_id.clone())
+                            .or_insert(0);
+                        *counter = counter.saturating_add(1);
                     }
                     sim.schedule(
                         sim.now(),
@@ -521,6 +1118,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                 let mut start_cfg = None;
                 {
                     let mut st = state.lock().expect("rank workload state lock");
+                    let quorum = st.collective_quorum;
                     let entry = st
                         .pending_collectives
                         .entry(comm_id.clone())
@@ -530,6 +1128,8 @@ impl htsim_rs::sim::Event for StartRankStep {
                             op: op.clone(),
                             is_async,
                             comm_stream,
+                            decompress_ns,
+                            alltoall_matrix: alltoall_matrix.clone(),
                             arrived: Vec::new(),
                         });
                     if entry.op != op || entry.is_async != is_async {
@@ -556,14 +1156,41 @@ impl htsim_rs::sim::Event for StartRankStep {
                             comm_id, entry.comm_stream, comm_stream
                         );
                     }
+                    if entry.decompress_ns != decompress_ns {
+                        panic!(
+                            "comm_id {:?} collective compression_ms mismatch: existing ns={} vs new ns={}",
+                            comm_id, entry.decompress_ns, decompress_ns
+                        );
+                    }
+                    if entry.alltoall_matrix != alltoall_matrix {
+                        panic!(
+                            "comm_id {:?} collective alltoall_matrix mismatch across ranks",
+                            comm_id
+                        );
+                    }
                     if !entry.arrived.contains(&rank_id) {
                         entry.arrived.push(rank_id);
                     }
-                    if entry.arrived.len() == entry.hosts.len() {
-                        let entry = st
+                    if entry.arrived.len() >= collective_quorum_count(entry.hosts.len(), quorum) {
+                        let mut entry = st
                             .pending_collectives
                             .remove(&comm_id)
                             .expect("pending collective missing");
+                        if entry.arrived.len() < entry.hosts.len() {
+                            let late = st.late_collectives.entry(comm_id.clone()).or_default();
+                            for h in entry.hosts.iter().filter(|h| !entry.arrived.contains(h)) {
+                                *late.entry(*h).or_insert(0) += 1;
+                            }
+                            let keep = entry
+                                .hosts
+                                .iter()
+                                .map(|h| entry.arrived.contains(h))
+                                .collect::<Vec<_>>();
+                            if let Some(matrix) = &mut entry.alltoall_matrix {
+                                *matrix = retain_matrix(matrix, &keep);
+                            }
+                            entry.hosts.retain(|h| entry.arrived.contains(h));
+                        }
                         if entry.comm_bytes == 0 || entry.hosts.len() <= 1 {
                             start_cfg = Some((
                                 None,
@@ -573,6 +1200,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                                 Some(entry.op),
                                 entry.is_async,
                                 entry.comm_stream,
+                                entry.decompress_ns,
                             ));
                         } else {
                             let host_nodes = entry
@@ -593,20 +1221,29 @@ impl htsim_rs::sim::Event for StartRankStep {
                             let start_flow_id = st.next_flow_id;
                             st.next_flow_id = st.next_flow_id.saturating_add(flow_span);
                             start_cfg = Some((
-                                Some((start_flow_id, host_nodes, algo)),
+                                Some((start_flow_id, host_nodes, algo, entry.alltoall_matrix)),
                                 entry.hosts,
                                 entry.comm_bytes,
                                 Some(comm_id.clone()),
                                 Some(entry.op),
                                 entry.is_async,
                                 entry.comm_stream,
+                                entry.decompress_ns,
                             ));
                         }
                     }
                 }
 
-                if let Some((start_cfg, hosts, bytes, comm_id, op, is_async, comm_stream)) =
-                    start_cfg
+                if let Some((
+                    start_cfg,
+                    hosts,
+                    bytes,
+                    comm_id,
+                    op,
+                    is_async,
+                    comm_stream,
+                    decompress_ns,
+                )) = start_cfg
                 {
                     if bytes == 0 || hosts.len() <= 1 {
                         if !is_async {
@@ -623,18 +1260,32 @@ impl htsim_rs::sim::Event for StartRankStep {
                         }
                         return;
                     }
-                    let (start_flow_id, host_nodes, algo) =
+                    let (start_flow_id, host_nodes, algo, alltoall_matrix) =
                         start_cfg.expect("ring allreduce config missing");
                     let chunk_bytes = algo.chunk_bytes(bytes, host_nodes.len());
+                    let (tcp_cfg, dctcp_cfg) = step_transport_cfgs(&step, tcp_cfg, dctcp_cfg);
+                    let tenant = state
+                        .lock()
+                        .expect("rank workload state lock")
+                        .tenant_of(rank_id);
                     let transport: Box<dyn RingTransport> = match protocol {
-                        TransportProtocol::Tcp => Box::new(TcpRingTransport { cfg: tcp_cfg }),
-                        TransportProtocol::Dctcp => Box::new(DctcpRingTransport { cfg: dctcp_cfg }),
+                        TransportProtocol::Tcp => Box::new(TcpRingTransport {
+                            cfg: tcp_cfg,
+                            tenant,
+                        }),
+                        TransportProtocol::Dctcp => Box::new(DctcpRingTransport {
+                            cfg: dctcp_cfg,
+                            tenant,
+                        }),
                     };
                     let done_cb: Option<ring::RingAllreduceDoneCallback> = if is_async {
                         let done_state = Arc::clone(&state);
                         let done_hosts = hosts.clone();
                         let done_comm_stream = comm_stream;
+                        let done_comm_id = comm_id.clone().unwrap_or_default();
                         Some(Box::new(move |now, sim| {
+                            release_nic_streams(&done_state, &done_hosts, done_comm_stream, sim);
+                            let wake_at = SimTime(now.0.saturating_add(decompress_ns));
                             let mut wake = Vec::new();
                             {
                                 let mut st = done_state.lock().expect("rank workload state lock");
@@ -654,6 +1305,16 @@ impl htsim_rs::sim::Event for StartRankStep {
                                     if remove_stream {
                                         rank_state.pending_async_by_stream.remove(&done_comm_stream);
                                     }
+                                    let mut remove_comm_id = false;
+                                    if let Some(counter) =
+                                        rank_state.pending_async_by_comm_id.get_mut(&done_comm_id)
+                                    {
+                                        *counter = counter.saturating_sub(1);
+                                        remove_comm_id = *counter == 0;
+                                    }
+                                    if remove_comm_id {
+                                        rank_state.pending_async_by_comm_id.remove(&done_comm_id);
+                                    }
                                     let should_wake = match rank_state.waiting_for_async {
                                         AsyncWaitKind::None => false,
                                         AsyncWaitKind::All => rank_state.pending_async_total == 0,
@@ -663,6 +1324,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                                             .copied()
                                             .unwrap_or(0)
                                             == 0,
+                                        AsyncWaitKind::Deps => rank_state
+                                            .steps
+                                            .get(rank_state.idx)
+                                            .is_none_or(|step| !has_pending_deps(step, rank_state)),
                                     };
                                     if should_wake {
                                         rank_state.waiting_for_async = AsyncWaitKind::None;
@@ -672,7 +1337,7 @@ impl htsim_rs::sim::Event for StartRankStep {
                             }
                             for hid in wake {
                                 sim.schedule(
-                                    now,
+                                    wake_at,
                                     StartRankStep {
                                         rank_id: hid,
                                         state: Arc::clone(&done_state),
@@ -684,9 +1349,11 @@ impl htsim_rs::sim::Event for StartRankStep {
                         let done_state = Arc::clone(&state);
                         let done_hosts = hosts.clone();
                         Some(Box::new(move |now, sim| {
+                            release_nic_streams(&done_state, &done_hosts, comm_stream, sim);
+                            let wake_at = SimTime(now.0.saturating_add(decompress_ns));
                             for hid in &done_hosts {
                                 sim.schedule(
-                                    now,
+                                    wake_at,
                                     StartRankStep {
                                         rank_id: *hid,
                                         state: Arc::clone(&done_state),
@@ -707,6 +1374,14 @@ impl htsim_rs::sim::Event for StartRankStep {
                         start_flow_id,
                         transport,
                         done_cb,
+                        tags: collective_flow_tags(
+                            step.label.as_deref(),
+                            comm_id.as_deref(),
+                            op.as_deref(),
+                        ),
+                        direction: ring::RingDirection::Clockwise,
+                        rotation: 0,
+                        reduce_ns_per_byte: 0.0,
                     };
                     let handle = match algo {
                         CollectiveOp::Allreduce => {
@@ -718,8 +1393,15 @@ impl htsim_rs::sim::Event for StartRankStep {
                         CollectiveOp::Reducescatter => {
                             ring::start_ring_reducescatter_at(sim, cfg, sim.now())
                         }
-                        CollectiveOp::Alltoall => ring::start_ring_alltoall_at(sim, cfg, sim.now()),
+                        CollectiveOp::Alltoall => match alltoall_matrix {
+                            Some(m) => ring::start_ring_alltoallv_at(sim, cfg, m, sim.now()),
+                            None => ring::start_ring_alltoall_at(sim, cfg, sim.now()),
+                        },
                     };
+                    {
+                        let mut st = state.lock().expect("rank workload state lock");
+                        st.nic.admit(hosts.clone(), comm_stream, &handle);
+                    }
                     let record = CollectiveRecord {
                         step_id: step.id,
                         label: step.label.clone(),
@@ -734,6 +1416,64 @@ impl htsim_rs::sim::Event for StartRankStep {
                     }
                 }
             }
+            RankStepKind::Barrier => {
+                let comm_id = match step.comm_id.clone() {
+                    Some(id) => id,
+                    None => {
+                        sim.schedule(
+                            sim.now(),
+                            StartRankStep {
+                                rank_id,
+                                state: Arc::clone(&state),
+                            },
+                        );
+                        return;
+                    }
+                };
+                let hosts = step.hosts.clone().unwrap_or_else(|| hosts_all.clone());
+                if !hosts.contains(&rank_id) {
+                    panic!(
+                        "rank {} not included in barrier hosts for comm_id {:?}: hosts={:?}",
+                        rank_id, comm_id, hosts
+                    );
+                }
+
+                let release = {
+                    let mut st = state.lock().expect("rank workload state lock");
+                    let entry = st
+                        .pending_barriers
+                        .entry(comm_id.clone())
+                        .or_insert_with(|| BarrierWait {
+                            hosts: hosts.clone(),
+                            arrived: Vec::new(),
+                        });
+                    if entry.hosts != hosts {
+                        panic!(
+                            "comm_id {:?} barrier hosts mismatch: existing hosts={:?} vs new hosts={:?}",
+                            comm_id, entry.hosts, hosts
+                        );
+                    }
+                    if !entry.arrived.contains(&rank_id) {
+                        entry.arrived.push(rank_id);
+                    }
+                    if entry.arrived.len() == entry.hosts.len() {
+                        st.pending_barriers.remove(&comm_id).map(|b| b.hosts)
+                    } else {
+                        None
+                    }
+                };
+
+                // The last arrival releases every member at once; no flows are started.
+                for hid in release.into_iter().flatten() {
+                    sim.schedule(
+                        sim.now(),
+                        StartRankStep {
+                            rank_id: hid,
+                            state: Arc::clone(&state),
+                        },
+                    );
+                }
+            }
             RankStepKind::Sendrecv => {
                 let comm_id = match step.comm_id.clone() {
                     Some(id) => id,
@@ -757,9 +1497,10 @@ impl htsim_rs::sim::Event for StartRankStep {
                     let mut st = state.lock().expect("rank workload state lock");
                     let entry = st
                         .pending_sendrecv
-                        .entry(comm_id.clone())
+                        .entry((comm_id.clone(), step.tag))
                         .or_insert_with(|| SendRecvWait {
                             comm_bytes,
+                            protocol: None,
                             sender: None,
                             receiver: None,
                             arrived: Vec::new(),
@@ -770,6 +1511,15 @@ impl htsim_rs::sim::Event for StartRankStep {
                             comm_id, entry.comm_bytes, comm_bytes
                         );
                     }
+                    if let Some(p) = step.protocol {
+                        match entry.protocol {
+                            Some(existing) if existing != p => panic!(
+                                "comm_id {:?} sendrecv protocol mismatch: existing {:?} vs new {:?}",
+                                comm_id, existing, p
+                            ),
+                            _ => entry.protocol = Some(p),
+                        }
+                    }
                     if !entry.arrived.contains(&rank_id) {
                         entry.arrived.push(rank_id);
                     }
@@ -836,17 +1586,27 @@ impl htsim_rs::sim::Event for StartRankStep {
                         };
                         let entry = st
                             .pending_sendrecv
-                            .remove(&comm_id)
+                            .remove(&(comm_id.clone(), step.tag))
                             .expect("pending sendrecv missing");
                         let src = *st.host_map.get(&sender).expect("unknown host id");
                         let dst = *st.host_map.get(&receiver).expect("unknown host id");
                         let flow_id = st.next_flow_id;
                         st.next_flow_id = st.next_flow_id.saturating_add(1);
-                        start_cfg = Some((sender, receiver, entry.comm_bytes, flow_id, src, dst));
+                        w.net.set_flow_tenant(flow_id, st.tenant_of(sender));
+                        let protocol = entry.protocol.unwrap_or(st.protocol);
+                        start_cfg = Some((
+                            sender,
+                            receiver,
+                            entry.comm_bytes,
+                            flow_id,
+                            src,
+                            dst,
+                            protocol,
+                        ));
                     }
                 }
 
-                if let Some((sender, receiver, bytes, flow_id, src, dst)) = start_cfg {
+                if let Some((sender, receiver, bytes, flow_id, src, dst, protocol)) = start_cfg {
                     let done_state = Arc::clone(&state);
                     let done_cb: ring::RingDoneCallback = Box::new(move |now, sim| {
                         for hid in [sender, receiver] {
@@ -869,6 +1629,29 @@ impl htsim_rs::sim::Event for StartRankStep {
                     );
                 }
             }
+            RankStepKind::Rpc => {
+                let peer = step
+                    .peer
+                    .unwrap_or_else(|| panic!("rank {rank_id} rpc step needs a peer"));
+                let rpc_idx = {
+                    let mut st = state.lock().expect("rank workload state lock");
+                    if !st.host_map.contains_key(&peer) {
+                        panic!("rank {rank_id} rpc step has unknown peer {peer}");
+                    }
+                    st.rpcs.push(RpcRecord {
+                        rank: rank_id,
+                        peer,
+                        label: step.label.clone(),
+                        req_bytes: step.req_bytes.unwrap_or(0),
+                        resp_bytes: step.resp_bytes.unwrap_or(0),
+                        protocol,
+                        start_ns: sim.now().0,
+                        end_ns: None,
+                    });
+                    st.rpcs.len() - 1
+                };
+                start_rpc_leg(sim, w, &state, rpc_idx, false);
+            }
         }
     }
 }
@@ -926,6 +1709,20 @@ fn topology_eq(a: &TopologySpec, b: &TopologySpec) -> bool {
     }
 }
 
+/// Per-tenant weighted DRR at every switch egress, tenant `i` weighted `weights[i]`
+/// (no-op when `weights` is empty).
+fn apply_tenant_weights(net: &mut Network, weights: &[u32]) {
+    if weights.is_empty() {
+        return;
+    }
+    net.set_switch_egress_scheduler(EgressScheduler::TenantDrr {
+        quantum_bytes: DEFAULT_PKT_BYTES,
+    });
+    for (tenant, &weight) in weights.iter().enumerate() {
+        net.set_tenant_weight(tenant as u32, weight);
+    }
+}
+
 fn build_topology(world: &mut NetWorld, topo: &TopologySpec) -> Vec<NodeId> {
     match topo {
         TopologySpec::Dumbbell {
@@ -951,6 +1748,7 @@ fn build_topology(world: &mut NetWorld, topo: &TopologySpec) -> Vec<NodeId> {
                 k: *k as usize,
                 link_gbps: link_gbps.unwrap_or(100),
                 link_latency: SimTime::from_micros(link_latency_us.unwrap_or(2)),
+                oversubscription: 1,
             };
             let topo = build_fat_tree(world, &opts);
             topo.hosts
@@ -1016,7 +1814,10 @@ fn remap_rank_steps(
                 })
                 .collect::<Vec<_>>();
             s.hosts = Some(mapped);
-        } else if matches!(rank_step_kind(&s), RankStepKind::Collective) {
+        } else if matches!(
+            rank_step_kind(&s),
+            RankStepKind::Collective | RankStepKind::Barrier
+        ) {
             s.hosts = Some(default_hosts.to_vec());
         }
         if let Some(comm_id) = &s.comm_id {
@@ -1030,6 +1831,27 @@ fn remap_rank_steps(
     out
 }
 
+/// Collectives that launched but never finished, e.g. because a member flow was failed.
+fn stuck_collectives(list: &[CollectiveRecord]) -> Vec<String> {
+    list.iter()
+        .filter_map(|record| {
+            let stats = record.handle.stats();
+            if stats.done_at.is_some() {
+                return None;
+            }
+            Some(format!(
+                "step_id={:?} label={:?} comm_id={:?} op={:?} hosts={} pending_flows={:?}",
+                record.step_id,
+                record.label,
+                record.comm_id,
+                record.op,
+                record.hosts,
+                stats.pending_flows
+            ))
+        })
+        .collect()
+}
+
 fn main() {
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -1097,6 +1919,18 @@ fn main() {
     world
         .net
         .set_host_egress_queue_capacity_bytes(host_queue_bytes);
+    if args.host_egress_flow_wrr {
+        for node in &topo_hosts {
+            world
+                .net
+                .set_egress_scheduler(*node, EgressScheduler::FlowWrr);
+        }
+    }
+    apply_tenant_weights(&mut world.net, &args.tenant_weights);
+    world
+        .net
+        .validate_queue_capacities()
+        .unwrap_or_else(|err| panic!("invalid queue configuration: {err}"));
 
     let defaults_first = workloads[0].1.defaults.clone().unwrap_or(WorkloadDefaults {
         protocol: Some(TransportProtocol::Tcp),
@@ -1147,9 +1981,20 @@ fn main() {
         CcRoutingMode::PerFlow => EcmpHashMode::Flow,
         CcRoutingMode::PerPacket => EcmpHashMode::Packet,
     });
+    if let Some(seed) = args.ecmp_seed {
+        world.net.set_ecmp_seed(seed);
+    }
 
-    if args.viz_json.is_some() {
-        world.net.viz = Some(VizLogger::default());
+    if args.viz_json.is_some() || args.viz_throughput_json.is_some() {
+        let mut v = VizLogger::default();
+        if args.viz_json.is_none() {
+            // Only the throughput aggregate is wanted; keep no raw events.
+            v = v.with_max_events(0);
+        }
+        if args.viz_throughput_json.is_some() {
+            v = v.with_throughput_bin(SimTime::from_micros(args.viz_throughput_bin_us));
+        }
+        world.net.viz = Some(v);
         world.net.emit_viz_meta();
     }
 
@@ -1174,7 +2019,7 @@ fn main() {
         let fallback_gpu = w.meta.as_ref().and_then(|m| m.device.clone());
         let mut gpu_by_old = HashMap::new();
         for h in &w.hosts {
-            gpu_by_old.insert(h.id, h.gpu.as_ref().map(|g| g.model.clone()));
+            gpu_by_old.insert(h.id, h.gpu.clone());
         }
 
         let mut dc_hist = vec![0usize; dc_count];
@@ -1216,10 +2061,12 @@ fn main() {
             dc_hist[dc_used] = dc_hist[dc_used].saturating_add(1);
 
             host_map.insert(new_id, topo_hosts[topo_index]);
-            let gpu = gpu_by_old
-                .get(old_id)
-                .and_then(|g| g.clone())
-                .or_else(|| fallback_gpu.clone());
+            let gpu = gpu_by_old.get(old_id).and_then(|g| g.clone()).or_else(|| {
+                fallback_gpu.clone().map(|model| GpuSpec {
+                    model,
+                    peak_flops: None,
+                })
+            });
             gpu_map.insert(new_id, gpu);
         }
 
@@ -1251,11 +2098,14 @@ fn main() {
             ranks.insert(
                 new_rank_id,
                 RankState {
+                    tenant: tenant_idx as u32,
                     steps,
                     idx: 0,
                     pending_async_total: 0,
                     pending_async_by_stream: HashMap::new(),
+                    pending_async_by_comm_id: HashMap::new(),
                     waiting_for_async: AsyncWaitKind::None,
+                    timeline: Vec::new(),
                 },
             );
         }
@@ -1263,6 +2113,17 @@ fn main() {
         next_dc_start = (next_dc_start + 1) % dc_count;
     }
 
+    assert!(
+        args.collective_quorum > 0.0 && args.collective_quorum <= 1.0,
+        "--collective-quorum must be in (0, 1], got {}",
+        args.collective_quorum
+    );
+
+    let step_filter = StepFilter {
+        stop_at_label: args.stop_at_label.clone(),
+        only_labels: args.only_labels.clone(),
+    };
+
     let state = Arc::new(Mutex::new(RankWorkloadState {
         ranks,
         hosts_all: hosts_all.clone(),
@@ -1274,8 +2135,14 @@ fn main() {
         tcp_cfg: default_tcp_cfg(),
         dctcp_cfg: DctcpConfig::default(),
         pending_collectives: HashMap::new(),
+        collective_quorum: args.collective_quorum,
+        late_collectives: HashMap::new(),
+        nic: NicStreams::new(args.nic_streams),
         pending_sendrecv: HashMap::new(),
+        pending_barriers: HashMap::new(),
+        rpcs: Vec::new(),
         collective_handles: Arc::clone(&collective_handles),
+        step_filter,
     }));
 
     for rank_id in hosts_all {
@@ -1294,7 +2161,14 @@ fn main() {
         sim.run(&mut world);
     }
 
-    if args.until_ms.is_none() {
+    if let Ok(list) = collective_handles.lock() {
+        for stuck in stuck_collectives(&list) {
+            eprintln!("stuck collective: {stuck}");
+        }
+    }
+
+    // Halting at a label legitimately leaves collectives unresolved.
+    if args.until_ms.is_none() && args.stop_at_label.is_none() {
         let st = state.lock().expect("rank workload state lock");
         if !st.pending_collectives.is_empty() {
             let keys = st.pending_collectives.keys().cloned().collect::<Vec<_>>();
@@ -1304,6 +2178,19 @@ fn main() {
             let keys = st.pending_sendrecv.keys().cloned().collect::<Vec<_>>();
             panic!("unresolved sendrecv at end of sim: {keys:?}");
         }
+        if !st.pending_barriers.is_empty() {
+            let keys = st.pending_barriers.keys().cloned().collect::<Vec<_>>();
+            panic!("unresolved barriers at end of sim: {keys:?}");
+        }
+        let pending_rpcs = st
+            .rpcs
+            .iter()
+            .filter(|rpc| rpc.end_ns.is_none())
+            .map(|rpc| (rpc.rank, rpc.peer))
+            .collect::<Vec<_>>();
+        if !pending_rpcs.is_empty() {
+            panic!("unresolved rpcs at end of sim: {pending_rpcs:?}");
+        }
         let pending_async = st
             .ranks
             .iter()
@@ -1329,7 +2216,7 @@ fn main() {
                     .done_at
                     .map(|d| d.0.saturating_sub(start.0))
                     .unwrap_or(0);
-                let p99_ns = percentile_ns(&stats.flow_fct_ns, 0.99).unwrap_or(0);
+                let p99_ns = percentile(&stats.flow_fct_ns, 0.99).unwrap_or(0);
                 let max_flow_ns = stats.flow_fct_ns.iter().copied().max().unwrap_or(0);
                 let makespan_ms = fct_ns as f64 / 1_000_000.0;
                 let p99_ms = p99_ns as f64 / 1_000_000.0;
@@ -1349,6 +2236,46 @@ fn main() {
                 );
             }
         }
+        let st = state.lock().expect("rank workload state lock");
+        for rpc in &st.rpcs {
+            let Some(end_ns) = rpc.end_ns else {
+                continue;
+            };
+            let latency_ms = end_ns.saturating_sub(rpc.start_ns) as f64 / 1_000_000.0;
+            println!(
+                "rpc_latency rank={} peer={} label={:?} req_bytes={} resp_bytes={} latency_ms={:.6}",
+                rpc.rank, rpc.peer, rpc.label, rpc.req_bytes, rpc.resp_bytes, latency_ms
+            );
+        }
+    }
+
+    if let Some(path) = &args.fct_csv {
+        let rows = collective_handles
+            .lock()
+            .map(|list| fct_rows(&list))
+            .unwrap_or_default();
+        fs::write(path, fct_csv(&rows)).expect("write fct csv");
+        eprintln!("wrote collective fct stats to {}", path.display());
+    }
+
+    if args.timeline_json.is_some() || args.timeline_csv.is_some() {
+        let rows = timeline_rows(&state.lock().expect("rank workload state lock"));
+        if let Some(path) = &args.timeline_json {
+            let json = serde_json::to_string_pretty(&rows).expect("serialize timeline");
+            fs::write(path, json).expect("write timeline json");
+            eprintln!("wrote rank timeline to {}", path.display());
+        }
+        if let Some(path) = &args.timeline_csv {
+            fs::write(path, timeline_csv(&rows)).expect("write timeline csv");
+            eprintln!("wrote rank timeline to {}", path.display());
+        }
+    }
+
+    if let (Some(path), Some(v)) = (&args.viz_throughput_json, &world.net.viz) {
+        let series = v.throughput_series(SimTime::from_micros(args.viz_throughput_bin_us));
+        let json = serde_json::to_string_pretty(&series).expect("serialize throughput series");
+        fs::write(path, json).expect("write throughput json");
+        eprintln!("wrote throughput series to {}", path.display());
     }
 
     if let Some(path) = args.viz_json {
@@ -1358,6 +2285,10 @@ fn main() {
             eprintln!("wrote viz events to {}", path.display());
         }
     }
+
+    if args.sim_stats {
+        println!("sim_stats {}", sim.metrics());
+    }
 }
 
 #[cfg(test)]
@@ -1371,12 +2302,23 @@ mod tests {
             kind: Some(RankStepKind::Sendrecv),
             op: None,
             compute_ms: None,
+            flops: None,
             comm_bytes: Some(123),
             comm_id: Some("comm".to_string()),
             comm_stream: None,
+            depends_on: Vec::new(),
             hosts: None,
             peer: Some(peer),
             direction: Some(direction),
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }
     }
 
@@ -1387,12 +2329,23 @@ mod tests {
             kind: Some(RankStepKind::Collective),
             op: Some(op.to_string()),
             compute_ms: None,
+            flops: None,
             comm_bytes: Some(456),
             comm_id: Some("cid".to_string()),
             comm_stream: None,
+            depends_on: Vec::new(),
             hosts: None,
             peer: None,
             direction: None,
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }
     }
 
@@ -1406,12 +2359,23 @@ mod tests {
                 kind: Some(RankStepKind::Collective),
                 op: Some("allreduce".to_string()),
                 compute_ms: None,
+                flops: None,
                 comm_bytes: Some(10),
                 comm_id: Some("x".to_string()),
                 comm_stream: None,
+                depends_on: Vec::new(),
                 hosts: Some(vec![0, 1]),
                 peer: None,
                 direction: None,
+                tag: None,
+                req_bytes: None,
+                resp_bytes: None,
+                compression: None,
+                compression_ms: None,
+                alltoall_matrix: None,
+                init_cwnd_bytes: None,
+                init_ssthresh_bytes: None,
+                protocol: None,
             },
             step_collective_without_hosts("allgather"),
         ];
@@ -1458,15 +2422,95 @@ mod tests {
             kind: Some(RankStepKind::Collective),
             op: Some("allreduce".to_string()),
             compute_ms: None,
+            flops: None,
             comm_bytes: Some(10),
             comm_id: Some("x".to_string()),
             comm_stream: None,
+            depends_on: Vec::new(),
             hosts: Some(vec![123]),
             peer: None,
             direction: None,
+            tag: None,
+            req_bytes: None,
+            resp_bytes: None,
+            compression: None,
+            compression_ms: None,
+            alltoall_matrix: None,
+            init_cwnd_bytes: None,
+            init_ssthresh_bytes: None,
+            protocol: None,
         }];
         let id_map = HashMap::new();
         let default_hosts = vec![];
         let _ = remap_rank_steps(1, &steps, &id_map, &default_hosts);
     }
+
+    /// Tenant 0 runs three collective flows h0 -> h2 and tenant 1 a single flow
+    /// h1 -> h3, all crossing the s0 -> s1 1Gbps bottleneck. Returns the bytes each
+    /// tenant received between 250us and 2ms.
+    fn two_tenant_bytes(weights: &[u32]) -> (u64, u64) {
+        let mut sim = Simulator::default();
+        let mut world = NetWorld::default();
+        let (h0, h1) = (world.net.add_host("h0"), world.net.add_host("h1"));
+        let (s0, s1) = (world.net.add_switch("s0"), world.net.add_switch("s1"));
+        let (h2, h3) = (world.net.add_host("h2"), world.net.add_host("h3"));
+        let latency = SimTime::from_micros(1);
+        for (a, b, bps) in [
+            (h0, s0, 100_000_000_000),
+            (h1, s0, 100_000_000_000),
+            (s0, s1, 1_000_000_000),
+            (s1, h2, 100_000_000_000),
+            (s1, h3, 100_000_000_000),
+        ] {
+            world.net.connect(a, b, latency, bps);
+            world.net.connect(b, a, latency, bps);
+        }
+        world.net.set_all_link_queue_capacity_bytes(10_000_000);
+        apply_tenant_weights(&mut world.net, weights);
+
+        for (flow_id, tenant, src, dst) in [
+            (1, 0, h0, h2),
+            (2, 0, h0, h2),
+            (3, 0, h0, h2),
+            (4, 1, h1, h3),
+        ] {
+            let mut transport = TcpRingTransport {
+                cfg: default_tcp_cfg(),
+                tenant,
+            };
+            transport.start_flow(
+                flow_id,
+                src,
+                dst,
+                10_000_000,
+                CcRoutingMode::PerFlow,
+                &mut sim,
+                &mut world,
+                Box::new(|_, _| {}),
+            );
+        }
+        assert_eq!(world.net.flow_tenant(3), 0);
+        assert_eq!(world.net.flow_tenant(4), 1);
+
+        sim.run_until(SimTime::from_micros(250), &mut world);
+        let start = (world.net.host_rx_bytes(h2), world.net.host_rx_bytes(h3));
+        sim.run_until(SimTime::from_millis(2), &mut world);
+        (
+            world.net.host_rx_bytes(h2) - start.0,
+            world.net.host_rx_bytes(h3) - start.1,
+        )
+    }
+
+    #[test]
+    fn tenant_weights_split_the_bottleneck_by_tenant_not_flow_count() {
+        // Weights 1:2, so tenant 1's single flow gets twice tenant 0's three flows.
+        let (t0, t1) = two_tenant_bytes(&[1, 2]);
+        let ratio = t1 as f64 / t0 as f64;
+        assert!((1.8..=2.2).contains(&ratio), "weighted t0={t0} t1={t1}");
+
+        // Without tenant weights the switch queue is shared FIFO and tenant 0's
+        // three flows take the larger share.
+        let (t0, t1) = two_tenant_bytes(&[]);
+        assert!(t0 > 2 * t1, "fifo t0={t0} t1={t1}");
+    }
 }
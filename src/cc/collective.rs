@@ -1,5 +1,16 @@
 //! Helpers for collective communication operations.
 
+use crate::sim::SimTime;
+
+/// Analytical estimate of one collective, from `CollectiveOp::cost_model`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// Completion time assuming every step runs at full link rate with no contention.
+    pub time_ns: u64,
+    /// Bytes sent by all ranks together.
+    pub bytes_on_wire: u64,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CollectiveOp {
     Allreduce,
@@ -47,6 +58,34 @@ impl CollectiveOp {
             Self::Alltoall => div_ceil(comm_bytes, ranks.max(1) as u64),
         }
     }
+
+    /// Alpha-beta cost of the ring algorithm this crate runs for `self`.
+    ///
+    /// Each of the `total_steps` steps moves one chunk per rank over a `link_gbps`
+    /// link and pays `latency` once, e.g. ring allreduce takes
+    /// `2*(n-1)/n * bytes / bw + 2*(n-1) * latency`. Chunks are not rounded up to
+    /// whole bytes for the time estimate; `bytes_on_wire` uses `chunk_bytes`.
+    pub fn cost_model(
+        self,
+        ranks: usize,
+        bytes: u64,
+        link_gbps: f64,
+        latency: SimTime,
+    ) -> CostEstimate {
+        let steps = self.total_steps(ranks) as f64;
+        let n = ranks.max(1) as f64;
+        let chunk = match self {
+            Self::Allreduce | Self::Reducescatter | Self::Alltoall => bytes as f64 / n,
+            Self::Allgather => bytes as f64,
+        };
+        let chunk_ns = chunk * 8.0 / link_gbps;
+        CostEstimate {
+            time_ns: (steps * (chunk_ns + latency.0 as f64)).round() as u64,
+            bytes_on_wire: (self.total_steps(ranks) as u64)
+                .saturating_mul(self.chunk_bytes(bytes, ranks))
+                .saturating_mul(ranks as u64),
+        }
+    }
 }
 
 fn div_ceil(n: u64, d: u64) -> u64 {
@@ -1,18 +1,29 @@
 //! Ring-based collective communication algorithms.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
 
-use crate::net::{NetWorld, NodeId};
+use crate::net::{FlowTags, NetWorld, NodeId};
 use crate::sim::{Event, SimTime, Simulator, World};
 
 /// Routing policy used by ring collectives.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum RoutingMode {
     PerFlow,
     PerPacket,
 }
 
+/// Direction in which data travels around the ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RingDirection {
+    /// Rank i sends to rank (i+1) % n.
+    #[default]
+    Clockwise,
+    /// Rank i sends to rank (i-1+n) % n.
+    CounterClockwise,
+}
+
 /// Callback invoked when a flow finishes.
 pub type RingDoneCallback = Box<dyn Fn(SimTime, &mut Simulator) + Send>;
 /// Callback invoked when a ring collective finishes.
@@ -33,6 +44,8 @@ pub trait RingTransport: Send + 'static {
     );
 }
 
+type SharedTransport = Arc<Mutex<Box<dyn RingTransport>>>;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum DstMode {
     /// Each rank sends to its immediate successor (rank+1).
@@ -47,8 +60,18 @@ struct State {
     chunk_bytes: u64,
     routing: RoutingMode,
     dst_mode: DstMode,
+    direction: RingDirection,
+    rotation: usize,
+    reduce_ns_per_byte: f64,
+    /// Per-pair bytes (`[src][dst]`, ring positions) overriding `chunk_bytes`.
+    pair_bytes: Option<Vec<Vec<u64>>>,
     step: usize,
-    inflight: usize,
+    step_start_at: SimTime,
+    /// Step barrier: flow ids of the current step that have not reported done.
+    /// The step ends when it empties; done calls for other ids are ignored.
+    step_pending: BTreeSet<u64>,
+    /// Flow id of the first flow of step 0.
+    first_flow_id: u64,
     next_flow_id: u64,
     total_steps: usize,
     reduce_steps: usize,
@@ -57,13 +80,42 @@ struct State {
     done_at: Option<SimTime>,
     flow_start_at: HashMap<u64, SimTime>,
     flow_fct_ns: Vec<u64>,
+    /// Bytes handed to the transport by reduce-phase / later steps.
+    reduce_bytes: u64,
+    gather_bytes: u64,
+    step_transfer_ns: Vec<u64>,
+    step_compute_ns: Vec<u64>,
+    step_done_times: Vec<SimTime>,
     done_cb: Option<RingAllreduceDoneCallback>,
+    tags: FlowTags,
+    /// `Network::next_collective_id`, taken when the first step starts.
+    collective_id: Option<u64>,
+    /// Set by `RingAllreduceHandle::pause`; no new step starts while true.
+    paused: bool,
+    /// Transport and time of the step start that was held back by a pause.
+    parked: Option<(SharedTransport, SimTime)>,
+    /// Total time the collective sat idle between a parked step and `resume`.
+    paused_ns: u64,
 }
 
 impl State {
     fn total_steps(&self) -> usize {
         self.total_steps
     }
+
+    /// Time spent reducing the received chunk after `step`'s transfers finish.
+    fn reduce_compute_ns(&self, step: usize) -> u64 {
+        if step >= self.reduce_steps || self.reduce_ns_per_byte <= 0.0 {
+            return 0;
+        }
+        (self.reduce_ns_per_byte * self.chunk_bytes as f64).ceil() as u64
+    }
+
+    /// Flow ids of the first `steps` steps, which use `ranks` consecutive ids each.
+    fn flow_ids_before(&self, steps: usize) -> u64 {
+        self.first_flow_id
+            .saturating_add((steps as u64).saturating_mul(self.ranks as u64))
+    }
 }
 
 struct StepContext {
@@ -73,17 +125,58 @@ struct StepContext {
     routing: RoutingMode,
     step: usize,
     dst_mode: DstMode,
+    direction: RingDirection,
+    rotation: usize,
+    pair_bytes: Option<Vec<Vec<u64>>>,
     start_flow_id: u64,
+    tags: FlowTags,
+    collective_id: u64,
+}
+
+impl StepContext {
+    /// Ring position the `idx`-th flow of a step is sent from.
+    fn src_rank(&self, idx: usize) -> usize {
+        (idx + self.rotation) % self.ranks
+    }
+
+    /// Ring position `rank` sends to in this step.
+    fn dst_rank(&self, rank: usize) -> usize {
+        let shift = match self.dst_mode {
+            DstMode::Neighbor => 1,
+            DstMode::ShiftByStep => self.step + 1,
+        } % self.ranks;
+        match self.direction {
+            RingDirection::Clockwise => (rank + shift) % self.ranks,
+            RingDirection::CounterClockwise => (rank + self.ranks - shift) % self.ranks,
+        }
+    }
+
+    fn flow_bytes(&self, src: usize, dst: usize) -> u64 {
+        match &self.pair_bytes {
+            Some(m) => m[src][dst],
+            None => self.chunk_bytes,
+        }
+    }
+
+    /// Bytes sent by all flows of this step.
+    fn step_bytes(&self) -> u64 {
+        (0..self.ranks)
+            .map(|idx| {
+                let rank = self.src_rank(idx);
+                self.flow_bytes(rank, self.dst_rank(rank))
+            })
+            .sum()
+    }
 }
 
 struct StartStep {
     state: Arc<Mutex<State>>,
-    transport: Arc<Mutex<Box<dyn RingTransport>>>,
+    transport: SharedTransport,
 }
 
 struct FlowDone {
     state: Arc<Mutex<State>>,
-    transport: Arc<Mutex<Box<dyn RingTransport>>>,
+    transport: SharedTransport,
     flow_id: u64,
     done_at: SimTime,
 }
@@ -120,39 +213,59 @@ impl Event for StartStep {
                 }
                 return;
             }
+            if st.paused {
+                // Hold the step back until `resume` reschedules it.
+                st.parked = Some((transport, sim.now()));
+                return;
+            }
             if st.start_at.is_none() {
                 st.start_at = Some(sim.now());
             }
-            st.inflight = st.ranks;
             let start_flow_id = st.next_flow_id;
             st.next_flow_id = st.next_flow_id.saturating_add(st.ranks as u64);
+            st.step_pending = (start_flow_id..st.next_flow_id).collect();
+            let collective_id = *st
+                .collective_id
+                .get_or_insert_with(|| w.net.next_collective_id());
             let step_start = sim.now();
+            st.step_start_at = step_start;
             for rank in 0..st.ranks {
                 let flow_id = start_flow_id.saturating_add(rank as u64);
                 st.flow_start_at.insert(flow_id, step_start);
             }
-            StepContext {
+            let ctx = StepContext {
                 ranks: st.ranks,
                 hosts: st.hosts.clone(),
                 chunk_bytes: st.chunk_bytes,
                 routing: st.routing,
                 step: st.step,
                 dst_mode: st.dst_mode,
+                direction: st.direction,
+                rotation: st.rotation,
+                pair_bytes: st.pair_bytes.clone(),
                 start_flow_id,
+                tags: st.tags.clone(),
+                collective_id,
+            };
+            let step_bytes = ctx.step_bytes();
+            if st.step < st.reduce_steps {
+                st.reduce_bytes = st.reduce_bytes.saturating_add(step_bytes);
+            } else {
+                st.gather_bytes = st.gather_bytes.saturating_add(step_bytes);
             }
+            ctx
         };
 
         let transport_arc = Arc::clone(&transport);
         let mut transport = transport_arc.lock().expect("ring transport lock");
 
-        for rank in 0..ctx.ranks {
-            let flow_id = ctx.start_flow_id.saturating_add(rank as u64);
+        for idx in 0..ctx.ranks {
+            let flow_id = ctx.start_flow_id.saturating_add(idx as u64);
+            let rank = ctx.src_rank(idx);
+            let dst_rank = ctx.dst_rank(rank);
             let src = ctx.hosts[rank];
-            let dst_idx = match ctx.dst_mode {
-                DstMode::Neighbor => (rank + 1) % ctx.ranks,
-                DstMode::ShiftByStep => (rank + ctx.step + 1) % ctx.ranks,
-            };
-            let dst = ctx.hosts[dst_idx];
+            let dst = ctx.hosts[dst_rank];
+            let bytes = ctx.flow_bytes(rank, dst_rank);
             let done_state = Arc::clone(&state);
             let done_transport = Arc::clone(&transport_arc);
             let done_cb: RingDoneCallback = Box::new(move |now, sim| {
@@ -166,16 +279,17 @@ impl Event for StartStep {
                     },
                 );
             });
-            transport.start_flow(
-                flow_id,
-                src,
-                dst,
-                ctx.chunk_bytes,
-                ctx.routing,
-                sim,
-                w,
-                done_cb,
-            );
+            if bytes == 0 {
+                // Nothing to send for this pair (sparse all-to-all): done immediately.
+                done_cb(sim.now(), sim);
+                continue;
+            }
+            if !ctx.tags.is_empty() {
+                w.net.set_flow_tags(flow_id, ctx.tags.clone());
+            }
+            let label = format!("c{}:step{}:rank{}", ctx.collective_id, ctx.step, rank);
+            w.net.set_flow_label(flow_id, label);
+            transport.start_flow(flow_id, src, dst, bytes, ctx.routing, sim, w, done_cb);
         }
     }
 }
@@ -188,28 +302,37 @@ impl Event for FlowDone {
             flow_id,
             done_at,
         } = *self;
-        let mut start_next = false;
+        let mut start_next: Option<SimTime> = None;
         let mut done_cb: Option<RingAllreduceDoneCallback> = None;
         {
             let mut st = state.lock().expect("ring allreduce state lock");
-            if st.inflight == 0 || st.done_at.is_some() {
+            // A repeated or stale done call must not count towards the barrier.
+            if st.done_at.is_some() || !st.step_pending.remove(&flow_id) {
                 return;
             }
             if let Some(start_at) = st.flow_start_at.remove(&flow_id) {
                 let fct_ns = done_at.0.saturating_sub(start_at.0);
                 st.flow_fct_ns.push(fct_ns);
             }
-            st.inflight = st.inflight.saturating_sub(1);
-            if st.inflight == 0 {
+            if st.step_pending.is_empty() {
+                st.step_done_times.push(sim.now());
+                let transfer_ns = sim.now().0.saturating_sub(st.step_start_at.0);
+                let compute_ns = st.reduce_compute_ns(st.step);
+                st.step_transfer_ns.push(transfer_ns);
+                st.step_compute_ns.push(compute_ns);
+                let step_end = SimTime(sim.now().0.saturating_add(compute_ns));
                 if st.reduce_steps > 0 && st.step + 1 == st.reduce_steps {
-                    st.reduce_done_at = Some(sim.now());
+                    st.reduce_done_at = Some(step_end);
                 }
                 st.step = st.step.saturating_add(1);
-                if st.step >= st.total_steps() {
+                if compute_ns > 0 {
+                    // StartStep finishes the collective once every step is done.
+                    start_next = Some(step_end);
+                } else if st.step >= st.total_steps() {
                     st.done_at = Some(sim.now());
                     done_cb = st.done_cb.take();
                 } else {
-                    start_next = true;
+                    start_next = Some(sim.now());
                 }
             }
         }
@@ -218,8 +341,8 @@ impl Event for FlowDone {
             cb(sim.now(), sim);
         }
 
-        if start_next {
-            sim.schedule(sim.now(), StartStep { state, transport });
+        if let Some(at) = start_next {
+            sim.schedule(at, StartStep { state, transport });
         }
     }
 }
@@ -233,6 +356,15 @@ pub struct RingAllreduceConfig {
     pub start_flow_id: u64,
     pub transport: Box<dyn RingTransport>,
     pub done_cb: Option<RingAllreduceDoneCallback>,
+    /// Tags attached to every flow the collective starts (see `Network::flow_tags`).
+    pub tags: FlowTags,
+    /// Direction data travels around the ring.
+    pub direction: RingDirection,
+    /// Ring position that gets the first flow id of every step.
+    pub rotation: usize,
+    /// Reduction cost per received byte; each reduce-phase step waits
+    /// `reduce_ns_per_byte * chunk_bytes` after its transfers finish. 0 disables it.
+    pub reduce_ns_per_byte: f64,
 }
 
 /// Runtime stats collected by a ring collective.
@@ -243,9 +375,40 @@ pub struct RingAllreduceStats {
     pub done_at: Option<SimTime>,
     pub total_steps: usize,
     pub flow_fct_ns: Vec<u64>,
+    /// Flows started but not yet finished (ascending); useful when a collective hangs.
+    pub pending_flows: Vec<u64>,
+    /// Per completed step: time from step start until its last flow finished.
+    pub step_transfer_ns: Vec<u64>,
+    /// Per completed step: reduction compute time after the transfers.
+    pub step_compute_ns: Vec<u64>,
+    /// Per completed step: when its last flow finished and the barrier released.
+    pub step_done_times: Vec<SimTime>,
+    /// Time spent paused between steps (see `RingAllreduceHandle::pause`).
+    pub paused_ns: u64,
+    /// Flow ids of the reduce-scatter phase and of the steps after it (the
+    /// allgather phase of an allreduce); together they cover every flow id.
+    pub phase_flow_ids: (Range<u64>, Range<u64>),
+    /// Bytes started so far by reduce-scatter steps.
+    pub reduce_bytes: u64,
+    /// Bytes started so far by the steps after the reduce-scatter phase.
+    pub gather_bytes: u64,
+}
+
+impl RingAllreduceStats {
+    /// Start-to-finish time excluding paused intervals; `None` until the collective is done.
+    pub fn makespan_ns(&self) -> Option<u64> {
+        let start = self.start_at?;
+        let done = self.done_at?;
+        Some(
+            done.0
+                .saturating_sub(start.0)
+                .saturating_sub(self.paused_ns),
+        )
+    }
 }
 
 /// Handle for inspecting ring collective progress/results.
+#[derive(Clone)]
 pub struct RingAllreduceHandle {
     state: Arc<Mutex<State>>,
 }
@@ -259,8 +422,57 @@ impl RingAllreduceHandle {
             done_at: st.done_at,
             total_steps: st.total_steps(),
             flow_fct_ns: st.flow_fct_ns.clone(),
+            pending_flows: {
+                let mut ids = st.flow_start_at.keys().copied().collect::<Vec<_>>();
+                ids.sort_unstable();
+                ids
+            },
+            step_transfer_ns: st.step_transfer_ns.clone(),
+            step_compute_ns: st.step_compute_ns.clone(),
+            step_done_times: st.step_done_times.clone(),
+            paused_ns: st.paused_ns,
+            phase_flow_ids: {
+                let split = st.flow_ids_before(st.reduce_steps);
+                let end = st.flow_ids_before(st.total_steps);
+                (st.first_flow_id..split, split..end)
+            },
+            reduce_bytes: st.reduce_bytes,
+            gather_bytes: st.gather_bytes,
         }
     }
+
+    /// Stop starting new steps; flows already in flight run to completion.
+    pub fn pause(&self) {
+        let mut st = self.state.lock().expect("ring allreduce state lock");
+        st.paused = true;
+    }
+
+    /// Continue from the step the collective reached, at `sim.now()`.
+    pub fn resume(&self, sim: &mut Simulator) {
+        let mut st = self.state.lock().expect("ring allreduce state lock");
+        st.paused = false;
+        if let Some((transport, parked_at)) = st.parked.take() {
+            // A collective parked before its first step has not started yet:
+            // the wait moves `start_at` instead of counting as paused time.
+            if st.start_at.is_some() {
+                st.paused_ns = st
+                    .paused_ns
+                    .saturating_add(sim.now().0.saturating_sub(parked_at.0));
+            }
+            drop(st);
+            sim.schedule(
+                sim.now(),
+                StartStep {
+                    state: Arc::clone(&self.state),
+                    transport,
+                },
+            );
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().expect("ring allreduce state lock").paused
+    }
 }
 
 /// Schedule a ring allreduce at SimTime::ZERO and return a handle for stats.
@@ -338,6 +550,29 @@ pub fn start_ring_alltoall_at(
     start_ring_at_internal(sim, cfg, start_at, total_steps, 0, DstMode::ShiftByStep)
 }
 
+/// Schedule an all-to-all with a per-pair message size matrix (e.g. skewed MoE
+/// token routing). `pair_bytes[i][j]` is what ring position `i` sends to `j`;
+/// `cfg.chunk_bytes` is ignored and zero entries start no flow.
+pub fn start_ring_alltoallv_at(
+    sim: &mut Simulator,
+    cfg: RingAllreduceConfig,
+    pair_bytes: Vec<Vec<u64>>,
+    start_at: SimTime,
+) -> RingAllreduceHandle {
+    assert!(
+        pair_bytes.len() == cfg.ranks && pair_bytes.iter().all(|row| row.len() == cfg.ranks),
+        "alltoallv matrix must be {0}x{0}",
+        cfg.ranks
+    );
+    let handle = start_ring_alltoall_at(sim, cfg, start_at);
+    handle
+        .state
+        .lock()
+        .expect("ring allreduce state lock")
+        .pair_bytes = Some(pair_bytes);
+    handle
+}
+
 fn start_ring_at_internal(
     sim: &mut Simulator,
     cfg: RingAllreduceConfig,
@@ -352,8 +587,19 @@ fn start_ring_at_internal(
         chunk_bytes: cfg.chunk_bytes,
         routing: cfg.routing,
         dst_mode,
+        direction: cfg.direction,
+        rotation: if cfg.ranks == 0 {
+            0
+        } else {
+            cfg.rotation % cfg.ranks
+        },
+        reduce_ns_per_byte: cfg.reduce_ns_per_byte,
+        pair_bytes: None,
         step: 0,
-        inflight: 0,
+        step_start_at: SimTime::ZERO,
+        step_pending: BTreeSet::new(),
+        step_done_times: Vec::new(),
+        first_flow_id: cfg.start_flow_id,
         next_flow_id: cfg.start_flow_id,
         total_steps,
         reduce_steps: reduce_steps.min(total_steps),
@@ -362,7 +608,16 @@ fn start_ring_at_internal(
         done_at: None,
         flow_start_at: HashMap::new(),
         flow_fct_ns: Vec::new(),
+        reduce_bytes: 0,
+        gather_bytes: 0,
+        step_transfer_ns: Vec::new(),
+        step_compute_ns: Vec::new(),
         done_cb: cfg.done_cb,
+        tags: cfg.tags,
+        collective_id: None,
+        paused: false,
+        parked: None,
+        paused_ns: 0,
     }));
 
     let transport = Arc::new(Mutex::new(cfg.transport));
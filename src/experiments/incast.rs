@@ -0,0 +1,138 @@
+//! Incast 流量生成器
+//!
+//! N 个发送端在同一时刻向同一个接收端各发送 `bytes` 字节（同步读）。
+//! 接收端的下行链路成为汇聚点，N 增大、缓冲变浅时同步丢包迅速增多。
+
+use std::sync::{Arc, Mutex};
+
+use crate::cc::ring::RoutingMode;
+use crate::net::{NetWorld, NodeId};
+use crate::proto::dctcp::DctcpConfig;
+use crate::proto::tcp::TcpConfig;
+use crate::sim::{SimTime, Simulator, TransportProtocol};
+
+use super::start_p2p_flow;
+
+/// Incast 参数
+#[derive(Debug, Clone)]
+pub struct IncastOpts {
+    /// 每条流的字节数
+    pub bytes: u64,
+    pub protocol: TransportProtocol,
+    pub routing: RoutingMode,
+    pub tcp_cfg: TcpConfig,
+    pub dctcp_cfg: DctcpConfig,
+    /// 第一条流的 id；第 i 个发送端使用 `first_flow_id + i`
+    pub first_flow_id: u64,
+}
+
+impl Default for IncastOpts {
+    fn default() -> Self {
+        Self {
+            bytes: 256 * 1024,
+            protocol: TransportProtocol::Tcp,
+            routing: RoutingMode::PerFlow,
+            tcp_cfg: TcpConfig::default(),
+            dctcp_cfg: DctcpConfig::default(),
+            first_flow_id: 1,
+        }
+    }
+}
+
+/// 一次 incast 的汇总结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncastReport {
+    pub fanin: usize,
+    pub bytes_per_flow: u64,
+    /// 已完成的流数
+    pub flows_done: usize,
+    /// 最后一条流完成的时间（仍有流未完成时为 `None`）
+    pub completion: Option<SimTime>,
+    /// 已完成字节 / 从启动到最后一次完成的时长（Gbps）
+    pub goodput_gbps: f64,
+    /// 启动以来全网的丢包数
+    pub dropped_pkts: u64,
+}
+
+/// 已启动的 incast；仿真运行后用 `report` 汇总。
+#[derive(Debug)]
+pub struct Incast {
+    bytes_per_flow: u64,
+    start: SimTime,
+    dropped_before: u64,
+    /// 每条流的完成时间（按发送端顺序）
+    done: Arc<Mutex<Vec<Option<SimTime>>>>,
+}
+
+/// 在当前仿真时刻让 `senders` 中的每个节点同时向 `receiver` 发起一条流。
+pub fn incast(
+    sim: &mut Simulator,
+    world: &mut NetWorld,
+    senders: &[NodeId],
+    receiver: NodeId,
+    opts: &IncastOpts,
+) -> Incast {
+    assert!(!senders.is_empty(), "incast needs at least one sender");
+    assert!(
+        !senders.contains(&receiver),
+        "incast receiver {:?} is also a sender",
+        receiver
+    );
+    let done = Arc::new(Mutex::new(vec![None; senders.len()]));
+    let dropped_before = world.net.stats.dropped_pkts;
+    for (i, &src) in senders.iter().enumerate() {
+        let slot = Arc::clone(&done);
+        start_p2p_flow(
+            sim,
+            world,
+            opts.protocol,
+            opts.routing,
+            &opts.tcp_cfg,
+            &opts.dctcp_cfg,
+            opts.first_flow_id.saturating_add(i as u64),
+            src,
+            receiver,
+            opts.bytes,
+            Box::new(move |now, _sim| {
+                slot.lock().expect("incast done lock")[i] = Some(now);
+            }),
+        );
+    }
+    Incast {
+        bytes_per_flow: opts.bytes,
+        start: sim.now(),
+        dropped_before,
+        done,
+    }
+}
+
+impl Incast {
+    /// 汇总到目前为止的完成情况与丢包
+    pub fn report(&self, world: &NetWorld) -> IncastReport {
+        let done = self.done.lock().expect("incast done lock");
+        let finished: Vec<SimTime> = done.iter().flatten().copied().collect();
+        let last = finished.iter().max().copied();
+        let span_ns = last.map_or(0, |t| t.0.saturating_sub(self.start.0));
+        let done_bits = (finished.len() as f64) * (self.bytes_per_flow as f64) * 8.0;
+        IncastReport {
+            fanin: done.len(),
+            bytes_per_flow: self.bytes_per_flow,
+            flows_done: finished.len(),
+            completion: if finished.len() == done.len() {
+                last
+            } else {
+                None
+            },
+            goodput_gbps: if span_ns == 0 {
+                0.0
+            } else {
+                done_bits / span_ns as f64
+            },
+            dropped_pkts: world
+                .net
+                .stats
+                .dropped_pkts
+                .saturating_sub(self.dropped_before),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! 实验辅助模块
+//!
+//! 与具体拓扑无关的流量生成器：调用方先搭好拓扑，再把端点的 `NodeId` 交给生成器。
+
+pub mod incast;
+mod p2p;
+pub mod traffic_matrix;
+
+pub use incast::{Incast, IncastOpts, IncastReport, incast};
+pub use p2p::start_p2p_flow;
+pub use traffic_matrix::{
+    BackgroundTraffic, BackgroundTrafficReport, FlowSizeDist, PlannedFlow, TrafficMatrix,
+    TrafficMatrixOpts, traffic_matrix,
+};
@@ -0,0 +1,71 @@
+//! 点对点流启动
+//!
+//! 按协议（TCP/DCTCP）与路由模式（per-flow ECMP / per-packet）启动一条单向传输，
+//! 完成时调用 done 回调。workload 执行器与流量生成器共用这段逻辑。
+
+use crate::cc::ring::{RingDoneCallback, RoutingMode};
+use crate::net::{NetWorld, NodeId};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpDoneCallback};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpDoneCallback};
+use crate::sim::{Simulator, TransportProtocol};
+
+/// 从 `src` 向 `dst` 启动一条 `bytes` 字节的流（流 id 为 `flow_id`），完成时调用 `done`。
+///
+/// per-flow 路由时路径由 `try_route_ecmp_path` 按 `flow_id` 哈希选出。`dst` 不可达时
+/// 不启动该流、也不调用 `done`，只计入 `Stats::dropped_no_route`（见
+/// `Network::fail_flow_no_route`）。
+#[allow(clippy::too_many_arguments)]
+pub fn start_p2p_flow(
+    sim: &mut Simulator,
+    world: &mut NetWorld,
+    protocol: TransportProtocol,
+    routing: RoutingMode,
+    tcp_cfg: &TcpConfig,
+    dctcp_cfg: &DctcpConfig,
+    flow_id: u64,
+    src: NodeId,
+    dst: NodeId,
+    bytes: u64,
+    done: RingDoneCallback,
+) {
+    let Some(route) = world.net.try_route_ecmp_path(src, dst, flow_id) else {
+        world.net.fail_flow_no_route(flow_id, src, dst);
+        return;
+    };
+    match protocol {
+        TransportProtocol::Tcp => {
+            let mut tcp = std::mem::take(&mut world.net.tcp);
+            let conn = match routing {
+                RoutingMode::PerFlow => {
+                    TcpConn::new(flow_id, src, dst, route, bytes, tcp_cfg.clone())
+                }
+                RoutingMode::PerPacket => {
+                    TcpConn::new_dynamic(flow_id, src, dst, bytes, tcp_cfg.clone())
+                }
+            };
+            let done_cb: TcpDoneCallback = Box::new(move |_, now, sim| {
+                done(now, sim);
+            });
+            tcp.set_done_callback(flow_id, done_cb);
+            tcp.start_conn(conn, sim, &mut world.net);
+            world.net.tcp = tcp;
+        }
+        TransportProtocol::Dctcp => {
+            let mut dctcp = std::mem::take(&mut world.net.dctcp);
+            let conn = match routing {
+                RoutingMode::PerFlow => {
+                    DctcpConn::new(flow_id, src, dst, route, bytes, dctcp_cfg.clone())
+                }
+                RoutingMode::PerPacket => {
+                    DctcpConn::new_dynamic(flow_id, src, dst, bytes, dctcp_cfg.clone())
+                }
+            };
+            let done_cb: DctcpDoneCallback = Box::new(move |_, now, sim| {
+                done(now, sim);
+            });
+            dctcp.set_done_callback(flow_id, done_cb);
+            dctcp.start_conn(conn, sim, &mut world.net);
+            world.net.dctcp = dctcp;
+        }
+    }
+}
@@ -0,0 +1,405 @@
+//! 背景流量生成器（随机流量矩阵）
+//!
+//! 按需求矩阵给出的权重挑选 (src, dst) 对，流以 Poisson 过程到达，流大小从给定分布
+//! （固定值、Pareto 或 web search / data mining 等经验 CDF）抽样。总到达率按目标负载
+//! 换算：`load * hosts * host_link_bps / (8 * 平均流大小)`。
+//!
+//! 到达序列由种子完全决定，先用 `TrafficMatrix::plan` 生成，再由 `traffic_matrix`
+//! 在对应时刻调度 `start_p2p_flow`。
+
+use std::sync::{Arc, Mutex};
+
+use crate::analysis::percentile;
+use crate::cc::ring::RoutingMode;
+use crate::net::{NetWorld, NodeId};
+use crate::proto::dctcp::DctcpConfig;
+use crate::proto::tcp::TcpConfig;
+use crate::sim::{Event, SimTime, Simulator, TransportProtocol, World};
+
+use super::start_p2p_flow;
+
+/// 流大小分布
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlowSizeDist {
+    /// 所有流同样大小
+    Fixed(u64),
+    /// Pareto 分布，按均值与形状参数（> 1）给出
+    Pareto { mean_bytes: f64, shape: f64 },
+    /// 分段线性的经验 CDF：`(字节数, 累积概率)`，两列均单调不减，最后一个概率为 1
+    Cdf(Vec<(u64, f64)>),
+}
+
+impl FlowSizeDist {
+    /// DCTCP 论文测得的 web search 流大小分布
+    pub fn web_search() -> Self {
+        Self::Cdf(vec![
+            (0, 0.0),
+            (10_000, 0.15),
+            (20_000, 0.2),
+            (30_000, 0.3),
+            (50_000, 0.4),
+            (80_000, 0.53),
+            (200_000, 0.6),
+            (1_000_000, 0.7),
+            (2_000_000, 0.8),
+            (5_000_000, 0.9),
+            (10_000_000, 0.97),
+            (30_000_000, 1.0),
+        ])
+    }
+
+    /// VL2 论文测得的 data mining 流大小分布（CONGA 等工作使用的离散化版本）
+    pub fn data_mining() -> Self {
+        Self::Cdf(vec![
+            (0, 0.0),
+            (180, 0.1),
+            (216, 0.2),
+            (560, 0.3),
+            (900, 0.4),
+            (1_100, 0.5),
+            (1_870, 0.6),
+            (3_160, 0.7),
+            (10_000, 0.8),
+            (400_000, 0.9),
+            (3_160_000, 0.95),
+            (100_000_000, 0.98),
+            (1_000_000_000, 1.0),
+        ])
+    }
+
+    /// 按名字选取内置的经验分布：`websearch` 或 `datamining`
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.to_ascii_lowercase().replace(['_', '-'], "").as_str() {
+            "websearch" => Ok(Self::web_search()),
+            "datamining" => Ok(Self::data_mining()),
+            other => Err(format!(
+                "unknown flow size cdf {other:?} (expected websearch or datamining)"
+            )),
+        }
+    }
+
+    /// 分布的均值（字节）
+    pub fn mean_bytes(&self) -> f64 {
+        match self {
+            Self::Fixed(bytes) => *bytes as f64,
+            Self::Pareto { mean_bytes, .. } => *mean_bytes,
+            Self::Cdf(points) => points
+                .windows(2)
+                .map(|w| (w[1].1 - w[0].1) * (w[0].0 + w[1].0) as f64 / 2.0)
+                .sum(),
+        }
+    }
+
+    fn validate(&self) {
+        match self {
+            Self::Fixed(bytes) => assert!(*bytes > 0, "fixed flow size must be positive"),
+            Self::Pareto { mean_bytes, shape } => {
+                assert!(*mean_bytes > 0.0, "pareto mean must be positive");
+                assert!(*shape > 1.0, "pareto shape must be > 1 for a finite mean");
+            }
+            Self::Cdf(points) => {
+                assert!(points.len() >= 2, "flow size cdf needs at least two points");
+                assert!(
+                    points
+                        .windows(2)
+                        .all(|w| w[0].0 <= w[1].0 && w[0].1 <= w[1].1),
+                    "flow size cdf must be non-decreasing"
+                );
+                let last = points[points.len() - 1].1;
+                assert!((last - 1.0).abs() < 1e-9, "flow size cdf must end at 1");
+            }
+        }
+    }
+
+    /// 由 (0, 1] 上的均匀数 `u` 做逆变换抽样，结果至少 1 字节
+    fn sample(&self, u: f64) -> u64 {
+        let bytes = match self {
+            Self::Fixed(bytes) => *bytes as f64,
+            Self::Pareto { mean_bytes, shape } => {
+                let scale = mean_bytes * (shape - 1.0) / shape;
+                scale / u.powf(1.0 / shape)
+            }
+            Self::Cdf(points) => {
+                let i = points.partition_point(|&(_, p)| p < u).max(1);
+                match points.get(i) {
+                    Some(&(hi, p_hi)) => {
+                        let (lo, p_lo) = points[i - 1];
+                        let frac = if p_hi > p_lo {
+                            (u - p_lo) / (p_hi - p_lo)
+                        } else {
+                            1.0
+                        };
+                        lo as f64 + frac * (hi - lo) as f64
+                    }
+                    None => points[points.len() - 1].0 as f64,
+                }
+            }
+        };
+        (bytes.round() as u64).max(1)
+    }
+}
+
+/// 背景流量参数
+#[derive(Debug, Clone)]
+pub struct TrafficMatrixOpts {
+    /// 目标负载：平均每个 host 的发送速率占 `host_link_bps` 的比例
+    pub load: f64,
+    pub host_link_bps: u64,
+    pub sizes: FlowSizeDist,
+    /// 流到达的时间窗口长度（从调度时刻起算）
+    pub duration: SimTime,
+    pub seed: u64,
+    pub protocol: TransportProtocol,
+    pub routing: RoutingMode,
+    pub tcp_cfg: TcpConfig,
+    pub dctcp_cfg: DctcpConfig,
+    /// 第一条流的 id；第 i 条流使用 `first_flow_id + i`
+    pub first_flow_id: u64,
+}
+
+impl Default for TrafficMatrixOpts {
+    fn default() -> Self {
+        Self {
+            load: 0.5,
+            host_link_bps: 100_000_000_000,
+            sizes: FlowSizeDist::web_search(),
+            duration: SimTime::from_millis(10),
+            seed: 1,
+            protocol: TransportProtocol::Tcp,
+            routing: RoutingMode::PerFlow,
+            tcp_cfg: TcpConfig::default(),
+            dctcp_cfg: DctcpConfig::default(),
+            first_flow_id: 1,
+        }
+    }
+}
+
+/// host 间的需求矩阵：`demand[i][j]` 为 `hosts[i] -> hosts[j]` 的相对权重
+#[derive(Debug, Clone)]
+pub struct TrafficMatrix {
+    hosts: Vec<NodeId>,
+    demand: Vec<Vec<f64>>,
+}
+
+/// 计划中的一条流（`at` 相对调度时刻）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlannedFlow {
+    pub at: SimTime,
+    pub src: NodeId,
+    pub dst: NodeId,
+    pub bytes: u64,
+}
+
+impl TrafficMatrix {
+    pub fn new(hosts: Vec<NodeId>, demand: Vec<Vec<f64>>) -> Self {
+        let n = hosts.len();
+        assert!(
+            demand.len() == n && demand.iter().all(|row| row.len() == n),
+            "demand matrix must be {n}x{n}"
+        );
+        assert!(
+            demand.iter().flatten().all(|&w| w >= 0.0 && w.is_finite()),
+            "demand weights must be finite and non-negative"
+        );
+        assert!(
+            demand.iter().flatten().any(|&w| w > 0.0),
+            "demand matrix has no positive entry"
+        );
+        Self { hosts, demand }
+    }
+
+    /// 所有不同 host 对权重相同（all-to-all）
+    pub fn uniform(hosts: Vec<NodeId>) -> Self {
+        let n = hosts.len();
+        assert!(n >= 2, "uniform traffic matrix needs at least two hosts");
+        let demand = (0..n)
+            .map(|i| (0..n).map(|j| if i == j { 0.0 } else { 1.0 }).collect())
+            .collect();
+        Self::new(hosts, demand)
+    }
+
+    pub fn hosts(&self) -> &[NodeId] {
+        &self.hosts
+    }
+
+    /// 生成 `[0, opts.duration)` 内的流到达序列（按时间排序）
+    pub fn plan(&self, opts: &TrafficMatrixOpts) -> Vec<PlannedFlow> {
+        assert!(opts.load > 0.0, "traffic load must be positive");
+        opts.sizes.validate();
+
+        let pairs = self
+            .demand
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, &w)| (i, j, w)))
+            .filter(|&(_, _, w)| w > 0.0)
+            .collect::<Vec<_>>();
+        let mut cum = Vec::with_capacity(pairs.len());
+        let mut total = 0.0;
+        for &(_, _, w) in &pairs {
+            total += w;
+            cum.push(total);
+        }
+
+        // 每纳秒的到达数
+        let capacity_bytes_per_ns = self.hosts.len() as f64 * opts.host_link_bps as f64 / 8e9;
+        let rate = opts.load * capacity_bytes_per_ns / opts.sizes.mean_bytes();
+
+        let mut rng = opts.seed;
+        let mut t = 0.0;
+        let mut flows = Vec::new();
+        loop {
+            t += -next_unit(&mut rng).ln() / rate;
+            if t >= opts.duration.0 as f64 {
+                break;
+            }
+            let pick = (1.0 - next_unit(&mut rng)) * total;
+            let (i, j, _) = pairs[cum.partition_point(|&c| c <= pick).min(pairs.len() - 1)];
+            flows.push(PlannedFlow {
+                at: SimTime(t as u64),
+                src: self.hosts[i],
+                dst: self.hosts[j],
+                bytes: opts.sizes.sample(next_unit(&mut rng)),
+            });
+        }
+        flows
+    }
+}
+
+/// (0, 1] 上的均匀分布（splitmix64）
+fn next_unit(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^= z >> 31;
+    ((z >> 11) + 1) as f64 / (1u64 << 53) as f64
+}
+
+/// 背景流量的汇总结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundTrafficReport {
+    pub flows: usize,
+    pub flows_done: usize,
+    /// 计划窗口内的提供负载（占全部 host 发送容量的比例）
+    pub offered_load: f64,
+    /// 已完成流的 FCT 中位数 / p99
+    pub p50_fct: Option<SimTime>,
+    pub p99_fct: Option<SimTime>,
+    /// 启动以来全网的丢包数
+    pub dropped_pkts: u64,
+}
+
+/// 已调度的背景流量；仿真运行后用 `report` 汇总。
+#[derive(Debug)]
+pub struct BackgroundTraffic {
+    flows: Vec<PlannedFlow>,
+    start: SimTime,
+    offered_load: f64,
+    dropped_before: u64,
+    /// 每条流的完成时间（按计划顺序）
+    done: Arc<Mutex<Vec<Option<SimTime>>>>,
+}
+
+struct StartBackgroundFlow {
+    idx: usize,
+    flow: PlannedFlow,
+    opts: Arc<TrafficMatrixOpts>,
+    done: Arc<Mutex<Vec<Option<SimTime>>>>,
+}
+
+impl Event for StartBackgroundFlow {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let w = world
+            .as_any_mut()
+            .downcast_mut::<NetWorld>()
+            .expect("world must be NetWorld");
+        let StartBackgroundFlow {
+            idx,
+            flow,
+            opts,
+            done,
+        } = *self;
+        start_p2p_flow(
+            sim,
+            w,
+            opts.protocol,
+            opts.routing,
+            &opts.tcp_cfg,
+            &opts.dctcp_cfg,
+            opts.first_flow_id.saturating_add(idx as u64),
+            flow.src,
+            flow.dst,
+            flow.bytes,
+            Box::new(move |now, _sim| {
+                done.lock().expect("background done lock")[idx] = Some(now);
+            }),
+        );
+    }
+}
+
+/// 从当前仿真时刻起，按 `matrix` 与 `opts` 生成背景流量并调度每条流的启动。
+pub fn traffic_matrix(
+    sim: &mut Simulator,
+    world: &mut NetWorld,
+    matrix: &TrafficMatrix,
+    opts: &TrafficMatrixOpts,
+) -> BackgroundTraffic {
+    let flows = matrix.plan(opts);
+    let start = sim.now();
+    let done = Arc::new(Mutex::new(vec![None; flows.len()]));
+    let shared = Arc::new(opts.clone());
+    for (idx, &flow) in flows.iter().enumerate() {
+        sim.schedule(
+            SimTime(start.0.saturating_add(flow.at.0)),
+            StartBackgroundFlow {
+                idx,
+                flow,
+                opts: Arc::clone(&shared),
+                done: Arc::clone(&done),
+            },
+        );
+    }
+    let capacity_bits =
+        matrix.hosts.len() as f64 * opts.host_link_bps as f64 * opts.duration.0 as f64 / 1e9;
+    let offered_bits = flows.iter().map(|f| f.bytes as f64 * 8.0).sum::<f64>();
+    BackgroundTraffic {
+        flows,
+        start,
+        offered_load: offered_bits / capacity_bits,
+        dropped_before: world.net.stats.dropped_pkts,
+        done,
+    }
+}
+
+impl BackgroundTraffic {
+    pub fn flows(&self) -> &[PlannedFlow] {
+        &self.flows
+    }
+
+    /// 汇总到目前为止的完成情况、FCT 与丢包
+    pub fn report(&self, world: &NetWorld) -> BackgroundTrafficReport {
+        let done = self.done.lock().expect("background done lock");
+        let fcts = self
+            .flows
+            .iter()
+            .zip(done.iter())
+            .filter_map(|(flow, end)| {
+                let begin = self.start.0.saturating_add(flow.at.0);
+                end.map(|end| end.0.saturating_sub(begin))
+            })
+            .collect::<Vec<_>>();
+        BackgroundTrafficReport {
+            flows: self.flows.len(),
+            flows_done: fcts.len(),
+            offered_load: self.offered_load,
+            p50_fct: percentile(&fcts, 0.5).map(SimTime),
+            p99_fct: percentile(&fcts, 0.99).map(SimTime),
+            dropped_pkts: world
+                .net
+                .stats
+                .dropped_pkts
+                .saturating_sub(self.dropped_before),
+        }
+    }
+}
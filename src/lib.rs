@@ -1,4 +1,6 @@
+pub mod analysis;
 pub mod cc;
+pub mod experiments;
 pub mod net;
 pub mod proto;
 pub mod queue;
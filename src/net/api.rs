@@ -1,9 +1,11 @@
 //! Network-facing API used by protocol stacks.
 
-use crate::sim::Simulator;
+use crate::proto::dctcp::DctcpConn;
+use crate::proto::tcp::TcpConn;
+use crate::sim::{SimTime, Simulator};
 use crate::viz::VizCwndReason;
 
-use super::{NodeId, Packet};
+use super::{FlowStats, NodeId, Packet};
 
 /// Minimal network API for protocol stacks.
 pub trait NetApi {
@@ -16,6 +18,31 @@ pub trait NetApi {
         dst: NodeId,
     ) -> Packet;
     fn forward_from(&mut self, from: NodeId, pkt: Packet, sim: &mut Simulator);
+    /// ECMP path from `src` to `dst` chosen by hashing `flow_id`, or `None`
+    /// if `dst` is unreachable.
+    fn try_route_ecmp_path(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        flow_id: u64,
+    ) -> Option<Vec<NodeId>>;
+    /// Give up on a flow whose destination is unreachable (see
+    /// `Network::fail_flow_no_route`).
+    fn fail_flow_no_route(&mut self, flow_id: u64, src: NodeId, dst: NodeId);
+    /// Whether `flow_id` has been failed by fault injection as of `now`
+    /// (see `Network::fail_flow_at`).
+    fn flow_failed(&self, flow_id: u64, now: SimTime) -> bool;
+
+    /// Per-host flow admission: returns the connection if it may start now,
+    /// otherwise queues it until a slot on its source host frees up.
+    fn admit_tcp_conn(&mut self, conn: TcpConn) -> Option<TcpConn>;
+    fn admit_dctcp_conn(&mut self, conn: DctcpConn) -> Option<DctcpConn>;
+    /// Release the slot held by a finished flow (no-op if it held none).
+    fn release_flow_slot(&mut self, flow_id: u64, sim: &mut Simulator);
+    /// Fire the protocol-independent completion callbacks for `flow_id`.
+    fn flow_completed(&mut self, flow_id: u64, sim: &mut Simulator);
+    /// Per-flow statistics record, created on first use.
+    fn flow_stats_mut(&mut self, flow_id: u64) -> &mut FlowStats;
 
     fn viz_tcp_send_data(&mut self, t_ns: u64, conn_id: u64, seq: u64, len: u32, retrans: bool);
     fn viz_tcp_send_ack(&mut self, t_ns: u64, conn_id: u64, ack: u64, ecn_echo: bool);
@@ -55,6 +82,43 @@ impl NetApi for super::Network {
         super::Network::forward_from(self, from, pkt, sim)
     }
 
+    fn try_route_ecmp_path(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        flow_id: u64,
+    ) -> Option<Vec<NodeId>> {
+        super::Network::try_route_ecmp_path(self, src, dst, flow_id)
+    }
+
+    fn fail_flow_no_route(&mut self, flow_id: u64, src: NodeId, dst: NodeId) {
+        super::Network::fail_flow_no_route(self, flow_id, src, dst)
+    }
+
+    fn flow_failed(&self, flow_id: u64, now: SimTime) -> bool {
+        super::Network::flow_failed(self, flow_id, now)
+    }
+
+    fn admit_tcp_conn(&mut self, conn: TcpConn) -> Option<TcpConn> {
+        super::Network::admit_tcp_conn(self, conn)
+    }
+
+    fn admit_dctcp_conn(&mut self, conn: DctcpConn) -> Option<DctcpConn> {
+        super::Network::admit_dctcp_conn(self, conn)
+    }
+
+    fn release_flow_slot(&mut self, flow_id: u64, sim: &mut Simulator) {
+        super::Network::release_flow_slot(self, flow_id, sim)
+    }
+
+    fn flow_completed(&mut self, flow_id: u64, sim: &mut Simulator) {
+        super::Network::flow_completed(self, flow_id, sim)
+    }
+
+    fn flow_stats_mut(&mut self, flow_id: u64) -> &mut FlowStats {
+        super::Network::flow_stats_mut(self, flow_id)
+    }
+
     fn viz_tcp_send_data(&mut self, t_ns: u64, conn_id: u64, seq: u64, len: u32, retrans: bool) {
         self.viz_tcp_send_data(t_ns, conn_id, seq, len, retrans)
     }
@@ -0,0 +1,35 @@
+//! Per-event context handed to protocol stacks.
+//!
+//! Bundles the simulator and the network so protocol handlers take one
+//! argument instead of threading `sim` and `net` through every helper.
+
+use crate::sim::{Event, SimTime, Simulator};
+
+use super::{NetApi, NodeId, Packet};
+
+/// Simulator + network view for the event currently being executed.
+pub struct SimContext<'a> {
+    pub sim: &'a mut Simulator,
+    pub net: &'a mut dyn NetApi,
+}
+
+impl<'a> SimContext<'a> {
+    pub fn new(sim: &'a mut Simulator, net: &'a mut dyn NetApi) -> Self {
+        Self { sim, net }
+    }
+
+    /// Current simulation time.
+    pub fn now(&self) -> SimTime {
+        self.sim.now()
+    }
+
+    /// Schedule `ev` at absolute time `at`.
+    pub fn schedule<E: Event>(&mut self, at: SimTime, ev: E) {
+        self.sim.schedule(at, ev);
+    }
+
+    /// Inject `pkt` into the network at node `from`.
+    pub fn forward_from(&mut self, from: NodeId, pkt: Packet) {
+        self.net.forward_from(from, pkt, self.sim);
+    }
+}
@@ -0,0 +1,129 @@
+//! Per-host admission control for concurrent flows.
+//!
+//! Models a NIC with a finite number of queue pairs: once a host has `n`
+//! active flows, further connections wait in FIFO order until a slot frees.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::proto::dctcp::DctcpConn;
+use crate::proto::tcp::TcpConn;
+use crate::sim::{Event, Simulator, World};
+
+use super::proto_bridge::{with_dctcp_stack, with_tcp_stack};
+use super::{Network, NodeId};
+
+/// A connection waiting for a free slot on its source host.
+#[derive(Debug)]
+pub(crate) enum QueuedFlow {
+    Tcp(Box<TcpConn>),
+    Dctcp(Box<DctcpConn>),
+}
+
+impl QueuedFlow {
+    fn id(&self) -> u64 {
+        match self {
+            QueuedFlow::Tcp(c) => c.id,
+            QueuedFlow::Dctcp(c) => c.id,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FlowAdmission {
+    limits: HashMap<NodeId, usize>,
+    active: HashMap<NodeId, usize>,
+    /// flow_id -> host whose slot the flow holds
+    admitted: HashMap<u64, NodeId>,
+    waiting: HashMap<NodeId, VecDeque<QueuedFlow>>,
+}
+
+impl FlowAdmission {
+    /// Take a slot on `src` for `flow_id`; returns false if the host is saturated.
+    fn try_admit(&mut self, src: NodeId, flow_id: u64) -> bool {
+        let Some(&limit) = self.limits.get(&src) else {
+            return true;
+        };
+        let active = self.active.entry(src).or_insert(0);
+        if *active >= limit {
+            return false;
+        }
+        *active += 1;
+        self.admitted.insert(flow_id, src);
+        true
+    }
+
+    fn enqueue(&mut self, src: NodeId, flow: QueuedFlow) {
+        self.waiting.entry(src).or_default().push_back(flow);
+    }
+
+    /// Free the slot held by `flow_id` and hand it to the next waiter, if any.
+    fn release(&mut self, flow_id: u64) -> Option<QueuedFlow> {
+        let src = self.admitted.remove(&flow_id)?;
+        if let Some(next) = self.waiting.get_mut(&src).and_then(|q| q.pop_front()) {
+            self.admitted.insert(next.id(), src);
+            return Some(next);
+        }
+        if let Some(active) = self.active.get_mut(&src) {
+            *active = active.saturating_sub(1);
+        }
+        None
+    }
+}
+
+/// Event: start a connection that has just been granted a slot.
+#[derive(Debug)]
+pub(crate) struct StartAdmittedFlow {
+    flow: QueuedFlow,
+}
+
+impl Event for StartAdmittedFlow {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        match self.flow {
+            QueuedFlow::Tcp(conn) => with_tcp_stack(sim, world, |cx, tcp| {
+                tcp.start_admitted_conn(*conn, cx);
+            }),
+            QueuedFlow::Dctcp(conn) => with_dctcp_stack(sim, world, |cx, dctcp| {
+                dctcp.start_admitted_conn(*conn, cx);
+            }),
+        }
+    }
+}
+
+impl Network {
+    /// 限制某个 Host 同时活跃的流数量（模拟 NIC QP 数量有限）。
+    ///
+    /// 超出限制的连接在 `start_conn` 时排队，等已有流完成后按 FIFO 顺序启动。
+    pub fn set_host_max_concurrent_flows(&mut self, node: NodeId, n: usize) {
+        assert!(n > 0, "max concurrent flows must be > 0 (node {:?})", node);
+        self.admission.limits.insert(node, n);
+    }
+
+    /// 某个 Host 当前等待 slot 的流数量。
+    pub fn host_queued_flows(&self, node: NodeId) -> usize {
+        self.admission.waiting.get(&node).map_or(0, |q| q.len())
+    }
+
+    pub(crate) fn admit_tcp_conn(&mut self, conn: TcpConn) -> Option<TcpConn> {
+        if self.admission.try_admit(conn.src, conn.id) {
+            return Some(conn);
+        }
+        self.admission
+            .enqueue(conn.src, QueuedFlow::Tcp(Box::new(conn)));
+        None
+    }
+
+    pub(crate) fn admit_dctcp_conn(&mut self, conn: DctcpConn) -> Option<DctcpConn> {
+        if self.admission.try_admit(conn.src, conn.id) {
+            return Some(conn);
+        }
+        self.admission
+            .enqueue(conn.src, QueuedFlow::Dctcp(Box::new(conn)));
+        None
+    }
+
+    pub(crate) fn release_flow_slot(&mut self, flow_id: u64, sim: &mut Simulator) {
+        if let Some(flow) = self.admission.release(flow_id) {
+            sim.schedule(sim.now(), StartAdmittedFlow { flow });
+        }
+    }
+}
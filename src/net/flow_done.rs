@@ -0,0 +1,55 @@
+//! Protocol-independent flow-completion callbacks.
+//!
+//! TCP and DCTCP each keep their own `set_done_callback` registry; this one
+//! lives on the network so experiment code can wait for a flow without knowing
+//! which stack carries it. Both stacks report here at the instant the last byte
+//! is acknowledged, so `done_notify_delay` does not apply.
+
+use std::collections::HashMap;
+
+use crate::sim::{SimTime, Simulator};
+
+use super::{NetWorld, Network};
+
+/// Callback receiving the finished flow's id and its completion time.
+pub type FlowDoneCallback = Box<dyn Fn(u64, SimTime, &mut Simulator) + Send>;
+
+#[derive(Default)]
+pub(crate) struct FlowDoneCallbacks {
+    /// One-shot callbacks keyed by flow id.
+    by_flow: HashMap<u64, FlowDoneCallback>,
+    /// Callbacks fired for every completed flow, in registration order.
+    any: Vec<FlowDoneCallback>,
+}
+
+impl Network {
+    /// Called by the protocol stacks when `flow_id` completes at `sim.now()`.
+    pub(crate) fn flow_completed(&mut self, flow_id: u64, sim: &mut Simulator) {
+        let now = sim.now();
+        if let Some(cb) = self.flow_done.by_flow.remove(&flow_id) {
+            cb(flow_id, now, sim);
+        }
+        for cb in &self.flow_done.any {
+            cb(flow_id, now, sim);
+        }
+    }
+}
+
+impl NetWorld {
+    /// 注册某条流（TCP 或 DCTCP）完成时的回调，触发一次后移除；同一流重复注册会覆盖。
+    pub fn on_flow_complete(
+        &mut self,
+        flow_id: u64,
+        cb: impl Fn(u64, SimTime, &mut Simulator) + Send + 'static,
+    ) {
+        self.net.flow_done.by_flow.insert(flow_id, Box::new(cb));
+    }
+
+    /// 注册对每条完成的流都会调用的回调（与承载协议无关），按注册顺序执行。
+    pub fn on_any_flow_complete(
+        &mut self,
+        cb: impl Fn(u64, SimTime, &mut Simulator) + Send + 'static,
+    ) {
+        self.net.flow_done.any.push(Box::new(cb));
+    }
+}
@@ -0,0 +1,26 @@
+//! 延迟转发事件
+//!
+//! 定义交换机处理时延结束后继续转发数据包的事件。
+
+use super::id::NodeId;
+use super::net_world::NetWorld;
+use super::packet::Packet;
+use crate::sim::{Event, Simulator, World};
+
+/// 事件：节点处理完 packet 后，从 `from` 把它转发到下一跳。
+#[derive(Debug)]
+pub struct ForwardPacket {
+    pub from: NodeId,
+    pub pkt: Packet,
+}
+
+impl Event for ForwardPacket {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let ForwardPacket { from, pkt } = *self;
+        let w = world
+            .as_any_mut()
+            .downcast_mut::<NetWorld>()
+            .expect("world must be NetWorld");
+        w.net.forward_from(from, pkt, sim);
+    }
+}
@@ -2,8 +2,9 @@
 //!
 //! 定义网络链路及其传输时延计算。
 
+use super::Packet;
 use super::id::NodeId;
-use crate::queue::{DEFAULT_PKT_BYTES, PacketQueue, PriorityQueue};
+use crate::queue::{DEFAULT_PKT_BYTES, PacketQueue, PriorityQueue, RedMarker};
 use crate::sim::SimTime;
 
 // Default to a very large buffer so links behave as "almost infinite"
@@ -21,8 +22,24 @@ pub struct Link {
     pub busy_until: SimTime,
     /// ECN 标记阈值（bytes）。None 表示不开启 ECN 标记。
     pub ecn_threshold_bytes: Option<u64>,
+    /// 按 RED 平均队长概率标记 ECN（与阈值标记互斥；None 表示不开启）
+    pub(crate) red_ecn: Option<RedMarker>,
     /// 链路上的排队策略（默认 DropTail，容量极大，行为与旧逻辑一致但可扩展）
     pub queue: Box<dyn PacketQueue>,
+    /// 队列占用的历史峰值（bytes），每次入队成功后更新
+    pub peak_queue_bytes: u64,
+    /// 队列里有 `Network::preload_link_queue` 预载的包且链路尚未开始发送
+    pub preloaded: bool,
+    /// 累计开始序列化发送的字节数（用于利用率采样）
+    pub tx_bytes: u64,
+    /// 队列占用的时间加权直方图（未开启时为 None）
+    pub(crate) occupancy: Option<QueueOccupancy>,
+    /// 队列与链路之间的令牌桶整形器（未开启时为 None）
+    pub(crate) shaper: Option<TokenBucket>,
+    /// 每包传播时延的抖动（未开启时为 None）
+    pub(crate) jitter: Option<LinkJitter>,
+    /// 入队前的独立随机丢包（未开启时为 None）
+    pub(crate) loss: Option<LinkLoss>,
 }
 
 impl Link {
@@ -35,7 +52,35 @@ impl Link {
             bandwidth_bps,
             busy_until: SimTime::ZERO,
             ecn_threshold_bytes: None,
+            red_ecn: None,
             queue: Box::new(PriorityQueue::new(DEFAULT_LINK_QUEUE_BYTES)),
+            peak_queue_bytes: 0,
+            preloaded: false,
+            tx_bytes: 0,
+            occupancy: None,
+            shaper: None,
+            jitter: None,
+            loss: None,
+        }
+    }
+
+    /// 阈值 ECN 标记：若 `pkt` 入队后队列占用达到 `ecn_threshold_bytes`，把 ECT 包标记为 CE。
+    /// 非 ECT 包不受影响（队列满时照常尾丢弃）。
+    pub(crate) fn mark_ecn_threshold(&self, pkt: &mut Packet) {
+        let Some(th) = self.ecn_threshold_bytes else {
+            return;
+        };
+        let q_next = self.queue.bytes().saturating_add(pkt.size_bytes as u64);
+        if q_next >= th {
+            pkt.mark_ce_if_ect();
+        }
+    }
+
+    /// 队列字节数可能变化后调用：把上次变化以来的时长计入旧占用所在的桶。
+    pub(crate) fn note_queue_change(&mut self, now: SimTime) {
+        let bytes = self.queue.bytes();
+        if let Some(occ) = self.occupancy.as_mut() {
+            occ.advance(now, bytes);
         }
     }
 
@@ -51,3 +96,186 @@ impl Link {
         SimTime(nanos.min(u64::MAX as u128) as u64)
     }
 }
+
+/// 每包传播时延抖动的分布，以链路的基础 `latency` 为中心；采样结果不小于 0。
+///
+/// 抖动超过包间隔时后发的包可能先到，即链路会乱序交付。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkJitter {
+    /// 在 `[latency - max, latency + max]` 内均匀分布
+    Uniform { max: SimTime },
+    /// 均值 `latency`、标准差 `std_dev` 的正态分布，截断在 ±3σ 内
+    Normal { std_dev: SimTime },
+}
+
+impl LinkJitter {
+    /// 为一个包采样传播时延
+    pub(crate) fn sample(&self, base: SimTime, rng: &mut JitterRng) -> SimTime {
+        let offset = match *self {
+            LinkJitter::Uniform { max } => (2.0 * rng.next_uniform() - 1.0) * max.0 as f64,
+            LinkJitter::Normal { std_dev } => {
+                // Box-Muller
+                let u1 = 1.0 - rng.next_uniform();
+                let u2 = rng.next_uniform();
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                z.clamp(-3.0, 3.0) * std_dev.0 as f64
+            }
+        };
+        SimTime((base.0 as f64 + offset).round().max(0.0) as u64)
+    }
+}
+
+/// 链路抖动使用的确定性随机数流（splitmix64）
+#[derive(Debug, Clone)]
+pub(crate) struct JitterRng(u64);
+
+impl JitterRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// [0, 1) 上的均匀分布
+    pub(crate) fn next_uniform(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// 链路随机丢包：每个包独立地以 `probability` 被丢弃，随机数流按链路各自播种
+#[derive(Debug, Clone)]
+pub(crate) struct LinkLoss {
+    pub(crate) probability: f64,
+    pub(crate) rng: JitterRng,
+}
+
+impl LinkLoss {
+    /// 为下一个包抽样，返回是否丢弃
+    pub(crate) fn drops(&mut self) -> bool {
+        self.rng.next_uniform() < self.probability
+    }
+}
+
+/// 令牌桶整形器：令牌按 `rate_bps` 随时间连续累积，上限为 `burst_bytes`。
+///
+/// 桶内令牌非负时队头包才能开始发送，发送时按包长扣除令牌（可扣成负数），
+/// 因此长期速率不超过 `rate_bps`，突发最多为 `burst_bytes` 加一个包。
+/// 令牌以 “bit × 1e9” 为单位存为整数，按纳秒补充时没有舍入误差。
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBucket {
+    rate_bps: u64,
+    burst_bytes: u64,
+    tokens: i128,
+    last_refill: SimTime,
+}
+
+impl TokenBucket {
+    const SCALE: i128 = 8 * 1_000_000_000;
+
+    /// 新建一个满桶
+    pub(crate) fn new(rate_bps: u64, burst_bytes: u64) -> Self {
+        Self {
+            rate_bps,
+            burst_bytes,
+            tokens: burst_bytes as i128 * Self::SCALE,
+            last_refill: SimTime::ZERO,
+        }
+    }
+
+    pub(crate) fn rate_bps(&self) -> u64 {
+        self.rate_bps
+    }
+
+    pub(crate) fn burst_bytes(&self) -> u64 {
+        self.burst_bytes
+    }
+
+    fn refill(&mut self, now: SimTime) {
+        let dt = now.0.saturating_sub(self.last_refill.0) as i128;
+        let cap = self.burst_bytes as i128 * Self::SCALE;
+        self.tokens = (self.tokens + dt * self.rate_bps as i128).min(cap);
+        self.last_refill = self.last_refill.max(now);
+    }
+
+    /// 补充令牌到 `now`；令牌不足时返回还需等待的时长。
+    pub(crate) fn wait_time(&mut self, now: SimTime) -> Option<SimTime> {
+        self.refill(now);
+        if self.tokens >= 0 {
+            return None;
+        }
+        let rate = self.rate_bps as i128;
+        let ns = (-self.tokens + rate - 1) / rate;
+        Some(SimTime(ns.min(u64::MAX as i128) as u64))
+    }
+
+    /// 为开始发送的包扣除令牌
+    pub(crate) fn consume(&mut self, bytes: u32) {
+        self.tokens -= bytes as i128 * Self::SCALE;
+    }
+}
+
+/// 队列占用的时间加权直方图：每个桶累计队列字节数落在该桶内的时长。
+///
+/// 桶由升序的上界（含）给出，超过最后一个上界的占用计入上界为 `u64::MAX` 的溢出桶。
+/// 从 t=0（占用 0）开始积分，到最近一次队列变化为止，空闲时段计入占用为 0 的桶。
+#[derive(Debug, Clone)]
+pub(crate) struct QueueOccupancy {
+    upper_bounds: Vec<u64>,
+    time_ns: Vec<u64>,
+    last_t: SimTime,
+    last_bytes: u64,
+}
+
+impl QueueOccupancy {
+    pub(crate) fn new(mut upper_bounds: Vec<u64>, cur_bytes: u64) -> Self {
+        assert!(
+            !upper_bounds.is_empty(),
+            "queue occupancy histogram needs at least one bucket edge"
+        );
+        assert!(
+            upper_bounds.windows(2).all(|w| w[0] < w[1]),
+            "queue occupancy bucket edges must be strictly ascending, got {:?}",
+            upper_bounds
+        );
+        if upper_bounds.last() != Some(&u64::MAX) {
+            upper_bounds.push(u64::MAX);
+        }
+        let time_ns = vec![0; upper_bounds.len()];
+        Self {
+            upper_bounds,
+            time_ns,
+            last_t: SimTime::ZERO,
+            last_bytes: cur_bytes,
+        }
+    }
+
+    fn advance(&mut self, now: SimTime, bytes: u64) {
+        let now = now.max(self.last_t);
+        let bucket = self
+            .upper_bounds
+            .partition_point(|&ub| ub < self.last_bytes);
+        self.time_ns[bucket] += now.0 - self.last_t.0;
+        self.last_t = now;
+        self.last_bytes = bytes;
+    }
+
+    /// `(桶上界, 时间占比)`；尚未经过任何时间时占比均为 0。
+    pub(crate) fn fractions(&self) -> Vec<(u64, f64)> {
+        let total = self.last_t.0;
+        self.upper_bounds
+            .iter()
+            .zip(&self.time_ns)
+            .map(|(&ub, &ns)| {
+                let frac = if total == 0 {
+                    0.0
+                } else {
+                    ns as f64 / total as f64
+                };
+                (ub, frac)
+            })
+            .collect()
+    }
+}
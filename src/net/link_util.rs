@@ -0,0 +1,131 @@
+//! Periodic per-link utilization sampling.
+//!
+//! Once enabled, every `interval` the network records, for each link, the
+//! fraction of the window's capacity spent serializing bytes. Bytes are
+//! attributed to the window in which their serialization starts, so a single
+//! sample can slightly exceed 1.0 when a packet straddles the boundary.
+//!
+//! The sampler only keeps ticking while links carry traffic: a window in which
+//! no link sent anything stops it, and the next transmission re-arms it on the
+//! next interval boundary. `Simulator::run` therefore still terminates, and a
+//! network that never enables sampling schedules nothing.
+
+use std::io::{self, Write};
+
+use crate::sim::{Event, SimTime, Simulator, World};
+
+use super::Network;
+use super::id::LinkId;
+use super::net_world::NetWorld;
+
+#[derive(Debug)]
+pub(crate) struct LinkUtilSampler {
+    interval: SimTime,
+    /// Whether a `LinkUtilSample` event is pending.
+    armed: bool,
+    /// Per-link `tx_bytes` at the previous sample.
+    last_tx_bytes: Vec<u64>,
+    samples: Vec<(SimTime, LinkId, f64)>,
+}
+
+impl LinkUtilSampler {
+    /// First interval boundary strictly after `now`.
+    fn next_boundary(&self, now: SimTime) -> SimTime {
+        let k = now.0 / self.interval.0 + 1;
+        SimTime(k.saturating_mul(self.interval.0))
+    }
+}
+
+/// Event: close the current sampling window and record one sample per link.
+#[derive(Debug)]
+pub struct LinkUtilSample;
+
+impl Event for LinkUtilSample {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let w = world
+            .as_any_mut()
+            .downcast_mut::<NetWorld>()
+            .expect("world must be NetWorld");
+        w.net.sample_link_util(sim);
+    }
+}
+
+impl Network {
+    /// 开启链路利用率采样：每隔 `interval` 记录一次各链路在该窗口内的忙碌比例
+    /// （序列化字节 / 窗口容量），结果见 `link_util_series`。
+    ///
+    /// 采样点对齐到 `interval` 的整数倍；全网空闲的窗口不记录。未开启时不产生任何事件。
+    pub fn enable_link_util_sampling(&mut self, interval: SimTime) {
+        assert!(
+            interval.0 > 0,
+            "link util sampling interval must be > 0, got {:?}",
+            interval
+        );
+        self.link_util = Some(LinkUtilSampler {
+            interval,
+            armed: false,
+            last_tx_bytes: self.links.iter().map(|l| l.tx_bytes).collect(),
+            samples: Vec::new(),
+        });
+    }
+
+    /// 已记录的链路利用率样本：`(窗口结束时间, 链路, 忙碌比例)`。
+    pub fn link_util_series(&self) -> &[(SimTime, LinkId, f64)] {
+        self.link_util.as_ref().map_or(&[], |s| &s.samples)
+    }
+
+    /// 以 CSV 写出链路利用率样本（表头 `t_ns,link_id,from,to,util`）。
+    pub fn write_link_util_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "t_ns,link_id,from,to,util")?;
+        for &(t, link_id, util) in self.link_util_series() {
+            let link = &self.links[link_id.0];
+            writeln!(
+                w,
+                "{},{},{},{},{:.6}",
+                t.0, link_id.0, link.from.0, link.to.0, util
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 有链路开始发送时调用：采样器空闲则在下一个窗口边界布置采样事件。
+    pub(super) fn arm_link_util_sampling(&mut self, sim: &mut Simulator) {
+        let Some(sampler) = self.link_util.as_mut() else {
+            return;
+        };
+        if sampler.armed {
+            return;
+        }
+        sampler.armed = true;
+        sim.schedule(sampler.next_boundary(sim.now()), LinkUtilSample);
+    }
+
+    fn sample_link_util(&mut self, sim: &mut Simulator) {
+        let now = sim.now();
+        let Some(sampler) = self.link_util.as_mut() else {
+            return;
+        };
+        let window_ns = sampler.interval.0 as f64;
+        sampler.last_tx_bytes.resize(self.links.len(), 0);
+        let mut window = Vec::with_capacity(self.links.len());
+        let mut any_tx = false;
+        for (i, link) in self.links.iter().enumerate() {
+            let bytes = link.tx_bytes - sampler.last_tx_bytes[i];
+            sampler.last_tx_bytes[i] = link.tx_bytes;
+            any_tx |= bytes > 0;
+            let util = if link.bandwidth_bps == 0 {
+                0.0
+            } else {
+                (bytes as f64 * 8.0 * 1e9) / (link.bandwidth_bps as f64 * window_ns)
+            };
+            window.push((now, LinkId(i), util));
+        }
+        if !any_tx {
+            // 整个窗口全网空闲：不记录并停止，待下次发送时重新布置
+            sampler.armed = false;
+            return;
+        }
+        sampler.samples.extend(window);
+        sim.schedule(sampler.next_boundary(now), LinkUtilSample);
+    }
+}
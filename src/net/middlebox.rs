@@ -0,0 +1,60 @@
+//! Per-node packet-processing hooks ("middleboxes").
+//!
+//! A middlebox sees every packet a node forwards, right before it is enqueued
+//! on the egress link, and may rewrite it in place. This is the extension point
+//! for things like explicit ECN marking, priority rewriting or telemetry,
+//! without special-casing each of them in `forward_from`.
+
+use std::collections::HashMap;
+
+use crate::sim::SimTime;
+
+use super::{Network, NodeId, Packet};
+
+/// What a middlebox knows about the forwarding decision.
+#[derive(Debug, Clone, Copy)]
+pub struct MiddleboxCtx {
+    /// Node running the hook (the sender side of the egress link).
+    pub node: NodeId,
+    /// Next hop chosen for the packet.
+    pub next_hop: NodeId,
+    pub now: SimTime,
+    /// Egress queue occupancy before this packet is enqueued.
+    pub queue_bytes: u64,
+}
+
+/// Hook invoked on each forwarded packet.
+pub type Middlebox = Box<dyn FnMut(&mut Packet, &MiddleboxCtx) + Send>;
+
+#[derive(Default)]
+pub(crate) struct Middleboxes {
+    by_node: HashMap<NodeId, Vec<Middlebox>>,
+}
+
+impl Middleboxes {
+    /// Run the hooks registered on `ctx.node` in registration order.
+    pub(crate) fn process(&mut self, pkt: &mut Packet, ctx: &MiddleboxCtx) {
+        if let Some(hooks) = self.by_node.get_mut(&ctx.node) {
+            for hook in hooks {
+                hook(pkt, ctx);
+            }
+        }
+    }
+}
+
+impl Network {
+    /// 在节点上注册一个中间盒：该节点转发的每个包在入队前都会经过 `hook`，可就地修改。
+    ///
+    /// 同一节点可注册多个，按注册顺序执行。
+    pub fn add_middlebox(
+        &mut self,
+        node: NodeId,
+        hook: impl FnMut(&mut Packet, &MiddleboxCtx) + Send + 'static,
+    ) {
+        self.middleboxes
+            .by_node
+            .entry(node)
+            .or_default()
+            .push(Box::new(hook));
+    }
+}
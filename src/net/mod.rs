@@ -4,10 +4,16 @@
 
 // 子模块声明
 mod api;
+mod context;
 mod deliver_packet;
+mod flow_admission;
+mod flow_done;
+mod forward_packet;
 mod id;
 mod link;
 mod link_ready;
+mod link_util;
+mod middlebox;
 mod net_world;
 mod network;
 mod network_proto;
@@ -21,15 +27,23 @@ mod transport;
 
 // 重新导出公共接口
 pub use api::NetApi;
+pub use context::SimContext;
 pub use deliver_packet::DeliverPacket;
+pub use flow_done::FlowDoneCallback;
+pub use forward_packet::ForwardPacket;
 pub use id::{LinkId, NodeId};
-pub use link::Link;
+pub use link::{Link, LinkJitter};
 pub use link_ready::LinkReady;
+pub use link_util::LinkUtilSample;
+pub use middlebox::{Middlebox, MiddleboxCtx};
 pub use net_world::NetWorld;
-pub use network::{EcmpHashMode, Network};
-pub use node::{Host, Node, Switch};
+pub use network::{
+    DEFAULT_ECMP_SEED, EcmpHashInputs, EcmpHashMode, FIBER_NS_PER_METER, FlowTags, Network,
+    PRELOAD_FLOW_ID, TCP_IP_HEADER_BYTES,
+};
+pub use node::{CUT_THROUGH_HEADER_BYTES, ForwardingMode, Host, Node, Switch};
 pub use packet::{Ecn, Packet};
 pub(crate) use proto_bridge::{with_dctcp_stack, with_tcp_stack};
-pub use routing::RoutingTable;
-pub use stats::Stats;
+pub use routing::{RoutingTable, ecmp_flow_key};
+pub use stats::{FlowStats, NodeStats, Stats, jain_fairness_index};
 pub use transport::{DctcpSegment, TcpSegment, Transport};
@@ -2,32 +2,112 @@
 //!
 //! 定义网络拓扑结构，包含节点、链路、数据包转发和统计信息。
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use super::deliver_packet::DeliverPacket;
+use super::flow_admission::FlowAdmission;
+use super::flow_done::FlowDoneCallbacks;
 use super::id::{LinkId, NodeId};
-use super::link::Link;
+use super::link::{JitterRng, Link, LinkJitter, LinkLoss, QueueOccupancy, TokenBucket};
 use super::link_ready::LinkReady;
-use super::node::{Host, Node, Switch};
+use super::link_util::LinkUtilSampler;
+use super::middlebox::{MiddleboxCtx, Middleboxes};
+use super::node::{CUT_THROUGH_HEADER_BYTES, ForwardingMode, Host, Node, Switch};
 use super::packet::Packet;
-use super::routing::RoutingTable;
-use super::stats::Stats;
+use super::routing::{RoutingTable, ecmp_flow_key};
+use super::stats::{FlowStats, NodeStats, Stats, jain_fairness_index};
 use crate::proto::dctcp::DctcpStack;
 use crate::proto::tcp::TcpStack;
-use crate::queue::PriorityQueue;
+use crate::queue::{
+    CoDelParams, CoDelQueue, DEFAULT_PKT_BYTES, EgressScheduler, PacketQueue, RedMarker, RedParams,
+    RedQueue,
+};
 use crate::sim::{SimTime, Simulator};
 use crate::viz::{VizLogger, VizNodeKind};
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
+
+/// 光在光纤中的传播时延（约 2/3 光速）
+pub const FIBER_NS_PER_METER: f64 = 5.0;
+
+/// 默认的 ECMP 哈希种子（固定值，保证每次运行 ECMP 选择可重复）
+pub const DEFAULT_ECMP_SEED: u64 = 0xC5A1_DA7A_5EED_1234;
+
+/// `preload_link_queue` 注入的背景包使用的流 id
+pub const PRELOAD_FLOW_ID: u64 = u64::MAX;
+
+/// TCP/IP 头部开销（IPv4 20B + TCP 20B），MSS + 头部不应超过 MTU
+pub const TCP_IP_HEADER_BYTES: u64 = 40;
 
 /// ECMP 哈希的粒度。
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum EcmpHashMode {
     /// 按 flow_id（默认，per-flow ECMP）
+    #[default]
     Flow,
-    /// 按 packet（包含 pkt_id，per-packet ECMP）
+    /// 按 flow_id + 每流熵值（模拟 UDP 源端口熵，仍是 per-flow ECMP）
+    FlowEntropy,
+    /// 按 packet（flow_id 与 pkt_id，per-packet ECMP）
     Packet,
+    /// Flowlet 交换：同一交换机上同一条流的相邻包间隔超过 `gap` 时才重新哈希选路，
+    /// 否则沿用上一个包的下一跳（在冲突与乱序之间折中）
+    Flowlet { gap: SimTime },
+}
+
+/// ECMP 哈希输入配置。
+///
+/// 真实交换机的负载均衡依赖报文头部熵；同一对 src/dst 之间的多条流
+/// 可以通过不同的熵值（如 UDP 源端口）被分散到不同路径上。
+#[derive(Debug, Clone, Default)]
+pub struct EcmpHashInputs {
+    /// 哈希粒度
+    pub mode: EcmpHashMode,
+    /// 每条流的熵值（flow_id -> entropy）；未配置的流熵值为 0
+    pub flow_entropy: HashMap<u64, u64>,
 }
 
+impl EcmpHashInputs {
+    /// 计算某个 packet 的 ECMP 哈希 key（`src`/`dst` 为 packet 的源与目的）。
+    pub fn key(&self, src: NodeId, dst: NodeId, flow_id: u64, pkt_id: u64) -> u64 {
+        match self.mode {
+            EcmpHashMode::Flow | EcmpHashMode::Flowlet { .. } | EcmpHashMode::FlowEntropy => {
+                self.flow_key(src, dst, flow_id)
+            }
+            EcmpHashMode::Packet => ecmp_flow_key(src, dst, flow_id) ^ pkt_id,
+        }
+    }
+
+    /// 计算整条流的 ECMP 哈希 key（按 (src, dst, flow_id) 混合、不含 pkt_id，用于生成静态路径）。
+    pub fn flow_key(&self, src: NodeId, dst: NodeId, flow_id: u64) -> u64 {
+        match self.mode {
+            EcmpHashMode::FlowEntropy => {
+                let entropy = self.flow_entropy.get(&flow_id).copied().unwrap_or(0);
+                // 先把熵值打散，避免 flow_id 与 entropy 的低位简单抵消
+                ecmp_flow_key(
+                    src,
+                    dst,
+                    flow_id ^ entropy.wrapping_mul(0xD6E8_FEB8_6659_FD93),
+                )
+            }
+            EcmpHashMode::Flow | EcmpHashMode::Packet | EcmpHashMode::Flowlet { .. } => {
+                ecmp_flow_key(src, dst, flow_id)
+            }
+        }
+    }
+}
+
+/// 某个交换机上某条流当前 flowlet 的状态（`EcmpHashMode::Flowlet`）。
+#[derive(Debug, Clone, Copy, Default)]
+struct FlowletState {
+    /// 该流上一个包经过此节点的时间
+    last_seen: SimTime,
+    /// flowlet 序号，每开始一个新 flowlet 加一，参与哈希
+    id: u64,
+    next_hop: Option<NodeId>,
+}
+
+/// 流的键值标签（如 layer、tensor id、tenant），用于分析时按标签分组统计。
+pub type FlowTags = BTreeMap<String, String>;
+
 /// 网络拓扑
 pub struct Network {
     nodes: Vec<Option<Box<dyn Node>>>,
@@ -43,7 +123,49 @@ pub struct Network {
     pub tcp: TcpStack,
     pub dctcp: DctcpStack,
     pub viz: Option<VizLogger>,
-    ecmp_hash_mode: EcmpHashMode,
+    ecmp_hash: EcmpHashInputs,
+    flow_tags: HashMap<u64, FlowTags>,
+    /// 流的可读名称（如 `c0:step2:rank1`），写入 viz 输出
+    pub(super) flow_labels: HashMap<u64, String>,
+    /// 已经把名称写进 viz 的流（Meta 里或 `FlowLabel` 事件）
+    pub(super) viz_labeled_flows: HashSet<u64>,
+    /// 下一个集合通信实例的编号（用于生成流名称）
+    next_collective_id: u64,
+    /// (node, flow_id) -> 当前 flowlet（仅 `EcmpHashMode::Flowlet` 使用）
+    flowlets: HashMap<(NodeId, u64), FlowletState>,
+    pub(super) admission: FlowAdmission,
+    /// 与协议无关的流完成回调
+    pub(super) flow_done: FlowDoneCallbacks,
+    /// 链路 MTU（bytes，默认 1500），按包数换算队列容量时的单位
+    mtu_bytes: u64,
+    /// 预期的最大包大小（bytes），用于校验队列容量
+    max_packet_bytes: u64,
+    /// 故障注入：flow_id -> 开始丢包的时间
+    failed_flows: HashMap<u64, SimTime>,
+    /// 按节点配置的出方向调度策略（未配置即 FIFO）
+    egress_schedulers: HashMap<NodeId, EgressScheduler>,
+    /// 按流配置的出方向调度权重（仅对 FlowWrr / Drr 队列生效）
+    flow_egress_weights: HashMap<u64, u32>,
+    /// 按租户配置的出方向调度权重（仅对 TenantDrr 队列生效）
+    tenant_egress_weights: HashMap<u32, u32>,
+    /// 流所属的租户（打在该流的每个包上；未配置即租户 0）
+    flow_tenants: HashMap<u64, u32>,
+    /// 按流配置的截止时间（打在该流的每个包上，供 EDF 队列使用）
+    flow_deadlines: HashMap<u64, SimTime>,
+    /// 按节点注册的逐包处理钩子
+    pub(super) middleboxes: Middleboxes,
+    /// 按流统计（字节、起止时间、重传、ECN 标记）
+    flow_stats: HashMap<u64, FlowStats>,
+    /// 按节点统计（下标为 NodeId）
+    node_stats: Vec<NodeStats>,
+    /// Host 网卡发送速率限制（该 Host 所有出方向链路共享一个令牌桶）
+    host_nics: HashMap<NodeId, TokenBucket>,
+    /// 链路利用率采样（未开启时为 None）
+    pub(super) link_util: Option<LinkUtilSampler>,
+    /// 链路抖动的随机数流，由 ECMP 种子播种
+    jitter_rng: JitterRng,
+    /// 找不到路由时是否 panic（默认丢包并计入 `Stats::dropped_no_route`）
+    strict_routing: bool,
 }
 
 impl Default for Network {
@@ -56,22 +178,162 @@ impl Default for Network {
             edges: HashMap::new(),
             adj: Vec::new(),
             rev_adj: Vec::new(),
-            // 固定盐，保证每次运行 ECMP 选择可重复
-            routing: RoutingTable::new(0xC5A1_DA7A_5EED_1234),
+            routing: RoutingTable::new(DEFAULT_ECMP_SEED),
             next_pkt_id: 0,
             stats: Stats::default(),
             tcp: TcpStack::default(),
             dctcp: DctcpStack::default(),
             viz: None,
-            ecmp_hash_mode: EcmpHashMode::Flow,
+            ecmp_hash: EcmpHashInputs::default(),
+            flow_tags: HashMap::new(),
+            flow_labels: HashMap::new(),
+            viz_labeled_flows: HashSet::new(),
+            next_collective_id: 0,
+            flowlets: HashMap::new(),
+            admission: FlowAdmission::default(),
+            flow_done: FlowDoneCallbacks::default(),
+            mtu_bytes: DEFAULT_PKT_BYTES,
+            max_packet_bytes: DEFAULT_PKT_BYTES,
+            failed_flows: HashMap::new(),
+            egress_schedulers: HashMap::new(),
+            flow_egress_weights: HashMap::new(),
+            tenant_egress_weights: HashMap::new(),
+            flow_tenants: HashMap::new(),
+            flow_deadlines: HashMap::new(),
+            middleboxes: Middleboxes::default(),
+            flow_stats: HashMap::new(),
+            node_stats: Vec::new(),
+            host_nics: HashMap::new(),
+            link_util: None,
+            jitter_rng: JitterRng::new(DEFAULT_ECMP_SEED),
+            strict_routing: false,
         }
     }
 }
 
 impl Network {
+    /// 某条流的统计；该流尚未发送或送达任何数据时返回 `None`。
+    pub fn flow_stats(&self, flow_id: u64) -> Option<FlowStats> {
+        self.flow_stats.get(&flow_id).copied()
+    }
+
+    /// 所有流的统计（flow_id -> FlowStats）。
+    pub fn all_flow_stats(&self) -> &HashMap<u64, FlowStats> {
+        &self.flow_stats
+    }
+
+    /// 所有已开始的流在 `now` 时刻的交付速率的 Jain 公平性指数
+    /// （见 `FlowStats::delivery_rate_bps`）；没有可统计的流时返回 `None`。
+    pub fn flow_fairness_index(&self, now: SimTime) -> Option<f64> {
+        let rates: Vec<f64> = self
+            .flow_stats
+            .values()
+            .filter_map(|s| s.delivery_rate_bps(now))
+            .collect();
+        jain_fairness_index(&rates)
+    }
+
+    /// 某个节点的收发与丢包计数。
+    pub fn node_stats(&self, node: NodeId) -> NodeStats {
+        self.node_stats[node.0]
+    }
+
+    pub(crate) fn flow_stats_mut(&mut self, flow_id: u64) -> &mut FlowStats {
+        self.flow_stats.entry(flow_id).or_default()
+    }
+
+    /// 设置 ECMP 哈希种子（默认 `DEFAULT_ECMP_SEED`），并使路由表重建。
+    ///
+    /// 同一种子下的选路完全确定；换种子可以刻意得到另一组同样可复现的 ECMP 选择。
+    /// 种子只改变哈希盐，哈希 key 不变：per-packet 模式仍按 flow_id 与包 id 选路。
+    /// 同时重新播种链路抖动（`set_link_jitter`）的随机数流。
+    pub fn set_ecmp_seed(&mut self, seed: u64) {
+        self.routing.set_hash_salt(seed);
+        self.jitter_rng = JitterRng::new(seed);
+    }
+
     /// 设置 ECMP 哈希粒度（per-flow / per-packet）。
     pub fn set_ecmp_hash_mode(&mut self, mode: EcmpHashMode) {
-        self.ecmp_hash_mode = mode;
+        self.ecmp_hash.mode = mode;
+    }
+
+    /// 整体替换 ECMP 哈希输入配置。
+    pub fn set_ecmp_hash_inputs(&mut self, inputs: EcmpHashInputs) {
+        self.ecmp_hash = inputs;
+    }
+
+    /// 当前的 ECMP 哈希输入配置。
+    pub fn ecmp_hash_inputs(&self) -> &EcmpHashInputs {
+        &self.ecmp_hash
+    }
+
+    /// 设置某条流的 ECMP 熵值（仅在 `EcmpHashMode::FlowEntropy` 下生效）。
+    pub fn set_flow_ecmp_entropy(&mut self, flow_id: u64, entropy: u64) {
+        self.ecmp_hash.flow_entropy.insert(flow_id, entropy);
+    }
+
+    /// 设置某条流的标签（覆盖已有标签）。
+    pub fn set_flow_tags(&mut self, flow_id: u64, tags: FlowTags) {
+        self.flow_tags.insert(flow_id, tags);
+    }
+
+    /// 给某条流追加/覆盖一个标签。
+    pub fn tag_flow(&mut self, flow_id: u64, key: impl Into<String>, value: impl Into<String>) {
+        self.flow_tags
+            .entry(flow_id)
+            .or_default()
+            .insert(key.into(), value.into());
+    }
+
+    /// 获取某条流的标签。
+    pub fn flow_tags(&self, flow_id: u64) -> Option<&FlowTags> {
+        self.flow_tags.get(&flow_id)
+    }
+
+    /// 给某条流起一个可读名称（覆盖已有名称）。
+    ///
+    /// `emit_viz_meta` 之前设置的名称写进 Meta 的 `flow_labels`；之后设置的名称
+    /// 在该流的下一条 viz 事件之前以 `FlowLabel` 事件写出。
+    pub fn set_flow_label(&mut self, flow_id: u64, label: impl Into<String>) {
+        self.flow_labels.insert(flow_id, label.into());
+        self.viz_labeled_flows.remove(&flow_id);
+    }
+
+    /// 获取某条流的名称。
+    pub fn flow_label(&self, flow_id: u64) -> Option<&str> {
+        self.flow_labels.get(&flow_id).map(String::as_str)
+    }
+
+    /// 分配一个集合通信实例编号（按调用顺序 0, 1, 2, ...），用于流名称里的 `c<N>`。
+    pub fn next_collective_id(&mut self) -> u64 {
+        let id = self.next_collective_id;
+        self.next_collective_id += 1;
+        id
+    }
+
+    /// 返回带有 `key=value` 标签的所有 flow_id（升序）。
+    pub fn flows_with_tag(&self, key: &str, value: &str) -> Vec<u64> {
+        let mut ids = self
+            .flow_tags
+            .iter()
+            .filter(|(_, tags)| tags.get(key).is_some_and(|v| v == value))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// 故障注入：从 `at` 起丢弃 `flow_id` 的所有包。
+    ///
+    /// 用于测试集合通信遇到永不完成的成员流时的表现（应能被诊断出来，而不是静默卡住）。
+    /// TCP/DCTCP 发送端在 `at` 之后第一次 RTO 超时时放弃该流：不再重传并释放并发流 slot，
+    /// 仿真因此能正常结束，未完成的流留给上层报告。
+    pub fn fail_flow_at(&mut self, flow_id: u64, at: SimTime) {
+        self.failed_flows.insert(flow_id, at);
+    }
+
+    pub(crate) fn flow_failed(&self, flow_id: u64, now: SimTime) -> bool {
+        self.failed_flows.get(&flow_id).is_some_and(|at| now >= *at)
     }
 
     /// 添加主机节点
@@ -81,6 +343,7 @@ impl Network {
         self.nodes.push(Some(Box::new(Host::new(id, name.clone()))));
         self.node_names.push(name);
         self.node_kinds.push(VizNodeKind::Host);
+        self.node_stats.push(NodeStats::default());
         self.adj.push(Vec::new());
         self.rev_adj.push(Vec::new());
         id
@@ -94,11 +357,45 @@ impl Network {
             .push(Some(Box::new(Switch::new(id, name.clone()))));
         self.node_names.push(name);
         self.node_kinds.push(VizNodeKind::Switch);
+        self.node_stats.push(NodeStats::default());
         self.adj.push(Vec::new());
         self.rev_adj.push(Vec::new());
         id
     }
 
+    /// 设置某个交换机的转发处理时延：包到达后延迟 `delay` 才转发到出端口。
+    ///
+    /// `node` 不是交换机时 panic；Host 的转发不受影响。
+    pub fn set_switch_processing_delay(&mut self, node: NodeId, delay: SimTime) {
+        self.nodes[node.0]
+            .as_mut()
+            .expect("node exists")
+            .as_switch_mut()
+            .unwrap_or_else(|| panic!("{:?} is not a switch", node))
+            .set_processing_delay(delay);
+    }
+
+    /// 设置某个交换机的转发方式（store-and-forward / cut-through）。
+    ///
+    /// `node` 不是交换机时 panic。
+    pub fn set_switch_forwarding_mode(&mut self, node: NodeId, mode: ForwardingMode) {
+        self.nodes[node.0]
+            .as_mut()
+            .expect("node exists")
+            .as_switch_mut()
+            .unwrap_or_else(|| panic!("{:?} is not a switch", node))
+            .set_forwarding_mode(mode);
+    }
+
+    /// `to` 是否在收到包头后就开始转发 `pkt`（cut-through 交换机且不是包的终点）
+    fn cuts_through(&self, to: NodeId, pkt: &Packet) -> bool {
+        pkt.dst != to
+            && self.nodes[to.0]
+                .as_deref()
+                .and_then(|node| node.as_switch())
+                .is_some_and(|sw| sw.forwarding_mode() == ForwardingMode::CutThrough)
+    }
+
     /// 连接两个节点（创建单向链路）
     pub fn connect(
         &mut self,
@@ -107,6 +404,17 @@ impl Network {
         latency: SimTime,
         bandwidth_bps: u64,
     ) -> LinkId {
+        // 单位检查：过大的时延在 debug 构建下直接 panic，过小的只告警
+        if let Some(issue) = latency.link_latency_unit_issue() {
+            debug_assert!(
+                latency <= SimTime::MAX_PLAUSIBLE_LINK_LATENCY,
+                "{:?}->{:?}: {}",
+                from,
+                to,
+                issue
+            );
+            warn!(from = ?from, to = ?to, "{}", issue);
+        }
         let id = LinkId(self.links.len());
         self.links.push(Link::new(from, to, latency, bandwidth_bps));
         self.edges.insert((from, to), id);
@@ -116,6 +424,102 @@ impl Network {
         id
     }
 
+    /// 按链路长度（米）创建单向链路，时延按光纤中的光速（约 5 ns/m）推算。
+    ///
+    /// 适合建模跨地域/长距离链路；本质上是 `connect` 的薄封装。
+    pub fn connect_by_distance(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        meters: f64,
+        bandwidth_bps: u64,
+    ) -> LinkId {
+        assert!(
+            meters.is_finite() && meters >= 0.0,
+            "link length must be a non-negative number of meters, got {}",
+            meters
+        );
+        let latency = SimTime((meters * FIBER_NS_PER_METER).round() as u64);
+        self.connect(from, to, latency, bandwidth_bps)
+    }
+
+    /// 设置 MTU（默认 1500B，巨帧实验可设为 9000），同时把预期的最大包大小设为该值。
+    pub fn set_mtu(&mut self, bytes: u64) {
+        assert!(bytes > 0, "MTU must be > 0");
+        self.mtu_bytes = bytes;
+        self.max_packet_bytes = bytes;
+    }
+
+    /// 当前 MTU（bytes）
+    pub fn mtu(&self) -> u64 {
+        self.mtu_bytes
+    }
+
+    /// 按 MTU 把“包数”换算为字节数（队列容量、ECN 阈值等），对应 `queue::mem_from_pkt`。
+    pub fn mem_from_pkt(&self, pkts: u64) -> u64 {
+        pkts.saturating_mul(self.mtu_bytes)
+    }
+
+    /// 检查 `mss + TCP_IP_HEADER_BYTES` 不超过 MTU。
+    pub fn validate_mss(&self, mss: u32) -> Result<(), String> {
+        let wire = mss as u64 + TCP_IP_HEADER_BYTES;
+        if wire > self.mtu_bytes {
+            return Err(format!(
+                "mss {mss} + {TCP_IP_HEADER_BYTES}-byte TCP/IP header = {wire} bytes exceeds the {}-byte MTU",
+                self.mtu_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    /// 设置预期的最大包大小（默认 1500B），队列容量小于它时会告警。
+    ///
+    /// 使用更小 MSS 的实验（例如刻意构造小缓冲）应同步调小此值。
+    pub fn set_max_packet_bytes(&mut self, bytes: u64) {
+        self.max_packet_bytes = bytes;
+    }
+
+    /// 队列容量小于一个最大包时，所有数据包都会被丢弃、流永远无法推进。
+    fn warn_if_sub_packet_capacity(&self, capacity_bytes: u64, scope: &str) {
+        if capacity_bytes < self.max_packet_bytes {
+            warn!(
+                capacity_bytes,
+                max_packet_bytes = self.max_packet_bytes,
+                scope,
+                "队列容量小于一个最大包：大于容量的包会被全部丢弃（检查 pkts→bytes 换算）"
+            );
+        }
+    }
+
+    /// 检查所有链路的队列容量都至少能放下一个最大包。
+    ///
+    /// 否则大于容量的包（例如满 MSS 的数据段）每次都会被丢弃，流会一直卡在 RTO 重传里。
+    pub fn validate_queue_capacities(&self) -> Result<(), String> {
+        let bad = self
+            .links
+            .iter()
+            .filter(|l| l.queue.capacity_bytes() < self.max_packet_bytes)
+            .map(|l| {
+                format!(
+                    "{}->{} ({} B)",
+                    self.node_names[l.from.0],
+                    self.node_names[l.to.0],
+                    l.queue.capacity_bytes()
+                )
+            })
+            .collect::<Vec<_>>();
+        if bad.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "queue capacity is smaller than one {}-byte packet on {} link(s): {}; \
+             every larger packet would be dropped",
+            self.max_packet_bytes,
+            bad.len(),
+            bad.join(", ")
+        ))
+    }
+
     /// 设置某条单向链路的队列容量（字节）。
     ///
     /// 用于实验中把“瓶颈链路”改为有限缓冲，从而产生丢包（DropTail）。
@@ -124,13 +528,149 @@ impl Network {
             .edges
             .get(&(from, to))
             .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
-        self.links[link_id.0].queue = Box::new(PriorityQueue::new(capacity_bytes));
+        self.warn_if_sub_packet_capacity(capacity_bytes, "link");
+        self.links[link_id.0].queue = self.new_link_queue(from, capacity_bytes);
+    }
+
+    /// 按带宽时延积设置某条单向链路的队列容量：`multiple * bandwidth_bps * base_rtt / 8`，
+    /// 其中 base_rtt 取该链路传播时延的两倍。
+    pub fn set_link_queue_capacity_bdp(&mut self, from: NodeId, to: NodeId, multiple: f64) {
+        let base_rtt = SimTime(self.link_latency(from, to).0.saturating_mul(2));
+        self.set_link_queue_capacity_bdp_rtt(from, to, multiple, base_rtt);
+    }
+
+    /// 同 `set_link_queue_capacity_bdp`，但显式给出 base_rtt（如整条路径的往返时延）。
+    pub fn set_link_queue_capacity_bdp_rtt(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        multiple: f64,
+        base_rtt: SimTime,
+    ) {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        let capacity_bytes = bdp_bytes(multiple, self.links[link_id.0].bandwidth_bps, base_rtt);
+        self.set_link_queue_capacity_bytes(from, to, capacity_bytes);
+    }
+
+    /// 按各自带宽把所有链路的队列容量设为 `multiple` 倍 BDP（base_rtt 对所有链路相同）。
+    pub fn set_all_link_queue_capacity_bdp(&mut self, multiple: f64, base_rtt: SimTime) {
+        let links = self
+            .links
+            .iter()
+            .map(|l| (l.from, l.to))
+            .collect::<Vec<_>>();
+        for (from, to) in links {
+            self.set_link_queue_capacity_bdp_rtt(from, to, multiple, base_rtt);
+        }
+    }
+
+    /// 某条单向链路的队列容量（字节）。
+    pub fn link_queue_capacity_bytes(&self, from: NodeId, to: NodeId) -> u64 {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].queue.capacity_bytes()
+    }
+
+    /// 在某条单向链路的队列与链路之间安装令牌桶整形器（速率 `rate_bps`，桶深 `burst_bytes`）。
+    ///
+    /// 与链路序列化速率无关：令牌不足时队头包在队列中等待，长期速率被限制在
+    /// `rate_bps`，空闲后最多以链路速率突发 `burst_bytes`（另加一个包）。
+    pub fn set_link_shaper(&mut self, from: NodeId, to: NodeId, rate_bps: u64, burst_bytes: u64) {
+        assert!(rate_bps > 0, "shaper rate must be > 0");
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].shaper = Some(TokenBucket::new(rate_bps, burst_bytes));
+    }
+
+    /// 某条链路的整形参数 `(rate_bps, burst_bytes)`（未安装返回 `None`）。
+    pub fn link_shaper(&self, from: NodeId, to: NodeId) -> Option<(u64, u64)> {
+        let link_id = self.edges.get(&(from, to))?;
+        let tb = self.links[link_id.0].shaper.as_ref()?;
+        Some((tb.rate_bps(), tb.burst_bytes()))
+    }
+
+    /// 设置某个 Host 的网卡发送速率：该 Host 所有出方向链路共享一个速率为 `rate_bps`、
+    /// 桶深为 0 的令牌桶，逐包 pacing，即使链路更快也不超过网卡速率。
+    ///
+    /// 与 `set_host_egress_queue_capacity_bytes` 不同，它限制的是发送速率而不是缓冲大小。
+    /// `node` 不是 Host 时 panic。
+    pub fn set_host_nic_rate(&mut self, node: NodeId, rate_bps: u64) {
+        assert!(rate_bps > 0, "host NIC rate must be > 0");
+        assert!(
+            matches!(self.node_kinds.get(node.0), Some(VizNodeKind::Host)),
+            "{:?} is not a host",
+            node
+        );
+        self.host_nics.insert(node, TokenBucket::new(rate_bps, 0));
+    }
+
+    /// 某个 Host 的网卡发送速率（未设置返回 `None`）。
+    pub fn host_nic_rate(&self, node: NodeId) -> Option<u64> {
+        self.host_nics.get(&node).map(TokenBucket::rate_bps)
+    }
+
+    /// 给 Host 加一块网卡：创建 `host <-> switch` 的一对链路，返回网卡序号（见 `host_nics`）。
+    ///
+    /// 不指定网卡的流照常按 ECMP 在各条上行链路之间选路；`route_ecmp_path_via_nic`
+    /// 把流固定在某块网卡上。`set_host_nic_rate` 限制的是整台 Host 的总发送速率，
+    /// 单块网卡的速率就是其链路带宽。`host` 不是 Host、`switch` 不是交换机或
+    /// Host 已经连到该交换机时 panic。
+    pub fn add_host_nic(
+        &mut self,
+        host: NodeId,
+        switch: NodeId,
+        latency: SimTime,
+        bandwidth_bps: u64,
+    ) -> usize {
+        assert!(
+            matches!(self.node_kinds.get(host.0), Some(VizNodeKind::Host)),
+            "{:?} is not a host",
+            host
+        );
+        assert!(
+            matches!(self.node_kinds.get(switch.0), Some(VizNodeKind::Switch)),
+            "{:?} is not a switch",
+            switch
+        );
+        assert!(
+            !self.host_nics(host).contains(&switch),
+            "{:?} already has a NIC on {:?}",
+            host,
+            switch
+        );
+        self.connect(host, switch, latency, bandwidth_bps);
+        self.connect(switch, host, latency, bandwidth_bps);
+        self.host_nics(host).len() - 1
+    }
+
+    /// Host 的各块网卡所连的交换机，按网卡序号排列。
+    ///
+    /// 每条 Host -> 交换机的上行链路都是一块网卡，按连接顺序编号：拓扑构建时用
+    /// `connect` 接上的原有上行链路是 0 号网卡，之后 `add_host_nic` 加的依次往后排。
+    pub fn host_nics(&self, host: NodeId) -> Vec<NodeId> {
+        let mut nics: Vec<NodeId> = Vec::new();
+        for &next in self.adj.get(host.0).map_or(&[][..], Vec::as_slice) {
+            if matches!(self.node_kinds.get(next.0), Some(VizNodeKind::Switch))
+                && !nics.contains(&next)
+            {
+                nics.push(next);
+            }
+        }
+        nics
     }
 
     /// 设置所有链路的队列容量（字节）。
     pub fn set_all_link_queue_capacity_bytes(&mut self, capacity_bytes: u64) {
-        for link in &mut self.links {
-            link.queue = Box::new(PriorityQueue::new(capacity_bytes));
+        self.warn_if_sub_packet_capacity(capacity_bytes, "all links");
+        for i in 0..self.links.len() {
+            self.links[i].queue = self.new_link_queue(self.links[i].from, capacity_bytes);
         }
     }
 
@@ -140,30 +680,175 @@ impl Network {
     /// 过小的队列会把“本地发送缓存不足”误建模成网络丢包，导致 TCP 进入
     /// 一段时间的 RTO 退化（one-segment-per-RTO）并夸大 FCT。
     pub fn set_host_egress_queue_capacity_bytes(&mut self, capacity_bytes: u64) {
-        for link in &mut self.links {
+        self.warn_if_sub_packet_capacity(capacity_bytes, "host egress");
+        for i in 0..self.links.len() {
+            let from = self.links[i].from;
             if self
                 .node_kinds
-                .get(link.from.0)
+                .get(from.0)
                 .is_some_and(|k| matches!(*k, VizNodeKind::Host))
             {
-                link.queue = Box::new(PriorityQueue::new(capacity_bytes));
+                self.links[i].queue = self.new_link_queue(from, capacity_bytes);
             }
         }
     }
 
     /// 设置所有 Switch 节点“出方向”链路的队列容量（字节）。
     pub fn set_switch_egress_queue_capacity_bytes(&mut self, capacity_bytes: u64) {
-        for link in &mut self.links {
+        self.warn_if_sub_packet_capacity(capacity_bytes, "switch egress");
+        for i in 0..self.links.len() {
+            let from = self.links[i].from;
             if self
                 .node_kinds
-                .get(link.from.0)
+                .get(from.0)
                 .is_some_and(|k| matches!(*k, VizNodeKind::Switch))
             {
-                link.queue = Box::new(PriorityQueue::new(capacity_bytes));
+                self.links[i].queue = self.new_link_queue(from, capacity_bytes);
+            }
+        }
+    }
+
+    /// 把某条单向链路的队列换成 RED（`RedQueue`），容量为 `capacity_bytes`。
+    ///
+    /// ECT 包在早期拥塞时被标记 CE，其余包被提前丢弃。之后再调用容量/调度相关的
+    /// setter 会按出口调度策略重建队列，RED 配置随之失效。
+    pub fn set_link_red_queue(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        capacity_bytes: u64,
+        params: RedParams,
+    ) {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.warn_if_sub_packet_capacity(capacity_bytes, "link");
+        self.links[link_id.0].queue = Box::new(RedQueue::new(capacity_bytes, params));
+    }
+
+    /// 把所有 Switch 节点出方向链路的队列换成 RED（`RedQueue`）。
+    pub fn set_switch_egress_red_queue(&mut self, capacity_bytes: u64, params: RedParams) {
+        self.warn_if_sub_packet_capacity(capacity_bytes, "switch egress");
+        for i in 0..self.links.len() {
+            let from = self.links[i].from;
+            if self
+                .node_kinds
+                .get(from.0)
+                .is_some_and(|k| matches!(*k, VizNodeKind::Switch))
+            {
+                self.links[i].queue = Box::new(RedQueue::new(capacity_bytes, params));
+            }
+        }
+    }
+
+    /// 把某条单向链路的队列换成 CoDel（`CoDelQueue`），容量为 `capacity_bytes`。
+    ///
+    /// 与 RED 一样，之后再调用容量/调度相关的 setter 会重建队列。
+    pub fn set_link_codel_queue(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        capacity_bytes: u64,
+        params: CoDelParams,
+    ) {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.warn_if_sub_packet_capacity(capacity_bytes, "link");
+        self.links[link_id.0].queue = Box::new(CoDelQueue::new(capacity_bytes, params));
+    }
+
+    /// 设置某个节点所有出方向链路的调度策略（保留当前队列容量）。
+    ///
+    /// 例如 Host 上同时进行多个异步 collective 时，`FlowWrr` 让各条流轮流
+    /// 占用出口带宽，避免先到的大 backlog 饿死后到的流。需在注入流量前调用。
+    pub fn set_egress_scheduler(&mut self, node: NodeId, scheduler: EgressScheduler) {
+        self.egress_schedulers.insert(node, scheduler);
+        for i in 0..self.links.len() {
+            if self.links[i].from == node {
+                let capacity_bytes = self.links[i].queue.capacity_bytes();
+                self.links[i].queue = self.new_link_queue(node, capacity_bytes);
             }
         }
     }
 
+    /// 设置某条流在 `FlowWrr` 出口上的权重（每轮可发送的包数，默认 1）；
+    /// 在 `Drr` 出口上则按该倍数放大每轮配额。
+    pub fn set_flow_egress_weight(&mut self, flow_id: u64, weight: u32) {
+        assert!(weight > 0, "egress weight must be > 0 (flow {})", flow_id);
+        self.flow_egress_weights.insert(flow_id, weight);
+        for link in &mut self.links {
+            link.queue.set_flow_weight(flow_id, weight);
+        }
+    }
+
+    /// 设置某个租户在 `TenantDrr` 出口上的权重（默认 1）：积压的租户按权重之比
+    /// 分享出口带宽，与各自的流数无关。
+    pub fn set_tenant_weight(&mut self, tenant_id: u32, weight: u32) {
+        assert!(
+            weight > 0,
+            "tenant weight must be > 0 (tenant {})",
+            tenant_id
+        );
+        self.tenant_egress_weights.insert(tenant_id, weight);
+        for link in &mut self.links {
+            link.queue.set_tenant_weight(tenant_id, weight);
+        }
+    }
+
+    /// 把某条流划给租户 `tenant_id`：此后为该流创建的包（含 ACK）都带上该租户。
+    pub fn set_flow_tenant(&mut self, flow_id: u64, tenant_id: u32) {
+        self.flow_tenants.insert(flow_id, tenant_id);
+    }
+
+    /// 某条流所属的租户（未设置为 0）。
+    pub fn flow_tenant(&self, flow_id: u64) -> u32 {
+        self.flow_tenants.get(&flow_id).copied().unwrap_or(0)
+    }
+
+    /// 设置某条流的截止时间：此后为该流创建的包都会携带它，`Edf` 出口按它排序。
+    pub fn set_flow_deadline(&mut self, flow_id: u64, deadline: SimTime) {
+        self.flow_deadlines.insert(flow_id, deadline);
+    }
+
+    /// 某条流的截止时间（未设置返回 `None`）。
+    pub fn flow_deadline(&self, flow_id: u64) -> Option<SimTime> {
+        self.flow_deadlines.get(&flow_id).copied()
+    }
+
+    /// 设置所有 Switch 节点出方向链路的调度策略（保留当前队列容量）。
+    pub fn set_switch_egress_scheduler(&mut self, scheduler: EgressScheduler) {
+        let switches = self
+            .node_kinds
+            .iter()
+            .enumerate()
+            .filter(|(_, k)| matches!(**k, VizNodeKind::Switch))
+            .map(|(i, _)| NodeId(i))
+            .collect::<Vec<_>>();
+        for node in switches {
+            self.set_egress_scheduler(node, scheduler);
+        }
+    }
+
+    /// 按 `from` 节点的调度策略创建链路队列
+    fn new_link_queue(&self, from: NodeId, capacity_bytes: u64) -> Box<dyn PacketQueue> {
+        let scheduler = self
+            .egress_schedulers
+            .get(&from)
+            .copied()
+            .unwrap_or_default();
+        let mut queue = scheduler.build(capacity_bytes);
+        for (flow_id, weight) in &self.flow_egress_weights {
+            queue.set_flow_weight(*flow_id, *weight);
+        }
+        for (tenant_id, weight) in &self.tenant_egress_weights {
+            queue.set_tenant_weight(*tenant_id, *weight);
+        }
+        queue
+    }
+
     /// 设置某条单向链路的 ECN 标记阈值（bytes）。
     pub fn set_link_ecn_threshold_bytes(&mut self, from: NodeId, to: NodeId, threshold_bytes: u64) {
         let link_id = *self
@@ -171,27 +856,255 @@ impl Network {
             .get(&(from, to))
             .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
         self.links[link_id.0].ecn_threshold_bytes = Some(threshold_bytes);
+        self.links[link_id.0].red_ecn = None;
     }
 
     /// 设置所有链路的 ECN 标记阈值（bytes）。
     pub fn set_all_link_ecn_threshold_bytes(&mut self, threshold_bytes: u64) {
         for link in &mut self.links {
             link.ecn_threshold_bytes = Some(threshold_bytes);
+            link.red_ecn = None;
+        }
+    }
+
+    /// 让某条单向链路按 RED 概率标记 ECN：每个到达包先更新平均队长，再以
+    /// `params.probability(avg)` 的概率被标记 CE（非 ECT 包不受影响，也不会被丢弃）。
+    ///
+    /// 与 `set_link_red_queue` 不同，这里不替换队列；会取代该链路的阈值标记。
+    pub fn set_link_red_ecn(&mut self, from: NodeId, to: NodeId, params: RedParams) {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].red_ecn = Some(RedMarker::new(params));
+        self.links[link_id.0].ecn_threshold_bytes = None;
+    }
+
+    /// 所有链路按 RED 概率标记 ECN（阈值单位 bytes）。
+    ///
+    /// `w_q = 1`，即按到达时的瞬时队长计算概率：DCTCP 需要对瞬时拥塞作出反应，
+    /// 平均队长会把标记推迟很多个 RTT。每条链路用各自的随机种子（链路序号）。
+    pub fn set_all_link_red_ecn(&mut self, min_th: u64, max_th: u64, max_p: f64) {
+        for (i, link) in self.links.iter_mut().enumerate() {
+            let params = RedParams {
+                min_th,
+                max_th,
+                max_p,
+                w_q: 1.0,
+                seed: i as u64 + 1,
+            };
+            link.red_ecn = Some(RedMarker::new(params));
+            link.ecn_threshold_bytes = None;
+        }
+    }
+
+    /// 某个 Host 作为源发出的总字节数（所有流，含重传与控制包）。
+    pub fn host_tx_bytes(&self, node: NodeId) -> u64 {
+        self.stats.host_tx_bytes.get(&node).copied().unwrap_or(0)
+    }
+
+    /// 某个 Host 作为目的收到的总字节数（所有流）。
+    pub fn host_rx_bytes(&self, node: NodeId) -> u64 {
+        self.stats.host_rx_bytes.get(&node).copied().unwrap_or(0)
+    }
+
+    /// 单向链路 `from -> to` 的 LinkId（不存在时为 `None`）。
+    pub fn link_id(&self, from: NodeId, to: NodeId) -> Option<LinkId> {
+        self.edges.get(&(from, to)).copied()
+    }
+
+    /// 为某条单向链路开启每包传播时延抖动（以链路 `latency` 为中心）。
+    ///
+    /// 采样使用由 ECMP 种子（`set_ecmp_seed`）播种的随机数流，同一种子下完全可复现。
+    pub fn set_link_jitter(&mut self, from: NodeId, to: NodeId, jitter: LinkJitter) {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].jitter = Some(jitter);
+    }
+
+    /// 让某条单向链路在入队前以概率 `probability` 独立地随机丢包（与队列溢出无关），
+    /// 丢包计入 `Stats::dropped_random`。同一 `seed` 下丢哪些包完全可复现；概率为 0 时关闭。
+    pub fn set_link_loss(&mut self, from: NodeId, to: NodeId, probability: f64, seed: u64) {
+        assert!(
+            (0.0..=1.0).contains(&probability),
+            "loss probability must be in [0, 1], got {probability}"
+        );
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].loss = (probability > 0.0).then(|| LinkLoss {
+            probability,
+            rng: JitterRng::new(seed),
+        });
+    }
+
+    /// 某条单向链路的随机丢包概率（未开启时为 `None`）。
+    pub fn link_loss(&self, from: NodeId, to: NodeId) -> Option<f64> {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].loss.as_ref().map(|l| l.probability)
+    }
+
+    /// 某条单向链路的传播时延。
+    pub fn link_latency(&self, from: NodeId, to: NodeId) -> SimTime {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].latency
+    }
+
+    /// 某条单向链路队列占用的历史峰值（bytes）。
+    ///
+    /// 即该链路在本次仿真中为避免丢包所需的最小缓冲。
+    pub fn link_peak_queue_bytes(&self, from: NodeId, to: NodeId) -> u64 {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0].peak_queue_bytes
+    }
+
+    /// 所有 Switch 出方向端口队列占用历史峰值（`link_peak_queue_bytes`）中的最大值（bytes）。
+    ///
+    /// 只看交换机端口，不含 Host 网卡出口队列；没有交换机时为 0。
+    pub fn max_switch_occupancy(&self) -> u64 {
+        self.links
+            .iter()
+            .filter(|link| {
+                self.node_kinds
+                    .get(link.from.0)
+                    .is_some_and(|k| matches!(*k, VizNodeKind::Switch))
+            })
+            .map(|link| link.peak_queue_bytes)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 为某条单向链路开启队列占用直方图，`upper_bounds` 为升序的桶上界（bytes，含）。
+    ///
+    /// 超过最后一个上界的占用计入上界为 `u64::MAX` 的溢出桶。重复调用会清空已有统计。
+    pub fn set_link_queue_occupancy_buckets(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        upper_bounds: Vec<u64>,
+    ) {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        let link = &mut self.links[link_id.0];
+        link.occupancy = Some(QueueOccupancy::new(upper_bounds, link.queue.bytes()));
+    }
+
+    /// 为所有链路开启队列占用直方图（桶上界同 `set_link_queue_occupancy_buckets`）。
+    pub fn set_all_link_queue_occupancy_buckets(&mut self, upper_bounds: Vec<u64>) {
+        for link in &mut self.links {
+            link.occupancy = Some(QueueOccupancy::new(
+                upper_bounds.clone(),
+                link.queue.bytes(),
+            ));
         }
     }
 
+    /// 某条单向链路的队列占用分布：`(桶上界 bytes, 时间占比)`。
+    ///
+    /// 从 t=0 积分到该链路最近一次入队/出队，空闲时段计入占用为 0 的桶；未开启统计时返回空。
+    pub fn queue_occupancy_histogram(&self, from: NodeId, to: NodeId) -> Vec<(u64, f64)> {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        self.links[link_id.0]
+            .occupancy
+            .as_ref()
+            .map_or_else(Vec::new, |occ| occ.fractions())
+    }
+
+    /// 在仿真开始前向某条单向链路的队列预先塞入 `n_pkts` 个 `pkt_bytes` 大小的背景包，
+    /// 让后续的流从“已拥塞”的缓冲开始，而不必先模拟一段很长的预热。
+    ///
+    /// 背景包使用 `PRELOAD_FLOW_ID`，只走这一跳并在 `to` 处送达（计入送达统计）。
+    /// 链路在下一个包到达该队列时开始发送它们。队列放满后多余的包被忽略，返回实际入队的包数。
+    pub fn preload_link_queue(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        n_pkts: u64,
+        pkt_bytes: u32,
+    ) -> u64 {
+        let link_id = *self
+            .edges
+            .get(&(from, to))
+            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
+        let mut queued = 0;
+        for _ in 0..n_pkts {
+            let pkt = self.make_packet(PRELOAD_FLOW_ID, pkt_bytes, vec![from, to]);
+            let link = &mut self.links[link_id.0];
+            if link.queue.enqueue(pkt).is_err() {
+                break;
+            }
+            link.peak_queue_bytes = link.peak_queue_bytes.max(link.queue.bytes());
+            link.note_queue_change(SimTime::ZERO);
+            queued += 1;
+        }
+        self.links[link_id.0].preloaded = queued > 0;
+        queued
+    }
+
     /// 生成基于 ECMP 的单路径（按最短跳数 + flow_id 选择下一跳）。
+    ///
+    /// 在 `EcmpHashMode::FlowEntropy` 下同时计入该流的熵值。`dst` 不可达时 panic，
+    /// 需要容错时用 `try_route_ecmp_path`。
     pub fn route_ecmp_path(&mut self, src: NodeId, dst: NodeId, flow_id: u64) -> Vec<NodeId> {
+        self.try_route_ecmp_path(src, dst, flow_id)
+            .unwrap_or_else(|| panic!("no route from {:?} to {:?}", src, dst))
+    }
+
+    /// 同 `route_ecmp_path`，但从 `src` 的第 `nic` 块网卡（见 `add_host_nic`）出发：
+    /// 第一跳固定为该网卡所连的交换机，之后按 ECMP 选路。
+    ///
+    /// 网卡不存在或 `dst` 从该交换机不可达时 panic。
+    pub fn route_ecmp_path_via_nic(
+        &mut self,
+        src: NodeId,
+        nic: usize,
+        dst: NodeId,
+        flow_id: u64,
+    ) -> Vec<NodeId> {
+        let switch = *self
+            .host_nics(src)
+            .get(nic)
+            .unwrap_or_else(|| panic!("{:?} has no NIC {}", src, nic));
+        let rest = self
+            .try_route_ecmp_path(switch, dst, flow_id)
+            .unwrap_or_else(|| panic!("no route from {:?} NIC {} to {:?}", src, nic, dst));
+        let mut path = vec![src];
+        path.extend(rest);
+        path
+    }
+
+    /// 同 `route_ecmp_path`，但 `dst` 不可达时返回 `None`。
+    pub fn try_route_ecmp_path(
+        &mut self,
+        src: NodeId,
+        dst: NodeId,
+        flow_id: u64,
+    ) -> Option<Vec<NodeId>> {
         self.routing.ensure_built(&self.adj, &self.rev_adj);
+        let key = self.ecmp_hash.flow_key(src, dst, flow_id);
         let mut path = vec![src];
         let mut cur = src;
         let max_hops = self.nodes.len().saturating_add(1);
         while cur != dst {
-            let cands = self
-                .routing
-                .next_hops(cur, dst)
-                .unwrap_or_else(|| panic!("no route from {:?} to {:?}", cur, dst));
-            let nh = self.routing.pick_ecmp_with_key(cur, dst, flow_id, cands);
+            let cands = self.routing.next_hops(cur, dst)?;
+            let nh = self.routing.pick_ecmp_with_key(cur, dst, key, cands);
             path.push(nh);
             cur = nh;
             if path.len() > max_hops {
@@ -201,14 +1114,58 @@ impl Network {
                 );
             }
         }
-        path
+        Some(path)
+    }
+
+    /// 找不到路由时是否直接 panic（默认 false：丢弃该包并计入 `Stats::dropped_no_route`）。
+    /// 适合希望拓扑配置错误立即暴露的测试。
+    pub fn set_strict_routing(&mut self, strict: bool) {
+        self.strict_routing = strict;
+    }
+
+    /// 丢弃一个在 `from` 处无路可走（下一跳 `to` 不可达或没有链路）的包
+    fn drop_no_route(&mut self, from: NodeId, to: NodeId, pkt: &Packet, now: SimTime) {
+        if self.strict_routing {
+            panic!("no route from {:?} to {:?}", from, to);
+        }
+        warn!(
+            from = ?from,
+            to = ?to,
+            pkt_id = pkt.id,
+            flow_id = pkt.flow_id,
+            "没有可用路由，丢弃 packet"
+        );
+        self.stats.dropped_pkts += 1;
+        self.stats.dropped_bytes += pkt.size_bytes as u64;
+        self.stats.dropped_no_route += 1;
+        self.node_stats[from.0].dropped_pkts += 1;
+        self.viz_drop(now, pkt, from, to, 0, 0);
+    }
+
+    /// 放弃一条从 `src` 到 `dst` 无路可达的流：不再启动它，计入 `Stats::dropped_no_route`。
+    ///
+    /// 严格路由模式（`set_strict_routing`）下直接 panic。
+    pub fn fail_flow_no_route(&mut self, flow_id: u64, src: NodeId, dst: NodeId) {
+        if self.strict_routing {
+            panic!("no route from {:?} to {:?} (flow_id={})", src, dst, flow_id);
+        }
+        warn!(
+            src = ?src,
+            dst = ?dst,
+            flow_id,
+            "目的主机不可达，放弃该流"
+        );
+        self.stats.dropped_no_route += 1;
     }
 
     /// 创建数据包
     pub fn make_packet(&mut self, flow_id: u64, size_bytes: u32, route: Vec<NodeId>) -> Packet {
         let id = self.next_pkt_id;
         self.next_pkt_id = self.next_pkt_id.wrapping_add(1);
-        Packet::new_preset(id, flow_id, size_bytes, route)
+        let mut pkt = Packet::new_preset(id, flow_id, size_bytes, route);
+        pkt.deadline = self.flow_deadline(flow_id);
+        pkt.tenant_id = self.flow_tenant(flow_id);
+        pkt
     }
 
     /// 创建“纯动态路由”的数据包：每一跳根据 FIB/ECMP 决定下一跳
@@ -221,7 +1178,10 @@ impl Network {
     ) -> Packet {
         let id = self.next_pkt_id;
         self.next_pkt_id = self.next_pkt_id.wrapping_add(1);
-        Packet::new_dynamic(id, flow_id, size_bytes, src, dst)
+        let mut pkt = Packet::new_dynamic(id, flow_id, size_bytes, src, dst);
+        pkt.deadline = self.flow_deadline(flow_id);
+        pkt.tenant_id = self.flow_tenant(flow_id);
+        pkt
     }
 
     /// 创建“混合路由”的数据包：先沿 prefix 预设前缀走，再动态路由到 dst
@@ -234,7 +1194,10 @@ impl Network {
     ) -> Packet {
         let id = self.next_pkt_id;
         self.next_pkt_id = self.next_pkt_id.wrapping_add(1);
-        Packet::new_mixed(id, flow_id, size_bytes, prefix, dst)
+        let mut pkt = Packet::new_mixed(id, flow_id, size_bytes, prefix, dst);
+        pkt.deadline = self.flow_deadline(flow_id);
+        pkt.tenant_id = self.flow_tenant(flow_id);
+        pkt
     }
 
     /// 将数据包交付给节点处理
@@ -243,6 +1206,9 @@ impl Network {
         debug!("📬 将数据包交付给节点处理");
 
         self.viz_arrive_node(sim.now(), &pkt, to);
+        let counters = &mut self.node_stats[to.0];
+        counters.rx_pkts += 1;
+        counters.rx_bytes += pkt.size_bytes as u64;
 
         // 暂时把节点取出来，避免 &mut self 与 &mut node 的重叠借用。
         let mut node = self.nodes[to.0].take().expect("node exists");
@@ -273,25 +1239,42 @@ impl Network {
         } else {
             // 动态路由：根据 FIB/ECMP 选择下一跳
             self.routing.ensure_built(&self.adj, &self.rev_adj);
-            let cands = self
-                .routing
-                .next_hops(from, pkt.dst)
-                .unwrap_or_else(|| panic!("no route from {:?} to {:?}", from, pkt.dst));
-            let key = match self.ecmp_hash_mode {
-                EcmpHashMode::Flow => pkt.flow_id,
-                EcmpHashMode::Packet => pkt.flow_id ^ pkt.id,
+            let Some(cands) = self.routing.next_hops(from, pkt.dst) else {
+                self.drop_no_route(from, pkt.dst, &pkt, sim.now());
+                return;
+            };
+            let nh = if let EcmpHashMode::Flowlet { gap } = self.ecmp_hash.mode {
+                let now = sim.now();
+                let state = self.flowlets.entry((from, pkt.flow_id)).or_default();
+                let idle = now.0.saturating_sub(state.last_seen.0);
+                let nh = match state.next_hop {
+                    Some(nh) if idle <= gap.0 && cands.contains(&nh) => nh,
+                    prev => {
+                        // 新 flowlet：换一个哈希 key 重新选路
+                        if prev.is_some() {
+                            state.id = state.id.wrapping_add(1);
+                        }
+                        let key = ecmp_flow_key(pkt.src, pkt.dst, pkt.flow_id)
+                            ^ state.id.wrapping_mul(0xD6E8_FEB8_6659_FD93);
+                        self.routing.pick_ecmp_with_key(from, pkt.dst, key, cands)
+                    }
+                };
+                state.last_seen = now;
+                state.next_hop = Some(nh);
+                nh
+            } else {
+                let key = self.ecmp_hash.key(pkt.src, pkt.dst, pkt.flow_id, pkt.id);
+                self.routing.pick_ecmp_with_key(from, pkt.dst, key, cands)
             };
-            let nh = self.routing.pick_ecmp_with_key(from, pkt.dst, key, cands);
             trace!(to = ?nh, cands = ?cands, "动态路由（ECMP）选择下一跳");
             nh
         };
 
+        let Some(&link_id) = self.edges.get(&(from, to)) else {
+            self.drop_no_route(from, to, &pkt, sim.now());
+            return;
+        };
         self.viz_node_forward(sim.now(), &pkt, from, to);
-
-        let link_id = *self
-            .edges
-            .get(&(from, to))
-            .unwrap_or_else(|| panic!("no link from {:?} to {:?}", from, to));
         debug!(
             link_id = ?link_id,
             latency = ?self.links[link_id.0].latency,
@@ -301,20 +1284,69 @@ impl Network {
 
         // 入队：若队列满则直接丢弃（DropTail）
         let now = sim.now();
+
+        // 中间盒钩子在入队前执行，可修改包（ECN、优先级、遥测等）
+        let ctx = MiddleboxCtx {
+            node: from,
+            next_hop: to,
+            now,
+            queue_bytes: self.links[link_id.0].queue.bytes(),
+        };
+        self.middleboxes.process(&mut pkt, &ctx);
+        if pkt.created_at.is_none() {
+            pkt.created_at = Some(now);
+        }
+        if from == pkt.src {
+            *self.stats.host_tx_bytes.entry(from).or_insert(0) += pkt.size_bytes as u64;
+        }
         let (pkt_id, flow_id, pkt_bytes, pkt_kind) =
             (pkt.id, pkt.flow_id, pkt.size_bytes, Self::pkt_kind(&pkt));
 
+        // 故障注入：失效流的包在入队前直接丢弃
+        if self.flow_failed(flow_id, now) {
+            let queue = &self.links[link_id.0].queue;
+            let (q_bytes, q_cap_bytes) = (queue.bytes(), queue.capacity_bytes());
+            self.stats.dropped_pkts += 1;
+            self.stats.dropped_bytes += pkt.size_bytes as u64;
+            self.node_stats[from.0].dropped_pkts += 1;
+            self.viz_drop(now, &pkt, from, to, q_bytes, q_cap_bytes);
+            debug!(now = ?now, flow_id, "流已被注入故障，丢弃 packet");
+            return;
+        }
+
+        // 链路随机丢包：同样发生在入队前，与队列是否已满无关
+        if self.links[link_id.0]
+            .loss
+            .as_mut()
+            .is_some_and(|l| l.drops())
+        {
+            let queue = &self.links[link_id.0].queue;
+            let (q_bytes, q_cap_bytes) = (queue.bytes(), queue.capacity_bytes());
+            self.stats.dropped_pkts += 1;
+            self.stats.dropped_bytes += pkt.size_bytes as u64;
+            self.stats.dropped_random += 1;
+            self.node_stats[from.0].dropped_pkts += 1;
+            self.viz_drop(now, &pkt, from, to, q_bytes, q_cap_bytes);
+            debug!(now = ?now, flow_id, "链路随机丢包");
+            return;
+        }
+
         // 为了避免同时可变借用 `self.links[..]` 与 `self`（写 viz），先把结果与队列状态拷出来
         let (enqueue_res, q_bytes, q_cap_bytes, q_len) = {
             let link = &mut self.links[link_id.0];
-            if let Some(th) = link.ecn_threshold_bytes {
-                let q_next = link.queue.bytes().saturating_add(pkt.size_bytes as u64);
-                if q_next >= th {
-                    pkt.mark_ce_if_ect();
-                }
+            link.mark_ecn_threshold(&mut pkt);
+            let queued = link.queue.bytes();
+            if link
+                .red_ecn
+                .as_mut()
+                .is_some_and(|red| red.on_arrival(queued))
+            {
+                pkt.mark_ce_if_ect();
             }
-            let res = link.queue.enqueue(pkt);
+            let res = link.queue.enqueue_at(pkt, now);
             let q_bytes = link.queue.bytes();
+            link.peak_queue_bytes = link.peak_queue_bytes.max(q_bytes);
+            link.note_queue_change(now);
             let q_cap_bytes = link.queue.capacity_bytes();
             let q_len = link.queue.len();
             (res, q_bytes, q_cap_bytes, q_len)
@@ -343,6 +1375,7 @@ impl Network {
             Err(pkt) => {
                 self.stats.dropped_pkts += 1;
                 self.stats.dropped_bytes += pkt.size_bytes as u64;
+                self.node_stats[from.0].dropped_pkts += 1;
                 self.viz_drop(now, &pkt, from, to, q_bytes, q_cap_bytes);
                 debug!(
                     now = ?now,
@@ -350,7 +1383,10 @@ impl Network {
                     dropped_pkts = self.stats.dropped_pkts,
                     "队列已满，DropTail 丢弃 packet"
                 );
-                return;
+                // 预载的队列满着但还没开始发送：即使丢包也要启动链路
+                if !self.links[link_id.0].preloaded {
+                    return;
+                }
             }
         }
 
@@ -379,22 +1415,60 @@ impl Network {
     fn transmit_next_on_link(&mut self, link_id: LinkId, sim: &mut Simulator) {
         let now = sim.now();
 
+        // 整形器或网卡令牌不足：队头包留在队列里，等令牌够了再触发出队
+        {
+            let link = &mut self.links[link_id.0];
+            if link.queue.len() > 0 {
+                let shaper_wait = link.shaper.as_mut().and_then(|tb| tb.wait_time(now));
+                let nic_wait = self
+                    .host_nics
+                    .get_mut(&link.from)
+                    .and_then(|tb| tb.wait_time(now));
+                if let Some(wait) = shaper_wait.max(nic_wait) {
+                    let ready = SimTime(now.0.saturating_add(wait.0));
+                    link.busy_until = ready;
+                    sim.schedule(ready, LinkReady { link_id });
+                    return;
+                }
+            }
+        }
+
         // 先取出必要的链路参数，避免同时持有 link 的可变借用与 schedule
-        let (from, to, latency, bandwidth_bps, pkt_opt) = {
+        let (from, to, latency, bandwidth_bps, pkt_opt, dropped) = {
             let link = &mut self.links[link_id.0];
-            let pkt_opt = link.queue.dequeue();
+            let pkt_opt = link.queue.dequeue_at(now);
+            link.note_queue_change(now);
             (
                 link.from,
                 link.to,
                 link.latency,
                 link.bandwidth_bps,
                 pkt_opt,
+                link.queue.take_dropped(),
             )
         };
 
+        // 出队时被 AQM（如 CoDel）丢弃的包
+        if !dropped.is_empty() {
+            let queue = &self.links[link_id.0].queue;
+            let (q_bytes, q_cap_bytes) = (queue.bytes(), queue.capacity_bytes());
+            for pkt in &dropped {
+                self.stats.dropped_pkts += 1;
+                self.stats.dropped_bytes += pkt.size_bytes as u64;
+                self.node_stats[from.0].dropped_pkts += 1;
+                self.viz_drop(now, pkt, from, to, q_bytes, q_cap_bytes);
+            }
+            debug!(now = ?now, link_id = ?link_id, n = dropped.len(), "AQM 出队丢弃 packet");
+        }
+
         let Some(pkt) = pkt_opt else {
             return;
         };
+        self.links[link_id.0].preloaded = false;
+        let latency = match self.links[link_id.0].jitter {
+            Some(jitter) => jitter.sample(latency, &mut self.jitter_rng),
+            None => latency,
+        };
 
         // 重新借用 link 更新 busy_until（仅此处更新）
         let tx_time = {
@@ -406,8 +1480,27 @@ impl Network {
         {
             let link = &mut self.links[link_id.0];
             link.busy_until = depart;
+            link.tx_bytes += pkt.size_bytes as u64;
+            if let Some(tb) = link.shaper.as_mut() {
+                tb.consume(pkt.size_bytes);
+            }
         }
+        if let Some(tb) = self.host_nics.get_mut(&from) {
+            tb.consume(pkt.size_bytes);
+        }
+        let counters = &mut self.node_stats[from.0];
+        counters.tx_pkts += 1;
+        counters.tx_bytes += pkt.size_bytes as u64;
+        self.arm_link_util_sampling(sim);
         let arrive = SimTime(depart.0.saturating_add(latency.0));
+        // cut-through 交换机收到包头即可开始转发；链路本身仍按整包占用
+        let handoff = if self.cuts_through(to, &pkt) {
+            let header =
+                self.links[link_id.0].tx_time(CUT_THROUGH_HEADER_BYTES.min(pkt.size_bytes));
+            SimTime(now.0.saturating_add(header.0).saturating_add(latency.0))
+        } else {
+            arrive
+        };
 
         self.viz_tx_start(now, &pkt, from, to, depart, arrive);
 
@@ -424,7 +1517,7 @@ impl Network {
 
         // 到达事件（传播时延 + 序列化时延）
         sim.schedule(
-            arrive,
+            handoff,
             DeliverPacket {
                 to,
                 pkt: pkt.advance(),
@@ -434,3 +1527,12 @@ impl Network {
         sim.schedule(depart, LinkReady { link_id });
     }
 }
+
+/// `multiple` 倍带宽时延积（字节，四舍五入）。
+fn bdp_bytes(multiple: f64, bandwidth_bps: u64, base_rtt: SimTime) -> u64 {
+    assert!(
+        multiple > 0.0 && multiple.is_finite(),
+        "BDP multiple must be positive and finite, got {multiple}"
+    );
+    (multiple * bandwidth_bps as f64 * base_rtt.0 as f64 / 8e9).round() as u64
+}
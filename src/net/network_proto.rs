@@ -1,9 +1,9 @@
 //! Protocol dispatch hooks for the network.
 
-use crate::sim::Simulator;
+use crate::sim::{SimTime, Simulator};
 use tracing::{debug, info};
 
-use super::{Network, NodeId, Packet, Transport};
+use super::{Network, NodeId, Packet, SimContext, Transport};
 
 impl Network {
     /// 数据包送达目的地时的处理
@@ -18,6 +18,16 @@ impl Network {
 
         self.stats.delivered_pkts += 1;
         self.stats.delivered_bytes += pkt.size_bytes as u64;
+        if pkt.transport.is_control() {
+            self.stats.delivered_control_pkts += 1;
+            self.stats.delivered_control_bytes += pkt.size_bytes as u64;
+        }
+        *self.stats.host_rx_bytes.entry(at).or_insert(0) += pkt.size_bytes as u64;
+        if let Some(created) = pkt.created_at {
+            self.stats
+                .latencies
+                .push(SimTime(sim.now().0.saturating_sub(created.0)));
+        }
 
         debug!(
             size_bytes = pkt.size_bytes,
@@ -28,17 +38,21 @@ impl Network {
             "更新统计信息"
         );
 
+        if pkt.ecn.is_ce() && !pkt.transport.is_control() {
+            self.flow_stats_mut(pkt.flow_id).ecn_marks += 1;
+        }
+
         // 传输层处理（例如 TCP：目的端产生 ACK、源端处理 ACK 驱动继续发送）
         if let Transport::Tcp(seg) = pkt.transport {
             let conn_id = pkt.flow_id;
             let mut tcp = std::mem::take(&mut self.tcp);
-            tcp.on_tcp_segment(conn_id, at, seg, sim, self);
+            tcp.on_tcp_segment(conn_id, at, seg, &mut SimContext::new(sim, self));
             self.tcp = tcp;
         } else if let Transport::Dctcp(seg) = pkt.transport {
             let conn_id = pkt.flow_id;
             let ecn = pkt.ecn;
             let mut dctcp = std::mem::take(&mut self.dctcp);
-            dctcp.on_dctcp_segment(conn_id, at, seg, ecn, sim, self);
+            dctcp.on_dctcp_segment(conn_id, at, seg, ecn, &mut SimContext::new(sim, self));
             self.dctcp = dctcp;
         }
     }
@@ -1,5 +1,7 @@
 //! Visualization hooks for the network.
 
+use std::collections::BTreeMap;
+
 use crate::sim::SimTime;
 use crate::viz::{
     VizCwndReason, VizEvent, VizEventKind, VizLinkInfo, VizNodeInfo, VizNodeKind, VizPacketKind,
@@ -23,9 +25,26 @@ impl Network {
     }
 
     fn viz_push(&mut self, ev: VizEvent) {
-        if let Some(v) = &mut self.viz {
-            v.push(ev);
+        let Some(v) = &mut self.viz else {
+            return;
+        };
+        // 流的名称在它的第一条事件之前写出（Meta 里已有的除外）
+        if let Some(flow_id) = ev.flow_id
+            && let Some(label) = self.flow_labels.get(&flow_id)
+            && self.viz_labeled_flows.insert(flow_id)
+        {
+            v.push(VizEvent {
+                t_ns: ev.t_ns,
+                pkt_id: None,
+                flow_id: Some(flow_id),
+                pkt_bytes: None,
+                pkt_kind: None,
+                kind: VizEventKind::FlowLabel {
+                    label: label.clone(),
+                },
+            });
         }
+        v.push(ev);
     }
 
     pub fn emit_viz_meta(&mut self) {
@@ -53,13 +72,23 @@ impl Network {
                 q_cap_bytes: l.queue.capacity_bytes(),
             })
             .collect::<Vec<_>>();
+        let flow_labels = self
+            .flow_labels
+            .iter()
+            .map(|(id, label)| (*id, label.clone()))
+            .collect::<BTreeMap<_, _>>();
+        self.viz_labeled_flows.extend(flow_labels.keys().copied());
         self.viz_push(VizEvent {
             t_ns: 0,
             pkt_id: None,
             flow_id: None,
             pkt_bytes: None,
             pkt_kind: None,
-            kind: VizEventKind::Meta { nodes, links },
+            kind: VizEventKind::Meta {
+                nodes,
+                links,
+                flow_labels,
+            },
         });
     }
 
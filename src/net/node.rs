@@ -2,10 +2,11 @@
 //!
 //! 定义网络节点，包括节点 trait 和具体实现（主机、交换机）。
 
+use super::forward_packet::ForwardPacket;
 use super::id::NodeId;
 use super::network::Network;
 use super::packet::Packet;
-use crate::sim::Simulator;
+use crate::sim::{SimTime, Simulator};
 use tracing::{debug, info, trace};
 
 /// 节点接口
@@ -18,6 +19,31 @@ pub trait Node: Send {
 
     /// 处理到达的数据包
     fn on_packet(&mut self, pkt: Packet, sim: &mut Simulator, net: &mut Network);
+
+    /// 若节点是交换机则返回它（用于配置交换机专有参数）
+    fn as_switch_mut(&mut self) -> Option<&mut Switch> {
+        None
+    }
+
+    /// 若节点是交换机则返回它
+    fn as_switch(&self) -> Option<&Switch> {
+        None
+    }
+}
+
+/// cut-through 交换机开始转发前需要收到的字节数（以太网最小帧长）
+pub const CUT_THROUGH_HEADER_BYTES: u32 = 64;
+
+/// 交换机的转发方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForwardingMode {
+    /// 收完整个包才开始转发（默认）
+    #[default]
+    StoreAndForward,
+    /// 收到前 `CUT_THROUGH_HEADER_BYTES` 字节即开始转发，省去逐跳的整包序列化等待。
+    ///
+    /// 假设出端口不快于入端口（否则出端口会先于包尾发完）。
+    CutThrough,
 }
 
 /// 主机节点
@@ -70,6 +96,9 @@ impl Node for Host {
 pub struct Switch {
     id: NodeId,
     name: String,
+    /// 转发处理时延：包到达后隔这么久才进入出端口队列（默认 0）
+    processing_delay: SimTime,
+    forwarding_mode: ForwardingMode,
 }
 
 impl Switch {
@@ -78,8 +107,30 @@ impl Switch {
         Self {
             id,
             name: name.into(),
+            processing_delay: SimTime::ZERO,
+            forwarding_mode: ForwardingMode::StoreAndForward,
         }
     }
+
+    /// 转发处理时延
+    pub fn processing_delay(&self) -> SimTime {
+        self.processing_delay
+    }
+
+    /// 设置转发处理时延（cut-through 或 store-and-forward 的固定开销）
+    pub fn set_processing_delay(&mut self, delay: SimTime) {
+        self.processing_delay = delay;
+    }
+
+    /// 转发方式
+    pub fn forwarding_mode(&self) -> ForwardingMode {
+        self.forwarding_mode
+    }
+
+    /// 设置转发方式
+    pub fn set_forwarding_mode(&mut self, mode: ForwardingMode) {
+        self.forwarding_mode = mode;
+    }
 }
 
 impl Node for Switch {
@@ -100,12 +151,24 @@ impl Node for Switch {
             "数据包信息"
         );
 
-        if self.id != pkt.dst {
+        if self.id == pkt.dst {
+            info!("已到达目的地，标记为已送达");
+            net.on_delivered(self.id, pkt, sim);
+        } else if self.processing_delay == SimTime::ZERO {
             debug!("未到达目的地，继续转发");
             net.forward_from(self.id, pkt, sim);
         } else {
-            info!("已到达目的地，标记为已送达");
-            net.on_delivered(self.id, pkt, sim);
+            debug!(delay = ?self.processing_delay, "未到达目的地，处理时延后转发");
+            let at = SimTime(sim.now().0.saturating_add(self.processing_delay.0));
+            sim.schedule(at, ForwardPacket { from: self.id, pkt });
         }
     }
+
+    fn as_switch_mut(&mut self) -> Option<&mut Switch> {
+        Some(self)
+    }
+
+    fn as_switch(&self) -> Option<&Switch> {
+        Some(self)
+    }
 }
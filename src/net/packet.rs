@@ -4,6 +4,7 @@
 
 use super::id::NodeId;
 use super::transport::Transport;
+use crate::sim::SimTime;
 
 /// 网络数据包
 #[derive(Debug, Clone)]
@@ -20,6 +21,14 @@ pub struct Packet {
     pub transport: Transport,
     /// 已经走过的 hop 数（用于调试/统计）
     pub hops_taken: u32,
+    /// 所属流的截止时间（EDF 调度用）；`None` 表示没有截止时间
+    pub deadline: Option<SimTime>,
+    /// 流量类别优先级（越大越优先，默认 0），由 `PriorityQueue` 按类严格优先调度
+    pub priority: u8,
+    /// 所属租户（默认 0），由 `Network::set_flow_tenant` 按流打标，`TenantDrr` 出口按它划分带宽
+    pub tenant_id: u32,
+    /// 首次注入网络（在源端调用 `forward_from`）的时刻，用于端到端时延统计
+    pub created_at: Option<SimTime>,
 }
 
 /// ECN 码点（简化：只区分 Not-ECT / ECT / CE）
@@ -66,6 +75,10 @@ impl Packet {
             routing: Routing::Preset { path, idx: 0 },
             transport: Transport::None,
             hops_taken: 0,
+            deadline: None,
+            priority: 0,
+            tenant_id: 0,
+            created_at: None,
         }
     }
 
@@ -81,6 +94,10 @@ impl Packet {
             routing: Routing::Dynamic,
             transport: Transport::None,
             hops_taken: 0,
+            deadline: None,
+            priority: 0,
+            tenant_id: 0,
+            created_at: None,
         }
     }
 
@@ -103,6 +120,10 @@ impl Packet {
             routing: Routing::Mixed { prefix, idx: 0 },
             transport: Transport::None,
             hops_taken: 0,
+            deadline: None,
+            priority: 0,
+            tenant_id: 0,
+            created_at: None,
         }
     }
 
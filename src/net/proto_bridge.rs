@@ -2,34 +2,34 @@
 
 use crate::proto::dctcp::DctcpStack;
 use crate::proto::tcp::TcpStack;
-use crate::sim::World;
+use crate::sim::{Simulator, World};
 
-use super::{NetApi, NetWorld};
+use super::{NetWorld, SimContext};
 
-pub(crate) fn with_tcp_stack<F, R>(world: &mut dyn World, f: F) -> R
+pub(crate) fn with_tcp_stack<F, R>(sim: &mut Simulator, world: &mut dyn World, f: F) -> R
 where
-    F: FnOnce(&mut dyn NetApi, &mut TcpStack) -> R,
+    F: FnOnce(&mut SimContext<'_>, &mut TcpStack) -> R,
 {
     let w = world
         .as_any_mut()
         .downcast_mut::<NetWorld>()
         .expect("world must be NetWorld");
     let mut tcp = std::mem::take(&mut w.net.tcp);
-    let result = f(&mut w.net, &mut tcp);
+    let result = f(&mut SimContext::new(sim, &mut w.net), &mut tcp);
     w.net.tcp = tcp;
     result
 }
 
-pub(crate) fn with_dctcp_stack<F, R>(world: &mut dyn World, f: F) -> R
+pub(crate) fn with_dctcp_stack<F, R>(sim: &mut Simulator, world: &mut dyn World, f: F) -> R
 where
-    F: FnOnce(&mut dyn NetApi, &mut DctcpStack) -> R,
+    F: FnOnce(&mut SimContext<'_>, &mut DctcpStack) -> R,
 {
     let w = world
         .as_any_mut()
         .downcast_mut::<NetWorld>()
         .expect("world must be NetWorld");
     let mut dctcp = std::mem::take(&mut w.net.dctcp);
-    let result = f(&mut w.net, &mut dctcp);
+    let result = f(&mut SimContext::new(sim, &mut w.net), &mut dctcp);
     w.net.dctcp = dctcp;
     result
 }
@@ -28,6 +28,12 @@ impl RoutingTable {
         }
     }
 
+    /// 替换 ECMP 哈希盐并标记路由表需要重建。
+    pub fn set_hash_salt(&mut self, hash_salt: u64) {
+        self.hash_salt = hash_salt;
+        self.mark_dirty();
+    }
+
     pub fn mark_dirty(&mut self) {
         self.dirty = true;
     }
@@ -96,9 +102,16 @@ impl RoutingTable {
         self.next_hops.get(&(from, dst)).map(|v| v.as_slice())
     }
 
-    /// 基于 flow_id 的稳定 ECMP 选择。
-    pub fn pick_ecmp(&self, from: NodeId, dst: NodeId, flow_id: u64, cands: &[NodeId]) -> NodeId {
-        self.pick_ecmp_with_key(from, dst, flow_id, cands)
+    /// 基于 (src, dst, flow_id) 的稳定 ECMP 选择（见 `ecmp_flow_key`）。
+    pub fn pick_ecmp(
+        &self,
+        from: NodeId,
+        src: NodeId,
+        dst: NodeId,
+        flow_id: u64,
+        cands: &[NodeId],
+    ) -> NodeId {
+        self.pick_ecmp_with_key(from, dst, ecmp_flow_key(src, dst, flow_id), cands)
     }
 
     /// 基于任意 key 的稳定 ECMP 选择。
@@ -120,6 +133,14 @@ impl RoutingTable {
     }
 }
 
+/// 按流 ECMP 的哈希 key：逐项混合 (src, dst, flow_id)。
+///
+/// 与简单异或不同，不同的三元组不会相互抵消：不同 src/dst 对即使复用同一个
+/// flow_id，也会得到彼此独立的 key。
+pub fn ecmp_flow_key(src: NodeId, dst: NodeId, flow_id: u64) -> u64 {
+    mix64(mix64(mix64(src.0 as u64) ^ dst.0 as u64) ^ flow_id)
+}
+
 /// 一个简单、确定性的 64-bit mixing（替代 RandomState，避免每次运行 hash 不稳定）。
 fn mix64(mut x: u64) -> u64 {
     // splitmix64
@@ -2,11 +2,128 @@
 //!
 //! 定义网络仿真统计数据结构。
 
+use std::collections::BTreeMap;
+
+use crate::analysis::percentile;
+use crate::sim::SimTime;
+
+use super::NodeId;
+
 /// 网络统计信息
 #[derive(Debug, Default)]
 pub struct Stats {
     pub delivered_pkts: u64,
     pub delivered_bytes: u64,
+    /// 已送达的控制面包（ACK/握手）数量与字节数
+    pub delivered_control_pkts: u64,
+    pub delivered_control_bytes: u64,
     pub dropped_pkts: u64,
     pub dropped_bytes: u64,
+    /// 其中因找不到路由（或预设路由的下一跳没有链路）而丢弃的包数
+    pub dropped_no_route: u64,
+    /// 其中被链路随机丢包（`Network::set_link_loss`）丢弃的包数
+    pub dropped_random: u64,
+    /// 按源 Host 统计的发出字节（含重传与控制包）；BTreeMap 保证 Debug 输出有序
+    pub host_tx_bytes: BTreeMap<NodeId, u64>,
+    /// 按目的 Host 统计的收到字节
+    pub host_rx_bytes: BTreeMap<NodeId, u64>,
+    /// 每个已送达包的端到端时延（送达时刻 - 首次注入时刻），按送达顺序
+    pub latencies: Vec<SimTime>,
+}
+
+impl Stats {
+    /// 已送达的控制面字节（ACK/握手），用于衡量协议开销
+    pub fn control_bytes(&self) -> u64 {
+        self.delivered_control_bytes
+    }
+
+    /// 已送达的数据面字节（delivered_bytes 中除控制面以外的部分）
+    pub fn data_bytes(&self) -> u64 {
+        self.delivered_bytes
+            .saturating_sub(self.delivered_control_bytes)
+    }
+
+    /// 端到端时延的 `p` 分位数（`p` 取 [0, 1]，语义同 `analysis::percentile`）；尚无送达包时返回 `None`
+    pub fn latency_percentile(&self, p: f64) -> Option<SimTime> {
+        let ns: Vec<u64> = self.latencies.iter().map(|t| t.0).collect();
+        percentile(&ns, p).map(SimTime)
+    }
+
+    /// 端到端时延均值（向下取整到 ns）；尚无送达包时返回 `None`
+    pub fn latency_mean(&self) -> Option<SimTime> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let sum: u128 = self.latencies.iter().map(|t| t.0 as u128).sum();
+        Some(SimTime((sum / self.latencies.len() as u128) as u64))
+    }
+}
+
+/// 单个节点的收发与丢包计数（见 `Network::node_stats`）
+///
+/// `tx` 在包开始发送到出链路上时计数，`dropped_pkts` 统计在该节点出方向丢弃的包
+/// （队列满、AQM、无路由、故障注入），因此无排队残留时交换机满足 `rx = tx + dropped`。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    pub rx_pkts: u64,
+    pub tx_pkts: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub dropped_pkts: u64,
+}
+
+/// 单条流的统计（见 `Network::flow_stats`）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlowStats {
+    /// 按序交付给接收端的载荷字节（不含重复收到的数据）
+    pub bytes_delivered: u64,
+    /// 流开始发送的时间（握手时为发出 SYN 的时间）
+    pub start: Option<SimTime>,
+    /// 发送端收到覆盖全部数据的最后一个 ACK 的时间
+    pub completion: Option<SimTime>,
+    /// 重传的数据段数（RTO、快速重传、TLP）
+    pub retransmits: u64,
+    /// 到达接收端时带 CE 标记的数据包数
+    pub ecn_marks: u64,
+    /// 越过空洞到达接收端的数据段数（`seq > rcv_nxt`，含丢包造成的空洞）
+    pub reordered_segments: u64,
+    /// 最大乱序距离（字节）：乱序段到达时 `seq - rcv_nxt` 的最大值
+    pub max_reorder_bytes: u64,
+}
+
+impl FlowStats {
+    /// 接收端收到起始于 `seq` 的数据段时调用（`rcv_nxt` 为处理前的值）
+    pub(crate) fn note_data_arrival(&mut self, seq: u64, rcv_nxt: u64) {
+        if seq > rcv_nxt {
+            self.reordered_segments += 1;
+            self.max_reorder_bytes = self.max_reorder_bytes.max(seq - rcv_nxt);
+        }
+    }
+
+    /// 流完成时间（FCT）：`completion - start`
+    pub fn fct(&self) -> Option<SimTime> {
+        let (start, done) = (self.start?, self.completion?);
+        Some(SimTime(done.0.saturating_sub(start.0)))
+    }
+
+    /// 平均交付速率（bit/s）：`bytes_delivered` 除以从 `start` 到完成时间
+    /// （未完成时取 `now`）的时长；未开始或时长为 0 时返回 `None`
+    pub fn delivery_rate_bps(&self, now: SimTime) -> Option<f64> {
+        let start = self.start?;
+        let end = self.completion.unwrap_or(now);
+        let dur_ns = end.0.checked_sub(start.0).filter(|&d| d > 0)?;
+        Some(self.bytes_delivered as f64 * 8.0 * 1e9 / dur_ns as f64)
+    }
+}
+
+/// Jain 公平性指数 `(Σx)^2 / (n·Σx^2)`，取值 `1/n..=1`，1 表示完全公平。
+///
+/// 输入为空或全为 0 时返回 `None`。
+pub fn jain_fairness_index(xs: &[f64]) -> Option<f64> {
+    let sum: f64 = xs.iter().sum();
+    let sum_sq: f64 = xs.iter().map(|x| x * x).sum();
+    if xs.is_empty() || sum_sq == 0.0 {
+        return None;
+    }
+    Some(sum * sum / (xs.len() as f64 * sum_sq))
 }
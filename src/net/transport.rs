@@ -15,6 +15,21 @@ pub enum Transport {
     Dctcp(DctcpSegment),
 }
 
+impl Transport {
+    /// Whether this is control-plane traffic (ACK/handshake) rather than payload.
+    pub fn is_control(&self) -> bool {
+        matches!(
+            self,
+            Transport::Tcp(
+                TcpSegment::Ack { .. }
+                    | TcpSegment::Syn
+                    | TcpSegment::SynAck
+                    | TcpSegment::HandshakeAck
+            ) | Transport::Dctcp(DctcpSegment::Ack { .. })
+        )
+    }
+}
+
 /// TCP segment (minimal fields for simulation).
 #[derive(Debug, Clone)]
 pub enum TcpSegment {
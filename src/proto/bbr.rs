@@ -0,0 +1,341 @@
+//! BBR 拥塞控制（简化的 BBRv1，`CcAlgo::Bbr`）
+//!
+//! 维护瓶颈带宽（最近 10 个 RTT 轮次内投递速率的最大值）与最小 RTT 两个模型量，
+//! 以 `btlbw * min_rtt` 作为 BDP，按状态机（Startup → Drain → ProbeBW，
+//! 周期性进入 ProbeRTT）给出 pacing 速率与 cwnd：
+//! - pacing 速率 = `pacing_gain * btlbw`
+//! - cwnd = `cwnd_gain * BDP`（至少 4 个 MSS）
+//!
+//! 丢包/超时时的窗口处理同 Reno。省略了 app-limited 标记、长期带宽（policer）检测等细节。
+
+use std::collections::VecDeque;
+
+use crate::proto::flow::{AckEvent, CongestionControl, CwndChange, RateSample};
+use crate::proto::reno::{RenoCc, growth_change};
+use crate::sim::SimTime;
+
+/// Startup 阶段的增益 2/ln2：每轮投递速率翻倍
+const HIGH_GAIN: f64 = 2.885;
+/// ProbeBW 阶段的 pacing 增益循环（每阶段约一个 min_rtt）
+const PACING_GAIN_CYCLE: [f64; 8] = [1.25, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+/// ProbeBW 阶段的 cwnd 增益
+const CWND_GAIN: f64 = 2.0;
+/// 瓶颈带宽最大值滤波器的窗口（RTT 轮次）
+const BTLBW_FILTER_ROUNDS: u64 = 10;
+/// min_rtt 过期时间：超过这么久没有刷新就进入 ProbeRTT
+const MIN_RTT_WINDOW: SimTime = SimTime(10_000_000_000);
+/// ProbeRTT 保持最小窗口的时长
+const PROBE_RTT_DURATION: SimTime = SimTime(200_000_000);
+/// 连续多少轮带宽增长不足 25% 视为管道已满
+const FULL_BW_ROUNDS: u32 = 3;
+
+/// BBR 状态机的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BbrMode {
+    /// 指数探测带宽
+    Startup,
+    /// 排空 Startup 期间积累的队列
+    Drain,
+    /// 稳态：按增益循环探测/让出带宽
+    ProbeBw,
+    /// 把窗口降到 4 个 MSS，重新测量最小 RTT
+    ProbeRtt,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Bbr {
+    mss: u64,
+    mode: BbrMode,
+    pacing_gain: f64,
+    cwnd_gain: f64,
+    /// (轮次, 投递速率) 样本，用于窗口最大值滤波
+    bw_samples: VecDeque<(u64, f64)>,
+    btlbw: f64,
+    min_rtt: Option<SimTime>,
+    min_rtt_stamp: SimTime,
+    round_count: u64,
+    next_round_delivered: u64,
+    round_start: bool,
+    full_bw: f64,
+    full_bw_count: u32,
+    filled_pipe: bool,
+    cycle_index: usize,
+    cycle_stamp: SimTime,
+    probe_rtt_done: Option<SimTime>,
+    prior_cwnd: u64,
+}
+
+impl Bbr {
+    pub fn new(mss: u64) -> Self {
+        Self {
+            mss,
+            mode: BbrMode::Startup,
+            pacing_gain: HIGH_GAIN,
+            cwnd_gain: HIGH_GAIN,
+            bw_samples: VecDeque::new(),
+            btlbw: 0.0,
+            min_rtt: None,
+            min_rtt_stamp: SimTime::ZERO,
+            round_count: 0,
+            next_round_delivered: 0,
+            round_start: false,
+            full_bw: 0.0,
+            full_bw_count: 0,
+            filled_pipe: false,
+            cycle_index: 0,
+            cycle_stamp: SimTime::ZERO,
+            probe_rtt_done: None,
+            prior_cwnd: 0,
+        }
+    }
+
+    pub fn mode(&self) -> BbrMode {
+        self.mode
+    }
+
+    /// 瓶颈带宽估计（字节/秒）；还没有样本时为 None
+    pub fn btlbw(&self) -> Option<f64> {
+        (self.btlbw > 0.0).then_some(self.btlbw)
+    }
+
+    /// BDP 估计（字节）
+    pub fn bdp(&self) -> Option<f64> {
+        Some(self.btlbw()? * self.min_rtt?.0 as f64 / 1e9)
+    }
+
+    /// 当前 pacing 速率（字节/秒）
+    pub fn pacing_rate(&self) -> Option<f64> {
+        Some(self.pacing_gain * self.btlbw()?)
+    }
+
+    /// 处理一个 ACK：更新模型与状态机，返回新的 cwnd。
+    pub fn on_ack(
+        &mut self,
+        now: SimTime,
+        rs: Option<RateSample>,
+        rtt: Option<SimTime>,
+        acked: u64,
+        cwnd: u64,
+        inflight: u64,
+    ) -> u64 {
+        self.round_start = false;
+        if let Some(rs) = rs {
+            if rs.prior_delivered >= self.next_round_delivered {
+                self.next_round_delivered = rs.delivered;
+                self.round_count += 1;
+                self.round_start = true;
+            }
+            self.update_btlbw(rs.delivery_rate);
+        }
+        self.check_full_pipe();
+        self.update_min_rtt(now, rtt, cwnd);
+
+        match self.mode {
+            BbrMode::Startup if self.filled_pipe => {
+                self.mode = BbrMode::Drain;
+                self.pacing_gain = 1.0 / HIGH_GAIN;
+                self.cwnd_gain = HIGH_GAIN;
+            }
+            BbrMode::ProbeBw => self.advance_cycle(now, inflight),
+            BbrMode::ProbeRtt => self.check_probe_rtt_done(now, inflight),
+            _ => {}
+        }
+        if self.mode == BbrMode::Drain && self.bdp().is_some_and(|bdp| inflight as f64 <= bdp) {
+            self.enter_probe_bw(now);
+        }
+
+        self.next_cwnd(cwnd, acked)
+    }
+
+    fn update_btlbw(&mut self, rate: f64) {
+        self.bw_samples.push_back((self.round_count, rate));
+        while self
+            .bw_samples
+            .front()
+            .is_some_and(|&(round, _)| round + BTLBW_FILTER_ROUNDS <= self.round_count)
+        {
+            self.bw_samples.pop_front();
+        }
+        self.btlbw = self.bw_samples.iter().map(|&(_, r)| r).fold(0.0, f64::max);
+    }
+
+    fn check_full_pipe(&mut self) {
+        if self.filled_pipe || !self.round_start || self.btlbw == 0.0 {
+            return;
+        }
+        if self.btlbw >= self.full_bw * 1.25 {
+            self.full_bw = self.btlbw;
+            self.full_bw_count = 0;
+            return;
+        }
+        self.full_bw_count += 1;
+        if self.full_bw_count >= FULL_BW_ROUNDS {
+            self.filled_pipe = true;
+        }
+    }
+
+    fn update_min_rtt(&mut self, now: SimTime, rtt: Option<SimTime>, cwnd: u64) {
+        let expired = now.0 > self.min_rtt_stamp.0.saturating_add(MIN_RTT_WINDOW.0);
+        if let Some(rtt) = rtt
+            && (expired || self.min_rtt.is_none_or(|m| rtt <= m))
+        {
+            self.min_rtt = Some(rtt);
+            self.min_rtt_stamp = now;
+        }
+        if expired && self.min_rtt.is_some() && self.mode != BbrMode::ProbeRtt {
+            self.mode = BbrMode::ProbeRtt;
+            self.pacing_gain = 1.0;
+            self.cwnd_gain = 1.0;
+            self.prior_cwnd = cwnd;
+            self.probe_rtt_done = None;
+        }
+    }
+
+    fn enter_probe_bw(&mut self, now: SimTime) {
+        self.mode = BbrMode::ProbeBw;
+        self.cwnd_gain = CWND_GAIN;
+        // 从巡航阶段开始，避免刚排空就再次探测
+        self.cycle_index = 2;
+        self.cycle_stamp = now;
+        self.pacing_gain = PACING_GAIN_CYCLE[self.cycle_index];
+    }
+
+    fn advance_cycle(&mut self, now: SimTime, inflight: u64) {
+        let Some(min_rtt) = self.min_rtt else {
+            return;
+        };
+        let elapsed = now.0.saturating_sub(self.cycle_stamp.0) > min_rtt.0;
+        let drained = self.pacing_gain < 1.0 && self.bdp().is_some_and(|b| inflight as f64 <= b);
+        if elapsed || drained {
+            self.cycle_index = (self.cycle_index + 1) % PACING_GAIN_CYCLE.len();
+            self.cycle_stamp = now;
+            self.pacing_gain = PACING_GAIN_CYCLE[self.cycle_index];
+        }
+    }
+
+    fn check_probe_rtt_done(&mut self, now: SimTime, inflight: u64) {
+        match self.probe_rtt_done {
+            None if inflight <= 4 * self.mss => {
+                self.probe_rtt_done = Some(SimTime(now.0.saturating_add(PROBE_RTT_DURATION.0)));
+            }
+            Some(done) if now >= done => {
+                self.min_rtt_stamp = now;
+                if self.filled_pipe {
+                    self.enter_probe_bw(now);
+                } else {
+                    self.mode = BbrMode::Startup;
+                    self.pacing_gain = HIGH_GAIN;
+                    self.cwnd_gain = HIGH_GAIN;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn next_cwnd(&mut self, cwnd: u64, acked: u64) -> u64 {
+        let min_cwnd = 4 * self.mss;
+        if self.mode == BbrMode::ProbeRtt {
+            return cwnd.min(min_cwnd);
+        }
+        if self.prior_cwnd > 0 {
+            // 刚离开 ProbeRTT：恢复进入前的窗口
+            let restored = cwnd.max(std::mem::take(&mut self.prior_cwnd));
+            return restored.max(min_cwnd);
+        }
+        let Some(bdp) = self.bdp() else {
+            // 还没有带宽/RTT 估计：像慢启动一样按 ACK 增长
+            return cwnd.saturating_add(acked).max(min_cwnd);
+        };
+        let target = (self.cwnd_gain * bdp) as u64;
+        let next = if self.filled_pipe {
+            cwnd.saturating_add(acked).min(target)
+        } else if cwnd < target {
+            cwnd.saturating_add(acked)
+        } else {
+            cwnd
+        };
+        next.max(min_cwnd)
+    }
+}
+
+/// BBR 作为 `CongestionControl`：ACK 时按模型设定 cwnd 并给出 pacing 速率，
+/// 快速恢复、丢包与超时交给 Reno 处理。
+#[derive(Debug, Clone)]
+pub struct BbrCc {
+    reno: RenoCc,
+    bbr: Bbr,
+}
+
+impl BbrCc {
+    pub fn new(mss: u64, init_cwnd_bytes: u64, init_ssthresh_bytes: u64) -> Self {
+        Self {
+            reno: RenoCc::new(init_cwnd_bytes, init_ssthresh_bytes),
+            bbr: Bbr::new(mss),
+        }
+    }
+
+    pub fn mode(&self) -> BbrMode {
+        self.bbr.mode()
+    }
+
+    /// 瓶颈带宽估计（字节/秒）；还没有样本时为 None
+    pub fn btlbw(&self) -> Option<f64> {
+        self.bbr.btlbw()
+    }
+
+    /// BDP 估计（字节）
+    pub fn bdp(&self) -> Option<f64> {
+        self.bbr.bdp()
+    }
+}
+
+impl CongestionControl for BbrCc {
+    fn cwnd_bytes(&self) -> u64 {
+        self.reno.cwnd_bytes()
+    }
+
+    fn ssthresh_bytes(&self) -> u64 {
+        self.reno.ssthresh_bytes()
+    }
+
+    fn pacing_rate(&self) -> Option<f64> {
+        self.bbr.pacing_rate()
+    }
+
+    fn always_paces(&self) -> bool {
+        true
+    }
+
+    fn on_ack(&mut self, ack: &AckEvent) -> CwndChange {
+        if let Some(recovery) = ack.recovery {
+            return self.reno.on_recovery_ack(ack, recovery);
+        }
+        let change = growth_change(self.reno.in_slow_start());
+        let cwnd = self.bbr.on_ack(
+            ack.now,
+            ack.rate,
+            ack.rtt,
+            ack.newly_acked,
+            self.reno.cwnd_bytes(),
+            ack.inflight_bytes,
+        );
+        self.reno.set_cwnd_bytes(cwnd);
+        change
+    }
+
+    fn on_loss(&mut self, mss: u64) {
+        self.reno.on_loss(mss);
+    }
+
+    fn on_dup_ack(&mut self, mss: u64) {
+        self.reno.on_dup_ack(mss);
+    }
+
+    fn on_recovery_exit(&mut self, flight_bytes: u64, mss: u64) {
+        self.reno.on_recovery_exit(flight_bytes, mss);
+    }
+
+    fn on_rto(&mut self, mss: u64) {
+        self.reno.on_rto(mss);
+    }
+}
@@ -2,16 +2,20 @@
 //!
 //! 目标：支持一个 dumbbell DCTCP 实验所需的最小功能：
 //! - 数据段/ACK 段
-//! - ECN 标记反馈（ACK 回显）
-//! - DCTCP alpha 更新与窗口缩减
-//! - 超时重传（固定/指数退避的 RTO）
+//! - ECN 标记反馈（ACK 回显，见 `DctcpEcnEchoMode`）
+//! - DCTCP alpha 更新与窗口缩减（`DctcpCc`）
+//! - 超时重传（按测得 RTT 自适应、超时指数退避的 RTO）
+//!
+//! 发送状态由 `proto::flow::FlowCore` 维护，窗口由 `CongestionControl` 决定；
+//! `DctcpConn::set_cc` 可换上其他拥塞控制算法。
 //!
 //! 注意：这是仿真用途的“极简 DCTCP”，不实现握手/窗口通告/选择确认等。
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 use std::fmt;
 
-use crate::net::{DctcpSegment, Ecn, NetApi, NodeId, Transport, with_dctcp_stack};
+use crate::net::{DctcpSegment, Ecn, NetApi, NodeId, SimContext, Transport, with_dctcp_stack};
+use crate::proto::flow::{AckEvent, CongestionControl, CwndChange, FlowCore};
 use crate::sim::{Event, SimTime, Simulator, World};
 use crate::viz::VizCwndReason;
 
@@ -19,6 +23,32 @@ use crate::viz::VizCwndReason;
 pub type DctcpConnId = u64;
 pub type DctcpDoneCallback = Box<dyn Fn(DctcpConnId, SimTime, &mut Simulator) + Send>;
 
+/// 接收端回显 CE 标记的方式。
+///
+/// 两种方式下发送端都按“带 ECE 的 ACK 所确认的字节数 / 窗口内确认的字节数”估计标记比例。
+/// 纯 ACK 同样以 ECT(0) 发送（与 Linux DCTCP 一致，不同于 RFC 3168 §6.1.4）：反向拥塞时
+/// ACK 被标记而不是被 AQM 丢弃；发送端不对 ACK 上的 CE 作出反应。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DctcpEcnEchoMode {
+    /// 每个数据段立即 ACK，ECE 即为该数据段的 CE 位
+    #[default]
+    PerAck,
+    /// RFC 8257 §3.2 的接收端状态机：维护 CE 状态，按延迟 ACK 每两个段确认一次，
+    /// ACK 上的 ECE 回显当前 CE 状态；CE 状态翻转时先立即确认此前收到的数据
+    /// （带旧状态），使每个字节的标记都被如实回显
+    Classic,
+}
+
+impl DctcpEcnEchoMode {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "per_ack" | "per-ack" => Ok(Self::PerAck),
+            "classic" => Ok(Self::Classic),
+            _ => Err(format!("unknown ECN echo mode: {raw}")),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct DctcpConfig {
     /// MSS（数据段载荷大小，字节）
@@ -29,12 +59,27 @@ pub struct DctcpConfig {
     pub init_cwnd_bytes: u64,
     /// 初始 ssthresh（字节）
     pub init_ssthresh_bytes: u64,
-    /// 初始 RTO
+    /// 初始 RTO（尚无 RTT 样本时使用）
     pub init_rto: SimTime,
+    /// 最小 RTO（`srtt + 4*rttvar` 的下限）
+    pub min_rto: SimTime,
     /// 最大 RTO（用于退避上限）
     pub max_rto: SimTime,
     /// DCTCP alpha 更新的增益 g（典型为 1/16）
     pub g: f64,
+    /// 完成通知延迟：最后一个 ACK 到达后，再过多久调用 done callback（默认 0）
+    pub done_notify_delay: SimTime,
+    /// 数据段的流量类别优先级，见 `Packet::priority`
+    pub data_priority: u8,
+    /// ACK 的流量类别优先级；调高可让 ACK 越过同一队列里的数据
+    pub ack_priority: u8,
+    /// 接收端的 CE 回显方式（默认每段一个 ACK）
+    pub ecn_echo_mode: DctcpEcnEchoMode,
+    /// `Classic` 回显下延迟 ACK 的最长等待时间
+    pub delayed_ack_timeout: SimTime,
+    /// 发送端 pacing：按 `cwnd/srtt` 的速率逐段发送（同 `TcpConfig::pacing`），
+    /// 还没有 RTT 样本时（初始窗口）仍然突发发送
+    pub pacing: bool,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -52,15 +97,124 @@ impl Default for DctcpConfig {
             init_cwnd_bytes: (mss as u64).saturating_mul(10),
             init_ssthresh_bytes: (mss as u64).saturating_mul(1_000),
             init_rto: SimTime::from_micros(200),
+            min_rto: SimTime::from_micros(200),
             max_rto: SimTime::from_millis(200),
             g: 1.0 / 16.0,
+            done_notify_delay: SimTime::ZERO,
+            data_priority: 0,
+            ack_priority: 0,
+            ecn_echo_mode: DctcpEcnEchoMode::PerAck,
+            delayed_ack_timeout: SimTime::from_micros(40),
+            pacing: false,
         }
     }
 }
 
+/// DCTCP 拥塞控制：按窗口统计被标记字节的比例，`alpha = (1-g)*alpha + g*frac`；
+/// 窗口内有标记时 cwnd 乘以 `1 - alpha/2`。其余为 Reno 风格的慢启动/拥塞避免。
 #[derive(Debug, Clone)]
-struct SentSeg {
-    len: u32,
+pub struct DctcpCc {
+    g: f64,
+    cwnd_bytes: u64,
+    ssthresh_bytes: u64,
+    alpha: f64,
+    window_end: u64,
+    acked_in_window: u64,
+    marked_in_window: u64,
+}
+
+impl DctcpCc {
+    pub fn new(cfg: &DctcpConfig) -> Self {
+        let cwnd = cfg.init_cwnd_bytes.max(cfg.mss as u64);
+        let ssthresh = cfg.init_ssthresh_bytes.max(cfg.mss as u64);
+        Self {
+            g: cfg.g,
+            cwnd_bytes: cwnd,
+            ssthresh_bytes: ssthresh,
+            alpha: 0.0,
+            window_end: cwnd,
+            acked_in_window: 0,
+            marked_in_window: 0,
+        }
+    }
+}
+
+impl CongestionControl for DctcpCc {
+    fn cwnd_bytes(&self) -> u64 {
+        self.cwnd_bytes
+    }
+
+    fn ssthresh_bytes(&self) -> u64 {
+        self.ssthresh_bytes
+    }
+
+    fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    fn on_mark(&mut self, marked_bytes: u64) {
+        self.marked_in_window = self.marked_in_window.saturating_add(marked_bytes);
+    }
+
+    fn on_ack(&mut self, ack: &AckEvent) -> CwndChange {
+        let was_slow_start = self.cwnd_bytes < self.ssthresh_bytes;
+
+        // 按窗口统计 ECN 标记比例
+        self.acked_in_window = self.acked_in_window.saturating_add(ack.newly_acked);
+        let mut ecn_frac = None;
+        let mut ecn_window_marked = false;
+        if ack.last_acked >= self.window_end {
+            let frac = if self.acked_in_window == 0 {
+                0.0
+            } else {
+                self.marked_in_window as f64 / self.acked_in_window as f64
+            };
+            ecn_frac = Some(frac);
+            self.alpha = (1.0 - self.g) * self.alpha + self.g * frac;
+            if self.marked_in_window > 0 {
+                ecn_window_marked = true;
+                let factor = 1.0 - self.alpha / 2.0;
+                let new_cwnd = (self.cwnd_bytes as f64 * factor)
+                    .max(ack.mss as f64)
+                    .floor() as u64;
+                self.cwnd_bytes = new_cwnd.max(ack.mss);
+            }
+            self.acked_in_window = 0;
+            self.marked_in_window = 0;
+            self.window_end = ack.last_acked.saturating_add(self.cwnd_bytes);
+        }
+
+        // 慢启动 / 拥塞避免（极简）
+        if self.cwnd_bytes < self.ssthresh_bytes {
+            self.cwnd_bytes = self.cwnd_bytes.saturating_add(ack.newly_acked);
+        } else {
+            let inc = (ack.mss.saturating_mul(ack.mss) / self.cwnd_bytes).max(1);
+            self.cwnd_bytes = self.cwnd_bytes.saturating_add(inc);
+        }
+
+        let reason = if ecn_window_marked {
+            VizCwndReason::DctcpEcnWindow
+        } else if was_slow_start {
+            VizCwndReason::AckSlowStart
+        } else {
+            VizCwndReason::AckCongestionAvoidance
+        };
+        CwndChange { reason, ecn_frac }
+    }
+
+    fn on_loss(&mut self, mss: u64) {
+        self.ssthresh_bytes = (self.cwnd_bytes / 2).max(2 * mss);
+        self.cwnd_bytes = self.ssthresh_bytes.saturating_add(3 * mss);
+    }
+
+    fn on_dup_ack(&mut self, mss: u64) {
+        self.cwnd_bytes = self.cwnd_bytes.saturating_add(mss);
+    }
+
+    fn on_rto(&mut self, mss: u64) {
+        self.ssthresh_bytes = (self.cwnd_bytes / 2).max(2 * mss);
+        self.cwnd_bytes = mss;
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -75,23 +229,22 @@ pub struct DctcpConn {
     pub cfg: DctcpConfig,
 
     // sender
-    next_seq: u64,
-    last_acked: u64,
-    cwnd_bytes: u64,
-    ssthresh_bytes: u64,
-    dup_acks: u32,
-    rto: SimTime,
-    inflight: BTreeMap<u64, SentSeg>, // seq -> segment
-
-    // DCTCP alpha
-    alpha: f64,
-    window_end: u64,
-    acked_in_window: u64,
-    marked_in_window: u64,
+    core: FlowCore,
+    cc: Box<dyn CongestionControl>,
     cwnd_log: Option<Vec<CwndSample>>,
+    /// pacing：下一个数据段最早的发送时刻
+    pace_next: SimTime,
+    /// pacing：已排程的 `DctcpPacedSend` 的发送时刻（没有时为 None）
+    pace_deadline: Option<SimTime>,
 
     // receiver
     rcv_nxt: u64,
+    /// `Classic` 回显：最近一个按序数据段是否带 CE（RFC 8257 的 DCTCP.CE）
+    ce_state: bool,
+    /// `Classic` 回显：尚未确认的按序字节数与延迟 ACK 定时器
+    ack_pending_bytes: u64,
+    delack_deadline: Option<SimTime>,
+    delack_token: u64,
 
     // stats
     start_at: Option<SimTime>,
@@ -109,10 +262,8 @@ impl DctcpConn {
     ) -> Self {
         let mut rev_route = fwd_route.clone();
         rev_route.reverse();
-        let init_rto = cfg.init_rto;
-        let cwnd = cfg.init_cwnd_bytes.max(cfg.mss as u64);
-        let ssthresh = cfg.init_ssthresh_bytes.max(cfg.mss as u64);
-        let window_end = cwnd;
+        let core = FlowCore::new(total_bytes, cfg.init_rto);
+        let cc = Box::new(DctcpCc::new(&cfg));
         Self {
             id,
             src,
@@ -122,19 +273,16 @@ impl DctcpConn {
             routing_mode: DctcpRoutingMode::Preset,
             total_bytes,
             cfg,
-            next_seq: 0,
-            last_acked: 0,
-            cwnd_bytes: cwnd,
-            ssthresh_bytes: ssthresh,
-            dup_acks: 0,
-            rto: init_rto,
-            inflight: BTreeMap::new(),
-            alpha: 0.0,
-            window_end,
-            acked_in_window: 0,
-            marked_in_window: 0,
+            core,
+            cc,
             cwnd_log: None,
+            pace_next: SimTime::ZERO,
+            pace_deadline: None,
             rcv_nxt: 0,
+            ce_state: false,
+            ack_pending_bytes: 0,
+            delack_deadline: None,
+            delack_token: 0,
             start_at: None,
             done_at: None,
         }
@@ -149,10 +297,8 @@ impl DctcpConn {
     ) -> Self {
         let fwd_route = vec![src, dst];
         let rev_route = vec![dst, src];
-        let init_rto = cfg.init_rto;
-        let cwnd = cfg.init_cwnd_bytes.max(cfg.mss as u64);
-        let ssthresh = cfg.init_ssthresh_bytes.max(cfg.mss as u64);
-        let window_end = cwnd;
+        let core = FlowCore::new(total_bytes, cfg.init_rto);
+        let cc = Box::new(DctcpCc::new(&cfg));
         Self {
             id,
             src,
@@ -162,26 +308,23 @@ impl DctcpConn {
             routing_mode: DctcpRoutingMode::Dynamic,
             total_bytes,
             cfg,
-            next_seq: 0,
-            last_acked: 0,
-            cwnd_bytes: cwnd,
-            ssthresh_bytes: ssthresh,
-            dup_acks: 0,
-            rto: init_rto,
-            inflight: BTreeMap::new(),
-            alpha: 0.0,
-            window_end,
-            acked_in_window: 0,
-            marked_in_window: 0,
+            core,
+            cc,
             cwnd_log: None,
+            pace_next: SimTime::ZERO,
+            pace_deadline: None,
             rcv_nxt: 0,
+            ce_state: false,
+            ack_pending_bytes: 0,
+            delack_deadline: None,
+            delack_token: 0,
             start_at: None,
             done_at: None,
         }
     }
 
     pub fn bytes_acked(&self) -> u64 {
-        self.last_acked.min(self.total_bytes)
+        self.core.last_acked().min(self.total_bytes)
     }
 
     pub fn is_done(&self) -> bool {
@@ -196,6 +339,52 @@ impl DctcpConn {
         self.done_at
     }
 
+    /// 当前的 DCTCP alpha（被标记比例的滑动估计）。
+    pub fn alpha(&self) -> f64 {
+        self.cc.alpha()
+    }
+
+    pub fn cwnd_bytes(&self) -> u64 {
+        self.cc.cwnd_bytes()
+    }
+
+    /// 换用其他拥塞控制算法（默认 `DctcpCc`）；应在连接启动前调用。
+    pub fn set_cc(&mut self, cc: Box<dyn CongestionControl>) {
+        self.cc = cc;
+    }
+
+    /// 观测到的最小 RTT（无样本时为 None；重传过的段不产生样本）。
+    pub fn min_rtt(&self) -> Option<SimTime> {
+        self.core.rtt().min_rtt()
+    }
+
+    /// 平滑 RTT（尚无样本时为 `None`）。
+    pub fn srtt(&self) -> Option<SimTime> {
+        self.core.rtt().srtt()
+    }
+
+    pub fn rttvar(&self) -> SimTime {
+        self.core.rtt().rttvar()
+    }
+
+    /// 当前 RTO。
+    pub fn rto(&self) -> SimTime {
+        self.core.rtt().rto()
+    }
+
+    /// 为最早未确认段 `seq` (重新)设置 RTO 定时器，此前设置的定时器随之失效。
+    fn arm_rto(&mut self, seq: u64, cx: &mut SimContext<'_>) {
+        let token = self.core.next_rto_token();
+        cx.schedule(
+            SimTime(cx.now().0.saturating_add(self.rto().0)),
+            DctcpRto {
+                conn_id: self.id,
+                seq,
+                token,
+            },
+        );
+    }
+
     pub fn enable_cwnd_log(&mut self) {
         self.cwnd_log = Some(Vec::new());
     }
@@ -204,34 +393,54 @@ impl DctcpConn {
         self.cwnd_log.as_deref()
     }
 
-    fn earliest_unacked_seq(&self) -> Option<u64> {
-        self.inflight.keys().next().copied()
+    fn inflight_bytes(&self) -> u64 {
+        self.core.inflight_bytes()
     }
 
-    fn inflight_bytes(&self) -> u64 {
-        self.inflight.values().map(|s| s.len as u64).sum()
+    /// 是否逐段 pacing：显式开启 `pacing`，或拥塞控制总是 pacing。
+    fn paces(&self) -> bool {
+        self.cfg.pacing || self.cc.always_paces()
+    }
+
+    /// pacing 时发出 `len` 字节后到下一段的间隔（同 `TcpConn` 的计算）：拥塞控制给出速率时为
+    /// `len / pacing_rate`，否则为 `len * srtt / cwnd`；不 pacing 或没有 RTT 样本时返回 None。
+    fn pacing_gap(&self, len: u32) -> Option<SimTime> {
+        if !self.paces() {
+            return None;
+        }
+        if let Some(rate) = self.cc.pacing_rate() {
+            return Some(SimTime::from_secs_f64(len as f64 / rate));
+        }
+        let srtt = self.srtt()?;
+        let cwnd = self.cc.cwnd_bytes().max(1) as u128;
+        let gap = (len as u128).saturating_mul(srtt.0 as u128) / cwnd;
+        Some(SimTime(gap.min(u64::MAX as u128) as u64))
     }
 
     fn make_data_packet(&self, net: &mut dyn NetApi) -> crate::net::Packet {
-        match self.routing_mode {
+        let mut pkt = match self.routing_mode {
             DctcpRoutingMode::Preset => {
                 net.make_packet(self.id, self.cfg.mss, self.fwd_route.clone())
             }
             DctcpRoutingMode::Dynamic => {
                 net.make_packet_dynamic(self.id, self.cfg.mss, self.src, self.dst)
             }
-        }
+        };
+        pkt.priority = self.cfg.data_priority;
+        pkt
     }
 
     fn make_ack_packet(&self, net: &mut dyn NetApi) -> crate::net::Packet {
-        match self.routing_mode {
+        let mut pkt = match self.routing_mode {
             DctcpRoutingMode::Preset => {
                 net.make_packet(self.id, self.cfg.ack_bytes, self.rev_route.clone())
             }
             DctcpRoutingMode::Dynamic => {
                 net.make_packet_dynamic(self.id, self.cfg.ack_bytes, self.dst, self.src)
             }
-        }
+        };
+        pkt.priority = self.cfg.ack_priority;
+        pkt
     }
 
     pub(crate) fn record_cwnd(&mut self, now: SimTime) {
@@ -240,10 +449,10 @@ impl DctcpConn {
         };
         log.push(CwndSample {
             t_ns: now.0,
-            cwnd_bytes: self.cwnd_bytes,
-            ssthresh_bytes: self.ssthresh_bytes,
-            alpha: self.alpha,
-            acked_bytes: self.last_acked,
+            cwnd_bytes: self.cc.cwnd_bytes(),
+            ssthresh_bytes: self.cc.ssthresh_bytes(),
+            alpha: self.cc.alpha(),
+            acked_bytes: self.core.last_acked(),
         });
     }
 }
@@ -283,26 +492,44 @@ impl DctcpStack {
     }
 
     /// Insert a connection, record initial cwnd sample, and start sending.
+    ///
+    /// Subject to the source host's concurrent-flow limit.
     pub fn start_conn(&mut self, conn: DctcpConn, sim: &mut Simulator, net: &mut dyn NetApi) {
+        let Some(conn) = net.admit_dctcp_conn(conn) else {
+            return;
+        };
+        self.start_admitted_conn(conn, &mut SimContext::new(sim, net));
+    }
+
+    /// 动态路由的连接若目的主机不可达，则放弃该流（见 `Network::fail_flow_no_route`）
+    /// 并释放其占用的并发流 slot。
+    pub(crate) fn start_admitted_conn(&mut self, conn: DctcpConn, cx: &mut SimContext<'_>) {
         let id = conn.id;
+        if conn.routing_mode == DctcpRoutingMode::Dynamic
+            && cx.net.try_route_ecmp_path(conn.src, conn.dst, id).is_none()
+        {
+            cx.net.fail_flow_no_route(id, conn.src, conn.dst);
+            cx.net.release_flow_slot(id, cx.sim);
+            return;
+        }
         self.insert(conn);
         if let Some(c) = self.get_mut(id) {
-            let now = sim.now();
+            let now = cx.now();
             c.record_cwnd(now);
-            net.viz_dctcp_cwnd(
+            cx.net.viz_dctcp_cwnd(
                 now.0,
                 c.id,
-                c.cwnd_bytes,
-                c.ssthresh_bytes,
+                c.cc.cwnd_bytes(),
+                c.cc.ssthresh_bytes(),
                 c.inflight_bytes(),
-                c.alpha,
+                c.cc.alpha(),
                 VizCwndReason::Init,
                 None,
                 None,
                 None,
             );
         }
-        self.send_data_if_possible(id, sim, net);
+        self.send_data_if_possible(id, cx);
     }
 
     pub fn get(&self, id: DctcpConnId) -> Option<&DctcpConn> {
@@ -313,12 +540,7 @@ impl DctcpStack {
         self.conns.get_mut(&id)
     }
 
-    pub(crate) fn send_data_if_possible(
-        &mut self,
-        id: DctcpConnId,
-        sim: &mut Simulator,
-        net: &mut dyn NetApi,
-    ) {
+    pub(crate) fn send_data_if_possible(&mut self, id: DctcpConnId, cx: &mut SimContext<'_>) {
         let Some(conn) = self.conns.get_mut(&id) else {
             return;
         };
@@ -327,62 +549,58 @@ impl DctcpStack {
         }
 
         if conn.start_at.is_none() {
-            conn.start_at = Some(sim.now());
+            conn.start_at = Some(cx.now());
+            cx.net.flow_stats_mut(conn.id).start = conn.start_at;
         }
 
-        let inflight_bytes: u64 = conn.inflight.values().map(|s| s.len as u64).sum();
-        let mut avail = conn.cwnd_bytes.saturating_sub(inflight_bytes);
-
-        while avail > 0 && conn.next_seq < conn.total_bytes {
-            let remain = conn.total_bytes - conn.next_seq;
-            let len = (conn.cfg.mss as u64).min(remain).min(avail) as u32;
-            if len == 0 {
+        let mut avail = conn.cc.cwnd_bytes().saturating_sub(conn.inflight_bytes());
+        while avail > 0 && conn.core.next_seq() < conn.core.total_bytes() {
+            if conn.paces() && cx.now() < conn.pace_next {
+                // 还没到 pacing 时刻：排一个 DctcpPacedSend，到时再继续发
+                if conn.pace_deadline.is_none() {
+                    conn.pace_deadline = Some(conn.pace_next);
+                    cx.schedule(conn.pace_next, DctcpPacedSend { conn_id: conn.id });
+                }
                 break;
             }
-            let seq = conn.next_seq;
-            conn.next_seq = conn.next_seq.saturating_add(len as u64);
-            avail = avail.saturating_sub(len as u64);
-
-            let mut pkt = conn.make_data_packet(net);
+            let Some((seq, len, retrans)) = conn.core.send_next(conn.cfg.mss, &mut avail, cx.now())
+            else {
+                break;
+            };
+            if let Some(gap) = conn.pacing_gap(len) {
+                conn.pace_next = SimTime(cx.now().0.saturating_add(gap.0));
+            }
+            let mut pkt = conn.make_data_packet(cx.net);
             pkt.size_bytes = conn.cfg.mss;
             pkt.transport = Transport::Dctcp(DctcpSegment::Data { seq, len });
             pkt.ecn = Ecn::Ect0;
 
-            net.viz_tcp_send_data(sim.now().0, conn.id, seq, len, false);
-
-            conn.inflight.insert(seq, SentSeg { len });
+            cx.net
+                .viz_tcp_send_data(cx.now().0, conn.id, seq, len, retrans);
+            if retrans {
+                cx.net.flow_stats_mut(conn.id).retransmits += 1;
+            }
 
-            if conn.earliest_unacked_seq() == Some(seq) {
-                sim.schedule(
-                    SimTime(sim.now().0.saturating_add(conn.rto.0)),
-                    DctcpRto {
-                        conn_id: conn.id,
-                        seq,
-                    },
-                );
+            if conn.core.earliest_unacked_seq() == Some(seq) {
+                conn.arm_rto(seq, cx);
             }
 
-            net.forward_from(conn.src, pkt, sim);
+            cx.forward_from(conn.src, pkt);
         }
     }
 
-    fn send_ack(
-        &mut self,
-        id: DctcpConnId,
-        ack: u64,
-        ecn_echo: bool,
-        sim: &mut Simulator,
-        net: &mut dyn NetApi,
-    ) {
+    fn send_ack(&mut self, id: DctcpConnId, ack: u64, ecn_echo: bool, cx: &mut SimContext<'_>) {
         let Some(conn) = self.conns.get(&id) else {
             return;
         };
-        let mut pkt = conn.make_ack_packet(net);
+        let mut pkt = conn.make_ack_packet(cx.net);
         pkt.size_bytes = conn.cfg.ack_bytes;
         pkt.transport = Transport::Dctcp(DctcpSegment::Ack { ack, ecn_echo });
+        // ACK 同样标为 ECT，反向路径上的 AQM 会标记而不是丢弃它
+        pkt.ecn = Ecn::Ect0;
 
-        net.viz_tcp_send_ack(sim.now().0, conn.id, ack, ecn_echo);
-        net.forward_from(conn.dst, pkt, sim);
+        cx.net.viz_tcp_send_ack(cx.now().0, conn.id, ack, ecn_echo);
+        cx.forward_from(conn.dst, pkt);
     }
 
     pub fn on_dctcp_segment(
@@ -391,8 +609,7 @@ impl DctcpStack {
         at: NodeId,
         seg: DctcpSegment,
         ecn: Ecn,
-        sim: &mut Simulator,
-        net: &mut dyn NetApi,
+        cx: &mut SimContext<'_>,
     ) {
         match seg {
             DctcpSegment::Data { seq, len } => {
@@ -403,13 +620,64 @@ impl DctcpStack {
                     return;
                 }
 
-                if seq == conn.rcv_nxt {
+                let in_order = seq == conn.rcv_nxt;
+                let prev = conn.rcv_nxt;
+                cx.net.flow_stats_mut(conn_id).note_data_arrival(seq, prev);
+                if in_order {
                     conn.rcv_nxt = conn.rcv_nxt.saturating_add(len as u64);
+                    cx.net.flow_stats_mut(conn_id).bytes_delivered += len as u64;
+                }
+                if conn.cfg.ecn_echo_mode == DctcpEcnEchoMode::PerAck {
+                    let ack = conn.rcv_nxt;
+                    let ecn_echo = ecn.is_ce();
+                    let _ = conn;
+                    self.send_ack(conn_id, ack, ecn_echo, cx);
+                    return;
+                }
+
+                // Classic：乱序/重复数据立即以当前 CE 状态发 dupACK
+                if !in_order {
+                    conn.ack_pending_bytes = 0;
+                    conn.delack_deadline = None;
+                    let (ack, ecn_echo) = (conn.rcv_nxt, conn.ce_state);
+                    let _ = conn;
+                    self.send_ack(conn_id, ack, ecn_echo, cx);
+                    return;
+                }
+                let ce = ecn.is_ce();
+                let flush =
+                    (ce != conn.ce_state && conn.ack_pending_bytes > 0).then_some(conn.ce_state);
+                conn.ce_state = ce;
+                if let Some(old) = flush {
+                    // CE 状态翻转：先把之前攒下的数据按旧状态确认掉
+                    conn.ack_pending_bytes = 0;
+                    self.send_ack(conn_id, prev, old, cx);
                 }
-                let ack = conn.rcv_nxt;
-                let ecn_echo = ecn.is_ce();
-                let _ = conn;
-                self.send_ack(conn_id, ack, ecn_echo, sim, net);
+                let Some(conn) = self.conns.get_mut(&conn_id) else {
+                    return;
+                };
+                conn.ack_pending_bytes = conn.ack_pending_bytes.saturating_add(len as u64);
+                let finished = conn.rcv_nxt >= conn.total_bytes;
+                if conn.ack_pending_bytes < 2 * conn.cfg.mss as u64 && !finished {
+                    if conn.delack_deadline.is_none() {
+                        let deadline =
+                            SimTime(cx.now().0.saturating_add(conn.cfg.delayed_ack_timeout.0));
+                        conn.delack_deadline = Some(deadline);
+                        conn.delack_token = conn.delack_token.wrapping_add(1);
+                        cx.schedule(
+                            deadline,
+                            DctcpDelayedAck {
+                                conn_id,
+                                token: conn.delack_token,
+                            },
+                        );
+                    }
+                    return;
+                }
+                conn.ack_pending_bytes = 0;
+                conn.delack_deadline = None;
+                let (ack, ecn_echo) = (conn.rcv_nxt, conn.ce_state);
+                self.send_ack(conn_id, ack, ecn_echo, cx);
             }
             DctcpSegment::Ack { ack, ecn_echo } => {
                 let Some(conn) = self.conns.get_mut(&conn_id) else {
@@ -419,152 +687,107 @@ impl DctcpStack {
                     return;
                 }
 
-                net.viz_tcp_recv_ack(sim.now().0, conn.id, ack, ecn_echo);
-
-                if ack > conn.last_acked {
-                    let was_slow_start = conn.cwnd_bytes < conn.ssthresh_bytes;
-                    conn.dup_acks = 0;
-                    let newly_acked = ack - conn.last_acked;
-                    conn.last_acked = ack;
-
-                    let mut to_remove = Vec::new();
-                    for (&s, sent) in conn.inflight.iter() {
-                        let end = s.saturating_add(sent.len as u64);
-                        if end <= ack {
-                            to_remove.push(s);
-                        } else {
-                            break;
-                        }
-                    }
-                    for s in to_remove {
-                        conn.inflight.remove(&s);
-                    }
+                cx.net.viz_tcp_recv_ack(cx.now().0, conn.id, ack, ecn_echo);
 
-                    // DCTCP：按窗口统计 ECN 标记比例
-                    conn.acked_in_window = conn.acked_in_window.saturating_add(newly_acked);
-                    if ecn_echo {
-                        conn.marked_in_window = conn.marked_in_window.saturating_add(newly_acked);
-                    }
-
-                    let mut ecn_frac = None;
-                    let mut ecn_window_marked = false;
-                    if conn.last_acked >= conn.window_end {
-                        let frac = if conn.acked_in_window == 0 {
-                            0.0
-                        } else {
-                            conn.marked_in_window as f64 / conn.acked_in_window as f64
-                        };
-                        ecn_frac = Some(frac);
-                        conn.alpha = (1.0 - conn.cfg.g) * conn.alpha + conn.cfg.g * frac;
-                        if conn.marked_in_window > 0 {
-                            ecn_window_marked = true;
-                            let factor = 1.0 - conn.alpha / 2.0;
-                            let new_cwnd = (conn.cwnd_bytes as f64 * factor)
-                                .max(conn.cfg.mss as f64)
-                                .floor() as u64;
-                            conn.cwnd_bytes = new_cwnd.max(conn.cfg.mss as u64);
-                        }
-                        conn.acked_in_window = 0;
-                        conn.marked_in_window = 0;
-                        conn.window_end = conn.last_acked.saturating_add(conn.cwnd_bytes);
+                if ack > conn.core.last_acked() {
+                    let now = cx.now();
+                    let mss = conn.cfg.mss as u64;
+                    // 每个被累计确认、且未重传过的段都贡献一个样本（Karn：跳过重传段）
+                    let (min_rto, max_rto) = (conn.cfg.min_rto, conn.cfg.max_rto);
+                    let ack_ev = conn.core.on_new_ack(ack, mss, now, min_rto, max_rto);
+                    let newly_acked = ack_ev.newly_acked;
+                    // 有新数据被确认：为新的最早未确认段重启 RTO 定时器（RFC 6298 §5.3）
+                    if let Some(seq) = conn.core.earliest_unacked_seq() {
+                        conn.arm_rto(seq, cx);
                     }
 
-                    // 拥塞控制：慢启动 / 拥塞避免（极简）
-                    if conn.cwnd_bytes < conn.ssthresh_bytes {
-                        conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(newly_acked);
-                    } else {
-                        let mss = conn.cfg.mss as u64;
-                        let inc = (mss.saturating_mul(mss) / conn.cwnd_bytes).max(1);
-                        conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(inc);
+                    if ecn_echo {
+                        conn.cc.on_mark(newly_acked);
                     }
+                    let change = conn.cc.on_ack(&ack_ev);
 
-                    let now = sim.now();
-                    let reason = if ecn_window_marked {
-                        VizCwndReason::DctcpEcnWindow
-                    } else if was_slow_start {
-                        VizCwndReason::AckSlowStart
-                    } else {
-                        VizCwndReason::AckCongestionAvoidance
-                    };
                     conn.record_cwnd(now);
-                    net.viz_dctcp_cwnd(
+                    cx.net.viz_dctcp_cwnd(
                         now.0,
                         conn.id,
-                        conn.cwnd_bytes,
-                        conn.ssthresh_bytes,
+                        conn.cc.cwnd_bytes(),
+                        conn.cc.ssthresh_bytes(),
                         conn.inflight_bytes(),
-                        conn.alpha,
-                        reason,
+                        conn.cc.alpha(),
+                        change.reason,
                         Some(newly_acked),
                         None,
-                        ecn_frac,
+                        change.ecn_frac,
                     );
 
-                    let done = conn.last_acked >= conn.total_bytes && conn.done_at.is_none();
+                    let done = conn.core.last_acked() >= conn.total_bytes && conn.done_at.is_none();
                     if done {
-                        conn.done_at = Some(sim.now());
+                        conn.done_at = Some(cx.now());
+                        cx.net.flow_stats_mut(conn_id).completion = conn.done_at;
+                        cx.net.release_flow_slot(conn_id, cx.sim);
+                        cx.net.flow_completed(conn_id, cx.sim);
+                        let notify_delay = conn.cfg.done_notify_delay;
                         let done_cb = self.done_callbacks.remove(&conn_id);
                         if let Some(cb) = done_cb {
-                            cb(conn_id, sim.now(), sim);
+                            if notify_delay == SimTime::ZERO {
+                                cb(conn_id, cx.now(), cx.sim);
+                            } else {
+                                let at = SimTime(cx.now().0.saturating_add(notify_delay.0));
+                                cx.schedule(at, DctcpDoneNotify { conn_id, cb });
+                            }
                         }
                         return;
                     }
 
                     let id = conn.id;
                     let _ = conn;
-                    self.send_data_if_possible(id, sim, net);
-                } else if ack == conn.last_acked {
-                    conn.dup_acks = conn.dup_acks.saturating_add(1);
-                    let dup = conn.dup_acks;
+                    self.send_data_if_possible(id, cx);
+                } else if ack == conn.core.last_acked() {
+                    let dup = conn.core.on_dup_ack();
                     let mss = conn.cfg.mss as u64;
                     if dup == 3 {
-                        if let Some(seq0) = conn.earliest_unacked_seq() {
-                            conn.ssthresh_bytes = (conn.cwnd_bytes / 2).max(2 * mss);
-                            conn.cwnd_bytes = conn.ssthresh_bytes.saturating_add(3 * mss);
-                            let now = sim.now();
+                        if let Some((seq0, len)) = conn.core.retransmit_earliest() {
+                            conn.cc.on_loss(mss);
+                            let now = cx.now();
                             conn.record_cwnd(now);
-                            net.viz_dctcp_cwnd(
+                            cx.net.viz_dctcp_cwnd(
                                 now.0,
                                 conn.id,
-                                conn.cwnd_bytes,
-                                conn.ssthresh_bytes,
+                                conn.cc.cwnd_bytes(),
+                                conn.cc.ssthresh_bytes(),
                                 conn.inflight_bytes(),
-                                conn.alpha,
+                                conn.cc.alpha(),
                                 VizCwndReason::DupAck3,
                                 None,
                                 Some(dup),
                                 None,
                             );
-                            let len = conn
-                                .inflight
-                                .get(&seq0)
-                                .map(|s| s.len)
-                                .unwrap_or(conn.cfg.mss);
-                            let mut pkt = conn.make_data_packet(net);
+                            let mut pkt = conn.make_data_packet(cx.net);
                             pkt.size_bytes = conn.cfg.mss;
                             pkt.transport = Transport::Dctcp(DctcpSegment::Data { seq: seq0, len });
                             pkt.ecn = Ecn::Ect0;
-                            net.forward_from(conn.src, pkt, sim);
+                            cx.forward_from(conn.src, pkt);
+                            cx.net.flow_stats_mut(conn.id).retransmits += 1;
                         }
                     } else if dup > 3 {
-                        conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(mss);
+                        conn.cc.on_dup_ack(mss);
                         let id = conn.id;
                         let _ = conn;
-                        let now = sim.now();
+                        let now = cx.now();
                         conn.record_cwnd(now);
-                        net.viz_dctcp_cwnd(
+                        cx.net.viz_dctcp_cwnd(
                             now.0,
                             conn.id,
-                            conn.cwnd_bytes,
-                            conn.ssthresh_bytes,
+                            conn.cc.cwnd_bytes(),
+                            conn.cc.ssthresh_bytes(),
                             conn.inflight_bytes(),
-                            conn.alpha,
+                            conn.cc.alpha(),
                             VizCwndReason::DupAckMore,
                             None,
                             Some(dup),
                             None,
                         );
-                        self.send_data_if_possible(id, sim, net);
+                        self.send_data_if_possible(id, cx);
                     }
                 }
             }
@@ -581,71 +804,120 @@ pub struct DctcpStart {
 impl Event for DctcpStart {
     fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
         let DctcpStart { conn } = *self;
-        let id = conn.id;
-        with_dctcp_stack(world, move |net, dctcp| {
-            dctcp.insert(conn);
-            if let Some(c) = dctcp.get_mut(id) {
-                let now = sim.now();
-                c.record_cwnd(now);
-                net.viz_dctcp_cwnd(
-                    now.0,
-                    c.id,
-                    c.cwnd_bytes,
-                    c.ssthresh_bytes,
-                    c.inflight_bytes(),
-                    c.alpha,
-                    VizCwndReason::Init,
-                    None,
-                    None,
-                    None,
-                );
+        with_dctcp_stack(sim, world, move |cx, dctcp| {
+            let Some(conn) = cx.net.admit_dctcp_conn(conn) else {
+                return;
+            };
+            dctcp.start_admitted_conn(conn, cx);
+        });
+    }
+}
+
+/// 延迟触发的 DCTCP 完成通知（见 `DctcpConfig::done_notify_delay`）
+pub struct DctcpDoneNotify {
+    pub conn_id: DctcpConnId,
+    pub cb: DctcpDoneCallback,
+}
+
+impl Event for DctcpDoneNotify {
+    fn execute(self: Box<Self>, sim: &mut Simulator, _world: &mut dyn World) {
+        let DctcpDoneNotify { conn_id, cb } = *self;
+        cb(conn_id, sim.now(), sim);
+    }
+}
+
+/// DCTCP pacing 定时器（见 `DctcpConfig::pacing`）：到点后继续发送窗口内的下一段。
+#[derive(Debug)]
+pub struct DctcpPacedSend {
+    pub conn_id: DctcpConnId,
+}
+
+impl Event for DctcpPacedSend {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let DctcpPacedSend { conn_id } = *self;
+        with_dctcp_stack(sim, world, |cx, dctcp| {
+            let Some(conn) = dctcp.get_mut(conn_id) else {
+                return;
+            };
+            conn.pace_deadline = None;
+            dctcp.send_data_if_possible(conn_id, cx);
+        });
+    }
+}
+
+/// DCTCP 延迟 ACK 定时器（`DctcpEcnEchoMode::Classic`）：到期时以当前 CE 状态确认已攒下的数据。
+#[derive(Debug)]
+pub struct DctcpDelayedAck {
+    pub conn_id: DctcpConnId,
+    pub token: u64,
+}
+
+impl Event for DctcpDelayedAck {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let DctcpDelayedAck { conn_id, token } = *self;
+        with_dctcp_stack(sim, world, |cx, dctcp| {
+            let Some(conn) = dctcp.get_mut(conn_id) else {
+                return;
+            };
+            if conn.delack_token != token || conn.delack_deadline.take().is_none() {
+                return;
             }
-            dctcp.send_data_if_possible(id, sim, net);
+            if conn.ack_pending_bytes == 0 {
+                return;
+            }
+            conn.ack_pending_bytes = 0;
+            let (ack, ecn_echo) = (conn.rcv_nxt, conn.ce_state);
+            dctcp.send_ack(conn_id, ack, ecn_echo, cx);
         });
     }
 }
 
-/// DCTCP RTO 事件：若该 seq 仍是最早未确认段，则触发超时重传
+/// DCTCP RTO 事件：若定时器未被重置且该 seq 仍是最早未确认段，则触发超时重传
 #[derive(Debug)]
 pub struct DctcpRto {
     pub conn_id: DctcpConnId,
     pub seq: u64,
+    pub token: u64,
 }
 
 impl Event for DctcpRto {
     fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
-        let DctcpRto { conn_id, seq } = *self;
-        with_dctcp_stack(world, |net, dctcp| {
-            net.viz_tcp_rto(sim.now().0, conn_id, seq);
-
+        let DctcpRto {
+            conn_id,
+            seq,
+            token,
+        } = *self;
+        with_dctcp_stack(sim, world, |cx, dctcp| {
             let Some(conn) = dctcp.get_mut(conn_id) else {
                 return;
             };
-            if conn.done_at.is_some() {
+            if conn.done_at.is_some() || conn.core.rto_token() != token {
                 return;
             }
 
-            if conn.earliest_unacked_seq() != Some(seq) {
+            if conn.core.earliest_unacked_seq() != Some(seq) {
                 return;
             }
-            if !conn.inflight.contains_key(&seq) {
+            if !conn.core.is_inflight(seq) {
                 return;
             };
+            // 被注入故障的流不再重传（见 `Network::fail_flow_at`）
+            if cx.net.flow_failed(conn_id, cx.now()) {
+                cx.net.release_flow_slot(conn_id, cx.sim);
+                return;
+            }
+            cx.net.viz_tcp_rto(cx.now().0, conn_id, seq);
 
-            let mss = conn.cfg.mss as u64;
-            conn.ssthresh_bytes = (conn.cwnd_bytes / 2).max(2 * mss);
-            conn.cwnd_bytes = mss;
-            conn.dup_acks = 0;
-            conn.rto = SimTime((conn.rto.0.saturating_mul(2)).min(conn.cfg.max_rto.0));
-            let now = sim.now();
+            conn.cc.on_rto(conn.cfg.mss as u64);
+            let now = cx.now();
             conn.record_cwnd(now);
-            net.viz_dctcp_cwnd(
+            cx.net.viz_dctcp_cwnd(
                 now.0,
                 conn.id,
-                conn.cwnd_bytes,
-                conn.ssthresh_bytes,
+                conn.cc.cwnd_bytes(),
+                conn.cc.ssthresh_bytes(),
                 conn.inflight_bytes(),
-                conn.alpha,
+                conn.cc.alpha(),
                 VizCwndReason::RtoTimeout,
                 None,
                 None,
@@ -654,11 +926,10 @@ impl Event for DctcpRto {
 
             // Same rationale as TCP: restart from last ACKed byte to avoid
             // tail-loss recovery degenerating into one segment per RTO.
-            conn.next_seq = conn.last_acked;
-            conn.inflight.clear();
+            conn.core.on_rto(conn.cfg.min_rto, conn.cfg.max_rto);
             let id = conn.id;
             let _ = conn;
-            dctcp.send_data_if_possible(id, sim, net);
+            dctcp.send_data_if_possible(id, cx);
         });
     }
 }
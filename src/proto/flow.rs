@@ -0,0 +1,459 @@
+//! 与具体拥塞控制无关的流（发送端）抽象
+//!
+//! - `RttEstimator`：RFC 6298 的 srtt/rttvar/RTO 估计与最小 RTT（TCP 与 DCTCP 共用）
+//! - `FlowCore`：按字节序号的发送状态：未确认段、累计 ACK、dupACK 计数、超时回退、
+//!   投递速率采样
+//! - `CongestionControl`：拥塞控制钩子（`on_ack` / `on_mark` / `on_loss` / `on_rto`）
+//!
+//! TCP 与 DCTCP 栈的发送循环都只通过 `CongestionControl` 读写窗口，新的算法实现该 trait 后
+//! 用 `TcpConn::set_cc` / `DctcpConn::set_cc` 装上即可复用整套数据/ACK/重传逻辑。
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::sim::SimTime;
+use crate::viz::VizCwndReason;
+
+/// RFC 6298 的 RTT/RTO 估计器。
+#[derive(Debug, Clone)]
+pub struct RttEstimator {
+    srtt: Option<SimTime>,
+    rttvar: SimTime,
+    rto: SimTime,
+    min_rtt: Option<SimTime>,
+    samples: u64,
+}
+
+impl RttEstimator {
+    pub fn new(init_rto: SimTime) -> Self {
+        Self {
+            srtt: None,
+            rttvar: SimTime::ZERO,
+            rto: init_rto,
+            min_rtt: None,
+            samples: 0,
+        }
+    }
+
+    /// 平滑 RTT（尚无样本时为 `None`）。
+    pub fn srtt(&self) -> Option<SimTime> {
+        self.srtt
+    }
+
+    pub fn rttvar(&self) -> SimTime {
+        self.rttvar
+    }
+
+    /// 当前 RTO。
+    pub fn rto(&self) -> SimTime {
+        self.rto
+    }
+
+    /// 观测到的最小 RTT（无样本时为 None）。
+    pub fn min_rtt(&self) -> Option<SimTime> {
+        self.min_rtt
+    }
+
+    /// 已喂入的样本数。
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// 喂入一个 RTT 样本：更新 srtt/rttvar 与最小 RTT，`rto = srtt + 4*rttvar` 并夹在
+    /// `[min_rto, max_rto]` 内。
+    pub fn on_sample(&mut self, sample: SimTime, min_rto: SimTime, max_rto: SimTime) {
+        self.samples += 1;
+        self.min_rtt = Some(self.min_rtt.map_or(sample, |m| m.min(sample)));
+        if let Some(srtt) = self.srtt {
+            let diff = sample.0.abs_diff(srtt.0);
+            let rttvar = (self.rttvar.0 * 3 / 4).saturating_add(diff / 4);
+            let srtt = (srtt.0 * 7 / 8).saturating_add(sample.0 / 8);
+            self.srtt = Some(SimTime(srtt));
+            self.rttvar = SimTime(rttvar);
+        } else {
+            self.srtt = Some(sample);
+            self.rttvar = SimTime(sample.0 / 2);
+        }
+        let srtt = self.srtt.unwrap();
+        let rto = srtt.0.saturating_add(self.rttvar.0.saturating_mul(4));
+        self.rto = SimTime(rto.max(min_rto.0).min(max_rto.0));
+    }
+
+    /// 超时退避：RTO 翻倍并夹在 `[min_rto, max_rto]` 内。
+    pub fn backoff(&mut self, min_rto: SimTime, max_rto: SimTime) {
+        let rto = self.rto.0.saturating_mul(2);
+        self.rto = SimTime(rto.max(min_rto.0).min(max_rto.0));
+    }
+}
+
+/// 一次 ACK 得到的投递速率样本
+#[derive(Debug, Clone, Copy)]
+pub struct RateSample {
+    /// 被确认的最新段发出时连接已投递的字节数
+    pub prior_delivered: u64,
+    /// 当前已投递的字节数
+    pub delivered: u64,
+    /// 投递速率（字节/秒）
+    pub delivery_rate: f64,
+}
+
+#[derive(Debug, Clone)]
+struct SentSeg {
+    len: u32,
+    sent_at: SimTime,
+    retransmitted: bool,
+    /// 发送时连接已投递的字节数与对应时刻（用于投递速率采样）
+    delivered: u64,
+    delivered_at: SimTime,
+    /// 发送时所在发送区间的起点（最近被确认段的发送时刻）
+    first_sent_at: SimTime,
+}
+
+/// 一条流发送端与拥塞控制无关的状态。
+#[derive(Debug, Clone)]
+pub struct FlowCore {
+    total_bytes: u64,
+    next_seq: u64,
+    last_acked: u64,
+    dup_acks: u32,
+    inflight: BTreeMap<u64, SentSeg>, // seq -> segment
+    /// 超时回退前发出过的最高序号：低于它的段再次发送时视为重传（Karn）
+    rto_retrans_end: Option<u64>,
+    rtt: RttEstimator,
+    /// 每次(重新)设置 RTO 定时器时递增，过期的定时器事件据此忽略
+    rto_token: u64,
+    /// 投递速率采样：累计被确认的字节数、最近一次确认的时刻，
+    /// 以及最近被确认段的发送时刻
+    delivered: u64,
+    delivered_at: SimTime,
+    first_sent_at: SimTime,
+}
+
+impl FlowCore {
+    pub fn new(total_bytes: u64, init_rto: SimTime) -> Self {
+        Self {
+            total_bytes,
+            next_seq: 0,
+            last_acked: 0,
+            dup_acks: 0,
+            inflight: BTreeMap::new(),
+            rto_retrans_end: None,
+            rtt: RttEstimator::new(init_rto),
+            rto_token: 0,
+            delivered: 0,
+            delivered_at: SimTime::ZERO,
+            first_sent_at: SimTime::ZERO,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// 追加 `bytes` 字节待发送的数据（MPTCP 子流按窗口领取连接级数据）。
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.total_bytes = self.total_bytes.saturating_add(bytes);
+    }
+
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// 累计确认到的字节序号。
+    pub fn last_acked(&self) -> u64 {
+        self.last_acked
+    }
+
+    pub fn dup_acks(&self) -> u32 {
+        self.dup_acks
+    }
+
+    pub fn rtt(&self) -> &RttEstimator {
+        &self.rtt
+    }
+
+    pub fn rto_token(&self) -> u64 {
+        self.rto_token
+    }
+
+    pub fn earliest_unacked_seq(&self) -> Option<u64> {
+        self.inflight.keys().next().copied()
+    }
+
+    pub fn is_inflight(&self, seq: u64) -> bool {
+        self.inflight.contains_key(&seq)
+    }
+
+    pub fn has_inflight(&self) -> bool {
+        !self.inflight.is_empty()
+    }
+
+    pub fn inflight_bytes(&self) -> u64 {
+        self.inflight.values().map(|s| s.len as u64).sum()
+    }
+
+    /// 在剩余窗口 `avail` 内切出下一个至多 `mss` 字节的段并记为在途，返回
+    /// `(seq, len, retransmit)`；`retransmit` 表示该段低于超时回退前的最高序号，即为重传。
+    /// 窗口用尽或数据发完时返回 None。
+    pub fn send_next(
+        &mut self,
+        mss: u32,
+        avail: &mut u64,
+        now: SimTime,
+    ) -> Option<(u64, u32, bool)> {
+        if *avail == 0 || self.next_seq >= self.total_bytes {
+            return None;
+        }
+        let remain = self.total_bytes - self.next_seq;
+        let len = (mss as u64).min(remain).min(*avail) as u32;
+        if len == 0 {
+            return None;
+        }
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.saturating_add(len as u64);
+        *avail = avail.saturating_sub(len as u64);
+        let retransmitted = self.rto_retrans_end.is_some_and(|end| seq < end);
+        if self.inflight.is_empty() {
+            // 管道空了：重新开始一个发送/投递区间
+            self.first_sent_at = now;
+            self.delivered_at = now;
+        }
+        self.inflight.insert(
+            seq,
+            SentSeg {
+                len,
+                sent_at: now,
+                retransmitted,
+                delivered: self.delivered,
+                delivered_at: self.delivered_at,
+                first_sent_at: self.first_sent_at,
+            },
+        );
+        Some((seq, len, retransmitted))
+    }
+
+    /// 处理推进了累计确认的 `ack`：被确认且未重传过的段各贡献一个 RTT 样本（Karn），
+    /// 取一个投递速率样本，移除已确认段并清零 dupACK 计数。
+    ///
+    /// 返回交给 `CongestionControl::on_ack` 的 ACK 信息（`recovery`/`coupling` 为 None，
+    /// 由调用方按需填写）。
+    pub fn on_new_ack(
+        &mut self,
+        ack: u64,
+        mss: u64,
+        now: SimTime,
+        min_rto: SimTime,
+        max_rto: SimTime,
+    ) -> AckEvent {
+        let rtt_samples: Vec<SimTime> = self
+            .inflight
+            .range(..ack)
+            .take_while(|(s, sent)| s.saturating_add(sent.len as u64) <= ack)
+            .filter(|(_, sent)| !sent.retransmitted)
+            .map(|(_, sent)| SimTime(now.0.saturating_sub(sent.sent_at.0)))
+            .collect();
+        let rtt = rtt_samples.iter().min().copied();
+        for sample in rtt_samples {
+            self.rtt.on_sample(sample, min_rto, max_rto);
+        }
+        self.dup_acks = 0;
+        let newly_acked = ack - self.last_acked;
+        let rate = self.take_rate_sample(ack, newly_acked, now);
+
+        let mut to_remove = Vec::new();
+        for (&s, sent) in self.inflight.iter() {
+            let end = s.saturating_add(sent.len as u64);
+            if end <= ack {
+                to_remove.push(s);
+            } else {
+                break;
+            }
+        }
+        for s in to_remove {
+            self.inflight.remove(&s);
+        }
+        self.last_acked = ack;
+
+        AckEvent {
+            newly_acked,
+            last_acked: ack,
+            mss,
+            now,
+            rtt,
+            rate,
+            inflight_bytes: self.inflight_bytes(),
+            next_seq: self.next_seq,
+            recovery: None,
+            coupling: None,
+        }
+    }
+
+    /// 记录 `newly_acked` 字节被投递，并用被确认的最高一个未重传段生成投递速率样本：
+    /// 区间取其发送区间与确认区间中较长者，避免 ACK 压缩造成高估。
+    fn take_rate_sample(&mut self, ack: u64, newly_acked: u64, now: SimTime) -> Option<RateSample> {
+        self.delivered = self.delivered.saturating_add(newly_acked);
+        self.delivered_at = now;
+        let sent = self
+            .inflight
+            .range(..ack)
+            .take_while(|(s, sent)| s.saturating_add(sent.len as u64) <= ack)
+            .filter(|(_, sent)| !sent.retransmitted)
+            .last()
+            .map(|(_, sent)| sent.clone())?;
+        self.first_sent_at = sent.sent_at;
+        let send_elapsed = sent.sent_at.0.saturating_sub(sent.first_sent_at.0);
+        let ack_elapsed = now.0.saturating_sub(sent.delivered_at.0);
+        let interval = send_elapsed.max(ack_elapsed);
+        if interval == 0 {
+            return None;
+        }
+        let bytes = self.delivered.saturating_sub(sent.delivered);
+        Some(RateSample {
+            prior_delivered: sent.delivered,
+            delivered: self.delivered,
+            delivery_rate: bytes as f64 * 1e9 / interval as f64,
+        })
+    }
+
+    /// 记一个重复 ACK，返回累计的 dupACK 数。
+    pub fn on_dup_ack(&mut self) -> u32 {
+        self.dup_acks = self.dup_acks.saturating_add(1);
+        self.dup_acks
+    }
+
+    /// 快速重传最早未确认段：标记为重传（不再产生 RTT 样本）并返回 `(seq, len)`。
+    pub fn retransmit_earliest(&mut self) -> Option<(u64, u32)> {
+        let (&seq, sent) = self.inflight.iter_mut().next()?;
+        sent.retransmitted = true;
+        Some((seq, sent.len))
+    }
+
+    /// 尾部丢包探测：重传最后一个未确认段，标记方式同 `retransmit_earliest`。
+    pub fn retransmit_latest(&mut self) -> Option<(u64, u32)> {
+        let (&seq, sent) = self.inflight.iter_mut().next_back()?;
+        sent.retransmitted = true;
+        Some((seq, sent.len))
+    }
+
+    /// 不改变发送状态的 RTO 退避（如 SYN 超时重传）。
+    pub fn backoff_rto(&mut self, min_rto: SimTime, max_rto: SimTime) {
+        self.rtt.backoff(min_rto, max_rto);
+    }
+
+    /// 超时：RTO 退避，清空在途段并从最后确认处重新发送（回退 N）。
+    pub fn on_rto(&mut self, min_rto: SimTime, max_rto: SimTime) {
+        self.dup_acks = 0;
+        self.rtt.backoff(min_rto, max_rto);
+        self.rto_retrans_end = Some(
+            self.rto_retrans_end
+                .map_or(self.next_seq, |end| end.max(self.next_seq)),
+        );
+        self.next_seq = self.last_acked;
+        self.inflight.clear();
+    }
+
+    /// 使此前设置的 RTO 定时器失效，返回新定时器的 token。
+    pub fn next_rto_token(&mut self) -> u64 {
+        self.rto_token = self.rto_token.wrapping_add(1);
+        self.rto_token
+    }
+}
+
+/// ACK 到达时发送端所处的快速恢复阶段（NewReno，RFC 6582）。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAck {
+    /// 部分确认：恢复点之前仍有数据未被确认
+    Partial,
+    /// 确认越过恢复点，退出快速恢复
+    Exit,
+}
+
+/// 传给 `CongestionControl::on_ack` 的 ACK 信息。
+#[derive(Debug, Clone, Copy)]
+pub struct AckEvent {
+    /// 本次新确认的字节数
+    pub newly_acked: u64,
+    /// 确认后的累计确认序号
+    pub last_acked: u64,
+    pub mss: u64,
+    pub now: SimTime,
+    /// 本次 ACK 产生的最小 RTT 样本（确认的都是重传段时为 None）
+    pub rtt: Option<SimTime>,
+    pub rate: Option<RateSample>,
+    /// 确认后仍在途的字节数
+    pub inflight_bytes: u64,
+    /// 下一个待发送的字节序号
+    pub next_seq: u64,
+    /// 发送端处于快速恢复时的确认类型（不做快速恢复的发送端总为 None）
+    pub recovery: Option<RecoveryAck>,
+    /// MPTCP 子流的 LIA 耦合参数 `(alpha, cwnd_total)`（普通连接为 None）
+    pub coupling: Option<(f64, u64)>,
+}
+
+/// 一次 ACK 处理后的窗口变化（用于可视化）。
+#[derive(Debug, Clone, Copy)]
+pub struct CwndChange {
+    pub reason: VizCwndReason,
+    /// 本次结束的观测窗口内被标记的比例（仅 ECN 类算法）
+    pub ecn_frac: Option<f64>,
+}
+
+/// 拥塞控制算法：维护 cwnd/ssthresh，由发送循环在各事件上调用。
+///
+/// 实现 `Clone` 的算法自动满足 `CcClone`，连接因此可以整体克隆。
+pub trait CongestionControl: CcClone + fmt::Debug + Send + Any {
+    fn cwnd_bytes(&self) -> u64;
+
+    fn ssthresh_bytes(&self) -> u64;
+
+    /// ECN 标记比例的估计（DCTCP 的 alpha）；不使用 ECN 的算法返回 0。
+    fn alpha(&self) -> f64 {
+        0.0
+    }
+
+    /// 算法自己给出的 pacing 速率（字节/秒）；None 时按 `cwnd/srtt` pacing（若启用）。
+    fn pacing_rate(&self) -> Option<f64> {
+        None
+    }
+
+    /// 是否不论配置都逐段 pacing（如 BBR）。
+    fn always_paces(&self) -> bool {
+        false
+    }
+
+    /// 一个带 ECE 的 ACK 新确认了 `marked_bytes` 字节；在同一 ACK 的 `on_ack` 之前调用。
+    fn on_mark(&mut self, _marked_bytes: u64) {}
+
+    /// 累计确认前进（包括快速恢复中的确认，见 `AckEvent::recovery`）。
+    fn on_ack(&mut self, ack: &AckEvent) -> CwndChange;
+
+    /// 第 3 个重复 ACK：快速重传之前调用。
+    fn on_loss(&mut self, mss: u64);
+
+    /// 第 3 个之后的每个重复 ACK（含快速恢复期间的重复 ACK）。
+    fn on_dup_ack(&mut self, _mss: u64) {}
+
+    /// 快速恢复被超时打断：在 `on_rto` 之前先按 `flight_bytes` 收回窗口。
+    fn on_recovery_exit(&mut self, _flight_bytes: u64, _mss: u64) {}
+
+    /// 超时重传。
+    fn on_rto(&mut self, mss: u64);
+}
+
+/// `Box<dyn CongestionControl>` 的克隆支持。
+pub trait CcClone {
+    fn clone_box(&self) -> Box<dyn CongestionControl>;
+}
+
+impl<T: CongestionControl + Clone> CcClone for T {
+    fn clone_box(&self) -> Box<dyn CongestionControl> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn CongestionControl> {
+    fn clone(&self) -> Self {
+        self.as_ref().clone_box()
+    }
+}
@@ -1,8 +1,13 @@
 //! 传输层/协议模块
 //!
-//! 包含 TCP / DCTCP 的简化实现（用于仿真实验）。
+//! 包含 TCP / DCTCP（以及 TCP 的 Reno / Vegas / BBR 拥塞控制）的简化实现（用于仿真实验）。
+//! `flow` 提供与协议无关的发送端状态与拥塞控制 trait。
 
+pub mod bbr;
 pub mod dctcp;
+pub mod flow;
+pub mod reno;
 pub mod tcp;
+pub mod vegas;
 
 // Transport tag types live in `net::transport`.
@@ -0,0 +1,144 @@
+//! Reno 拥塞控制（TCP 的默认算法，`CcAlgo::Reno`）
+//!
+//! - 慢启动每 ACK +min(acked, mss)（不越过 ssthresh），拥塞避免每 RTT 约 +1 MSS
+//! - 3 个重复 ACK 进入 NewReno 快速恢复（RFC 6582）：部分确认收缩窗口，越过恢复点时
+//!   窗口收回到 `min(ssthresh, flight + mss)`
+//! - MPTCP 子流的拥塞避免按 LIA（RFC 6356）耦合（见 `AckEvent::coupling`）
+//!
+//! Vegas 与 BBR 复用这里的丢包/超时处理，只替换 ACK 时的窗口增长。
+
+use crate::proto::flow::{AckEvent, CongestionControl, CwndChange, RecoveryAck};
+use crate::viz::VizCwndReason;
+
+#[derive(Debug, Clone)]
+pub struct RenoCc {
+    cwnd_bytes: u64,
+    ssthresh_bytes: u64,
+}
+
+impl RenoCc {
+    pub fn new(init_cwnd_bytes: u64, init_ssthresh_bytes: u64) -> Self {
+        Self {
+            cwnd_bytes: init_cwnd_bytes,
+            ssthresh_bytes: init_ssthresh_bytes,
+        }
+    }
+
+    pub(crate) fn in_slow_start(&self) -> bool {
+        self.cwnd_bytes < self.ssthresh_bytes
+    }
+
+    pub(crate) fn set_cwnd_bytes(&mut self, cwnd_bytes: u64) {
+        self.cwnd_bytes = cwnd_bytes;
+    }
+
+    pub(crate) fn set_ssthresh_bytes(&mut self, ssthresh_bytes: u64) {
+        self.ssthresh_bytes = ssthresh_bytes;
+    }
+
+    /// Reno 风格的窗口增长：慢启动每 ACK +min(acked, mss)，拥塞避免每 RTT 约 +1 MSS。
+    pub(crate) fn grow(&mut self, newly_acked: u64, mss: u64) {
+        if self.cwnd_bytes < self.ssthresh_bytes {
+            let capped = newly_acked.min(mss);
+            let room = self.ssthresh_bytes.saturating_sub(self.cwnd_bytes);
+            let inc = capped.min(room);
+            self.cwnd_bytes = self.cwnd_bytes.saturating_add(inc);
+        } else {
+            // AIMD：每个 ACK 让 cwnd 以 mss^2/cwnd 增长（至少 +1）
+            let inc = (mss.saturating_mul(mss) / self.cwnd_bytes).max(1);
+            self.cwnd_bytes = self.cwnd_bytes.saturating_add(inc);
+        }
+    }
+
+    /// LIA（RFC 6356）耦合的窗口增长：慢启动不耦合；拥塞避免每 ACK 增加
+    /// `min(alpha * acked * mss / cwnd_total, acked * mss / cwnd)`，
+    /// 使所有子流合起来不比一条走最好路径的单路径 TCP 更激进。
+    fn grow_coupled(&mut self, newly_acked: u64, mss: u64, alpha: f64, cwnd_total: u64) {
+        if self.cwnd_bytes < self.ssthresh_bytes {
+            self.grow(newly_acked, mss);
+            return;
+        }
+        let mss = mss as f64;
+        let acked = newly_acked as f64;
+        let coupled = alpha * acked * mss / cwnd_total.max(1) as f64;
+        let uncoupled = acked * mss / self.cwnd_bytes.max(1) as f64;
+        let inc = (coupled.min(uncoupled) as u64).max(1);
+        self.cwnd_bytes = self.cwnd_bytes.saturating_add(inc);
+    }
+
+    /// 快速恢复中的确认：部分确认按新确认的字节收缩窗口再加一个 MSS，
+    /// 越过恢复点时收回到 `min(ssthresh, flight + mss)`。
+    pub(crate) fn on_recovery_ack(&mut self, ack: &AckEvent, recovery: RecoveryAck) -> CwndChange {
+        let reason = match recovery {
+            RecoveryAck::Partial => {
+                self.cwnd_bytes = self
+                    .cwnd_bytes
+                    .saturating_sub(ack.newly_acked)
+                    .saturating_add(ack.mss);
+                VizCwndReason::FastRecoveryPartialAck
+            }
+            RecoveryAck::Exit => {
+                let flight = ack.next_seq.saturating_sub(ack.last_acked);
+                self.on_recovery_exit(flight, ack.mss);
+                VizCwndReason::FastRecoveryExit
+            }
+        };
+        CwndChange {
+            reason,
+            ecn_frac: None,
+        }
+    }
+}
+
+/// 按 ACK 前所处阶段给出窗口变化原因。
+pub(crate) fn growth_change(was_slow_start: bool) -> CwndChange {
+    let reason = if was_slow_start {
+        VizCwndReason::AckSlowStart
+    } else {
+        VizCwndReason::AckCongestionAvoidance
+    };
+    CwndChange {
+        reason,
+        ecn_frac: None,
+    }
+}
+
+impl CongestionControl for RenoCc {
+    fn cwnd_bytes(&self) -> u64 {
+        self.cwnd_bytes
+    }
+
+    fn ssthresh_bytes(&self) -> u64 {
+        self.ssthresh_bytes
+    }
+
+    fn on_ack(&mut self, ack: &AckEvent) -> CwndChange {
+        if let Some(recovery) = ack.recovery {
+            return self.on_recovery_ack(ack, recovery);
+        }
+        let was_slow_start = self.in_slow_start();
+        match ack.coupling {
+            Some((alpha, total)) => self.grow_coupled(ack.newly_acked, ack.mss, alpha, total),
+            None => self.grow(ack.newly_acked, ack.mss),
+        }
+        growth_change(was_slow_start)
+    }
+
+    fn on_loss(&mut self, mss: u64) {
+        self.ssthresh_bytes = (self.cwnd_bytes / 2).max(2 * mss);
+        self.cwnd_bytes = self.ssthresh_bytes.saturating_add(3 * mss);
+    }
+
+    fn on_dup_ack(&mut self, mss: u64) {
+        self.cwnd_bytes = self.cwnd_bytes.saturating_add(mss);
+    }
+
+    fn on_recovery_exit(&mut self, flight_bytes: u64, mss: u64) {
+        self.cwnd_bytes = self.ssthresh_bytes.min(flight_bytes.saturating_add(mss));
+    }
+
+    fn on_rto(&mut self, mss: u64) {
+        self.ssthresh_bytes = (self.cwnd_bytes / 2).max(2 * mss);
+        self.cwnd_bytes = mss;
+    }
+}
@@ -1,15 +1,72 @@
 //! TCP 协议实现（用于仿真实验）
+//!
+//! 发送状态由 `proto::flow::FlowCore` 维护，窗口由 `CongestionControl` 决定
+//! （按 `TcpConfig::cc` 构造，`TcpConn::set_cc` 可换上其他算法）；这里只负责握手、
+//! NewReno 快速恢复的重传时机、TLP、pacing、延迟 ACK 与 MPTCP 子流。
 
+use std::any::Any;
 use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
-use crate::net::{NetApi, NodeId, TcpSegment, Transport, with_tcp_stack};
+use crate::net::{NetApi, NodeId, SimContext, TcpSegment, Transport, with_tcp_stack};
+use crate::proto::bbr::{BbrCc, BbrMode};
+use crate::proto::flow::{CongestionControl, FlowCore, RecoveryAck};
+use crate::proto::reno::RenoCc;
+use crate::proto::vegas::VegasCc;
 use crate::sim::{Event, SimTime, Simulator, World};
 use crate::viz::VizCwndReason;
 
 /// 一个 TCP 连接的唯一标识（复用 `flow_id` 的语义）。
 pub type TcpConnId = u64;
 
+/// MPTCP 子流的 id 步长：连接 `id` 的第 k 个子流使用 `id + k * MPTCP_SUBFLOW_ID_STRIDE`
+/// （第 0 个子流沿用连接自身的 id）。
+pub const MPTCP_SUBFLOW_ID_STRIDE: u64 = 1 << 32;
+/// 为 MPTCP 子流寻找不同 ECMP 路径时最多尝试的 hash key 数
+const MPTCP_ROUTE_ATTEMPTS: u64 = 64;
+
+/// 拥塞避免算法。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CcAlgo {
+    /// 基于丢包的 AIMD（Reno 风格）
+    #[default]
+    Reno,
+    /// 基于时延的 Vegas：用 base RTT 估计瓶颈处排队的包数，
+    /// 少于 `alpha` 个则每 RTT +1 MSS，多于 `beta` 个则每 RTT -1 MSS。
+    Vegas { alpha: u32, beta: u32 },
+    /// 基于模型的 BBR：估计瓶颈带宽与最小 RTT，按 `btlbw * min_rtt * gain`
+    /// 设定 cwnd，并总是以 `pacing_gain * btlbw` 的速率 pacing（见 `proto::bbr`）
+    Bbr,
+}
+
+impl CcAlgo {
+    /// 常用的 Vegas 参数（alpha=2, beta=4）。
+    pub fn vegas() -> Self {
+        Self::Vegas { alpha: 2, beta: 4 }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_lowercase().as_str() {
+            "reno" => Ok(Self::Reno),
+            "vegas" => Ok(Self::vegas()),
+            "bbr" => Ok(Self::Bbr),
+            _ => Err(format!("unknown congestion control: {raw}")),
+        }
+    }
+
+    /// 按配置构造该算法的 `CongestionControl`。
+    pub fn build(self, cfg: &TcpConfig) -> Box<dyn CongestionControl> {
+        let mss = cfg.mss as u64;
+        let cwnd = cfg.init_cwnd_bytes.max(mss);
+        let ssthresh = cfg.init_ssthresh_bytes.max(mss);
+        match self {
+            Self::Reno => Box::new(RenoCc::new(cwnd, ssthresh)),
+            Self::Vegas { alpha, beta } => Box::new(VegasCc::new(alpha, beta, cwnd, ssthresh)),
+            Self::Bbr => Box::new(BbrCc::new(mss, cwnd, ssthresh)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TcpConfig {
     /// MSS（数据段载荷大小，字节）
@@ -30,6 +87,28 @@ pub struct TcpConfig {
     pub handshake: bool,
     /// 应用层限速（包/秒）
     pub app_limited_pps: Option<u64>,
+    /// 完成通知延迟：最后一个 ACK 到达后，再过多久调用 done callback
+    ///
+    /// 用于建模完成通知本身的开销（如额外一次跨网络的确认）；默认 0 表示立即通知。
+    pub done_notify_delay: SimTime,
+    /// 拥塞避免算法（默认 Reno）
+    pub cc: CcAlgo,
+    /// 启用尾部丢包探测（TLP，RFC 8985）：尾部数据未被确认时，在
+    /// `max(2*srtt, min_rto)` 后重传最后一个未确认段，而不是等待完整的 RTO
+    pub enable_tlp: bool,
+    /// 延迟 ACK：按序到达的数据攒满两个 MSS 或等待该时长后才发累计 ACK；
+    /// 乱序/重复数据仍立即发 dupACK。`None` 表示每个数据段都立即 ACK
+    pub delayed_ack: Option<SimTime>,
+    /// 数据段（含 SYN）的流量类别优先级，见 `Packet::priority`
+    pub data_priority: u8,
+    /// ACK（含 SYN-ACK）的流量类别优先级；调高可让 ACK 越过同一队列里的数据
+    pub ack_priority: u8,
+    /// 发送端 pacing：按 `cwnd/srtt` 的速率逐段发送（每段之间间隔 `len*srtt/cwnd`），
+    /// 而不是一次性把整个窗口发出；还没有 RTT 样本时（初始窗口）仍然突发发送
+    pub pacing: bool,
+    /// MPTCP 子流数：大于 1 时连接在启动时拆成多个子流，各自走不同的 ECMP 路径，
+    /// 共享连接级的字节序号空间；Reno 的拥塞避免按 LIA（RFC 6356）耦合
+    pub subflows: usize,
 }
 
 impl Default for TcpConfig {
@@ -45,6 +124,14 @@ impl Default for TcpConfig {
             max_rto: SimTime::from_millis(60000), // 60 秒最大 RTO
             handshake: false,
             app_limited_pps: None,
+            done_notify_delay: SimTime::ZERO,
+            cc: CcAlgo::Reno,
+            enable_tlp: false,
+            delayed_ack: None,
+            data_priority: 0,
+            ack_priority: 0,
+            pacing: false,
+            subflows: 1,
         }
     }
 }
@@ -68,13 +155,6 @@ enum ReceiverState {
     Established,
 }
 
-#[derive(Debug, Clone)]
-struct SentSeg {
-    len: u32,
-    sent_at: SimTime,
-    retransmitted: bool,
-}
-
 #[derive(Debug, Clone)]
 pub struct TcpConn {
     pub id: TcpConnId,
@@ -87,29 +167,32 @@ pub struct TcpConn {
     pub routing_mode: TcpRoutingMode,
 
     // sender
-    next_seq: u64,
-    last_acked: u64,
-    cwnd_bytes: u64,
-    ssthresh_bytes: u64,
-    dup_acks: u32,
-    rto: SimTime,
+    core: FlowCore,
+    cc: Box<dyn CongestionControl>,
     rto_deadline: Option<SimTime>,
-    rto_token: u64,
-    srtt: Option<SimTime>,
-    rttvar: SimTime,
-    inflight: BTreeMap<u64, SentSeg>, // seq -> segment
     recover: u64,
     in_fast_recovery: bool,
-    /// Upper bound of seq range that should be treated as retransmissions after an RTO.
-    ///
-    /// When an RTO triggers we restart sending from `last_acked` but keep track of the
-    /// previous `next_seq` as a "high watermark": any segment with `seq < watermark`
-    /// was sent before and should be marked as a retransmission in viz logs.
-    rto_retrans_end: Option<u64>,
+    /// TLP 探测定时器（与 RTO 一样用 token 使过期事件失效）
+    tlp_deadline: Option<SimTime>,
+    tlp_token: u64,
+    /// 已发出探测、尚未收到新的 ACK：此期间不再重复探测
+    tlp_outstanding: bool,
+    tlp_probes: u64,
+    cwnd_log: Option<Vec<CwndSample>>,
 
     // receiver
     rcv_nxt: u64,
     out_of_order: BTreeMap<u64, u32>,
+    /// 延迟 ACK：尚未确认的按序字节数与定时器
+    ack_pending_bytes: u64,
+    delack_deadline: Option<SimTime>,
+    delack_token: u64,
+    /// pacing：下一个数据段最早的发送时刻
+    pace_next: SimTime,
+    /// pacing：已排程的 `TcpPacedSend` 的发送时刻（没有时为 None）
+    pace_deadline: Option<SimTime>,
+    /// MPTCP 子流所属的连接 id（普通连接为 None）
+    mptcp: Option<TcpConnId>,
 
     // handshake
     sender_state: SenderState,
@@ -133,9 +216,8 @@ impl TcpConn {
     ) -> Self {
         let mut rev_route = fwd_route.clone();
         rev_route.reverse();
-        let init_rto = cfg.init_rto;
-        let cwnd = cfg.init_cwnd_bytes.max(cfg.mss as u64);
-        let ssthresh = cfg.init_ssthresh_bytes.max(cfg.mss as u64);
+        let core = FlowCore::new(total_bytes, cfg.init_rto);
+        let cc = cfg.cc.build(&cfg);
         let sender_state = if cfg.handshake {
             SenderState::SynSent
         } else {
@@ -155,22 +237,24 @@ impl TcpConn {
             total_bytes,
             cfg,
             routing_mode: TcpRoutingMode::Preset,
-            next_seq: 0,
-            last_acked: 0,
-            cwnd_bytes: cwnd,
-            ssthresh_bytes: ssthresh,
-            dup_acks: 0,
-            rto: init_rto,
+            core,
+            cc,
             rto_deadline: None,
-            rto_token: 0,
-            srtt: None,
-            rttvar: SimTime::ZERO,
-            inflight: BTreeMap::new(),
             recover: 0,
             in_fast_recovery: false,
-            rto_retrans_end: None,
+            tlp_deadline: None,
+            tlp_token: 0,
+            tlp_outstanding: false,
+            tlp_probes: 0,
+            cwnd_log: None,
             rcv_nxt: 0,
             out_of_order: BTreeMap::new(),
+            ack_pending_bytes: 0,
+            delack_deadline: None,
+            delack_token: 0,
+            pace_next: SimTime::ZERO,
+            pace_deadline: None,
+            mptcp: None,
             sender_state,
             receiver_state,
             syn_sent_at: None,
@@ -187,9 +271,8 @@ impl TcpConn {
         total_bytes: u64,
         cfg: TcpConfig,
     ) -> Self {
-        let init_rto = cfg.init_rto;
-        let cwnd = cfg.init_cwnd_bytes.max(cfg.mss as u64);
-        let ssthresh = cfg.init_ssthresh_bytes.max(cfg.mss as u64);
+        let core = FlowCore::new(total_bytes, cfg.init_rto);
+        let cc = cfg.cc.build(&cfg);
         let sender_state = if cfg.handshake {
             SenderState::SynSent
         } else {
@@ -209,22 +292,24 @@ impl TcpConn {
             total_bytes,
             cfg,
             routing_mode: TcpRoutingMode::Dynamic,
-            next_seq: 0,
-            last_acked: 0,
-            cwnd_bytes: cwnd,
-            ssthresh_bytes: ssthresh,
-            dup_acks: 0,
-            rto: init_rto,
+            core,
+            cc,
             rto_deadline: None,
-            rto_token: 0,
-            srtt: None,
-            rttvar: SimTime::ZERO,
-            inflight: BTreeMap::new(),
             recover: 0,
             in_fast_recovery: false,
-            rto_retrans_end: None,
+            tlp_deadline: None,
+            tlp_token: 0,
+            tlp_outstanding: false,
+            tlp_probes: 0,
+            cwnd_log: None,
             rcv_nxt: 0,
             out_of_order: BTreeMap::new(),
+            ack_pending_bytes: 0,
+            delack_deadline: None,
+            delack_token: 0,
+            pace_next: SimTime::ZERO,
+            pace_deadline: None,
+            mptcp: None,
             sender_state,
             receiver_state,
             syn_sent_at: None,
@@ -235,7 +320,7 @@ impl TcpConn {
     }
 
     pub fn bytes_acked(&self) -> u64 {
-        self.last_acked.min(self.total_bytes)
+        self.core.last_acked().min(self.total_bytes)
     }
 
     pub fn is_done(&self) -> bool {
@@ -250,45 +335,156 @@ impl TcpConn {
         self.done_at
     }
 
-    fn earliest_unacked_seq(&self) -> Option<u64> {
-        self.inflight.keys().next().copied()
+    pub fn cwnd_bytes(&self) -> u64 {
+        self.cc.cwnd_bytes()
+    }
+
+    /// 换用其他拥塞控制算法（默认按 `cfg.cc` 构造）；应在连接启动前调用。
+    pub fn set_cc(&mut self, cc: Box<dyn CongestionControl>) {
+        self.cc = cc;
+    }
+
+    /// 平滑 RTT（尚无样本时为 `None`）。
+    pub fn srtt(&self) -> Option<SimTime> {
+        self.core.rtt().srtt()
+    }
+
+    pub fn rttvar(&self) -> SimTime {
+        self.core.rtt().rttvar()
+    }
+
+    /// 当前 RTO。
+    pub fn rto(&self) -> SimTime {
+        self.core.rtt().rto()
+    }
+
+    /// 已用于更新 srtt/rttvar 的 RTT 样本数（重传过的段不产生样本）。
+    pub fn rtt_samples(&self) -> u64 {
+        self.core.rtt().samples()
+    }
+
+    fn bbr(&self) -> Option<&BbrCc> {
+        (self.cc.as_ref() as &dyn Any).downcast_ref::<BbrCc>()
+    }
+
+    /// BBR 当前所处阶段（非 BBR 连接为 None）。
+    pub fn bbr_mode(&self) -> Option<BbrMode> {
+        self.bbr().map(BbrCc::mode)
+    }
+
+    /// BBR 的瓶颈带宽估计（bit/s；非 BBR 连接或尚无样本时为 None）。
+    pub fn bbr_btlbw_bps(&self) -> Option<f64> {
+        Some(self.bbr()?.btlbw()? * 8.0)
+    }
+
+    /// BBR 的 BDP 估计 `btlbw * min_rtt`（字节）。
+    pub fn bbr_bdp_bytes(&self) -> Option<u64> {
+        Some(self.bbr()?.bdp()? as u64)
+    }
+
+    /// MPTCP 子流所属的连接 id（普通连接为 None）。
+    pub fn mptcp_conn(&self) -> Option<TcpConnId> {
+        self.mptcp
+    }
+
+    /// 发出的尾部丢包探测次数。
+    pub fn tlp_probes(&self) -> u64 {
+        self.tlp_probes
+    }
+
+    pub fn enable_cwnd_log(&mut self) {
+        self.cwnd_log = Some(Vec::new());
+    }
+
+    pub fn cwnd_samples(&self) -> Option<&[CwndSample]> {
+        self.cwnd_log.as_deref()
+    }
+
+    fn record_cwnd(&mut self, now: SimTime) {
+        let inflight = self.inflight_bytes();
+        let Some(log) = &mut self.cwnd_log else {
+            return;
+        };
+        log.push(CwndSample {
+            t_ns: now.0,
+            cwnd_bytes: self.cc.cwnd_bytes(),
+            ssthresh_bytes: self.cc.ssthresh_bytes(),
+            srtt_ns: self.core.rtt().srtt().map(|t| t.0),
+            inflight_bytes: inflight,
+            acked_bytes: self.core.last_acked(),
+        });
+    }
+
+    /// 数据段在线路上的大小：普通段按 mss 计（简化），传输末尾的尾段按实际字节数，
+    /// 这样小于 mss 的传输只发一个大小正确的包。
+    fn data_packet_bytes(&self, seq: u64, len: u32) -> u32 {
+        if seq.saturating_add(len as u64) >= self.total_bytes {
+            len
+        } else {
+            self.cfg.mss
+        }
     }
 
     fn make_data_packet(&self, net: &mut dyn NetApi) -> crate::net::Packet {
-        match self.routing_mode {
+        let mut pkt = match self.routing_mode {
             TcpRoutingMode::Preset => {
                 net.make_packet(self.id, self.cfg.mss, self.fwd_route.clone())
             }
             TcpRoutingMode::Dynamic => {
                 net.make_packet_dynamic(self.id, self.cfg.mss, self.src, self.dst)
             }
-        }
+        };
+        pkt.priority = self.cfg.data_priority;
+        pkt
     }
 
     fn make_ack_packet(&self, net: &mut dyn NetApi) -> crate::net::Packet {
-        match self.routing_mode {
+        let mut pkt = match self.routing_mode {
             TcpRoutingMode::Preset => {
                 net.make_packet(self.id, self.cfg.ack_bytes, self.rev_route.clone())
             }
             TcpRoutingMode::Dynamic => {
                 net.make_packet_dynamic(self.id, self.cfg.ack_bytes, self.dst, self.src)
             }
-        }
+        };
+        pkt.priority = self.cfg.ack_priority;
+        pkt
     }
 
     fn inflight_bytes(&self) -> u64 {
-        self.inflight.values().map(|s| s.len as u64).sum()
+        self.core.inflight_bytes()
+    }
+
+    /// 是否逐段 pacing：显式开启 `pacing`，或拥塞控制总是 pacing（BBR）。
+    fn paces(&self) -> bool {
+        self.cfg.pacing || self.cc.always_paces()
+    }
+
+    /// pacing 时发出 `len` 字节后到下一段的间隔：拥塞控制给出速率时（BBR 有带宽估计）为
+    /// `len / pacing_rate`，否则为 `len * srtt / cwnd`；不 pacing 或没有 RTT 样本时返回 None
+    /// （突发发送）。
+    fn pacing_gap(&self, len: u32) -> Option<SimTime> {
+        if !self.paces() {
+            return None;
+        }
+        if let Some(rate) = self.cc.pacing_rate() {
+            return Some(SimTime::from_secs_f64(len as f64 / rate));
+        }
+        let srtt = self.srtt()?;
+        let cwnd = self.effective_cwnd().max(1) as u128;
+        let gap = (len as u128).saturating_mul(srtt.0 as u128) / cwnd;
+        Some(SimTime(gap.min(u64::MAX as u128) as u64))
     }
 
     fn effective_cwnd(&self) -> u64 {
-        let mut cwnd = self.cwnd_bytes;
+        let mut cwnd = self.cc.cwnd_bytes();
         if self.in_fast_recovery {
-            cwnd = cwnd.min(self.ssthresh_bytes);
+            cwnd = cwnd.min(self.cc.ssthresh_bytes());
         }
         let Some(pps) = self.cfg.app_limited_pps else {
             return cwnd;
         };
-        let Some(srtt) = self.srtt else {
+        let Some(srtt) = self.srtt() else {
             return cwnd;
         };
         let rtt_ns = srtt.0.max(1);
@@ -300,32 +496,15 @@ impl TcpConn {
         cwnd
     }
 
-    fn update_rto_with_sample(&mut self, sample: SimTime) {
-        if let Some(srtt) = self.srtt {
-            let diff = if sample.0 >= srtt.0 {
-                sample.0 - srtt.0
-            } else {
-                srtt.0 - sample.0
-            };
-            let rttvar = (self.rttvar.0 * 3 / 4).saturating_add(diff / 4);
-            let srtt = (srtt.0 * 7 / 8).saturating_add(sample.0 / 8);
-            self.srtt = Some(SimTime(srtt));
-            self.rttvar = SimTime(rttvar);
-        } else {
-            self.srtt = Some(sample);
-            self.rttvar = SimTime(sample.0 / 2);
-        }
-        let srtt = self.srtt.unwrap();
-        let mut rto = srtt.0.saturating_add(self.rttvar.0.saturating_mul(4));
-        rto = rto.max(self.cfg.min_rto.0).min(self.cfg.max_rto.0);
-        self.rto = SimTime(rto);
+    /// 观测到的最小 RTT（无样本时为 None）。
+    pub fn min_rtt(&self) -> Option<SimTime> {
+        self.core.rtt().min_rtt()
     }
 
     fn schedule_rto(&mut self, sim: &mut Simulator) {
-        let deadline = SimTime(sim.now().0.saturating_add(self.rto.0));
+        let deadline = SimTime(sim.now().0.saturating_add(self.rto().0));
         self.rto_deadline = Some(deadline);
-        self.rto_token = self.rto_token.wrapping_add(1);
-        let token = self.rto_token;
+        let token = self.core.next_rto_token();
         sim.schedule(
             deadline,
             TcpRto {
@@ -339,13 +518,13 @@ impl TcpConn {
         if self.rto_deadline.is_some() {
             return;
         }
-        if self.syn_sent_at.is_some() || !self.inflight.is_empty() {
+        if self.syn_sent_at.is_some() || self.core.has_inflight() {
             self.schedule_rto(sim);
         }
     }
 
     fn restart_rto(&mut self, sim: &mut Simulator) {
-        if self.syn_sent_at.is_some() || !self.inflight.is_empty() {
+        if self.syn_sent_at.is_some() || self.core.has_inflight() {
             self.schedule_rto(sim);
         } else {
             self.rto_deadline = None;
@@ -356,6 +535,41 @@ impl TcpConn {
         self.rto_deadline = None;
     }
 
+    /// 发送窗口用尽（或数据已全部发出）且仍有未确认数据时布置 TLP 探测定时器。
+    ///
+    /// 探测时刻为 `max(2*srtt, min_rto)` 之后；没有 RTT 样本、处于快速恢复、
+    /// 已有探测未被确认，或探测不早于 RTO 时不布置（交给 RTO 处理）。
+    fn arm_tlp(&mut self, window_full: bool, sim: &mut Simulator) {
+        self.tlp_deadline = None;
+        if !self.cfg.enable_tlp
+            || self.in_fast_recovery
+            || self.tlp_outstanding
+            || !self.core.has_inflight()
+        {
+            return;
+        }
+        if !window_full && self.core.next_seq() < self.core.total_bytes() {
+            return;
+        }
+        let Some(srtt) = self.srtt() else {
+            return;
+        };
+        let pto = srtt.0.saturating_mul(2).max(self.cfg.min_rto.0);
+        let deadline = SimTime(sim.now().0.saturating_add(pto));
+        if self.rto_deadline.is_some_and(|rto| deadline >= rto) {
+            return;
+        }
+        self.tlp_deadline = Some(deadline);
+        self.tlp_token = self.tlp_token.wrapping_add(1);
+        sim.schedule(
+            deadline,
+            TcpTlp {
+                conn_id: self.id,
+                token: self.tlp_token,
+            },
+        );
+    }
+
     fn recv_data(&mut self, seq: u64, len: u32) -> u64 {
         if seq == self.rcv_nxt {
             self.rcv_nxt = self.rcv_nxt.saturating_add(len as u64);
@@ -369,12 +583,35 @@ impl TcpConn {
     }
 }
 
+/// TCP 拥塞窗口采样（用于离线绘图；`srtt_ns` 在尚无 RTT 样本时为 None）
+#[derive(Debug, Clone)]
+pub struct CwndSample {
+    pub t_ns: u64,
+    pub cwnd_bytes: u64,
+    pub ssthresh_bytes: u64,
+    pub srtt_ns: Option<u64>,
+    pub inflight_bytes: u64,
+    pub acked_bytes: u64,
+}
+
 pub type TcpDoneCallback = Box<dyn Fn(TcpConnId, SimTime, &mut Simulator) + Send>;
 
+/// MPTCP 连接级状态：各子流从共享的字节序号空间中按窗口领取数据
+#[derive(Debug, Clone)]
+struct MptcpConn {
+    total_bytes: u64,
+    /// 下一个尚未分配给任何子流的连接级字节
+    data_next: u64,
+    /// 各子流累计确认的字节数之和
+    data_acked: u64,
+    subflows: Vec<TcpConnId>,
+}
+
 #[derive(Default)]
 pub struct TcpStack {
     conns: HashMap<TcpConnId, TcpConn>,
     done_callbacks: HashMap<TcpConnId, TcpDoneCallback>,
+    mptcp: HashMap<TcpConnId, MptcpConn>,
 }
 
 impl fmt::Debug for TcpStack {
@@ -403,18 +640,126 @@ impl TcpStack {
         self.conns.get_mut(&id)
     }
 
+    /// Start a connection, subject to the source host's concurrent-flow limit.
+    /// A connection with `cfg.subflows > 1` starts all of its MPTCP subflows.
     pub fn start_conn(&mut self, conn: TcpConn, sim: &mut Simulator, net: &mut dyn NetApi) {
-        let id = conn.id;
-        self.insert(conn);
-        self.send_data_if_possible(id, sim, net);
+        let Some(conn) = net.admit_tcp_conn(conn) else {
+            return;
+        };
+        self.start_admitted_conn(conn, &mut SimContext::new(sim, net));
     }
 
-    pub(crate) fn send_data_if_possible(
-        &mut self,
-        id: TcpConnId,
-        sim: &mut Simulator,
-        net: &mut dyn NetApi,
-    ) {
+    pub(crate) fn start_admitted_conn(&mut self, conn: TcpConn, cx: &mut SimContext<'_>) {
+        let parent = conn.id;
+        let ids = self.insert_subflows(conn, cx.net);
+        if ids.is_empty() {
+            cx.net.release_flow_slot(parent, cx.sim);
+        }
+        for id in ids {
+            if let Some(conn) = self.get_mut(id) {
+                conn.record_cwnd(cx.now());
+            }
+            self.send_data_if_possible(id, cx);
+        }
+    }
+
+    /// 插入连接；`cfg.subflows > 1` 时拆成多个 MPTCP 子流并返回它们的 id。
+    ///
+    /// 第 0 个子流沿用连接的 id 与路径（动态路由的连接按 ECMP 选一条），
+    /// 其余子流用 `try_route_ecmp_path` 尝试不同的 hash key，优先选与已有子流不同的路径；
+    /// 拓扑中不同路径不够时会与已有子流共享路径。
+    ///
+    /// 动态路由的连接若目的主机不可达，则放弃该流（见 `Network::fail_flow_no_route`）
+    /// 并返回空列表。
+    fn insert_subflows(&mut self, conn: TcpConn, net: &mut dyn NetApi) -> Vec<TcpConnId> {
+        let parent = conn.id;
+        let first_route = match conn.routing_mode {
+            TcpRoutingMode::Preset => conn.fwd_route.clone(),
+            TcpRoutingMode::Dynamic => {
+                let Some(route) = net.try_route_ecmp_path(conn.src, conn.dst, parent) else {
+                    net.fail_flow_no_route(parent, conn.src, conn.dst);
+                    return Vec::new();
+                };
+                route
+            }
+        };
+        let n = conn.cfg.subflows;
+        if n <= 1 {
+            self.insert(conn);
+            return vec![parent];
+        }
+        let mut routes = vec![first_route];
+        let mut subflows = Vec::with_capacity(n);
+        for k in 0..n as u64 {
+            let id = parent.saturating_add(k.saturating_mul(MPTCP_SUBFLOW_ID_STRIDE));
+            if k > 0 {
+                let mut route = None;
+                for key in 0..MPTCP_ROUTE_ATTEMPTS {
+                    route = net.try_route_ecmp_path(conn.src, conn.dst, id.wrapping_add(key));
+                    if route.as_ref().is_some_and(|r| !routes.contains(r)) {
+                        break;
+                    }
+                }
+                let route = route.unwrap_or_else(|| routes[0].clone());
+                routes.push(route);
+            }
+            let mut sub = TcpConn::new(
+                id,
+                conn.src,
+                conn.dst,
+                routes[k as usize].clone(),
+                0,
+                conn.cfg.clone(),
+            );
+            sub.cc = conn.cc.clone();
+            sub.mptcp = Some(parent);
+            self.insert(sub);
+            subflows.push(id);
+        }
+        self.mptcp.insert(
+            parent,
+            MptcpConn {
+                total_bytes: conn.total_bytes,
+                data_next: 0,
+                data_acked: 0,
+                subflows: subflows.clone(),
+            },
+        );
+        subflows
+    }
+
+    /// MPTCP 连接的子流 id（普通连接为 None）。
+    pub fn subflows(&self, id: TcpConnId) -> Option<&[TcpConnId]> {
+        self.mptcp.get(&id).map(|mp| mp.subflows.as_slice())
+    }
+
+    /// MPTCP 连接级已确认的字节数（各子流之和；普通连接为 None）。
+    pub fn mptcp_bytes_acked(&self, id: TcpConnId) -> Option<u64> {
+        self.mptcp.get(&id).map(|mp| mp.data_acked)
+    }
+
+    /// LIA 的耦合参数 `(alpha, cwnd_total)`：
+    /// `alpha = cwnd_total * max(cwnd_i / rtt_i^2) / (sum(cwnd_i / rtt_i))^2`，
+    /// 只统计已有 RTT 样本的子流；都没有样本时返回 None。
+    fn lia_coupling(&self, parent: TcpConnId) -> Option<(f64, u64)> {
+        let mp = self.mptcp.get(&parent)?;
+        let mut total = 0u64;
+        let mut best = 0.0f64;
+        let mut sum = 0.0f64;
+        for sub in mp.subflows.iter().filter_map(|id| self.conns.get(id)) {
+            total = total.saturating_add(sub.cc.cwnd_bytes());
+            let Some(srtt) = sub.srtt() else {
+                continue;
+            };
+            let rtt = srtt.0.max(1) as f64;
+            let cwnd = sub.cc.cwnd_bytes() as f64;
+            best = best.max(cwnd / (rtt * rtt));
+            sum += cwnd / rtt;
+        }
+        (sum > 0.0).then(|| (total as f64 * best / (sum * sum), total))
+    }
+
+    pub(crate) fn send_data_if_possible(&mut self, id: TcpConnId, cx: &mut SimContext<'_>) {
         let Some(conn) = self.conns.get_mut(&id) else {
             return;
         };
@@ -424,71 +769,88 @@ impl TcpStack {
 
         if conn.sender_state != SenderState::Established {
             if conn.syn_sent_at.is_none() {
-                let mut pkt = conn.make_data_packet(net);
+                let mut pkt = conn.make_data_packet(cx.net);
                 pkt.size_bytes = conn.cfg.ack_bytes;
                 pkt.transport = Transport::Tcp(TcpSegment::Syn);
-                conn.syn_sent_at = Some(sim.now());
+                conn.syn_sent_at = Some(cx.now());
                 conn.syn_retries = conn.syn_retries.saturating_add(1);
                 if conn.start_at.is_none() {
-                    conn.start_at = Some(sim.now());
+                    conn.start_at = Some(cx.now());
+                    cx.net.flow_stats_mut(conn.id).start = conn.start_at;
                 }
-                net.forward_from(conn.src, pkt, sim);
+                cx.forward_from(conn.src, pkt);
             }
-            conn.ensure_rto(sim);
+            conn.ensure_rto(cx.sim);
             return;
         }
 
         if conn.start_at.is_none() {
-            conn.start_at = Some(sim.now());
+            conn.start_at = Some(cx.now());
+            cx.net.flow_stats_mut(conn.id).start = conn.start_at;
         }
 
         // 发送窗口：inflight bytes < cwnd
         let inflight_bytes = conn.inflight_bytes();
         let mut avail = conn.effective_cwnd().saturating_sub(inflight_bytes);
 
-        while avail > 0 && conn.next_seq < conn.total_bytes {
-            let remain = conn.total_bytes - conn.next_seq;
-            let len = (conn.cfg.mss as u64).min(remain).min(avail) as u32;
-            if len == 0 {
+        // MPTCP 子流：按窗口余量从连接级序号空间领取尚未分配的数据（按整 MSS 领取，
+        // 避免子流的数据末尾总落在窗口边缘而被切成小段）
+        if let Some(mp) = conn.mptcp.and_then(|parent| self.mptcp.get_mut(&parent)) {
+            let mss = conn.cfg.mss as u64;
+            let unsent = conn.core.total_bytes().saturating_sub(conn.core.next_seq());
+            let grant = avail
+                .saturating_sub(unsent)
+                .div_ceil(mss)
+                .saturating_mul(mss)
+                .min(mp.total_bytes.saturating_sub(mp.data_next));
+            mp.data_next = mp.data_next.saturating_add(grant);
+            conn.total_bytes = conn.total_bytes.saturating_add(grant);
+            conn.core.add_bytes(grant);
+        }
+
+        while avail > 0 && conn.core.next_seq() < conn.core.total_bytes() {
+            if conn.paces() && cx.now() < conn.pace_next {
+                // 还没到 pacing 时刻：排一个 TcpPacedSend，到时再继续发
+                if conn.pace_deadline.is_none() {
+                    conn.pace_deadline = Some(conn.pace_next);
+                    cx.schedule(conn.pace_next, TcpPacedSend { conn_id: conn.id });
+                }
                 break;
             }
-            let seq = conn.next_seq;
-            conn.next_seq = conn.next_seq.saturating_add(len as u64);
-            avail = avail.saturating_sub(len as u64);
+            let Some((seq, len, retrans)) = conn.core.send_next(conn.cfg.mss, &mut avail, cx.now())
+            else {
+                break;
+            };
+            if let Some(gap) = conn.pacing_gap(len) {
+                conn.pace_next = SimTime(cx.now().0.saturating_add(gap.0));
+            }
 
             // 构造 data 包
-            let mut pkt = conn.make_data_packet(net);
-            pkt.size_bytes = conn.cfg.mss; // 包大小按 mss 计（简化）
+            let mut pkt = conn.make_data_packet(cx.net);
+            pkt.size_bytes = conn.data_packet_bytes(seq, len);
             pkt.transport = Transport::Tcp(TcpSegment::Data { seq, len });
 
-            let retrans = conn
-                .rto_retrans_end
-                .is_some_and(|watermark| seq < watermark);
-            net.viz_tcp_send_data(sim.now().0, conn.id, seq, len, retrans);
-
-            conn.inflight.insert(
-                seq,
-                SentSeg {
-                    len,
-                    sent_at: sim.now(),
-                    retransmitted: retrans,
-                },
-            );
+            cx.net
+                .viz_tcp_send_data(cx.now().0, conn.id, seq, len, retrans);
+            if retrans {
+                cx.net.flow_stats_mut(conn.id).retransmits += 1;
+            }
 
-            net.forward_from(conn.src, pkt, sim);
+            cx.forward_from(conn.src, pkt);
         }
-        conn.ensure_rto(sim);
+        conn.ensure_rto(cx.sim);
+        conn.arm_tlp(avail == 0, cx.sim);
     }
 
-    fn send_ack(&mut self, id: TcpConnId, ack: u64, sim: &mut Simulator, net: &mut dyn NetApi) {
+    fn send_ack(&mut self, id: TcpConnId, ack: u64, cx: &mut SimContext<'_>) {
         let Some(conn) = self.conns.get(&id) else {
             return;
         };
-        let mut pkt = conn.make_ack_packet(net);
+        let mut pkt = conn.make_ack_packet(cx.net);
         pkt.size_bytes = conn.cfg.ack_bytes;
         pkt.transport = Transport::Tcp(TcpSegment::Ack { ack });
-        net.viz_tcp_send_ack(sim.now().0, conn.id, ack, false);
-        net.forward_from(conn.dst, pkt, sim);
+        cx.net.viz_tcp_send_ack(cx.now().0, conn.id, ack, false);
+        cx.forward_from(conn.dst, pkt);
     }
 
     pub fn on_tcp_segment(
@@ -496,8 +858,7 @@ impl TcpStack {
         conn_id: TcpConnId,
         at: NodeId,
         seg: TcpSegment,
-        sim: &mut Simulator,
-        net: &mut dyn NetApi,
+        cx: &mut SimContext<'_>,
     ) {
         match seg {
             TcpSegment::Syn => {
@@ -510,10 +871,10 @@ impl TcpStack {
                 if conn.receiver_state == ReceiverState::Idle {
                     conn.receiver_state = ReceiverState::SynReceived;
                 }
-                let mut pkt = conn.make_ack_packet(net);
+                let mut pkt = conn.make_ack_packet(cx.net);
                 pkt.size_bytes = conn.cfg.ack_bytes;
                 pkt.transport = Transport::Tcp(TcpSegment::SynAck);
-                net.forward_from(conn.dst, pkt, sim);
+                cx.forward_from(conn.dst, pkt);
             }
             TcpSegment::SynAck => {
                 let start_data = {
@@ -528,15 +889,15 @@ impl TcpStack {
                     conn.syn_retries = 0;
                     conn.stop_rto();
                     if conn.cfg.handshake {
-                        let mut pkt = conn.make_data_packet(net);
+                        let mut pkt = conn.make_data_packet(cx.net);
                         pkt.size_bytes = conn.cfg.ack_bytes;
                         pkt.transport = Transport::Tcp(TcpSegment::HandshakeAck);
-                        net.forward_from(conn.src, pkt, sim);
+                        cx.forward_from(conn.src, pkt);
                     }
                     true
                 };
                 if start_data {
-                    self.send_data_if_possible(conn_id, sim, net);
+                    self.send_data_if_possible(conn_id, cx);
                 }
             }
             TcpSegment::HandshakeAck => {
@@ -559,12 +920,46 @@ impl TcpStack {
                 if conn.cfg.handshake && conn.receiver_state == ReceiverState::Idle {
                     return;
                 }
+                let in_order = seq == conn.rcv_nxt && conn.out_of_order.is_empty();
+                let prev = conn.rcv_nxt;
+                cx.net.flow_stats_mut(conn_id).note_data_arrival(seq, prev);
                 let ack = conn.recv_data(seq, len);
+                if ack > prev {
+                    cx.net.flow_stats_mut(conn_id).bytes_delivered += ack - prev;
+                }
+                if let Some(delay) = conn.cfg.delayed_ack
+                    && in_order
+                {
+                    // 按序数据：攒够两个 MSS 才发 ACK，否则等延迟定时器
+                    conn.ack_pending_bytes = conn.ack_pending_bytes.saturating_add(ack - prev);
+                    if conn.ack_pending_bytes < 2 * conn.cfg.mss as u64 {
+                        if conn.delack_deadline.is_none() {
+                            let deadline = SimTime(cx.now().0.saturating_add(delay.0));
+                            conn.delack_deadline = Some(deadline);
+                            conn.delack_token = conn.delack_token.wrapping_add(1);
+                            cx.sim.schedule(
+                                deadline,
+                                TcpDelayedAck {
+                                    conn_id,
+                                    token: conn.delack_token,
+                                },
+                            );
+                        }
+                        return;
+                    }
+                }
+                conn.ack_pending_bytes = 0;
+                conn.delack_deadline = None;
                 let _ = conn;
                 // 无论是否乱序，都发累计 ACK（dupACK 体现为 ack 不前进）
-                self.send_ack(conn_id, ack, sim, net);
+                self.send_ack(conn_id, ack, cx);
             }
             TcpSegment::Ack { ack } => {
+                let coupling = self
+                    .conns
+                    .get(&conn_id)
+                    .and_then(|conn| conn.mptcp)
+                    .and_then(|parent| self.lia_coupling(parent));
                 let Some(conn) = self.conns.get_mut(&conn_id) else {
                     return;
                 };
@@ -573,148 +968,122 @@ impl TcpStack {
                 }
 
                 // 记录“收到 ACK”这一事实（无论新 ACK 或 dupACK）
-                net.viz_tcp_recv_ack(sim.now().0, conn.id, ack, false);
-
-                if ack > conn.last_acked {
-                    let in_fast_recovery = conn.in_fast_recovery;
-                    let recover_point = conn.recover;
-                    let was_slow_start = !in_fast_recovery && conn.cwnd_bytes < conn.ssthresh_bytes;
-                    let now = sim.now();
-                    let mut rtt_sample = None;
-                    for (&s, sent) in conn.inflight.iter() {
-                        let end = s.saturating_add(sent.len as u64);
-                        if end <= ack {
-                            if !sent.retransmitted {
-                                let delta = now.0.saturating_sub(sent.sent_at.0);
-                                rtt_sample = Some(SimTime(delta));
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    if let Some(sample) = rtt_sample {
-                        conn.update_rto_with_sample(sample);
-                    }
-
-                    conn.dup_acks = 0;
-                    let newly_acked = ack - conn.last_acked;
-
-                    let mut to_remove = Vec::new();
-                    for (&s, sent) in conn.inflight.iter() {
-                        let end = s.saturating_add(sent.len as u64);
-                        if end <= ack {
-                            to_remove.push(s);
-                        } else {
-                            break;
-                        }
-                    }
-                    for s in to_remove {
-                        conn.inflight.remove(&s);
-                    }
-
-                    let prev_acked = conn.last_acked;
-                    conn.last_acked = ack;
+                cx.net.viz_tcp_recv_ack(cx.now().0, conn.id, ack, false);
 
+                if ack > conn.core.last_acked() {
+                    let recovery = match (conn.in_fast_recovery, ack >= conn.recover) {
+                        (false, _) => None,
+                        (true, true) => Some(RecoveryAck::Exit),
+                        (true, false) => Some(RecoveryAck::Partial),
+                    };
+                    let now = cx.now();
                     let mss = conn.cfg.mss as u64;
-                    if conn.in_fast_recovery {
-                        if ack >= conn.recover {
-                            let flightsize = conn.next_seq.saturating_sub(ack);
-                            conn.cwnd_bytes =
-                                conn.ssthresh_bytes.min(flightsize.saturating_add(mss));
-                            conn.in_fast_recovery = false;
-                        } else {
-                            let new_data = ack.saturating_sub(prev_acked);
-                            if new_data < conn.cwnd_bytes {
-                                conn.cwnd_bytes = conn.cwnd_bytes.saturating_sub(new_data);
-                            } else {
-                                conn.cwnd_bytes = 0;
-                            }
-                            conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(mss);
-                            if let Some(seq0) = conn.earliest_unacked_seq() {
-                                let len = conn
-                                    .inflight
-                                    .get(&seq0)
-                                    .map(|s| s.len)
-                                    .unwrap_or(conn.cfg.mss);
-                                let mut pkt = conn.make_data_packet(net);
-                                pkt.size_bytes = conn.cfg.mss;
+                    // 每个被累计确认、且未重传过的段都贡献一个样本（Karn：跳过重传段）
+                    let (min_rto, max_rto) = (conn.cfg.min_rto, conn.cfg.max_rto);
+                    let mut ack_ev = conn.core.on_new_ack(ack, mss, now, min_rto, max_rto);
+                    ack_ev.recovery = recovery;
+                    ack_ev.coupling = coupling;
+                    let newly_acked = ack_ev.newly_acked;
+                    conn.tlp_outstanding = false;
+
+                    // 拥塞控制：慢启动 / 拥塞避免，或快速恢复中的窗口收缩
+                    let change = conn.cc.on_ack(&ack_ev);
+                    match recovery {
+                        Some(RecoveryAck::Exit) => conn.in_fast_recovery = false,
+                        Some(RecoveryAck::Partial) => {
+                            if let Some((seq0, len)) = conn.core.retransmit_earliest() {
+                                let mut pkt = conn.make_data_packet(cx.net);
+                                pkt.size_bytes = conn.data_packet_bytes(seq0, len);
                                 pkt.transport = Transport::Tcp(TcpSegment::Data { seq: seq0, len });
-                                net.viz_tcp_send_data(sim.now().0, conn.id, seq0, len, true);
-                                net.forward_from(conn.src, pkt, sim);
-                                if let Some(sent) = conn.inflight.get_mut(&seq0) {
-                                    sent.sent_at = sim.now();
-                                    sent.retransmitted = true;
-                                }
+                                cx.net
+                                    .viz_tcp_send_data(cx.now().0, conn.id, seq0, len, true);
+                                cx.net.flow_stats_mut(conn.id).retransmits += 1;
+                                cx.forward_from(conn.src, pkt);
                             }
                         }
-                    } else {
-                        // 拥塞控制：慢启动 / 拥塞避免（极简）
-                        if conn.cwnd_bytes < conn.ssthresh_bytes {
-                            let capped = newly_acked.min(mss);
-                            let room = conn.ssthresh_bytes.saturating_sub(conn.cwnd_bytes);
-                            let inc = capped.min(room);
-                            conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(inc);
-                        } else {
-                            // AIMD：每个 ACK 让 cwnd 以 mss^2/cwnd 增长（至少 +1）
-                            let inc = (mss.saturating_mul(mss) / conn.cwnd_bytes).max(1);
-                            conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(inc);
-                        }
+                        None => {}
                     }
 
-                    let reason = if in_fast_recovery {
-                        if ack >= recover_point {
-                            VizCwndReason::FastRecoveryExit
-                        } else {
-                            VizCwndReason::FastRecoveryPartialAck
-                        }
-                    } else if was_slow_start {
-                        VizCwndReason::AckSlowStart
-                    } else {
-                        VizCwndReason::AckCongestionAvoidance
-                    };
                     // 记录 cwnd 状态变化
-                    net.viz_dctcp_cwnd(
-                        sim.now().0,
+                    conn.record_cwnd(now);
+                    cx.net.viz_dctcp_cwnd(
+                        cx.now().0,
                         conn.id,
-                        conn.cwnd_bytes,
-                        conn.ssthresh_bytes,
+                        conn.cc.cwnd_bytes(),
+                        conn.cc.ssthresh_bytes(),
                         conn.inflight_bytes(),
-                        0.0,
-                        reason,
+                        conn.cc.alpha(),
+                        change.reason,
                         Some(newly_acked),
                         None,
                         None,
                     );
 
                     // 移除已确认段
-                    // 完成判定：所有数据都被累计确认
-                    if conn.last_acked >= conn.total_bytes && conn.done_at.is_none() {
-                        conn.done_at = Some(sim.now());
-                        conn.stop_rto();
-                        let done_cb = self.done_callbacks.remove(&conn_id);
+                    // 完成判定：所有数据都被累计确认（MPTCP 子流看连接级的确认量）
+                    let mp = conn.mptcp.and_then(|parent| self.mptcp.get_mut(&parent));
+                    let finished = match mp {
+                        Some(mp) => {
+                            mp.data_acked = mp.data_acked.saturating_add(newly_acked);
+                            mp.data_acked >= mp.total_bytes
+                        }
+                        None => conn.core.last_acked() >= conn.total_bytes,
+                    };
+                    if finished && conn.done_at.is_none() {
+                        let done_id = conn.mptcp.unwrap_or(conn_id);
+                        let notify_delay = conn.cfg.done_notify_delay;
+                        let subflows = self
+                            .mptcp
+                            .get(&done_id)
+                            .map_or_else(|| vec![conn_id], |mp| mp.subflows.clone());
+                        for id in subflows {
+                            let Some(conn) = self.conns.get_mut(&id) else {
+                                continue;
+                            };
+                            conn.done_at = Some(cx.now());
+                            cx.net.flow_stats_mut(id).completion = conn.done_at;
+                            conn.stop_rto();
+                            conn.tlp_deadline = None;
+                        }
+                        cx.net.release_flow_slot(done_id, cx.sim);
+                        cx.net.flow_completed(done_id, cx.sim);
+                        let done_cb = self.done_callbacks.remove(&done_id);
                         if let Some(cb) = done_cb {
-                            cb(conn_id, sim.now(), sim);
+                            if notify_delay == SimTime::ZERO {
+                                cb(done_id, cx.now(), cx.sim);
+                            } else {
+                                let at = SimTime(cx.now().0.saturating_add(notify_delay.0));
+                                cx.schedule(
+                                    at,
+                                    TcpDoneNotify {
+                                        conn_id: done_id,
+                                        cb,
+                                    },
+                                );
+                            }
                         }
                         return;
                     }
-                    conn.restart_rto(sim);
+                    conn.restart_rto(cx.sim);
 
                     // 继续发送
                     let id = conn.id;
                     let _ = conn;
-                    self.send_data_if_possible(id, sim, net);
-                } else if ack == conn.last_acked {
+                    self.send_data_if_possible(id, cx);
+                } else if ack == conn.core.last_acked() {
                     // dupACK
+                    let mss = conn.cfg.mss as u64;
                     if conn.in_fast_recovery {
-                        conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(conn.cfg.mss as u64);
+                        conn.cc.on_dup_ack(mss);
                         // 记录快速恢复中 dupACK 增加 cwnd 后的状态
-                        net.viz_dctcp_cwnd(
-                            sim.now().0,
+                        conn.record_cwnd(cx.now());
+                        cx.net.viz_dctcp_cwnd(
+                            cx.now().0,
                             conn.id,
-                            conn.cwnd_bytes,
-                            conn.ssthresh_bytes,
+                            conn.cc.cwnd_bytes(),
+                            conn.cc.ssthresh_bytes(),
                             conn.inflight_bytes(),
-                            0.0,
+                            conn.cc.alpha(),
                             VizCwndReason::FastRecoveryDupAck,
                             None,
                             None,
@@ -722,45 +1091,36 @@ impl TcpStack {
                         );
                         let id = conn.id;
                         let _ = conn;
-                        self.send_data_if_possible(id, sim, net);
+                        self.send_data_if_possible(id, cx);
                         return;
                     }
 
-                    conn.dup_acks = conn.dup_acks.saturating_add(1);
-                    let dup = conn.dup_acks;
-                    let mss = conn.cfg.mss as u64;
+                    let dup = conn.core.on_dup_ack();
                     if dup == 3 {
-                        if conn.last_acked < conn.recover {
+                        if conn.core.last_acked() < conn.recover {
                             return;
                         }
-                        conn.ssthresh_bytes = (conn.cwnd_bytes / 2).max(2 * mss);
-                        if let Some(seq0) = conn.earliest_unacked_seq() {
-                            let len = conn
-                                .inflight
-                                .get(&seq0)
-                                .map(|s| s.len)
-                                .unwrap_or(conn.cfg.mss);
-                            let mut pkt = conn.make_data_packet(net);
-                            pkt.size_bytes = conn.cfg.mss;
+                        conn.cc.on_loss(mss);
+                        if let Some((seq0, len)) = conn.core.retransmit_earliest() {
+                            let mut pkt = conn.make_data_packet(cx.net);
+                            pkt.size_bytes = conn.data_packet_bytes(seq0, len);
                             pkt.transport = Transport::Tcp(TcpSegment::Data { seq: seq0, len });
-                            net.viz_tcp_send_data(sim.now().0, conn.id, seq0, len, true);
-                            net.forward_from(conn.src, pkt, sim);
-                            if let Some(sent) = conn.inflight.get_mut(&seq0) {
-                                sent.sent_at = sim.now();
-                                sent.retransmitted = true;
-                            }
+                            cx.net
+                                .viz_tcp_send_data(cx.now().0, conn.id, seq0, len, true);
+                            cx.net.flow_stats_mut(conn.id).retransmits += 1;
+                            cx.forward_from(conn.src, pkt);
                         }
-                        conn.cwnd_bytes = conn.ssthresh_bytes.saturating_add(3 * mss);
                         conn.in_fast_recovery = true;
-                        conn.recover = conn.next_seq;
+                        conn.recover = conn.core.next_seq();
                         // 记录 3 dupACK 触发快速恢复时的 cwnd 状态
-                        net.viz_dctcp_cwnd(
-                            sim.now().0,
+                        conn.record_cwnd(cx.now());
+                        cx.net.viz_dctcp_cwnd(
+                            cx.now().0,
                             conn.id,
-                            conn.cwnd_bytes,
-                            conn.ssthresh_bytes,
+                            conn.cc.cwnd_bytes(),
+                            conn.cc.ssthresh_bytes(),
                             conn.inflight_bytes(),
-                            0.0,
+                            conn.cc.alpha(),
                             VizCwndReason::FastRecoveryEnter,
                             None,
                             Some(dup),
@@ -768,12 +1128,12 @@ impl TcpStack {
                         );
                         let id = conn.id;
                         let _ = conn;
-                        self.send_data_if_possible(id, sim, net);
+                        self.send_data_if_possible(id, cx);
                     } else if dup > 3 {
-                        conn.cwnd_bytes = conn.cwnd_bytes.saturating_add(mss);
+                        conn.cc.on_dup_ack(mss);
                         let id = conn.id;
                         let _ = conn;
-                        self.send_data_if_possible(id, sim, net);
+                        self.send_data_if_possible(id, cx);
                     }
                 }
             }
@@ -791,13 +1151,20 @@ impl Event for TcpStart {
     fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
         let TcpStart { conn } = *self;
         let id = conn.id;
-        let init_cwnd = conn.cwnd_bytes;
-        let init_ssthresh = conn.ssthresh_bytes;
-        with_tcp_stack(world, move |net, tcp| {
-            tcp.insert(conn);
+        let init_cwnd = conn.cc.cwnd_bytes();
+        let init_ssthresh = conn.cc.ssthresh_bytes();
+        with_tcp_stack(sim, world, move |cx, tcp| {
+            let Some(conn) = cx.net.admit_tcp_conn(conn) else {
+                return;
+            };
+            let ids = tcp.insert_subflows(conn, cx.net);
+            if ids.is_empty() {
+                cx.net.release_flow_slot(id, cx.sim);
+                return;
+            }
             // 记录初始 cwnd/ssthresh 状态
-            net.viz_dctcp_cwnd(
-                sim.now().0,
+            cx.net.viz_dctcp_cwnd(
+                cx.now().0,
                 id,
                 init_cwnd,
                 init_ssthresh,
@@ -808,11 +1175,29 @@ impl Event for TcpStart {
                 None,
                 None,
             );
-            tcp.send_data_if_possible(id, sim, net);
+            for id in ids {
+                if let Some(conn) = tcp.get_mut(id) {
+                    conn.record_cwnd(cx.now());
+                }
+                tcp.send_data_if_possible(id, cx);
+            }
         });
     }
 }
 
+/// 延迟触发的 TCP 完成通知（见 `TcpConfig::done_notify_delay`）
+pub struct TcpDoneNotify {
+    pub conn_id: TcpConnId,
+    pub cb: TcpDoneCallback,
+}
+
+impl Event for TcpDoneNotify {
+    fn execute(self: Box<Self>, sim: &mut Simulator, _world: &mut dyn World) {
+        let TcpDoneNotify { conn_id, cb } = *self;
+        cb(conn_id, sim.now(), sim);
+    }
+}
+
 /// TCP RTO 事件：若该 seq 仍是最早未确认段，则触发超时重传
 #[derive(Debug)]
 pub struct TcpRto {
@@ -823,73 +1208,73 @@ pub struct TcpRto {
 impl Event for TcpRto {
     fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
         let TcpRto { conn_id, token } = *self;
-        with_tcp_stack(world, |net, tcp| {
+        with_tcp_stack(sim, world, |cx, tcp| {
             let Some(conn) = tcp.get_mut(conn_id) else {
                 return;
             };
             if conn.done_at.is_some() {
                 return;
             }
-            if conn.rto_deadline.is_none() || conn.rto_token != token {
+            if conn.rto_deadline.is_none() || conn.core.rto_token() != token {
                 return;
             }
             let deadline = conn.rto_deadline.unwrap();
-            if sim.now() < deadline {
+            if cx.now() < deadline {
                 return;
             }
             conn.rto_deadline = None;
+            conn.tlp_deadline = None;
+            conn.tlp_outstanding = false;
+
+            // 被注入故障的流不再重传（见 `Network::fail_flow_at`）
+            if cx.net.flow_failed(conn_id, cx.now()) {
+                cx.net.release_flow_slot(conn_id, cx.sim);
+                return;
+            }
 
             if conn.sender_state != SenderState::Established {
                 // SYN 超时重传
                 if conn.syn_sent_at.is_some() {
-                    let rto = conn.rto.0.saturating_mul(2);
-                    let rto = rto.max(conn.cfg.min_rto.0).min(conn.cfg.max_rto.0);
-                    conn.rto = SimTime(rto);
-                    let mut pkt = conn.make_data_packet(net);
+                    conn.core.backoff_rto(conn.cfg.min_rto, conn.cfg.max_rto);
+                    let mut pkt = conn.make_data_packet(cx.net);
                     pkt.size_bytes = conn.cfg.ack_bytes;
                     pkt.transport = Transport::Tcp(TcpSegment::Syn);
-                    conn.syn_sent_at = Some(sim.now());
+                    conn.syn_sent_at = Some(cx.now());
                     conn.syn_retries = conn.syn_retries.saturating_add(1);
-                    net.forward_from(conn.src, pkt, sim);
-                    conn.schedule_rto(sim);
+                    cx.forward_from(conn.src, pkt);
+                    conn.schedule_rto(cx.sim);
                 }
                 return;
             }
 
-            let Some(seq0) = conn.earliest_unacked_seq() else {
+            let Some(seq0) = conn.core.earliest_unacked_seq() else {
                 return;
             };
 
             // 先记录 RTO 事件（即将触发重传）
-            net.viz_tcp_rto(sim.now().0, conn_id, seq0);
+            cx.net.viz_tcp_rto(cx.now().0, conn_id, seq0);
 
+            let mss = conn.cfg.mss as u64;
             if conn.in_fast_recovery {
-                let flightsize = conn.next_seq.saturating_sub(conn.last_acked);
-                conn.cwnd_bytes = conn
-                    .ssthresh_bytes
-                    .min(flightsize.saturating_add(conn.cfg.mss as u64));
+                let flight = conn.core.next_seq().saturating_sub(conn.core.last_acked());
+                conn.cc.on_recovery_exit(flight, mss);
             }
 
             // 超时：回到慢启动
-            let mss = conn.cfg.mss as u64;
-            conn.ssthresh_bytes = (conn.cwnd_bytes / 2).max(2 * mss);
-            conn.cwnd_bytes = mss;
-            conn.dup_acks = 0;
+            conn.cc.on_rto(mss);
             conn.in_fast_recovery = false;
             // Allow future fast retransmits; RTO recovery resets the flight anyway.
-            conn.recover = conn.last_acked;
-            let rto = conn.rto.0.saturating_mul(2);
-            let rto = rto.max(conn.cfg.min_rto.0).min(conn.cfg.max_rto.0);
-            conn.rto = SimTime(rto);
+            conn.recover = conn.core.last_acked();
 
             // 记录 RTO 触发后的 cwnd 状态
-            net.viz_dctcp_cwnd(
-                sim.now().0,
+            conn.record_cwnd(cx.now());
+            cx.net.viz_dctcp_cwnd(
+                cx.now().0,
                 conn_id,
-                conn.cwnd_bytes,
-                conn.ssthresh_bytes,
+                conn.cc.cwnd_bytes(),
+                conn.cc.ssthresh_bytes(),
                 conn.inflight_bytes(),
-                0.0,
+                conn.cc.alpha(),
                 VizCwndReason::RtoTimeout,
                 None,
                 None,
@@ -898,13 +1283,95 @@ impl Event for TcpRto {
 
             // RTO typically indicates severe loss; restarting from the last ACKed byte
             // avoids "one-segment-per-RTO" tail loss recovery.
-            let watermark = conn.next_seq;
-            conn.rto_retrans_end = Some(watermark);
-            conn.next_seq = conn.last_acked;
-            conn.inflight.clear();
+            conn.core.on_rto(conn.cfg.min_rto, conn.cfg.max_rto);
             let id = conn.id;
             let _ = conn;
-            tcp.send_data_if_possible(id, sim, net);
+            tcp.send_data_if_possible(id, cx);
+        });
+    }
+}
+
+/// TCP 尾部丢包探测事件（见 `TcpConfig::enable_tlp`）：重传最后一个未确认段。
+///
+/// 探测本身不改变 cwnd；若它暴露出丢包，后续由快速重传/RTO 照常处理。
+#[derive(Debug)]
+pub struct TcpTlp {
+    pub conn_id: TcpConnId,
+    pub token: u64,
+}
+
+impl Event for TcpTlp {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let TcpTlp { conn_id, token } = *self;
+        with_tcp_stack(sim, world, |cx, tcp| {
+            let Some(conn) = tcp.get_mut(conn_id) else {
+                return;
+            };
+            if conn.done_at.is_some() || conn.tlp_token != token {
+                return;
+            }
+            if conn.tlp_deadline.take().is_none() || conn.in_fast_recovery {
+                return;
+            }
+            let Some((seq, len)) = conn.core.retransmit_latest() else {
+                return;
+            };
+            let mut pkt = conn.make_data_packet(cx.net);
+            pkt.size_bytes = conn.data_packet_bytes(seq, len);
+            pkt.transport = Transport::Tcp(TcpSegment::Data { seq, len });
+            cx.net
+                .viz_tcp_send_data(cx.now().0, conn.id, seq, len, true);
+            cx.net.flow_stats_mut(conn.id).retransmits += 1;
+            cx.forward_from(conn.src, pkt);
+            conn.tlp_outstanding = true;
+            conn.tlp_probes = conn.tlp_probes.saturating_add(1);
+            conn.restart_rto(cx.sim);
+        });
+    }
+}
+
+/// TCP pacing 定时器（见 `TcpConfig::pacing`）：到点后继续发送窗口内的下一段。
+#[derive(Debug)]
+pub struct TcpPacedSend {
+    pub conn_id: TcpConnId,
+}
+
+impl Event for TcpPacedSend {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let TcpPacedSend { conn_id } = *self;
+        with_tcp_stack(sim, world, |cx, tcp| {
+            let Some(conn) = tcp.get_mut(conn_id) else {
+                return;
+            };
+            conn.pace_deadline = None;
+            tcp.send_data_if_possible(conn_id, cx);
+        });
+    }
+}
+
+/// TCP 延迟 ACK 定时器（见 `TcpConfig::delayed_ack`）：到期时确认已攒下的按序数据。
+#[derive(Debug)]
+pub struct TcpDelayedAck {
+    pub conn_id: TcpConnId,
+    pub token: u64,
+}
+
+impl Event for TcpDelayedAck {
+    fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World) {
+        let TcpDelayedAck { conn_id, token } = *self;
+        with_tcp_stack(sim, world, |cx, tcp| {
+            let Some(conn) = tcp.get_mut(conn_id) else {
+                return;
+            };
+            if conn.delack_token != token || conn.delack_deadline.take().is_none() {
+                return;
+            }
+            if conn.ack_pending_bytes == 0 {
+                return;
+            }
+            conn.ack_pending_bytes = 0;
+            let ack = conn.rcv_nxt;
+            tcp.send_ack(conn_id, ack, cx);
         });
     }
 }
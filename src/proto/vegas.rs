@@ -0,0 +1,108 @@
+//! Vegas 拥塞控制（`CcAlgo::Vegas`）
+//!
+//! 用 base RTT（观测到的最小 RTT）估计瓶颈处排队的包数：每个 RTT 轮次结束时
+//! 少于 `alpha` 个则 +1 MSS，多于 `beta` 个则 -1 MSS。慢启动与丢包/超时处理同 Reno。
+
+use crate::proto::flow::{AckEvent, CongestionControl, CwndChange};
+use crate::proto::reno::{RenoCc, growth_change};
+use crate::sim::SimTime;
+
+#[derive(Debug, Clone)]
+pub struct VegasCc {
+    reno: RenoCc,
+    alpha: u32,
+    beta: u32,
+    /// 观测到的最小 RTT
+    base_rtt: Option<SimTime>,
+    /// 当前 RTT 轮次内的最小 RTT 样本
+    round_min_rtt: Option<SimTime>,
+    /// 当前 RTT 轮次结束点：ACK 越过此 seq 即开始下一轮
+    round_end_seq: u64,
+}
+
+impl VegasCc {
+    pub fn new(alpha: u32, beta: u32, init_cwnd_bytes: u64, init_ssthresh_bytes: u64) -> Self {
+        Self {
+            reno: RenoCc::new(init_cwnd_bytes, init_ssthresh_bytes),
+            alpha,
+            beta,
+            base_rtt: None,
+            round_min_rtt: None,
+            round_end_seq: 0,
+        }
+    }
+}
+
+impl CongestionControl for VegasCc {
+    fn cwnd_bytes(&self) -> u64 {
+        self.reno.cwnd_bytes()
+    }
+
+    fn ssthresh_bytes(&self) -> u64 {
+        self.reno.ssthresh_bytes()
+    }
+
+    /// 慢启动照常按 ACK 增长；每个 RTT 轮次结束时，
+    /// 用 `cwnd * (rtt - base_rtt) / rtt` 估计排队包数并调整窗口。
+    fn on_ack(&mut self, ack: &AckEvent) -> CwndChange {
+        // 快速恢复期间的 RTT 样本同样计入
+        if let Some(rtt) = ack.rtt {
+            self.base_rtt = Some(self.base_rtt.map_or(rtt, |m| m.min(rtt)));
+            self.round_min_rtt = Some(self.round_min_rtt.map_or(rtt, |m| m.min(rtt)));
+        }
+        if let Some(recovery) = ack.recovery {
+            return self.reno.on_recovery_ack(ack, recovery);
+        }
+
+        let mss = ack.mss;
+        let in_slow_start = self.reno.in_slow_start();
+        let change = growth_change(in_slow_start);
+        if in_slow_start {
+            self.reno.grow(ack.newly_acked, mss);
+        }
+        if ack.last_acked < self.round_end_seq {
+            return change;
+        }
+        self.round_end_seq = ack.next_seq;
+        let (Some(base), Some(rtt)) = (self.base_rtt, self.round_min_rtt.take()) else {
+            return change;
+        };
+        let rtt_ns = rtt.0.max(1);
+        let cwnd = self.reno.cwnd_bytes();
+        // 若无排队，窗口应为 cwnd * base / rtt；差值即为瓶颈处排队的数据量
+        let target = cwnd.saturating_mul(base.0) / rtt_ns;
+        let queued_pkts = cwnd.saturating_sub(target) / mss;
+        if in_slow_start {
+            if queued_pkts > self.alpha as u64 {
+                // 排队已经出现：退出慢启动，把窗口收回到目标附近
+                let cwnd = cwnd.min(target.saturating_add(mss));
+                self.reno.set_cwnd_bytes(cwnd);
+                self.reno.set_ssthresh_bytes(cwnd);
+            }
+        } else if queued_pkts > self.beta as u64 {
+            let cwnd = cwnd.saturating_sub(mss).max(2 * mss);
+            self.reno.set_cwnd_bytes(cwnd);
+            // 保持在拥塞避免阶段，避免慢启动把窗口又涨回去
+            self.reno.set_ssthresh_bytes(cwnd);
+        } else if queued_pkts < self.alpha as u64 {
+            self.reno.set_cwnd_bytes(cwnd.saturating_add(mss));
+        }
+        change
+    }
+
+    fn on_loss(&mut self, mss: u64) {
+        self.reno.on_loss(mss);
+    }
+
+    fn on_dup_ack(&mut self, mss: u64) {
+        self.reno.on_dup_ack(mss);
+    }
+
+    fn on_recovery_exit(&mut self, flight_bytes: u64, mss: u64) {
+        self.reno.on_recovery_exit(flight_bytes, mss);
+    }
+
+    fn on_rto(&mut self, mss: u64) {
+        self.reno.on_rto(mss);
+    }
+}
@@ -0,0 +1,208 @@
+//! CoDel (Controlled Delay) queue, following RFC 8289.
+//!
+//! Each packet is stamped on arrival; at dequeue time its sojourn time is
+//! compared with `target`. Once the sojourn has stayed above `target` for a
+//! whole `interval`, CoDel enters the dropping state and drops head packets at
+//! `interval / sqrt(count)` spacing until the sojourn falls back below
+//! `target` (or the queue holds at most one MTU). The hard capacity is still
+//! drop-tail.
+//!
+//! Packets dropped at dequeue time are handed back through `take_dropped` so
+//! the network can account for them.
+
+use std::collections::VecDeque;
+
+use crate::net::Packet;
+use crate::sim::SimTime;
+
+use super::{DEFAULT_PKT_BYTES, PacketQueue};
+
+/// CoDel control parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoDelParams {
+    /// Acceptable standing queue delay.
+    pub target: SimTime,
+    /// Window the sojourn time must stay above `target` before dropping starts.
+    pub interval: SimTime,
+}
+
+impl Default for CoDelParams {
+    fn default() -> Self {
+        Self {
+            target: SimTime::from_millis(5),
+            interval: SimTime::from_millis(100),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CoDelQueue {
+    max_bytes: u64,
+    cur_bytes: u64,
+    q: VecDeque<(SimTime, Packet)>,
+    params: CoDelParams,
+    /// Latest time seen by `enqueue_at` / `dequeue_at`; stamps plain `enqueue`s.
+    now: SimTime,
+    first_above_time: Option<SimTime>,
+    dropping: bool,
+    drop_next: SimTime,
+    count: u32,
+    last_count: u32,
+    dropped: Vec<Packet>,
+    dropped_pkts: u64,
+}
+
+impl CoDelQueue {
+    pub fn new(capacity_bytes: u64, params: CoDelParams) -> Self {
+        assert!(
+            params.interval.0 > 0,
+            "CoDel interval must be > 0, got {:?}",
+            params.interval
+        );
+        Self {
+            max_bytes: capacity_bytes,
+            cur_bytes: 0,
+            q: VecDeque::new(),
+            params,
+            now: SimTime::ZERO,
+            first_above_time: None,
+            dropping: false,
+            drop_next: SimTime::ZERO,
+            count: 0,
+            last_count: 0,
+            dropped: Vec::new(),
+            dropped_pkts: 0,
+        }
+    }
+
+    pub fn params(&self) -> &CoDelParams {
+        &self.params
+    }
+
+    /// Whether CoDel is currently in its dropping state.
+    pub fn is_dropping(&self) -> bool {
+        self.dropping
+    }
+
+    /// Drops in the current (or last) dropping state.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Packets dropped by the control law (not counting drop-tail overflow).
+    pub fn dropped_pkts(&self) -> u64 {
+        self.dropped_pkts
+    }
+
+    /// `t + interval / sqrt(count)`
+    fn control_law(&self, t: SimTime, count: u32) -> SimTime {
+        let step = self.params.interval.0 as f64 / (count.max(1) as f64).sqrt();
+        SimTime(t.0.saturating_add(step as u64))
+    }
+
+    /// Pop the head packet and report whether its sojourn time allows a drop.
+    fn do_dequeue(&mut self, now: SimTime) -> (Option<Packet>, bool) {
+        let Some((enqueued_at, pkt)) = self.q.pop_front() else {
+            self.first_above_time = None;
+            return (None, false);
+        };
+        self.cur_bytes = self.cur_bytes.saturating_sub(pkt.size_bytes as u64);
+        let sojourn = now.0.saturating_sub(enqueued_at.0);
+        let mut ok_to_drop = false;
+        if sojourn < self.params.target.0 || self.cur_bytes <= DEFAULT_PKT_BYTES {
+            self.first_above_time = None;
+        } else {
+            match self.first_above_time {
+                None => {
+                    self.first_above_time =
+                        Some(SimTime(now.0.saturating_add(self.params.interval.0)));
+                }
+                Some(t) => ok_to_drop = now >= t,
+            }
+        }
+        (Some(pkt), ok_to_drop)
+    }
+
+    fn drop_pkt(&mut self, pkt: Packet) {
+        self.dropped_pkts += 1;
+        self.dropped.push(pkt);
+    }
+}
+
+impl PacketQueue for CoDelQueue {
+    fn enqueue(&mut self, pkt: Packet) -> Result<(), Packet> {
+        self.enqueue_at(pkt, self.now)
+    }
+
+    fn enqueue_at(&mut self, pkt: Packet, now: SimTime) -> Result<(), Packet> {
+        self.now = self.now.max(now);
+        let sz = pkt.size_bytes as u64;
+        if self.cur_bytes.saturating_add(sz) > self.max_bytes {
+            return Err(pkt);
+        }
+        self.cur_bytes = self.cur_bytes.saturating_add(sz);
+        self.q.push_back((now, pkt));
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        self.dequeue_at(self.now)
+    }
+
+    fn dequeue_at(&mut self, now: SimTime) -> Option<Packet> {
+        self.now = self.now.max(now);
+        let (mut pkt, mut ok_to_drop) = self.do_dequeue(now);
+        if pkt.is_none() {
+            self.dropping = false;
+            return None;
+        }
+
+        if self.dropping {
+            if !ok_to_drop {
+                self.dropping = false;
+            }
+            while self.dropping && now >= self.drop_next {
+                if let Some(p) = pkt.take() {
+                    self.drop_pkt(p);
+                }
+                self.count = self.count.saturating_add(1);
+                (pkt, ok_to_drop) = self.do_dequeue(now);
+                if !ok_to_drop || pkt.is_none() {
+                    self.dropping = false;
+                } else {
+                    self.drop_next = self.control_law(self.drop_next, self.count);
+                }
+            }
+        } else if ok_to_drop {
+            if let Some(p) = pkt.take() {
+                self.drop_pkt(p);
+            }
+            (pkt, _) = self.do_dequeue(now);
+            self.dropping = true;
+            // Resume near the previous drop rate if the last dropping state was recent.
+            let delta = self.count.saturating_sub(self.last_count);
+            let recent =
+                now.0.saturating_sub(self.drop_next.0) < self.params.interval.0.saturating_mul(16);
+            self.count = if delta > 1 && recent { delta } else { 1 };
+            self.drop_next = self.control_law(now, self.count);
+            self.last_count = self.count;
+        }
+        pkt
+    }
+
+    fn take_dropped(&mut self) -> Vec<Packet> {
+        std::mem::take(&mut self.dropped)
+    }
+
+    fn len(&self) -> usize {
+        self.q.len()
+    }
+
+    fn bytes(&self) -> u64 {
+        self.cur_bytes
+    }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+}
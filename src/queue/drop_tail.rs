@@ -1,6 +1,7 @@
 //! DropTail（尾丢弃）队列
 //!
 //! 当队列容量不足时，直接丢弃新到达的 packet。
+//! ECN 阈值标记在链路上做（见 `Network::set_link_ecn_threshold_bytes`），与队列类型无关。
 
 use std::collections::VecDeque;
 
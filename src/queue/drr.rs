@@ -0,0 +1,195 @@
+//! Deficit Round Robin (DRR) fair queue with drop-tail capacity.
+//!
+//! Control packets (ACK/handshake) keep strict priority as in `PriorityQueue`.
+//! Data packets are kept in one FIFO per flow (`Packet::flow_id`). Active flows
+//! are visited in round-robin order, and each visit adds `quantum_bytes` to the
+//! flow's deficit counter. The flow then sends head packets while they fit in
+//! the deficit. Unlike `FlowWrrQueue`, the shares are therefore equal in
+//! bytes, not in packets, even when flows use different packet sizes.
+//!
+//! A flow weight set via `set_flow_weight` scales its quantum. A flow that
+//! drains its sub-queue loses its leftover deficit.
+//!
+//! `DrrQueue::per_tenant` keys the sub-queues by `Packet::tenant_id` instead,
+//! so tenants split the link by `set_tenant_weight` however many flows each
+//! one runs; packets of one tenant stay FIFO among themselves.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::net::Packet;
+
+use super::{PacketQueue, PriorityQueue};
+
+#[derive(Debug, Default)]
+struct FlowQueue {
+    q: VecDeque<Packet>,
+    /// Bytes the flow may still send before yielding its turn.
+    deficit: u64,
+}
+
+/// What a DRR sub-queue is keyed by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DrrKey {
+    Flow,
+    Tenant,
+}
+
+#[derive(Debug)]
+pub struct DrrQueue {
+    max_bytes: u64,
+    cur_bytes: u64,
+    len: usize,
+    quantum_bytes: u64,
+    key: DrrKey,
+    hi: VecDeque<Packet>,
+    flows: HashMap<u64, FlowQueue>,
+    /// Sub-queues (flows or tenants) with queued data, in service order; the
+    /// front is being served.
+    active: VecDeque<u64>,
+    weights: HashMap<u64, u32>,
+    /// Whether the front flow has already received its quantum for this turn.
+    turn_started: bool,
+}
+
+impl DrrQueue {
+    pub fn new(max_bytes: u64, quantum_bytes: u64) -> Self {
+        Self::with_key(max_bytes, quantum_bytes, DrrKey::Flow)
+    }
+
+    /// DRR across tenants (`Packet::tenant_id`) rather than flows.
+    pub fn per_tenant(max_bytes: u64, quantum_bytes: u64) -> Self {
+        Self::with_key(max_bytes, quantum_bytes, DrrKey::Tenant)
+    }
+
+    fn with_key(max_bytes: u64, quantum_bytes: u64, key: DrrKey) -> Self {
+        assert!(quantum_bytes > 0, "DRR quantum must be > 0");
+        Self {
+            max_bytes,
+            cur_bytes: 0,
+            len: 0,
+            quantum_bytes,
+            key,
+            hi: VecDeque::new(),
+            flows: HashMap::new(),
+            active: VecDeque::new(),
+            weights: HashMap::new(),
+            turn_started: false,
+        }
+    }
+
+    pub fn quantum_bytes(&self) -> u64 {
+        self.quantum_bytes
+    }
+
+    /// Current deficit counter of `flow_id` (0 if the flow has nothing queued).
+    /// On a per-tenant queue the argument is a tenant id.
+    pub fn deficit(&self, flow_id: u64) -> u64 {
+        self.flows.get(&flow_id).map_or(0, |f| f.deficit)
+    }
+
+    fn key_of(&self, pkt: &Packet) -> u64 {
+        match self.key {
+            DrrKey::Flow => pkt.flow_id,
+            DrrKey::Tenant => pkt.tenant_id as u64,
+        }
+    }
+
+    fn quantum(&self, flow_id: u64) -> u64 {
+        let weight = self.weights.get(&flow_id).copied().unwrap_or(1);
+        self.quantum_bytes.saturating_mul(weight as u64)
+    }
+
+    /// Hand the turn to the next active flow.
+    fn end_turn(&mut self) {
+        self.active.rotate_left(1);
+        self.turn_started = false;
+    }
+
+    fn dequeue_data(&mut self) -> Option<Packet> {
+        loop {
+            let flow_id = *self.active.front()?;
+            let quantum = self.quantum(flow_id);
+            let flow = self
+                .flows
+                .get_mut(&flow_id)
+                .expect("active flow has a queue");
+            if !self.turn_started {
+                flow.deficit = flow.deficit.saturating_add(quantum);
+                self.turn_started = true;
+            }
+            let head_bytes = flow
+                .q
+                .front()
+                .expect("active flow queue is non-empty")
+                .size_bytes as u64;
+            if head_bytes > flow.deficit {
+                self.end_turn();
+                continue;
+            }
+            flow.deficit -= head_bytes;
+            let pkt = flow.q.pop_front().expect("head checked above");
+            if flow.q.is_empty() {
+                self.flows.remove(&flow_id);
+                self.active.pop_front();
+                self.turn_started = false;
+            }
+            return Some(pkt);
+        }
+    }
+}
+
+impl PacketQueue for DrrQueue {
+    fn enqueue(&mut self, pkt: Packet) -> Result<(), Packet> {
+        let sz = pkt.size_bytes as u64;
+        if self.cur_bytes.saturating_add(sz) > self.max_bytes {
+            return Err(pkt);
+        }
+        self.cur_bytes = self.cur_bytes.saturating_add(sz);
+        self.len += 1;
+        if PriorityQueue::is_high_priority(&pkt) {
+            self.hi.push_back(pkt);
+            return Ok(());
+        }
+        let flow_id = self.key_of(&pkt);
+        let flow = self.flows.entry(flow_id).or_default();
+        if flow.q.is_empty() {
+            self.active.push_back(flow_id);
+        }
+        flow.q.push_back(pkt);
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        let pkt = match self.hi.pop_front() {
+            Some(pkt) => pkt,
+            None => self.dequeue_data()?,
+        };
+        self.cur_bytes = self.cur_bytes.saturating_sub(pkt.size_bytes as u64);
+        self.len -= 1;
+        Some(pkt)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn bytes(&self) -> u64 {
+        self.cur_bytes
+    }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    fn set_flow_weight(&mut self, flow_id: u64, weight: u32) {
+        if self.key == DrrKey::Flow {
+            self.weights.insert(flow_id, weight);
+        }
+    }
+
+    fn set_tenant_weight(&mut self, tenant_id: u32, weight: u32) {
+        if self.key == DrrKey::Tenant {
+            self.weights.insert(tenant_id as u64, weight);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Earliest-deadline-first queue with drop-tail capacity.
+//!
+//! Control packets (ACK/handshake) keep strict priority as in `PriorityQueue`.
+//! Data packets are served in order of the deadline carried on the packet
+//! (`Packet::deadline`); packets without a deadline go after all deadline
+//! traffic. Ties, including deadline-less packets, are served FIFO.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::net::Packet;
+use crate::sim::SimTime;
+
+use super::{PacketQueue, PriorityQueue};
+
+#[derive(Debug)]
+struct Queued {
+    deadline: SimTime,
+    seq: u64,
+    pkt: Packet,
+}
+
+impl Queued {
+    fn key(&self) -> (SimTime, u64) {
+        (self.deadline, self.seq)
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[derive(Debug)]
+pub struct EdfQueue {
+    max_bytes: u64,
+    cur_bytes: u64,
+    hi: VecDeque<Packet>,
+    data: BinaryHeap<Reverse<Queued>>,
+    next_seq: u64,
+}
+
+impl EdfQueue {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            cur_bytes: 0,
+            hi: VecDeque::new(),
+            data: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+}
+
+impl PacketQueue for EdfQueue {
+    fn enqueue(&mut self, pkt: Packet) -> Result<(), Packet> {
+        let sz = pkt.size_bytes as u64;
+        if self.cur_bytes.saturating_add(sz) > self.max_bytes {
+            return Err(pkt);
+        }
+        self.cur_bytes = self.cur_bytes.saturating_add(sz);
+        if PriorityQueue::is_high_priority(&pkt) {
+            self.hi.push_back(pkt);
+            return Ok(());
+        }
+        let seq = self.next_seq;
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.data.push(Reverse(Queued {
+            deadline: pkt.deadline.unwrap_or(SimTime(u64::MAX)),
+            seq,
+            pkt,
+        }));
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        let pkt = match self.hi.pop_front() {
+            Some(pkt) => pkt,
+            None => self.data.pop()?.0.pkt,
+        };
+        self.cur_bytes = self.cur_bytes.saturating_sub(pkt.size_bytes as u64);
+        Some(pkt)
+    }
+
+    fn len(&self) -> usize {
+        self.hi.len().saturating_add(self.data.len())
+    }
+
+    fn bytes(&self) -> u64 {
+        self.cur_bytes
+    }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+}
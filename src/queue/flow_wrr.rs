@@ -0,0 +1,120 @@
+//! Per-flow weighted round-robin queue with drop-tail capacity.
+//!
+//! Control packets (ACK/handshake) keep strict priority as in `PriorityQueue`.
+//! Data packets are kept in one FIFO per flow, and active flows are served in
+//! round-robin order. Each turn a flow may send `weight` packets (default 1).
+//! A flow with a large standing backlog therefore cannot starve a newly
+//! arrived flow on the same egress.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::net::Packet;
+
+use super::{PacketQueue, PriorityQueue};
+
+#[derive(Debug)]
+pub struct FlowWrrQueue {
+    max_bytes: u64,
+    cur_bytes: u64,
+    len: usize,
+    hi: VecDeque<Packet>,
+    flows: HashMap<u64, VecDeque<Packet>>,
+    /// Flows with queued data, in service order; the front is being served.
+    active: VecDeque<u64>,
+    weights: HashMap<u64, u32>,
+    /// Packets the front flow may still send in its current turn.
+    credit: u32,
+}
+
+impl FlowWrrQueue {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            max_bytes,
+            cur_bytes: 0,
+            len: 0,
+            hi: VecDeque::new(),
+            flows: HashMap::new(),
+            active: VecDeque::new(),
+            weights: HashMap::new(),
+            credit: 0,
+        }
+    }
+
+    fn weight(&self, flow_id: u64) -> u32 {
+        self.weights.get(&flow_id).copied().unwrap_or(1)
+    }
+
+    /// Start a fresh turn for whichever flow is now at the front.
+    fn refill_credit(&mut self) {
+        self.credit = self.active.front().map_or(0, |f| self.weight(*f));
+    }
+}
+
+impl PacketQueue for FlowWrrQueue {
+    fn enqueue(&mut self, pkt: Packet) -> Result<(), Packet> {
+        let sz = pkt.size_bytes as u64;
+        if self.cur_bytes.saturating_add(sz) > self.max_bytes {
+            return Err(pkt);
+        }
+        self.cur_bytes = self.cur_bytes.saturating_add(sz);
+        self.len += 1;
+        if PriorityQueue::is_high_priority(&pkt) {
+            self.hi.push_back(pkt);
+            return Ok(());
+        }
+        let flow_id = pkt.flow_id;
+        let q = self.flows.entry(flow_id).or_default();
+        let newly_active = q.is_empty();
+        q.push_back(pkt);
+        if newly_active {
+            self.active.push_back(flow_id);
+            if self.active.len() == 1 {
+                self.refill_credit();
+            }
+        }
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        let pkt = match self.hi.pop_front() {
+            Some(pkt) => pkt,
+            None => {
+                let flow_id = *self.active.front()?;
+                let q = self
+                    .flows
+                    .get_mut(&flow_id)
+                    .expect("active flow has a queue");
+                let pkt = q.pop_front().expect("active flow queue is non-empty");
+                self.credit = self.credit.saturating_sub(1);
+                if q.is_empty() {
+                    self.flows.remove(&flow_id);
+                    self.active.pop_front();
+                    self.refill_credit();
+                } else if self.credit == 0 {
+                    self.active.rotate_left(1);
+                    self.refill_credit();
+                }
+                pkt
+            }
+        };
+        self.cur_bytes = self.cur_bytes.saturating_sub(pkt.size_bytes as u64);
+        self.len -= 1;
+        Some(pkt)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn bytes(&self) -> u64 {
+        self.cur_bytes
+    }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    fn set_flow_weight(&mut self, flow_id: u64, weight: u32) {
+        self.weights.insert(flow_id, weight);
+    }
+}
@@ -1,17 +1,30 @@
 //! 队列策略（Queue disciplines）
 //!
-//! 目前先提供最基础的 DropTail（尾丢弃）队列，后续可以在此扩展 RED/CoDel 等策略。
+//! 提供 DropTail（尾丢弃）、优先级、按流 WRR、DRR、EDF 与 RED、CoDel 等队列。
 
 use crate::net::Packet;
+use crate::sim::SimTime;
 
+mod codel;
 mod drop_tail;
+mod drr;
+mod edf;
+mod flow_wrr;
 mod priority;
+mod red;
 
+pub use codel::{CoDelParams, CoDelQueue};
 pub use drop_tail::DropTailQueue;
+pub use drr::DrrQueue;
+pub use edf::EdfQueue;
+pub use flow_wrr::FlowWrrQueue;
 pub use priority::PriorityQueue;
+pub use red::{RedMarker, RedParams, RedQueue};
 
+/// 默认包大小 / MTU（bytes）
 pub const DEFAULT_PKT_BYTES: u64 = 1500;
 
+/// 按默认 MTU 把包数换算为字节；自定义 MTU 时用 `Network::mem_from_pkt`
 pub fn mem_from_pkt(pkts: u64) -> u64 {
     pkts.saturating_mul(DEFAULT_PKT_BYTES)
 }
@@ -19,11 +32,70 @@ pub fn mem_from_pkt(pkts: u64) -> u64 {
 /// Packet 队列抽象
 pub trait PacketQueue: std::fmt::Debug {
     /// 入队：成功返回 Ok；若被丢弃则返回 Err(pkt)
+    // 被丢弃的包按值交还给调用方（用于统计/可视化），不额外装箱
+    #[allow(clippy::result_large_err)]
     fn enqueue(&mut self, pkt: Packet) -> Result<(), Packet>;
+    /// 带时间戳入队（`now` 为到达时刻）；默认忽略时间，等价于 `enqueue`
+    #[allow(clippy::result_large_err)]
+    fn enqueue_at(&mut self, pkt: Packet, _now: SimTime) -> Result<(), Packet> {
+        self.enqueue(pkt)
+    }
     /// 出队：按队列策略返回下一个 packet
     fn dequeue(&mut self) -> Option<Packet>;
+    /// 在 `now` 时刻出队；需要逗留时间的策略（CoDel）重写它，默认等价于 `dequeue`
+    fn dequeue_at(&mut self, _now: SimTime) -> Option<Packet> {
+        self.dequeue()
+    }
+    /// 取走出队时被策略丢弃的包（用于统计/可视化）；默认没有
+    fn take_dropped(&mut self) -> Vec<Packet> {
+        Vec::new()
+    }
 
     fn len(&self) -> usize;
     fn bytes(&self) -> u64;
     fn capacity_bytes(&self) -> u64;
+
+    /// 设置某条流的调度权重；不区分流的队列忽略该设置
+    fn set_flow_weight(&mut self, _flow_id: u64, _weight: u32) {}
+
+    /// 设置某个租户的调度权重；不区分租户的队列忽略该设置
+    fn set_tenant_weight(&mut self, _tenant_id: u32, _weight: u32) {}
+}
+
+/// 出方向调度策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EgressScheduler {
+    /// 按 `Packet::priority` 严格优先，同优先级内控制包优先、类内 FIFO（`PriorityQueue`）
+    #[default]
+    Fifo,
+    /// 控制包优先，数据包按流加权轮询（`FlowWrrQueue`）
+    FlowWrr,
+    /// 控制包优先，但数据包被连续绕过 `n` 次后提升优先级（带老化的 `PriorityQueue`）
+    AgedPriority(u32),
+    /// 控制包优先，数据包按所属流的截止时间 EDF 调度（`EdfQueue`）
+    Edf,
+    /// 控制包优先，数据包按流 Deficit Round Robin，每轮配额 `quantum_bytes`（`DrrQueue`）
+    Drr { quantum_bytes: u64 },
+    /// 控制包优先，数据包按租户（`Packet::tenant_id`）加权 DRR，租户内 FIFO（`DrrQueue::per_tenant`）
+    TenantDrr { quantum_bytes: u64 },
+}
+
+impl EgressScheduler {
+    /// 按该策略创建一个容量为 `capacity_bytes` 的队列
+    pub fn build(self, capacity_bytes: u64) -> Box<dyn PacketQueue> {
+        match self {
+            EgressScheduler::Fifo => Box::new(PriorityQueue::new(capacity_bytes)),
+            EgressScheduler::FlowWrr => Box::new(FlowWrrQueue::new(capacity_bytes)),
+            EgressScheduler::AgedPriority(n) => {
+                Box::new(PriorityQueue::with_aging(capacity_bytes, n))
+            }
+            EgressScheduler::Edf => Box::new(EdfQueue::new(capacity_bytes)),
+            EgressScheduler::Drr { quantum_bytes } => {
+                Box::new(DrrQueue::new(capacity_bytes, quantum_bytes))
+            }
+            EgressScheduler::TenantDrr { quantum_bytes } => {
+                Box::new(DrrQueue::per_tenant(capacity_bytes, quantum_bytes))
+            }
+        }
+    }
 }
@@ -1,21 +1,42 @@
 //! Priority queue with drop-tail capacity.
 //!
-//! This queue gives strict priority to control traffic (e.g., TCP/DCTCP ACKs)
-//! over bulk data packets. It helps avoid ACK starvation when bidirectional
-//! data flows share the same egress queue.
+//! Packets are split into traffic classes and served with strict priority,
+//! FIFO within a class. A packet's class is ranked first by `Packet::priority`
+//! (higher is served first) and then, within the same priority, control
+//! traffic (e.g., TCP/DCTCP ACKs) ahead of bulk data. With every packet at the
+//! default priority 0 this is the plain control-over-data queue, which helps
+//! avoid ACK starvation when bidirectional data flows share an egress queue.
+//!
+//! Optional aging bounds starvation of the lower classes: every time a packet
+//! is served while a lower class waits, that class's head gains priority, and
+//! once it has been bypassed `aging_limit` times it is served next regardless
+//! of the classes above it.
 
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
-use crate::net::{DctcpSegment, Packet, TcpSegment, Transport};
+use crate::net::Packet;
 
 use super::PacketQueue;
 
+/// Service rank of a traffic class: `(priority, is_control)`, larger first.
+type ClassRank = (u8, bool);
+
+#[derive(Debug, Default)]
+struct Class {
+    q: VecDeque<Packet>,
+    /// How many times this class's head has been bypassed by a higher class.
+    head_bypassed: u32,
+}
+
 #[derive(Debug)]
 pub struct PriorityQueue {
     max_bytes: u64,
     cur_bytes: u64,
-    hi: VecDeque<Packet>,
-    lo: VecDeque<Packet>,
+    /// Non-empty classes only; a class is dropped once it drains.
+    classes: BTreeMap<ClassRank, Class>,
+    len: usize,
+    /// Max number of higher-class dequeues a class head may wait for.
+    aging_limit: Option<u32>,
 }
 
 impl PriorityQueue {
@@ -23,21 +44,54 @@ impl PriorityQueue {
         Self {
             max_bytes,
             cur_bytes: 0,
-            hi: VecDeque::new(),
-            lo: VecDeque::new(),
+            classes: BTreeMap::new(),
+            len: 0,
+            aging_limit: None,
         }
     }
 
-    fn is_high_priority(pkt: &Packet) -> bool {
-        match &pkt.transport {
-            Transport::Tcp(TcpSegment::Ack { .. })
-            | Transport::Tcp(TcpSegment::Syn)
-            | Transport::Tcp(TcpSegment::SynAck)
-            | Transport::Tcp(TcpSegment::HandshakeAck)
-            | Transport::Dctcp(DctcpSegment::Ack { .. }) => true,
-            _ => false,
+    /// Enable priority aging: a class head is promoted after being bypassed
+    /// by `limit` packets from higher classes. A limit of 0 lets a waiting
+    /// lower class jump ahead of the top class on every dequeue.
+    pub fn with_aging(max_bytes: u64, limit: u32) -> Self {
+        Self {
+            aging_limit: Some(limit),
+            ..Self::new(max_bytes)
         }
     }
+
+    pub fn aging_limit(&self) -> Option<u32> {
+        self.aging_limit
+    }
+
+    /// Number of packets queued at traffic class priority `priority`.
+    pub fn len_at_priority(&self, priority: u8) -> usize {
+        self.classes
+            .range((priority, false)..=(priority, true))
+            .map(|(_, c)| c.q.len())
+            .sum()
+    }
+
+    pub(crate) fn is_high_priority(pkt: &Packet) -> bool {
+        pkt.transport.is_control()
+    }
+
+    fn rank(pkt: &Packet) -> ClassRank {
+        (pkt.priority, Self::is_high_priority(pkt))
+    }
+
+    /// Class to serve next: the highest aged class below the top one if
+    /// aging is enabled, otherwise the top class.
+    fn next_class(&self) -> Option<ClassRank> {
+        let mut ranks = self.classes.iter().rev();
+        let (&top, _) = ranks.next()?;
+        let aged = self.aging_limit.and_then(|limit| {
+            ranks
+                .find(|(_, c)| c.head_bypassed >= limit)
+                .map(|(&rank, _)| rank)
+        });
+        Some(aged.unwrap_or(top))
+    }
 }
 
 impl PacketQueue for PriorityQueue {
@@ -47,22 +101,33 @@ impl PacketQueue for PriorityQueue {
             return Err(pkt);
         }
         self.cur_bytes = self.cur_bytes.saturating_add(sz);
-        if Self::is_high_priority(&pkt) {
-            self.hi.push_back(pkt);
-        } else {
-            self.lo.push_back(pkt);
-        }
+        self.len += 1;
+        self.classes
+            .entry(Self::rank(&pkt))
+            .or_default()
+            .q
+            .push_back(pkt);
         Ok(())
     }
 
     fn dequeue(&mut self) -> Option<Packet> {
-        let pkt = self.hi.pop_front().or_else(|| self.lo.pop_front())?;
+        let rank = self.next_class()?;
+        for (_, c) in self.classes.range_mut(..rank) {
+            c.head_bypassed = c.head_bypassed.saturating_add(1);
+        }
+        let class = self.classes.get_mut(&rank)?;
+        class.head_bypassed = 0;
+        let pkt = class.q.pop_front()?;
+        if class.q.is_empty() {
+            self.classes.remove(&rank);
+        }
+        self.len -= 1;
         self.cur_bytes = self.cur_bytes.saturating_sub(pkt.size_bytes as u64);
         Some(pkt)
     }
 
     fn len(&self) -> usize {
-        self.hi.len().saturating_add(self.lo.len())
+        self.len
     }
 
     fn bytes(&self) -> u64 {
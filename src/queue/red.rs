@@ -0,0 +1,215 @@
+//! Random Early Detection (RED) queue.
+//!
+//! Every arrival updates an EWMA of the queue occupancy (in bytes). Below
+//! `min_th` packets are always accepted; between `min_th` and `max_th` they are
+//! hit with a probability rising linearly from 0 to `max_p`; at or above
+//! `max_th` every packet is hit. A hit ECT packet is marked CE and enqueued, a
+//! hit non-ECT packet is dropped. The hard capacity is still drop-tail.
+//!
+//! The random draws come from a seeded splitmix64 stream, so runs are
+//! deterministic. The average is only updated on arrivals (no idle-time decay).
+//!
+//! The averaging and the random decision live in `RedMarker`, which links also
+//! use on their own to mark ECN probabilistically in front of any queue.
+
+use std::collections::VecDeque;
+
+use crate::net::Packet;
+
+use super::PacketQueue;
+
+/// RED thresholds and averaging weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RedParams {
+    /// Average queue size (bytes) where early marking/dropping starts.
+    pub min_th: u64,
+    /// Average queue size (bytes) from which every packet is marked/dropped.
+    pub max_th: u64,
+    /// Marking/dropping probability just below `max_th`.
+    pub max_p: f64,
+    /// EWMA weight of the instantaneous queue size, in (0, 1].
+    pub w_q: f64,
+    /// Seed of the random stream used for the early decisions.
+    pub seed: u64,
+}
+
+impl Default for RedParams {
+    fn default() -> Self {
+        Self {
+            min_th: 5 * 1500,
+            max_th: 15 * 1500,
+            max_p: 0.1,
+            w_q: 0.002,
+            seed: 1,
+        }
+    }
+}
+
+impl RedParams {
+    /// Probability that an arrival is marked/dropped at average queue size `avg_bytes`.
+    pub fn probability(&self, avg_bytes: f64) -> f64 {
+        if avg_bytes < self.min_th as f64 {
+            return 0.0;
+        }
+        if avg_bytes >= self.max_th as f64 {
+            return 1.0;
+        }
+        let span = (self.max_th - self.min_th) as f64;
+        self.max_p * (avg_bytes - self.min_th as f64) / span
+    }
+}
+
+/// RED's average-queue estimator and early-hit decision, without a queue.
+#[derive(Debug, Clone)]
+pub struct RedMarker {
+    params: RedParams,
+    avg_bytes: f64,
+    rng: u64,
+}
+
+impl RedMarker {
+    pub fn new(params: RedParams) -> Self {
+        assert!(
+            params.min_th < params.max_th,
+            "RED min_th ({}) must be below max_th ({})",
+            params.min_th,
+            params.max_th
+        );
+        assert!(
+            params.max_p > 0.0 && params.max_p <= 1.0,
+            "RED max_p must be in (0, 1], got {}",
+            params.max_p
+        );
+        assert!(
+            params.w_q > 0.0 && params.w_q <= 1.0,
+            "RED w_q must be in (0, 1], got {}",
+            params.w_q
+        );
+        Self {
+            params,
+            avg_bytes: 0.0,
+            rng: params.seed,
+        }
+    }
+
+    pub fn params(&self) -> &RedParams {
+        &self.params
+    }
+
+    /// EWMA of the queue size (bytes) as of the last arrival.
+    pub fn avg_bytes(&self) -> f64 {
+        self.avg_bytes
+    }
+
+    /// Current marking/dropping probability for the next arrival's average.
+    pub fn probability(&self) -> f64 {
+        self.params.probability(self.avg_bytes)
+    }
+
+    /// Record an arrival that finds `queue_bytes` queued and decide whether it
+    /// is hit (marked or dropped early).
+    pub fn on_arrival(&mut self, queue_bytes: u64) -> bool {
+        let w_q = self.params.w_q;
+        self.avg_bytes = (1.0 - w_q) * self.avg_bytes + w_q * queue_bytes as f64;
+        let p = self.probability();
+        p > 0.0 && (p >= 1.0 || self.next_uniform() < p)
+    }
+
+    /// Uniform draw in [0, 1).
+    fn next_uniform(&mut self) -> f64 {
+        // splitmix64
+        self.rng = self.rng.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug)]
+pub struct RedQueue {
+    max_bytes: u64,
+    cur_bytes: u64,
+    q: VecDeque<Packet>,
+    marker: RedMarker,
+    marked_pkts: u64,
+    early_drops: u64,
+}
+
+impl RedQueue {
+    pub fn new(capacity_bytes: u64, params: RedParams) -> Self {
+        Self {
+            max_bytes: capacity_bytes,
+            cur_bytes: 0,
+            q: VecDeque::new(),
+            marker: RedMarker::new(params),
+            marked_pkts: 0,
+            early_drops: 0,
+        }
+    }
+
+    pub fn params(&self) -> &RedParams {
+        self.marker.params()
+    }
+
+    /// EWMA of the queue size (bytes) as of the last arrival.
+    pub fn avg_bytes(&self) -> f64 {
+        self.marker.avg_bytes()
+    }
+
+    /// Current marking/dropping probability for the next arrival's average.
+    pub fn probability(&self) -> f64 {
+        self.marker.probability()
+    }
+
+    /// ECT packets marked CE by RED.
+    pub fn marked_pkts(&self) -> u64 {
+        self.marked_pkts
+    }
+
+    /// Non-ECT packets dropped by RED (not counting drop-tail overflow).
+    pub fn early_drops(&self) -> u64 {
+        self.early_drops
+    }
+}
+
+impl PacketQueue for RedQueue {
+    fn enqueue(&mut self, mut pkt: Packet) -> Result<(), Packet> {
+        let hit = self.marker.on_arrival(self.cur_bytes);
+        if hit && !pkt.ecn.is_ect() {
+            self.early_drops += 1;
+            return Err(pkt);
+        }
+
+        let sz = pkt.size_bytes as u64;
+        if self.cur_bytes.saturating_add(sz) > self.max_bytes {
+            return Err(pkt);
+        }
+        if hit {
+            pkt.mark_ce_if_ect();
+            self.marked_pkts += 1;
+        }
+        self.cur_bytes = self.cur_bytes.saturating_add(sz);
+        self.q.push_back(pkt);
+        Ok(())
+    }
+
+    fn dequeue(&mut self) -> Option<Packet> {
+        let pkt = self.q.pop_front()?;
+        self.cur_bytes = self.cur_bytes.saturating_sub(pkt.size_bytes as u64);
+        Some(pkt)
+    }
+
+    fn len(&self) -> usize {
+        self.q.len()
+    }
+
+    fn bytes(&self) -> u64 {
+        self.cur_bytes
+    }
+
+    fn capacity_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+}
@@ -0,0 +1,50 @@
+//! 确定性检查
+//!
+//! 以相同输入运行两次仿真，比较两次执行的事件序列与统计摘要是否逐字节一致，
+//! 用于发现依赖 `HashMap` 迭代顺序等非确定性问题。
+
+use super::simulator::Simulator;
+use super::time::SimTime;
+
+/// 一条已执行事件的记录。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventRecord {
+    pub at: SimTime,
+    pub seq: u64,
+    /// 事件类型名
+    pub kind: &'static str,
+}
+
+/// 运行两次 `run`，比较执行的事件序列与其返回的统计摘要。
+///
+/// `run` 在给定的（已开启事件记录的）仿真器上搭建并运行仿真，返回 `(结果, 统计摘要)`。
+/// 两次一致时返回第一次运行的仿真器与结果，否则返回首个差异的描述。
+pub fn check_determinism<T>(
+    mut run: impl FnMut(&mut Simulator) -> (T, String),
+) -> Result<(Simulator, T), String> {
+    let mut first = Simulator::default();
+    first.record_events();
+    let (out, first_stats) = run(&mut first);
+
+    let mut second = Simulator::default();
+    second.record_events();
+    let (_, second_stats) = run(&mut second);
+
+    compare_event_traces(first.event_trace(), second.event_trace())?;
+    if first_stats != second_stats {
+        return Err(format!(
+            "stats differ:\n--- first run\n{first_stats}\n--- second run\n{second_stats}"
+        ));
+    }
+    Ok((first, out))
+}
+
+fn compare_event_traces(a: &[EventRecord], b: &[EventRecord]) -> Result<(), String> {
+    if let Some(i) = a.iter().zip(b).position(|(x, y)| x != y) {
+        return Err(format!("event #{i} differs: {:?} vs {:?}", a[i], b[i]));
+    }
+    if a.len() != b.len() {
+        return Err(format!("event count differs: {} vs {}", a.len(), b.len()));
+    }
+    Ok(())
+}
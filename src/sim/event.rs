@@ -8,4 +8,10 @@ use super::world::World;
 /// 事件：可被调度执行。使用 `self: Box<Self>` 以支持 move/所有权转移。
 pub trait Event: Send + 'static {
     fn execute(self: Box<Self>, sim: &mut Simulator, world: &mut dyn World);
+
+    /// 事件的简短名字（默认取类型名最后一段，如 `TcpRto`），用于调试时按类型匹配事件。
+    fn label(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
 }
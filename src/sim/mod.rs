@@ -3,6 +3,7 @@
 //! 此模块包含事件驱动仿真的核心组件，如仿真时间、事件、世界和仿真器。
 
 // 子模块声明
+mod determinism;
 mod event;
 mod scheduled_event;
 mod simulator;
@@ -11,9 +12,10 @@ mod workload;
 mod world;
 
 // 重新导出公共接口
+pub use determinism::{EventRecord, check_determinism};
 pub use event::Event;
 pub use scheduled_event::ScheduledEvent;
-pub use simulator::Simulator;
+pub use simulator::{EventToken, ProgressFn, SimMetrics, Simulator};
 pub use time::SimTime;
 pub use workload::{
     GpuSpec, HostSpec, RankSpec, RankStepKind, RankStepSpec, RoutingMode, SendRecvDirection,
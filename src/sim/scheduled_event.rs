@@ -7,13 +7,19 @@ use super::time::SimTime;
 use std::cmp::Ordering;
 
 /// 调度事件，包含执行时间、序列号和事件对象。
+///
+/// 排序键为 `(at, seq)`：`seq` 由仿真器按调度顺序单调分配，
+/// 因此同一时刻的事件严格按调度（插入）顺序先进先出执行，与堆的内部布局无关。
 pub struct ScheduledEvent {
     pub(crate) at: SimTime,
+    /// 调度序号（全局单调递增），用于同一时刻事件的 FIFO 排序
     pub(crate) seq: u64,
+    /// 事件类型名（用于事件记录）
+    pub(crate) kind: &'static str,
     pub(crate) ev: Box<dyn Event>,
 }
 
-// BinaryHeap 是 max-heap；我们需要最小时间优先，因此反向比较。
+// BinaryHeap 是 max-heap；我们需要最小时间优先（同一时刻序号小者优先），因此反向比较。
 impl Ord for ScheduledEvent {
     fn cmp(&self, other: &Self) -> Ordering {
         match self.at.cmp(&other.at) {
@@ -2,19 +2,69 @@
 //!
 //! 定义事件驱动仿真器，维护当前时间与事件队列。
 
+use super::determinism::EventRecord;
 use super::event::Event;
 use super::scheduled_event::ScheduledEvent;
 use super::time::SimTime;
 use super::world::World;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashSet};
+use std::time::{Duration, Instant};
 use tracing::{debug, info, trace};
 
+/// `Simulator::schedule_cancellable` 返回的令牌，用于 `Simulator::cancel`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventToken(u64);
+
+/// 进度回调：参数为跨过的时间点与此时待执行的事件数。
+pub type ProgressFn = Box<dyn FnMut(SimTime, usize) + Send>;
+
+/// `Simulator::set_progress_hook` 注册的进度回调及下一个触发时间点
+struct ProgressHook {
+    every: SimTime,
+    next: SimTime,
+    f: ProgressFn,
+}
+
+/// `Simulator::metrics` 返回的运行统计
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimMetrics {
+    /// 已执行的事件数（不含被取消的事件）
+    pub events_executed: u64,
+    /// 在 `run*` / `step` 中累计花费的墙钟时间
+    pub wall_elapsed: Duration,
+    /// 事件吞吐（事件/秒，按墙钟时间计）
+    pub events_per_sec: f64,
+}
+
+impl std::fmt::Display for SimMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "events={} wall_ms={:.3} events_per_sec={:.0}",
+            self.events_executed,
+            self.wall_elapsed.as_secs_f64() * 1e3,
+            self.events_per_sec
+        )
+    }
+}
+
 /// 事件驱动仿真器：维护当前时间与事件队列。
 #[derive(Default)]
 pub struct Simulator {
     now: SimTime,
     next_seq: u64,
     q: BinaryHeap<ScheduledEvent>,
+    /// 已执行事件的记录；`None` 表示未开启记录
+    trace: Option<Vec<EventRecord>>,
+    /// 仍在队列中、可被取消的事件序号
+    cancellable: HashSet<u64>,
+    /// 已取消但尚未从堆中弹出的事件序号（弹出时丢弃）
+    cancelled: HashSet<u64>,
+    /// 暂停标志：置位后 `run*` 在当前事件执行完后返回，直到 `resume`
+    paused: bool,
+    progress: Option<ProgressHook>,
+    events_executed: u64,
+    wall_elapsed: Duration,
 }
 
 impl Simulator {
@@ -23,9 +73,59 @@ impl Simulator {
         self.now
     }
 
+    /// 开启事件记录：此后每个被执行的事件都会追加到事件序列中。
+    pub fn record_events(&mut self) {
+        self.trace.get_or_insert_with(Vec::new);
+    }
+
+    /// 已记录的事件序列（未开启记录时为空）。
+    pub fn event_trace(&self) -> &[EventRecord] {
+        self.trace.as_deref().unwrap_or(&[])
+    }
+
+    /// 事件数与墙钟耗时统计
+    pub fn metrics(&self) -> SimMetrics {
+        let secs = self.wall_elapsed.as_secs_f64();
+        SimMetrics {
+            events_executed: self.events_executed,
+            wall_elapsed: self.wall_elapsed,
+            events_per_sec: if secs > 0.0 {
+                self.events_executed as f64 / secs
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// 计数并（开启记录时）记录即将执行的事件
+    fn record(&mut self, item: &ScheduledEvent) {
+        self.events_executed += 1;
+        if let Some(trace) = &mut self.trace {
+            trace.push(EventRecord {
+                at: item.at,
+                seq: item.seq,
+                kind: item.kind,
+            });
+        }
+    }
+
     /// 调度事件在指定时间执行
+    ///
+    /// 同一时刻的多个事件按调度顺序（FIFO）执行，包括在事件内部调度到当前时刻的事件，
+    /// 因此同样的调度序列在每次运行中都以同样的顺序执行。
     #[tracing::instrument(skip(self, ev), fields(event_type = std::any::type_name::<E>(), schedule_at = ?at))]
     pub fn schedule<E: Event>(&mut self, at: SimTime, ev: E) {
+        self.push(at, ev);
+    }
+
+    /// 调度事件并返回令牌；事件执行前可用 `cancel` 撤销。
+    pub fn schedule_cancellable<E: Event>(&mut self, at: SimTime, ev: E) -> EventToken {
+        let seq = self.push(at, ev);
+        self.cancellable.insert(seq);
+        EventToken(seq)
+    }
+
+    fn push<E: Event>(&mut self, at: SimTime, ev: E) -> u64 {
         let seq = self.next_seq;
         trace!(now = ?self.now, seq, "调度事件");
 
@@ -33,36 +133,197 @@ impl Simulator {
         self.q.push(ScheduledEvent {
             at,
             seq,
+            kind: std::any::type_name::<E>(),
             ev: Box::new(ev),
         });
 
         debug!(queue_size = self.q.len(), "事件已加入队列");
+        seq
     }
 
-    /// 运行直到事件队列为空或到达 `until`。
-    pub fn run_until(&mut self, until: SimTime, world: &mut dyn World) {
+    /// 取消一个尚未执行的事件；返回是否真的撤销了事件
+    /// （事件已执行或已被取消时返回 false）。被取消的事件不会执行，也不会被记录。
+    pub fn cancel(&mut self, token: EventToken) -> bool {
+        if !self.cancellable.remove(&token.0) {
+            return false;
+        }
+        self.cancelled.insert(token.0);
+        true
+    }
+
+    /// 暂停：当前事件执行完后 `run` / `run_until` / `run_until_event` / `run_for_events` 立即返回，
+    /// 且在 `resume` 之前不再执行事件，时间也不再推进。通常由事件或 `World::on_tick` 调用。
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// 解除暂停；之后再次调用 `run*` 从暂停处继续。
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// 注册进度回调：虚拟时间每跨过一个 `every` 的整数倍（从注册时刻起算）调用一次 `f`，
+    /// 参数为该时间点（而非触发它的事件时间）与此时待执行的事件数。
+    ///
+    /// 回调只依赖虚拟时间，触发次数与事件疏密无关；它拿不到仿真器，不影响事件顺序。
+    /// 时间停在某个时间点上（如 `run_until` 正好到达）也算跨过。
+    pub fn set_progress_hook(&mut self, every: SimTime, f: ProgressFn) {
+        assert!(every.0 > 0, "progress hook interval must be positive");
+        self.progress = Some(ProgressHook {
+            every,
+            next: SimTime(self.now.0.saturating_add(every.0)),
+            f,
+        });
+    }
+
+    /// 推进当前时间，先为途经的每个进度时间点调用回调
+    fn advance_to(&mut self, at: SimTime) {
+        if let Some(hook) = &mut self.progress {
+            while hook.next <= at {
+                let pending = self.q.len().saturating_sub(self.cancelled.len());
+                (hook.f)(hook.next, pending);
+                match hook.next.0.checked_add(hook.every.0) {
+                    Some(next) => hook.next = SimTime(next),
+                    None => {
+                        // 已无可表示的下一个时间点
+                        self.progress = None;
+                        break;
+                    }
+                }
+            }
+        }
+        self.now = at;
+    }
+
+    /// 丢弃堆顶所有已取消的事件
+    fn discard_cancelled(&mut self) {
+        if self.cancelled.is_empty() {
+            return;
+        }
         while let Some(top) = self.q.peek() {
-            if top.at > until {
+            if !self.cancelled.remove(&top.seq) {
                 break;
             }
-            let item = self.q.pop().expect("peek then pop");
-            self.now = item.at;
+            self.q.pop();
+        }
+    }
+
+    /// 弹出下一个未取消的事件
+    fn pop_live(&mut self) -> Option<ScheduledEvent> {
+        self.discard_cancelled();
+        let item = self.q.pop()?;
+        if !self.cancellable.is_empty() {
+            self.cancellable.remove(&item.seq);
+        }
+        Some(item)
+    }
+
+    /// 运行直到事件队列为空或到达 `until`（暂停时提前返回，时间停在暂停处）。
+    pub fn run_until(&mut self, until: SimTime, world: &mut dyn World) {
+        let started = Instant::now();
+        while !self.paused {
+            self.discard_cancelled();
+            let at = match self.q.peek() {
+                Some(top) if top.at <= until => top.at,
+                _ => break,
+            };
+            // 先推进时间再弹出，进度回调看到的待执行事件数包含即将执行的事件
+            self.advance_to(at);
+            let item = self.pop_live().expect("peek then pop");
+            self.record(&item);
             item.ev.execute(self, world);
             world.on_tick(self);
         }
-        self.now = self.now.max(until);
+        if !self.paused {
+            self.advance_to(self.now.max(until));
+        }
+        self.wall_elapsed += started.elapsed();
+    }
+
+    /// 最多执行 `max` 个事件（暂停时提前返回），返回事件队列是否已经清空。
+    ///
+    /// 用于有界调试与 fuzz：中途返回时时间停在最后执行的事件处，之后可用任意 `run*` 继续。
+    pub fn run_for_events(&mut self, max: usize, world: &mut dyn World) -> bool {
+        let started = Instant::now();
+        let mut executed = 0;
+        while executed < max && !self.paused {
+            self.discard_cancelled();
+            let Some(at) = self.q.peek().map(|top| top.at) else {
+                break;
+            };
+            self.advance_to(at);
+            let item = self.pop_live().expect("peek then pop");
+            self.record(&item);
+            item.ev.execute(self, world);
+            world.on_tick(self);
+            executed += 1;
+        }
+        self.discard_cancelled();
+        self.wall_elapsed += started.elapsed();
+        self.q.is_empty()
     }
 
-    /// 运行所有事件直到队列为空。
+    /// 执行队首的一个事件；队列为空时返回 false。暂停不影响单步执行。
+    pub fn step(&mut self, world: &mut dyn World) -> bool {
+        self.discard_cancelled();
+        let Some(at) = self.q.peek().map(|top| top.at) else {
+            return false;
+        };
+        let started = Instant::now();
+        self.advance_to(at);
+        let item = self.pop_live().expect("peek then pop");
+        self.record(&item);
+        item.ev.execute(self, world);
+        world.on_tick(self);
+        self.wall_elapsed += started.elapsed();
+        true
+    }
+
+    /// 运行直到下一个满足 `pred` 的事件即将执行（该事件不执行，时间推进到它的调度时刻），
+    /// 或事件队列为空。
+    ///
+    /// 返回 true 表示停在了匹配的事件上；之后可用 `step` 执行它再继续。
+    /// 例如调试时“运行到下一个 RTO”：`sim.run_until_event(world, |ev| ev.label() == "TcpRto")`。
+    pub fn run_until_event(
+        &mut self,
+        world: &mut dyn World,
+        pred: impl Fn(&dyn Event) -> bool,
+    ) -> bool {
+        while !self.paused {
+            self.discard_cancelled();
+            let Some(top) = self.q.peek() else {
+                break;
+            };
+            if pred(top.ev.as_ref()) {
+                let at = top.at;
+                self.advance_to(at);
+                return true;
+            }
+            self.step(world);
+        }
+        false
+    }
+
+    /// 运行所有事件直到队列为空（或被暂停）。
     #[tracing::instrument(skip(self, world))]
     pub fn run(&mut self, world: &mut dyn World) {
         info!("▶️  开始运行仿真");
         debug!(now = ?self.now, queue_size = self.q.len(), "初始状态");
 
+        let started = Instant::now();
         let mut event_count = 0;
-        while let Some(item) = self.q.pop() {
+        while !self.paused {
+            self.discard_cancelled();
+            let Some(at) = self.q.peek().map(|top| top.at) else {
+                break;
+            };
+            self.advance_to(at);
+            let item = self.pop_live().expect("peek then pop");
             event_count += 1;
-            self.now = item.at;
 
             debug!(
                 event_num = event_count,
@@ -73,9 +334,11 @@ impl Simulator {
                 "执行事件"
             );
 
+            self.record(&item);
             item.ev.execute(self, world);
             world.on_tick(self);
         }
+        self.wall_elapsed += started.elapsed();
 
         info!(
             total_events = event_count,
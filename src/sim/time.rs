@@ -8,6 +8,14 @@ pub struct SimTime(pub u64);
 
 impl SimTime {
     pub const ZERO: SimTime = SimTime(0);
+    /// 链路时延的合理上限：超过 1s 基本意味着把 ms/s 当成了 ns 以外的单位
+    pub const MAX_PLAUSIBLE_LINK_LATENCY: SimTime = SimTime(1_000_000_000);
+    /// 链路时延的合理下限：非零但小于 10ns 多半是把 us 数值直接当成了 ns
+    pub const MIN_PLAUSIBLE_LINK_LATENCY: SimTime = SimTime(10);
+
+    pub fn from_nanos(ns: u64) -> SimTime {
+        SimTime(ns)
+    }
     pub fn from_micros(us: u64) -> SimTime {
         SimTime(us.saturating_mul(1_000))
     }
@@ -17,4 +25,32 @@ impl SimTime {
     pub fn from_secs(s: u64) -> SimTime {
         SimTime(s.saturating_mul(1_000_000_000))
     }
+    /// 从浮点秒数构造（四舍五入到 ns，超出范围饱和）。负数或 NaN 会 panic。
+    pub fn from_secs_f64(s: f64) -> SimTime {
+        assert!(
+            s >= 0.0,
+            "SimTime::from_secs_f64 expects a non-negative value, got {s}"
+        );
+        SimTime((s * 1e9).round() as u64)
+    }
+    pub fn as_secs_f64(self) -> f64 {
+        self.0 as f64 / 1e9
+    }
+
+    /// 检查作为链路时延是否在合理范围内；不合理时返回说明（多半是单位用错）。
+    pub fn link_latency_unit_issue(self) -> Option<String> {
+        if self > Self::MAX_PLAUSIBLE_LINK_LATENCY {
+            return Some(format!(
+                "link latency {} ns exceeds 1 s; was a ms/s value passed where ns is expected?",
+                self.0
+            ));
+        }
+        if self > Self::ZERO && self < Self::MIN_PLAUSIBLE_LINK_LATENCY {
+            return Some(format!(
+                "link latency {} ns is below 10 ns; was a us value passed where ns is expected?",
+                self.0
+            ));
+        }
+        None
+    }
 }
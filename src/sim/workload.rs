@@ -57,7 +57,7 @@ pub struct WorkloadDefaults {
     pub bytes_per_element: Option<u64>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
 #[serde(rename_all = "snake_case")]
 pub enum TransportProtocol {
     Tcp,
@@ -85,6 +85,10 @@ pub struct HostSpec {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GpuSpec {
     pub model: String,
+    /// Optional peak throughput in FLOP/s; compute steps that give `flops`
+    /// take `flops / peak_flops` seconds on this GPU.
+    #[serde(default)]
+    pub peak_flops: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -122,6 +126,15 @@ pub enum RankStepKind {
     /// block when they reach an explicit wait.
     CollectiveWait,
     Sendrecv,
+    /// Block until every rank in `hosts` (default: all hosts) has reached the
+    /// barrier with the same `comm_id`, then release them all at once.
+    ///
+    /// Moves no data, so unlike a zero-byte collective it starts no flows.
+    Barrier,
+    /// Send `req_bytes` to `peer`, wait for `resp_bytes` to come back, then continue.
+    ///
+    /// The peer serves the response on its own; it needs no matching step.
+    Rpc,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -143,6 +156,10 @@ pub struct RankStepSpec {
     pub op: Option<String>,
     #[serde(default)]
     pub compute_ms: Option<f64>,
+    /// Optional work of a compute step in FLOPs. When the rank's GPU has a
+    /// `peak_flops` this sets the duration and `compute_ms` is ignored.
+    #[serde(default)]
+    pub flops: Option<f64>,
     #[serde(default)]
     pub comm_bytes: Option<u64>,
     #[serde(default)]
@@ -153,10 +170,51 @@ pub struct RankStepSpec {
     /// comm step on that stream will wait for prior async comm to complete.
     #[serde(default)]
     pub comm_stream: Option<u32>,
+    /// Optional `comm_id`s of earlier async collectives this step must wait for.
+    ///
+    /// Unlike `collective_wait`, the step only blocks on the named collectives
+    /// and may start while other async collectives are still in flight.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
     #[serde(default)]
     pub hosts: Option<Vec<usize>>,
     #[serde(default)]
     pub peer: Option<usize>,
     #[serde(default)]
     pub direction: Option<SendRecvDirection>,
+    /// Optional sendrecv tag: a send and a recv on the same `comm_id` only
+    /// match when their tags are equal (both absent counts as equal).
+    #[serde(default)]
+    pub tag: Option<u64>,
+    /// Request payload of an `rpc` step, sent to `peer`.
+    #[serde(default)]
+    pub req_bytes: Option<u64>,
+    /// Response payload of an `rpc` step, sent back by `peer`.
+    #[serde(default)]
+    pub resp_bytes: Option<u64>,
+    /// Optional gradient compression ratio in (0, 1] applied to `comm_bytes`
+    /// before a collective launches its flows (e.g. 0.25 sends a quarter).
+    #[serde(default)]
+    pub compression: Option<f64>,
+    /// Optional per-rank decompression cost, charged after the collective completes.
+    #[serde(default)]
+    pub compression_ms: Option<f64>,
+    /// Optional per-pair message sizes for `alltoall` (e.g. skewed MoE routing):
+    /// `alltoall_matrix[i][j]` is the bytes `hosts[i]` sends to `hosts[j]`.
+    /// When set it replaces the uniform split of `comm_bytes`.
+    #[serde(default)]
+    pub alltoall_matrix: Option<Vec<Vec<u64>>>,
+    /// Optional initial congestion window (bytes) for the flows of this
+    /// collective, overriding the simulator-wide transport config.
+    #[serde(default)]
+    pub init_cwnd_bytes: Option<u64>,
+    /// Optional initial slow-start threshold (bytes) for the flows of this
+    /// collective, overriding the simulator-wide transport config.
+    #[serde(default)]
+    pub init_ssthresh_bytes: Option<u64>,
+    /// Optional transport for the flows of this step, overriding the workload
+    /// default so tenants on a shared fabric can run different protocols.
+    /// Both sides of a sendrecv must agree when both set it.
+    #[serde(default)]
+    pub protocol: Option<TransportProtocol>,
 }
@@ -0,0 +1,84 @@
+use crate::analysis::{Histogram, percentile};
+
+#[test]
+fn percentile_handles_empty_and_clamps_out_of_range_p() {
+    assert_eq!(percentile(&[], 0.5), None);
+    assert_eq!(percentile(&[], 0.0), None);
+
+    let v = [30, 10, 20];
+    assert_eq!(percentile(&v, 0.0), Some(10));
+    assert_eq!(percentile(&v, -1.0), Some(10));
+    assert_eq!(percentile(&v, 1.0), Some(30));
+    assert_eq!(percentile(&v, 7.5), Some(30));
+}
+
+#[test]
+fn percentile_uses_nearest_rank_for_interior_p() {
+    let v: Vec<u64> = (1..=100).rev().collect();
+    assert_eq!(percentile(&v, 0.5), Some(50));
+    assert_eq!(percentile(&v, 0.99), Some(99));
+    assert_eq!(percentile(&v, 0.001), Some(1));
+    assert_eq!(percentile(&v, 0.505), Some(51));
+
+    let v = [5, 1, 9, 7];
+    assert_eq!(percentile(&v, 0.25), Some(1));
+    assert_eq!(percentile(&v, 0.26), Some(5));
+    assert_eq!(percentile(&v, 0.75), Some(7));
+    assert_eq!(percentile(&[42], 0.3), Some(42));
+}
+
+#[test]
+fn histogram_buckets_values_by_upper_bound() {
+    let mut h = Histogram::new(vec![10, 100, 1_000]);
+    for v in [0, 10, 11, 100, 500, 1_000, 1_001, 50_000] {
+        h.record(v);
+    }
+    assert_eq!(h.count(), 8);
+    assert_eq!(h.min(), Some(0));
+    assert_eq!(h.max(), Some(50_000));
+    assert_eq!(
+        h.buckets().collect::<Vec<_>>(),
+        vec![(Some(10), 2), (Some(100), 2), (Some(1_000), 2), (None, 2)]
+    );
+
+    assert_eq!(
+        Histogram::linear(5, 3)
+            .buckets()
+            .map(|b| b.0)
+            .collect::<Vec<_>>(),
+        vec![Some(5), Some(10), Some(15), None]
+    );
+    assert_eq!(
+        Histogram::exponential(1, 10, 4)
+            .buckets()
+            .map(|b| b.0)
+            .collect::<Vec<_>>(),
+        vec![Some(1), Some(10), Some(100), Some(1_000), None]
+    );
+}
+
+#[test]
+fn histogram_percentile_returns_bucket_bound_of_the_ranked_sample() {
+    let empty = Histogram::linear(10, 10);
+    assert_eq!(empty.percentile(0.5), None);
+
+    let mut h = Histogram::linear(10, 10);
+    for v in 1..=100 {
+        h.record(v);
+    }
+    assert_eq!(h.percentile(0.0), Some(10));
+    assert_eq!(h.percentile(0.5), Some(50));
+    assert_eq!(h.percentile(0.55), Some(60));
+    assert_eq!(h.percentile(1.0), Some(100));
+
+    // Samples past the last bound report the observed maximum.
+    let mut h = Histogram::new(vec![10]);
+    h.record(3);
+    h.record(250);
+    assert_eq!(h.percentile(0.5), Some(10));
+    assert_eq!(h.percentile(0.99), Some(250));
+    // A bucket bound never overshoots the largest value seen.
+    let mut h = Histogram::new(vec![1_000]);
+    h.record(7);
+    assert_eq!(h.percentile(0.5), Some(7));
+}
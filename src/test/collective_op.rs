@@ -1,4 +1,5 @@
-use crate::cc::collective::CollectiveOp;
+use crate::cc::collective::{CollectiveOp, CostEstimate};
+use crate::sim::SimTime;
 
 #[test]
 fn collective_op_steps_edge_cases() {
@@ -73,3 +74,56 @@ fn parse_collective_op_trims_whitespace() {
     );
     assert_eq!(CollectiveOp::parse("   ").unwrap(), CollectiveOp::Allreduce);
 }
+
+#[test]
+fn ring_allreduce_cost_model_matches_alpha_beta_formula() {
+    let latency = SimTime::from_micros(2);
+    let gbps = 100.0;
+    for ranks in [2_usize, 4, 8, 16] {
+        for bytes in [1_u64 << 10, 1 << 20, 64 << 20, 1_000_003] {
+            let n = ranks as f64;
+            let bw_bytes_per_ns = gbps / 8.0;
+            let expected = 2.0 * (n - 1.0) / n * bytes as f64 / bw_bytes_per_ns
+                + 2.0 * (n - 1.0) * latency.0 as f64;
+            let est = CollectiveOp::Allreduce.cost_model(ranks, bytes, gbps, latency);
+            assert_eq!(
+                est.time_ns,
+                expected.round() as u64,
+                "ranks={ranks} bytes={bytes}"
+            );
+            let chunk = bytes.div_ceil(ranks as u64);
+            assert_eq!(
+                est.bytes_on_wire,
+                2 * (ranks as u64 - 1) * chunk * ranks as u64
+            );
+        }
+    }
+}
+
+#[test]
+fn cost_model_scales_steps_per_op() {
+    let latency = SimTime(1000);
+    let bytes = 4_000_000;
+    // 8 bytes per ns at 64Gbps.
+    let est = |op: CollectiveOp| op.cost_model(4, bytes, 64.0, latency);
+    assert_eq!(
+        est(CollectiveOp::Reducescatter),
+        CostEstimate {
+            time_ns: 3 * (125_000 + 1000),
+            bytes_on_wire: 3 * bytes,
+        }
+    );
+    // Allgather's comm_bytes is each rank's contribution.
+    assert_eq!(est(CollectiveOp::Allgather).time_ns, 3 * (500_000 + 1000));
+    assert_eq!(
+        est(CollectiveOp::Allreduce).time_ns,
+        2 * est(CollectiveOp::Reducescatter).time_ns
+    );
+    assert_eq!(
+        CollectiveOp::Allreduce.cost_model(1, bytes, 64.0, latency),
+        CostEstimate {
+            time_ns: 0,
+            bytes_on_wire: 0,
+        }
+    );
+}
@@ -1,7 +1,9 @@
-use crate::net::NetWorld;
-use crate::proto::dctcp::{DctcpConfig, DctcpConn};
+use crate::net::{DctcpSegment, NetWorld, Transport};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpEcnEchoMode};
 use crate::sim::{SimTime, Simulator};
 use crate::viz::{VizCwndReason, VizEventKind, VizLogger};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[test]
 fn dctcp_emits_ecn_window_cwnd_event_when_link_marks_ce() {
@@ -56,3 +58,280 @@ fn dctcp_emits_ecn_window_cwnd_event_when_link_marks_ce() {
         "expected at least one DctcpEcnWindow cwnd event"
     );
 }
+
+/// Run one DCTCP flow h0 -> s0 -> h1; returns (packets seen by the middlebox,
+/// max alpha reported in ECN-window cwnd events).
+fn run_dctcp_through_switch(mark_at_switch: bool) -> (usize, f64) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+
+    let h0 = world.net.add_host("h0");
+    let s0 = world.net.add_switch("s0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let bw = 100_u64 * 1_000_000_000;
+    for (a, b) in [(h0, s0), (s0, h1)] {
+        world.net.connect(a, b, latency, bw);
+        world.net.connect(b, a, latency, bw);
+    }
+
+    let seen = Arc::new(AtomicUsize::new(0));
+    if mark_at_switch {
+        let seen = Arc::clone(&seen);
+        world.net.add_middlebox(s0, move |pkt, ctx| {
+            assert_eq!(ctx.node, s0);
+            seen.fetch_add(1, Ordering::Relaxed);
+            pkt.mark_ce_if_ect();
+        });
+    }
+    world.net.viz = Some(VizLogger::default());
+
+    let cfg = DctcpConfig::default();
+    let total_bytes = cfg.init_cwnd_bytes.max(cfg.mss as u64).saturating_mul(2);
+    let conn = DctcpConn::new(1, h0, h1, vec![h0, s0, h1], total_bytes, cfg);
+    let mut stack = std::mem::take(&mut world.net.dctcp);
+    stack.start_conn(conn, &mut sim, &mut world.net);
+    world.net.dctcp = stack;
+
+    sim.run(&mut world);
+    assert!(world.net.dctcp.get(1).expect("conn").is_done());
+
+    let max_alpha = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter_map(|ev| match &ev.kind {
+            VizEventKind::DctcpCwnd {
+                reason: VizCwndReason::DctcpEcnWindow,
+                alpha,
+                ..
+            } => Some(*alpha),
+            _ => None,
+        })
+        .fold(0.0, f64::max);
+    (seen.load(Ordering::Relaxed), max_alpha)
+}
+
+#[test]
+fn middlebox_marking_ce_at_switch_is_echoed_by_dctcp_receiver() {
+    let (seen, alpha) = run_dctcp_through_switch(true);
+    // Data goes s0 -> h1 and ACKs come back s0 -> h0; both pass the hook.
+    assert!(seen > 0);
+    assert!(
+        alpha > 0.0,
+        "receiver did not echo CE marks (alpha={alpha})"
+    );
+
+    let (seen, alpha) = run_dctcp_through_switch(false);
+    assert_eq!(seen, 0);
+    assert_eq!(alpha, 0.0);
+}
+
+/// Run one long DCTCP flow through a switch whose middlebox marks every
+/// `mark_every`-th data packet CE; returns (final alpha, ACKs seen at the switch).
+fn run_marked_stream(mode: DctcpEcnEchoMode, mark_every: usize) -> (f64, usize) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+
+    let h0 = world.net.add_host("h0");
+    let s0 = world.net.add_switch("s0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let bw = 100_u64 * 1_000_000_000;
+    for (a, b) in [(h0, s0), (s0, h1)] {
+        world.net.connect(a, b, latency, bw);
+        world.net.connect(b, a, latency, bw);
+    }
+
+    let data = Arc::new(AtomicUsize::new(0));
+    let acks = Arc::new(AtomicUsize::new(0));
+    {
+        let (data, acks) = (Arc::clone(&data), Arc::clone(&acks));
+        world.net.add_middlebox(s0, move |pkt, _ctx| {
+            if matches!(pkt.transport, Transport::Dctcp(DctcpSegment::Ack { .. })) {
+                acks.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            if (data.fetch_add(1, Ordering::Relaxed) + 1) % mark_every == 0 {
+                pkt.mark_ce_if_ect();
+            }
+        });
+    }
+
+    // Start in congestion avoidance so the flow spans hundreds of alpha windows.
+    let cfg = DctcpConfig {
+        init_ssthresh_bytes: 10 * 1460,
+        ecn_echo_mode: mode,
+        ..DctcpConfig::default()
+    };
+    let total_bytes = (cfg.mss as u64).saturating_mul(4_000);
+    let conn = DctcpConn::new(1, h0, h1, vec![h0, s0, h1], total_bytes, cfg);
+    let mut stack = std::mem::take(&mut world.net.dctcp);
+    stack.start_conn(conn, &mut sim, &mut world.net);
+    world.net.dctcp = stack;
+
+    sim.run(&mut world);
+    let conn = world.net.dctcp.get(1).expect("conn");
+    assert!(conn.is_done(), "{mode:?} flow did not finish");
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    (conn.alpha(), acks.load(Ordering::Relaxed))
+}
+
+#[test]
+fn dctcp_acks_are_ect_and_marked_under_reverse_congestion() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+
+    // h0 -> s0 -> s1 -> h1; only the ACK direction s1 -> s0 marks.
+    let h0 = world.net.add_host("h0");
+    let s0 = world.net.add_switch("s0");
+    let s1 = world.net.add_switch("s1");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let bw = 100_u64 * 1_000_000_000;
+    for (a, b) in [(h0, s0), (s0, s1), (s1, h1)] {
+        world.net.connect(a, b, latency, bw);
+        world.net.connect(b, a, latency, bw);
+    }
+    world.net.set_link_ecn_threshold_bytes(s1, s0, 1);
+
+    let acks = Arc::new(AtomicUsize::new(0));
+    let unmarked_acks = Arc::new(AtomicUsize::new(0));
+    {
+        let (acks, unmarked_acks) = (Arc::clone(&acks), Arc::clone(&unmarked_acks));
+        world.net.add_middlebox(s0, move |pkt, _ctx| {
+            if !matches!(pkt.transport, Transport::Dctcp(DctcpSegment::Ack { .. })) {
+                return;
+            }
+            acks.fetch_add(1, Ordering::Relaxed);
+            if !pkt.ecn.is_ce() {
+                unmarked_acks.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+    }
+
+    let cfg = DctcpConfig::default();
+    let total_bytes = (cfg.mss as u64).saturating_mul(100);
+    let conn = DctcpConn::new(1, h0, h1, vec![h0, s0, s1, h1], total_bytes, cfg);
+    let mut stack = std::mem::take(&mut world.net.dctcp);
+    stack.start_conn(conn, &mut sim, &mut world.net);
+    world.net.dctcp = stack;
+
+    sim.run(&mut world);
+    let conn = world.net.dctcp.get(1).expect("conn");
+    assert!(conn.is_done());
+
+    // Every ACK was ECT, so the congested reverse link marked it CE.
+    assert!(acks.load(Ordering::Relaxed) > 0);
+    assert_eq!(unmarked_acks.load(Ordering::Relaxed), 0);
+    // Marks on ACKs are not echoed back as forward congestion.
+    assert_eq!(conn.alpha(), 0.0);
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+}
+
+#[test]
+fn classic_ecn_echo_makes_alpha_converge_to_marked_fraction() {
+    let (per_ack_alpha, per_ack_acks) = run_marked_stream(DctcpEcnEchoMode::PerAck, 4);
+    let (classic_alpha, classic_acks) = run_marked_stream(DctcpEcnEchoMode::Classic, 4);
+
+    // One data packet in four carries CE.
+    for alpha in [per_ack_alpha, classic_alpha] {
+        assert!((alpha - 0.25).abs() < 0.03, "alpha {alpha}");
+    }
+    // Classic coalesces ACKs, yet the CE state machine still echoes every
+    // marked byte exactly.
+    assert!(
+        classic_acks < per_ack_acks * 3 / 4,
+        "classic {classic_acks} ACKs vs per-ack {per_ack_acks}"
+    );
+}
+
+#[test]
+fn ecn_echo_mode_parses_cli_names() {
+    assert_eq!(
+        DctcpEcnEchoMode::parse("per_ack"),
+        Ok(DctcpEcnEchoMode::PerAck)
+    );
+    assert_eq!(
+        DctcpEcnEchoMode::parse("Classic"),
+        Ok(DctcpEcnEchoMode::Classic)
+    );
+    assert!(DctcpEcnEchoMode::parse("l4s").is_err());
+}
+
+/// Two DCTCP flows share a 10Gbps bottleneck s0 -> h2 whose ECN marking is
+/// either a step threshold or RED; returns flow 1's alpha after every ACK,
+/// skipping the first quarter of the run.
+fn run_shared_bottleneck_alpha(red: bool) -> Vec<f64> {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let s0 = world.net.add_switch("s0");
+    let h2 = world.net.add_host("h2");
+    let latency = SimTime::from_micros(40);
+    let bw = 10_u64 * 1_000_000_000;
+    for (a, b) in [(h0, s0), (h1, s0), (s0, h2)] {
+        world.net.connect(a, b, latency, bw);
+        world.net.connect(b, a, latency, bw);
+    }
+    let pkt = 1500;
+    if red {
+        world.net.set_all_link_red_ecn(5 * pkt, 35 * pkt, 1.0);
+    } else {
+        world.net.set_all_link_ecn_threshold_bytes(20 * pkt);
+    }
+
+    // A larger gain lets the per-window marked fraction show through in alpha.
+    let cfg = DctcpConfig {
+        g: 0.25,
+        init_ssthresh_bytes: 10 * 1460,
+        ..DctcpConfig::default()
+    };
+    let mut stack = std::mem::take(&mut world.net.dctcp);
+    for (id, src) in [(1, h0), (2, h1)] {
+        let mut conn = DctcpConn::new(id, src, h2, vec![src, s0, h2], 2_000_000, cfg.clone());
+        if id == 1 {
+            conn.enable_cwnd_log();
+        }
+        stack.start_conn(conn, &mut sim, &mut world.net);
+    }
+    world.net.dctcp = stack;
+
+    sim.run(&mut world);
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    let conn = world.net.dctcp.get(1).expect("conn");
+    assert!(conn.is_done());
+    let samples = conn.cwnd_samples().expect("cwnd log enabled");
+    samples[samples.len() / 4..]
+        .iter()
+        .map(|s| s.alpha)
+        .collect()
+}
+
+fn mean_and_std_dev(xs: &[f64]) -> (f64, f64) {
+    let n = xs.len() as f64;
+    let mean = xs.iter().sum::<f64>() / n;
+    let var = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+
+#[test]
+fn red_ecn_marking_gives_a_smoother_alpha_than_a_step_threshold() {
+    let (step_mean, step_sd) = mean_and_std_dev(&run_shared_bottleneck_alpha(false));
+    let (red_mean, red_sd) = mean_and_std_dev(&run_shared_bottleneck_alpha(true));
+
+    // Both settle on a partial-marking alpha rather than 0 or 1.
+    for mean in [step_mean, red_mean] {
+        assert!(mean > 0.05 && mean < 0.8, "mean alpha {mean}");
+    }
+    // The step marks whole windows at once, so alpha swings further.
+    assert!(
+        red_sd < step_sd * 0.85,
+        "RED alpha sd {red_sd} vs step {step_sd}"
+    );
+}
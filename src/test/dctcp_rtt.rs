@@ -0,0 +1,92 @@
+use crate::net::NetWorld;
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u32 = 1000;
+
+#[test]
+fn min_rtt_on_dumbbell_is_propagation_plus_one_segment_serialization() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let opts = DumbbellOpts {
+        host_link_gbps: 100,
+        bottleneck_gbps: 10,
+        link_latency: SimTime::from_micros(2),
+        ..DumbbellOpts::default()
+    };
+    let (h0, h1, route) = build_dumbbell(&mut world, &opts);
+    let cfg = DctcpConfig {
+        mss: MSS,
+        ack_bytes: 125,
+        ..DctcpConfig::default()
+    };
+    let conn = DctcpConn::new(1, h0, h1, route, 50 * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, DctcpStart { conn });
+    sim.run(&mut world);
+
+    let conn = world.net.dctcp.get(1).unwrap();
+    assert!(conn.is_done());
+    // Three hops each way at 2us; a 1000B segment takes 80ns + 800ns + 80ns
+    // to serialize and a 125B ACK 10ns + 100ns + 10ns.
+    let expected = 6 * 2_000 + (80 + 800 + 80) + (10 + 100 + 10);
+    assert_eq!(conn.min_rtt(), Some(SimTime(expected)));
+}
+
+#[test]
+fn rto_adapts_to_measured_rtt_instead_of_initial_value() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(10);
+    world.net.connect(h0, h1, latency, 10_000_000_000);
+    world.net.connect(h1, h0, latency, 10_000_000_000);
+    world.net.viz = Some(VizLogger::default());
+
+    let init_rto = SimTime::from_millis(10);
+    let cfg = DctcpConfig {
+        mss: MSS,
+        init_rto,
+        min_rto: SimTime::from_micros(10),
+        ..DctcpConfig::default()
+    };
+    let conn = DctcpConn::new_dynamic(1, h0, h1, 500 * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, DctcpStart { conn });
+
+    // Black-hole the data path for a while mid-transfer: every in-flight
+    // segment is lost, so there are no dupACKs and only the RTO can recover.
+    sim.run_until(SimTime::from_micros(100), &mut world);
+    let conn = world.net.dctcp.get(1).unwrap();
+    let srtt = conn.srtt().expect("rtt samples before the outage");
+    assert!(conn.rto() < init_rto);
+    assert_eq!(
+        conn.rto().0,
+        srtt.0 + 4 * conn.rttvar().0,
+        "rto = srtt + 4*rttvar above min_rto"
+    );
+    world.net.set_link_loss(h0, h1, 1.0, 1);
+    sim.run_until(SimTime::from_micros(150), &mut world);
+    world.net.set_link_loss(h0, h1, 0.0, 1);
+    sim.run(&mut world);
+
+    assert!(world.net.stats.dropped_random > 0);
+    let conn = world.net.dctcp.get(1).unwrap();
+    assert!(conn.is_done());
+    let rtos = world
+        .net
+        .viz
+        .as_ref()
+        .unwrap()
+        .events
+        .iter()
+        .filter(|ev| matches!(ev.kind, VizEventKind::TcpRto(_)))
+        .count();
+    assert!(rtos > 0, "expected the outage to be recovered by RTO");
+    assert!(
+        conn.done_time().unwrap() < init_rto,
+        "finished at {:?}, no earlier than the initial RTO",
+        conn.done_time()
+    );
+}
@@ -0,0 +1,64 @@
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn};
+use crate::sim::{Event, SimTime, Simulator, World, check_determinism};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+fn run_two_tcp_flows(sim: &mut Simulator) -> ((), String) {
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    for id in 1..=2 {
+        let conn = TcpConn::new_dynamic(id, h0, h1, 200_000, TcpConfig::default());
+        tcp.start_conn(conn, sim, &mut world.net);
+    }
+    world.net.tcp = tcp;
+    sim.run(&mut world);
+    let stats = format!(
+        "now={:?} delivered={} dropped={}",
+        sim.now(),
+        world.net.stats.delivered_pkts,
+        world.net.stats.dropped_pkts
+    );
+    ((), stats)
+}
+
+#[test]
+fn deterministic_sim_passes_determinism_check() {
+    let (sim, ()) = check_determinism(run_two_tcp_flows).expect("sim should be deterministic");
+    assert!(!sim.event_trace().is_empty());
+    assert!(sim.event_trace().windows(2).all(|w| w[0].at <= w[1].at));
+}
+
+/// Schedules a follow-up whose delay depends on how often it has ever run,
+/// so a second identical run diverges.
+struct Flaky;
+
+static FLAKY_RUNS: AtomicU64 = AtomicU64::new(0);
+
+impl Event for Flaky {
+    fn execute(self: Box<Self>, sim: &mut Simulator, _world: &mut dyn World) {
+        let n = FLAKY_RUNS.fetch_add(1, Ordering::Relaxed);
+        sim.schedule(SimTime(sim.now().0 + 10 + n), Noop);
+    }
+}
+
+struct Noop;
+
+impl Event for Noop {
+    fn execute(self: Box<Self>, _sim: &mut Simulator, _world: &mut dyn World) {}
+}
+
+#[test]
+fn nondeterministic_event_fails_determinism_check() {
+    let result = check_determinism(|sim| {
+        let mut world = NetWorld::default();
+        sim.schedule(SimTime::ZERO, Flaky);
+        sim.run(&mut world);
+        ((), String::new())
+    });
+    let Err(err) = result else {
+        panic!("divergent runs must be reported");
+    };
+    assert!(err.contains("event #1 differs"), "{err}");
+}
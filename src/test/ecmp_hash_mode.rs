@@ -1,4 +1,7 @@
-use crate::net::{DeliverPacket, EcmpHashMode, NetWorld, NodeId, Packet, RoutingTable};
+use crate::net::{
+    DEFAULT_ECMP_SEED, DeliverPacket, EcmpHashInputs, EcmpHashMode, NetWorld, NodeId, Packet,
+    RoutingTable,
+};
 use crate::sim::{SimTime, Simulator};
 use crate::viz::{VizEventKind, VizLogger};
 
@@ -58,10 +61,11 @@ fn ecmp_hash_mode_flow_pins_packets_to_same_next_hop() {
     let forwards = s0_forwards(&world, s0);
     assert_eq!(forwards.len(), 2);
 
-    // In flow mode, both packets should pick the same ECMP next hop for a given flow_id.
+    // In flow mode, both packets should pick the same ECMP next hop for a given
+    // (src, dst, flow_id).
     let rt = RoutingTable::new(0xC5A1_DA7A_5EED_1234);
     let cands = vec![s1, s2];
-    let expected = rt.pick_ecmp_with_key(s0, h1, flow_id, &cands).0;
+    let expected = rt.pick_ecmp(s0, h0, h1, flow_id, &cands).0;
 
     for (pkt_id, next) in forwards {
         assert!(matches!(pkt_id, 10 | 11));
@@ -83,10 +87,14 @@ fn ecmp_hash_mode_packet_can_spread_packets_across_next_hops() {
 
     // Find two packet ids that deterministically map to different next hops.
     let rt = RoutingTable::new(0xC5A1_DA7A_5EED_1234);
+    let inputs = EcmpHashInputs {
+        mode: EcmpHashMode::Packet,
+        ..Default::default()
+    };
     let mut chosen: Option<(u64, NodeId)> = None;
     let mut ids: Option<(u64, u64, NodeId, NodeId)> = None;
     for pkt_id in 0..2048_u64 {
-        let key = flow_id ^ pkt_id;
+        let key = inputs.key(h0, h1, flow_id, pkt_id);
         let nh = rt.pick_ecmp_with_key(s0, h1, key, &cands);
         if let Some((first_id, first_nh)) = chosen {
             if first_nh != nh {
@@ -115,3 +123,159 @@ fn ecmp_hash_mode_packet_can_spread_packets_across_next_hops() {
     assert_eq!(forwards[1].0, pkt_id1);
     assert_eq!(forwards[1].1, nh1.0);
 }
+
+#[test]
+fn ecmp_flow_entropy_can_split_flows_between_same_pair() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    world.net.viz = Some(VizLogger::default());
+    let (h0, h1, s0, s1, s2) = build_diamond(&mut world);
+
+    world.net.set_ecmp_hash_mode(EcmpHashMode::FlowEntropy);
+
+    // Two flows between the same src/dst; without entropy both would use flow_id only.
+    let (flow_a, flow_b) = (5_u64, 6_u64);
+    let cands = vec![s1, s2];
+    let rt = RoutingTable::new(0xC5A1_DA7A_5EED_1234);
+    let mut inputs = EcmpHashInputs {
+        mode: EcmpHashMode::FlowEntropy,
+        ..Default::default()
+    };
+    inputs.flow_entropy.insert(flow_a, 0);
+    let nh_a = rt.pick_ecmp_with_key(s0, h1, inputs.flow_key(h0, h1, flow_a), &cands);
+
+    // Search for an entropy value that sends flow_b down the other path.
+    let entropy_b = (1..1024_u64)
+        .find(|&e| {
+            inputs.flow_entropy.insert(flow_b, e);
+            rt.pick_ecmp_with_key(s0, h1, inputs.flow_key(h0, h1, flow_b), &cands) != nh_a
+        })
+        .expect("failed to find entropy that changes ECMP hop");
+
+    world.net.set_flow_ecmp_entropy(flow_a, 0);
+    world.net.set_flow_ecmp_entropy(flow_b, entropy_b);
+
+    let pkt_a = Packet::new_dynamic(1, flow_a, 100, h0, h1);
+    let pkt_b = Packet::new_dynamic(2, flow_b, 100, h0, h1);
+    sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt: pkt_a });
+    sim.schedule(
+        SimTime::from_micros(1),
+        DeliverPacket { to: h0, pkt: pkt_b },
+    );
+    sim.run(&mut world);
+
+    let mut forwards = s0_forwards(&world, s0);
+    forwards.sort_by_key(|(pkt_id, _)| *pkt_id);
+    assert_eq!(forwards.len(), 2);
+    assert_eq!(forwards[0].1, nh_a.0);
+    assert_ne!(forwards[0].1, forwards[1].1);
+
+    // Static per-flow paths follow the same entropy-aware key.
+    let path_a = world.net.route_ecmp_path(h0, h1, flow_a);
+    let path_b = world.net.route_ecmp_path(h0, h1, flow_b);
+    assert_ne!(path_a[2], path_b[2]);
+}
+
+#[test]
+fn ecmp_flow_entropy_defaults_to_zero_for_unconfigured_flows() {
+    let inputs = EcmpHashInputs {
+        mode: EcmpHashMode::FlowEntropy,
+        ..Default::default()
+    };
+    let mut with_zero = inputs.clone();
+    with_zero.flow_entropy.insert(42, 0);
+    let (src, dst) = (NodeId(0), NodeId(1));
+    assert_eq!(
+        inputs.flow_key(src, dst, 42),
+        with_zero.flow_key(src, dst, 42)
+    );
+    // The packet id never contributes in entropy mode.
+    assert_eq!(inputs.key(src, dst, 42, 1), inputs.key(src, dst, 42, 2));
+}
+
+#[test]
+fn ecmp_seed_changes_next_hops_deterministically() {
+    // Next hop chosen at s0 (index 2 of h0 -> s0 -> {s1, s2} -> ...) for 64 flows.
+    let choices = |seed: Option<u64>| -> Vec<NodeId> {
+        let mut world = NetWorld::default();
+        let (h0, h1, _s0, _s1, _s2) = build_diamond(&mut world);
+        if let Some(seed) = seed {
+            world.net.set_ecmp_seed(seed);
+        }
+        (0..64)
+            .map(|flow_id| world.net.route_ecmp_path(h0, h1, flow_id)[2])
+            .collect()
+    };
+
+    let a = choices(Some(1));
+    let b = choices(Some(2));
+    assert_eq!(a, choices(Some(1)));
+    assert_eq!(b, choices(Some(2)));
+    assert_ne!(a, b);
+    assert_eq!(choices(None), choices(Some(DEFAULT_ECMP_SEED)));
+}
+
+#[test]
+fn set_ecmp_seed_after_routing_rebuilds_choices() {
+    let mut world = NetWorld::default();
+    let (h0, h1, _s0, _s1, _s2) = build_diamond(&mut world);
+    let before: Vec<NodeId> = (0..64)
+        .map(|flow_id| world.net.route_ecmp_path(h0, h1, flow_id)[2])
+        .collect();
+    world.net.set_ecmp_seed(7);
+    let after: Vec<NodeId> = (0..64)
+        .map(|flow_id| world.net.route_ecmp_path(h0, h1, flow_id)[2])
+        .collect();
+    assert_ne!(before, after);
+}
+
+#[test]
+fn flowlet_mode_rehashes_only_after_idle_gaps() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    world.net.viz = Some(VizLogger::default());
+    let (h0, h1, s0, _s1, _s2) = build_diamond(&mut world);
+    world.net.set_ecmp_hash_mode(EcmpHashMode::Flowlet {
+        gap: SimTime::from_micros(20),
+    });
+
+    // Flow 1: 16 bursts of 4 packets 1us apart, bursts 100us apart.
+    // Flow 2: 64 packets 1us apart, never idle long enough to start a new flowlet.
+    let (bursts, per_burst) = (16_u64, 4_u64);
+    for b in 0..bursts {
+        for i in 0..per_burst {
+            let at = SimTime::from_micros(b * 100 + i);
+            let pkt = Packet::new_dynamic(b * per_burst + i, 1, 100, h0, h1);
+            sim.schedule(at, DeliverPacket { to: h0, pkt });
+        }
+    }
+    for i in 0..64 {
+        let pkt = Packet::new_dynamic(1000 + i, 2, 100, h0, h1);
+        sim.schedule(SimTime::from_micros(i), DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+
+    let forwards = s0_forwards(&world, s0);
+    let next_of = |pkt_id: u64| {
+        forwards
+            .iter()
+            .find(|(id, _)| *id == pkt_id)
+            .map(|(_, next)| *next)
+            .expect("packet forwarded at s0")
+    };
+
+    let mut burst_hops = Vec::new();
+    for b in 0..bursts {
+        let first = next_of(b * per_burst);
+        for i in 1..per_burst {
+            assert_eq!(next_of(b * per_burst + i), first, "burst {b} split");
+        }
+        burst_hops.push(first);
+    }
+    // Each burst is a new flowlet, so the flow moves between both paths over time.
+    let distinct: std::collections::HashSet<_> = burst_hops.iter().collect();
+    assert_eq!(distinct.len(), 2, "bursts never rebalanced: {burst_hops:?}");
+
+    let steady = next_of(1000);
+    assert!((1000..1064).all(|id| next_of(id) == steady));
+}
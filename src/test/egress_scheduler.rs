@@ -0,0 +1,285 @@
+use std::sync::{Arc, Mutex};
+
+use crate::net::{DeliverPacket, NetWorld, NodeId, TcpSegment, Transport, jain_fairness_index};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::queue::EgressScheduler;
+use crate::sim::{SimTime, Simulator};
+
+/// Two bulk flows share the h0->h1 egress; the second starts 1ms after the
+/// first. Returns (bytes acked by flow 1, by flow 2) between 2ms and 6ms.
+fn acked_in_window(scheduler: EgressScheduler) -> (u64, u64) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(2);
+    world.net.connect(h0, h1, latency, 1_000_000_000);
+    world.net.connect(h1, h0, latency, 1_000_000_000);
+    world.net.set_egress_scheduler(h0, scheduler);
+
+    let route: Vec<NodeId> = vec![h0, h1];
+    let bytes = 100_000_000;
+    let cfg = TcpConfig::default();
+    for (id, start) in [(1, SimTime::ZERO), (2, SimTime::from_millis(1))] {
+        let conn = TcpConn::new(id, h0, h1, route.clone(), bytes, cfg.clone());
+        sim.schedule(start, TcpStart { conn });
+    }
+
+    let acked = |world: &NetWorld, id| world.net.tcp.get(id).map_or(0, |c| c.bytes_acked());
+    sim.run_until(SimTime::from_millis(2), &mut world);
+    let (a0, b0) = (acked(&world, 1), acked(&world, 2));
+    sim.run_until(SimTime::from_millis(6), &mut world);
+    (acked(&world, 1) - a0, acked(&world, 2) - b0)
+}
+
+#[test]
+fn flow_wrr_egress_shares_bandwidth_between_concurrent_flows() {
+    let (a, b) = acked_in_window(EgressScheduler::FlowWrr);
+    let ratio = a.max(b) as f64 / a.min(b).max(1) as f64;
+    assert!(ratio < 1.2, "wrr goodput a={a} b={b}");
+    // 4ms at 1Gbps is 500KB; the two flows together should use most of it.
+    assert!(a + b > 400_000, "wrr goodput a={a} b={b}");
+
+    // FIFO: flow 2's packets sit behind flow 1's standing backlog.
+    let (a, b) = acked_in_window(EgressScheduler::Fifo);
+    assert!(a > 2 * b, "fifo goodput a={a} b={b}");
+}
+
+/// Two raw-packet flows cross the s0->s1 bottleneck (1Gbps, 12us per packet):
+/// flow 1 (loose deadline) is enqueued first, flow 2 (tight deadline) 1us later.
+/// Returns each flow's completion time at its receiver.
+fn completion_with_deadlines(scheduler: EgressScheduler) -> [SimTime; 2] {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1) = (world.net.add_host("h0"), world.net.add_host("h1"));
+    let (s0, s1) = (world.net.add_switch("s0"), world.net.add_switch("s1"));
+    let (h2, h3) = (world.net.add_host("h2"), world.net.add_host("h3"));
+    let latency = SimTime::from_micros(1);
+    for (a, b, bps) in [
+        (h0, s0, 100_000_000_000),
+        (h1, s0, 100_000_000_000),
+        (s0, s1, 1_000_000_000),
+        (s1, h2, 100_000_000_000),
+        (s1, h3, 100_000_000_000),
+    ] {
+        world.net.connect(a, b, latency, bps);
+    }
+    world.net.set_egress_scheduler(s0, scheduler);
+    world.net.set_flow_deadline(1, SimTime::from_millis(10));
+    world.net.set_flow_deadline(2, SimTime::from_micros(150));
+
+    for (flow, src, dst, at) in [(1, h0, h2, 0), (2, h1, h3, 1_000)] {
+        for _ in 0..10 {
+            let pkt = world.net.make_packet(flow, 1500, vec![src, s0, s1, dst]);
+            assert_eq!(pkt.deadline, world.net.flow_deadline(flow));
+            sim.schedule(SimTime(at), DeliverPacket { to: src, pkt });
+        }
+    }
+
+    let mut done_at = [SimTime::ZERO; 2];
+    while sim.step(&mut world) {
+        for (k, dst) in [h2, h3].into_iter().enumerate() {
+            if done_at[k] == SimTime::ZERO && world.net.host_rx_bytes(dst) == 10 * 1500 {
+                done_at[k] = sim.now();
+            }
+        }
+    }
+    done_at
+}
+
+#[test]
+fn edf_egress_serves_tighter_deadline_flow_first() {
+    let deadline = SimTime::from_micros(150);
+
+    // FIFO: flow 2 waits behind all of flow 1 and misses its deadline.
+    let [fifo_1, fifo_2] = completion_with_deadlines(EgressScheduler::Fifo);
+    assert!(fifo_1 < fifo_2, "fifo: {fifo_1:?} {fifo_2:?}");
+    assert!(fifo_2 > deadline, "fifo: {fifo_2:?}");
+
+    // EDF: once flow 2 arrives its packets jump ahead of flow 1's backlog.
+    let [edf_1, edf_2] = completion_with_deadlines(EgressScheduler::Edf);
+    assert!(edf_2 < edf_1, "edf: {edf_1:?} {edf_2:?}");
+    assert!(edf_2 <= deadline, "edf: {edf_2:?}");
+    // Work-conserving: the bottleneck drains the same 20 packets either way.
+    assert_eq!(edf_1.max(edf_2), fifo_1.max(fifo_2));
+}
+
+#[test]
+fn tcp_config_stamps_traffic_class_on_data_and_acks() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world
+        .net
+        .connect(h0, h1, SimTime::from_micros(2), 1_000_000_000);
+    world
+        .net
+        .connect(h1, h0, SimTime::from_micros(2), 1_000_000_000);
+
+    // (is_ack, priority) of every segment the two hosts send.
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    for node in [h0, h1] {
+        let seen = Arc::clone(&seen);
+        world
+            .net
+            .add_middlebox(node, move |pkt, _| match pkt.transport {
+                Transport::Tcp(TcpSegment::Data { .. }) => {
+                    seen.lock().unwrap().push((false, pkt.priority))
+                }
+                Transport::Tcp(TcpSegment::Ack { .. }) => {
+                    seen.lock().unwrap().push((true, pkt.priority))
+                }
+                _ => {}
+            });
+    }
+
+    let cfg = TcpConfig {
+        handshake: false,
+        data_priority: 1,
+        ack_priority: 6,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 20_000, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let seen = seen.lock().unwrap();
+    assert!(seen.iter().any(|&(is_ack, _)| is_ack));
+    assert!(seen.iter().any(|&(is_ack, _)| !is_ack));
+    for &(is_ack, prio) in seen.iter() {
+        assert_eq!(prio, if is_ack { 6 } else { 1 });
+    }
+}
+
+/// Flow 1 (1500B packets) and flow 2 (300B packets) each dump a 600KB backlog
+/// onto the s0->s1 1Gbps bottleneck. Returns the bytes each receiver got in
+/// every 250us window of the first 2ms.
+fn bytes_per_window_mixed_sizes(scheduler: EgressScheduler) -> Vec<(u64, u64)> {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1) = (world.net.add_host("h0"), world.net.add_host("h1"));
+    let (s0, s1) = (world.net.add_switch("s0"), world.net.add_switch("s1"));
+    let (h2, h3) = (world.net.add_host("h2"), world.net.add_host("h3"));
+    let latency = SimTime::from_micros(1);
+    for (a, b, bps) in [
+        (h0, s0, 100_000_000_000),
+        (h1, s0, 100_000_000_000),
+        (s0, s1, 1_000_000_000),
+        (s1, h2, 100_000_000_000),
+        (s1, h3, 100_000_000_000),
+    ] {
+        world.net.connect(a, b, latency, bps);
+    }
+    world.net.set_egress_scheduler(s0, scheduler);
+    world.net.set_all_link_queue_capacity_bytes(10_000_000);
+
+    for (flow, src, dst, size) in [(1, h0, h2, 1500), (2, h1, h3, 300)] {
+        for _ in 0..600_000 / size {
+            let pkt = world.net.make_packet(flow, size, vec![src, s0, s1, dst]);
+            sim.schedule(SimTime::ZERO, DeliverPacket { to: src, pkt });
+        }
+    }
+
+    let window = SimTime::from_micros(250);
+    let mut last = (0, 0);
+    let mut out = Vec::new();
+    for k in 1..=8 {
+        sim.run_until(SimTime(window.0 * k), &mut world);
+        let now = (world.net.host_rx_bytes(h2), world.net.host_rx_bytes(h3));
+        out.push((now.0 - last.0, now.1 - last.1));
+        last = now;
+    }
+    out
+}
+
+fn jain_index(xs: &[u64]) -> f64 {
+    let xs: Vec<f64> = xs.iter().map(|&x| x as f64).collect();
+    jain_fairness_index(&xs).expect("no bytes in window")
+}
+
+#[test]
+fn drr_egress_equalizes_bytes_across_packet_sizes() {
+    let drr = bytes_per_window_mixed_sizes(EgressScheduler::Drr {
+        quantum_bytes: 1500,
+    });
+    // Skip the first window, which includes the pipeline fill.
+    for &(a, b) in &drr[1..] {
+        let jain = jain_index(&[a, b]);
+        assert!(jain > 0.99, "drr window a={a} b={b} jain={jain}");
+        // 250us at 1Gbps is 31.25KB; the bottleneck stays busy.
+        assert!(a + b > 30_000, "drr window a={a} b={b}");
+    }
+
+    // Packet-count round robin hands the 1500B flow five times the bytes.
+    let wrr = bytes_per_window_mixed_sizes(EgressScheduler::FlowWrr);
+    for &(a, b) in &wrr[1..] {
+        let jain = jain_index(&[a, b]);
+        assert!(jain < 0.8, "wrr window a={a} b={b} jain={jain}");
+    }
+}
+
+/// Tenant 1 runs three flows h0 -> h2 and tenant 2 a single flow h1 -> h3, each
+/// flow dumping a 600KB backlog onto the s0->s1 1Gbps bottleneck; tenant 2 is
+/// weighted 2. Returns the bytes each tenant received between 250us and 2ms.
+fn tenant_bytes(scheduler: EgressScheduler) -> (u64, u64) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1) = (world.net.add_host("h0"), world.net.add_host("h1"));
+    let (s0, s1) = (world.net.add_switch("s0"), world.net.add_switch("s1"));
+    let (h2, h3) = (world.net.add_host("h2"), world.net.add_host("h3"));
+    let latency = SimTime::from_micros(1);
+    for (a, b, bps) in [
+        (h0, s0, 100_000_000_000),
+        (h1, s0, 100_000_000_000),
+        (s0, s1, 1_000_000_000),
+        (s1, h2, 100_000_000_000),
+        (s1, h3, 100_000_000_000),
+    ] {
+        world.net.connect(a, b, latency, bps);
+    }
+    world.net.set_egress_scheduler(s0, scheduler);
+    world.net.set_all_link_queue_capacity_bytes(10_000_000);
+    world.net.set_tenant_weight(2, 2);
+
+    for (flow, tenant, src, dst) in [
+        (1, 1, h0, h2),
+        (2, 1, h0, h2),
+        (3, 1, h0, h2),
+        (4, 2, h1, h3),
+    ] {
+        world.net.set_flow_tenant(flow, tenant);
+        for _ in 0..400 {
+            let pkt = world.net.make_packet(flow, 1500, vec![src, s0, s1, dst]);
+            assert_eq!(pkt.tenant_id, tenant);
+            sim.schedule(SimTime::ZERO, DeliverPacket { to: src, pkt });
+        }
+    }
+
+    sim.run_until(SimTime::from_micros(250), &mut world);
+    let start = (world.net.host_rx_bytes(h2), world.net.host_rx_bytes(h3));
+    sim.run_until(SimTime::from_millis(2), &mut world);
+    (
+        world.net.host_rx_bytes(h2) - start.0,
+        world.net.host_rx_bytes(h3) - start.1,
+    )
+}
+
+#[test]
+fn tenant_drr_splits_bandwidth_by_tenant_weight_not_flow_count() {
+    let (t1, t2) = tenant_bytes(EgressScheduler::TenantDrr {
+        quantum_bytes: 1500,
+    });
+    // Weights 1:2, so tenant 2's single flow gets twice tenant 1's three flows.
+    let ratio = t2 as f64 / t1 as f64;
+    assert!((1.9..=2.1).contains(&ratio), "tenant drr t1={t1} t2={t2}");
+    // 1.75ms at 1Gbps is ~219KB; the bottleneck stays busy.
+    assert!(t1 + t2 > 210_000, "tenant drr t1={t1} t2={t2}");
+
+    // Per-flow DRR ignores tenants: tenant 1's three flows take three quarters.
+    let (t1, t2) = tenant_bytes(EgressScheduler::Drr {
+        quantum_bytes: 1500,
+    });
+    let ratio = t1 as f64 / t2 as f64;
+    assert!((2.8..=3.2).contains(&ratio), "flow drr t1={t1} t2={t2}");
+}
@@ -0,0 +1,131 @@
+use crate::net::{NetWorld, NodeId};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+
+fn build_two_hosts() -> (NetWorld, NodeId, NodeId) {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let bw = 10_000_000_000; // 10Gbps
+    world.net.connect(h0, h1, latency, bw);
+    world.net.connect(h1, h0, latency, bw);
+    (world, h0, h1)
+}
+
+fn run_two_tcp_flows(limit: Option<usize>) -> (NetWorld, Vec<(SimTime, SimTime)>) {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = build_two_hosts();
+    if let Some(n) = limit {
+        world.net.set_host_max_concurrent_flows(h0, n);
+    }
+
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    for id in [1_u64, 2] {
+        let conn = TcpConn::new_dynamic(id, h0, h1, 100_000, TcpConfig::default());
+        tcp.start_conn(conn, &mut sim, &mut world.net);
+    }
+    world.net.tcp = tcp;
+    if limit == Some(1) {
+        assert_eq!(world.net.host_queued_flows(h0), 1);
+        assert!(world.net.tcp.get(2).is_none());
+    }
+    sim.run(&mut world);
+
+    let times = [1_u64, 2]
+        .iter()
+        .map(|id| {
+            let c = world.net.tcp.get(*id).expect("tcp conn started");
+            assert!(c.is_done());
+            (c.start_time().unwrap(), c.done_time().unwrap())
+        })
+        .collect();
+    (world, times)
+}
+
+#[test]
+fn host_flow_limit_of_one_serializes_two_flows() {
+    let (world, times) = run_two_tcp_flows(Some(1));
+    let (_, done1) = times[0];
+    let (start2, _) = times[1];
+    assert_eq!(
+        start2, done1,
+        "second flow should start as soon as the first frees its slot"
+    );
+    assert_eq!(world.net.host_queued_flows(NodeId(0)), 0);
+}
+
+#[test]
+fn host_flows_overlap_without_limit_or_with_enough_slots() {
+    for limit in [None, Some(2)] {
+        let (_world, times) = run_two_tcp_flows(limit);
+        let (start1, done1) = times[0];
+        let (start2, _) = times[1];
+        assert_eq!(start1, start2);
+        assert!(start2 < done1);
+    }
+}
+
+#[test]
+fn host_flow_limit_applies_to_dctcp_and_is_per_host() {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = build_two_hosts();
+    world.net.set_host_max_concurrent_flows(h0, 1);
+
+    let mut dctcp = std::mem::take(&mut world.net.dctcp);
+    for (id, src, dst) in [(1_u64, h0, h1), (2, h0, h1), (3, h1, h0)] {
+        let conn = DctcpConn::new_dynamic(id, src, dst, 50_000, DctcpConfig::default());
+        dctcp.start_conn(conn, &mut sim, &mut world.net);
+    }
+    world.net.dctcp = dctcp;
+    sim.run(&mut world);
+
+    let c1 = world.net.dctcp.get(1).expect("flow 1");
+    let c2 = world.net.dctcp.get(2).expect("flow 2");
+    let c3 = world.net.dctcp.get(3).expect("flow 3");
+    assert!(c1.is_done() && c2.is_done() && c3.is_done());
+    assert!(c2.start_time().unwrap() >= c1.done_time().unwrap());
+    // h1 has no limit, so its flow starts right away.
+    assert_eq!(c3.start_time(), Some(SimTime::ZERO));
+}
+
+/// `build_two_hosts` plus a host `h2` that nothing is wired to; h0 gets one slot.
+fn build_with_unreachable_host() -> (Simulator, NetWorld, NodeId, NodeId, NodeId) {
+    let (mut world, h0, h1) = build_two_hosts();
+    let h2 = world.net.add_host("h2");
+    world.net.set_host_max_concurrent_flows(h0, 1);
+    (Simulator::default(), world, h0, h1, h2)
+}
+
+#[test]
+fn unreachable_tcp_flow_releases_its_slot() {
+    let (mut sim, mut world, h0, h1, h2) = build_with_unreachable_host();
+    for (id, dst) in [(1_u64, h2), (2, h1)] {
+        let conn = TcpConn::new_dynamic(id, h0, dst, 100_000, TcpConfig::default());
+        sim.schedule(SimTime::ZERO, TcpStart { conn });
+    }
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_no_route, 1);
+    assert!(world.net.tcp.get(1).is_none());
+    assert!(world.net.tcp.get(2).expect("flow 2 started").is_done());
+    assert_eq!(world.net.host_queued_flows(h0), 0);
+}
+
+#[test]
+fn unreachable_dctcp_flow_fails_and_releases_its_slot() {
+    let (mut sim, mut world, h0, h1, h2) = build_with_unreachable_host();
+    for (id, dst) in [(1_u64, h2), (2, h1)] {
+        let conn = DctcpConn::new_dynamic(id, h0, dst, 100_000, DctcpConfig::default());
+        sim.schedule(SimTime::ZERO, DctcpStart { conn });
+    }
+    // Would never return if flow 1 kept retransmitting into the void.
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_no_route, 1);
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    assert!(world.net.dctcp.get(1).is_none());
+    assert!(world.net.dctcp.get(2).expect("flow 2 started").is_done());
+    assert_eq!(world.net.host_queued_flows(h0), 0);
+}
@@ -0,0 +1,121 @@
+use crate::net::NetWorld;
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::proto::flow::{AckEvent, CongestionControl, CwndChange};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::VizCwndReason;
+
+const MSS: u32 = 1000;
+
+/// Keeps the window at a constant size and ignores every congestion signal.
+#[derive(Debug, Clone)]
+struct FixedWindow {
+    window: u64,
+}
+
+impl CongestionControl for FixedWindow {
+    fn cwnd_bytes(&self) -> u64 {
+        self.window
+    }
+
+    fn ssthresh_bytes(&self) -> u64 {
+        self.window
+    }
+
+    fn on_ack(&mut self, _ack: &AckEvent) -> CwndChange {
+        CwndChange {
+            reason: VizCwndReason::AckCongestionAvoidance,
+            ecn_frac: None,
+        }
+    }
+
+    fn on_loss(&mut self, _mss: u64) {}
+
+    fn on_rto(&mut self, _mss: u64) {}
+}
+
+#[test]
+fn fixed_window_cc_drives_the_shared_flow_core() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let opts = DumbbellOpts {
+        host_link_gbps: 100,
+        bottleneck_gbps: 10,
+        link_latency: SimTime::from_micros(2),
+        ..DumbbellOpts::default()
+    };
+    let (h0, h1, route) = build_dumbbell(&mut world, &opts);
+    let cfg = DctcpConfig {
+        mss: MSS,
+        ack_bytes: 125,
+        ..DctcpConfig::default()
+    };
+    let window = 4 * MSS as u64;
+    let segments = 40;
+    let mut conn = DctcpConn::new(1, h0, h1, route, segments * MSS as u64, cfg);
+    conn.set_cc(Box::new(FixedWindow { window }));
+    conn.enable_cwnd_log();
+    sim.schedule(SimTime::ZERO, DctcpStart { conn });
+    sim.run(&mut world);
+
+    let conn = world.net.dctcp.get(1).unwrap();
+    assert!(conn.is_done());
+    assert_eq!(conn.bytes_acked(), segments * MSS as u64);
+    let samples = conn.cwnd_samples().unwrap();
+    assert!(samples.len() > 1);
+    assert!(samples.iter().all(|s| s.cwnd_bytes == window));
+    assert!(samples.iter().all(|s| s.alpha == 0.0));
+
+    // At most one window is in flight, so each window of segments costs at
+    // least one round trip after the first.
+    let min_rtt = conn.min_rtt().unwrap();
+    let rounds = segments * MSS as u64 / window;
+    assert!(
+        conn.done_time().unwrap().0 >= (rounds - 1) * min_rtt.0,
+        "done at {:?} with min rtt {:?}",
+        conn.done_time(),
+        min_rtt
+    );
+}
+
+#[test]
+fn fixed_window_cc_drives_a_tcp_flow() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let opts = DumbbellOpts {
+        host_link_gbps: 100,
+        bottleneck_gbps: 10,
+        link_latency: SimTime::from_micros(2),
+        ..DumbbellOpts::default()
+    };
+    let (h0, h1, route) = build_dumbbell(&mut world, &opts);
+    let cfg = TcpConfig {
+        mss: MSS,
+        ack_bytes: 64,
+        ..TcpConfig::default()
+    };
+    let window = 4 * MSS as u64;
+    let segments = 40;
+    let mut conn = TcpConn::new(1, h0, h1, route, segments * MSS as u64, cfg);
+    conn.set_cc(Box::new(FixedWindow { window }));
+    conn.enable_cwnd_log();
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let conn = world.net.tcp.get(1).unwrap();
+    assert!(conn.is_done());
+    assert_eq!(conn.bytes_acked(), segments * MSS as u64);
+    let samples = conn.cwnd_samples().unwrap();
+    assert!(samples.len() > 1);
+    assert!(samples.iter().all(|s| s.cwnd_bytes == window));
+
+    let min_rtt = conn.min_rtt().unwrap();
+    let rounds = segments * MSS as u64 / window;
+    assert!(
+        conn.done_time().unwrap().0 >= (rounds - 1) * min_rtt.0,
+        "done at {:?} with min rtt {:?}",
+        conn.done_time(),
+        min_rtt
+    );
+}
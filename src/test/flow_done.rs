@@ -0,0 +1,43 @@
+use std::sync::{Arc, Mutex};
+
+use crate::net::NetWorld;
+use crate::proto::dctcp::{DctcpConfig, DctcpConn};
+use crate::proto::tcp::{TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+
+#[test]
+fn flow_complete_callbacks_fire_for_tcp_and_dctcp_flows() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let bw = 10_000_000_000; // 10Gbps
+    world.net.connect(h0, h1, latency, bw);
+    world.net.connect(h1, h0, latency, bw);
+
+    let any = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&any);
+    world.on_any_flow_complete(move |id, now, _| seen.lock().unwrap().push((id, now)));
+    let one = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&one);
+    world.on_flow_complete(2, move |id, now, _| seen.lock().unwrap().push((id, now)));
+
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    let conn = TcpConn::new_dynamic(1, h0, h1, 100_000, TcpConfig::default());
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+    let mut dctcp = std::mem::take(&mut world.net.dctcp);
+    let conn = DctcpConn::new_dynamic(2, h1, h0, 50_000, DctcpConfig::default());
+    dctcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.dctcp = dctcp;
+    sim.run(&mut world);
+
+    let tcp_done = world.net.tcp.get(1).unwrap().done_time().unwrap();
+    let dctcp_done = world.net.dctcp.get(2).unwrap().done_time().unwrap();
+    let mut any = any.lock().unwrap().clone();
+    any.sort();
+    assert_eq!(any, vec![(1, tcp_done), (2, dctcp_done)]);
+    // The per-flow callback only sees its own flow, exactly once.
+    assert_eq!(*one.lock().unwrap(), vec![(2, dctcp_done)]);
+}
@@ -0,0 +1,133 @@
+use crate::net::{NetWorld, jain_fairness_index};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::{VizEventKind, VizLogger};
+
+#[test]
+fn completed_tcp_flow_reports_bytes_and_completion_at_last_ack() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    world.net.viz = Some(VizLogger::default());
+
+    let total_bytes = 100_000;
+    let cfg = TcpConfig {
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(7, h0, h1, total_bytes, cfg);
+    sim.schedule(SimTime::from_micros(5), TcpStart { conn });
+    sim.run(&mut world);
+
+    let last_ack = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter(|ev| matches!(ev.kind, VizEventKind::TcpRecvAck(_)))
+        .map(|ev| SimTime(ev.t_ns))
+        .max()
+        .expect("no ACKs received");
+
+    let stats = world.net.flow_stats(7).expect("flow stats missing");
+    assert_eq!(stats.bytes_delivered, total_bytes);
+    assert_eq!(stats.start, Some(SimTime::from_micros(5)));
+    assert_eq!(stats.completion, Some(last_ack));
+    assert_eq!(
+        stats.completion,
+        world.net.tcp.get(7).and_then(|c| c.done_time())
+    );
+    assert_eq!(
+        stats.fct(),
+        Some(SimTime(last_ack.0 - SimTime::from_micros(5).0))
+    );
+    assert_eq!(stats.retransmits, 0);
+    assert_eq!(stats.ecn_marks, 0);
+
+    assert!(world.net.flow_stats(8).is_none());
+    assert_eq!(world.net.all_flow_stats().len(), 1);
+}
+
+#[test]
+fn jain_index_is_one_for_equal_flows_and_drops_with_a_starved_flow() {
+    assert_eq!(jain_fairness_index(&[]), None);
+    assert_eq!(jain_fairness_index(&[0.0, 0.0]), None);
+    assert_eq!(jain_fairness_index(&[5e9; 4]), Some(1.0));
+    // One of four flows gets nothing: (3x)^2 / (4 * 3x^2) = 0.75.
+    let starved = jain_fairness_index(&[5e9, 5e9, 5e9, 0.0]).unwrap();
+    assert!((starved - 0.75).abs() < 1e-12, "index {starved}");
+}
+
+#[test]
+fn flow_fairness_index_over_delivery_rates() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let cfg = TcpConfig {
+        handshake: false,
+        ..TcpConfig::default()
+    };
+
+    // Two identical flows run one after the other, so they see the same rate.
+    for (id, at) in [(1, 0), (2, 1_000)] {
+        let conn = TcpConn::new_dynamic(id, h0, h1, 100_000, cfg.clone());
+        sim.schedule(SimTime::from_micros(at), TcpStart { conn });
+    }
+    sim.run_until(SimTime::from_micros(2_000), &mut world);
+    let index = world.net.flow_fairness_index(sim.now()).unwrap();
+    assert!((index - 1.0).abs() < 1e-9, "index {index}");
+
+    // A third flow that has started but delivered nothing yet is starved.
+    let conn = TcpConn::new_dynamic(3, h0, h1, 100_000, cfg);
+    sim.schedule(SimTime::from_micros(2_000), TcpStart { conn });
+    sim.run_until(SimTime::from_micros(2_001), &mut world);
+    assert_eq!(world.net.flow_stats(3).unwrap().bytes_delivered, 0);
+    let index = world.net.flow_fairness_index(sim.now()).unwrap();
+    assert!((index - 2.0 / 3.0).abs() < 1e-9, "index {index}");
+}
+
+#[test]
+fn dctcp_rto_resends_count_as_retransmits() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world.net.connect(h0, h1, SimTime(1000), 1_000_000_000);
+    world.net.connect(h1, h0, SimTime(1000), 1_000_000_000);
+    // One segment transmitting plus one queued: the third is a tail loss with no
+    // dupACKs behind it, so only the RTO can recover it.
+    world.net.set_host_egress_queue_capacity_bytes(100);
+    world.net.viz = Some(VizLogger::default());
+
+    let cfg = DctcpConfig {
+        mss: 100,
+        init_cwnd_bytes: 1_000,
+        init_rto: SimTime::from_micros(10),
+        min_rto: SimTime::from_micros(10),
+        max_rto: SimTime::from_millis(1),
+        ..DctcpConfig::default()
+    };
+    let conn = DctcpConn::new_dynamic(1, h0, h1, 300, cfg);
+    sim.schedule(SimTime::ZERO, DctcpStart { conn });
+    sim.run(&mut world);
+
+    assert!(world.net.stats.dropped_pkts > 0);
+    assert!(world.net.dctcp.get(1).expect("dctcp conn").is_done());
+    let events = &world.net.viz.as_ref().expect("viz enabled").events;
+    assert!(
+        events
+            .iter()
+            .any(|ev| matches!(ev.kind, VizEventKind::TcpRto(_)))
+    );
+    let resends = events
+        .iter()
+        .filter(|ev| matches!(&ev.kind, VizEventKind::TcpSendData(v) if v.retrans == Some(true)))
+        .count() as u64;
+    assert!(resends > 0);
+    let stats = world.net.flow_stats(1).expect("flow stats");
+    assert_eq!(stats.retransmits, resends);
+}
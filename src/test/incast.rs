@@ -0,0 +1,56 @@
+use crate::experiments::{IncastOpts, IncastReport, incast};
+use crate::net::NetWorld;
+use crate::proto::tcp::TcpConfig;
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_fanin_dumbbell};
+
+const MSS: u32 = 1460;
+
+/// Fan-in-8 incast of 64KB per flow across the default dumbbell (10Gbps
+/// bottleneck), with every switch egress queue limited to `queue_pkts`.
+fn run_fanin_8(queue_pkts: u64) -> IncastReport {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (senders, rx) = build_fanin_dumbbell(&mut world, &DumbbellOpts::default(), 8);
+    assert_eq!(senders.len(), 8);
+    world.net.set_max_packet_bytes(MSS as u64);
+    world
+        .net
+        .set_switch_egress_queue_capacity_bytes(queue_pkts * MSS as u64);
+
+    let opts = IncastOpts {
+        bytes: 64 * 1024,
+        tcp_cfg: TcpConfig {
+            mss: MSS,
+            init_rto: SimTime::from_millis(1),
+            min_rto: SimTime::from_millis(1),
+            handshake: false,
+            ..TcpConfig::default()
+        },
+        ..IncastOpts::default()
+    };
+    let run = incast(&mut sim, &mut world, &senders, rx, &opts);
+    sim.run(&mut world);
+    run.report(&world)
+}
+
+#[test]
+fn incast_drops_rise_sharply_with_small_switch_queue() {
+    let deep = run_fanin_8(1_000);
+    let shallow = run_fanin_8(8);
+    for r in [&deep, &shallow] {
+        assert_eq!(r.fanin, 8);
+        assert_eq!(r.flows_done, 8, "{r:?}");
+        assert!(r.completion.is_some(), "{r:?}");
+    }
+
+    // A deep buffer absorbs the synchronized burst (8 x 64KB = 512KB).
+    assert_eq!(deep.dropped_pkts, 0, "{deep:?}");
+    assert!(shallow.dropped_pkts >= 20, "{shallow:?}");
+    // Losses cost goodput: the deep-buffer run keeps the bottleneck busy.
+    assert!(deep.goodput_gbps > 8.0, "{deep:?}");
+    assert!(
+        shallow.goodput_gbps < deep.goodput_gbps,
+        "{deep:?} {shallow:?}"
+    );
+}
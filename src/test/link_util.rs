@@ -0,0 +1,67 @@
+use crate::net::{DeliverPacket, NetWorld, NodeId, Packet};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+
+/// Burst `pkts` MTU packets from h0 to h1 at t=0; the 100G access link feeds the 10G
+/// bottleneck much faster than it drains, so s0->s1 stays busy until the queue empties.
+fn run_burst(world: &mut NetWorld, pkts: u64) -> Vec<NodeId> {
+    let (h0, h1, route) = build_dumbbell(world, &DumbbellOpts::default());
+    let mut sim = Simulator::default();
+    for i in 0..pkts {
+        let pkt = Packet::new_dynamic(i, 1, 1500, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+    sim.run(world);
+    assert_eq!(world.net.stats.delivered_pkts, pkts);
+    route
+}
+
+#[test]
+fn saturated_bottleneck_reports_full_utilization() {
+    let mut world = NetWorld::default();
+    let interval = SimTime::from_micros(100);
+    world.net.enable_link_util_sampling(interval);
+    // 2000 x 1500B at 10Gbps keeps the bottleneck busy for 2.4ms.
+    let route = run_burst(&mut world, 2000);
+
+    let (s0, s1) = (route[1], route[2]);
+    let bottleneck = world.net.link_id(s0, s1).expect("bottleneck link");
+    let series: Vec<(SimTime, f64)> = world
+        .net
+        .link_util_series()
+        .iter()
+        .filter(|(_, link, _)| *link == bottleneck)
+        .map(|&(t, _, util)| (t, util))
+        .collect();
+    assert!(series.len() >= 24, "only {} samples", series.len());
+    assert!(series.iter().all(|(t, _)| t.0 % interval.0 == 0));
+
+    // Skip the first window (the burst is still reaching s0) and the tail where it drains.
+    for &(t, util) in &series[1..23] {
+        assert!((0.98..=1.02).contains(&util), "util {util} at {t:?}");
+    }
+    // The reverse direction carries nothing.
+    let reverse = world.net.link_id(s1, s0).expect("reverse link");
+    assert!(
+        world
+            .net
+            .link_util_series()
+            .iter()
+            .filter(|(_, link, _)| *link == reverse)
+            .all(|&(_, _, util)| util == 0.0)
+    );
+
+    let mut csv = Vec::new();
+    world.net.write_link_util_csv(&mut csv).expect("write csv");
+    let csv = String::from_utf8(csv).expect("utf8");
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("t_ns,link_id,from,to,util"));
+    assert_eq!(lines.count(), world.net.link_util_series().len());
+}
+
+#[test]
+fn link_util_sampling_is_off_by_default() {
+    let mut world = NetWorld::default();
+    run_burst(&mut world, 10);
+    assert!(world.net.link_util_series().is_empty());
+}
@@ -1,14 +1,37 @@
+mod analysis;
 mod collective_op;
 mod dctcp_ecn;
+mod dctcp_rtt;
+mod determinism;
 mod ecmp_hash_mode;
+mod egress_scheduler;
+mod flow_admission;
+mod flow_cc;
+mod flow_done;
+mod flow_stats;
+mod incast;
+mod link_util;
 mod network_integration;
 mod packet;
 mod queues;
+mod reordering;
 mod ring_collectives;
 mod routing_table;
+mod sim_context;
 mod sim_time;
 mod simulator;
+mod tcp_bbr;
+mod tcp_delayed_ack;
+mod tcp_mptcp;
+mod tcp_pacing;
 mod tcp_rto;
+mod tcp_rtt_sampling;
+mod tcp_tlp;
+mod tcp_transfer_sizes;
+mod tcp_vegas;
 mod topologies;
+mod traffic_matrix;
+mod viz_export;
 mod viz_meta;
+mod viz_throughput;
 mod workload_spec;
@@ -1,5 +1,10 @@
-use crate::net::{DeliverPacket, NetWorld, NodeId, Packet, TcpSegment, Transport};
+use crate::net::{
+    CUT_THROUGH_HEADER_BYTES, DeliverPacket, ForwardingMode, LinkJitter, NetWorld, Network, NodeId,
+    PRELOAD_FLOW_ID, Packet, Stats, TcpSegment, Transport,
+};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
 use crate::sim::{Event, SimTime, Simulator, World};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use crate::viz::{VizEventKind, VizLogger};
 
 fn expected_tx_time_ns(bytes: u32, bandwidth_bps: u64) -> u64 {
@@ -240,3 +245,851 @@ fn link_priority_queue_sends_ack_before_data_when_both_queued() {
     assert_eq!(starts[1].1, 3);
     assert_eq!(starts[2].1, 2);
 }
+
+#[test]
+fn link_peak_queue_bytes_tracks_max_depth_after_burst() {
+    let latency = SimTime(1000);
+    let bw = 1_000_000_000;
+    let bytes = 1000_u32;
+    let burst = 8_u64;
+    let (mut world, h0, h1) = build_two_host_link(latency, bw);
+    assert_eq!(world.net.link_peak_queue_bytes(h0, h1), 0);
+
+    let mut sim = Simulator::default();
+    for i in 0..burst {
+        let pkt = Packet::new_dynamic(i, 1, bytes, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+    assert_eq!(world.net.stats.delivered_pkts, burst);
+
+    let max_observed = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter_map(|ev| match &ev.kind {
+            VizEventKind::Enqueue {
+                link_from,
+                link_to,
+                q_bytes,
+                ..
+            } if *link_from == h0.0 && *link_to == h1.0 => Some(*q_bytes),
+            _ => None,
+        })
+        .max()
+        .expect("enqueue events");
+
+    // The first packet starts transmitting immediately, so the rest pile up behind it.
+    assert_eq!(max_observed, (burst - 1) * bytes as u64);
+    // The queue has drained by now, but the high-water mark is kept.
+    assert_eq!(world.net.link_peak_queue_bytes(h0, h1), max_observed);
+}
+
+#[test]
+fn switch_peak_occupancy_is_burst_minus_what_drained_during_it() {
+    let latency = SimTime(1000);
+    let bytes = 1000_u32;
+    let burst = 20_u64;
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let sw = world.net.add_switch("sw");
+    let h1 = world.net.add_host("h1");
+    // The bottleneck serializes a packet in exactly the time the host link
+    // delivers ten of them.
+    world.net.connect(h0, sw, latency, 100_000_000_000);
+    world.net.connect(sw, h1, latency, 10_000_000_000);
+    assert_eq!(world.net.max_switch_occupancy(), 0);
+
+    let mut sim = Simulator::default();
+    for i in 0..burst {
+        let pkt = Packet::new_dynamic(i, 1, bytes, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+    assert_eq!(world.net.stats.delivered_pkts, burst);
+
+    // When the last packet reaches the switch, only packets 0 and 1 have
+    // started onto the bottleneck.
+    let drained = 2;
+    let peak = (burst - drained) * bytes as u64;
+    assert_eq!(world.net.link_peak_queue_bytes(sw, h1), peak);
+    // The host NIC queue peaks higher, but is not a switch port.
+    assert_eq!(
+        world.net.link_peak_queue_bytes(h0, sw),
+        (burst - 1) * bytes as u64
+    );
+    assert_eq!(world.net.max_switch_occupancy(), peak);
+}
+
+#[test]
+fn queue_occupancy_histogram_weights_by_time_including_idle() {
+    let bytes = 1000_u32;
+    // 1000B at 1Gbps serializes in 8us.
+    let (mut world, h0, h1) = build_two_host_link(SimTime(1000), 1_000_000_000);
+    world
+        .net
+        .set_link_queue_occupancy_buckets(h0, h1, vec![0, 1000, 2000, 3000]);
+
+    // Two bursts of 5 packets, at 0us and 100us. Each burst queues 4000B behind the packet
+    // on the wire and drains 1000B every 8us; between bursts the queue sits empty.
+    let mut sim = Simulator::default();
+    for (burst, at) in [SimTime::ZERO, SimTime::from_micros(100)]
+        .into_iter()
+        .enumerate()
+    {
+        for i in 0..5 {
+            let pkt = Packet::new_dynamic(burst as u64 * 5 + i, 1, bytes, h0, h1);
+            sim.schedule(at, DeliverPacket { to: h0, pkt });
+        }
+    }
+    sim.run(&mut world);
+
+    // Integrated up to the link's final dequeue attempt, when the last packet finishes at 140us.
+    let hist = world.net.queue_occupancy_histogram(h0, h1);
+    let expected = [
+        (0, 76.0 / 140.0),
+        (1000, 16.0 / 140.0),
+        (2000, 16.0 / 140.0),
+        (3000, 16.0 / 140.0),
+        (u64::MAX, 16.0 / 140.0),
+    ];
+    assert_eq!(hist.len(), expected.len());
+    for (&(ub, frac), &(want_ub, want)) in hist.iter().zip(&expected) {
+        assert_eq!(ub, want_ub);
+        assert!((frac - want).abs() < 1e-9, "bucket {ub}: {frac} vs {want}");
+    }
+    let total: f64 = hist.iter().map(|&(_, f)| f).sum();
+    assert!((total - 1.0).abs() < 1e-9, "fractions sum to {total}");
+
+    // Links without a histogram report nothing.
+    world.net.connect(h1, h0, SimTime(1000), 1_000_000_000);
+    assert!(world.net.queue_occupancy_histogram(h1, h0).is_empty());
+}
+
+#[test]
+fn sub_mss_queue_capacity_fails_validation() {
+    let (mut world, h0, h1) = build_two_host_link(SimTime(1000), 1_000_000_000);
+    assert!(world.net.validate_queue_capacities().is_ok());
+
+    // e.g. "1 packet" converted with a 1000B packet size while the MSS is 1460B.
+    world.net.set_link_queue_capacity_bytes(h0, h1, 1000);
+    let err = world
+        .net
+        .validate_queue_capacities()
+        .expect_err("sub-MSS queue must be rejected");
+    assert!(err.contains("h0->h1 (1000 B)"), "{err}");
+
+    // Experiments with a matching small MSS opt in explicitly.
+    world.net.set_max_packet_bytes(1000);
+    assert!(world.net.validate_queue_capacities().is_ok());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "exceeds 1 s")]
+fn connect_rejects_latency_in_wrong_units_in_debug_builds() {
+    // 2000 "us" mistakenly passed to from_millis.
+    let _ = build_two_host_link(SimTime::from_millis(2000), 1_000_000_000);
+}
+
+#[test]
+fn connect_by_distance_derives_latency_from_fiber_length() {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world.net.connect_by_distance(h0, h1, 200.0, 1_000_000_000);
+    assert_eq!(world.net.link_latency(h0, h1), SimTime::from_micros(1));
+
+    // 1000 km long-haul span: 5 ms one way.
+    world
+        .net
+        .connect_by_distance(h1, h0, 1_000_000.0, 1_000_000_000);
+    assert_eq!(world.net.link_latency(h1, h0), SimTime::from_millis(5));
+}
+
+#[test]
+fn delivered_bytes_split_into_control_and_data() {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = build_two_host_link(SimTime(1000), 1_000_000_000);
+    world.net.connect(h1, h0, SimTime(1000), 1_000_000_000);
+    let cfg = TcpConfig {
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let ack_bytes = cfg.ack_bytes as u64;
+    let total_bytes = 100_000;
+    let conn = TcpConn::new(1, h0, h1, vec![h0, h1], total_bytes, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+    assert!(world.net.tcp.get(1).expect("conn").is_done());
+
+    let stats = &world.net.stats;
+    assert_eq!(
+        stats.control_bytes() + stats.data_bytes(),
+        stats.delivered_bytes
+    );
+    assert!(stats.delivered_control_pkts > 0);
+    assert_eq!(
+        stats.control_bytes(),
+        stats.delivered_control_pkts * ack_bytes
+    );
+    // No losses: every payload byte (plus per-segment headers) is delivered once.
+    assert!(stats.data_bytes() >= total_bytes);
+}
+
+#[test]
+fn preloaded_queue_drops_arrivals_until_it_drains() {
+    let bw = 1_000_000_000;
+    let bytes = 1000_u32;
+    let tx_ns = expected_tx_time_ns(bytes, bw);
+    let (mut world, h0, h1) = build_two_host_link(SimTime(1000), bw);
+    world
+        .net
+        .set_link_queue_capacity_bytes(h0, h1, 10 * bytes as u64);
+    // Only what fits is preloaded.
+    assert_eq!(world.net.preload_link_queue(h0, h1, 12, bytes), 10);
+    assert_eq!(world.net.link_peak_queue_bytes(h0, h1), 10 * bytes as u64);
+
+    // A burst into the full buffer: the first packet is dropped and starts the
+    // drain, which frees one slot for the second; the third is dropped again.
+    let mut sim = Simulator::default();
+    for id in 100..103 {
+        let pkt = Packet::new_dynamic(id, 1, bytes, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+    // Once the 10 preloaded packets and the accepted one are gone, nothing drops.
+    let drained = SimTime(11 * tx_ns);
+    for id in 200..203 {
+        let pkt = Packet::new_dynamic(id, 1, bytes, h0, h1);
+        sim.schedule(drained, DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+
+    let drops = drop_events(&world, h0, h1);
+    let dropped_ids: Vec<u64> = drops.iter().map(|d| d.1).collect();
+    assert_eq!(dropped_ids, vec![100, 102]);
+    assert!(drops.iter().all(|d| d.0 == 0));
+    assert_eq!(world.net.stats.dropped_pkts, 2);
+    assert_eq!(world.net.stats.delivered_pkts, 10 + 1 + 3);
+
+    // The preloaded packets go out first, back to back from t=0.
+    let mut starts = tx_start_events(&world, h0, h1);
+    starts.sort_by_key(|s| s.0);
+    assert_eq!(starts[0].0, 0);
+    assert_eq!(starts[10].1, 101);
+    assert_eq!(starts[10].0, 10 * tx_ns);
+    let preload_pkts = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter(|ev| {
+            matches!(ev.kind, VizEventKind::Delivered { .. }) && ev.flow_id == Some(PRELOAD_FLOW_ID)
+        })
+        .count();
+    assert_eq!(preload_pkts, 10);
+}
+
+#[test]
+fn link_shaper_limits_long_term_rate_and_allows_initial_burst() {
+    let latency = SimTime::from_micros(1);
+    let bw = 100_000_000_000; // 100Gbps
+    let rate = 10_000_000_000; // shaped to 10Gbps
+    let bytes = 1500_u32;
+    let burst = 10 * bytes as u64;
+    let (mut world, h0, h1) = build_two_host_link(latency, bw);
+    world.net.viz = None;
+    world.net.set_link_shaper(h0, h1, rate, burst);
+    assert_eq!(world.net.link_shaper(h0, h1), Some((rate, burst)));
+    assert_eq!(world.net.link_shaper(h1, h0), None);
+
+    let mut sim = Simulator::default();
+    for i in 0..2_000 {
+        let pkt = Packet::new_dynamic(i, 1, bytes, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+
+    // The full bucket (plus the packet that overdraws it) leaves back to back
+    // at 100Gbps: 11 packets * 120ns after the 1us propagation delay.
+    sim.run_until(SimTime(1_000 + 11 * 120), &mut world);
+    assert_eq!(world.net.host_rx_bytes(h1), 11 * bytes as u64);
+
+    // Afterwards the shaper paces at 10Gbps: 1.25MB per ms.
+    sim.run_until(SimTime::from_millis(1), &mut world);
+    let at_1ms = world.net.host_rx_bytes(h1);
+    sim.run_until(SimTime::from_millis(2), &mut world);
+    let in_window = world.net.host_rx_bytes(h1) - at_1ms;
+    let gbps = in_window as f64 * 8.0 / 1e6;
+    assert!((gbps - 10.0).abs() < 0.02, "shaped rate {gbps} Gbps");
+
+    sim.run(&mut world);
+    assert_eq!(world.net.host_rx_bytes(h1), 2_000 * bytes as u64);
+    // 3MB at 10Gbps is 2.4ms, minus what the initial burst got ahead.
+    let done = sim.now();
+    assert!(
+        done > SimTime::from_micros(2_380) && done < SimTime::from_micros(2_400),
+        "done at {done:?}"
+    );
+}
+
+#[test]
+fn delivered_latency_matches_propagation_plus_serialization() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let opts = DumbbellOpts::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &opts);
+
+    // Spaced far enough apart that no packet ever waits behind another.
+    let bytes = opts.pkt_bytes;
+    for i in 0..10u64 {
+        let pkt = if i % 2 == 0 {
+            world.net.make_packet(1, bytes, route.clone())
+        } else {
+            world.net.make_packet_dynamic(2, bytes, h0, h1)
+        };
+        let at = SimTime(5_000 + i * opts.gap.0);
+        sim.schedule(at, DeliverPacket { to: h0, pkt });
+    }
+    assert_eq!(world.net.stats.latency_percentile(0.5), None);
+    sim.run(&mut world);
+
+    let host_bps = opts.host_link_gbps * 1_000_000_000;
+    let bottleneck_bps = opts.bottleneck_gbps * 1_000_000_000;
+    let expected = SimTime(
+        3 * opts.link_latency.0
+            + 2 * expected_tx_time_ns(bytes, host_bps)
+            + expected_tx_time_ns(bytes, bottleneck_bps),
+    );
+    let stats = &world.net.stats;
+    assert_eq!(stats.latencies, vec![expected; 10]);
+    assert_eq!(stats.latency_percentile(0.5), Some(expected));
+    assert_eq!(stats.latency_percentile(0.99), Some(expected));
+    assert_eq!(stats.latency_mean(), Some(expected));
+
+    // Fractional p, nearest rank, same as `analysis::percentile`.
+    let spread = Stats {
+        latencies: (1..=10).map(SimTime).collect(),
+        ..Stats::default()
+    };
+    assert_eq!(spread.latency_percentile(0.0), Some(SimTime(1)));
+    assert_eq!(spread.latency_percentile(0.9), Some(SimTime(9)));
+    assert_eq!(spread.latency_percentile(1.0), Some(SimTime(10)));
+}
+
+/// One `bytes`-sized packet over h0 -> s0 -> s1 -> s2 -> h1 (10Gbps links), after
+/// `setup` has configured each switch.
+fn chain_latency(bytes: u32, setup: impl Fn(&mut Network, NodeId)) -> SimTime {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let switches = [
+        world.net.add_switch("s0"),
+        world.net.add_switch("s1"),
+        world.net.add_switch("s2"),
+    ];
+    let h1 = world.net.add_host("h1");
+    let mut prev = h0;
+    for node in switches.into_iter().chain([h1]) {
+        world.net.connect(prev, node, SimTime(1000), 10_000_000_000);
+        prev = node;
+    }
+    for sw in switches {
+        setup(&mut world.net, sw);
+    }
+
+    let pkt = world.net.make_packet_dynamic(1, bytes, h0, h1);
+    sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    sim.run(&mut world);
+    assert_eq!(world.net.stats.latencies.len(), 1);
+    world.net.stats.latencies[0]
+}
+
+#[test]
+fn switch_processing_delay_adds_per_hop_latency() {
+    let base = chain_latency(1500, |_, _| {});
+    let delayed = chain_latency(1500, |net, sw| {
+        net.set_switch_processing_delay(sw, SimTime(500))
+    });
+    assert_eq!(delayed.0 - base.0, 3 * 500);
+}
+
+#[test]
+fn cut_through_skips_per_hop_serialization() {
+    let bytes = 1500;
+    let store_and_forward = chain_latency(bytes, |_, _| {});
+    let cut_through = chain_latency(bytes, |net, sw| {
+        net.set_switch_forwarding_mode(sw, ForwardingMode::CutThrough)
+    });
+    // Each of the three switches only waits for the header instead of the
+    // whole packet; the last link still delivers the full packet.
+    let saved = expected_tx_time_ns(bytes, 10_000_000_000)
+        - expected_tx_time_ns(CUT_THROUGH_HEADER_BYTES, 10_000_000_000);
+    assert_eq!(store_and_forward.0 - cut_through.0, 3 * saved);
+}
+
+#[test]
+#[should_panic(expected = "is not a switch")]
+fn switch_processing_delay_rejects_hosts() {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    world.net.set_switch_processing_delay(h0, SimTime(500));
+}
+
+/// Packets spaced 1us apart over a 10us, 10Gbps link with `jitter`; returns the
+/// delivery latencies and the order in which packet ids reached `h1`.
+fn jittered_deliveries(jitter: LinkJitter) -> (Vec<SimTime>, Vec<u64>) {
+    let (mut world, h0, h1) = build_two_host_link(SimTime::from_micros(10), 10_000_000_000);
+    world.net.set_link_jitter(h0, h1, jitter);
+    let mut sim = Simulator::default();
+    for i in 0..200 {
+        let pkt = world.net.make_packet_dynamic(1, 1000, h0, h1);
+        sim.schedule(SimTime(i * 1000), DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+    assert_eq!(world.net.stats.delivered_pkts, 200);
+
+    let arrivals = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter_map(|ev| match ev.kind {
+            VizEventKind::ArriveNode { node } if node == h1.0 => ev.pkt_id,
+            _ => None,
+        })
+        .collect();
+    (world.net.stats.latencies.clone(), arrivals)
+}
+
+#[test]
+fn link_jitter_keeps_latencies_in_band_and_can_reorder() {
+    let base = SimTime::from_micros(10).0 + expected_tx_time_ns(1000, 10_000_000_000);
+    let bands = [
+        (
+            LinkJitter::Uniform {
+                max: SimTime::from_micros(3),
+            },
+            3_000,
+        ),
+        (
+            LinkJitter::Normal {
+                std_dev: SimTime::from_micros(1),
+            },
+            3_000,
+        ),
+    ];
+    for (jitter, half_width) in bands {
+        let (latencies, arrivals) = jittered_deliveries(jitter);
+        for lat in &latencies {
+            assert!(
+                (base - half_width..=base + half_width).contains(&lat.0),
+                "{jitter:?}: latency {lat:?} outside {base}±{half_width}ns"
+            );
+        }
+        let min = latencies.iter().min().expect("latencies").0;
+        let max = latencies.iter().max().expect("latencies").0;
+        assert!(max - min > half_width, "{jitter:?}: spread {min}..{max}ns");
+        // Jitter of several microseconds exceeds the 1us packet spacing.
+        assert!(
+            arrivals.windows(2).any(|w| w[0] > w[1]),
+            "{jitter:?}: expected some reordering"
+        );
+    }
+}
+
+/// Completion time of a 1MB TCP flow across the dumbbell whose bottleneck has
+/// +-5us of uniform jitter, far more than the 1.2us packet spacing.
+fn jittered_tcp_done(seed: u64) -> SimTime {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    world.net.set_ecmp_seed(seed);
+    world.net.set_link_jitter(
+        route[1],
+        route[2],
+        LinkJitter::Uniform {
+            max: SimTime::from_micros(5),
+        },
+    );
+    let conn = TcpConn::new(1, h0, h1, route, 1_000_000, TcpConfig::default());
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+    sim.run_until(SimTime::from_millis(100), &mut world);
+
+    let conn = world.net.tcp.get(1).expect("conn");
+    assert!(conn.is_done(), "flow did not finish under reordering");
+    assert_eq!(conn.bytes_acked(), 1_000_000);
+    conn.done_time().expect("done time")
+}
+
+#[test]
+fn tcp_completes_over_jittered_link_reproducibly_per_seed() {
+    let a = jittered_tcp_done(1);
+    assert_eq!(a, jittered_tcp_done(1), "same seed must replay exactly");
+    assert_ne!(a, jittered_tcp_done(2), "another seed draws other jitter");
+}
+
+#[test]
+fn jumbo_mtu_scales_serialization_time_and_queue_conversions() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    assert_eq!(world.net.mtu(), 1500);
+    assert!(world.net.validate_mss(1460).is_ok());
+    assert!(world.net.validate_mss(1461).is_err());
+
+    world.net.set_mtu(9000);
+    assert!(world.net.validate_mss(8960).is_ok());
+    assert!(world.net.validate_mss(8961).is_err());
+    assert_eq!(world.net.mem_from_pkt(4), 36_000);
+
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let bps = 10_000_000_000;
+    world.net.connect(h0, h1, SimTime::from_micros(1), bps);
+    world.net.connect(h1, h0, SimTime::from_micros(1), bps);
+    // One MTU-sized packet of buffer is enough for a jumbo frame.
+    let cap = world.net.mem_from_pkt(1);
+    world.net.set_all_link_queue_capacity_bytes(cap);
+    assert!(world.net.validate_queue_capacities().is_ok());
+
+    for (i, bytes) in [(0u64, 9000), (1, 1500)] {
+        let pkt = world.net.make_packet_dynamic(i + 1, bytes, h0, h1);
+        sim.schedule(SimTime(i * 100_000), DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    let serialization: Vec<u64> = world
+        .net
+        .stats
+        .latencies
+        .iter()
+        .map(|t| t.0 - 1_000)
+        .collect();
+    // 9000B at 10Gbps is 7.2us, six times a 1500B frame.
+    assert_eq!(serialization, vec![7_200, 1_200]);
+    assert_eq!(serialization[0], expected_tx_time_ns(9000, bps));
+}
+
+/// h0 - s0 - h1, plus an island host h2 with no links. Sends one dynamically
+/// routed packet h0 -> h2 and one h0 -> h1.
+fn send_to_island(strict: bool) -> NetWorld {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let s0 = world.net.add_switch("s0");
+    let h2 = world.net.add_host("h2");
+    let latency = SimTime::from_micros(1);
+    for (a, b) in [(h0, s0), (s0, h0), (s0, h1), (h1, s0)] {
+        world.net.connect(a, b, latency, 10_000_000_000);
+    }
+    world.net.set_strict_routing(strict);
+    world.net.viz = Some(VizLogger::default());
+
+    assert_eq!(world.net.try_route_ecmp_path(h0, h2, 1), None);
+    assert_eq!(
+        world.net.try_route_ecmp_path(h0, h1, 2),
+        Some(vec![h0, s0, h1])
+    );
+    for (flow, dst) in [(1, h2), (2, h1)] {
+        let pkt = world.net.make_packet_dynamic(flow, 1000, h0, dst);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+    world
+}
+
+#[test]
+fn unreachable_destination_is_dropped_as_no_route() {
+    let world = send_to_island(false);
+    assert_eq!(world.net.stats.dropped_no_route, 1);
+    assert_eq!(world.net.stats.dropped_pkts, 1);
+    assert_eq!(world.net.stats.dropped_bytes, 1000);
+    assert_eq!(world.net.stats.delivered_pkts, 1);
+
+    let drops: Vec<_> = world
+        .net
+        .viz
+        .as_ref()
+        .unwrap()
+        .events
+        .iter()
+        .filter(|ev| matches!(ev.kind, VizEventKind::Drop { .. }))
+        .map(|ev| ev.flow_id)
+        .collect();
+    assert_eq!(drops, [Some(1)]);
+}
+
+#[test]
+#[should_panic(expected = "no route")]
+fn strict_routing_panics_on_unreachable_destination() {
+    send_to_island(true);
+}
+
+#[test]
+fn tcp_flow_to_disconnected_host_fails_without_panicking() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let s0 = world.net.add_switch("s0");
+    let h2 = world.net.add_host("h2");
+    let latency = SimTime::from_micros(1);
+    for (a, b) in [(h0, s0), (s0, h0), (s0, h1), (h1, s0)] {
+        world.net.connect(a, b, latency, 10_000_000_000);
+    }
+
+    let cfg = TcpConfig::default();
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    for (id, dst) in [(1, h2), (2, h1)] {
+        let conn = TcpConn::new_dynamic(id, h0, dst, 100_000, cfg.clone());
+        tcp.start_conn(conn, &mut sim, &mut world.net);
+    }
+    world.net.tcp = tcp;
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_no_route, 1);
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    assert!(world.net.tcp.get(1).is_none());
+    assert!(world.net.tcp.get(2).unwrap().is_done());
+}
+
+#[test]
+fn node_stats_balance_across_a_three_node_chain() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let s = world.net.add_switch("s");
+    let h1 = world.net.add_host("h1");
+    // The switch drains 10x slower than it fills, into a 4-packet queue.
+    world.net.connect(h0, s, SimTime(1000), 10_000_000_000);
+    world.net.connect(s, h1, SimTime(1000), 1_000_000_000);
+    world.net.set_link_queue_capacity_bytes(s, h1, 4 * 1500);
+
+    for _ in 0..20 {
+        let pkt = world.net.make_packet_dynamic(1, 1500, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+    sim.run(&mut world);
+
+    let (src, mid, dst) = (
+        world.net.node_stats(h0),
+        world.net.node_stats(s),
+        world.net.node_stats(h1),
+    );
+    // The source sends everything it was handed.
+    assert_eq!((src.rx_pkts, src.tx_pkts, src.dropped_pkts), (20, 20, 0));
+    // The switch forwards exactly what it receives, minus what it drops.
+    assert_eq!(mid.rx_pkts, src.tx_pkts);
+    assert!(mid.dropped_pkts > 0, "{mid:?}");
+    assert_eq!(mid.rx_pkts, mid.tx_pkts + mid.dropped_pkts);
+    assert_eq!(mid.rx_bytes, mid.tx_bytes + 1500 * mid.dropped_pkts);
+    assert_eq!(mid.dropped_pkts, world.net.stats.dropped_pkts);
+    // Everything the switch sent arrives; the sink sends nothing.
+    assert_eq!((dst.rx_pkts, dst.rx_bytes), (mid.tx_pkts, mid.tx_bytes));
+    assert_eq!((dst.tx_pkts, dst.dropped_pkts), (0, 0));
+    assert_eq!(dst.rx_pkts, world.net.stats.delivered_pkts);
+}
+
+#[test]
+fn host_nic_rate_caps_goodput_below_link_speed() {
+    let latency = SimTime::from_micros(1);
+    let bw = 100_000_000_000; // 100Gbps link
+    let nic = 10_000_000_000; // 10Gbps NIC
+    let bytes = 1500_u32;
+    let (mut world, h0, h1) = build_two_host_link(latency, bw);
+    world.net.viz = None;
+    world.net.set_host_nic_rate(h0, nic);
+    assert_eq!(world.net.host_nic_rate(h0), Some(nic));
+    assert_eq!(world.net.host_nic_rate(h1), None);
+
+    let mut sim = Simulator::default();
+    for i in 0..2_000 {
+        let pkt = Packet::new_dynamic(i, 1, bytes, h0, h1);
+        sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+    }
+
+    // Unlike the link shaper there is no burst: the NIC paces from the first
+    // packet, so goodput over any window matches the NIC rate.
+    sim.run_until(SimTime::from_millis(1), &mut world);
+    let at_1ms = world.net.host_rx_bytes(h1);
+    let gbps = at_1ms as f64 * 8.0 / 1e6;
+    assert!((gbps - 10.0).abs() < 0.02, "NIC-limited rate {gbps} Gbps");
+    sim.run_until(SimTime::from_millis(2), &mut world);
+    let gbps = (world.net.host_rx_bytes(h1) - at_1ms) as f64 * 8.0 / 1e6;
+    assert!((gbps - 10.0).abs() < 0.02, "NIC-limited rate {gbps} Gbps");
+
+    sim.run(&mut world);
+    assert_eq!(world.net.host_rx_bytes(h1), 2_000 * bytes as u64);
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    // 3MB at 10Gbps is 2.4ms; at the 100Gbps link speed it would be 0.24ms.
+    let done = sim.now();
+    assert!(
+        done > SimTime::from_micros(2_390) && done < SimTime::from_micros(2_410),
+        "done at {done:?}"
+    );
+}
+
+#[test]
+#[should_panic(expected = "is not a host")]
+fn host_nic_rate_rejects_switches() {
+    let mut world = NetWorld::default();
+    let s = world.net.add_switch("s");
+    world.net.set_host_nic_rate(s, 1_000_000_000);
+}
+
+#[test]
+fn queue_capacity_in_bdp_multiples_matches_formula() {
+    // 10Gbps with 5us one-way latency: base RTT 10us, one BDP is 12.5KB.
+    let (mut world, h0, h1) = build_two_host_link(SimTime::from_micros(5), 10_000_000_000);
+    world.net.set_link_queue_capacity_bdp(h0, h1, 2.0);
+    assert_eq!(world.net.link_queue_capacity_bytes(h0, h1), 25_000);
+    world.net.set_link_queue_capacity_bdp(h0, h1, 0.5);
+    assert_eq!(world.net.link_queue_capacity_bytes(h0, h1), 6_250);
+
+    // An explicit RTT (e.g. a whole path's) replaces the link-derived one.
+    world
+        .net
+        .set_link_queue_capacity_bdp_rtt(h0, h1, 1.0, SimTime::from_micros(48));
+    assert_eq!(world.net.link_queue_capacity_bytes(h0, h1), 60_000);
+    world
+        .net
+        .set_all_link_queue_capacity_bdp(1.5, SimTime::from_micros(8));
+    assert_eq!(world.net.link_queue_capacity_bytes(h0, h1), 15_000);
+}
+
+/// 4MB TCP transfer whose data leaves h0 over a link dropping 10% of packets.
+fn lossy_tcp_run(seed: u64) -> (NetWorld, SimTime) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    world.net.set_link_loss(route[0], route[1], 0.1, seed);
+    assert_eq!(world.net.link_loss(route[0], route[1]), Some(0.1));
+    assert_eq!(world.net.link_loss(route[1], route[0]), None);
+    let cfg = TcpConfig {
+        min_rto: SimTime::from_millis(1),
+        init_rto: SimTime::from_millis(1),
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new(1, h0, h1, route, 4_000_000, cfg);
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+    sim.run(&mut world);
+
+    let conn = world.net.tcp.get(1).expect("conn");
+    assert!(conn.is_done(), "flow did not survive random loss");
+    assert_eq!(conn.bytes_acked(), 4_000_000);
+    let done = conn.done_time().expect("done time");
+    (world, done)
+}
+
+#[test]
+fn lossy_link_drops_its_share_of_packets_and_tcp_recovers() {
+    let (world, done) = lossy_tcp_run(7);
+    let stats = &world.net.stats;
+    // h0 only sends data, so every attempt on the lossy link either left h0
+    // or was dropped at random.
+    let h0 = NodeId(0);
+    let attempts = world.net.node_stats(h0).tx_pkts + stats.dropped_random;
+    let ratio = stats.dropped_random as f64 / attempts as f64;
+    assert!((0.08..=0.12).contains(&ratio), "drop ratio {ratio}");
+    assert_eq!(stats.dropped_pkts, stats.dropped_random);
+    let retransmits = world.net.flow_stats(1).expect("flow stats").retransmits;
+    assert!(
+        retransmits >= stats.dropped_random,
+        "{retransmits} retransmits"
+    );
+
+    let (again, done_again) = lossy_tcp_run(7);
+    assert_eq!(again.net.stats.dropped_random, stats.dropped_random);
+    assert_eq!(done_again, done, "same seed must drop the same packets");
+    let (other, _) = lossy_tcp_run(8);
+    assert_ne!(other.net.stats.dropped_random, stats.dropped_random);
+}
+
+#[test]
+fn dual_homed_host_pins_flows_to_nics_and_exceeds_one_uplink() {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let s0 = world.net.add_switch("s0");
+    let s1 = world.net.add_switch("s1");
+    let s2 = world.net.add_switch("s2");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let nic_bw = 10_000_000_000; // 10Gbps per NIC
+    let core_bw = 100_000_000_000;
+    assert_eq!(world.net.add_host_nic(h0, s0, latency, nic_bw), 0);
+    assert_eq!(world.net.add_host_nic(h0, s1, latency, nic_bw), 1);
+    assert_eq!(world.net.host_nics(h0), &[s0, s1]);
+    assert!(world.net.host_nics(h1).is_empty());
+    for (a, b) in [(s0, s2), (s1, s2), (s2, h1)] {
+        world.net.connect(a, b, latency, core_bw);
+        world.net.connect(b, a, latency, core_bw);
+    }
+    world.net.viz = Some(VizLogger::default());
+
+    let routes = [
+        world.net.route_ecmp_path_via_nic(h0, 0, h1, 1),
+        world.net.route_ecmp_path_via_nic(h0, 1, h1, 2),
+    ];
+    assert_eq!(routes[0], vec![h0, s0, s2, h1]);
+    assert_eq!(routes[1], vec![h0, s1, s2, h1]);
+
+    let mut sim = Simulator::default();
+    let pkts = 1_000_u64;
+    let bytes = 1500_u32;
+    for (flow_id, route) in [(1, &routes[0]), (2, &routes[1])] {
+        for _ in 0..pkts {
+            let pkt = world.net.make_packet(flow_id, bytes, route.clone());
+            sim.schedule(SimTime::ZERO, DeliverPacket { to: h0, pkt });
+        }
+    }
+
+    // Each NIC runs at its own 10Gbps, so together they beat a single uplink.
+    sim.run_until(SimTime::from_micros(500), &mut world);
+    let gbps = world.net.host_rx_bytes(h1) as f64 * 8.0 / 500e3;
+    assert!(gbps > 18.0, "aggregate {gbps} Gbps");
+
+    sim.run(&mut world);
+    assert_eq!(world.net.host_rx_bytes(h1), 2 * pkts * bytes as u64);
+    assert_eq!(tx_start_events(&world, h0, s0).len(), pkts as usize);
+    assert_eq!(tx_start_events(&world, h0, s1).len(), pkts as usize);
+}
+
+#[test]
+fn original_uplink_counts_as_nic_zero() {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let s0 = world.net.add_switch("s0");
+    let s1 = world.net.add_switch("s1");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    let bw = 10_000_000_000;
+    // Wired the way topology builders do it, before any extra NIC exists.
+    for (a, b) in [(h0, s0), (s0, h1), (s1, h1)] {
+        world.net.connect(a, b, latency, bw);
+        world.net.connect(b, a, latency, bw);
+    }
+    assert_eq!(world.net.host_nics(h0), vec![s0]);
+
+    assert_eq!(world.net.add_host_nic(h0, s1, latency, bw), 1);
+    assert_eq!(world.net.host_nics(h0), vec![s0, s1]);
+    assert_eq!(
+        world.net.route_ecmp_path_via_nic(h0, 0, h1, 1),
+        vec![h0, s0, h1]
+    );
+    assert_eq!(
+        world.net.route_ecmp_path_via_nic(h0, 1, h1, 1),
+        vec![h0, s1, h1]
+    );
+}
@@ -1,5 +1,9 @@
-use crate::net::{DctcpSegment, NodeId, Packet, TcpSegment, Transport};
-use crate::queue::{DEFAULT_PKT_BYTES, DropTailQueue, PacketQueue, PriorityQueue, mem_from_pkt};
+use crate::net::{DctcpSegment, Ecn, Link, NodeId, Packet, TcpSegment, Transport};
+use crate::queue::{
+    CoDelParams, CoDelQueue, DEFAULT_PKT_BYTES, DropTailQueue, DrrQueue, EdfQueue, FlowWrrQueue,
+    PacketQueue, PriorityQueue, RedParams, RedQueue, mem_from_pkt,
+};
+use crate::sim::SimTime;
 
 fn dyn_pkt(id: u64, size_bytes: u32) -> Packet {
     Packet::new_dynamic(id, 0, size_bytes, NodeId(0), NodeId(1))
@@ -39,6 +43,59 @@ fn droptail_queue_zero_sized_packets_do_not_consume_capacity() {
     assert!(q.dequeue().is_none());
 }
 
+#[test]
+fn link_ecn_threshold_marks_ect_and_droptail_drops_at_capacity() {
+    let mut link = Link::new(NodeId(0), NodeId(1), SimTime::ZERO, 1_000_000_000);
+    link.queue = Box::new(DropTailQueue::new(1_000));
+    link.ecn_threshold_bytes = Some(400);
+    let pkt = |id: u64, ecn: Ecn| {
+        let mut p = dyn_pkt(id, 100);
+        p.ecn = ecn;
+        p
+    };
+    // Returns the id of a dropped packet.
+    let offer = |link: &mut Link, mut p: Packet| {
+        link.mark_ecn_threshold(&mut p);
+        link.queue.enqueue(p).map_err(|p| p.id)
+    };
+
+    // Alternate ECT and non-ECT arrivals until the queue is full (10 x 100B).
+    for id in 0..10 {
+        let ecn = if id % 2 == 0 { Ecn::Ect0 } else { Ecn::NotEct };
+        assert!(offer(&mut link, pkt(id, ecn)).is_ok(), "id={id}");
+    }
+    // Both kinds are dropped once the buffer is full.
+    assert_eq!(offer(&mut link, pkt(10, Ecn::Ect0)), Err(10));
+    assert_eq!(offer(&mut link, pkt(11, Ecn::NotEct)), Err(11));
+
+    // Packet `id` brings the queue to (id + 1) * 100 bytes: ECT packets that
+    // take it to 400B or more are marked CE, non-ECT packets are left untouched.
+    let ecn: Vec<(u64, Ecn)> =
+        std::iter::from_fn(|| link.queue.dequeue().map(|p| (p.id, p.ecn))).collect();
+    assert_eq!(
+        ecn,
+        vec![
+            (0, Ecn::Ect0),
+            (1, Ecn::NotEct),
+            (2, Ecn::Ect0),
+            (3, Ecn::NotEct),
+            (4, Ecn::Ce),
+            (5, Ecn::NotEct),
+            (6, Ecn::Ce),
+            (7, Ecn::NotEct),
+            (8, Ecn::Ce),
+            (9, Ecn::NotEct),
+        ]
+    );
+
+    // Without a threshold nothing is marked.
+    link.ecn_threshold_bytes = None;
+    for id in 0..10 {
+        assert!(offer(&mut link, pkt(id, Ecn::Ect0)).is_ok());
+    }
+    assert!(std::iter::from_fn(|| link.queue.dequeue()).all(|p| p.ecn == Ecn::Ect0));
+}
+
 #[test]
 fn priority_queue_dequeues_high_priority_before_low_priority() {
     let mut q = PriorityQueue::new(1_000);
@@ -141,3 +198,330 @@ fn priority_queue_len_and_bytes_track_enqueues_and_dequeues() {
     assert_eq!(q.bytes(), 0);
     assert!(q.dequeue().is_none());
 }
+
+/// Under a continuous stream of ACKs, return how many dequeues it takes for
+/// a single waiting data packet to leave the queue (None if it never does).
+fn dequeues_until_data_served(mut q: PriorityQueue, rounds: u64) -> Option<u64> {
+    let mut data = dyn_pkt(0, 100);
+    data.transport = Transport::Tcp(TcpSegment::Data { seq: 0, len: 100 });
+    assert!(q.enqueue(data).is_ok());
+    for i in 1..=rounds {
+        let mut ack = dyn_pkt(i, 40);
+        ack.transport = Transport::Tcp(TcpSegment::Ack { ack: i });
+        assert!(q.enqueue(ack).is_ok());
+        if q.dequeue().expect("pkt").id == 0 {
+            return Some(i);
+        }
+    }
+    None
+}
+
+#[test]
+fn priority_queue_aging_bounds_low_priority_starvation() {
+    // Strict priority: the data packet starves for as long as ACKs keep arriving.
+    assert_eq!(
+        dequeues_until_data_served(PriorityQueue::new(1_000), 1_000),
+        None
+    );
+
+    for limit in [0, 1, 4, 16] {
+        let q = PriorityQueue::with_aging(1_000, limit);
+        assert_eq!(q.aging_limit(), Some(limit));
+        // Bypassed exactly `limit` times, then served on the next dequeue.
+        assert_eq!(
+            dequeues_until_data_served(q, 1_000),
+            Some(limit as u64 + 1),
+            "limit={limit}"
+        );
+    }
+}
+
+fn prio_pkt(id: u64, priority: u8) -> Packet {
+    let mut p = dyn_pkt(id, 100);
+    p.transport = Transport::Tcp(TcpSegment::Data { seq: 0, len: 100 });
+    p.priority = priority;
+    p
+}
+
+#[test]
+fn priority_queue_serves_higher_traffic_class_first_fifo_within_class() {
+    let mut q = PriorityQueue::new(10_000);
+    // Interleave two classes; low-priority packets arrive first.
+    for (id, prio) in [(1, 0), (2, 3), (3, 0), (4, 0), (5, 3), (6, 3), (7, 0)] {
+        assert!(q.enqueue(prio_pkt(id, prio)).is_ok());
+    }
+    assert_eq!(q.len_at_priority(3), 3);
+    assert_eq!(q.len_at_priority(0), 4);
+
+    let order: Vec<u64> = std::iter::from_fn(|| q.dequeue()).map(|p| p.id).collect();
+    assert_eq!(order, vec![2, 5, 6, 1, 3, 4, 7]);
+    assert_eq!(q.len(), 0);
+    assert_eq!(q.bytes(), 0);
+}
+
+#[test]
+fn priority_queue_priority_outranks_control_and_low_class_starves() {
+    let mut q = PriorityQueue::new(100_000);
+    // A priority-0 ACK still yields to priority-1 data.
+    let mut ack = dyn_pkt(1, 40);
+    ack.transport = Transport::Tcp(TcpSegment::Ack { ack: 1 });
+    assert!(q.enqueue(ack).is_ok());
+    assert!(q.enqueue(prio_pkt(2, 1)).is_ok());
+    assert_eq!(q.dequeue().expect("pkt").id, 2);
+    assert_eq!(q.dequeue().expect("pkt").id, 1);
+
+    // Under a continuous high-priority stream the low class never gets served.
+    assert!(q.enqueue(prio_pkt(100, 0)).is_ok());
+    for i in 0..500 {
+        assert!(q.enqueue(prio_pkt(1_000 + i, 5)).is_ok());
+        assert_eq!(q.dequeue().expect("pkt").id, 1_000 + i);
+    }
+    assert_eq!(q.len_at_priority(0), 1);
+    assert_eq!(q.dequeue().expect("pkt").id, 100);
+
+    // Aging still bounds the wait across priority classes.
+    let mut q = PriorityQueue::with_aging(100_000, 4);
+    assert!(q.enqueue(prio_pkt(100, 0)).is_ok());
+    let mut served_at = None;
+    for i in 1..=100 {
+        assert!(q.enqueue(prio_pkt(1_000 + i, 5)).is_ok());
+        if q.dequeue().expect("pkt").id == 100 {
+            served_at = Some(i);
+            break;
+        }
+    }
+    assert_eq!(served_at, Some(5));
+}
+
+#[test]
+fn flow_wrr_queue_interleaves_flows_by_weight() {
+    let data = |id: u64, flow_id: u64| {
+        let mut p = Packet::new_dynamic(id, flow_id, 100, NodeId(0), NodeId(1));
+        p.transport = Transport::Tcp(TcpSegment::Data { seq: 0, len: 100 });
+        p
+    };
+    let mut q = FlowWrrQueue::new(10_000);
+    q.set_flow_weight(2, 2);
+    // Flow 1 builds a backlog before flow 2 shows up.
+    for id in 1..=4 {
+        assert!(q.enqueue(data(id, 1)).is_ok());
+    }
+    for id in 11..=14 {
+        assert!(q.enqueue(data(id, 2)).is_ok());
+    }
+    let mut ack = dyn_pkt(99, 40);
+    ack.transport = Transport::Tcp(TcpSegment::Ack { ack: 1 });
+    assert!(q.enqueue(ack).is_ok());
+    assert_eq!(q.len(), 9);
+    assert_eq!(q.bytes(), 840);
+
+    let order = std::iter::from_fn(|| q.dequeue().map(|p| p.id)).collect::<Vec<_>>();
+    assert_eq!(order, vec![99, 1, 11, 12, 2, 13, 14, 3, 4]);
+    assert_eq!(q.len(), 0);
+    assert_eq!(q.bytes(), 0);
+}
+
+#[test]
+fn drr_queue_shares_bytes_between_flows_with_different_packet_sizes() {
+    let data = |id: u64, flow_id: u64, size: u32| {
+        let mut p = Packet::new_dynamic(id, flow_id, size, NodeId(0), NodeId(1));
+        p.transport = Transport::Tcp(TcpSegment::Data { seq: 0, len: size });
+        p
+    };
+    let mut q = DrrQueue::new(100_000, 500);
+    assert_eq!(q.quantum_bytes(), 500);
+    // Flow 1 sends 500B packets, flow 2 sends 250B packets.
+    for id in 1..=3 {
+        assert!(q.enqueue(data(id, 1, 500)).is_ok());
+    }
+    for id in 11..=16 {
+        assert!(q.enqueue(data(id, 2, 250)).is_ok());
+    }
+    let mut ack = dyn_pkt(99, 40);
+    ack.transport = Transport::Tcp(TcpSegment::Ack { ack: 1 });
+    assert!(q.enqueue(ack).is_ok());
+    assert_eq!(q.len(), 10);
+
+    // Each turn is worth 500 bytes: one big packet or two small ones.
+    let order = std::iter::from_fn(|| q.dequeue().map(|p| p.id)).collect::<Vec<_>>();
+    assert_eq!(order, vec![99, 1, 11, 12, 2, 13, 14, 3, 15, 16]);
+    assert_eq!(q.len(), 0);
+    assert_eq!(q.bytes(), 0);
+
+    // A head packet larger than the quantum waits until enough deficit accrues.
+    let mut q = DrrQueue::new(100_000, 400);
+    assert!(q.enqueue(data(1, 1, 1000)).is_ok());
+    assert!(q.enqueue(data(2, 2, 400)).is_ok());
+    assert!(q.enqueue(data(3, 2, 400)).is_ok());
+    assert!(q.enqueue(data(4, 2, 400)).is_ok());
+    assert_eq!(q.dequeue().expect("pkt").id, 2);
+    assert_eq!(q.deficit(1), 400);
+    assert_eq!(q.dequeue().expect("pkt").id, 3);
+    assert_eq!(q.deficit(1), 800);
+    assert_eq!(q.dequeue().expect("pkt").id, 1);
+    assert_eq!(q.dequeue().expect("pkt").id, 4);
+    assert!(q.dequeue().is_none());
+}
+
+#[test]
+fn edf_queue_orders_data_by_deadline_and_keeps_acks_first() {
+    let data = |id: u64, deadline: Option<u64>| {
+        let mut p = dyn_pkt(id, 100);
+        p.transport = Transport::Tcp(TcpSegment::Data { seq: 0, len: 100 });
+        p.deadline = deadline.map(SimTime);
+        p
+    };
+    let mut q = EdfQueue::new(10_000);
+    assert!(q.enqueue(data(1, None)).is_ok());
+    assert!(q.enqueue(data(2, Some(500))).is_ok());
+    assert!(q.enqueue(data(3, Some(100))).is_ok());
+    assert!(q.enqueue(data(4, None)).is_ok());
+    assert!(q.enqueue(data(5, Some(100))).is_ok());
+    let mut ack = dyn_pkt(6, 40);
+    ack.transport = Transport::Tcp(TcpSegment::Ack { ack: 1 });
+    assert!(q.enqueue(ack).is_ok());
+    assert_eq!(q.len(), 6);
+    assert_eq!(q.bytes(), 540);
+
+    let order: Vec<u64> = std::iter::from_fn(|| q.dequeue().map(|p| p.id)).collect();
+    assert_eq!(order, vec![6, 3, 5, 2, 1, 4]);
+    assert_eq!(q.bytes(), 0);
+}
+
+#[test]
+fn red_queue_average_tracks_steady_queue_size() {
+    let params = RedParams {
+        min_th: 1_000_000,
+        max_th: 2_000_000,
+        w_q: 0.1,
+        ..RedParams::default()
+    };
+    let mut q = RedQueue::new(1_000_000, params);
+    // The average samples the queue as each packet arrives.
+    assert!(q.enqueue(dyn_pkt(0, 1500)).is_ok());
+    assert_eq!(q.avg_bytes(), 0.0);
+    assert!(q.enqueue(dyn_pkt(1, 1500)).is_ok());
+    assert!((q.avg_bytes() - 150.0).abs() < 1e-9);
+    for id in 2..10 {
+        assert!(q.enqueue(dyn_pkt(id, 1500)).is_ok());
+    }
+
+    // Steady state: one departure per arrival, so every arrival sees 9 packets.
+    for id in 10..300 {
+        q.dequeue().expect("pkt");
+        assert!(q.enqueue(dyn_pkt(id, 1500)).is_ok());
+        assert!(q.avg_bytes() <= 9.0 * 1500.0 + 1e-9);
+    }
+    assert!(
+        (q.avg_bytes() - 9.0 * 1500.0).abs() < 1.0,
+        "avg={}",
+        q.avg_bytes()
+    );
+    assert_eq!(q.marked_pkts() + q.early_drops(), 0);
+}
+
+#[test]
+fn red_queue_marking_probability_rises_linearly_between_thresholds() {
+    let params = RedParams {
+        min_th: 10_000,
+        max_th: 30_000,
+        max_p: 0.2,
+        w_q: 1.0,
+        seed: 7,
+    };
+    assert_eq!(params.probability(9_999.0), 0.0);
+    assert_eq!(params.probability(10_000.0), 0.0);
+    for (avg, p) in [(15_000.0, 0.05), (20_000.0, 0.1), (25_000.0, 0.15)] {
+        assert!((params.probability(avg) - p).abs() < 1e-12, "avg={avg}");
+    }
+    assert_eq!(params.probability(30_000.0), 1.0);
+
+    // With w_q = 1 the average is the queue size seen by the arrival. Hold the
+    // queue at a fixed depth and count how many ECT arrivals get marked.
+    let ect = |id: u64| {
+        let mut p = dyn_pkt(id, 1000);
+        p.ecn = Ecn::Ect0;
+        p
+    };
+    let marked_fraction = |depth_pkts: u64| {
+        let mut q = RedQueue::new(100_000, params);
+        for id in 0..depth_pkts {
+            assert!(q.enqueue(ect(id)).is_ok());
+        }
+        let before = q.marked_pkts();
+        let trials = 20_000;
+        for id in 0..trials {
+            let res = q.enqueue(ect(depth_pkts + id));
+            assert!(res.is_ok(), "ECT packets are marked, not dropped");
+            q.dequeue().expect("pkt");
+        }
+        (q.marked_pkts() - before) as f64 / trials as f64
+    };
+    let mut prev = 0.0;
+    for depth in [15, 20, 25] {
+        let expected = params.probability(depth as f64 * 1000.0);
+        let got = marked_fraction(depth);
+        assert!((got - expected).abs() < 0.01, "depth={depth} got={got}");
+        assert!(got > prev);
+        prev = got;
+    }
+
+    // Above max_th non-ECT packets are always dropped early.
+    let mut q = RedQueue::new(100_000, params);
+    for id in 0..30 {
+        assert!(q.enqueue(ect(id)).is_ok());
+    }
+    let dropped = q.enqueue(dyn_pkt(99, 1000)).expect_err("should drop");
+    assert_eq!(dropped.id, 99);
+    assert_eq!(q.early_drops(), 1);
+    assert_eq!(q.len(), 30);
+}
+
+#[test]
+fn codel_queue_drops_after_interval_and_backs_off_below_target() {
+    let ms = SimTime::from_millis;
+    let mut q = CoDelQueue::new(1_000_000, CoDelParams::default());
+    let mut next_id = 0;
+    let mut pkt = || {
+        next_id += 1;
+        dyn_pkt(next_id, 1500)
+    };
+
+    // A 50-packet standing queue served and refilled once per ms: the head's
+    // sojourn climbs to 50ms and exceeds the 5ms target from t=5ms on.
+    for _ in 0..50 {
+        assert!(q.enqueue_at(pkt(), SimTime::ZERO).is_ok());
+    }
+    let mut drop_times = Vec::new();
+    for t in 1..300 {
+        let now = ms(t);
+        let served = q.dequeue_at(now);
+        assert!(served.is_some());
+        let dropped = q.take_dropped();
+        if !dropped.is_empty() {
+            drop_times.push(t);
+        }
+        // Nothing is dropped until the sojourn has stayed above target for an interval.
+        assert_eq!(q.is_dropping(), t >= 105, "t={t}ms");
+        assert!(q.enqueue_at(pkt(), now).is_ok());
+    }
+    // First drop at 5ms + 100ms, then interval / sqrt(count) apart: +100ms, +70.7ms.
+    assert_eq!(drop_times, vec![105, 205, 276]);
+    assert_eq!(q.dropped_pkts(), 3);
+    assert_eq!(q.count(), 3);
+
+    // Serve the backlog down to 3 packets, then keep it there: the sojourn falls
+    // to 3ms, below target, so CoDel leaves the dropping state and stops dropping.
+    while q.len() > 3 {
+        q.dequeue_at(ms(300)).expect("pkt");
+    }
+    assert!(q.take_dropped().is_empty());
+    for t in 301..600 {
+        let now = ms(t);
+        q.dequeue_at(now).expect("pkt");
+        assert!(!q.is_dropping(), "t={t}ms");
+        assert!(q.enqueue_at(pkt(), now).is_ok());
+    }
+    assert_eq!(q.dropped_pkts(), 3);
+    assert!(q.take_dropped().is_empty());
+}
@@ -0,0 +1,75 @@
+use crate::net::{DctcpSegment, Ecn, NetWorld, NodeId, SimContext, TcpSegment};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn};
+use crate::proto::tcp::{TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+
+const MSS: u32 = 1000;
+
+fn two_hosts() -> (NetWorld, NodeId, NodeId) {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world
+        .net
+        .connect(h0, h1, SimTime::from_micros(1), 1_000_000_000);
+    world
+        .net
+        .connect(h1, h0, SimTime::from_micros(1), 1_000_000_000);
+    (world, h0, h1)
+}
+
+#[test]
+fn tcp_receiver_reports_injected_reordering_gap() {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = two_hosts();
+    let cfg = TcpConfig {
+        mss: MSS,
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.insert(TcpConn::new_dynamic(1, h0, h1, 5 * MSS as u64, cfg));
+
+    // Segment 1 is overtaken by segments 2..=4: a gap of three segments.
+    let mut cx = SimContext::new(&mut sim, &mut world.net);
+    for idx in [0_u64, 2, 3, 4, 1] {
+        let seg = TcpSegment::Data {
+            seq: idx * MSS as u64,
+            len: MSS,
+        };
+        tcp.on_tcp_segment(1, h1, seg, &mut cx);
+    }
+    world.net.tcp = tcp;
+
+    let stats = world.net.flow_stats(1).expect("flow stats");
+    assert_eq!(stats.bytes_delivered, 5 * MSS as u64);
+    assert_eq!(stats.reordered_segments, 3);
+    assert_eq!(stats.max_reorder_bytes, 3 * MSS as u64);
+}
+
+#[test]
+fn dctcp_receiver_reports_injected_reordering_gap() {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = two_hosts();
+    let cfg = DctcpConfig {
+        mss: MSS,
+        ..DctcpConfig::default()
+    };
+    let mut dctcp = std::mem::take(&mut world.net.dctcp);
+    dctcp.insert(DctcpConn::new_dynamic(1, h0, h1, 4 * MSS as u64, cfg));
+
+    // In-order traffic reports nothing; then segment 2 is overtaken by 3.
+    let mut cx = SimContext::new(&mut sim, &mut world.net);
+    for idx in [0_u64, 1, 3, 2] {
+        let seg = DctcpSegment::Data {
+            seq: idx * MSS as u64,
+            len: MSS,
+        };
+        dctcp.on_dctcp_segment(1, h1, seg, Ecn::Ect0, &mut cx);
+    }
+    world.net.dctcp = dctcp;
+
+    let stats = world.net.flow_stats(1).expect("flow stats");
+    assert_eq!(stats.reordered_segments, 1);
+    assert_eq!(stats.max_reorder_bytes, MSS as u64);
+}
@@ -1,6 +1,9 @@
 use crate::cc::ring::{self, RingAllreduceConfig, RingDoneCallback, RingTransport, RoutingMode};
-use crate::net::{NetWorld, NodeId};
+use crate::net::{EcmpHashMode, FlowTags, NetWorld, NodeId};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpDoneCallback};
 use crate::sim::{Event, SimTime, Simulator, World};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::topo::fat_tree::{FatTreeOpts, build_fat_tree};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
@@ -103,6 +106,60 @@ impl RingTransport for VariableDelayTransport {
     }
 }
 
+struct TcpTransport {
+    cfg: TcpConfig,
+}
+
+impl RingTransport for TcpTransport {
+    fn start_flow(
+        &mut self,
+        flow_id: u64,
+        src: NodeId,
+        dst: NodeId,
+        chunk_bytes: u64,
+        _routing: RoutingMode,
+        sim: &mut Simulator,
+        world: &mut NetWorld,
+        done: RingDoneCallback,
+    ) {
+        let mut tcp = std::mem::take(&mut world.net.tcp);
+        let route = world.net.route_ecmp_path(src, dst, flow_id);
+        let conn = TcpConn::new(flow_id, src, dst, route, chunk_bytes, self.cfg.clone());
+        let done_cb: TcpDoneCallback = Box::new(move |_, now, sim| done(now, sim));
+        tcp.set_done_callback(flow_id, done_cb);
+        tcp.start_conn(conn, sim, &mut world.net);
+        world.net.tcp = tcp;
+    }
+}
+
+fn run_tcp_allreduce_on_dumbbell(done_notify_delay: SimTime) -> ring::RingAllreduceStats {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let cfg = TcpConfig {
+        done_notify_delay,
+        ..TcpConfig::default()
+    };
+    let handle = ring::start_ring_allreduce(
+        &mut sim,
+        RingAllreduceConfig {
+            ranks: 2,
+            hosts: vec![h0, h1],
+            chunk_bytes: 20_000,
+            routing: RoutingMode::PerFlow,
+            start_flow_id: 1,
+            transport: Box::new(TcpTransport { cfg }),
+            done_cb: None,
+            tags: FlowTags::new(),
+            direction: ring::RingDirection::Clockwise,
+            rotation: 0,
+            reduce_ns_per_byte: 0.0,
+        },
+    );
+    sim.run(&mut world);
+    handle.stats()
+}
+
 fn run_collective(
     ranks: usize,
     start_flow_id: u64,
@@ -126,6 +183,10 @@ fn run_collective(
         start_flow_id,
         transport: Box::new(transport),
         done_cb: None,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
     };
 
     let mut sim = Simulator::default();
@@ -161,6 +222,10 @@ fn run_collective_at(
         start_flow_id,
         transport: Box::new(transport),
         done_cb,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
     };
 
     let mut sim = Simulator::default();
@@ -270,6 +335,39 @@ fn ring_reducescatter_reduce_done_at_equals_done_at() {
     );
 }
 
+#[test]
+fn ring_allreduce_phase_flow_ids_partition_flows_and_bytes_split_evenly() {
+    let ranks = 4;
+    let start_flow_id = 500;
+    let (handle, records, _final_time) = run_collective(
+        ranks,
+        start_flow_id,
+        SimTime::from_micros(10),
+        ring::start_ring_allreduce,
+    );
+    let stats = handle.stats();
+    let (reduce_ids, gather_ids) = stats.phase_flow_ids.clone();
+    assert_eq!(reduce_ids, 500..512);
+    assert_eq!(gather_ids, 512..524);
+
+    let list = records.lock().expect("records lock");
+    assert_eq!(list.len(), 24);
+    for rec in list.iter() {
+        assert!(reduce_ids.contains(&rec.flow_id) != gather_ids.contains(&rec.flow_id));
+    }
+    let phase_bytes = |ids: &std::ops::Range<u64>| {
+        list.iter()
+            .filter(|rec| ids.contains(&rec.flow_id))
+            .map(|rec| rec.chunk_bytes)
+            .sum::<u64>()
+    };
+    assert_eq!(stats.reduce_bytes, phase_bytes(&reduce_ids));
+    assert_eq!(stats.gather_bytes, phase_bytes(&gather_ids));
+    // 3 steps * 4 flows * 123 bytes in each phase.
+    assert_eq!(stats.reduce_bytes, 3 * 4 * 123);
+    assert_eq!(stats.reduce_bytes, stats.gather_bytes);
+}
+
 #[test]
 fn ring_allgather_has_no_reduce_done_at() {
     let ranks = 4;
@@ -546,6 +644,10 @@ fn ring_alltoall_waits_for_slowest_flow_each_step_with_variable_delays() {
         start_flow_id,
         transport: Box::new(transport),
         done_cb,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
     };
 
     let mut sim = Simulator::default();
@@ -739,3 +841,632 @@ fn ring_alltoall_covers_all_pairs_for_multiple_sizes() {
         assert_eq!(seen.len(), ranks * (ranks - 1));
     }
 }
+
+#[test]
+fn ring_allreduce_tcp_done_notify_delay_pushes_done_at_later() {
+    let base = run_tcp_allreduce_on_dumbbell(SimTime::ZERO);
+    let delay = SimTime::from_micros(50);
+    let delayed = run_tcp_allreduce_on_dumbbell(delay);
+
+    let base_done = base.done_at.expect("base allreduce finishes");
+    let delayed_done = delayed.done_at.expect("delayed allreduce finishes");
+    assert_eq!(base.total_steps, delayed.total_steps);
+
+    // Every step waits for its (delayed) completion notification before the next starts.
+    let expected = time_add(base_done, time_mul(delay, delayed.total_steps as u64));
+    assert_eq!(delayed_done, expected);
+    for (b, d) in base.flow_fct_ns.iter().zip(&delayed.flow_fct_ns) {
+        assert_eq!(*d, b + delay.0);
+    }
+}
+
+#[test]
+fn ring_flow_tags_allow_filtering_flow_stats_by_tag() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+
+    let mut handles = Vec::new();
+    for (layer, start_flow_id, chunk_bytes) in [("l0", 1_u64, 10_000_u64), ("l1", 100, 50_000)] {
+        let mut tags = FlowTags::new();
+        tags.insert("layer".to_string(), layer.to_string());
+        handles.push(ring::start_ring_allgather(
+            &mut sim,
+            RingAllreduceConfig {
+                ranks: 2,
+                hosts: vec![h0, h1],
+                chunk_bytes,
+                routing: RoutingMode::PerFlow,
+                start_flow_id,
+                transport: Box::new(TcpTransport {
+                    cfg: TcpConfig::default(),
+                }),
+                done_cb: None,
+                tags,
+                direction: ring::RingDirection::Clockwise,
+                rotation: 0,
+                reduce_ns_per_byte: 0.0,
+            },
+        ));
+    }
+    sim.run(&mut world);
+
+    // Allgather over 2 ranks is a single step with one flow per rank.
+    assert_eq!(world.net.flows_with_tag("layer", "l0"), vec![1, 2]);
+    assert_eq!(world.net.flows_with_tag("layer", "l1"), vec![100, 101]);
+    assert!(world.net.flows_with_tag("layer", "l2").is_empty());
+    assert!(world.net.flows_with_tag("tenant", "l0").is_empty());
+
+    let fct_ns_for = |layer: &str| {
+        world
+            .net
+            .flows_with_tag("layer", layer)
+            .into_iter()
+            .map(|id| {
+                let c = world.net.tcp.get(id).expect("tagged tcp conn");
+                assert_eq!(
+                    world
+                        .net
+                        .flow_tags(id)
+                        .and_then(|t| t.get("layer"))
+                        .map(String::as_str),
+                    Some(layer)
+                );
+                c.done_time().expect("done").0 - c.start_time().expect("started").0
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut l1 = fct_ns_for("l1");
+    l1.sort_unstable();
+    let mut expected = handles[1].stats().flow_fct_ns;
+    expected.sort_unstable();
+    assert_eq!(l1, expected);
+    assert_eq!(fct_ns_for("l0").len(), 2);
+}
+
+#[test]
+fn ring_allreduce_reports_per_step_reduce_and_transfer_time() {
+    let ranks = 4;
+    let delay = SimTime::from_micros(3);
+    let chunk_bytes = 1_000;
+    let reduce_ns_per_byte = 2.5;
+    let transport = RecordingTransport {
+        delay,
+        records: Arc::new(Mutex::new(Vec::new())),
+    };
+    let cfg = RingAllreduceConfig {
+        ranks,
+        hosts: (0..ranks).map(NodeId).collect(),
+        chunk_bytes,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(transport),
+        done_cb: None,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte,
+    };
+
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let handle = ring::start_ring_allreduce(&mut sim, cfg);
+    sim.run(&mut world);
+
+    let stats = handle.stats();
+    let compute = (reduce_ns_per_byte * chunk_bytes as f64) as u64;
+    // Only the reduce-scatter half (ranks - 1 steps) pays the reduction cost.
+    assert_eq!(
+        stats.step_compute_ns,
+        vec![compute, compute, compute, 0, 0, 0]
+    );
+    assert_eq!(stats.step_transfer_ns, vec![delay.0; 6]);
+    assert_eq!(
+        stats.reduce_done_at,
+        Some(SimTime(3 * delay.0 + 3 * compute))
+    );
+    assert_eq!(stats.done_at, Some(SimTime(6 * delay.0 + 3 * compute)));
+}
+
+#[test]
+fn ring_reducescatter_finishes_after_last_reduction() {
+    let ranks = 3;
+    let delay = SimTime::from_micros(1);
+    let cfg = RingAllreduceConfig {
+        ranks,
+        hosts: (0..ranks).map(NodeId).collect(),
+        chunk_bytes: 400,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(RecordingTransport {
+            delay,
+            records: Arc::new(Mutex::new(Vec::new())),
+        }),
+        done_cb: None,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 1.0,
+    };
+
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let handle = ring::start_ring_reducescatter(&mut sim, cfg);
+    sim.run(&mut world);
+
+    let stats = handle.stats();
+    assert_eq!(stats.step_compute_ns, vec![400, 400]);
+    assert_eq!(stats.done_at, Some(SimTime(2 * delay.0 + 800)));
+    assert_eq!(stats.reduce_done_at, stats.done_at);
+}
+
+fn run_oriented_allgather(
+    ranks: usize,
+    direction: ring::RingDirection,
+    rotation: usize,
+) -> Vec<FlowStart> {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let cfg = RingAllreduceConfig {
+        ranks,
+        hosts: (0..ranks).map(NodeId).collect(),
+        chunk_bytes: 100,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(RecordingTransport {
+            delay: SimTime::from_micros(1),
+            records: Arc::clone(&records),
+        }),
+        done_cb: None,
+        tags: FlowTags::new(),
+        direction,
+        rotation,
+        reduce_ns_per_byte: 0.0,
+    };
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    ring::start_ring_allgather(&mut sim, cfg);
+    sim.run(&mut world);
+    records.lock().expect("records lock").clone()
+}
+
+#[test]
+fn ring_counterclockwise_sends_to_previous_rank() {
+    let n = 4;
+    let flows = run_oriented_allgather(n, ring::RingDirection::CounterClockwise, 0);
+    assert_eq!(flows.len(), n * (n - 1));
+    for f in &flows {
+        assert_eq!(f.dst.0, (f.src.0 + n - 1) % n, "flow {f:?}");
+    }
+
+    let flows = run_oriented_allgather(n, ring::RingDirection::Clockwise, 0);
+    for f in &flows {
+        assert_eq!(f.dst.0, (f.src.0 + 1) % n, "flow {f:?}");
+    }
+}
+
+#[test]
+fn ring_rotation_shifts_which_rank_gets_the_first_flow_id() {
+    let flows = run_oriented_allgather(4, ring::RingDirection::Clockwise, 2);
+    let first_step = flows
+        .iter()
+        .filter(|f| f.flow_id <= 4)
+        .map(|f| (f.flow_id, f.src.0))
+        .collect::<Vec<_>>();
+    assert_eq!(first_step, vec![(1, 2), (2, 3), (3, 0), (4, 1)]);
+}
+
+#[test]
+fn ring_alltoallv_sends_each_pair_its_matrix_entry() {
+    let n = 4;
+    let matrix: Vec<Vec<u64>> = vec![
+        vec![0, 100, 2000, 0],
+        vec![10, 0, 0, 40_000],
+        vec![300, 5, 0, 7],
+        vec![0, 0, 900, 0],
+    ];
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let done = Arc::new(AtomicUsize::new(0));
+    let done_count = Arc::clone(&done);
+    let cfg = RingAllreduceConfig {
+        ranks: n,
+        hosts: (0..n).map(NodeId).collect(),
+        chunk_bytes: 1,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(RecordingTransport {
+            delay: SimTime::from_micros(1),
+            records: Arc::clone(&records),
+        }),
+        done_cb: Some(Box::new(move |_, _| {
+            done_count.fetch_add(1, Ordering::SeqCst);
+        })),
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
+    };
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let handle = ring::start_ring_alltoallv_at(&mut sim, cfg, matrix.clone(), SimTime::ZERO);
+    sim.run(&mut world);
+
+    assert_eq!(done.load(Ordering::SeqCst), 1);
+    assert!(handle.stats().done_at.is_some());
+    let sent = records
+        .lock()
+        .expect("records lock")
+        .iter()
+        .map(|f| ((f.src.0, f.dst.0), f.chunk_bytes))
+        .collect::<HashMap<_, _>>();
+    let expected = (0..n)
+        .flat_map(|i| (0..n).map(move |j| (i, j)))
+        .filter(|&(i, j)| i != j && matrix[i][j] > 0)
+        .map(|(i, j)| ((i, j), matrix[i][j]))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(sent, expected);
+}
+
+/// Finishes each flow after one nanosecond per byte, so bigger pairs are slower.
+struct ByteDelayTransport {
+    records: Arc<Mutex<Vec<FlowStart>>>,
+}
+
+impl RingTransport for ByteDelayTransport {
+    fn start_flow(
+        &mut self,
+        flow_id: u64,
+        src: NodeId,
+        dst: NodeId,
+        chunk_bytes: u64,
+        routing: RoutingMode,
+        sim: &mut Simulator,
+        _world: &mut NetWorld,
+        done: RingDoneCallback,
+    ) {
+        let start_at = sim.now();
+        let done_at = SimTime(start_at.0.saturating_add(chunk_bytes));
+        self.records.lock().expect("records lock").push(FlowStart {
+            flow_id,
+            src,
+            dst,
+            routing,
+            start_at,
+            done_at,
+            chunk_bytes,
+        });
+        sim.schedule(done_at, CallDone { done });
+    }
+}
+
+#[test]
+fn ring_alltoallv_steps_wait_for_their_largest_pair() {
+    let n = 3;
+    // Step 0 sends i -> i+1, step 1 sends i -> i+2.
+    let matrix: Vec<Vec<u64>> = vec![vec![0, 500, 20], vec![30, 0, 4_000], vec![900, 60, 0]];
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let cfg = RingAllreduceConfig {
+        ranks: n,
+        hosts: (0..n).map(NodeId).collect(),
+        chunk_bytes: 1,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(ByteDelayTransport {
+            records: Arc::clone(&records),
+        }),
+        done_cb: None,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
+    };
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let handle = ring::start_ring_alltoallv_at(&mut sim, cfg, matrix.clone(), SimTime::ZERO);
+    sim.run(&mut world);
+
+    let list = records.lock().expect("records lock");
+    for rec in list.iter() {
+        assert_eq!(rec.chunk_bytes, matrix[rec.src.0][rec.dst.0]);
+    }
+    let step0 = [500, 4_000, 900];
+    let step1 = [20, 30, 60];
+    let step1_start = *step0.iter().max().unwrap();
+    for rec in list.iter() {
+        let expected_start = if step0.contains(&rec.chunk_bytes) {
+            0
+        } else {
+            step1_start
+        };
+        assert_eq!(rec.start_at, SimTime(expected_start), "{rec:?}");
+    }
+    let stats = handle.stats();
+    assert_eq!(stats.step_transfer_ns, vec![4_000, 60]);
+    assert_eq!(stats.done_at, Some(SimTime(4_060)));
+    assert_eq!(stats.gather_bytes, step0.iter().chain(&step1).sum::<u64>());
+}
+
+#[test]
+fn ring_allreduce_spreads_tx_and_rx_bytes_evenly_across_ranks() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let sw = world.net.add_switch("s0");
+    let hosts: Vec<NodeId> = (0..4)
+        .map(|i| {
+            let h = world.net.add_host(format!("h{i}"));
+            world
+                .net
+                .connect(h, sw, SimTime::from_micros(1), 10_000_000_000);
+            world
+                .net
+                .connect(sw, h, SimTime::from_micros(1), 10_000_000_000);
+            h
+        })
+        .collect();
+    ring::start_ring_allreduce(
+        &mut sim,
+        RingAllreduceConfig {
+            ranks: hosts.len(),
+            hosts: hosts.clone(),
+            chunk_bytes: 50_000,
+            routing: RoutingMode::PerFlow,
+            start_flow_id: 1,
+            transport: Box::new(TcpTransport {
+                cfg: TcpConfig::default(),
+            }),
+            done_cb: None,
+            tags: FlowTags::new(),
+            direction: ring::RingDirection::Clockwise,
+            rotation: 0,
+            reduce_ns_per_byte: 0.0,
+        },
+    );
+    sim.run(&mut world);
+
+    assert_eq!(world.net.host_tx_bytes(sw), 0);
+    let tx = hosts
+        .iter()
+        .map(|h| world.net.host_tx_bytes(*h))
+        .collect::<Vec<_>>();
+    let rx = hosts
+        .iter()
+        .map(|h| world.net.host_rx_bytes(*h))
+        .collect::<Vec<_>>();
+    // Each rank sends and receives one chunk per step over 2(n-1) steps, plus ACKs.
+    let min_bytes = 2 * 3 * 50_000;
+    for bytes in tx.iter().chain(&rx) {
+        assert!(*bytes >= min_bytes, "tx={tx:?} rx={rx:?}");
+    }
+    let (lo, hi) = (
+        *tx.iter().chain(&rx).min().unwrap(),
+        *tx.iter().chain(&rx).max().unwrap(),
+    );
+    assert!(hi - lo <= hi / 20, "tx={tx:?} rx={rx:?}");
+    assert_eq!(tx.iter().sum::<u64>(), rx.iter().sum::<u64>());
+}
+
+#[test]
+fn ring_allreduce_pause_and_resume_excludes_paused_interval() {
+    let ranks = 4;
+    let delay = SimTime::from_micros(3);
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let cfg = RingAllreduceConfig {
+        ranks,
+        hosts: (0..ranks).map(NodeId).collect(),
+        chunk_bytes: 1_000,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(RecordingTransport {
+            delay,
+            records: Arc::clone(&records),
+        }),
+        done_cb: None,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
+    };
+
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let handle = ring::start_ring_allreduce(&mut sim, cfg);
+
+    // Pause while step 2 is in flight: it finishes, step 3 is held back.
+    sim.run_until(SimTime(delay.0 + delay.0 / 2), &mut world);
+    handle.pause();
+    sim.run(&mut world);
+    let stats = handle.stats();
+    assert!(handle.is_paused());
+    assert_eq!(stats.step_transfer_ns.len(), 2);
+    assert!(stats.pending_flows.is_empty());
+    assert_eq!(stats.done_at, None);
+    assert_eq!(sim.now(), SimTime(2 * delay.0));
+
+    let resume_at = SimTime::from_micros(50);
+    sim.run_until(resume_at, &mut world);
+    handle.resume(&mut sim);
+    sim.run(&mut world);
+
+    let stats = handle.stats();
+    assert!(!handle.is_paused());
+    assert_eq!(stats.step_transfer_ns, vec![delay.0; 6]);
+    assert_eq!(stats.paused_ns, resume_at.0 - 2 * delay.0);
+    assert_eq!(stats.done_at, Some(SimTime(resume_at.0 + 4 * delay.0)));
+    assert_eq!(stats.makespan_ns(), Some(6 * delay.0));
+
+    // Flow ids continue where the paused step left off.
+    let records = records.lock().unwrap();
+    let ids: Vec<u64> = records.iter().map(|r| r.flow_id).collect();
+    assert_eq!(ids, (1..=6 * ranks as u64).collect::<Vec<_>>());
+    assert_eq!(records[2 * ranks].start_at, resume_at);
+}
+
+#[test]
+fn ring_collectives_label_flows_by_collective_step_and_rank() {
+    let ranks = 3;
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    for (start_flow_id, start_at) in [(1, SimTime::ZERO), (100, SimTime::from_millis(1))] {
+        let transport = RecordingTransport {
+            delay: SimTime::from_micros(1),
+            records: Arc::new(Mutex::new(Vec::new())),
+        };
+        let cfg = RingAllreduceConfig {
+            ranks,
+            hosts: (0..ranks).map(NodeId).collect(),
+            chunk_bytes: 123,
+            routing: RoutingMode::PerFlow,
+            start_flow_id,
+            transport: Box::new(transport),
+            done_cb: None,
+            tags: FlowTags::new(),
+            direction: ring::RingDirection::Clockwise,
+            rotation: 0,
+            reduce_ns_per_byte: 0.0,
+        };
+        ring::start_ring_allgather_at(&mut sim, cfg, start_at);
+    }
+    sim.run(&mut world);
+
+    // Steps use `ranks` consecutive flow ids, one per sending rank.
+    assert_eq!(world.net.flow_label(1), Some("c0:step0:rank0"));
+    assert_eq!(world.net.flow_label(3), Some("c0:step0:rank2"));
+    assert_eq!(world.net.flow_label(5), Some("c0:step1:rank1"));
+    assert_eq!(world.net.flow_label(100), Some("c1:step0:rank0"));
+    assert_eq!(world.net.flow_label(105), Some("c1:step1:rank2"));
+    assert_eq!(world.net.flow_label(106), None);
+}
+
+/// TCP transport that sprays every packet over ECMP (`TcpConn::new_dynamic`).
+struct SprayedTcpTransport;
+
+impl RingTransport for SprayedTcpTransport {
+    fn start_flow(
+        &mut self,
+        flow_id: u64,
+        src: NodeId,
+        dst: NodeId,
+        chunk_bytes: u64,
+        _routing: RoutingMode,
+        sim: &mut Simulator,
+        world: &mut NetWorld,
+        done: RingDoneCallback,
+    ) {
+        let cfg = TcpConfig {
+            handshake: false,
+            ..TcpConfig::default()
+        };
+        let mut tcp = std::mem::take(&mut world.net.tcp);
+        let conn = TcpConn::new_dynamic(flow_id, src, dst, chunk_bytes, cfg);
+        let done_cb: TcpDoneCallback = Box::new(move |_, now, sim| done(now, sim));
+        tcp.set_done_callback(flow_id, done_cb);
+        tcp.start_conn(conn, sim, &mut world.net);
+        world.net.tcp = tcp;
+    }
+}
+
+fn run_sprayed_tcp_allreduce_on_fat_tree() -> ring::RingAllreduceStats {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let topo = build_fat_tree(&mut world, &FatTreeOpts::default());
+    world.net.set_ecmp_hash_mode(EcmpHashMode::Packet);
+    let ranks = 4;
+    let handle = ring::start_ring_allreduce(
+        &mut sim,
+        RingAllreduceConfig {
+            ranks,
+            // One host per pod, so every flow crosses the core.
+            hosts: topo.hosts.iter().step_by(4).copied().collect(),
+            chunk_bytes: 100_000,
+            routing: RoutingMode::PerPacket,
+            start_flow_id: 1,
+            transport: Box::new(SprayedTcpTransport),
+            done_cb: None,
+            tags: FlowTags::new(),
+            direction: ring::RingDirection::Clockwise,
+            rotation: 0,
+            reduce_ns_per_byte: 0.0,
+        },
+    );
+    sim.run(&mut world);
+    handle.stats()
+}
+
+#[test]
+fn sprayed_tcp_allreduce_step_done_times_increase_and_repeat_across_runs() {
+    let stats = run_sprayed_tcp_allreduce_on_fat_tree();
+    assert_eq!(stats.step_done_times.len(), stats.total_steps);
+    assert!(
+        stats.step_done_times.windows(2).all(|w| w[0] < w[1]),
+        "{:?}",
+        stats.step_done_times
+    );
+    assert_eq!(stats.step_done_times.last().copied(), stats.done_at);
+    let start = stats.start_at.expect("started");
+    for (i, done) in stats.step_done_times.iter().enumerate() {
+        let step_start: u64 = stats.step_transfer_ns[..i].iter().sum();
+        assert_eq!(done.0 - start.0, step_start + stats.step_transfer_ns[i]);
+    }
+
+    let again = run_sprayed_tcp_allreduce_on_fat_tree();
+    assert_eq!(again.step_done_times, stats.step_done_times);
+}
+
+/// Calls `done` twice for the first flow of every step, straight away; the
+/// other two flows take `slow` and `3 * slow`.
+struct DoubleDoneTransport {
+    slow: SimTime,
+}
+
+impl RingTransport for DoubleDoneTransport {
+    fn start_flow(
+        &mut self,
+        flow_id: u64,
+        _src: NodeId,
+        _dst: NodeId,
+        _chunk_bytes: u64,
+        _routing: RoutingMode,
+        sim: &mut Simulator,
+        _world: &mut NetWorld,
+        done: RingDoneCallback,
+    ) {
+        if flow_id % 3 == 1 {
+            let now = sim.now();
+            done(now, sim);
+            done(now, sim);
+        } else {
+            let at = time_add(sim.now(), time_mul(self.slow, 3 - flow_id % 3));
+            sim.schedule(at, CallDone { done });
+        }
+    }
+}
+
+#[test]
+fn ring_step_barrier_ignores_repeated_done_calls() {
+    let slow = SimTime::from_micros(5);
+    let cfg = RingAllreduceConfig {
+        ranks: 3,
+        hosts: (0..3).map(NodeId).collect(),
+        chunk_bytes: 123,
+        routing: RoutingMode::PerFlow,
+        start_flow_id: 1,
+        transport: Box::new(DoubleDoneTransport { slow }),
+        done_cb: None,
+        tags: FlowTags::new(),
+        direction: ring::RingDirection::Clockwise,
+        rotation: 0,
+        reduce_ns_per_byte: 0.0,
+    };
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let handle = ring::start_ring_allgather(&mut sim, cfg);
+    sim.run(&mut world);
+
+    // Each step still waits for the slowest flow.
+    let stats = handle.stats();
+    assert_eq!(
+        stats.step_done_times,
+        vec![time_mul(slow, 3), time_mul(slow, 6)],
+        "a duplicate done released a step early"
+    );
+    assert_eq!(stats.flow_fct_ns.len(), 6);
+}
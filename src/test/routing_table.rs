@@ -1,4 +1,4 @@
-use crate::net::{NodeId, RoutingTable};
+use crate::net::{DEFAULT_ECMP_SEED, NodeId, RoutingTable};
 use std::collections::HashSet;
 
 fn build_rev_adj(adj: &[Vec<NodeId>]) -> Vec<Vec<NodeId>> {
@@ -49,8 +49,8 @@ fn routing_table_pick_ecmp_is_deterministic_and_within_candidates() {
     let rt = RoutingTable::new(123);
     let cands = [NodeId(1), NodeId(2)];
 
-    let a = rt.pick_ecmp(NodeId(0), NodeId(3), 999, &cands);
-    let b = rt.pick_ecmp(NodeId(0), NodeId(3), 999, &cands);
+    let a = rt.pick_ecmp(NodeId(0), NodeId(0), NodeId(3), 999, &cands);
+    let b = rt.pick_ecmp(NodeId(0), NodeId(0), NodeId(3), 999, &cands);
     assert_eq!(a, b);
     assert!(cands.contains(&a));
 }
@@ -132,3 +132,42 @@ fn routing_table_pick_ecmp_with_single_candidate_always_returns_it() {
         );
     }
 }
+
+#[test]
+fn pick_ecmp_hashes_src_and_dst_along_with_flow_id() {
+    let rt = RoutingTable::new(DEFAULT_ECMP_SEED);
+    let cands = [NodeId(100), NodeId(101)];
+    let from = NodeId(99);
+    let flow_id = 7;
+
+    // Many src/dst pairs reusing one flow id spread evenly over the next hops.
+    let mut first = 0;
+    for src in 0..32 {
+        for dst in 32..64 {
+            let nh = rt.pick_ecmp(from, NodeId(src), NodeId(dst), flow_id, &cands);
+            assert_eq!(
+                nh,
+                rt.pick_ecmp(from, NodeId(src), NodeId(dst), flow_id, &cands)
+            );
+            first += (nh == cands[0]) as usize;
+        }
+    }
+    assert!(
+        (440..=584).contains(&first),
+        "{first} of 1024 on the first hop"
+    );
+
+    // The source alone changes the choice for a fixed (dst, flow_id)...
+    let by_src: HashSet<NodeId> = (0..16)
+        .map(|src| rt.pick_ecmp(from, NodeId(src), NodeId(40), flow_id, &cands))
+        .collect();
+    assert_eq!(by_src.len(), 2);
+    // ...and swapping dst with flow_id no longer cancels out as a plain XOR would.
+    let swapped = (0..64)
+        .filter(|&k| {
+            rt.pick_ecmp(from, NodeId(0), NodeId(k as usize), k + 1, &cands)
+                != rt.pick_ecmp(from, NodeId(0), NodeId(k as usize + 1), k, &cands)
+        })
+        .count();
+    assert!(swapped > 16, "{swapped} of 64 swapped pairs differ");
+}
@@ -0,0 +1,88 @@
+use crate::net::{NetWorld, SimContext};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{Event, SimTime, Simulator, World};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static FIRED_AT: AtomicU64 = AtomicU64::new(0);
+
+struct Mark;
+
+impl Event for Mark {
+    fn execute(self: Box<Self>, sim: &mut Simulator, _world: &mut dyn World) {
+        FIRED_AT.store(sim.now().0, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn sim_context_exposes_clock_scheduler_and_network() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world
+        .net
+        .connect(h0, h1, SimTime::from_micros(1), 1_000_000_000);
+
+    {
+        let mut cx = SimContext::new(&mut sim, &mut world.net);
+        assert_eq!(cx.now(), SimTime::ZERO);
+        cx.schedule(SimTime::from_micros(5), Mark);
+        let pkt = cx.net.make_packet_dynamic(7, 125, h0, h1);
+        cx.forward_from(h0, pkt);
+    }
+    sim.run(&mut world);
+
+    assert_eq!(FIRED_AT.load(Ordering::Relaxed), 5_000);
+    assert_eq!(world.net.stats.delivered_pkts, 1);
+    assert_eq!(world.net.host_rx_bytes(h1), 125);
+}
+
+/// TCP (with handshake) and DCTCP sharing an ECN-marking dumbbell bottleneck.
+fn run_mixed_flows() -> (Vec<SimTime>, usize) {
+    let mut sim = Simulator::default();
+    sim.record_events();
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    world
+        .net
+        .set_link_queue_capacity_bytes(route[1], route[2], 400 * 1500);
+    world
+        .net
+        .set_link_ecn_threshold_bytes(route[1], route[2], 10 * 1500);
+
+    let tcp_cfg = TcpConfig {
+        handshake: true,
+        ..TcpConfig::default()
+    };
+    for id in 1..=2 {
+        let conn = TcpConn::new_dynamic(id, h0, h1, 300_000, tcp_cfg.clone());
+        sim.schedule(SimTime::ZERO, TcpStart { conn });
+    }
+    let conn = DctcpConn::new(3, h0, h1, route, 300_000, DctcpConfig::default());
+    sim.schedule(SimTime::ZERO, DctcpStart { conn });
+    sim.run(&mut world);
+
+    let mut done = (1..=2)
+        .map(|id| world.net.tcp.get(id).and_then(|c| c.done_time()))
+        .collect::<Vec<_>>();
+    done.push(world.net.dctcp.get(3).and_then(|c| c.done_time()));
+    let done = done
+        .into_iter()
+        .map(|t| t.expect("flow did not finish"))
+        .collect();
+    (done, sim.event_trace().len())
+}
+
+#[test]
+fn protocol_stacks_behave_the_same_through_sim_context() {
+    // Recorded before the protocol stacks switched to `SimContext`.
+    let (done, events) = run_mixed_flows();
+    assert_eq!(
+        done,
+        vec![SimTime(669_719), SimTime(734_519), SimTime(604_980)]
+    );
+    // 7882 then, plus one RTO restart per new DCTCP ACK since DCTCP re-arms its timer.
+    assert_eq!(events, 8088);
+}
@@ -13,3 +13,34 @@ fn sim_time_unit_conversions_saturate_on_overflow() {
     assert_eq!(SimTime::from_millis(u64::MAX), SimTime(u64::MAX));
     assert_eq!(SimTime::from_secs(u64::MAX), SimTime(u64::MAX));
 }
+
+#[test]
+fn sim_time_float_and_nanos_helpers() {
+    assert_eq!(SimTime::from_nanos(42), SimTime(42));
+    assert_eq!(SimTime::from_secs_f64(1.5), SimTime(1_500_000_000));
+    assert_eq!(SimTime::from_secs_f64(2e-6), SimTime::from_micros(2));
+    assert_eq!(SimTime::from_secs_f64(0.0), SimTime::ZERO);
+    assert_eq!(SimTime::from_secs_f64(1e30), SimTime(u64::MAX));
+    assert_eq!(SimTime::from_millis(250).as_secs_f64(), 0.25);
+}
+
+#[test]
+#[should_panic(expected = "non-negative")]
+fn sim_time_from_secs_f64_rejects_negative() {
+    let _ = SimTime::from_secs_f64(-1.0);
+}
+
+#[test]
+fn link_latency_unit_issue_flags_wrong_units() {
+    assert!(SimTime::from_micros(2).link_latency_unit_issue().is_none());
+    assert!(SimTime::from_millis(10).link_latency_unit_issue().is_none());
+    assert!(SimTime::ZERO.link_latency_unit_issue().is_none());
+    // 2 "us" passed as raw ns
+    assert!(SimTime(2).link_latency_unit_issue().is_some());
+    // 2000 "us" passed to from_millis
+    assert!(
+        SimTime::from_millis(2000)
+            .link_latency_unit_issue()
+            .is_some()
+    );
+}
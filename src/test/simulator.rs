@@ -1,4 +1,7 @@
-use crate::sim::{Event, SimTime, Simulator, World};
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn};
+use crate::sim::{Event, EventToken, SimTime, Simulator, World};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use std::any::Any;
 use std::sync::{Arc, Mutex};
 
@@ -78,6 +81,35 @@ fn scheduled_events_order_by_time_then_seq() {
     assert_eq!(sim.now(), SimTime(10));
 }
 
+#[test]
+fn equal_time_events_run_in_insertion_order() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let mut sim = Simulator::default();
+    // Interleave other timestamps so the heap has to reshuffle between the
+    // equal-time pushes.
+    for id in 0..64 {
+        let at = if id % 3 == 0 {
+            SimTime(7)
+        } else {
+            SimTime(100 - id)
+        };
+        sim.schedule(
+            at,
+            Push {
+                id: id as u32,
+                log: Arc::clone(&log),
+            },
+        );
+    }
+
+    let mut world = DummyWorld::default();
+    sim.run_until(SimTime(7), &mut world);
+
+    let expected = (0..64).step_by(3).collect::<Vec<u32>>();
+    assert_eq!(*log.lock().expect("log lock"), expected);
+}
+
 #[test]
 fn event_scheduled_at_same_time_inside_event_runs_after_current_event() {
     let log = Arc::new(Mutex::new(Vec::new()));
@@ -163,3 +195,335 @@ fn run_until_advances_time_even_if_there_are_no_events() {
     assert_eq!(sim.now(), SimTime(7));
     assert_eq!(world.ticks, 0);
 }
+
+#[test]
+fn run_until_event_returns_false_when_queue_drains() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = Simulator::default();
+    let mut world = DummyWorld::default();
+    for (id, at) in [(1, 5), (2, 10)] {
+        let log = Arc::clone(&log);
+        sim.schedule(SimTime(at), Push { id, log });
+    }
+
+    assert!(!sim.run_until_event(&mut world, |ev| ev.label() == "TcpRto"));
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 2]);
+    assert_eq!(sim.now(), SimTime(10));
+    assert!(!sim.step(&mut world));
+}
+
+/// Cancels the event whose token is in `token` (filled in after scheduling).
+struct Cancel {
+    token: Arc<Mutex<Option<EventToken>>>,
+    removed: Arc<Mutex<Vec<bool>>>,
+}
+
+impl Event for Cancel {
+    fn execute(self: Box<Self>, sim: &mut Simulator, _world: &mut dyn World) {
+        let token = self.token.lock().expect("token lock").expect("token set");
+        let removed = sim.cancel(token);
+        self.removed.lock().expect("log lock").push(removed);
+    }
+}
+
+struct PushThenPause {
+    id: u32,
+    log: Arc<Mutex<Vec<u32>>>,
+}
+
+impl Event for PushThenPause {
+    fn execute(self: Box<Self>, sim: &mut Simulator, _world: &mut dyn World) {
+        self.log.lock().expect("log lock").push(self.id);
+        sim.pause();
+    }
+}
+
+#[test]
+fn cancelled_events_never_execute_even_at_same_timestamp() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let removed = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = Simulator::default();
+    let mut world = DummyWorld::default();
+    let push = |id| Push {
+        id,
+        log: Arc::clone(&log),
+    };
+
+    // Cancel before running: 2 is removed from among same-time events.
+    let t1 = sim.schedule_cancellable(SimTime(5), push(1));
+    let t2 = sim.schedule_cancellable(SimTime(5), push(2));
+    sim.schedule(SimTime(5), push(3));
+    assert!(sim.cancel(t2));
+    assert!(!sim.cancel(t2), "second cancel is a no-op");
+
+    // Cancel from inside an event at t=10: the first Cancel runs before 4
+    // (same time, earlier seq) and removes it; the second runs after 5 has
+    // already executed and finds nothing to remove.
+    let cancel = |sim: &mut Simulator| {
+        let token = Arc::new(Mutex::new(None));
+        sim.schedule(
+            SimTime(10),
+            Cancel {
+                token: Arc::clone(&token),
+                removed: Arc::clone(&removed),
+            },
+        );
+        token
+    };
+    let slot4 = cancel(&mut sim);
+    *slot4.lock().expect("token lock") = Some(sim.schedule_cancellable(SimTime(10), push(4)));
+    let t5 = sim.schedule_cancellable(SimTime(10), push(5));
+    let slot5 = cancel(&mut sim);
+    *slot5.lock().expect("token lock") = Some(t5);
+    sim.record_events();
+    sim.run(&mut world);
+
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 3, 5]);
+    assert_eq!(*removed.lock().expect("log lock"), vec![true, false]);
+    assert!(!sim.cancel(t1), "already executed");
+    assert_eq!(sim.event_trace().len(), 5, "1, 3, Cancel, 5, Cancel");
+    assert_eq!(sim.now(), SimTime(10));
+}
+
+#[test]
+fn pause_stops_run_until_resume_without_advancing_time() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = Simulator::default();
+    let mut world = DummyWorld::default();
+    sim.schedule(
+        SimTime(5),
+        Push {
+            id: 1,
+            log: Arc::clone(&log),
+        },
+    );
+    sim.schedule(
+        SimTime(10),
+        PushThenPause {
+            id: 2,
+            log: Arc::clone(&log),
+        },
+    );
+    sim.schedule(
+        SimTime(10),
+        Push {
+            id: 3,
+            log: Arc::clone(&log),
+        },
+    );
+    sim.schedule(
+        SimTime(20),
+        Push {
+            id: 4,
+            log: Arc::clone(&log),
+        },
+    );
+
+    sim.run_until(SimTime(100), &mut world);
+    assert!(sim.is_paused());
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 2]);
+    assert_eq!(
+        sim.now(),
+        SimTime(10),
+        "paused run_until keeps the current time"
+    );
+
+    // While paused, run/run_until do nothing.
+    sim.run(&mut world);
+    sim.run_until(SimTime(100), &mut world);
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 2]);
+    assert_eq!(sim.now(), SimTime(10));
+
+    sim.resume();
+    sim.run_until(SimTime(15), &mut world);
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 2, 3]);
+    assert_eq!(sim.now(), SimTime(15));
+    sim.run(&mut world);
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 2, 3, 4]);
+    assert_eq!(sim.now(), SimTime(20));
+}
+
+type ProgressLog = Arc<Mutex<Vec<(SimTime, usize)>>>;
+
+fn log_progress(sim: &mut Simulator, every: SimTime) -> ProgressLog {
+    let log: ProgressLog = Arc::default();
+    let sink = Arc::clone(&log);
+    sim.set_progress_hook(
+        every,
+        Box::new(move |at, pending| sink.lock().expect("progress lock").push((at, pending))),
+    );
+    log
+}
+
+/// Run a 1MB TCP flow across the default dumbbell until `until`.
+fn run_tcp_flow(sim: &mut Simulator, until: SimTime) {
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let conn = TcpConn::new(1, h0, h1, route, 1_000_000, TcpConfig::default());
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, sim, &mut world.net);
+    world.net.tcp = tcp;
+    sim.run_until(until, &mut world);
+}
+
+#[test]
+fn progress_hook_fires_once_per_interval_of_virtual_time() {
+    let every = SimTime::from_micros(100);
+    let until = SimTime::from_millis(2);
+
+    let mut plain = Simulator::default();
+    plain.record_events();
+    run_tcp_flow(&mut plain, until);
+
+    let mut sim = Simulator::default();
+    sim.record_events();
+    let log = log_progress(&mut sim, every);
+    run_tcp_flow(&mut sim, until);
+
+    let log = log.lock().expect("progress lock");
+    let times = log.iter().map(|(at, _)| *at).collect::<Vec<_>>();
+    let expected = (1..=20).map(|k| SimTime(k * every.0)).collect::<Vec<_>>();
+    assert_eq!(
+        times, expected,
+        "one call per 100us up to and including 2ms"
+    );
+    assert!(log[0].1 > 0, "the flow still has events queued at 100us");
+    assert_eq!(
+        sim.event_trace(),
+        plain.event_trace(),
+        "the hook must not change which events run or in what order"
+    );
+}
+
+#[test]
+fn progress_hook_catches_up_across_idle_gaps() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = Simulator::default();
+    let mut world = DummyWorld::default();
+    let progress = log_progress(&mut sim, SimTime(10));
+    sim.schedule(
+        SimTime(35),
+        Push {
+            id: 1,
+            log: Arc::clone(&log),
+        },
+    );
+    sim.schedule(
+        SimTime(50),
+        Push {
+            id: 2,
+            log: Arc::clone(&log),
+        },
+    );
+
+    sim.run(&mut world);
+    // A single sparse event still reports every boundary it jumps over, and the
+    // event due exactly on a boundary counts as pending there.
+    assert_eq!(
+        *progress.lock().expect("progress lock"),
+        vec![
+            (SimTime(10), 2),
+            (SimTime(20), 2),
+            (SimTime(30), 2),
+            (SimTime(40), 1),
+            (SimTime(50), 1),
+        ]
+    );
+    assert_eq!(*log.lock().expect("log lock"), vec![1, 2]);
+}
+
+#[test]
+fn progress_hook_stops_at_the_end_of_time() {
+    let mut sim = Simulator::default();
+    let mut world = DummyWorld::default();
+    let every = SimTime(u64::MAX / 2 + 1);
+    let progress = log_progress(&mut sim, every);
+    sim.run_until(SimTime(u64::MAX), &mut world);
+    assert_eq!(*progress.lock().expect("progress lock"), vec![(every, 0)]);
+
+    let mut sim = Simulator::default();
+    let progress = log_progress(&mut sim, SimTime(u64::MAX));
+    sim.run_until(SimTime(u64::MAX), &mut world);
+    sim.run_until(SimTime(u64::MAX), &mut world);
+    assert_eq!(
+        *progress.lock().expect("progress lock"),
+        vec![(SimTime(u64::MAX), 0)]
+    );
+}
+
+#[test]
+fn simulator_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Simulator>();
+}
+
+#[test]
+fn metrics_count_every_executed_event_across_run_modes() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = Simulator::default();
+    let mut world = DummyWorld::default();
+    assert_eq!(sim.metrics().events_executed, 0);
+    assert_eq!(sim.metrics().events_per_sec, 0.0);
+
+    for id in 1..=4 {
+        sim.schedule(
+            SimTime(u64::from(id) * 10),
+            PushThenScheduleNow {
+                id,
+                next_id: id + 100,
+                log: Arc::clone(&log),
+            },
+        );
+    }
+    let cancelled = sim.schedule_cancellable(
+        SimTime(15),
+        Push {
+            id: 99,
+            log: Arc::clone(&log),
+        },
+    );
+    assert!(sim.cancel(cancelled));
+
+    sim.run_until(SimTime(20), &mut world);
+    assert!(sim.step(&mut world));
+    sim.run(&mut world);
+
+    // Each PushThenScheduleNow also runs the Push it schedules; the
+    // cancelled event is never counted.
+    let executed = log.lock().expect("log lock").len() as u64;
+    assert_eq!(executed, 8);
+    let metrics = sim.metrics();
+    assert_eq!(metrics.events_executed, executed);
+    assert_eq!(metrics.events_executed, world.ticks as u64);
+}
+
+#[test]
+fn run_for_events_stops_after_budget_and_resumes() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut sim = Simulator::default();
+    for id in 0..100 {
+        sim.schedule(
+            SimTime(10 * (id as u64 + 1)),
+            Push {
+                id,
+                log: Arc::clone(&log),
+            },
+        );
+    }
+    let mut world = DummyWorld::default();
+
+    assert!(!sim.run_for_events(40, &mut world));
+    assert_eq!(sim.now(), SimTime(400));
+    assert_eq!(*log.lock().unwrap(), (0..40).collect::<Vec<_>>());
+    assert_eq!(world.ticks, 40);
+    assert_eq!(sim.metrics().events_executed, 40);
+
+    // A zero budget runs nothing; a large one drains the remaining 60.
+    assert!(!sim.run_for_events(0, &mut world));
+    assert_eq!(sim.now(), SimTime(400));
+    assert!(sim.run_for_events(1_000, &mut world));
+    assert_eq!(sim.now(), SimTime(1_000));
+    assert_eq!(*log.lock().unwrap(), (0..100).collect::<Vec<_>>());
+    assert_eq!(world.ticks, 100);
+    assert!(sim.run_for_events(1, &mut world));
+}
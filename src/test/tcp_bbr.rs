@@ -0,0 +1,105 @@
+use crate::net::{NetWorld, NodeId};
+use crate::proto::bbr::BbrMode;
+use crate::proto::tcp::{CcAlgo, TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+
+const MSS: u64 = 1460;
+const BULK_BYTES: u64 = 20_000_000;
+
+struct BulkRun {
+    /// cwnd sampled every 100us after the first millisecond
+    cwnd_samples: Vec<u64>,
+    peak_queue_bytes: u64,
+    dropped_pkts: u64,
+    /// 20MB over the flow's completion time
+    goodput_gbps: f64,
+    bdp_bytes: Option<u64>,
+    btlbw_bps: Option<f64>,
+    mode: Option<BbrMode>,
+}
+
+/// Run one bulk flow across the dumbbell (10Gbps bottleneck with a
+/// 200-packet buffer) and report the cwnd trajectory and bottleneck queueing.
+fn bulk_flow(cc: CcAlgo) -> BulkRun {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let (s0, s1): (NodeId, NodeId) = (route[1], route[2]);
+    world.net.set_link_queue_capacity_bytes(s0, s1, 200 * MSS);
+
+    let cfg = TcpConfig {
+        cc,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new(1, h0, h1, route, BULK_BYTES, cfg);
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+
+    let mut cwnd_samples = Vec::new();
+    let mut t = SimTime::from_millis(1);
+    while t <= SimTime::from_millis(10) {
+        sim.run_until(t, &mut world);
+        cwnd_samples.push(world.net.tcp.get(1).expect("conn").cwnd_bytes());
+        t = SimTime(t.0 + SimTime::from_micros(100).0);
+    }
+    sim.run_until(SimTime::from_millis(100), &mut world);
+
+    let conn = world.net.tcp.get(1).expect("conn");
+    assert!(conn.is_done(), "{cc:?} flow did not finish");
+    let done = conn.done_time().expect("done time");
+    BulkRun {
+        cwnd_samples,
+        peak_queue_bytes: world.net.link_peak_queue_bytes(s0, s1),
+        dropped_pkts: world.net.stats.dropped_pkts,
+        goodput_gbps: BULK_BYTES as f64 * 8.0 / done.0 as f64,
+        bdp_bytes: conn.bbr_bdp_bytes(),
+        btlbw_bps: conn.bbr_btlbw_bps(),
+        mode: conn.bbr_mode(),
+    }
+}
+
+#[test]
+fn bbr_parses_from_cc_name() {
+    assert_eq!(CcAlgo::parse("BBR"), Ok(CcAlgo::Bbr));
+}
+
+#[test]
+fn bbr_cwnd_converges_near_bdp_without_filling_the_buffer() {
+    let reno = bulk_flow(CcAlgo::Reno);
+    let bbr = bulk_flow(CcAlgo::Bbr);
+
+    // Reno only backs off after overflowing the 200-packet buffer.
+    assert_eq!(reno.peak_queue_bytes, 200 * MSS);
+    assert!(reno.dropped_pkts > 0);
+    assert_eq!(reno.mode, None);
+
+    // BBR measures the 10Gbps bottleneck and settles in ProbeBW.
+    assert_eq!(bbr.mode, Some(BbrMode::ProbeBw));
+    let btlbw = bbr.btlbw_bps.expect("btlbw estimate");
+    assert!((8e9..=10.5e9).contains(&btlbw), "btlbw {btlbw} bps");
+    let bdp = bbr.bdp_bytes.expect("bdp estimate");
+    let tail = &bbr.cwnd_samples[bbr.cwnd_samples.len() / 2..];
+    for &cwnd in tail {
+        // cwnd_gain is 2; allow the 4-MSS floor on tiny BDPs.
+        assert!(
+            cwnd <= (2 * bdp).max(4 * MSS) + MSS,
+            "cwnd {cwnd} B vs bdp {bdp} B"
+        );
+    }
+    assert_eq!(bbr.dropped_pkts, 0);
+    // Staying out of the buffer must not cost throughput: BBR keeps the
+    // 10Gbps bottleneck busy.
+    assert!(
+        bbr.goodput_gbps > 9.0,
+        "bbr goodput {} Gbps",
+        bbr.goodput_gbps
+    );
+    assert!(
+        bbr.peak_queue_bytes < reno.peak_queue_bytes / 4,
+        "bbr peak queue {} B vs reno {} B",
+        bbr.peak_queue_bytes,
+        reno.peak_queue_bytes
+    );
+}
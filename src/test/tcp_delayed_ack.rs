@@ -0,0 +1,75 @@
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u32 = 1000;
+const SEGMENTS: u64 = 200;
+
+struct Outcome {
+    acks_sent: usize,
+    cwnd_growth: u64,
+    done: bool,
+}
+
+fn run_slow_start(delayed_ack: Option<SimTime>) -> Outcome {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(10);
+    world.net.connect(h0, h1, latency, 10_000_000_000);
+    world.net.connect(h1, h0, latency, 10_000_000_000);
+    world.net.viz = Some(VizLogger::default());
+
+    let cfg = TcpConfig {
+        mss: MSS,
+        init_cwnd_bytes: 2 * MSS as u64,
+        init_ssthresh_bytes: u64::MAX,
+        handshake: false,
+        delayed_ack,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, SEGMENTS * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    assert_eq!(
+        world.net.stats.dropped_pkts, 0,
+        "test expects a lossless path"
+    );
+    let acks_sent = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter(|ev| matches!(ev.kind, VizEventKind::TcpSendAck(_)))
+        .count();
+    let conn = world.net.tcp.get(1).expect("tcp conn missing");
+    Outcome {
+        acks_sent,
+        cwnd_growth: conn.cwnd_bytes() - 2 * MSS as u64,
+        done: conn.is_done(),
+    }
+}
+
+#[test]
+fn delayed_ack_halves_acks_and_slow_start_growth() {
+    let every = run_slow_start(None);
+    let delayed = run_slow_start(Some(SimTime::from_micros(200)));
+    assert!(every.done && delayed.done);
+    assert_eq!(every.acks_sent, SEGMENTS as usize);
+
+    // One ACK per two full segments (the odd tail, if any, goes out on the timer).
+    let ratio = delayed.acks_sent as f64 / every.acks_sent as f64;
+    assert!((0.45..=0.55).contains(&ratio), "ack ratio {ratio}");
+
+    // Slow start grows by at most one MSS per ACK, so stretch ACKs halve the growth.
+    let growth = delayed.cwnd_growth as f64 / every.cwnd_growth as f64;
+    assert!(
+        (0.45..=0.55).contains(&growth),
+        "cwnd growth ratio {growth}"
+    );
+}
@@ -0,0 +1,154 @@
+use crate::net::{NetWorld, NodeId};
+use crate::proto::tcp::{MPTCP_SUBFLOW_ID_STRIDE, TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+
+const BOTTLENECK_BPS: u64 = 10_000_000_000;
+const RUN_FOR: SimTime = SimTime(2_000_000);
+
+struct TwoPaths {
+    world: NetWorld,
+    /// h0 -> h1 and h2 -> h3 both cross s0 -> {a, b} -> s1
+    hosts: [NodeId; 4],
+    s0: NodeId,
+    a: NodeId,
+    b: NodeId,
+    s1: NodeId,
+}
+
+/// Two 10Gbps bottlenecks in parallel between s0 and s1, 100Gbps host links.
+fn two_paths() -> TwoPaths {
+    let mut world = NetWorld::default();
+    let latency = SimTime::from_micros(2);
+    let host_bps = 100_000_000_000;
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let h2 = world.net.add_host("h2");
+    let h3 = world.net.add_host("h3");
+    let s0 = world.net.add_switch("s0");
+    let a = world.net.add_switch("a");
+    let b = world.net.add_switch("b");
+    let s1 = world.net.add_switch("s1");
+    let mut duplex = |x, y, bps| {
+        world.net.connect(x, y, latency, bps);
+        world.net.connect(y, x, latency, bps);
+    };
+    duplex(h0, s0, host_bps);
+    duplex(h2, s0, host_bps);
+    duplex(s1, h1, host_bps);
+    duplex(s1, h3, host_bps);
+    for mid in [a, b] {
+        duplex(s0, mid, BOTTLENECK_BPS);
+        duplex(mid, s1, BOTTLENECK_BPS);
+    }
+    TwoPaths {
+        world,
+        hosts: [h0, h1, h2, h3],
+        s0,
+        a,
+        b,
+        s1,
+    }
+}
+
+fn start(sim: &mut Simulator, world: &mut NetWorld, conn: TcpConn) {
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, sim, &mut world.net);
+    world.net.tcp = tcp;
+}
+
+struct BulkBytes {
+    /// Connection-level bytes acked for the h0 -> h1 flow
+    total: u64,
+    /// Of which carried over path a
+    on_path_a: u64,
+    /// Bytes acked for the single-path h2 -> h3 competitor
+    competitor: u64,
+}
+
+/// Bytes a bulk h0 -> h1 flow over path `a` gets acked within `RUN_FOR`,
+/// optionally split into `subflows` and competing with a single-path
+/// h2 -> h3 flow on the same path.
+fn bulk_bytes(subflows: usize, competitor: bool) -> BulkBytes {
+    let mut sim = Simulator::default();
+    let TwoPaths {
+        mut world,
+        hosts: [h0, h1, h2, h3],
+        s0,
+        a,
+        b,
+        s1,
+    } = two_paths();
+    let cfg = TcpConfig {
+        subflows,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new(1, h0, h1, vec![h0, s0, a, s1, h1], 1 << 30, cfg.clone());
+    start(&mut sim, &mut world, conn);
+    if competitor {
+        let conn = TcpConn::new(
+            2,
+            h2,
+            h3,
+            vec![h2, s0, a, s1, h3],
+            1 << 30,
+            TcpConfig { subflows: 1, ..cfg },
+        );
+        start(&mut sim, &mut world, conn);
+    }
+    sim.run_until(RUN_FOR, &mut world);
+
+    let tcp = &world.net.tcp;
+    let total = match tcp.subflows(1) {
+        Some(ids) => {
+            assert_eq!(ids, [1, 1 + MPTCP_SUBFLOW_ID_STRIDE]);
+            let route = |id| tcp.get(id).expect("subflow").fwd_route.clone();
+            assert_eq!(route(ids[0]), vec![h0, s0, a, s1, h1]);
+            assert_eq!(route(ids[1]), vec![h0, s0, b, s1, h1]);
+            tcp.mptcp_bytes_acked(1).expect("mptcp conn")
+        }
+        None => tcp.get(1).expect("conn").bytes_acked(),
+    };
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    BulkBytes {
+        total,
+        on_path_a: tcp.get(1).expect("conn").bytes_acked(),
+        competitor: tcp.get(2).map_or(0, TcpConn::bytes_acked),
+    }
+}
+
+#[test]
+fn two_subflows_double_throughput_over_disjoint_paths() {
+    let single = bulk_bytes(1, false).total;
+    let multi = bulk_bytes(2, false).total;
+    let line_rate = BOTTLENECK_BPS / 8 * RUN_FOR.0 / 1_000_000_000;
+    assert!(single > line_rate / 2, "single-path {single} B");
+    assert!(
+        multi as f64 > 1.7 * single as f64,
+        "mptcp {multi} B vs single-path {single} B"
+    );
+}
+
+#[test]
+fn mptcp_is_fair_to_a_single_path_flow_on_the_shared_bottleneck() {
+    let r = bulk_bytes(2, true);
+    let line_rate = BOTTLENECK_BPS / 8 * RUN_FOR.0 / 1_000_000_000;
+    // The subflow on the shared path takes no more than the single-path flow,
+    // which keeps roughly its fair half of path a...
+    assert!(
+        r.on_path_a as f64 <= 1.1 * r.competitor as f64,
+        "mptcp on path a {} B vs competitor {} B",
+        r.on_path_a,
+        r.competitor
+    );
+    assert!(
+        r.competitor as f64 > 0.45 * line_rate as f64,
+        "competitor {} B of {line_rate} B",
+        r.competitor
+    );
+    // ...while the other subflow still fills the idle path b.
+    assert!(
+        r.total - r.on_path_a > line_rate * 9 / 10,
+        "mptcp on path b {} B of {line_rate} B",
+        r.total - r.on_path_a
+    );
+}
@@ -0,0 +1,137 @@
+use crate::net::{NetWorld, NodeId};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u32 = 1000;
+const CWND_PKTS: u64 = 40;
+
+/// Two hosts joined by a 100Gbps, 10us link, with viz logging on.
+fn two_hosts() -> (NetWorld, NodeId, NodeId) {
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(10);
+    world.net.connect(h0, h1, latency, 100_000_000_000);
+    world.net.connect(h1, h0, latency, 100_000_000_000);
+    world.net.viz = Some(VizLogger::default());
+    (world, h0, h1)
+}
+
+/// `(departure time, len)` of each data segment at the source.
+fn data_sends(world: &NetWorld) -> Vec<(u64, u32)> {
+    world
+        .net
+        .viz
+        .as_ref()
+        .unwrap()
+        .events
+        .iter()
+        .filter_map(|ev| match &ev.kind {
+            VizEventKind::TcpSendData(t) => Some((ev.t_ns, t.len?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checks that the initial window leaves as one burst and that, once srtt
+/// has settled, each segment is followed by a gap of `len * srtt / cwnd`.
+fn assert_paced(sends: &[(u64, u32)], srtt: SimTime, cwnd: u64) {
+    let total: u64 = sends.iter().map(|&(_, len)| len as u64).sum();
+    assert_eq!(total, 10 * CWND_PKTS * MSS as u64);
+
+    // No RTT sample yet: the initial window still goes out as one burst.
+    let first_window = &sends[..CWND_PKTS as usize];
+    assert!(
+        first_window.iter().all(|&(t, _)| t == 0),
+        "{first_window:?}"
+    );
+
+    let tail = &sends[sends.len() - 20..];
+    for w in tail.windows(2) {
+        let ((t0, len), (t1, _)) = (w[0], w[1]);
+        let gap = (t1 - t0) as f64;
+        let expected = len as f64 * srtt.0 as f64 / cwnd as f64;
+        assert!(
+            (gap - expected).abs() <= 0.05 * expected + 1.0,
+            "gap {gap}ns after {len}B, expected ~{expected:.0}ns"
+        );
+    }
+}
+
+/// Runs one transfer and returns `(departure time, len)` of each data segment
+/// at the source, plus the final srtt and cwnd.
+fn run_transfer(pacing: bool) -> (Vec<(u64, u32)>, SimTime, u64) {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = two_hosts();
+
+    // The window stays well below the 250KB BDP, so the link never queues and
+    // srtt settles at the base RTT. Start in congestion avoidance so cwnd
+    // barely moves between segments.
+    let cfg = TcpConfig {
+        mss: MSS,
+        init_cwnd_bytes: CWND_PKTS * MSS as u64,
+        init_ssthresh_bytes: CWND_PKTS * MSS as u64,
+        handshake: false,
+        pacing,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 10 * CWND_PKTS * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    let conn = world.net.tcp.get(1).unwrap();
+    assert!(conn.is_done());
+    (data_sends(&world), conn.srtt().unwrap(), conn.cwnd_bytes())
+}
+
+/// Same transfer over DCTCP. The link has no ECN threshold, so no segment
+/// is marked and cwnd grows as in Reno congestion avoidance.
+fn run_dctcp_transfer(pacing: bool) -> (Vec<(u64, u32)>, SimTime, u64) {
+    let mut sim = Simulator::default();
+    let (mut world, h0, h1) = two_hosts();
+    let cfg = DctcpConfig {
+        mss: MSS,
+        init_cwnd_bytes: CWND_PKTS * MSS as u64,
+        init_ssthresh_bytes: CWND_PKTS * MSS as u64,
+        pacing,
+        ..DctcpConfig::default()
+    };
+    let conn = DctcpConn::new_dynamic(1, h0, h1, 10 * CWND_PKTS * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, DctcpStart { conn });
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    let conn = world.net.dctcp.get(1).unwrap();
+    assert!(conn.is_done());
+    (data_sends(&world), conn.srtt().unwrap(), conn.cwnd_bytes())
+}
+
+#[test]
+fn paced_segments_leave_at_cwnd_over_srtt() {
+    let (sends, srtt, cwnd) = run_transfer(true);
+    assert_paced(&sends, srtt, cwnd);
+}
+
+#[test]
+fn dctcp_paced_segments_leave_at_cwnd_over_srtt() {
+    let (sends, srtt, cwnd) = run_dctcp_transfer(true);
+    assert_paced(&sends, srtt, cwnd);
+
+    let (sends, srtt, cwnd) = run_dctcp_transfer(false);
+    let paced_gap = MSS as u64 * srtt.0 / cwnd;
+    let tail = &sends[sends.len() - 20..];
+    let min_gap = tail.windows(2).map(|w| w[1].0 - w[0].0).min().unwrap();
+    assert!(min_gap < paced_gap / 2, "min gap {min_gap}ns");
+}
+
+#[test]
+fn without_pacing_ack_clocked_segments_leave_in_bursts() {
+    let (sends, srtt, cwnd) = run_transfer(false);
+    let paced_gap = MSS as u64 * srtt.0 / cwnd;
+    let tail = &sends[sends.len() - 20..];
+    let min_gap = tail.windows(2).map(|w| w[1].0 - w[0].0).min().unwrap();
+    assert!(min_gap < paced_gap / 2, "min gap {min_gap}ns");
+}
@@ -1,6 +1,7 @@
 use crate::net::NetWorld;
-use crate::proto::tcp::{TcpConfig, TcpConn};
-use crate::sim::{SimTime, Simulator};
+use crate::proto::dctcp::{DctcpConfig, DctcpConn, DctcpStart};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{Event, SimTime, Simulator};
 use crate::viz::{VizEventKind, VizLogger};
 
 #[test]
@@ -78,3 +79,133 @@ fn tcp_rto_retransmits_after_drop_and_completes() {
         "expected at least one retransmitted data segment"
     );
 }
+
+#[test]
+fn run_until_event_stops_before_each_rto() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    world.net.connect(h0, h1, latency, 1_000_000_000);
+    world.net.connect(h1, h0, latency, 1_000_000_000);
+    // Every data packet is lost, so the sender can only make progress via RTO.
+    world.net.set_link_loss(h0, h1, 1.0, 0);
+
+    let cfg = TcpConfig {
+        init_rto: SimTime::from_millis(1),
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 10_000, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+
+    let is_rto = |ev: &dyn Event| ev.label() == "TcpRto";
+    assert!(sim.run_until_event(&mut world, is_rto));
+    assert_eq!(sim.now(), SimTime::from_millis(1));
+
+    // Fire it; the backed-off timer is the next RTO (1ms + 2ms).
+    assert!(sim.step(&mut world));
+    assert!(sim.run_until_event(&mut world, is_rto));
+    assert_eq!(sim.now(), SimTime::from_millis(3));
+}
+
+#[test]
+fn cwnd_log_records_slow_start_growth_and_rto_halving() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    world.net.connect(h0, h1, latency, 1_000_000_000);
+    world.net.connect(h1, h0, latency, 1_000_000_000);
+    // Same tail-loss setup as above: the third segment is dropped at h0.
+    world.net.set_host_egress_queue_capacity_bytes(100);
+
+    let mss = 100_u64;
+    let cfg = TcpConfig {
+        mss: mss as u32,
+        ack_bytes: 64,
+        init_cwnd_bytes: mss * 10,
+        init_ssthresh_bytes: mss * 1_000_000,
+        init_rto: SimTime::from_micros(10),
+        min_rto: SimTime::from_micros(10),
+        max_rto: SimTime::from_millis(1),
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let mut conn = TcpConn::new_dynamic(1, h0, h1, 300, cfg);
+    conn.enable_cwnd_log();
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let conn = world.net.tcp.get(1).expect("tcp conn missing");
+    assert!(conn.is_done());
+    let samples = conn.cwnd_samples().expect("cwnd log enabled");
+    assert_eq!(samples[0].cwnd_bytes, mss * 10);
+    assert_eq!(samples[0].srtt_ns, None);
+
+    // Each new ACK in slow start grows cwnd by one MSS.
+    assert_eq!(samples[1].cwnd_bytes, mss * 11);
+    assert_eq!(samples[2].cwnd_bytes, mss * 12);
+    assert_eq!(samples[2].acked_bytes, 200);
+    assert!(samples[1].srtt_ns.is_some());
+
+    let rto = samples
+        .iter()
+        .position(|s| s.cwnd_bytes == mss)
+        .expect("expected an RTO sample");
+    let before = &samples[rto - 1];
+    assert_eq!(samples[rto].ssthresh_bytes, before.cwnd_bytes / 2);
+    assert_eq!(samples[rto].acked_bytes, before.acked_bytes);
+    assert_eq!(samples.last().expect("samples").acked_bytes, 300);
+}
+
+#[test]
+fn injected_flow_failure_stops_retransmission_so_run_returns() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    world.net.connect(h0, h1, latency, 1_000_000_000);
+    world.net.connect(h1, h0, latency, 1_000_000_000);
+    world.net.set_host_max_concurrent_flows(h0, 2);
+    world.net.viz = Some(VizLogger::default());
+    // Flows 1 (TCP) and 2 (DCTCP) die mid-transfer; flow 3 queues behind them.
+    world.net.fail_flow_at(1, SimTime::from_micros(100));
+    world.net.fail_flow_at(2, SimTime::from_micros(100));
+
+    let tcp_cfg = TcpConfig {
+        init_rto: SimTime::from_millis(1),
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let dctcp_cfg = DctcpConfig {
+        init_rto: SimTime::from_millis(1),
+        ..DctcpConfig::default()
+    };
+    let tcp = TcpConn::new_dynamic(1, h0, h1, 1_000_000, tcp_cfg.clone());
+    sim.schedule(SimTime::ZERO, TcpStart { conn: tcp });
+    let dctcp = DctcpConn::new_dynamic(2, h0, h1, 1_000_000, dctcp_cfg);
+    sim.schedule(SimTime::ZERO, DctcpStart { conn: dctcp });
+    let queued = TcpConn::new_dynamic(3, h0, h1, 100_000, tcp_cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn: queued });
+    sim.run(&mut world);
+
+    assert!(!world.net.tcp.get(1).expect("flow 1").is_done());
+    assert!(!world.net.dctcp.get(2).expect("flow 2").is_done());
+    // Giving up freed the slots, so the queued flow still ran.
+    assert!(world.net.tcp.get(3).expect("flow 3").is_done());
+    // The first RTO after the failure abandons the flow instead of retransmitting.
+    let rtos = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter(|ev| matches!(ev.kind, VizEventKind::TcpRto(_)))
+        .count();
+    assert_eq!(rtos, 0);
+}
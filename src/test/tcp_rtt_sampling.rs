@@ -0,0 +1,132 @@
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u32 = 1000;
+
+/// RFC 6298 smoothing with the estimator's integer arithmetic.
+fn smoothed_rtt(samples: &[u64]) -> u64 {
+    let mut srtt = samples[0];
+    for &s in &samples[1..] {
+        srtt = srtt * 7 / 8 + s / 8;
+    }
+    srtt
+}
+
+#[test]
+fn one_cumulative_ack_feeds_a_sample_per_acked_segment() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(10);
+    world.net.connect(h0, h1, latency, 10_000_000_000);
+    world.net.connect(h1, h0, latency, 10_000_000_000);
+    world.net.viz = Some(VizLogger::default());
+
+    // seg0..seg2 leave at t=0. The delayed-ACK receiver acks seg0+seg1 at once
+    // and holds seg2; the ACK opens the window for seg3, and seg3's arrival
+    // releases a second ACK covering seg2 (sent at 0) and seg3 (sent one RTT later).
+    let cfg = TcpConfig {
+        mss: MSS,
+        init_cwnd_bytes: 3 * MSS as u64,
+        handshake: false,
+        delayed_ack: Some(SimTime::from_millis(1)),
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 4 * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let events = &world.net.viz.as_ref().unwrap().events;
+    let times = |pred: fn(&VizEventKind) -> bool| -> Vec<u64> {
+        events
+            .iter()
+            .filter(|ev| pred(&ev.kind))
+            .map(|ev| ev.t_ns)
+            .collect()
+    };
+    let sends = times(|k| matches!(k, VizEventKind::TcpSendData(_)));
+    let acks = times(|k| matches!(k, VizEventKind::TcpRecvAck(_)));
+    assert_eq!(sends.len(), 4);
+    assert_eq!(acks.len(), 2);
+    assert_eq!(sends[3], acks[0], "seg3 is sent when the first ACK arrives");
+
+    let conn = world.net.tcp.get(1).unwrap();
+    assert!(conn.is_done());
+    assert_eq!(conn.rtt_samples(), 4);
+
+    let samples = [
+        acks[0] - sends[0],
+        acks[0] - sends[1],
+        acks[1] - sends[2],
+        acks[1] - sends[3],
+    ];
+    assert!(samples[2] > samples[3]);
+    assert_eq!(conn.srtt(), Some(SimTime(smoothed_rtt(&samples))));
+    assert_eq!(conn.min_rtt(), Some(SimTime(samples[3])));
+}
+
+#[test]
+fn retransmitted_segments_are_excluded_from_rtt_samples() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world.net.connect(h0, h1, SimTime(1000), 1_000_000_000);
+    world.net.connect(h1, h0, SimTime(1000), 1_000_000_000);
+    // Room for one queued segment: of three back-to-back sends the third is dropped.
+    world.net.set_host_egress_queue_capacity_bytes(100);
+
+    let cfg = TcpConfig {
+        mss: 100,
+        init_cwnd_bytes: 1_000,
+        init_rto: SimTime::from_micros(10),
+        min_rto: SimTime::from_micros(10),
+        max_rto: SimTime::from_millis(1),
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 300, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    assert_eq!(world.net.stats.dropped_pkts, 1);
+    let stats = world.net.flow_stats(1).unwrap();
+    assert!(stats.retransmits > 0);
+    let conn = world.net.tcp.get(1).unwrap();
+    assert!(conn.is_done());
+    // Only the two segments that got through on the first attempt are timed.
+    assert_eq!(conn.rtt_samples(), 2);
+}
+
+#[test]
+fn min_rtt_on_dumbbell_is_propagation_plus_one_segment_serialization() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let opts = DumbbellOpts {
+        host_link_gbps: 100,
+        bottleneck_gbps: 10,
+        link_latency: SimTime::from_micros(2),
+        ..DumbbellOpts::default()
+    };
+    let (h0, h1, route) = build_dumbbell(&mut world, &opts);
+    let cfg = TcpConfig {
+        mss: MSS,
+        ack_bytes: 125,
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new(1, h0, h1, route, 50 * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let conn = world.net.tcp.get(1).unwrap();
+    assert!(conn.is_done());
+    // Three hops each way at 2us; a 1000B segment takes 80ns + 800ns + 80ns
+    // to serialize and a 125B ACK 10ns + 100ns + 10ns.
+    let expected = 6 * 2_000 + (80 + 800 + 80) + (10 + 100 + 10);
+    assert_eq!(conn.min_rtt(), Some(SimTime(expected)));
+}
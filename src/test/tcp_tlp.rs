@@ -0,0 +1,96 @@
+use crate::net::{NetWorld, TcpSegment, Transport};
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u32 = 1000;
+
+struct Outcome {
+    done_at: Option<SimTime>,
+    tlp_probes: u64,
+    cwnd_bytes: u64,
+    rtos: usize,
+}
+
+/// Two-segment transfer over a 1ms-each-way path whose last segment is lost once.
+fn run_tail_drop(enable_tlp: bool) -> Outcome {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_millis(1);
+    world.net.connect(h0, h1, latency, 1_000_000_000);
+    world.net.connect(h1, h0, latency, 1_000_000_000);
+    world.net.viz = Some(VizLogger::default());
+
+    // Strip the first transmission of the tail segment so the receiver never sees it.
+    let mut dropped = false;
+    world.net.add_middlebox(h0, move |pkt, _| {
+        if let Transport::Tcp(TcpSegment::Data { seq, .. }) = pkt.transport
+            && seq == MSS as u64
+            && !dropped
+        {
+            dropped = true;
+            pkt.transport = Transport::None;
+        }
+    });
+
+    let cfg = TcpConfig {
+        mss: MSS,
+        init_rto: SimTime::from_millis(200),
+        min_rto: SimTime::from_millis(1),
+        handshake: false,
+        enable_tlp,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 2 * MSS as u64, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let rtos = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter(|ev| matches!(ev.kind, VizEventKind::TcpRto(_)))
+        .count();
+    let conn = world.net.tcp.get(1).expect("tcp conn missing");
+    Outcome {
+        done_at: conn.done_time(),
+        tlp_probes: conn.tlp_probes(),
+        cwnd_bytes: conn.cwnd_bytes(),
+        rtos,
+    }
+}
+
+#[test]
+fn tail_loss_probe_recovers_single_trailing_drop_before_rto() {
+    let tlp = run_tail_drop(true);
+    let done = tlp.done_at.expect("tlp flow did not finish");
+    assert_eq!(tlp.tlp_probes, 1);
+    assert_eq!(
+        tlp.rtos, 0,
+        "the probe should repair the loss without an RTO"
+    );
+    // First ACK at ~2ms arms the probe 2*srtt (~4ms) later; it is acked ~2ms after that,
+    // far ahead of the 200ms init_rto.
+    assert!(
+        done < SimTime::from_millis(9),
+        "tlp flow finished at {done:?}"
+    );
+    assert!(
+        tlp.cwnd_bytes >= 10 * MSS as u64,
+        "probe collapsed cwnd to {}",
+        tlp.cwnd_bytes
+    );
+
+    // Without TLP the same loss waits for the RTO and restarts from one segment.
+    let base = run_tail_drop(false);
+    let base_done = base.done_at.expect("baseline flow did not finish");
+    assert_eq!(base.tlp_probes, 0);
+    assert_eq!(base.rtos, 1);
+    assert!(base_done > done);
+    assert!(base.cwnd_bytes < tlp.cwnd_bytes);
+}
@@ -0,0 +1,82 @@
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u32 = 1460;
+const LATENCY_NS: u64 = 1000;
+const BW_BPS: u64 = 1_000_000_000;
+
+struct Transfer {
+    done_at: SimTime,
+    /// (len, retrans) of every data segment the sender put on the wire
+    segments: Vec<(u32, bool)>,
+    data_bytes: u64,
+}
+
+fn run_transfer(total_bytes: u64) -> Transfer {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    world.net.connect(h0, h1, SimTime(LATENCY_NS), BW_BPS);
+    world.net.connect(h1, h0, SimTime(LATENCY_NS), BW_BPS);
+    world.net.viz = Some(VizLogger::default());
+
+    let cfg = TcpConfig {
+        mss: MSS,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, total_bytes, cfg);
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+    sim.run(&mut world);
+
+    let conn = world.net.tcp.get(1).expect("tcp conn missing");
+    let segments = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter_map(|ev| match &ev.kind {
+            VizEventKind::TcpSendData(t) => Some((t.len.unwrap_or(0), t.retrans == Some(true))),
+            _ => None,
+        })
+        .collect();
+    Transfer {
+        done_at: conn.done_time().expect("tcp conn did not complete"),
+        segments,
+        data_bytes: world.net.stats.data_bytes(),
+    }
+}
+
+fn ser_ns(bytes: u64) -> u64 {
+    bytes * 8 * 1_000_000_000 / BW_BPS
+}
+
+#[test]
+fn tcp_boundary_transfer_sizes_finish_in_one_round_trip() {
+    let mss = MSS as u64;
+    for total in [1, mss - 1, mss, mss + 1] {
+        let t = run_transfer(total);
+
+        let expected_segments: Vec<(u32, bool)> = if total <= mss {
+            vec![(total as u32, false)]
+        } else {
+            vec![(MSS, false), ((total - mss) as u32, false)]
+        };
+        assert_eq!(t.segments, expected_segments, "total={total}");
+        // The tail segment goes out byte-exact, so wire data bytes equal the transfer size.
+        assert_eq!(t.data_bytes, total, "total={total}");
+
+        // All segments leave at t=0 and the flow completes on the last ACK: no extra
+        // round trip. With two segments the tail's ACK queues behind the first ACK.
+        let ack_ns = ser_ns(TcpConfig::default().ack_bytes as u64);
+        let acks = t.segments.len() as u64;
+        let expected = ser_ns(total.min(mss)) + LATENCY_NS + acks * ack_ns + LATENCY_NS;
+        assert_eq!(t.done_at, SimTime(expected), "total={total}");
+    }
+}
@@ -0,0 +1,69 @@
+use crate::net::{NetWorld, NodeId};
+use crate::proto::tcp::{CcAlgo, TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::{VizEventKind, VizLogger};
+
+const MSS: u64 = 1460;
+
+/// Run one bulk flow across the dumbbell and return the bottleneck queue
+/// occupancy samples (bytes, at each enqueue) plus the flow's min RTT.
+fn bottleneck_queue_samples(cc: CcAlgo) -> (Vec<u64>, Option<SimTime>) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let (s0, s1): (NodeId, NodeId) = (route[1], route[2]);
+    world.net.set_link_queue_capacity_bytes(s0, s1, 200 * MSS);
+    world.net.viz = Some(VizLogger::default());
+
+    let cfg = TcpConfig {
+        cc,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new(1, h0, h1, route, 20_000_000, cfg);
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+    sim.run_until(SimTime::from_millis(100), &mut world);
+
+    let conn = world.net.tcp.get(1).expect("conn");
+    assert!(conn.is_done(), "{cc:?} flow did not finish");
+    let samples = world
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .events
+        .iter()
+        .filter_map(|ev| match ev.kind {
+            VizEventKind::Enqueue {
+                link_from,
+                link_to,
+                q_bytes,
+                ..
+            } if link_from == s0.0 && link_to == s1.0 => Some(q_bytes),
+            _ => None,
+        })
+        .collect();
+    (samples, conn.min_rtt())
+}
+
+#[test]
+fn vegas_keeps_a_small_standing_queue_unlike_reno() {
+    let (reno, _) = bottleneck_queue_samples(CcAlgo::Reno);
+    let (vegas, vegas_min_rtt) = bottleneck_queue_samples(CcAlgo::vegas());
+    let tail_avg = |q: &[u64]| {
+        let tail = &q[q.len() / 2..];
+        tail.iter().sum::<u64>() / tail.len() as u64
+    };
+    // Reno keeps growing until the 200-packet buffer overflows.
+    assert_eq!(reno.iter().max().copied(), Some(200 * MSS));
+    assert!(tail_avg(&reno) > 50 * MSS);
+    // Vegas backs off once a few packets are queued (alpha=2, beta=4).
+    assert!(
+        tail_avg(&vegas) <= 6 * MSS,
+        "vegas standing queue {} B",
+        tail_avg(&vegas)
+    );
+    assert!(vegas_min_rtt.is_some_and(|rtt| rtt > SimTime::ZERO));
+}
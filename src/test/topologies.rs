@@ -1,7 +1,10 @@
-use crate::net::{DeliverPacket, NetWorld, Packet};
+use crate::net::{DeliverPacket, EcmpHashMode, NetWorld, Packet};
+use crate::proto::tcp::{TcpConfig, TcpConn};
 use crate::sim::{SimTime, Simulator};
+use crate::topo::connect_clusters;
 use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
 use crate::topo::fat_tree::{FatTreeOpts, build_fat_tree};
+use crate::topo::torus::{TorusOpts, build_torus};
 use std::collections::HashSet;
 
 #[test]
@@ -20,6 +23,43 @@ fn dumbbell_route_delivers_packet_end_to_end() {
     assert_eq!(world.net.stats.delivered_pkts, 1);
 }
 
+/// Min RTT of a 1MB TCP flow from h0 to h1 across `opts`.
+fn dumbbell_tcp_min_rtt(opts: &DumbbellOpts) -> SimTime {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, route) = build_dumbbell(&mut world, opts);
+    let conn = TcpConn::new(1, h0, h1, route, 1_000_000, TcpConfig::default());
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    tcp.start_conn(conn, &mut sim, &mut world.net);
+    world.net.tcp = tcp;
+    sim.run(&mut world);
+    let conn = world.net.tcp.get(1).expect("conn");
+    assert!(conn.is_done());
+    conn.min_rtt().expect("rtt sample")
+}
+
+#[test]
+fn asymmetric_dumbbell_reverse_latency_shows_up_in_ack_clock() {
+    let symmetric = DumbbellOpts::default();
+    let slow_reverse = DumbbellOpts {
+        bottleneck_latency_reverse: Some(SimTime::from_micros(50)),
+        ..DumbbellOpts::default()
+    };
+
+    // Only the s1 -> s0 (ACK) direction is slower.
+    let mut world = NetWorld::default();
+    let (_, _, route) = build_dumbbell(&mut world, &slow_reverse);
+    let (s0, s1) = (route[1], route[2]);
+    assert_eq!(world.net.link_latency(s0, s1), SimTime::from_micros(2));
+    assert_eq!(world.net.link_latency(s1, s0), SimTime::from_micros(50));
+
+    // Data is serialized the same way on both, so the RTT grows by exactly
+    // the extra reverse propagation delay.
+    let base = dumbbell_tcp_min_rtt(&symmetric);
+    let slow = dumbbell_tcp_min_rtt(&slow_reverse);
+    assert_eq!(slow.0 - base.0, SimTime::from_micros(48).0);
+}
+
 #[test]
 fn fat_tree_counts_indexing_and_ecmp_variation() {
     let mut world = NetWorld::default();
@@ -27,6 +67,7 @@ fn fat_tree_counts_indexing_and_ecmp_variation() {
         k: 4,
         link_gbps: 100,
         link_latency: SimTime::from_micros(1),
+        oversubscription: 1,
     };
     let topo = build_fat_tree(&mut world, &opts);
 
@@ -85,6 +126,7 @@ fn fat_tree_shortest_paths_have_expected_lengths_and_core_usage() {
         k: 4,
         link_gbps: 100,
         link_latency: SimTime::from_micros(1),
+        oversubscription: 1,
     };
     let topo = build_fat_tree(&mut world, &opts);
 
@@ -116,3 +158,211 @@ fn fat_tree_shortest_paths_have_expected_lengths_and_core_usage() {
         "diff-pod path should traverse core: {p_diff_pod:?}"
     );
 }
+
+/// Cross-pod all-to-all of `pkts` MTU packets per host pair on a k-ary fat tree;
+/// returns the finish time and world.
+fn fat_tree_cross_pod_all_to_all(
+    k: usize,
+    oversubscription: u32,
+    pkts: u64,
+) -> (SimTime, NetWorld) {
+    let mut world = NetWorld::default();
+    let opts = FatTreeOpts {
+        k,
+        oversubscription,
+        ..FatTreeOpts::default()
+    };
+    let topo = build_fat_tree(&mut world, &opts);
+    world.net.set_ecmp_hash_mode(EcmpHashMode::Packet);
+
+    let pod_of = |i: usize| i / (opts.k / 2 * opts.k / 2);
+    let mut sim = Simulator::default();
+    let mut pkt_id = 0;
+    // Shifted order: in round `r` host i sends to host i+r, so receivers are not incast.
+    let n = topo.hosts.len();
+    for r in 1..n {
+        for i in 0..n {
+            let j = (i + r) % n;
+            if pod_of(i) == pod_of(j) {
+                continue;
+            }
+            let flow_id = (i * n + j) as u64;
+            for _ in 0..pkts {
+                let pkt = Packet::new_dynamic(pkt_id, flow_id, 1500, topo.hosts[i], topo.hosts[j]);
+                sim.schedule(
+                    SimTime::ZERO,
+                    DeliverPacket {
+                        to: topo.hosts[i],
+                        pkt,
+                    },
+                );
+                pkt_id += 1;
+            }
+        }
+    }
+    sim.run(&mut world);
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    assert_eq!(world.net.stats.delivered_pkts, pkt_id);
+    (sim.now(), world)
+}
+
+#[test]
+fn oversubscribed_fat_tree_keeps_fewer_uplinks_and_bottlenecks_all_to_all() {
+    let mut world = NetWorld::default();
+    let opts = FatTreeOpts {
+        k: 8,
+        oversubscription: 4,
+        ..FatTreeOpts::default()
+    };
+    let topo = build_fat_tree(&mut world, &opts);
+    // k/2 = 4 uplinks at 4:1 leaves exactly one per edge switch.
+    assert_eq!(topo.edge_uplinks(), 1);
+    assert_eq!(topo.hosts.len(), 128);
+    for pod in 0..opts.k {
+        for edge in 0..opts.k / 2 {
+            let uplinks = (0..opts.k / 2)
+                .filter(|&agg| {
+                    let (e, a) = (topo.edge(pod, edge), topo.agg(pod, agg));
+                    world.net.link_id(e, a).is_some()
+                })
+                .count();
+            assert_eq!(uplinks, 1, "edge p{pod}_e{edge}");
+        }
+    }
+    // Every host can still reach every other one over the reduced fabric.
+    for &src in &topo.hosts {
+        for &dst in &topo.hosts {
+            if src != dst {
+                let path = world.net.route_ecmp_path(src, dst, 0);
+                assert_eq!(path.last().copied(), Some(dst));
+            }
+        }
+    }
+
+    let (full_done, _) = fat_tree_cross_pod_all_to_all(8, 1, 4);
+    let (over_done, over_world) = fat_tree_cross_pod_all_to_all(8, 4, 4);
+    // Four hosts now share one edge uplink, cutting cross-pod capacity to a quarter.
+    // Per-packet ECMP collisions keep the non-blocking run short of ideal, so expect < 4x.
+    let slowdown = over_done.0 as f64 / full_done.0 as f64;
+    assert!(
+        slowdown > 2.5,
+        "slowdown {slowdown} ({full_done:?} vs {over_done:?})"
+    );
+
+    let (edge, agg) = (topo.edge(0, 0), topo.agg(0, 0));
+    assert!(
+        over_world.net.link_peak_queue_bytes(edge, agg) > 0,
+        "the remaining uplink should queue"
+    );
+}
+
+#[test]
+#[should_panic(expected = "must divide k/2")]
+fn fat_tree_rejects_oversubscription_that_does_not_divide_half_k() {
+    let mut world = NetWorld::default();
+    let opts = FatTreeOpts {
+        k: 4,
+        oversubscription: 4,
+        ..FatTreeOpts::default()
+    };
+    build_fat_tree(&mut world, &opts);
+}
+
+#[test]
+fn wan_link_bottlenecks_cross_cluster_traffic_only() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let opts = FatTreeOpts::default();
+    let a = build_fat_tree(&mut world, &opts);
+    let b = build_fat_tree(&mut world, &opts);
+    let (gw_a, gw_b) = (a.core_switches[0], b.core_switches[0]);
+    let wan_bps = 1_000_000_000;
+    connect_clusters(&mut world, gw_a, gw_b, wan_bps, SimTime::from_millis(1));
+    assert_eq!(world.net.link_latency(gw_b, gw_a), SimTime::from_millis(1));
+
+    // 20 packets from one host to a peer in the same cluster (flow 1)
+    // and 20 to a host in the other cluster (flow 2).
+    let (src, intra_dst, cross_dst) = (a.host(0, 0, 0), a.host(1, 0, 0), b.host(1, 0, 0));
+    let (pkts, pkt_bytes) = (20u64, 1500u32);
+    for i in 0..pkts {
+        for (flow, dst) in [(1, intra_dst), (2, cross_dst)] {
+            let pkt = world.net.make_packet_dynamic(flow, pkt_bytes, src, dst);
+            sim.schedule(SimTime(i), DeliverPacket { to: src, pkt });
+        }
+    }
+
+    // Step through the run and note when each destination received its last packet.
+    let mut done_at = [SimTime::ZERO; 2];
+    let mut delivered = [0u64; 2];
+    while sim.step(&mut world) {
+        for (k, dst) in [intra_dst, cross_dst].into_iter().enumerate() {
+            let d = world.net.host_rx_bytes(dst) / pkt_bytes as u64;
+            if d > delivered[k] {
+                delivered[k] = d;
+                done_at[k] = sim.now();
+            }
+        }
+    }
+    let [intra_done, cross_done] = done_at;
+    assert_eq!(world.net.stats.dropped_pkts, 0);
+    assert_eq!(delivered, [pkts, pkts]);
+
+    // Cross-cluster: every packet is serialized on the 1 Gbps WAN link.
+    let wan_ser_ns = pkts * pkt_bytes as u64 * 8 * 1_000_000_000 / wan_bps;
+    assert!(
+        cross_done.0 > SimTime::from_millis(1).0 + wan_ser_ns,
+        "{cross_done:?}"
+    );
+    assert!(world.net.link_peak_queue_bytes(gw_a, gw_b) > 0);
+    // Intra-cluster: 100 Gbps links, done long before a single WAN packet lands.
+    assert!(intra_done < SimTime::from_micros(50), "{intra_done:?}");
+}
+
+#[test]
+fn torus_4x4_has_degree_four_and_wraps_around() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let topo = build_torus(&mut world, &TorusOpts::default());
+    assert_eq!(topo.hosts.len(), 16);
+    assert_eq!(topo.switches.len(), 16);
+
+    for &s in &topo.switches {
+        let peers = topo
+            .switches
+            .iter()
+            .filter(|&&t| world.net.link_id(s, t).is_some())
+            .count();
+        assert_eq!(peers, 4, "switch {s:?}");
+    }
+    for (&h, &s) in topo.hosts.iter().zip(&topo.switches) {
+        assert!(world.net.link_id(h, s).is_some() && world.net.link_id(s, h).is_some());
+    }
+
+    // Opposite corners are one wraparound hop apart in each dimension; the
+    // torus diameter is k/2 hops per dimension.
+    let src = [0, 0];
+    for (dst, switch_hops) in [([3, 3], 2), ([2, 2], 4), ([1, 2], 3)] {
+        let ecmp = world
+            .net
+            .route_ecmp_path(topo.host(&src), topo.host(&dst), 1);
+        assert_eq!(ecmp.len(), switch_hops + 3, "ecmp {dst:?}: {ecmp:?}");
+        let dor = topo.dimension_order_route(&src, &dst);
+        assert_eq!(dor.len(), ecmp.len(), "dor {dst:?}: {dor:?}");
+    }
+
+    // Dimension 0 first, then dimension 1 the short way round (3 -> 0 wraps).
+    let route = topo.dimension_order_route(&[0, 0], &[2, 3]);
+    let expected = [[0, 0], [1, 0], [2, 0], [2, 3]].map(|c| topo.switch(&c));
+    assert_eq!(route[1..route.len() - 1], expected);
+    let dst = *route.last().unwrap();
+    let pkt = world.net.make_packet(1, 1500, route);
+    sim.schedule(
+        SimTime::ZERO,
+        DeliverPacket {
+            to: topo.host(&[0, 0]),
+            pkt,
+        },
+    );
+    sim.run(&mut world);
+    assert_eq!(world.net.host_rx_bytes(dst), 1500);
+}
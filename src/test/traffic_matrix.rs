@@ -0,0 +1,96 @@
+use crate::experiments::{FlowSizeDist, TrafficMatrix, TrafficMatrixOpts, traffic_matrix};
+use crate::net::NetWorld;
+use crate::sim::{SimTime, Simulator};
+use crate::topo::fat_tree::{FatTreeOpts, build_fat_tree};
+
+const HOST_BPS: u64 = 10_000_000_000;
+
+fn hosts(n: usize) -> Vec<crate::net::NodeId> {
+    let mut world = NetWorld::default();
+    (0..n)
+        .map(|i| world.net.add_host(format!("h{i}")))
+        .collect()
+}
+
+#[test]
+fn poisson_arrivals_approximate_target_load() {
+    let matrix = TrafficMatrix::uniform(hosts(8));
+    let window = SimTime::from_millis(1_000);
+    for (sizes, tolerance) in [
+        (FlowSizeDist::Fixed(100_000), 0.03),
+        (FlowSizeDist::web_search(), 0.10),
+        (
+            FlowSizeDist::Pareto {
+                mean_bytes: 200_000.0,
+                shape: 2.5,
+            },
+            0.10,
+        ),
+    ] {
+        let opts = TrafficMatrixOpts {
+            load: 0.6,
+            host_link_bps: HOST_BPS,
+            sizes: sizes.clone(),
+            duration: window,
+            ..TrafficMatrixOpts::default()
+        };
+        let flows = matrix.plan(&opts);
+        assert!(flows.windows(2).all(|w| w[0].at <= w[1].at));
+        assert!(flows.iter().all(|f| f.at < window && f.src != f.dst));
+
+        let capacity_bits = 8.0 * HOST_BPS as f64 * window.0 as f64 / 1e9;
+        let offered_bits = flows.iter().map(|f| f.bytes as f64 * 8.0).sum::<f64>();
+        let load = offered_bits / capacity_bits;
+        assert!(
+            (load - 0.6).abs() < 0.6 * tolerance,
+            "{sizes:?}: offered load {load:.3} over {} flows",
+            flows.len()
+        );
+    }
+}
+
+#[test]
+fn demand_matrix_weights_pick_host_pairs() {
+    let hosts = hosts(3);
+    // h0 -> h1 three times as often as h0 -> h2; nothing else.
+    let demand = vec![
+        vec![0.0, 3.0, 1.0],
+        vec![0.0, 0.0, 0.0],
+        vec![0.0, 0.0, 0.0],
+    ];
+    let matrix = TrafficMatrix::new(hosts.clone(), demand);
+    let opts = TrafficMatrixOpts {
+        host_link_bps: HOST_BPS,
+        sizes: FlowSizeDist::Fixed(10_000),
+        duration: SimTime::from_millis(100),
+        ..TrafficMatrixOpts::default()
+    };
+    let flows = matrix.plan(&opts);
+    assert!(flows.iter().all(|f| f.src == hosts[0]));
+    let to_h1 = flows.iter().filter(|f| f.dst == hosts[1]).count() as f64;
+    let share = to_h1 / flows.len() as f64;
+    assert!((share - 0.75).abs() < 0.03, "h0 -> h1 share {share:.3}");
+}
+
+#[test]
+fn scheduled_background_flows_start_on_plan_and_complete() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let topo = build_fat_tree(&mut world, &FatTreeOpts::default());
+    let matrix = TrafficMatrix::uniform(topo.hosts.clone());
+    let opts = TrafficMatrixOpts {
+        load: 0.3,
+        sizes: FlowSizeDist::Fixed(20_000),
+        duration: SimTime::from_micros(200),
+        ..TrafficMatrixOpts::default()
+    };
+    let run = traffic_matrix(&mut sim, &mut world, &matrix, &opts);
+    assert_eq!(run.flows(), matrix.plan(&opts).as_slice());
+    sim.run(&mut world);
+
+    let r = run.report(&world);
+    assert!(r.flows > 10, "{r:?}");
+    assert_eq!(r.flows_done, r.flows, "{r:?}");
+    assert!((r.offered_load - 0.3).abs() < 0.15, "{r:?}");
+    assert!(r.p50_fct.is_some_and(|p50| p50 <= r.p99_fct.unwrap()));
+}
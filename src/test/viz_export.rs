@@ -0,0 +1,238 @@
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::{
+    VIZ_CSV_HEADER, VIZ_STREAM_BUFFER_EVENTS, VizCsvRow, VizEvent, VizEventKind, VizLogger,
+    VizPacketKind, VizTcp, read_viz_csv,
+};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("htsim-rs-{}-{name}", std::process::id()))
+}
+
+fn pkt_event(t_ns: u64, pkt_id: u64, kind: VizEventKind) -> VizEvent {
+    VizEvent {
+        t_ns,
+        pkt_id: Some(pkt_id),
+        flow_id: Some(7),
+        pkt_bytes: Some(1500),
+        pkt_kind: Some(VizPacketKind::Data),
+        kind,
+    }
+}
+
+fn sample_events() -> Vec<VizEvent> {
+    vec![
+        pkt_event(
+            10,
+            1,
+            VizEventKind::Enqueue {
+                link_from: 0,
+                link_to: 2,
+                q_bytes: 1500,
+                q_cap_bytes: 3000,
+            },
+        ),
+        pkt_event(
+            20,
+            1,
+            VizEventKind::TxStart {
+                link_from: 0,
+                link_to: 2,
+                depart_ns: 20,
+                arrive_ns: 140,
+            },
+        ),
+        pkt_event(140, 1, VizEventKind::NodeForward { node: 2, next: 3 }),
+        pkt_event(900, 1, VizEventKind::Delivered { node: 3 }),
+        VizEvent {
+            t_ns: 1_000,
+            pkt_id: None,
+            flow_id: None,
+            pkt_bytes: None,
+            pkt_kind: None,
+            kind: VizEventKind::TcpRto(VizTcp {
+                conn_id: 9,
+                seq: Some(0),
+                ..VizTcp::default()
+            }),
+        },
+    ]
+}
+
+#[test]
+fn csv_round_trip_keeps_flattened_fields() {
+    let mut v = VizLogger::default();
+    for ev in sample_events() {
+        v.push(ev);
+    }
+    let path = temp_path("viz.csv");
+    v.write_csv(&path).expect("write csv");
+
+    let raw = fs::read_to_string(&path).expect("read csv");
+    assert_eq!(raw.lines().next(), Some(VIZ_CSV_HEADER));
+    assert_eq!(raw.lines().nth(2), Some("20,tx_start,,0,2,1,7,1500"));
+
+    let rows = read_viz_csv(&path).expect("parse csv");
+    let _ = fs::remove_file(&path);
+    let expected: Vec<VizCsvRow> = v.events.iter().map(VizCsvRow::from_event).collect();
+    assert_eq!(rows, expected);
+
+    let kinds: Vec<&str> = rows.iter().map(|r| r.kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        [
+            "enqueue",
+            "tx_start",
+            "node_forward",
+            "delivered",
+            "tcp_rto"
+        ]
+    );
+    assert_eq!(
+        (rows[2].node, rows[2].link_from, rows[2].link_to),
+        (Some(2), Some(2), Some(3))
+    );
+    assert_eq!((rows[3].node, rows[3].link_from), (Some(3), None));
+    // Transport events without packet info fall back to the connection id.
+    assert_eq!(rows[4].flow_id, Some(9));
+    assert_eq!((rows[4].pkt_id, rows[4].bytes), (None, None));
+}
+
+#[test]
+fn jsonl_logger_writes_one_event_per_line() {
+    let path = temp_path("viz.jsonl");
+    let mut v = VizLogger::jsonl(&path).expect("create jsonl");
+    assert!(v.is_streaming());
+    for ev in sample_events() {
+        v.push(ev);
+    }
+    v.flush().expect("flush jsonl");
+    assert!(v.events.is_empty());
+
+    let raw = fs::read_to_string(&path).expect("read jsonl");
+    let _ = fs::remove_file(&path);
+    let lines: Vec<serde_json::Value> = raw
+        .lines()
+        .map(|l| serde_json::from_str(l).expect("one JSON object per line"))
+        .collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[1]["kind"], "tx_start");
+    assert_eq!(lines[1]["arrive_ns"], 140);
+    assert_eq!(lines[4]["conn_id"], 9);
+}
+
+/// In-memory sink that stays readable after the logger takes ownership.
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn streaming_logger_keeps_memory_bounded_during_a_run() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    let sink = SharedBuf::default();
+    world.net.viz = Some(VizLogger::new_streaming(sink.clone()));
+
+    let cfg = TcpConfig {
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(1, h0, h1, 1_000_000, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+
+    let mut peak = 0;
+    while sim.step(&mut world) {
+        peak = peak.max(world.net.viz.as_ref().unwrap().events.len());
+    }
+    let mut v = world.net.viz.take().unwrap();
+    assert!(v.recorded() > 10 * VIZ_STREAM_BUFFER_EVENTS as u64);
+    assert!(peak <= VIZ_STREAM_BUFFER_EVENTS, "peak buffered {peak}");
+
+    v.flush().expect("flush");
+    let written = sink
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|&&b| b == b'\n')
+        .count();
+    assert_eq!(written as u64, v.recorded());
+    assert!(!v.is_truncated());
+}
+
+#[test]
+fn max_events_cap_stops_recording_and_flags_truncation() {
+    let mut v = VizLogger::default().with_max_events(3);
+    for ev in sample_events() {
+        v.push(ev);
+    }
+    assert_eq!(v.events.len(), 3);
+    assert_eq!(v.recorded(), 3);
+    assert!(v.is_truncated());
+
+    let sink = SharedBuf::default();
+    let mut v = VizLogger::new_streaming(sink.clone()).with_max_events(2);
+    for ev in sample_events() {
+        v.push(ev);
+    }
+    drop(v);
+    let raw = String::from_utf8(sink.0.lock().unwrap().clone()).unwrap();
+    assert_eq!(raw.lines().count(), 2);
+}
+
+#[test]
+fn flow_filter_keeps_only_the_selected_flow_and_meta() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    world.net.viz = Some(VizLogger::default().with_flow_filter([2].into()));
+    world.net.emit_viz_meta();
+
+    let cfg = TcpConfig {
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    for id in 1..=3 {
+        let conn = TcpConn::new_dynamic(id, h0, h1, 50_000, cfg.clone());
+        sim.schedule(SimTime::ZERO, TcpStart { conn });
+    }
+    sim.run(&mut world);
+
+    let v = world.net.viz.take().unwrap();
+    assert!(matches!(v.events[0].kind, VizEventKind::Meta { .. }));
+    let packet_events = v.events.iter().filter(|ev| ev.pkt_id.is_some()).count();
+    assert!(packet_events > 100, "{packet_events} packet events");
+    for ev in &v.events[1..] {
+        assert_eq!(ev.flow_id, Some(2), "{ev:?}");
+    }
+    assert_eq!(v.recorded(), v.events.len() as u64);
+}
+
+#[test]
+fn node_filter_keeps_events_touching_the_selected_nodes() {
+    let mut v = VizLogger::default().with_node_filter([3].into());
+    for ev in sample_events() {
+        v.push(ev);
+    }
+    let kept: Vec<_> = v.events.iter().map(|ev| ev.t_ns).collect();
+    // The 0->2 link events are dropped; the forward towards 3, the delivery
+    // at 3 and the node-less TCP timeout survive.
+    assert_eq!(kept, [140, 900, 1_000]);
+}
@@ -1,5 +1,6 @@
 use crate::net::NetWorld;
-use crate::sim::SimTime;
+use crate::proto::tcp::{TcpConfig, TcpConn, TcpStart};
+use crate::sim::{SimTime, Simulator};
 use crate::viz::{VizEventKind, VizLogger, VizNodeKind};
 use std::collections::HashMap;
 
@@ -25,7 +26,7 @@ fn viz_meta_includes_nodes_links_and_queue_caps() {
     assert_eq!(events[0].t_ns, 0);
 
     let (nodes, links) = match &events[0].kind {
-        VizEventKind::Meta { nodes, links } => (nodes, links),
+        VizEventKind::Meta { nodes, links, .. } => (nodes, links),
         _ => panic!("expected Meta event"),
     };
 
@@ -93,3 +94,50 @@ fn viz_meta_reflects_host_vs_switch_egress_queue_overrides() {
     assert_eq!(*by_pair.get(&(s0.0, h0.0)).unwrap(), 222);
     assert_eq!(*by_pair.get(&(s0.0, h1.0)).unwrap(), 222);
 }
+
+#[test]
+fn flow_labels_appear_in_meta_and_before_later_flows() {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let h0 = world.net.add_host("h0");
+    let h1 = world.net.add_host("h1");
+    let latency = SimTime::from_micros(1);
+    world.net.connect(h0, h1, latency, 10_000_000_000);
+    world.net.connect(h1, h0, latency, 10_000_000_000);
+
+    world.net.set_flow_label(1, "c0:step0:rank0");
+    world.net.set_flow_label(2, "bulk");
+    world.net.viz = Some(VizLogger::default());
+    world.net.emit_viz_meta();
+
+    // Named after the meta event: announced right before the flow's first event.
+    world.net.set_flow_label(3, "late");
+    assert_eq!(world.net.flow_label(3), Some("late"));
+    let cfg = TcpConfig {
+        handshake: false,
+        ..TcpConfig::default()
+    };
+    let conn = TcpConn::new_dynamic(3, h0, h1, 10_000, cfg);
+    sim.schedule(SimTime::ZERO, TcpStart { conn });
+    sim.run(&mut world);
+
+    let events = &world.net.viz.as_ref().expect("viz enabled").events;
+    let json = serde_json::to_value(events).expect("serialize viz events");
+    assert_eq!(json[0]["kind"], "meta");
+    assert_eq!(
+        json[0]["flow_labels"],
+        serde_json::json!({"1": "c0:step0:rank0", "2": "bulk"})
+    );
+
+    let flow3 = json
+        .as_array()
+        .expect("event array")
+        .iter()
+        .filter(|ev| ev["flow_id"] == 3)
+        .collect::<Vec<_>>();
+    assert!(flow3.len() > 1);
+    assert_eq!(flow3[0]["kind"], "flow_label");
+    assert_eq!(flow3[0]["label"], "late");
+    let label_events = flow3.iter().filter(|ev| ev["kind"] == "flow_label").count();
+    assert_eq!(label_events, 1);
+}
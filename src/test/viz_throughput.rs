@@ -0,0 +1,107 @@
+use crate::net::NetWorld;
+use crate::proto::tcp::{TcpConfig, TcpConn};
+use crate::sim::{SimTime, Simulator};
+use crate::topo::dumbbell::{DumbbellOpts, build_dumbbell};
+use crate::viz::VizLogger;
+
+/// Two 150KB TCP flows h0 -> h1 across the default dumbbell, logged by `viz`.
+fn run_two_flows(viz: VizLogger) -> (NetWorld, usize) {
+    let mut sim = Simulator::default();
+    let mut world = NetWorld::default();
+    let (h0, h1, _route) = build_dumbbell(&mut world, &DumbbellOpts::default());
+    world.net.viz = Some(viz);
+
+    let mut tcp = std::mem::take(&mut world.net.tcp);
+    for id in 1..=2 {
+        let conn = TcpConn::new_dynamic(id, h0, h1, 150_000, TcpConfig::default());
+        tcp.start_conn(conn, &mut sim, &mut world.net);
+    }
+    world.net.tcp = tcp;
+    sim.run(&mut world);
+    (world, h0.0)
+}
+
+#[test]
+fn throughput_series_sums_to_delivered_bytes() {
+    let (world, h0) =
+        run_two_flows(VizLogger::default().with_throughput_bin(SimTime::from_micros(1)));
+
+    let viz = world.net.viz.as_ref().expect("viz enabled");
+    let series = viz.throughput_series(SimTime::from_micros(10));
+    assert_eq!(series.bin_ns, 10_000);
+    assert!(series.bins > 1);
+    assert!(series.flows.iter().all(|f| f.bytes.len() == series.bins));
+    assert!(series.links.iter().all(|l| l.bytes.len() == series.bins));
+    assert_eq!(
+        series.flows.iter().map(|f| f.flow_id).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    let delivered: u64 = series.flows.iter().flat_map(|f| &f.bytes).sum();
+    assert_eq!(delivered, world.net.stats.delivered_bytes);
+
+    // h0's uplink carries every data byte of both flows at least once.
+    let uplink = series
+        .links
+        .iter()
+        .find(|l| l.from == h0)
+        .expect("h0 uplink series");
+    assert!(uplink.bytes.iter().sum::<u64>() >= 300_000);
+
+    // Re-binning a fine recording matches recording at the coarse width.
+    let (coarse, _) =
+        run_two_flows(VizLogger::default().with_throughput_bin(SimTime::from_micros(10)));
+    let coarse = coarse
+        .net
+        .viz
+        .as_ref()
+        .expect("viz enabled")
+        .throughput_series(SimTime::from_micros(10));
+    assert_eq!(coarse.bins, series.bins);
+    for (a, b) in coarse.flows.iter().zip(&series.flows) {
+        assert_eq!(a.bytes, b.bytes);
+    }
+}
+
+#[test]
+fn throughput_series_is_exact_when_raw_events_are_capped_or_filtered() {
+    let bin = SimTime::from_micros(10);
+    let (full, _) = run_two_flows(VizLogger::default().with_throughput_bin(bin));
+    let full_viz = full.net.viz.as_ref().expect("viz enabled");
+    let expected = full_viz.throughput_series(bin);
+
+    let (capped, _) = run_two_flows(
+        VizLogger::default()
+            .with_max_events(10)
+            .with_throughput_bin(bin),
+    );
+    let (filtered, _) = run_two_flows(
+        VizLogger::default()
+            .with_flow_filter([1].into_iter().collect())
+            .with_throughput_bin(bin),
+    );
+    let (streamed, _) =
+        run_two_flows(VizLogger::new_streaming(std::io::sink()).with_throughput_bin(bin));
+
+    let capped_viz = capped.net.viz.as_ref().expect("viz enabled");
+    assert!(capped_viz.is_truncated());
+    assert!(capped_viz.events.len() <= 10);
+    for world in [&capped, &filtered, &streamed] {
+        let got = world
+            .net
+            .viz
+            .as_ref()
+            .expect("viz enabled")
+            .throughput_series(bin);
+        assert_eq!(got.bins, expected.bins);
+        assert_eq!(got.flows.len(), 2);
+        for (a, b) in got.flows.iter().zip(&expected.flows) {
+            assert_eq!((a.flow_id, &a.bytes), (b.flow_id, &b.bytes));
+        }
+        for (a, b) in got.links.iter().zip(&expected.links) {
+            assert_eq!((a.from, a.to, &a.bytes), (b.from, b.to, &b.bytes));
+        }
+        let delivered: u64 = got.flows.iter().flat_map(|f| &f.bytes).sum();
+        assert_eq!(delivered, world.net.stats.delivered_bytes);
+    }
+}
@@ -87,6 +87,8 @@ fn workload_rank_step_enums_parse_snake_case() {
     assert!(matches!(kind, RankStepKind::Sendrecv));
     let kind: RankStepKind = serde_json::from_str("\"collective_wait\"").expect("parse kind");
     assert!(matches!(kind, RankStepKind::CollectiveWait));
+    let kind: RankStepKind = serde_json::from_str("\"barrier\"").expect("parse kind");
+    assert!(matches!(kind, RankStepKind::Barrier));
     let dir: SendRecvDirection = serde_json::from_str("\"recv\"").expect("parse dir");
     assert!(matches!(dir, SendRecvDirection::Recv));
 }
@@ -4,6 +4,9 @@ use crate::net::{NetWorld, NodeId};
 use crate::sim::SimTime;
 
 /// Dumbbell 拓扑配置选项
+///
+/// 正向指发送端 -> 接收端（s0 -> s1），反向为 ACK 方向；非对称字段为 `None` 时
+/// 沿用对称的 `host_link_gbps` / `bottleneck_gbps` / `link_latency`。
 #[derive(Debug, Clone)]
 pub struct DumbbellOpts {
     pub pkt_bytes: u32,
@@ -13,6 +16,16 @@ pub struct DumbbellOpts {
     pub bottleneck_gbps: u64,
     pub link_latency: SimTime,
     pub until: SimTime,
+    /// host -> 交换机方向的带宽
+    pub host_link_gbps_up: Option<u64>,
+    /// 交换机 -> host 方向的带宽
+    pub host_link_gbps_down: Option<u64>,
+    /// 瓶颈反向（s1 -> s0）带宽
+    pub bottleneck_gbps_reverse: Option<u64>,
+    /// 瓶颈正向（s0 -> s1）时延
+    pub bottleneck_latency: Option<SimTime>,
+    /// 瓶颈反向（s1 -> s0）时延；`None` 时与正向相同
+    pub bottleneck_latency_reverse: Option<SimTime>,
 }
 
 impl Default for DumbbellOpts {
@@ -25,10 +38,72 @@ impl Default for DumbbellOpts {
             bottleneck_gbps: 10,
             link_latency: SimTime::from_micros(2),
             until: SimTime::from_millis(50),
+            host_link_gbps_up: None,
+            host_link_gbps_down: None,
+            bottleneck_gbps_reverse: None,
+            bottleneck_latency: None,
+            bottleneck_latency_reverse: None,
         }
     }
 }
 
+/// 各方向链路的带宽（bps）与时延
+struct DumbbellLinks {
+    host_up_bps: u64,
+    host_down_bps: u64,
+    bottleneck_fwd_bps: u64,
+    bottleneck_rev_bps: u64,
+    host_latency: SimTime,
+    bottleneck_fwd_latency: SimTime,
+    bottleneck_rev_latency: SimTime,
+}
+
+impl DumbbellOpts {
+    fn links(&self) -> DumbbellLinks {
+        let gbps_to_bps = |g: u64| g.saturating_mul(1_000_000_000);
+        let bottleneck_fwd_latency = self.bottleneck_latency.unwrap_or(self.link_latency);
+        DumbbellLinks {
+            host_up_bps: gbps_to_bps(self.host_link_gbps_up.unwrap_or(self.host_link_gbps)),
+            host_down_bps: gbps_to_bps(self.host_link_gbps_down.unwrap_or(self.host_link_gbps)),
+            bottleneck_fwd_bps: gbps_to_bps(self.bottleneck_gbps),
+            bottleneck_rev_bps: gbps_to_bps(
+                self.bottleneck_gbps_reverse.unwrap_or(self.bottleneck_gbps),
+            ),
+            host_latency: self.link_latency,
+            bottleneck_fwd_latency,
+            bottleneck_rev_latency: self
+                .bottleneck_latency_reverse
+                .unwrap_or(bottleneck_fwd_latency),
+        }
+    }
+}
+
+/// 连接 s0 <-> s1 瓶颈
+fn connect_bottleneck(world: &mut NetWorld, links: &DumbbellLinks, s0: NodeId, s1: NodeId) {
+    world.net.connect(
+        s0,
+        s1,
+        links.bottleneck_fwd_latency,
+        links.bottleneck_fwd_bps,
+    );
+    world.net.connect(
+        s1,
+        s0,
+        links.bottleneck_rev_latency,
+        links.bottleneck_rev_bps,
+    );
+}
+
+/// 连接 host <-> 接入交换机
+fn connect_host(world: &mut NetWorld, links: &DumbbellLinks, host: NodeId, switch: NodeId) {
+    world
+        .net
+        .connect(host, switch, links.host_latency, links.host_up_bps);
+    world
+        .net
+        .connect(switch, host, links.host_latency, links.host_down_bps);
+}
+
 /// 构建 dumbbell 拓扑
 ///
 /// 拓扑结构：h0 <-> s0 <-> s1 <-> h1
@@ -39,20 +114,37 @@ pub fn build_dumbbell(world: &mut NetWorld, opts: &DumbbellOpts) -> (NodeId, Nod
     let s0 = world.net.add_switch("s0");
     let s1 = world.net.add_switch("s1");
 
-    let gbps_to_bps = |g: u64| g.saturating_mul(1_000_000_000);
-    let host_bps = gbps_to_bps(opts.host_link_gbps);
-    let bottleneck_bps = gbps_to_bps(opts.bottleneck_gbps);
-
-    // h0 <-> s0
-    world.net.connect(h0, s0, opts.link_latency, host_bps);
-    world.net.connect(s0, h0, opts.link_latency, host_bps);
-    // s0 <-> s1 (bottleneck)
-    world.net.connect(s0, s1, opts.link_latency, bottleneck_bps);
-    world.net.connect(s1, s0, opts.link_latency, bottleneck_bps);
-    // s1 <-> h1
-    world.net.connect(s1, h1, opts.link_latency, host_bps);
-    world.net.connect(h1, s1, opts.link_latency, host_bps);
+    let links = opts.links();
+    connect_host(world, &links, h0, s0);
+    connect_bottleneck(world, &links, s0, s1);
+    connect_host(world, &links, h1, s1);
 
     let route = vec![h0, s0, s1, h1];
     (h0, h1, route)
 }
+
+/// 构建多发送端 dumbbell（用于 incast 等汇聚场景）
+///
+/// 拓扑结构：h0..h{n-1} <-> s0 <-> s1 <-> rx，瓶颈为 s0 <-> s1
+/// 返回：(发送端列表, 接收端)
+pub fn build_fanin_dumbbell(
+    world: &mut NetWorld,
+    opts: &DumbbellOpts,
+    senders: usize,
+) -> (Vec<NodeId>, NodeId) {
+    let s0 = world.net.add_switch("s0");
+    let s1 = world.net.add_switch("s1");
+    let rx = world.net.add_host("rx");
+
+    let links = opts.links();
+    let hosts = (0..senders)
+        .map(|i| {
+            let h = world.net.add_host(format!("h{i}"));
+            connect_host(world, &links, h, s0);
+            h
+        })
+        .collect();
+    connect_bottleneck(world, &links, s0, s1);
+    connect_host(world, &links, rx, s1);
+    (hosts, rx)
+}
@@ -8,6 +8,9 @@ pub struct FatTreeOpts {
     pub k: usize,
     pub link_gbps: u64,
     pub link_latency: SimTime,
+    /// 边缘层超额订阅比（N 表示 N:1）：每个 edge 交换机只保留 `k/2 / N` 条上行链路；
+    /// N 必须整除 k/2，否则实际比例与配置不符（`build_fat_tree` 会 panic）
+    pub oversubscription: u32,
 }
 
 impl Default for FatTreeOpts {
@@ -16,6 +19,7 @@ impl Default for FatTreeOpts {
             k: 4,
             link_gbps: 100,
             link_latency: SimTime::from_micros(2),
+            oversubscription: 1,
         }
     }
 }
@@ -27,6 +31,7 @@ pub struct FatTreeTopology {
     pub edge_switches: Vec<NodeId>,
     pub agg_switches: Vec<NodeId>,
     pub core_switches: Vec<NodeId>,
+    edge_uplinks: usize,
 }
 
 impl FatTreeTopology {
@@ -52,6 +57,11 @@ impl FatTreeTopology {
         self.agg_switches[idx]
     }
 
+    /// 每个 edge 交换机的上行链路数（连到本 pod 的前若干个 agg 交换机）。
+    pub fn edge_uplinks(&self) -> usize {
+        self.edge_uplinks
+    }
+
     pub fn core(&self, group: usize, index: usize) -> NodeId {
         let half = self.half();
         let idx = group * half + index;
@@ -64,6 +74,15 @@ pub fn build_fat_tree(world: &mut NetWorld, opts: &FatTreeOpts) -> FatTreeTopolo
     assert!(k >= 2 && k % 2 == 0, "fat-tree k must be even and >= 2");
 
     let half = k / 2;
+    let over = opts.oversubscription as usize;
+    assert!(
+        over >= 1 && half.is_multiple_of(over),
+        "fat-tree oversubscription {}:1 must divide k/2 = {}",
+        over,
+        half
+    );
+    // 所有 edge 都连到同一组前 `uplinks` 个 agg，保证裁掉上行链路后全网仍连通
+    let uplinks = half / over;
     let link_bps = opts.link_gbps.saturating_mul(1_000_000_000);
     let latency = opts.link_latency;
 
@@ -112,7 +131,7 @@ pub fn build_fat_tree(world: &mut NetWorld, opts: &FatTreeOpts) -> FatTreeTopolo
 
     for pod in 0..k {
         for edge in 0..half {
-            for agg in 0..half {
+            for agg in 0..uplinks {
                 let edge_id = pod_edges[pod][edge];
                 let agg_id = pod_aggs[pod][agg];
                 world.net.connect(edge_id, agg_id, latency, link_bps);
@@ -138,5 +157,6 @@ pub fn build_fat_tree(world: &mut NetWorld, opts: &FatTreeOpts) -> FatTreeTopolo
         edge_switches,
         agg_switches,
         core_switches,
+        edge_uplinks: uplinks,
     }
 }
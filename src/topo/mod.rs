@@ -4,3 +4,7 @@
 
 pub mod dumbbell;
 pub mod fat_tree;
+pub mod torus;
+pub mod wan;
+
+pub use wan::connect_clusters;
@@ -0,0 +1,137 @@
+//! Torus（k-ary n-cube）拓扑构建
+//!
+//! 每个维度首尾相连的网格：每个交换机与各维度上的前后邻居相连，并挂一个 host。
+//! 既可以依赖 ECMP 最短路，也可以用 `TorusTopo::dimension_order_route` 生成预设的维序路由。
+
+use crate::net::{NetWorld, NodeId};
+use crate::sim::SimTime;
+
+#[derive(Debug, Clone)]
+pub struct TorusOpts {
+    /// 各维度的大小，例如 `[4, 4]` 为 4×4 的 2D torus
+    pub dims: Vec<usize>,
+    pub link_gbps: u64,
+    pub link_latency: SimTime,
+}
+
+impl Default for TorusOpts {
+    fn default() -> Self {
+        Self {
+            dims: vec![4, 4],
+            link_gbps: 100,
+            link_latency: SimTime::from_micros(2),
+        }
+    }
+}
+
+/// 构建好的 torus；`hosts[i]` 挂在 `switches[i]` 上，下标按第 0 维变化最快编号
+#[derive(Debug, Clone)]
+pub struct TorusTopo {
+    pub hosts: Vec<NodeId>,
+    pub switches: Vec<NodeId>,
+    pub dims: Vec<usize>,
+}
+
+impl TorusTopo {
+    /// 坐标 -> 下标
+    pub fn index(&self, coords: &[usize]) -> usize {
+        assert_eq!(coords.len(), self.dims.len(), "torus coordinate rank");
+        coords
+            .iter()
+            .zip(&self.dims)
+            .rev()
+            .fold(0, |idx, (&c, &k)| {
+                assert!(c < k, "torus coordinate {c} out of range 0..{k}");
+                idx * k + c
+            })
+    }
+
+    /// 下标 -> 坐标
+    pub fn coords(&self, mut idx: usize) -> Vec<usize> {
+        self.dims
+            .iter()
+            .map(|&k| {
+                let c = idx % k;
+                idx /= k;
+                c
+            })
+            .collect()
+    }
+
+    pub fn switch(&self, coords: &[usize]) -> NodeId {
+        self.switches[self.index(coords)]
+    }
+
+    pub fn host(&self, coords: &[usize]) -> NodeId {
+        self.hosts[self.index(coords)]
+    }
+
+    /// 维序路由：从 `src` 的 host 出发，按第 0 维、第 1 维……依次沿较短方向
+    /// （等距时取正方向）走到目标坐标，最后到达 `dst` 的 host。
+    pub fn dimension_order_route(&self, src: &[usize], dst: &[usize]) -> Vec<NodeId> {
+        let mut at = src.to_vec();
+        let mut route = vec![self.host(src), self.switch(src)];
+        for (d, &k) in self.dims.iter().enumerate() {
+            let forward = (dst[d] + k - at[d]) % k;
+            let step_forward = forward <= k - forward;
+            while at[d] != dst[d] {
+                at[d] = if step_forward {
+                    (at[d] + 1) % k
+                } else {
+                    (at[d] + k - 1) % k
+                };
+                route.push(self.switch(&at));
+            }
+        }
+        route.push(self.host(dst));
+        route
+    }
+}
+
+pub fn build_torus(world: &mut NetWorld, opts: &TorusOpts) -> TorusTopo {
+    assert!(
+        !opts.dims.is_empty() && opts.dims.iter().all(|&k| k >= 1),
+        "torus dims must be non-empty and >= 1"
+    );
+    let n: usize = opts.dims.iter().product();
+    let link_bps = opts.link_gbps.saturating_mul(1_000_000_000);
+    let latency = opts.link_latency;
+
+    let mut topo = TorusTopo {
+        hosts: Vec::with_capacity(n),
+        switches: Vec::with_capacity(n),
+        dims: opts.dims.clone(),
+    };
+    for idx in 0..n {
+        let name = topo
+            .coords(idx)
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join("_");
+        let switch = world.net.add_switch(format!("s{name}"));
+        let host = world.net.add_host(format!("h{name}"));
+        world.net.connect(host, switch, latency, link_bps);
+        world.net.connect(switch, host, latency, link_bps);
+        topo.switches.push(switch);
+        topo.hosts.push(host);
+    }
+
+    // 每个节点只连向各维度的 +1 邻居（首尾回绕），双向链路由此覆盖所有邻居对；
+    // 大小为 2 的维度前后邻居相同，只连一次
+    for idx in 0..n {
+        let coords = topo.coords(idx);
+        for (d, &k) in opts.dims.iter().enumerate() {
+            if k == 1 || (k == 2 && coords[d] == 1) {
+                continue;
+            }
+            let mut next = coords.clone();
+            next[d] = (coords[d] + 1) % k;
+            let (a, b) = (topo.switches[idx], topo.switch(&next));
+            world.net.connect(a, b, latency, link_bps);
+            world.net.connect(b, a, latency, link_bps);
+        }
+    }
+
+    topo
+}
@@ -0,0 +1,27 @@
+//! 跨集群 WAN 链路
+//!
+//! 用一条低带宽、高时延的双向链路把两个子拓扑（例如两个 fat-tree）连起来，
+//! 用于研究跨地域训练中跨集群集合通信共享稀缺 WAN 带宽的情况。
+
+use crate::net::{NetWorld, NodeId};
+use crate::sim::SimTime;
+
+/// 在两个集群的网关节点之间建立一条双向 WAN 链路（每个方向 `bps` 带宽、`latency` 时延）。
+///
+/// 跨集群流量走动态路由时会经过这条链路；两个集群内部的流量不受影响。
+pub fn connect_clusters(
+    world: &mut NetWorld,
+    cluster_a_gw: NodeId,
+    cluster_b_gw: NodeId,
+    bps: u64,
+    latency: SimTime,
+) {
+    assert!(
+        cluster_a_gw != cluster_b_gw,
+        "WAN link needs two distinct gateways, got {:?} twice",
+        cluster_a_gw
+    );
+    assert!(bps > 0, "WAN link bandwidth must be > 0");
+    world.net.connect(cluster_a_gw, cluster_b_gw, latency, bps);
+    world.net.connect(cluster_b_gw, cluster_a_gw, latency, bps);
+}
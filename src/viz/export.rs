@@ -0,0 +1,164 @@
+//! 可视化事件的扁平化导出（CSV）
+//!
+//! 每个事件一行，列为 `t_ns,kind,node,link_from,link_to,pkt_id,flow_id,bytes`，
+//! 不适用的列留空。只保留回放/统计最常用的字段，完整信息请用 JSON/JSONL。
+
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::{VizEvent, VizEventKind, VizLogger};
+
+/// CSV 表头
+pub const VIZ_CSV_HEADER: &str = "t_ns,kind,node,link_from,link_to,pkt_id,flow_id,bytes";
+
+/// CSV 中的一行
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VizCsvRow {
+    pub t_ns: u64,
+    /// 事件类型（与 JSON 里的 `kind` 相同，例如 `tx_start`）
+    pub kind: String,
+    pub node: Option<usize>,
+    pub link_from: Option<usize>,
+    pub link_to: Option<usize>,
+    pub pkt_id: Option<u64>,
+    /// 包所属流；TCP/DCTCP 事件没有包信息时取 `conn_id`
+    pub flow_id: Option<u64>,
+    pub bytes: Option<u32>,
+}
+
+impl VizEventKind {
+    /// 事件类型名（snake_case，与 JSON 序列化的 `kind` 一致）
+    pub fn name(&self) -> &'static str {
+        match self {
+            VizEventKind::Meta { .. } => "meta",
+            VizEventKind::FlowLabel { .. } => "flow_label",
+            VizEventKind::GpuBusy { .. } => "gpu_busy",
+            VizEventKind::NodeRx { .. } => "node_rx",
+            VizEventKind::NodeForward { .. } => "node_forward",
+            VizEventKind::Enqueue { .. } => "enqueue",
+            VizEventKind::TxStart { .. } => "tx_start",
+            VizEventKind::ArriveNode { .. } => "arrive_node",
+            VizEventKind::Delivered { .. } => "delivered",
+            VizEventKind::Drop { .. } => "drop",
+            VizEventKind::TcpSendData(_) => "tcp_send_data",
+            VizEventKind::TcpSendAck(_) => "tcp_send_ack",
+            VizEventKind::TcpRecvAck(_) => "tcp_recv_ack",
+            VizEventKind::TcpRto(_) => "tcp_rto",
+            VizEventKind::DctcpCwnd { .. } => "dctcp_cwnd",
+        }
+    }
+}
+
+impl VizCsvRow {
+    pub fn from_event(ev: &VizEvent) -> Self {
+        let (mut node, mut link, mut conn) = (None, None, None);
+        match &ev.kind {
+            VizEventKind::Meta { .. } | VizEventKind::FlowLabel { .. } => {}
+            VizEventKind::GpuBusy { node: n, .. }
+            | VizEventKind::NodeRx { node: n, .. }
+            | VizEventKind::ArriveNode { node: n }
+            | VizEventKind::Delivered { node: n } => node = Some(*n),
+            VizEventKind::NodeForward { node: n, next } => {
+                node = Some(*n);
+                link = Some((*n, *next));
+            }
+            VizEventKind::Enqueue {
+                link_from, link_to, ..
+            }
+            | VizEventKind::TxStart {
+                link_from, link_to, ..
+            }
+            | VizEventKind::Drop {
+                link_from, link_to, ..
+            } => link = Some((*link_from, *link_to)),
+            VizEventKind::TcpSendData(t)
+            | VizEventKind::TcpSendAck(t)
+            | VizEventKind::TcpRecvAck(t)
+            | VizEventKind::TcpRto(t) => conn = Some(t.conn_id),
+            VizEventKind::DctcpCwnd { conn_id, .. } => conn = Some(*conn_id),
+        }
+        Self {
+            t_ns: ev.t_ns,
+            kind: ev.kind.name().to_string(),
+            node,
+            link_from: link.map(|l| l.0),
+            link_to: link.map(|l| l.1),
+            pkt_id: ev.pkt_id,
+            flow_id: ev.flow_id.or(conn),
+            bytes: ev.pkt_bytes,
+        }
+    }
+
+    fn to_line(&self) -> String {
+        fn cell<T: ToString>(v: Option<T>) -> String {
+            v.map(|v| v.to_string()).unwrap_or_default()
+        }
+        format!(
+            "{},{},{},{},{},{},{},{}",
+            self.t_ns,
+            self.kind,
+            cell(self.node),
+            cell(self.link_from),
+            cell(self.link_to),
+            cell(self.pkt_id),
+            cell(self.flow_id),
+            cell(self.bytes)
+        )
+    }
+
+    /// 解析 `write_csv` 写出的一行（不含表头）
+    pub fn parse(line: &str) -> Result<Self, String> {
+        fn cell<T: std::str::FromStr>(raw: &str, col: &str) -> Result<Option<T>, String> {
+            if raw.is_empty() {
+                return Ok(None);
+            }
+            raw.parse()
+                .map(Some)
+                .map_err(|_| format!("invalid {col} value {raw:?}"))
+        }
+        let cols: Vec<&str> = line.split(',').collect();
+        let [t_ns, kind, node, link_from, link_to, pkt_id, flow_id, bytes] = cols[..] else {
+            return Err(format!("expected 8 columns, got {}: {line:?}", cols.len()));
+        };
+        Ok(Self {
+            t_ns: cell(t_ns, "t_ns")?.ok_or("missing t_ns")?,
+            kind: kind.to_string(),
+            node: cell(node, "node")?,
+            link_from: cell(link_from, "link_from")?,
+            link_to: cell(link_to, "link_to")?,
+            pkt_id: cell(pkt_id, "pkt_id")?,
+            flow_id: cell(flow_id, "flow_id")?,
+            bytes: cell(bytes, "bytes")?,
+        })
+    }
+}
+
+/// 读取 `VizLogger::write_csv` 写出的文件
+pub fn read_viz_csv(path: impl AsRef<Path>) -> io::Result<Vec<VizCsvRow>> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    if lines.next() != Some(VIZ_CSV_HEADER) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing viz csv header",
+        ));
+    }
+    lines
+        .map(|line| {
+            VizCsvRow::parse(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect()
+}
+
+impl VizLogger {
+    /// 把已缓存的事件按 CSV 写入 `path`（每个事件一行）
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(fs::File::create(path)?);
+        writeln!(w, "{VIZ_CSV_HEADER}")?;
+        for ev in &self.events {
+            writeln!(w, "{}", VizCsvRow::from_event(ev).to_line())?;
+        }
+        w.flush()
+    }
+}
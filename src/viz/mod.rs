@@ -5,9 +5,13 @@
 //! - **轻量**：不引入复杂依赖/运行时服务
 //! - **可回放**：支持时间轴播放、单步、过滤（pkt/flow）
 
+mod export;
+mod throughput;
 mod types;
 
+pub use export::{VIZ_CSV_HEADER, VizCsvRow, read_viz_csv};
+pub use throughput::{VizFlowSeries, VizLinkSeries, VizThroughputSeries};
 pub use types::{
-    VizCwndReason, VizEvent, VizEventKind, VizLinkInfo, VizLogger, VizNodeInfo, VizNodeKind,
-    VizPacketKind, VizTcp,
+    VIZ_STREAM_BUFFER_EVENTS, VizCwndReason, VizEvent, VizEventKind, VizLinkInfo, VizLogger,
+    VizNodeInfo, VizNodeKind, VizPacketKind, VizTcp,
 };
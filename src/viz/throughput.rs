@@ -0,0 +1,144 @@
+//! 吞吐时间序列（按固定时间桶预聚合）
+//!
+//! 回放器直接用这些序列画图，无需在前端遍历海量原始事件。
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::types::{VizEvent, VizEventKind, VizLogger};
+use crate::sim::SimTime;
+
+/// 一条单向链路的吞吐序列：`bytes[i]` 为第 i 个时间桶内开始发送的字节数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VizLinkSeries {
+    pub from: usize,
+    pub to: usize,
+    pub bytes: Vec<u64>,
+}
+
+/// 一条流的吞吐序列：`bytes[i]` 为第 i 个时间桶内交付的字节数。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VizFlowSeries {
+    pub flow_id: u64,
+    pub bytes: Vec<u64>,
+}
+
+/// 所有链路/流的吞吐序列，所有序列长度相同（共 `bins` 个桶）。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VizThroughputSeries {
+    /// 时间桶宽度（ns）；第 i 个桶覆盖 `[i * bin_ns, (i + 1) * bin_ns)`
+    pub bin_ns: u64,
+    pub bins: usize,
+    pub links: Vec<VizLinkSeries>,
+    pub flows: Vec<VizFlowSeries>,
+}
+
+/// 运行中按 `bin_ns` 宽的时间桶累加的吞吐（见 `VizLogger::with_throughput_bin`）
+#[derive(Debug, Clone)]
+pub(super) struct ThroughputAccum {
+    bin_ns: u64,
+    /// 覆盖已见事件的最大时间戳所需的桶数
+    bins: usize,
+    links: BTreeMap<(usize, usize), Vec<u64>>,
+    flows: BTreeMap<u64, Vec<u64>>,
+}
+
+impl ThroughputAccum {
+    pub(super) fn new(bin: SimTime) -> Self {
+        assert!(bin.0 > 0, "throughput bin width must be > 0");
+        Self {
+            bin_ns: bin.0,
+            bins: 0,
+            links: BTreeMap::new(),
+            flows: BTreeMap::new(),
+        }
+    }
+
+    pub(super) fn bin_ns(&self) -> u64 {
+        self.bin_ns
+    }
+
+    /// 把 `TxStart`（链路）与 `Delivered`（流）事件的字节数计入所在时间桶
+    pub(super) fn record(&mut self, ev: &VizEvent) {
+        let idx = (ev.t_ns / self.bin_ns) as usize;
+        self.bins = self.bins.max(idx + 1);
+        let Some(bytes) = ev.pkt_bytes else {
+            return;
+        };
+        let series = match &ev.kind {
+            VizEventKind::TxStart {
+                link_from, link_to, ..
+            } => self.links.entry((*link_from, *link_to)).or_default(),
+            VizEventKind::Delivered { .. } => {
+                let Some(flow_id) = ev.flow_id else {
+                    return;
+                };
+                self.flows.entry(flow_id).or_default()
+            }
+            _ => return,
+        };
+        if series.len() <= idx {
+            series.resize(idx + 1, 0);
+        }
+        series[idx] += u64::from(bytes);
+    }
+}
+
+impl VizLogger {
+    /// 运行中按 `bin` 宽的时间桶聚合吞吐，供 `throughput_series` 使用。
+    ///
+    /// 聚合发生在流/节点过滤与 `max_events` 截断之前，流式模式下也不依赖内存中的事件，
+    /// 因此序列总是覆盖全部 `TxStart` / `Delivered` 事件。
+    pub fn with_throughput_bin(mut self, bin: SimTime) -> Self {
+        self.throughput = Some(ThroughputAccum::new(bin));
+        self
+    }
+
+    /// 运行中聚合得到的吞吐序列，桶宽为 `bin`（须为 `with_throughput_bin` 桶宽的整数倍）。
+    ///
+    /// 未调用 `with_throughput_bin` 时 panic。
+    pub fn throughput_series(&self, bin: SimTime) -> VizThroughputSeries {
+        let acc = self
+            .throughput
+            .as_ref()
+            .expect("throughput aggregation not enabled (see VizLogger::with_throughput_bin)");
+        assert!(
+            bin.0 > 0 && bin.0.is_multiple_of(acc.bin_ns),
+            "throughput bin {} ns must be a positive multiple of the recording bin {} ns",
+            bin.0,
+            acc.bin_ns
+        );
+        let factor = (bin.0 / acc.bin_ns) as usize;
+        let bins = acc.bins.div_ceil(factor);
+        let rebin = |fine: &Vec<u64>| {
+            let mut bytes = vec![0; bins];
+            for (i, b) in fine.iter().enumerate() {
+                bytes[i / factor] += b;
+            }
+            bytes
+        };
+
+        VizThroughputSeries {
+            bin_ns: bin.0,
+            bins,
+            links: acc
+                .links
+                .iter()
+                .map(|(&(from, to), fine)| VizLinkSeries {
+                    from,
+                    to,
+                    bytes: rebin(fine),
+                })
+                .collect(),
+            flows: acc
+                .flows
+                .iter()
+                .map(|(&flow_id, fine)| VizFlowSeries {
+                    flow_id,
+                    bytes: rebin(fine),
+                })
+                .collect(),
+        }
+    }
+}
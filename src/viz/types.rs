@@ -1,5 +1,12 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 
+use super::throughput::ThroughputAccum;
+
 /// 可视化事件类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
@@ -8,7 +15,12 @@ pub enum VizEventKind {
     Meta {
         nodes: Vec<VizNodeInfo>,
         links: Vec<VizLinkInfo>,
+        /// 发出 Meta 时已命名的流（flow_id -> 名称）
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        flow_labels: BTreeMap<u64, String>,
     },
+    /// 流的名称（Meta 之后才命名的流，在该流第一条事件之前写出）
+    FlowLabel { label: String },
     /// GPU 计算（用于训练 step 的本地计算段）
     GpuBusy {
         node: usize,
@@ -161,14 +173,178 @@ pub struct VizEvent {
     pub kind: VizEventKind,
 }
 
+impl VizEvent {
+    /// 事件涉及的节点（链路事件为两端）；与节点无关的事件返回空
+    fn nodes(&self) -> Vec<usize> {
+        match &self.kind {
+            VizEventKind::GpuBusy { node, .. }
+            | VizEventKind::NodeRx { node, .. }
+            | VizEventKind::ArriveNode { node }
+            | VizEventKind::Delivered { node } => vec![*node],
+            VizEventKind::NodeForward { node, next } => vec![*node, *next],
+            VizEventKind::Enqueue {
+                link_from, link_to, ..
+            }
+            | VizEventKind::TxStart {
+                link_from, link_to, ..
+            }
+            | VizEventKind::Drop {
+                link_from, link_to, ..
+            } => vec![*link_from, *link_to],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// 流式模式下内存里最多攒这么多条事件，满了就写出
+pub const VIZ_STREAM_BUFFER_EVENTS: usize = 1024;
+
 /// 一个简单的事件收集器（存内存，仿真结束写 JSON 文件）
-#[derive(Debug, Default)]
+///
+/// 用 `new_streaming` / `jsonl` 创建时改为流式：事件按 JSONL 写到 writer，
+/// `events` 只保留尚未写出的少量事件（最多 `VIZ_STREAM_BUFFER_EVENTS` 条）。
+/// `with_max_events` 可限制记录总数，超出后丢弃后续事件并置 `is_truncated`。
+/// `with_flow_filter` / `with_node_filter` 只保留涉及指定流/节点的事件
+/// （`Meta` 总是保留，被过滤的事件不计入记录数）。
+/// `with_throughput_bin` 在过滤与截断之前按时间桶聚合吞吐（见 `throughput_series`）。
+#[derive(Default)]
 pub struct VizLogger {
     pub events: Vec<VizEvent>,
+    stream: Option<Box<dyn Write + Send>>,
+    max_events: Option<u64>,
+    recorded: u64,
+    truncated: bool,
+    flow_filter: Option<HashSet<u64>>,
+    node_filter: Option<HashSet<usize>>,
+    pub(super) throughput: Option<ThroughputAccum>,
+}
+
+impl std::fmt::Debug for VizLogger {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VizLogger")
+            .field("events", &self.events.len())
+            .field("streaming", &self.is_streaming())
+            .field("max_events", &self.max_events)
+            .field("recorded", &self.recorded)
+            .field("truncated", &self.truncated)
+            .field("flow_filter", &self.flow_filter)
+            .field("node_filter", &self.node_filter)
+            .field(
+                "throughput_bin_ns",
+                &self.throughput.as_ref().map(ThroughputAccum::bin_ns),
+            )
+            .finish()
+    }
 }
 
 impl VizLogger {
+    /// 创建流式 logger：事件按 JSONL（每行一个 JSON 对象）写到 `writer`
+    pub fn new_streaming(writer: impl Write + Send + 'static) -> Self {
+        let mut v = Self::default();
+        v.stream = Some(Box::new(writer));
+        v
+    }
+
+    /// 创建写文件的流式 logger（见 `new_streaming`）
+    pub fn jsonl(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new_streaming(BufWriter::new(File::create(path)?)))
+    }
+
+    /// 最多记录 `max_events` 条事件，之后的事件被丢弃
+    pub fn with_max_events(mut self, max_events: u64) -> Self {
+        self.max_events = Some(max_events);
+        self
+    }
+
+    /// 只记录 `flow_id` 在 `flows` 中的事件；不属于任何流的事件（如 GPU 计算）照常保留
+    pub fn with_flow_filter(mut self, flows: HashSet<u64>) -> Self {
+        self.flow_filter = Some(flows);
+        self
+    }
+
+    /// 只记录涉及 `nodes` 中节点的事件（链路事件任一端命中即可）；
+    /// 与节点无关的事件（如 TCP 状态）照常保留
+    pub fn with_node_filter(mut self, nodes: HashSet<usize>) -> Self {
+        self.node_filter = Some(nodes);
+        self
+    }
+
+    /// 事件是否通过流/节点过滤
+    fn keeps(&self, ev: &VizEvent) -> bool {
+        if matches!(ev.kind, VizEventKind::Meta { .. }) {
+            return true;
+        }
+        if let (Some(flows), Some(flow_id)) = (&self.flow_filter, ev.flow_id)
+            && !flows.contains(&flow_id)
+        {
+            return false;
+        }
+        if let Some(nodes) = &self.node_filter {
+            let touched = ev.nodes();
+            if !touched.is_empty() && !touched.iter().any(|n| nodes.contains(n)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// 是否为流式 logger
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// 是否因 `max_events` 上限丢弃过事件
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// 已记录（缓存或写出）的事件数
+    pub fn recorded(&self) -> u64 {
+        self.recorded
+    }
+
     pub fn push(&mut self, ev: VizEvent) {
+        if let Some(acc) = &mut self.throughput {
+            acc.record(&ev);
+        }
+        if !self.keeps(&ev) {
+            return;
+        }
+        if self.max_events.is_some_and(|max| self.recorded >= max) {
+            self.truncated = true;
+            return;
+        }
+        self.recorded += 1;
         self.events.push(ev);
+        if self.is_streaming() && self.events.len() >= VIZ_STREAM_BUFFER_EVENTS {
+            self.write_buffered().expect("write viz jsonl");
+        }
+    }
+
+    fn write_buffered(&mut self) -> io::Result<()> {
+        let Some(w) = &mut self.stream else {
+            return Ok(());
+        };
+        for ev in self.events.drain(..) {
+            serde_json::to_writer(&mut *w, &ev)?;
+            w.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// 流式模式下写出缓存的事件并刷新 writer；非流式时什么也不做
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_buffered()?;
+        match &mut self.stream {
+            Some(w) => w.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for VizLogger {
+    fn drop(&mut self) {
+        // 尽力写出流式模式下剩余的事件；需要处理错误时应显式调用 `flush`
+        let _ = self.flush();
     }
 }
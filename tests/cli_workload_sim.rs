@@ -80,6 +80,64 @@ fn workload_sim_writes_viz_json_with_meta_first() {
     let _ = fs::remove_dir_all(&dir);
 }
 
+#[test]
+fn workload_sim_writes_viz_jsonl_and_csv_formats() {
+    let dir = unique_temp_dir("workload-sim-viz-format");
+    let workload = write_file(
+        &dir,
+        "workload.json",
+        r#"
+{
+    "schema_version": 2,
+    "topology": { "kind": "dumbbell" },
+    "hosts": [ { "id": 0 }, { "id": 1 } ],
+    "ranks": [
+        { "id": 0, "steps": [ { "kind": "compute", "compute_ms": 0.001 } ] },
+        { "id": 1, "steps": [ { "kind": "compute", "compute_ms": 0.001 } ] }
+    ]
+}
+        "#,
+    );
+
+    for format in ["jsonl", "csv"] {
+        let out = dir.join(format!("viz.{format}"));
+        let output = Command::new(env!("CARGO_BIN_EXE_workload_sim"))
+            .args([
+                "--workload",
+                workload.to_str().unwrap(),
+                "--viz-json",
+                out.to_str().unwrap(),
+                "--viz-format",
+                format,
+                "--until-ms",
+                "0",
+            ])
+            .output()
+            .expect("run workload_sim");
+        assert!(
+            output.status.success(),
+            "workload_sim --viz-format {format} failed: stderr={}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let raw = fs::read_to_string(&out).expect("read viz output");
+        let mut lines = raw.lines();
+        if format == "jsonl" {
+            let first: Value = serde_json::from_str(lines.next().expect("meta line"))
+                .expect("parse first jsonl line");
+            assert_eq!(first.get("kind").and_then(|k| k.as_str()), Some("meta"));
+        } else {
+            assert_eq!(
+                lines.next(),
+                Some("t_ns,kind,node,link_from,link_to,pkt_id,flow_id,bytes")
+            );
+            assert_eq!(lines.next(), Some("0,meta,,,,,,"));
+        }
+    }
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
 #[test]
 fn workload_sim_allows_comm_overlap_across_comm_streams() {
     let dir = unique_temp_dir("workload-sim-stream-overlap");
@@ -237,3 +295,93 @@ fn workload_sim_exits_nonzero_on_comm_id_op_mismatch() {
     let _ = fs::remove_dir_all(&dir);
 }
 
+
+#[test]
+fn workload_sim_check_determinism_passes_for_allreduce() {
+    let dir = unique_temp_dir("workload-sim-determinism");
+    let workload = write_file(
+        &dir,
+        "workload.json",
+        r#"
+{
+    "schema_version": 2,
+    "topology": { "kind": "dumbbell" },
+    "hosts": [ { "id": 0 }, { "id": 1 } ],
+    "ranks": [
+        {
+            "id": 0,
+            "steps": [
+                { "kind": "compute", "compute_ms": 0.01 },
+                { "kind": "collective", "op": "allreduce", "comm_bytes": 200000, "comm_id": "c0", "hosts": [0, 1] }
+            ]
+        },
+        {
+            "id": 1,
+            "steps": [
+                { "kind": "compute", "compute_ms": 0.02 },
+                { "kind": "collective", "op": "allreduce", "comm_bytes": 200000, "comm_id": "c0", "hosts": [0, 1] }
+            ]
+        }
+    ]
+}
+        "#,
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_workload_sim"))
+        .args([
+            "--workload",
+            workload.to_str().unwrap(),
+            "--check-determinism",
+        ])
+        .output()
+        .expect("run workload_sim");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "workload_sim failed: stderr={stderr}"
+    );
+    assert!(
+        stderr.contains("determinism check passed"),
+        "stderr={stderr}"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn workload_sim_rejects_queue_smaller_than_one_packet() {
+    let dir = unique_temp_dir("workload-sim-sub-mss-queue");
+    let workload = write_file(
+        &dir,
+        "workload.json",
+        r#"
+{
+    "schema_version": 2,
+    "topology": { "kind": "dumbbell" },
+    "hosts": [ { "id": 0 }, { "id": 1 } ],
+    "ranks": [
+        { "id": 0, "steps": [ { "kind": "compute", "compute_ms": 0.001 } ] },
+        { "id": 1, "steps": [ { "kind": "compute", "compute_ms": 0.001 } ] }
+    ]
+}
+        "#,
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_workload_sim"))
+        .args([
+            "--workload",
+            workload.to_str().unwrap(),
+            "--queue-bytes",
+            "1000",
+        ])
+        .output()
+        .expect("run workload_sim");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "sub-MSS queue should be rejected");
+    assert!(
+        stderr.contains("queue capacity is smaller than one 1500-byte packet"),
+        "stderr={stderr}"
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}